@@ -0,0 +1,5 @@
+-- One-time schema migration for existing D1 databases.
+-- Adds expression indexes so case-insensitive address lookups (lower(address) = ?) do not scan the table.
+
+CREATE INDEX IF NOT EXISTS idx_tokens_address_lower ON tokens(lower(address));
+CREATE INDEX IF NOT EXISTS idx_contracts_address_lower ON contracts(lower(address));
@@ -0,0 +1,4 @@
+-- One-time schema migration for existing D1 databases.
+-- Indexes contracts.type so get_whale_activity can load CEX/Bridge labels without scanning the table.
+
+CREATE INDEX IF NOT EXISTS idx_contracts_type ON contracts(type);
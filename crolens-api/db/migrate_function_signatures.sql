@@ -0,0 +1,17 @@
+-- One-time schema migration for existing D1 databases.
+-- Adds the 4-byte selector table used by decode_transaction when the built-in ABI does not match.
+
+CREATE TABLE IF NOT EXISTS function_signatures (
+    selector TEXT PRIMARY KEY,
+    signature TEXT NOT NULL,
+    action TEXT
+);
+
+INSERT INTO function_signatures (selector, signature, action) VALUES
+('0x5c11d795', 'swapExactTokensForTokensSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)', 'Swap'),
+('0xb6f9de95', 'swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256)', 'Swap'),
+('0x791ac947', 'swapExactTokensForETHSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)', 'Swap'),
+('0xac9650d8', 'multicall(bytes[])', NULL)
+ON CONFLICT(selector) DO UPDATE SET
+  signature = excluded.signature,
+  action = excluded.action;
@@ -0,0 +1,16 @@
+-- One-time schema migration for existing D1 databases (run after migrate_function_signatures.sql).
+-- Widens the function_signatures primary key to (selector, signature) so colliding selectors keep every candidate.
+-- SQLite cannot alter a primary key in place, so the table is rebuilt and existing rows are copied over.
+
+CREATE TABLE IF NOT EXISTS function_signatures_new (
+    selector TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    action TEXT,
+    PRIMARY KEY (selector, signature)
+);
+
+INSERT OR IGNORE INTO function_signatures_new (selector, signature, action)
+SELECT selector, signature, action FROM function_signatures;
+
+DROP TABLE function_signatures;
+ALTER TABLE function_signatures_new RENAME TO function_signatures;
@@ -0,0 +1,9 @@
+-- One-time schema migration for existing D1 databases.
+-- Adds the Chainlink-style oracle table consulted before pool-derived prices (price_source "oracle").
+-- Insert one row per token: the token address, its USD aggregator/proxy address and the answer decimals.
+
+CREATE TABLE IF NOT EXISTS token_oracles (
+    token_address TEXT PRIMARY KEY,
+    aggregator_address TEXT NOT NULL,
+    decimals INTEGER NOT NULL DEFAULT 8
+);
@@ -0,0 +1,4 @@
+-- One-time schema migration for existing D1 databases.
+-- Adds a spam flag used to hide airdropped tokens from account summaries.
+
+ALTER TABLE tokens ADD COLUMN is_spam BOOLEAN DEFAULT 0;
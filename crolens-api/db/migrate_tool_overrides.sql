@@ -0,0 +1,9 @@
+-- One-time schema migration for existing D1 databases.
+-- Adds per-tool description / inputSchema overrides merged over the built-in definitions in tools/list.
+-- NULL columns keep the built-in value; overrides are cached in KV for 10 minutes.
+
+CREATE TABLE IF NOT EXISTS tool_overrides (
+    name TEXT PRIMARY KEY,
+    description TEXT,
+    schema_json TEXT
+);
@@ -53,8 +53,10 @@ CREATE TABLE IF NOT EXISTS tokens (
     logo_url TEXT,
     is_stablecoin BOOLEAN DEFAULT 0,
     coingecko_id TEXT,
-    is_anchor BOOLEAN DEFAULT 0
+    is_anchor BOOLEAN DEFAULT 0,
+    is_spam BOOLEAN DEFAULT 0
 );
+CREATE INDEX IF NOT EXISTS idx_tokens_address_lower ON tokens(lower(address));
 
 CREATE TABLE IF NOT EXISTS contracts (
     address TEXT PRIMARY KEY,
@@ -66,6 +68,27 @@ CREATE TABLE IF NOT EXISTS contracts (
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
 );
 CREATE INDEX IF NOT EXISTS idx_contracts_name ON contracts(name);
+CREATE INDEX IF NOT EXISTS idx_contracts_address_lower ON contracts(lower(address));
+CREATE INDEX IF NOT EXISTS idx_contracts_type ON contracts(type);
+
+CREATE TABLE IF NOT EXISTS function_signatures (
+    selector TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    action TEXT,
+    PRIMARY KEY (selector, signature)
+);
+
+CREATE TABLE IF NOT EXISTS token_oracles (
+    token_address TEXT PRIMARY KEY,
+    aggregator_address TEXT NOT NULL,
+    decimals INTEGER NOT NULL DEFAULT 8
+);
+
+CREATE TABLE IF NOT EXISTS tool_overrides (
+    name TEXT PRIMARY KEY,
+    description TEXT,
+    schema_json TEXT
+);
 
 CREATE TABLE IF NOT EXISTS system_config (
     key TEXT PRIMARY KEY,
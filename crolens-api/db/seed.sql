@@ -58,6 +58,15 @@ ON CONFLICT(address) DO UPDATE SET
   type = excluded.type,
   protocol_id = excluded.protocol_id;
 
+-- Exchange and bridge labels used by get_whale_activity; type must be exactly 'CEX' or 'Bridge'.
+-- No addresses ship with the seed: add only addresses verified on the Cronos explorer, e.g.
+-- INSERT INTO contracts (address, name, type, protocol_id) VALUES
+-- ('0x<exchange hot wallet>', '<Exchange> Hot Wallet', 'CEX', NULL),
+-- ('0x<bridge contract>', '<Bridge> Bridge', 'Bridge', NULL)
+-- ON CONFLICT(address) DO UPDATE SET
+--   name = excluded.name,
+--   type = excluded.type;
+
 INSERT INTO tokens (address, symbol, name, decimals, is_stablecoin, coingecko_id, is_anchor) VALUES
 ('0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23', 'WCRO', 'Wrapped CRO', 18, 0, 'crypto-com-chain', 1),
 ('0xc21223249CA28397B4B6541dfFaEcC539BfF0c59', 'USDC', 'USD Coin', 6, 1, 'usd-coin', 1),
@@ -85,3 +94,11 @@ ON CONFLICT(key) DO UPDATE SET
   value = excluded.value,
   value_type = excluded.value_type,
   description = excluded.description;
+
+INSERT INTO function_signatures (selector, signature, action) VALUES
+('0x5c11d795', 'swapExactTokensForTokensSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)', 'Swap'),
+('0xb6f9de95', 'swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256)', 'Swap'),
+('0x791ac947', 'swapExactTokensForETHSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)', 'Swap'),
+('0xac9650d8', 'multicall(bytes[])', NULL)
+ON CONFLICT(selector, signature) DO UPDATE SET
+  action = excluded.action;
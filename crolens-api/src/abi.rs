@@ -11,6 +11,13 @@ sol! {
     function transferFrom(address sender, address recipient, uint256 amount) external returns (bool);
     function approve(address spender, uint256 amount) external returns (bool);
 
+    // WCRO (WETH9) 包装/解包
+    function deposit() external payable;
+    function withdraw(uint256 wad) external;
+
+    // ERC-165
+    function supportsInterface(bytes4 interfaceId) external view returns (bool);
+
     function getAmountsOut(uint256 amountIn, address[] path) external view returns (uint256[] amounts);
     function swapExactTokensForTokens(
         uint256 amountIn,
@@ -100,7 +107,20 @@ sol! {
         uint256 exchangeRateMantissa
     );
     function supplyRatePerBlock() external view returns (uint256);
+    function totalBorrows() external view returns (uint256);
+    function totalReserves() external view returns (uint256);
+    function getCash() external view returns (uint256);
+    function exchangeRateStored() external view returns (uint256);
     function borrowRatePerBlock() external view returns (uint256);
+    function reserveFactorMantissa() external view returns (uint256);
+    function interestRateModel() external view returns (address);
+
+    // JumpRateModel (Tectonic 利率模型)
+    function baseRatePerBlock() external view returns (uint256);
+    function multiplierPerBlock() external view returns (uint256);
+    function jumpMultiplierPerBlock() external view returns (uint256);
+    function kink() external view returns (uint256);
+
     function mint(uint256 mintAmount) external returns (uint256);
     function redeem(uint256 redeemTokens) external returns (uint256);
     function redeemUnderlying(uint256 redeemAmount) external returns (uint256);
@@ -114,8 +134,32 @@ sol! {
     function poolInfo(uint256 pid) external view returns (address lpToken, uint256 allocPoint, uint256 lastRewardBlock, uint256 accVVSPerShare);
     function totalAllocPoint() external view returns (uint256);
     function vvsPerBlock() external view returns (uint256);
+    // 部分 farm 额外发放的 bonus 奖励代币 (不支持的合约会 revert)
+    function pendingTokens(uint256 pid, address user) external view returns (address[] tokens, uint256[] amounts);
+
+    // Chainlink AggregatorV3 (Band 的 Chainlink 兼容 proxy 同样适用)
+    function latestAnswer() external view returns (int256);
+    function latestRoundData() external view returns (uint80 roundId, int256 answer, uint256 startedAt, uint256 updatedAt, uint80 answeredInRound);
 
     struct Call3 { address target; bool allowFailure; bytes callData; }
     struct Result { bool success; bytes returnData; }
     function aggregate3(Call3[] calls) external payable returns (Result[] returnData);
 }
+
+sol! {
+    // Uniswap Permit2 (0x000000000022D473030F116dDEE9F6B43aC78BA3)
+    // 与 ERC20 的 transferFrom 同名，放在独立的 interface 中
+    interface IPermit2 {
+        struct PermitDetails { address token; uint160 amount; uint48 expiration; uint48 nonce; }
+        struct PermitSingle { PermitDetails details; address spender; uint256 sigDeadline; }
+        struct TokenPermissions { address token; uint256 amount; }
+        struct PermitTransferFrom { TokenPermissions permitted; uint256 nonce; uint256 deadline; }
+        struct PermitBatchTransferFrom { TokenPermissions[] permitted; uint256 nonce; uint256 deadline; }
+        struct SignatureTransferDetails { address to; uint256 requestedAmount; }
+
+        function permit(address owner, PermitSingle permitSingle, bytes signature) external;
+        function permitTransferFrom(PermitTransferFrom permit, SignatureTransferDetails transferDetails, address owner, bytes signature) external;
+        function permitBatchTransferFrom(PermitBatchTransferFrom permit, SignatureTransferDetails[] transferDetails, address owner, bytes signature) external;
+        function transferFrom(address from, address to, uint160 amount, address token) external;
+    }
+}
@@ -61,10 +61,11 @@ pub async fn get_approval_status(services: &infra::Services, args: Value) -> Res
     let input: GetApprovalStatusArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let owner = types::parse_address(&input.address)?;
+    let owner = types::parse_address_field("address", &input.address)?;
 
     // Get token list
-    let tokens = infra::token::list_tokens_cached(&services.db, &services.kv).await?;
+    let tokens =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix).await?;
 
     // If specific token requested, filter to that token
     let tokens_to_check: Vec<_> = if let Some(ref token_query) = input.token {
@@ -97,12 +98,30 @@ pub async fn get_approval_status(services: &infra::Services, args: Value) -> Res
         }
     }
 
-    let results = multicall.aggregate(calls).await?;
+    // 追加每个代币的 totalSupply，用于按总供应量倍数判断"实际无限"授权
+    let supply_offset = calls.len();
+    for token in &tokens_to_check {
+        calls.push(Call {
+            target: token.address,
+            call_data: abi::totalSupplyCall {}.abi_encode().into(),
+        });
+    }
+
+    let mut results = multicall.aggregate(calls).await?;
+    let supply_results = results.split_off(supply_offset.min(results.len()));
+    let total_supplies: Vec<Option<U256>> = (0..tokens_to_check.len())
+        .map(|ti| {
+            supply_results
+                .get(ti)
+                .and_then(|r| r.as_ref().ok())
+                .and_then(|d| abi::totalSupplyCall::abi_decode_returns(d, true).ok())
+                .map(|v| v._0)
+        })
+        .collect();
 
     // Process results
     let mut approvals: Vec<Value> = Vec::new();
-    let max_u256 = U256::MAX;
-    let unlimited_threshold = U256::from(10).pow(U256::from(30)); // 1e30
+    let threshold = services.approval_threshold;
 
     for (idx, result) in results.into_iter().enumerate() {
         let (ti, si) = call_map[idx];
@@ -118,7 +137,7 @@ pub async fn get_approval_status(services: &infra::Services, args: Value) -> Res
                     continue;
                 }
 
-                let is_unlimited = allowance == max_u256 || allowance >= unlimited_threshold;
+                let is_unlimited = threshold.is_unlimited(allowance, total_supplies[ti]);
                 let allowance_str = if is_unlimited {
                     "unlimited".to_string()
                 } else {
@@ -126,11 +145,7 @@ pub async fn get_approval_status(services: &infra::Services, args: Value) -> Res
                 };
 
                 // Determine risk level
-                let risk_level = if is_unlimited {
-                    "warning"
-                } else {
-                    "safe"
-                };
+                let risk_level = if is_unlimited { "warning" } else { "safe" };
 
                 approvals.push(serde_json::json!({
                     "token_symbol": token.symbol,
@@ -150,7 +165,11 @@ pub async fn get_approval_status(services: &infra::Services, args: Value) -> Res
     let total_approvals = approvals.len();
     let unlimited_approvals = approvals
         .iter()
-        .filter(|a| a.get("is_unlimited").and_then(|v| v.as_bool()).unwrap_or(false))
+        .filter(|a| {
+            a.get("is_unlimited")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
+        })
         .count();
 
     let risk_score = if total_approvals == 0 {
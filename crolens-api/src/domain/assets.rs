@@ -1,4 +1,4 @@
-use alloy_primitives::U256;
+use alloy_primitives::{Address, U256};
 use alloy_sol_types::SolCall;
 use serde::Deserialize;
 use serde_json::Value;
@@ -13,6 +13,71 @@ struct GetAccountSummaryArgs {
     address: String,
     #[serde(default)]
     simple_mode: bool,
+    /// 默认隐藏垃圾币；为 true 时全部列出
+    #[serde(default)]
+    include_spam: bool,
+    /// 附带 eth_getProof 账户证明，客户端可对照 state root 自行验证原生余额
+    #[serde(default)]
+    with_proof: bool,
+    /// 查询该历史区块的持仓；历史价格不可得，此时不输出 USD 估值
+    #[serde(default)]
+    block: Option<u64>,
+    /// 在 "pending" 区块读取余额 (包含内存池中的交易)；节点不支持时回退到 latest
+    #[serde(default)]
+    pending: bool,
+    /// 扫描最近窗口内的 ERC-721/1155 转账日志，列出推算仍持有的 NFT 合集
+    #[serde(default)]
+    include_nfts: bool,
+}
+
+/// 节点拒绝 pending 标签时的错误信息片段 (小写匹配)；只匹配区块标签相关的错误，
+/// 避免把其它含 "pending" / "not supported" 的错误 (如交易池、方法不支持) 当作可回退
+const PENDING_UNSUPPORTED_HINTS: &[&str] = &[
+    "pending block is not available",
+    "pending block not available",
+    "pending tag not supported",
+    "unsupported block tag",
+    "block tag not supported",
+    "invalid block tag",
+    "invalid block number",
+    "unknown block",
+    "header not found",
+];
+
+fn balance_block_tag(pending: bool) -> &'static str {
+    if pending {
+        "pending"
+    } else {
+        "latest"
+    }
+}
+
+/// 节点不支持 pending 读取时的 RPC 错误，可以回退到 latest 重试
+fn pending_unsupported(err: &CroLensError) -> bool {
+    let CroLensError::RpcError(message) = err else {
+        return false;
+    };
+    let message = message.to_lowercase();
+    PENDING_UNSUPPORTED_HINTS
+        .iter()
+        .any(|hint| message.contains(hint))
+}
+
+/// 按 pending 选项读取持仓；pending 被节点拒绝时回退到 latest，并返回是否发生回退
+async fn current_holdings(
+    services: &infra::Services,
+    address: Address,
+    include_spam: bool,
+    pending: bool,
+) -> Result<(WalletHoldings, bool)> {
+    let tag = balance_block_tag(pending);
+    match wallet_holdings(services, address, include_spam, tag).await {
+        Err(err) if pending && pending_unsupported(&err) => {
+            let holdings = wallet_holdings(services, address, include_spam, "latest").await?;
+            Ok((holdings, true))
+        }
+        other => other.map(|holdings| (holdings, false)),
+    }
 }
 
 fn validate_address(address: &str) -> Result<()> {
@@ -20,13 +85,120 @@ fn validate_address(address: &str) -> Result<()> {
     Ok(())
 }
 
-pub async fn get_account_summary(services: &infra::Services, args: Value) -> Result<Value> {
-    let input: GetAccountSummaryArgs = serde_json::from_value(args)
-        .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
-    validate_address(&input.address)?;
-    let address = types::parse_address(&input.address)?;
+/// 没有正的 USD 价格 (无流动性池可定价) 的非稳定币持仓；保留但标记为 unpriced
+fn is_unpriced_holding(token: &infra::token::Token, price_usd: Option<f64>) -> bool {
+    !token.is_stablecoin && !matches!(price_usd, Some(p) if p.is_finite() && p > 0.0)
+}
+
+/// 把 eth_getProof 结果整理为输出格式；缺少 accountProof 时视为无效响应
+fn shape_account_proof(raw: &Value, block_number: u64, state_root: Option<&str>) -> Result<Value> {
+    let invalid = || CroLensError::RpcError("Invalid eth_getProof response".to_string());
+    let account_proof = raw
+        .get("accountProof")
+        .and_then(|v| v.as_array())
+        .filter(|nodes| nodes.iter().all(|n| n.is_string()))
+        .ok_or_else(invalid)?;
+    let hex_field = |key: &str| raw.get(key).and_then(|v| v.as_str());
+    let balance = hex_field("balance")
+        .and_then(|v| types::parse_u256_hex(v).ok())
+        .ok_or_else(invalid)?;
+    let nonce = hex_field("nonce")
+        .and_then(|v| types::parse_u256_hex(v).ok())
+        .ok_or_else(invalid)?;
+
+    Ok(serde_json::json!({
+        "supported": true,
+        "block_number": block_number,
+        "state_root": state_root,
+        "balance": balance.to_string(),
+        "nonce": nonce.to_string(),
+        "code_hash": hex_field("codeHash"),
+        "storage_hash": hex_field("storageHash"),
+        "account_proof": account_proof,
+        "storage_proof": raw.get("storageProof").cloned().unwrap_or_else(|| serde_json::json!([])),
+    }))
+}
+
+/// 节点不支持 eth_getProof (或响应无效) 时不影响其余结果
+fn proof_unavailable(err: &CroLensError) -> Value {
+    serde_json::json!({
+        "supported": false,
+        "reason": err.to_string(),
+    })
+}
+
+/// 历史区块不能晚于当前链头
+fn validate_historical_block(block: u64, head: u64) -> Result<()> {
+    if block > head {
+        return Err(CroLensError::invalid_params(format!(
+            "block {block} is ahead of the latest block {head}"
+        )));
+    }
+    Ok(())
+}
+
+/// 历史区块只有余额可信：去掉按当前价格计算的 USD 字段并明确标注
+fn historical_summary(address: &str, block: u64, holdings: WalletHoldings) -> Value {
+    let wallet: Vec<Value> = holdings
+        .items
+        .into_iter()
+        .map(|mut item| {
+            if let Some(obj) = item.as_object_mut() {
+                obj.insert("price_usd".to_string(), Value::Null);
+                obj.insert("value_usd".to_string(), Value::Null);
+            }
+            item
+        })
+        .collect();
+
+    serde_json::json!({
+        "address": address,
+        "block": block,
+        "usd_valuation_omitted": true,
+        "total_net_worth_usd": null,
+        "wallet": wallet,
+        "hidden_tokens": holdings.hidden_tokens,
+        "defi_summary": null,
+    })
+}
 
-    let tokens = infra::token::list_tokens_cached(&services.db, &services.kv).await?;
+/// 在同一区块读取账户证明和该区块的 stateRoot；未指定区块时使用最新区块
+async fn account_proof(
+    services: &infra::Services,
+    address: Address,
+    block: Option<u64>,
+) -> Result<Value> {
+    let rpc = services.rpc()?;
+    let block_number = match block {
+        Some(block) => block,
+        None => rpc.latest_block_number_cached().await?,
+    };
+    let block_id = format!("0x{block_number:x}");
+    let (proof, block) = futures_util::future::try_join(
+        rpc.eth_get_proof(address, &[], &block_id),
+        rpc.eth_get_block_by_number(&block_id, false),
+    )
+    .await?;
+    let state_root = block.get("stateRoot").and_then(|v| v.as_str());
+    shape_account_proof(&proof, block_number, state_root)
+}
+
+/// 钱包中各代币的持仓与估值 (默认隐藏 DB 标记为 is_spam 的代币)
+pub(crate) struct WalletHoldings {
+    pub items: Vec<Value>,
+    pub value_usd: f64,
+    pub hidden_tokens: usize,
+}
+
+/// `block_id` 为 "latest"、"pending" 或历史区块号 (hex)；垃圾币判断始终使用当前价格
+pub(crate) async fn wallet_holdings(
+    services: &infra::Services,
+    address: Address,
+    include_spam: bool,
+    block_id: &str,
+) -> Result<WalletHoldings> {
+    let tokens =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix).await?;
     let mut calls = Vec::with_capacity(tokens.len());
     for token in &tokens {
         let call_data = abi::balanceOfCall { account: address }.abi_encode();
@@ -36,14 +208,12 @@ pub async fn get_account_summary(services: &infra::Services, args: Value) -> Res
         });
     }
 
-    let results = services.multicall()?.aggregate(calls).await?;
+    let results = services.multicall()?.aggregate_at(calls, block_id).await?;
 
     // Batch fetch token prices (best-effort via KV).
     let price_map = infra::price::get_prices_usd_batch(services, &tokens).await?;
 
-    let mut wallet = Vec::new();
-    let mut wallet_value_usd = 0.0_f64;
-
+    let mut balances = Vec::new();
     for (token, item) in tokens.into_iter().zip(results.into_iter()) {
         let Ok(return_data) = item else {
             continue;
@@ -54,9 +224,34 @@ pub async fn get_account_summary(services: &infra::Services, args: Value) -> Res
         if balance == U256::ZERO {
             continue;
         }
+        let price_usd = price_map.get(&token.address).copied();
+        balances.push((token, balance, price_usd));
+    }
+
+    Ok(summarize_holdings(
+        balances,
+        include_spam,
+        &services.precision,
+    ))
+}
+
+/// 非零余额 -> 输出项；include_spam 为 false 时跳过 is_spam 代币并计入 hidden_tokens
+fn summarize_holdings(
+    balances: Vec<(infra::token::Token, U256, Option<f64>)>,
+    include_spam: bool,
+    precision: &types::Precision,
+) -> WalletHoldings {
+    let mut wallet = Vec::new();
+    let mut wallet_value_usd = 0.0_f64;
+    let mut hidden_tokens = 0usize;
+
+    for (token, balance, price_usd) in balances {
+        if !include_spam && token.is_spam {
+            hidden_tokens += 1;
+            continue;
+        }
 
         let balance_formatted = types::format_units(&balance, token.decimals);
-        let price_usd = price_map.get(&token.address).copied();
         let value_usd = match (price_usd, balance_formatted.parse::<f64>().ok()) {
             (Some(p), Some(amount)) => {
                 let v = p * amount;
@@ -72,14 +267,78 @@ pub async fn get_account_summary(services: &infra::Services, args: Value) -> Res
             "decimals": token.decimals,
             "balance": balance.to_string(),
             "balance_formatted": balance_formatted,
-            "price_usd": price_usd.map(|p| format!("{p:.6}")),
-            "value_usd": value_usd.map(|v| format!("{v:.2}")),
+            "price_usd": price_usd.map(|p| precision.price(p)),
+            "value_usd": value_usd.map(|v| precision.usd(v)),
+            "unpriced": is_unpriced_holding(&token, price_usd),
         }));
     }
 
+    WalletHoldings {
+        items: wallet,
+        value_usd: wallet_value_usd,
+        hidden_tokens,
+    }
+}
+
+pub async fn get_account_summary(services: &infra::Services, args: Value) -> Result<Value> {
+    let input: GetAccountSummaryArgs = serde_json::from_value(args)
+        .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
+    validate_address(&input.address)?;
+    let address = types::parse_address_field("address", &input.address)?;
+    if input.pending && input.block.is_some() {
+        return Err(CroLensError::invalid_params(
+            "pending and block cannot be combined".to_string(),
+        ));
+    }
+    if input.include_nfts && input.block.is_some() {
+        return Err(CroLensError::invalid_params(
+            "include_nfts and block cannot be combined".to_string(),
+        ));
+    }
+
+    if let Some(block) = input.block {
+        let head = services.rpc()?.latest_block_number_cached().await?;
+        validate_historical_block(block, head)?;
+        let block_id = format!("0x{block:x}");
+        let holdings = wallet_holdings(services, address, input.include_spam, &block_id).await?;
+
+        if input.simple_mode {
+            let text = format!(
+                "Wallet tokens at block {block}: {} | USD value omitted (no historical prices)",
+                holdings.items.len()
+            );
+            return Ok(serde_json::json!({ "text": text, "meta": services.meta() }));
+        }
+
+        let mut result = historical_summary(&input.address, block, holdings);
+        result["meta"] = services.meta();
+        if input.with_proof {
+            result["proof"] = match account_proof(services, address, Some(block)).await {
+                Ok(proof) => proof,
+                Err(err) => proof_unavailable(&err),
+            };
+        }
+        return Ok(result);
+    }
+
+    let (holdings, pending_fallback) =
+        current_holdings(services, address, input.include_spam, input.pending).await?;
+    let WalletHoldings {
+        items: wallet,
+        value_usd: wallet_value_usd,
+        hidden_tokens,
+    } = holdings;
+    // 实际使用的区块标签 (pending 被拒绝时为 latest)
+    let block_tag = balance_block_tag(input.pending && !pending_fallback);
+
     if input.simple_mode {
+        let hidden = if hidden_tokens > 0 {
+            format!(" (+{hidden_tokens} hidden)")
+        } else {
+            String::new()
+        };
         let summary = format!(
-            "Wallet tokens: {} | Wallet value: ${wallet_value_usd:.2}",
+            "Wallet tokens: {}{hidden} | Wallet value: ${wallet_value_usd:.2}",
             wallet.len(),
         );
         return Ok(serde_json::json!({ "text": summary, "meta": services.meta() }));
@@ -89,9 +348,11 @@ pub async fn get_account_summary(services: &infra::Services, args: Value) -> Res
     let mut tectonic_supply_usd = 0.0_f64;
     let mut tectonic_borrow_usd = 0.0_f64;
 
-    if let Ok(defi) = crate::domain::defi::get_defi_positions(
+    // DeFi 头寸与钱包余额在同一区块标签读取
+    if let Ok(defi) = crate::domain::defi::defi_positions_at(
         services,
         serde_json::json!({ "address": input.address, "simple_mode": false }),
+        block_tag,
     )
     .await
     {
@@ -118,24 +379,202 @@ pub async fn get_account_summary(services: &infra::Services, args: Value) -> Res
     let total_defi_value_usd = vvs_liquidity_usd + (tectonic_supply_usd - tectonic_borrow_usd);
     let total_net_worth_usd = wallet_value_usd + total_defi_value_usd;
 
-    Ok(serde_json::json!({
+    let mut result = serde_json::json!({
         "address": input.address,
-        "total_net_worth_usd": format!("{total_net_worth_usd:.2}"),
+        "total_net_worth_usd": services.precision.usd(total_net_worth_usd),
         "wallet": wallet,
+        "hidden_tokens": hidden_tokens,
         "defi_summary": {
-            "total_defi_value_usd": format!("{total_defi_value_usd:.2}"),
-            "vvs_liquidity_usd": format!("{vvs_liquidity_usd:.2}"),
-            "tectonic_supply_usd": format!("{tectonic_supply_usd:.2}"),
-            "tectonic_borrow_usd": format!("{tectonic_borrow_usd:.2}"),
+            "total_defi_value_usd": services.precision.usd(total_defi_value_usd),
+            "vvs_liquidity_usd": services.precision.usd(vvs_liquidity_usd),
+            "tectonic_supply_usd": services.precision.usd(tectonic_supply_usd),
+            "tectonic_borrow_usd": services.precision.usd(tectonic_borrow_usd),
         },
         "meta": services.meta(),
-    }))
+    });
+    if input.pending {
+        result["block_tag"] = Value::from(block_tag);
+        result["pending_fallback"] = Value::Bool(pending_fallback);
+    }
+    if input.with_proof {
+        result["proof"] = match account_proof(services, address, None).await {
+            Ok(proof) => proof,
+            Err(err) => proof_unavailable(&err),
+        };
+    }
+    if input.include_nfts {
+        result["nfts"] = match crate::domain::nft::nft_holdings(services, address).await {
+            Ok(nfts) => nfts,
+            Err(err) => serde_json::json!({ "supported": false, "reason": err.to_string() }),
+        };
+    }
+
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn sample_proof() -> Value {
+        serde_json::json!({
+            "address": "0x5c7f8a570d578ed84e63fdfa7b1ee72deae1ae23",
+            "accountProof": ["0xf90211a0", "0xf8718080"],
+            "balance": "0xde0b6b3a7640000",
+            "codeHash": "0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+            "nonce": "0x5",
+            "storageHash": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+            "storageProof": []
+        })
+    }
+
+    #[test]
+    fn account_proof_is_shaped_for_verification() {
+        let shaped =
+            shape_account_proof(&sample_proof(), 12_345_678, Some("0xabc")).expect("valid proof");
+        assert_eq!(shaped["supported"], true);
+        assert_eq!(shaped["block_number"], 12_345_678);
+        assert_eq!(shaped["state_root"], "0xabc");
+        assert_eq!(shaped["balance"], "1000000000000000000");
+        assert_eq!(shaped["nonce"], "5");
+        assert_eq!(
+            shaped["account_proof"],
+            serde_json::json!(["0xf90211a0", "0xf8718080"])
+        );
+        assert_eq!(shaped["storage_proof"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn account_proof_without_nodes_is_invalid() {
+        let mut raw = sample_proof();
+        raw["accountProof"] = serde_json::json!(null);
+        let err = shape_account_proof(&raw, 1, None).unwrap_err();
+        assert!(matches!(err, CroLensError::RpcError(_)));
+    }
+
+    #[test]
+    fn unsupported_provider_omits_proof_gracefully() {
+        let err = CroLensError::RpcError("the method eth_getProof does not exist".to_string());
+        let value = proof_unavailable(&err);
+        assert_eq!(value["supported"], false);
+        assert!(value["reason"]
+            .as_str()
+            .expect("reason")
+            .contains("eth_getProof"));
+        assert!(value.get("account_proof").is_none());
+    }
+
+    #[test]
+    fn with_proof_defaults_to_false() {
+        let args: GetAccountSummaryArgs = serde_json::from_value(serde_json::json!({
+            "address": "0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23"
+        }))
+        .expect("args should parse");
+        assert!(!args.with_proof);
+    }
+
+    #[test]
+    fn block_defaults_to_latest() {
+        let args: GetAccountSummaryArgs = serde_json::from_value(serde_json::json!({
+            "address": "0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23",
+            "block": 12_000_000
+        }))
+        .expect("args should parse");
+        assert_eq!(args.block, Some(12_000_000));
+
+        let args: GetAccountSummaryArgs = serde_json::from_value(serde_json::json!({
+            "address": "0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23"
+        }))
+        .expect("args should parse");
+        assert_eq!(args.block, None);
+    }
+
+    #[test]
+    fn pending_option_selects_block_tag() {
+        let args: GetAccountSummaryArgs = serde_json::from_value(serde_json::json!({
+            "address": "0x1234567890123456789012345678901234567890",
+            "pending": true
+        }))
+        .expect("args should parse");
+        assert!(args.pending);
+        assert_eq!(balance_block_tag(args.pending), "pending");
+        assert_eq!(balance_block_tag(false), "latest");
+    }
+
+    #[test]
+    fn pending_rejection_falls_back_to_latest() {
+        for message in [
+            "pending block is not available",
+            "Unsupported block tag",
+            "invalid block number",
+            "header not found",
+        ] {
+            assert!(
+                pending_unsupported(&CroLensError::RpcError(message.to_string())),
+                "{message}"
+            );
+        }
+    }
+
+    #[test]
+    fn other_failures_do_not_fall_back() {
+        assert!(!pending_unsupported(&CroLensError::RpcError(
+            "execution reverted".to_string()
+        )));
+        assert!(!pending_unsupported(&CroLensError::Timeout {
+            timeout_ms: 30_000
+        }));
+        assert!(!pending_unsupported(&CroLensError::NotConfigured("RPC")));
+        // 含 "pending" / "not supported" 但与区块标签无关的错误
+        for message in [
+            "txpool is full, pending transactions dropped",
+            "method eth_getProof not supported",
+            "unsupported opcode",
+        ] {
+            assert!(
+                !pending_unsupported(&CroLensError::RpcError(message.to_string())),
+                "{message}"
+            );
+        }
+    }
+
+    #[test]
+    fn future_block_is_rejected() {
+        validate_historical_block(100, 100).expect("head block is allowed");
+        validate_historical_block(0, 100).expect("genesis is allowed");
+        let err = validate_historical_block(101, 100).unwrap_err();
+        assert!(matches!(err, CroLensError::InvalidParams(_)));
+    }
+
+    #[test]
+    fn historical_summary_omits_usd_valuation() {
+        let holdings = WalletHoldings {
+            items: vec![serde_json::json!({
+                "symbol": "WCRO",
+                "balance": "1000000000000000000",
+                "balance_formatted": "1",
+                "price_usd": "0.1",
+                "value_usd": "0.10",
+            })],
+            value_usd: 0.1,
+            hidden_tokens: 2,
+        };
+        let summary = historical_summary(
+            "0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23",
+            12_000_000,
+            holdings,
+        );
+        assert_eq!(summary["block"], 12_000_000);
+        assert_eq!(summary["usd_valuation_omitted"], true);
+        assert!(summary["total_net_worth_usd"].is_null());
+        assert!(summary["defi_summary"].is_null());
+        assert_eq!(summary["hidden_tokens"], 2);
+        let item = &summary["wallet"][0];
+        assert_eq!(item["balance"], "1000000000000000000");
+        assert!(item["price_usd"].is_null());
+        assert!(item["value_usd"].is_null());
+    }
+
     #[test]
     fn validate_address_accepts_valid() {
         validate_address("0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23")
@@ -153,6 +592,63 @@ mod tests {
         let json = serde_json::json!({ "address": "0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23" });
         let args: GetAccountSummaryArgs = serde_json::from_value(json).expect("should parse");
         assert!(!args.simple_mode);
+        assert!(!args.include_spam);
+    }
+
+    fn token(is_stablecoin: bool, is_spam: bool) -> infra::token::Token {
+        infra::token::Token {
+            address: alloy_primitives::Address::repeat_byte(0x11),
+            symbol: "TKN".to_string(),
+            decimals: 18,
+            is_stablecoin,
+            is_spam,
+        }
+    }
+
+    #[test]
+    fn priced_tokens_are_not_marked_unpriced() {
+        assert!(!is_unpriced_holding(&token(false, false), Some(0.25)));
+    }
+
+    #[test]
+    fn unpriced_or_zero_priced_tokens_are_marked() {
+        assert!(is_unpriced_holding(&token(false, false), None));
+        assert!(is_unpriced_holding(&token(false, false), Some(0.0)));
+        assert!(is_unpriced_holding(&token(false, false), Some(f64::NAN)));
+    }
+
+    #[test]
+    fn stablecoins_are_never_marked_unpriced() {
+        assert!(!is_unpriced_holding(&token(true, false), None));
+    }
+
+    #[test]
+    fn only_flagged_spam_is_hidden() {
+        let one = U256::from(10u64).pow(U256::from(18u64));
+        let balances = || {
+            vec![
+                (token(false, false), one, Some(1.0)),
+                (token(false, false), one, None),
+                (token(true, false), one, None),
+                (token(false, true), one, Some(2.0)),
+            ]
+        };
+        let precision = types::Precision::default();
+
+        let holdings = summarize_holdings(balances(), false, &precision);
+        assert_eq!(holdings.hidden_tokens, 1);
+        assert_eq!(holdings.items.len(), 3);
+        let unpriced: Vec<bool> = holdings
+            .items
+            .iter()
+            .map(|item| item["unpriced"].as_bool().expect("unpriced flag"))
+            .collect();
+        assert_eq!(unpriced, vec![false, true, false]);
+        assert!((holdings.value_usd - 1.0).abs() < 1e-9);
+
+        let with_spam = summarize_holdings(balances(), true, &precision);
+        assert_eq!(with_spam.hidden_tokens, 0);
+        assert_eq!(with_spam.items.len(), 4);
     }
 
     #[test]
@@ -108,7 +108,7 @@ pub async fn get_block_info(services: &infra::Services, args: Value) -> Result<V
         "transactions_count": transactions_count,
         "gas_used": gas_used.to_string(),
         "gas_limit": gas_limit.to_string(),
-        "gas_used_percent": format!("{:.2}", gas_used_percent),
+        "gas_used_percent": services.precision.pct(gas_used_percent),
         "base_fee_gwei": base_fee_gwei,
         "miner": miner,
         "meta": services.meta()
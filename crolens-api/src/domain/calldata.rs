@@ -5,6 +5,7 @@ use serde_json::Value;
 use crate::abi;
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::signatures::SignatureEntry;
 use crate::types;
 
 #[derive(Debug, Deserialize)]
@@ -33,22 +34,128 @@ pub async fn decode_calldata(services: &infra::Services, args: Value) -> Result<
     };
 
     let (method, params) = decode_known(&selector, &bytes);
+    let decoded = if method != "unknown" {
+        CalldataDecode::builtin(method, params)
+    } else {
+        let entries = if selector.len() == 10 {
+            infra::signatures::lookup_signatures(&services.db, &selector)
+                .await
+                .unwrap_or_else(|err| {
+                    // 签名表不可用时按无匹配处理
+                    worker::console_warn!("[WARN] signature lookup failed: {}", err);
+                    Vec::new()
+                })
+        } else {
+            Vec::new()
+        };
+        decode_fallback(&bytes, entries)
+    };
 
     if input.simple_mode {
         return Ok(serde_json::json!({
-            "text": format!("Calldata: {method}"),
+            "text": format!(
+                "Calldata: {} (confidence: {})",
+                decoded.method,
+                decoded.confidence.as_str()
+            ),
             "meta": services.meta(),
         }));
     }
 
     Ok(serde_json::json!({
         "selector": selector,
-        "method": method,
-        "params": params,
+        "method": decoded.method,
+        "params": decoded.params,
+        "confidence": decoded.confidence.as_str(),
+        "source": decoded.source,
+        "signature": decoded.signature,
+        "candidates": decoded.candidates,
         "meta": services.meta(),
     }))
 }
 
+/// 解码可信度：内置 ABI 为 high，签名表唯一匹配为 medium，选择器碰撞或无匹配时的按字猜测为 low
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Confidence {
+    High,
+    Medium,
+    Low,
+}
+
+impl Confidence {
+    fn as_str(self) -> &'static str {
+        match self {
+            Confidence::High => "high",
+            Confidence::Medium => "medium",
+            Confidence::Low => "low",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CalldataDecode {
+    method: String,
+    params: Value,
+    confidence: Confidence,
+    /// builtin | signature_db | guess
+    source: &'static str,
+    signature: Option<String>,
+    /// 签名表中该选择器的全部候选 (碰撞时多于 1 个)
+    candidates: Vec<String>,
+}
+
+impl CalldataDecode {
+    fn builtin(method: String, params: Value) -> Self {
+        Self {
+            method,
+            params,
+            confidence: Confidence::High,
+            source: "builtin",
+            signature: None,
+            candidates: Vec::new(),
+        }
+    }
+}
+
+/// 内置 ABI 无法解码时的结果；没有类型信息，参数按 32 字节字原样返回
+fn decode_fallback(bytes: &[u8], entries: Vec<SignatureEntry>) -> CalldataDecode {
+    let params = raw_words(bytes);
+    let candidates: Vec<String> = entries.iter().map(|e| e.signature.clone()).collect();
+    let Some(first) = entries.first() else {
+        return CalldataDecode {
+            method: "unknown".to_string(),
+            params,
+            confidence: Confidence::Low,
+            source: "guess",
+            signature: None,
+            candidates,
+        };
+    };
+    let confidence = if entries.len() == 1 {
+        Confidence::Medium
+    } else {
+        Confidence::Low
+    };
+    CalldataDecode {
+        method: first.method_name().to_string(),
+        params,
+        confidence,
+        source: "signature_db",
+        signature: Some(first.signature.clone()),
+        candidates,
+    }
+}
+
+/// 选择器之后的参数区按 32 字节切分为 0x 字符串；没有参数时为 null
+fn raw_words(bytes: &[u8]) -> Value {
+    let args = bytes.get(4..).unwrap_or_default();
+    if args.is_empty() {
+        return Value::Null;
+    }
+    let words: Vec<String> = args.chunks(32).map(types::bytes_to_hex0x).collect();
+    serde_json::json!({ "words": words })
+}
+
 fn decode_known(selector: &str, bytes: &[u8]) -> (String, Value) {
     match selector {
         "0xa9059cbb" => {
@@ -128,6 +235,72 @@ mod tests {
         assert!(params.is_null());
     }
 
+    fn entry(signature: &str) -> SignatureEntry {
+        SignatureEntry {
+            signature: signature.to_string(),
+            action: None,
+        }
+    }
+
+    #[test]
+    fn known_selector_is_high_confidence() {
+        let data = "0xa9059cbb0000000000000000000000001234567890123456789012345678901234567890000000000000000000000000000000000000000000000000000000000000000a";
+        let bytes = types::hex0x_to_bytes(data).expect("valid hex calldata");
+        let (method, params) = decode_known("0xa9059cbb", &bytes);
+        let decoded = CalldataDecode::builtin(method, params);
+        assert_eq!(decoded.confidence, Confidence::High);
+        assert_eq!(decoded.confidence.as_str(), "high");
+        assert_eq!(decoded.source, "builtin");
+    }
+
+    #[test]
+    fn single_signature_match_is_medium_confidence() {
+        let mut bytes = vec![0xac, 0x96, 0x50, 0xd8];
+        bytes.extend_from_slice(&[0u8; 31]);
+        bytes.push(0x20);
+        let decoded = decode_fallback(&bytes, vec![entry("multicall(bytes[])")]);
+        assert_eq!(decoded.confidence, Confidence::Medium);
+        assert_eq!(decoded.method, "multicall");
+        assert_eq!(decoded.source, "signature_db");
+        assert_eq!(decoded.signature.as_deref(), Some("multicall(bytes[])"));
+        assert_eq!(decoded.candidates, vec!["multicall(bytes[])".to_string()]);
+        assert_eq!(
+            decoded.params["words"][0],
+            format!("0x{}20", "00".repeat(31))
+        );
+    }
+
+    #[test]
+    fn selector_collision_is_low_confidence() {
+        let bytes = vec![0x42, 0x96, 0x6c, 0x68];
+        let decoded = decode_fallback(
+            &bytes,
+            vec![
+                entry("burn(uint256)"),
+                entry("collate_propagate_storage(bytes16)"),
+            ],
+        );
+        assert_eq!(decoded.confidence, Confidence::Low);
+        assert_eq!(decoded.method, "burn");
+        assert_eq!(decoded.candidates.len(), 2);
+        assert!(decoded.params.is_null());
+    }
+
+    #[test]
+    fn unmatched_selector_is_low_confidence_guess() {
+        let mut bytes = vec![0xde, 0xad, 0xbe, 0xef];
+        bytes.extend_from_slice(&[0x11; 40]);
+        let decoded = decode_fallback(&bytes, Vec::new());
+        assert_eq!(decoded.confidence.as_str(), "low");
+        assert_eq!(decoded.method, "unknown");
+        assert_eq!(decoded.source, "guess");
+        assert!(decoded.signature.is_none());
+        let words = decoded.params["words"].as_array().expect("words");
+        // 末尾不足 32 字节的部分单独成字
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[1], format!("0x{}", "11".repeat(8)));
+    }
+
     #[test]
     fn args_deserialize_defaults() {
         let json = serde_json::json!({ "data": "0xa9059cbb" });
@@ -1,11 +1,24 @@
+use alloy_primitives::{Address, Bytes, FixedBytes};
+use alloy_sol_types::SolCall;
 use serde::Deserialize;
 use serde_json::Value;
 use worker::d1::D1Type;
 
+use crate::abi;
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::explorer::ContractCreation;
+use crate::infra::multicall::Call;
 use crate::types;
 
+/// ERC-165 interface ids
+const ERC721_INTERFACE_ID: [u8; 4] = [0x80, 0xac, 0x58, 0xcd];
+const ERC1155_INTERFACE_ID: [u8; 4] = [0xd9, 0xb6, 0x7a, 0x26];
+
+/// EIP-1967 implementation slot: bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)
+const EIP1967_IMPLEMENTATION_SLOT: &str =
+    "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+
 #[derive(Debug, Deserialize)]
 struct ContractInfoArgs {
     address: String,
@@ -20,11 +33,99 @@ fn code_size_from_hex(code_hex: &str) -> Option<usize> {
     Some(code_hex.len().saturating_sub(2) / 2)
 }
 
+fn decode_supports_interface(result: Option<&std::result::Result<Bytes, CroLensError>>) -> bool {
+    result
+        .and_then(|r| r.as_ref().ok())
+        .and_then(|data| abi::supportsInterfaceCall::abi_decode_returns(data, true).ok())
+        .map(|v| v._0)
+        .unwrap_or(false)
+}
+
+fn decode_decimals(result: Option<&std::result::Result<Bytes, CroLensError>>) -> Option<u8> {
+    result
+        .and_then(|r| r.as_ref().ok())
+        .and_then(|data| abi::decimalsCall::abi_decode_returns(data, true).ok())
+        .map(|v| v._0)
+}
+
+fn decode_symbol(result: Option<&std::result::Result<Bytes, CroLensError>>) -> Option<String> {
+    result
+        .and_then(|r| r.as_ref().ok())
+        .and_then(|data| abi::symbolCall::abi_decode_returns(data, true).ok())
+        .map(|v| v._0)
+}
+
+/// ERC-165 声明优先；未实现 ERC-165 时 decimals() 与 symbol() 都可读视为 ERC-20
+fn classify_standard(
+    supports_erc721: bool,
+    supports_erc1155: bool,
+    decimals: Option<u8>,
+    symbol: Option<&str>,
+) -> &'static str {
+    if supports_erc721 {
+        "erc721"
+    } else if supports_erc1155 {
+        "erc1155"
+    } else if decimals.is_some() && symbol.is_some() {
+        "erc20"
+    } else {
+        "unknown"
+    }
+}
+
+/// 从 EIP-1967 implementation slot 的值中取出实现合约地址 (全零表示非代理)
+fn implementation_from_slot(slot_hex: &str) -> Option<Address> {
+    let trimmed = slot_hex.trim().trim_start_matches("0x");
+    if trimmed.len() < 40 {
+        return None;
+    }
+    let address = types::parse_address(&format!("0x{}", &trimmed[trimmed.len() - 40..])).ok()?;
+    if address == Address::ZERO {
+        None
+    } else {
+        Some(address)
+    }
+}
+
+/// 探测代币标准；对代理合约的调用会委托到实现合约，因此结果即实现合约的标准
+async fn detect_standard(services: &infra::Services, addr: Address) -> Option<&'static str> {
+    let multicall = services.multicall().ok()?;
+    let supports = |id: [u8; 4]| Call {
+        target: addr,
+        call_data: abi::supportsInterfaceCall {
+            interfaceId: FixedBytes::from(id),
+        }
+        .abi_encode()
+        .into(),
+    };
+    let calls = vec![
+        supports(ERC721_INTERFACE_ID),
+        supports(ERC1155_INTERFACE_ID),
+        Call {
+            target: addr,
+            call_data: abi::decimalsCall {}.abi_encode().into(),
+        },
+        Call {
+            target: addr,
+            call_data: abi::symbolCall {}.abi_encode().into(),
+        },
+    ];
+    let results = multicall.aggregate(calls).await.ok()?;
+
+    let symbol = decode_symbol(results.get(3));
+    Some(classify_standard(
+        decode_supports_interface(results.first()),
+        decode_supports_interface(results.get(1)),
+        decode_decimals(results.get(2)),
+        symbol.as_deref(),
+    ))
+}
+
 pub async fn get_contract_info(services: &infra::Services, args: Value) -> Result<Value> {
     let input: ContractInfoArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let addr = types::parse_address(&input.address)?;
+    let addr = types::parse_address_field("address", &input.address)?;
     let addr_lower = addr.to_string().to_lowercase();
     let addr_arg = D1Type::Text(&addr_lower);
 
@@ -36,7 +137,7 @@ pub async fn get_contract_info(services: &infra::Services, args: Value) -> Resul
         )
         .bind_refs([&addr_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
-    let result = infra::db::run("get_contract_info", statement.all()).await?;
+    let result = infra::db::run("get_contract_info", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -45,8 +146,12 @@ pub async fn get_contract_info(services: &infra::Services, args: Value) -> Resul
         .first()
         .map(|row| {
             (
-                row.get("name").and_then(|v| v.as_str()).map(|v| v.to_string()),
-                row.get("type").and_then(|v| v.as_str()).map(|v| v.to_string()),
+                row.get("name")
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.to_string()),
+                row.get("type")
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.to_string()),
                 row.get("protocol_id")
                     .and_then(|v| v.as_str())
                     .map(|v| v.to_string()),
@@ -58,28 +163,58 @@ pub async fn get_contract_info(services: &infra::Services, args: Value) -> Resul
         })
         .unwrap_or((None, None, None, false, None));
 
-    // Optional best-effort code size via RPC.
+    // Optional best-effort code size and EIP-1967 implementation via RPC.
     let mut code_size: Option<usize> = None;
+    let mut implementation: Option<Address> = None;
     if let Ok(rpc) = services.rpc() {
-        if let Ok(code) = rpc
-            .call("eth_getCode", serde_json::json!([addr.to_string(), "latest"]))
-            .await
-        {
-            if let Some(code_hex) = code.as_str() {
-                code_size = code_size_from_hex(code_hex);
-            }
+        let (code, slot) = futures_util::future::join(
+            rpc.call(
+                "eth_getCode",
+                serde_json::json!([addr.to_string(), "latest"]),
+            ),
+            rpc.call(
+                "eth_getStorageAt",
+                serde_json::json!([addr.to_string(), EIP1967_IMPLEMENTATION_SLOT, "latest"]),
+            ),
+        )
+        .await;
+        if let Some(code_hex) = code.ok().as_ref().and_then(|v| v.as_str()) {
+            code_size = code_size_from_hex(code_hex);
         }
+        implementation = slot
+            .ok()
+            .as_ref()
+            .and_then(|v| v.as_str())
+            .and_then(implementation_from_slot);
     }
 
+    // EOA 没有代码，不做标准探测
+    let standard = if code_size == Some(0) {
+        "unknown"
+    } else {
+        detect_standard(services, addr).await.unwrap_or("unknown")
+    };
+
+    let creation = infra::explorer::lookup_contract_creation(services, addr).await;
+
     if input.simple_mode {
-        let text = match name.as_ref() {
+        let mut text = match name.as_ref() {
             Some(n) => format!("Contract: {n} ({})", addr),
             None => format!("Contract: {addr}"),
         };
+        if standard != "unknown" {
+            text.push_str(&format!(" | {}", standard.to_uppercase()));
+        }
+        if implementation.is_some() {
+            text.push_str(" | proxy");
+        }
+        if let Some(creation) = creation.as_ref() {
+            text.push_str(&format!(" | deployed by {}", creation.creator_address));
+        }
         return Ok(serde_json::json!({ "text": text, "meta": services.meta() }));
     }
 
-    Ok(serde_json::json!({
+    let mut output = serde_json::json!({
         "address": addr.to_string(),
         "name": name,
         "type": contract_type,
@@ -87,8 +222,35 @@ pub async fn get_contract_info(services: &infra::Services, args: Value) -> Resul
         "verified": verified,
         "description": description,
         "code_size": code_size,
+        "standard": standard,
+        "is_proxy": implementation.is_some(),
+        "implementation": implementation.map(|a| a.to_string()),
         "meta": services.meta(),
-    }))
+    });
+    apply_creation(&mut output, creation.as_ref());
+    Ok(output)
+}
+
+/// explorer 已配置且查到创建信息时写入 creator_address / creation_tx / creation_block，否则不输出这些字段
+fn apply_creation(output: &mut Value, creation: Option<&ContractCreation>) {
+    let (Some(creation), Some(obj)) = (creation, output.as_object_mut()) else {
+        return;
+    };
+    obj.insert(
+        "creator_address".to_string(),
+        Value::from(creation.creator_address.clone()),
+    );
+    obj.insert(
+        "creation_tx".to_string(),
+        Value::from(creation.creation_tx.clone()),
+    );
+    obj.insert(
+        "creation_block".to_string(),
+        creation
+            .creation_block
+            .map(Value::from)
+            .unwrap_or(Value::Null),
+    );
 }
 
 #[cfg(test)]
@@ -103,6 +265,113 @@ mod tests {
         assert_eq!(code_size_from_hex("6000"), None);
     }
 
+    fn ok_bytes(data: Vec<u8>) -> Option<std::result::Result<Bytes, CroLensError>> {
+        Some(Ok(Bytes::from(data)))
+    }
+
+    #[test]
+    fn interface_ids_match_erc165_spec() {
+        // bytes4(keccak256(...)) XOR 结果，见 EIP-721 / EIP-1155
+        assert_eq!(u32::from_be_bytes(ERC721_INTERFACE_ID), 0x80ac58cd);
+        assert_eq!(u32::from_be_bytes(ERC1155_INTERFACE_ID), 0xd9b67a26);
+        let call = abi::supportsInterfaceCall {
+            interfaceId: FixedBytes::from(ERC721_INTERFACE_ID),
+        }
+        .abi_encode();
+        assert_eq!(&call[4..8], &ERC721_INTERFACE_ID);
+    }
+
+    #[test]
+    fn decodes_supports_interface_results() {
+        let yes = ok_bytes(abi::supportsInterfaceCall::abi_encode_returns(&(true,)));
+        let no = ok_bytes(abi::supportsInterfaceCall::abi_encode_returns(&(false,)));
+        let reverted = Some(Err(CroLensError::RpcError("revert".to_string())));
+        assert!(decode_supports_interface(yes.as_ref()));
+        assert!(!decode_supports_interface(no.as_ref()));
+        assert!(!decode_supports_interface(reverted.as_ref()));
+        assert!(!decode_supports_interface(ok_bytes(Vec::new()).as_ref()));
+    }
+
+    #[test]
+    fn classifies_erc165_standards_first() {
+        assert_eq!(classify_standard(true, false, None, Some("NFT")), "erc721");
+        assert_eq!(classify_standard(false, true, None, None), "erc1155");
+        // ERC-721 合约也可能有 symbol()，但 ERC-165 声明优先
+        assert_eq!(
+            classify_standard(true, false, Some(0), Some("NFT")),
+            "erc721"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_erc20_probe() {
+        let decimals = ok_bytes(abi::decimalsCall::abi_encode_returns(&(18u8,)));
+        let symbol = ok_bytes(abi::symbolCall::abi_encode_returns(&("VVS".to_string(),)));
+        let decimals = decode_decimals(decimals.as_ref());
+        let symbol = decode_symbol(symbol.as_ref());
+        assert_eq!(decimals, Some(18));
+        assert_eq!(
+            classify_standard(false, false, decimals, symbol.as_deref()),
+            "erc20"
+        );
+        assert_eq!(
+            classify_standard(false, false, None, Some("VVS")),
+            "unknown"
+        );
+        assert_eq!(classify_standard(false, false, None, None), "unknown");
+    }
+
+    #[test]
+    fn reads_eip1967_implementation_slot() {
+        let slot = "0x0000000000000000000000001111111111111111111111111111111111111111";
+        assert_eq!(
+            implementation_from_slot(slot),
+            Some(Address::from([0x11u8; 20]))
+        );
+        let empty = format!("0x{}", "0".repeat(64));
+        assert_eq!(implementation_from_slot(&empty), None);
+        assert_eq!(implementation_from_slot("0x"), None);
+    }
+
+    #[test]
+    fn creation_fields_are_added_when_found() {
+        let mut output =
+            serde_json::json!({ "address": "0x2222222222222222222222222222222222222222" });
+        let creation = ContractCreation {
+            creator_address: "0x1111111111111111111111111111111111111111".to_string(),
+            creation_tx: format!("0x{}", "ab".repeat(32)),
+            creation_block: Some(1_234_567),
+        };
+        apply_creation(&mut output, Some(&creation));
+        assert_eq!(
+            output["creator_address"],
+            "0x1111111111111111111111111111111111111111"
+        );
+        assert_eq!(output["creation_tx"], format!("0x{}", "ab".repeat(32)));
+        assert_eq!(output["creation_block"], 1_234_567);
+
+        let mut output = serde_json::json!({});
+        let without_block = ContractCreation {
+            creation_block: None,
+            ..creation
+        };
+        apply_creation(&mut output, Some(&without_block));
+        assert!(output["creation_block"].is_null());
+        assert!(output.get("creation_block").is_some());
+    }
+
+    #[test]
+    fn creation_fields_are_omitted_when_unconfigured() {
+        let addr = Address::from([0x22u8; 20]);
+        // 未配置 EXPLORER_API_URL 时不会发起查询
+        assert!(infra::explorer::creation_url(None, addr).is_none());
+        let mut output = serde_json::json!({ "address": addr.to_string() });
+        apply_creation(&mut output, None);
+        assert!(output.get("creator_address").is_none());
+        assert!(output.get("creation_tx").is_none());
+        assert!(output.get("creation_block").is_none());
+    }
+
     #[test]
     fn args_deserialize_defaults() {
         let json = serde_json::json!({ "address": "0x1234567890123456789012345678901234567890" });
@@ -12,44 +12,83 @@ struct SimpleModeArgs {
 
 const CRO_CHAIN_ID: u64 = 25;
 
-fn format_cro_price_text(price_usd: Option<f64>) -> String {
-    match price_usd {
-        Some(p) => format!("CRO price: ${p:.4}"),
-        None => "CRO overview available.".to_string(),
+/// CoinGecko 与链上 WCRO 价格偏离超过该百分比时标记 (可能是预言机或流动性问题)
+const PRICE_DISAGREEMENT_FLAG_PCT: f64 = 5.0;
+
+/// 两个来源的价格偏离，以 anchor 价格为基准；任一来源缺失时为 None
+fn price_disagreement_pct(anchor: Option<f64>, onchain: Option<f64>) -> Option<f64> {
+    match (anchor, onchain) {
+        (Some(a), Some(o)) if a > 0.0 && o > 0.0 => Some((o - a).abs() / a * 100.0),
+        _ => None,
     }
 }
 
-fn format_price_usd(price_usd: Option<f64>) -> Option<String> {
-    price_usd.map(|p| format!("{p:.6}"))
+fn is_price_disagreement(pct: Option<f64>) -> bool {
+    pct.map(|p| p > PRICE_DISAGREEMENT_FLAG_PCT)
+        .unwrap_or(false)
+}
+
+fn format_cro_price_text(price_usd: Option<f64>, disagreement_pct: Option<f64>) -> String {
+    match (price_usd, disagreement_pct) {
+        (Some(p), Some(d)) if is_price_disagreement(Some(d)) => {
+            format!("CRO price: ${p:.4} (sources disagree by {d:.2}%)")
+        }
+        (Some(p), _) => format!("CRO price: ${p:.4}"),
+        (None, _) => "CRO overview available.".to_string(),
+    }
 }
 
 pub async fn get_cro_overview(services: &infra::Services, args: Value) -> Result<Value> {
     let input: SimpleModeArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
+    // 与其它工具共用缓存的最新区块号；输出保持 eth_blockNumber 的 hex 格式
     let mut block_number: Option<String> = None;
     if let Ok(rpc) = services.rpc() {
-        if let Ok(result) = rpc.call("eth_blockNumber", serde_json::json!([])).await {
-            block_number = result.as_str().map(|v| v.to_string());
+        if let Ok(latest) = rpc.latest_block_number_cached().await {
+            block_number = Some(format!("0x{latest:x}"));
         }
     }
 
+    // 主价格走统一的价格来源 (缓存与优先级列表)；CoinGecko anchor 价格与链上 WCRO 池子价格只用于交叉校验
     let mut price_usd: Option<f64> = None;
-    if let Ok(tokens) = infra::token::list_tokens_cached(&services.db, &services.kv).await {
-        if let Some(wcro) = tokens.iter().find(|t| t.symbol.eq_ignore_ascii_case("WCRO")) {
-            price_usd = infra::price::get_price_usd(services, wcro).await.ok().flatten();
+    let mut anchor_price: Option<f64> = None;
+    let mut onchain_price: Option<f64> = None;
+    if let Ok(tokens) =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix).await
+    {
+        if let Some(wcro) = tokens
+            .iter()
+            .find(|t| t.symbol.eq_ignore_ascii_case("WCRO"))
+        {
+            let (price, anchor, onchain) = futures_util::future::join3(
+                infra::price::get_price_usd(services, wcro),
+                infra::price::get_anchor_price_usd(&services.kv, &services.kv_prefix, &wcro.symbol),
+                infra::price::derive_price_from_pool(services, wcro.address),
+            )
+            .await;
+            price_usd = price.ok().flatten();
+            anchor_price = anchor.ok().flatten();
+            onchain_price = onchain.ok().flatten();
         }
     }
+    let disagreement_pct = price_disagreement_pct(anchor_price, onchain_price);
 
     if input.simple_mode {
-        let text = format_cro_price_text(price_usd);
+        let text = format_cro_price_text(price_usd, disagreement_pct);
         return Ok(serde_json::json!({ "text": text, "meta": services.meta() }));
     }
 
     Ok(serde_json::json!({
         "chain_id": CRO_CHAIN_ID,
         "block_number": block_number,
-        "price_usd": format_price_usd(price_usd),
+        "price_usd": price_usd.map(|p| services.precision.price(p)),
+        "price_sources": {
+            "coingecko": anchor_price.map(|p| services.precision.price(p)),
+            "onchain": onchain_price.map(|p| services.precision.price(p)),
+        },
+        "price_disagreement_pct": disagreement_pct.map(|p| services.precision.pct(p)),
+        "price_disagreement": is_price_disagreement(disagreement_pct),
         "meta": services.meta(),
     }))
 }
@@ -60,14 +99,44 @@ mod tests {
 
     #[test]
     fn format_cro_price_text_variants() {
-        assert_eq!(format_cro_price_text(None), "CRO overview available.");
-        assert_eq!(format_cro_price_text(Some(1.2345)), "CRO price: $1.2345");
+        assert_eq!(format_cro_price_text(None, None), "CRO overview available.");
+        assert_eq!(
+            format_cro_price_text(Some(1.2345), None),
+            "CRO price: $1.2345"
+        );
+        assert_eq!(
+            format_cro_price_text(Some(1.2345), Some(1.0)),
+            "CRO price: $1.2345"
+        );
+        assert_eq!(
+            format_cro_price_text(Some(0.1), Some(12.5)),
+            "CRO price: $0.1000 (sources disagree by 12.50%)"
+        );
+    }
+
+    #[test]
+    fn disagreement_is_relative_to_anchor() {
+        let pct = price_disagreement_pct(Some(0.10), Some(0.11)).expect("both prices");
+        assert!((pct - 10.0).abs() < 1e-9);
+        let pct = price_disagreement_pct(Some(0.10), Some(0.09)).expect("both prices");
+        assert!((pct - 10.0).abs() < 1e-9);
+        assert_eq!(price_disagreement_pct(Some(0.10), Some(0.10)), Some(0.0));
+    }
+
+    #[test]
+    fn disagreement_requires_both_prices() {
+        assert_eq!(price_disagreement_pct(None, Some(0.1)), None);
+        assert_eq!(price_disagreement_pct(Some(0.1), None), None);
+        assert_eq!(price_disagreement_pct(Some(0.0), Some(0.1)), None);
+        assert!(!is_price_disagreement(None));
     }
 
     #[test]
-    fn format_price_usd_formats_six_decimals() {
-        assert_eq!(format_price_usd(None), None);
-        assert_eq!(format_price_usd(Some(1.0)), Some("1.000000".to_string()));
+    fn disagreement_flag_threshold() {
+        assert!(!is_price_disagreement(Some(PRICE_DISAGREEMENT_FLAG_PCT)));
+        assert!(is_price_disagreement(Some(
+            PRICE_DISAGREEMENT_FLAG_PCT + 0.01
+        )));
     }
 
     #[test]
@@ -4,47 +4,66 @@ use serde::Deserialize;
 use serde_json::Value;
 
 use crate::abi;
+use crate::domain::farm_apy::{self, FarmEmission, BLOCKS_PER_YEAR};
+use crate::domain::tectonic;
 use crate::error::{CroLensError, Result};
 use crate::infra;
 use crate::types;
 
-const BLOCKS_PER_YEAR: f64 = 179_740_800.0;
 const VVS_MASTERCHEF_ADDRESS: &str = "0x3790f3A1cf8A478042Ec112A70881Dcfa9c0fc21";
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DefiProtocol {
+    Vvs,
+    Tectonic,
+}
+
 #[derive(Debug, Deserialize)]
 struct GetDefiPositionsArgs {
     address: String,
+    /// 只查询指定协议；缺省或空数组表示全部
+    #[serde(default)]
+    protocols: Option<Vec<DefiProtocol>>,
     #[serde(default)]
     simple_mode: bool,
 }
 
-pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Result<Value> {
-    let t0 = types::now_ms();
-    let input: GetDefiPositionsArgs = serde_json::from_value(args.clone())
-        .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
-    let user = types::parse_address(&input.address)?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ProtocolSelection {
+    vvs: bool,
+    tectonic: bool,
+}
 
-    // 并行获取 pools, markets, masterchef, tokens (全部使用缓存版)
-    let (pools, markets, masterchef, tokens) = futures_util::future::try_join4(
-        infra::config::list_dex_pools_cached(&services.db, &services.kv, "vvs"),
-        infra::config::list_lending_markets_cached(&services.db, &services.kv, "tectonic"),
-        async {
-            match infra::config::get_protocol_contract(&services.db, "vvs", "masterchef").await {
-                Ok(addr) => Ok(addr),
-                Err(_) => types::parse_address(VVS_MASTERCHEF_ADDRESS),
-            }
-        },
-        infra::token::list_tokens_cached(&services.db, &services.kv),
-    )
-    .await?;
-    let t1 = types::now_ms();
-    worker::console_log!("[PERF] defi config load: {}ms", t1 - t0);
+impl ProtocolSelection {
+    fn from_args(protocols: Option<&[DefiProtocol]>) -> Self {
+        match protocols {
+            Some(list) if !list.is_empty() => Self {
+                vvs: list.contains(&DefiProtocol::Vvs),
+                tectonic: list.contains(&DefiProtocol::Tectonic),
+            },
+            _ => Self {
+                vvs: true,
+                tectonic: true,
+            },
+        }
+    }
+}
 
-    // ============ 第一阶段：快速过滤 - 只查询余额 ============
+/// 第一阶段余额查询：VVS LP/质押余额 + Tectonic 账户快照；未选择的协议不产生调用
+fn phase_one_calls(
+    selection: ProtocolSelection,
+    pools: &[infra::config::DexPool],
+    markets: &[infra::config::LendingMarket],
+    masterchef: alloy_primitives::Address,
+    user: alloy_primitives::Address,
+) -> Vec<infra::multicall::Call> {
+    let pools = if selection.vvs { pools } else { &[] };
+    let markets = if selection.tectonic { markets } else { &[] };
     let mut balance_calls = Vec::with_capacity(pools.len() * 2 + markets.len());
 
     // VVS: LP balance + staked balance
-    for pool in &pools {
+    for pool in pools {
         balance_calls.push(infra::multicall::Call {
             target: pool.lp_address,
             call_data: abi::balanceOfCall { account: user }.abi_encode().into(),
@@ -63,7 +82,7 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
     }
 
     // Tectonic: getAccountSnapshot (包含 cToken balance 和 borrow balance)
-    for market in &markets {
+    for market in markets {
         balance_calls.push(infra::multicall::Call {
             target: market.ctoken_address,
             call_data: abi::getAccountSnapshotCall { account: user }
@@ -72,12 +91,81 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
         });
     }
 
+    balance_calls
+}
+
+pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Result<Value> {
+    defi_positions_at(services, args, "latest").await
+}
+
+/// 同 `get_defi_positions`，余额与储备在 `block_id` ("latest" 或 "pending") 读取；farm 排放参数始终读取 latest
+pub(crate) async fn defi_positions_at(
+    services: &infra::Services,
+    args: Value,
+    block_id: &str,
+) -> Result<Value> {
+    let t0 = types::now_ms();
+    let input: GetDefiPositionsArgs = serde_json::from_value(args.clone())
+        .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
+    let user = types::parse_address_field("address", &input.address)?;
+    let selection = ProtocolSelection::from_args(input.protocols.as_deref());
+
+    // 并行获取 pools, markets, masterchef, tokens (全部使用缓存版)
+    // 未选择的协议不加载配置，后续也不会产生任何调用
+    let (pools, markets, masterchef, tokens) = futures_util::future::try_join4(
+        async {
+            if !selection.vvs {
+                return Ok(Vec::new());
+            }
+            infra::config::list_dex_pools_cached(
+                &services.db,
+                &services.kv,
+                &services.kv_prefix,
+                "vvs",
+            )
+            .await
+        },
+        async {
+            if !selection.tectonic {
+                return Ok(Vec::new());
+            }
+            infra::config::list_lending_markets_cached(
+                &services.db,
+                &services.kv,
+                &services.kv_prefix,
+                "tectonic",
+            )
+            .await
+        },
+        async {
+            // masterchef 只用于 VVS 调用，未选择时不查询
+            if !selection.vvs {
+                return Ok(alloy_primitives::Address::ZERO);
+            }
+            match services.protocol_contract("vvs", "masterchef").await {
+                Ok(addr) => Ok(addr),
+                Err(_) => types::parse_address(VVS_MASTERCHEF_ADDRESS),
+            }
+        },
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix),
+    )
+    .await?;
+    let t1 = types::now_ms();
+    worker::console_log!("[PERF] defi config load: {}ms", t1 - t0);
+
+    // ============ 第一阶段：快速过滤 - 只查询余额 ============
+    let balance_calls = phase_one_calls(selection, &pools, &markets, masterchef, user);
+
     let t2 = types::now_ms();
-    worker::console_log!("[PERF] phase1 build: {}ms, {} calls", t2 - t1, balance_calls.len());
+    worker::console_log!(
+        "[PERF] phase1 build: {}ms, {} calls",
+        t2 - t1,
+        balance_calls.len()
+    );
 
     // 并行执行第一阶段 multicall 和价格查询
     let (balance_results, price_map) = futures_util::future::try_join(
-        services.multicall()?.aggregate(balance_calls),
+        services.multicall()?.aggregate_at(balance_calls, block_id),
         infra::price::get_prices_usd_batch(services, &tokens),
     )
     .await?;
@@ -92,11 +180,9 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
 
     for (pool_idx, pool) in pools.iter().enumerate() {
         let wallet_lp = match balance_results.get(balance_idx) {
-            Some(Ok(data)) => {
-                abi::balanceOfCall::abi_decode_returns(data, true)
-                    .map(|r| r._0)
-                    .unwrap_or(U256::ZERO)
-            }
+            Some(Ok(data)) => abi::balanceOfCall::abi_decode_returns(data, true)
+                .map(|r| r._0)
+                .unwrap_or(U256::ZERO),
             _ => U256::ZERO,
         };
         balance_idx += 1;
@@ -136,15 +222,15 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
 
     for (market_idx, _market) in markets.iter().enumerate() {
         let snapshot = match balance_results.get(balance_idx) {
-            Some(Ok(data)) => {
-                abi::getAccountSnapshotCall::abi_decode_returns(data, true).ok()
-            }
+            Some(Ok(data)) => abi::getAccountSnapshotCall::abi_decode_returns(data, true).ok(),
             _ => None,
         };
         balance_idx += 1;
 
         if let Some(snap) = snapshot {
-            if snap.err == U256::ZERO && (snap.cTokenBalance > U256::ZERO || snap.borrowBalance > U256::ZERO) {
+            if snap.err == U256::ZERO
+                && (snap.cTokenBalance > U256::ZERO || snap.borrowBalance > U256::ZERO)
+            {
                 active_market_indices.push(market_idx);
                 market_snapshots.push(MarketSnapshot {
                     ctoken_balance: snap.cTokenBalance,
@@ -177,6 +263,7 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
                 "vvs": {
                     "total_liquidity_usd": "0.00",
                     "total_pending_rewards_usd": "0.00",
+                    "pending_rewards": [],
                     "positions": [],
                 },
                 "tectonic": {
@@ -195,9 +282,10 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
     }
 
     // ============ 第二阶段：只查询有余额的池子/市场的详细数据 ============
-    let mut detail_calls = Vec::with_capacity(active_pool_indices.len() * 3 + active_market_indices.len() * 2);
+    let mut detail_calls =
+        Vec::with_capacity(active_pool_indices.len() * 4 + active_market_indices.len() * 2);
 
-    // VVS: 只查询活跃池子的 reserves, totalSupply, pendingVVS
+    // VVS: 只查询活跃池子的 reserves, totalSupply, pendingVVS, pendingTokens
     for &pool_idx in &active_pool_indices {
         let pool = &pools[pool_idx];
         detail_calls.push(infra::multicall::Call {
@@ -218,6 +306,15 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
                 .abi_encode()
                 .into(),
             });
+            detail_calls.push(infra::multicall::Call {
+                target: masterchef,
+                call_data: abi::pendingTokensCall {
+                    pid: U256::from(pid as u64),
+                    user,
+                }
+                .abi_encode()
+                .into(),
+            });
         }
     }
 
@@ -235,13 +332,54 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
     }
 
     let t5 = types::now_ms();
-    worker::console_log!("[PERF] phase2 build: {}ms, {} calls", t5 - t4, detail_calls.len());
+    worker::console_log!(
+        "[PERF] phase2 build: {}ms, {} calls",
+        t5 - t4,
+        detail_calls.len()
+    );
 
-    let results = if detail_calls.is_empty() {
-        Vec::new()
-    } else {
-        services.multicall()?.aggregate(detail_calls).await?
-    };
+    // 活跃且可质押的池子额外读取 farm 排放参数 (用于 APY，失败不影响头寸)
+    let farms: Vec<(i64, alloy_primitives::Address)> = active_pool_indices
+        .iter()
+        .filter_map(|&idx| {
+            pools[idx]
+                .pool_index
+                .map(|pid| (pid, pools[idx].lp_address))
+        })
+        .collect();
+
+    // 接近请求截止时间时跳过第二阶段，返回只含余额的部分结果
+    let truncated = services.deadline_near();
+    if truncated {
+        worker::console_warn!(
+            "[PERF] deadline near, skipping phase2 ({} calls)",
+            detail_calls.len()
+        );
+    }
+
+    let (results, farm_emissions) = futures_util::future::try_join(
+        async {
+            if truncated || detail_calls.is_empty() {
+                Ok(Vec::new())
+            } else {
+                services
+                    .multicall()?
+                    .aggregate_at(detail_calls, block_id)
+                    .await
+            }
+        },
+        async {
+            if truncated {
+                return Ok(Default::default());
+            }
+            Ok::<_, CroLensError>(
+                farm_apy::fetch_farm_emissions(services, masterchef, &farms)
+                    .await
+                    .unwrap_or_default(),
+            )
+        },
+    )
+    .await?;
 
     let t6 = types::now_ms();
     worker::console_log!("[PERF] phase2 rpc: {}ms", t6 - t5);
@@ -250,13 +388,18 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
     let mut vvs_positions: Vec<Value> = Vec::new();
     let mut vvs_total_liquidity_usd = 0.0_f64;
     let mut vvs_total_pending_rewards_usd = 0.0_f64;
-    let mut vvs_total_pending_vvs: U256 = U256::ZERO;
 
     let token_map = tokens;
-    let vvs_price_usd = token_map
-        .iter()
-        .find(|t| t.symbol.eq_ignore_ascii_case("VVS"))
-        .and_then(|t| price_map.get(&t.address).copied());
+    // tokens 表中没有 VVS 时使用内置核心代币列表中的地址
+    let vvs_token = infra::token::resolve_token(&token_map, "VVS")
+        .or_else(|_| infra::token::resolve_token(&infra::token::fallback_tokens(), "VVS"))?
+        .address;
+    let vvs_price_usd = price_map.get(&vvs_token).copied();
+    // 所有头寸的奖励按代币汇总，VVS 始终为第一项
+    let mut vvs_total_pending_rewards = vec![PendingReward {
+        token: vvs_token,
+        amount: U256::ZERO,
+    }];
 
     let mut result_idx = 0usize;
 
@@ -266,20 +409,32 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
         let (wallet_lp, staked_lp) = pool_balances[i];
         let user_lp = wallet_lp.saturating_add(staked_lp);
 
-        let reserves_bytes = results.get(result_idx)
+        if truncated {
+            vvs_positions.push(balance_only_vvs_position(pool, wallet_lp, staked_lp));
+            continue;
+        }
+
+        let reserves_bytes = results
+            .get(result_idx)
             .ok_or_else(|| CroLensError::RpcError("Missing multicall result".to_string()))?;
         result_idx += 1;
-        let supply_bytes = results.get(result_idx)
+        let supply_bytes = results
+            .get(result_idx)
             .ok_or_else(|| CroLensError::RpcError("Missing multicall result".to_string()))?;
         result_idx += 1;
 
-        let pending_bytes = if pool.pool_index.is_some() {
-            let b = results.get(result_idx)
+        let (pending_bytes, bonus_bytes) = if pool.pool_index.is_some() {
+            let b = results
+                .get(result_idx)
+                .ok_or_else(|| CroLensError::RpcError("Missing multicall result".to_string()))?;
+            result_idx += 1;
+            let bonus = results
+                .get(result_idx)
                 .ok_or_else(|| CroLensError::RpcError("Missing multicall result".to_string()))?;
             result_idx += 1;
-            Some(b)
+            (Some(b), Some(bonus))
         } else {
-            None
+            (None, None)
         };
 
         let Ok(reserves_data) = reserves_bytes else {
@@ -301,8 +456,9 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
 
         let reserve0 = U256::from(reserves_ret.reserve0);
         let reserve1 = U256::from(reserves_ret.reserve1);
-        let token0_amount = reserve0.saturating_mul(user_lp) / total_supply;
-        let token1_amount = reserve1.saturating_mul(user_lp) / total_supply;
+        // reserve * user_lp 可能超出 U256，使用 512 位中间值计算份额
+        let token0_amount = types::mul_div(reserve0, user_lp, total_supply).unwrap_or(U256::MAX);
+        let token1_amount = types::mul_div(reserve1, user_lp, total_supply).unwrap_or(U256::MAX);
 
         let pending_vvs = match pending_bytes {
             Some(Ok(data)) if !data.is_empty() => {
@@ -314,6 +470,11 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
             _ => U256::ZERO,
         };
         let pending_vvs_formatted = types::format_units(&pending_vvs, 18);
+        let bonus_rewards = match bonus_bytes {
+            Some(Ok(data)) if !data.is_empty() => decode_bonus_rewards(data),
+            _ => Vec::new(),
+        };
+        let pending_rewards = position_pending_rewards(vvs_token, pending_vvs, bonus_rewards);
 
         let token0 = token_map
             .iter()
@@ -350,14 +511,31 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
             vvs_total_liquidity_usd += v;
         }
 
-        let pending_rewards_usd = match (vvs_price_usd, pending_vvs_formatted.parse::<f64>().ok()) {
-            (Some(price), Some(amount)) => Some(price * amount),
+        // 整个池子的 TVL -> LP 单价 -> farm APY
+        let pool_tvl_usd = match (token0_price, token1_price) {
+            (Some(p0), Some(p1)) => {
+                let r0 = types::format_units(&reserve0, token0_decimals)
+                    .parse::<f64>()
+                    .unwrap_or(0.0);
+                let r1 = types::format_units(&reserve1, token1_decimals)
+                    .parse::<f64>()
+                    .unwrap_or(0.0);
+                Some(r0 * p0 + r1 * p1)
+            }
             _ => None,
         };
+        let lp_price = pool_tvl_usd.and_then(|tvl| farm_apy::lp_price_usd(tvl, total_supply));
+        let apy = vvs_position_apy(pool.pool_index, &farm_emissions, vvs_price_usd, lp_price);
+
+        let pending_rewards_usd =
+            pending_rewards_usd_total(&pending_rewards, &token_map, &price_map);
         if let Some(v) = pending_rewards_usd {
             vvs_total_pending_rewards_usd += v;
         }
-        vvs_total_pending_vvs = vvs_total_pending_vvs.saturating_add(pending_vvs);
+        merge_pending_rewards(
+            &mut vvs_total_pending_rewards,
+            pending_rewards.iter().cloned(),
+        );
 
         vvs_positions.push(serde_json::json!({
             "pool_id": pool.pool_id,
@@ -377,12 +555,12 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
                 "amount": token1_amount.to_string(),
                 "amount_formatted": token1_formatted,
             },
-            "liquidity_usd": value_usd.map(|v| format!("{v:.2}")),
-            "pending_rewards": { "vvs": pending_vvs_formatted.clone() },
+            "liquidity_usd": value_usd.map(|v| services.precision.usd(v)),
+            "pending_rewards": pending_rewards_json(&pending_rewards, &token_map, &price_map, &services.precision),
             "pending_vvs": pending_vvs.to_string(),
             "pending_vvs_formatted": pending_vvs_formatted,
-            "pending_rewards_usd": pending_rewards_usd.map(|v| format!("{v:.2}")),
-            "apy": Value::Null,
+            "pending_rewards_usd": pending_rewards_usd.map(|v| services.precision.usd(v)),
+            "apy": apy,
         }));
     }
 
@@ -398,32 +576,37 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
         let market = &markets[market_idx];
         let decoded = &market_snapshots[i];
 
-        let supply_rate = results.get(result_idx)
-            .ok_or_else(|| CroLensError::RpcError("Missing multicall result".to_string()))?;
-        result_idx += 1;
-        let borrow_rate = results.get(result_idx)
-            .ok_or_else(|| CroLensError::RpcError("Missing multicall result".to_string()))?;
-        result_idx += 1;
+        // 截断时没有利率数据，APY 为 null
+        let (supply_apy, borrow_apy) = if truncated {
+            (None, None)
+        } else {
+            let supply_rate = results
+                .get(result_idx)
+                .ok_or_else(|| CroLensError::RpcError("Missing multicall result".to_string()))?;
+            result_idx += 1;
+            let borrow_rate = results
+                .get(result_idx)
+                .ok_or_else(|| CroLensError::RpcError("Missing multicall result".to_string()))?;
+            result_idx += 1;
 
-        let supply_rate_per_block = match supply_rate {
-            Ok(data) => {
-                abi::supplyRatePerBlockCall::abi_decode_returns(data, true)
+            let supply_rate_per_block = match supply_rate {
+                Ok(data) => abi::supplyRatePerBlockCall::abi_decode_returns(data, true)
                     .map(|d| d._0)
-                    .unwrap_or(U256::ZERO)
-            }
-            Err(_) => U256::ZERO,
-        };
-        let borrow_rate_per_block = match borrow_rate {
-            Ok(data) => {
-                abi::borrowRatePerBlockCall::abi_decode_returns(data, true)
+                    .unwrap_or(U256::ZERO),
+                Err(_) => U256::ZERO,
+            };
+            let borrow_rate_per_block = match borrow_rate {
+                Ok(data) => abi::borrowRatePerBlockCall::abi_decode_returns(data, true)
                     .map(|d| d._0)
-                    .unwrap_or(U256::ZERO)
-            }
-            Err(_) => U256::ZERO,
-        };
+                    .unwrap_or(U256::ZERO),
+                Err(_) => U256::ZERO,
+            };
 
-        let supply_apy = apy_percent_string(supply_rate_per_block);
-        let borrow_apy = apy_percent_string(borrow_rate_per_block);
+            (
+                apy_percent_string(supply_rate_per_block),
+                apy_percent_string(borrow_rate_per_block),
+            )
+        };
 
         let token = token_map
             .iter()
@@ -434,10 +617,8 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
             .as_ref()
             .and_then(|t| price_map.get(&t.address).copied());
 
-        let supply_underlying = decoded
-            .ctoken_balance
-            .saturating_mul(decoded.exchange_rate)
-            / U256::from(1_000_000_000_000_000_000u128);
+        let supply_underlying =
+            tectonic::underlying_from_ctoken(decoded.ctoken_balance, decoded.exchange_rate);
         let supply_formatted = types::format_units(&supply_underlying, decimals);
         let supply_value_usd = match (price, supply_formatted.parse::<f64>().ok()) {
             (Some(p), Some(a)) => Some(p * a),
@@ -466,14 +647,12 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
                     ));
                 }
             }
-            supplies.push(serde_json::json!({
-                "market_address": market.ctoken_address.to_string(),
-                "asset_symbol": market.underlying_symbol,
-                "supply_balance": supply_underlying.to_string(),
-                "supply_balance_usd": supply_value_usd.map(|v| format!("{v:.2}")),
-                "supply_apy": supply_apy,
-                "is_collateral": market.collateral_factor.is_some(),
-            }));
+            supplies.push(tectonic_supply_entry(
+                market,
+                supply_underlying,
+                supply_value_usd.map(|v| services.precision.usd(v)),
+                supply_apy,
+            ));
         }
 
         if borrow_underlying != U256::ZERO {
@@ -489,7 +668,7 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
                 "market_address": market.ctoken_address.to_string(),
                 "asset_symbol": market.underlying_symbol,
                 "borrow_balance": borrow_underlying.to_string(),
-                "borrow_balance_usd": borrow_value_usd.map(|v| format!("{v:.2}")),
+                "borrow_balance_usd": borrow_value_usd.map(|v| services.precision.usd(v)),
                 "borrow_apy": borrow_apy,
             }));
         }
@@ -498,7 +677,7 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
     let health_factor = health_factor_string(total_supply_usd, total_borrow_usd);
 
     let result = if input.simple_mode {
-        let pending_vvs_total_formatted = types::format_units(&vvs_total_pending_vvs, 18);
+        let pending_total_text = pending_rewards_text(&vvs_total_pending_rewards, &token_map);
         let mut tectonic_details = Vec::new();
         if let Some(v) = first_supply_detail {
             tectonic_details.push(v);
@@ -511,42 +690,92 @@ pub async fn get_defi_positions(services: &infra::Services, args: Value) -> Resu
         } else {
             format!(" ({})", tectonic_details.join(", "))
         };
-        let summary = format!(
-            "VVS: {} position(s), Pending {} VVS (${:.2}) | Tectonic: Supply ${:.2}, Borrow ${:.2}, Health {}{}",
+        let mut summary = format!(
+            "VVS: {} position(s), Pending {} (${:.2}) | Tectonic: Supply ${:.2}, Borrow ${:.2}, Health {}{}",
             vvs_positions.len(),
-            pending_vvs_total_formatted,
+            pending_total_text,
             vvs_total_pending_rewards_usd,
             total_supply_usd,
             total_borrow_usd,
             health_factor,
             tectonic_suffix
         );
+        if truncated {
+            summary.push_str(" (truncated)");
+        }
         serde_json::json!({ "text": summary, "meta": services.meta() })
     } else {
         let net_value_usd = total_supply_usd - total_borrow_usd;
-        serde_json::json!({
+        let mut result = serde_json::json!({
             "address": input.address,
             "vvs": {
-                "total_liquidity_usd": format!("{vvs_total_liquidity_usd:.2}"),
-                "total_pending_rewards_usd": format!("{vvs_total_pending_rewards_usd:.2}"),
+                "total_liquidity_usd": services.precision.usd(vvs_total_liquidity_usd),
+                "total_pending_rewards_usd": services.precision.usd(vvs_total_pending_rewards_usd),
+                "pending_rewards": pending_rewards_json(&vvs_total_pending_rewards, &token_map, &price_map, &services.precision),
                 "positions": vvs_positions,
             },
             "tectonic": {
-                "total_supply_usd": format!("{total_supply_usd:.2}"),
-                "total_borrow_usd": format!("{total_borrow_usd:.2}"),
-                "net_value_usd": format!("{net_value_usd:.2}"),
+                "total_supply_usd": services.precision.usd(total_supply_usd),
+                "total_borrow_usd": services.precision.usd(total_borrow_usd),
+                "net_value_usd": services.precision.usd(net_value_usd),
                 "supplies": supplies,
                 "borrows": borrows,
                 "health_factor": health_factor,
             },
             "meta": services.meta(),
-        })
+        });
+        if truncated {
+            result["truncated"] = serde_json::json!(true);
+        }
+        result
     };
 
     Ok(result)
 }
 
-fn apy_percent_string(rate_per_block: U256) -> Option<String> {
+/// 截断时的 VVS 头寸：只有第一阶段已知的 LP 余额，储备/奖励/APY 均未查询
+fn balance_only_vvs_position(
+    pool: &infra::config::DexPool,
+    wallet_lp: U256,
+    staked_lp: U256,
+) -> Value {
+    serde_json::json!({
+        "pool_id": pool.pool_id,
+        "pool_name": format!("{}-{}", pool.token0_symbol, pool.token1_symbol),
+        "lp_amount": wallet_lp.saturating_add(staked_lp).to_string(),
+        "lp_wallet_amount": wallet_lp.to_string(),
+        "lp_staked_amount": staked_lp.to_string(),
+        "liquidity_usd": Value::Null,
+        "pending_rewards_usd": Value::Null,
+        "apy": Value::Null,
+        "truncated": true,
+    })
+}
+
+/// Tectonic 供应条目；collateral_factor 原样带出，供清算风险按抵押率加权
+pub(crate) fn tectonic_supply_entry(
+    market: &infra::config::LendingMarket,
+    supply_underlying: U256,
+    supply_balance_usd: Option<String>,
+    supply_apy: Option<String>,
+) -> Value {
+    let collateral_factor = market
+        .collateral_factor
+        .as_deref()
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|v| v.is_finite());
+    serde_json::json!({
+        "market_address": market.ctoken_address.to_string(),
+        "asset_symbol": market.underlying_symbol,
+        "supply_balance": supply_underlying.to_string(),
+        "supply_balance_usd": supply_balance_usd,
+        "supply_apy": supply_apy,
+        "is_collateral": market.collateral_factor.is_some(),
+        "collateral_factor": collateral_factor,
+    })
+}
+
+pub(crate) fn apy_percent_string(rate_per_block: U256) -> Option<String> {
     if rate_per_block == U256::ZERO {
         return Some("0.00%".to_string());
     }
@@ -563,6 +792,18 @@ fn apy_percent_string(rate_per_block: U256) -> Option<String> {
     Some(format!("{:.2}%", apy * 100.0))
 }
 
+/// 只有可质押 (有 pool_index) 的池子才有 farm APY
+fn vvs_position_apy(
+    pool_index: Option<i64>,
+    emissions: &std::collections::HashMap<i64, FarmEmission>,
+    vvs_price_usd: Option<f64>,
+    lp_price_usd: Option<f64>,
+) -> Option<String> {
+    let emission = emissions.get(&pool_index?)?;
+    let apy = farm_apy::farm_apy_percent(emission, vvs_price_usd?, lp_price_usd?)?;
+    Some(format!("{:.2}%", apy))
+}
+
 fn health_factor_string(total_supply_usd: f64, total_borrow_usd: f64) -> String {
     if total_borrow_usd <= 0.0 {
         return "∞".to_string();
@@ -570,14 +811,236 @@ fn health_factor_string(total_supply_usd: f64, total_borrow_usd: f64) -> String
     format!("{:.2}", total_supply_usd / total_borrow_usd)
 }
 
+/// 单个奖励代币的待领取数量 (最小单位)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PendingReward {
+    token: alloy_primitives::Address,
+    amount: U256,
+}
+
+/// 按代币合并奖励，保持首次出现的顺序；新出现的零数量代币不加入列表
+fn merge_pending_rewards(
+    rewards: &mut Vec<PendingReward>,
+    extra: impl IntoIterator<Item = PendingReward>,
+) {
+    for reward in extra {
+        if let Some(existing) = rewards.iter_mut().find(|r| r.token == reward.token) {
+            existing.amount = existing.amount.saturating_add(reward.amount);
+        } else if reward.amount > U256::ZERO {
+            rewards.push(reward);
+        }
+    }
+}
+
+/// 单个头寸的奖励列表：VVS 始终为第一项，farm 额外发放的代币追加在后
+fn position_pending_rewards(
+    vvs_token: alloy_primitives::Address,
+    pending_vvs: U256,
+    bonus: Vec<PendingReward>,
+) -> Vec<PendingReward> {
+    let mut rewards = vec![PendingReward {
+        token: vvs_token,
+        amount: pending_vvs,
+    }];
+    merge_pending_rewards(&mut rewards, bonus);
+    rewards
+}
+
+/// 解码 `pendingTokens(pid, user)`；数组长度不一致时只取成对部分
+fn decode_bonus_rewards(data: &[u8]) -> Vec<PendingReward> {
+    abi::pendingTokensCall::abi_decode_returns(data, true)
+        .map(|ret| {
+            ret.tokens
+                .into_iter()
+                .zip(ret.amounts)
+                .map(|(token, amount)| PendingReward { token, amount })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 奖励的 (symbol, 格式化数量, USD 价值)；不在 tokens 表中的代币按 18 位精度且无价格
+fn describe_pending_reward(
+    reward: &PendingReward,
+    tokens: &[infra::token::Token],
+    prices: &std::collections::HashMap<alloy_primitives::Address, f64>,
+) -> (Option<String>, String, Option<f64>) {
+    let token = tokens.iter().find(|t| t.address == reward.token);
+    let decimals = token.map(|t| t.decimals).unwrap_or(18);
+    let formatted = types::format_units(&reward.amount, decimals);
+    let usd = match (prices.get(&reward.token), formatted.parse::<f64>().ok()) {
+        (Some(price), Some(amount)) => Some(price * amount),
+        _ => None,
+    };
+    (token.map(|t| t.symbol.clone()), formatted, usd)
+}
+
+/// 所有有价格的奖励的 USD 合计；全部无价格时为 None
+fn pending_rewards_usd_total(
+    rewards: &[PendingReward],
+    tokens: &[infra::token::Token],
+    prices: &std::collections::HashMap<alloy_primitives::Address, f64>,
+) -> Option<f64> {
+    rewards
+        .iter()
+        .filter_map(|r| describe_pending_reward(r, tokens, prices).2)
+        .fold(None, |acc, v| Some(acc.unwrap_or(0.0) + v))
+}
+
+fn pending_rewards_json(
+    rewards: &[PendingReward],
+    tokens: &[infra::token::Token],
+    prices: &std::collections::HashMap<alloy_primitives::Address, f64>,
+    precision: &types::Precision,
+) -> Value {
+    rewards
+        .iter()
+        .map(|reward| {
+            let (symbol, formatted, usd) = describe_pending_reward(reward, tokens, prices);
+            serde_json::json!({
+                "token": reward.token.to_string(),
+                "symbol": symbol,
+                "amount": reward.amount.to_string(),
+                "amount_formatted": formatted,
+                "usd": usd.map(|v| precision.usd(v)),
+            })
+        })
+        .collect()
+}
+
+/// simple_mode 文本，例如 "1.5 VVS + 2 TONIC"
+fn pending_rewards_text(rewards: &[PendingReward], tokens: &[infra::token::Token]) -> String {
+    rewards
+        .iter()
+        .map(|reward| {
+            let (symbol, formatted, _) =
+                describe_pending_reward(reward, tokens, &std::collections::HashMap::new());
+            format!(
+                "{formatted} {}",
+                symbol.unwrap_or_else(|| reward.token.to_string())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn sample_pool(lp: u8, pool_index: Option<i64>) -> infra::config::DexPool {
+        infra::config::DexPool {
+            pool_id: format!("pool-{lp}"),
+            pool_index,
+            lp_address: alloy_primitives::Address::repeat_byte(lp),
+            token0_address: alloy_primitives::Address::repeat_byte(0xa0),
+            token1_address: alloy_primitives::Address::repeat_byte(0xa1),
+            token0_symbol: "WCRO".to_string(),
+            token1_symbol: "USDC".to_string(),
+        }
+    }
+
+    fn sample_market(ctoken: u8) -> infra::config::LendingMarket {
+        infra::config::LendingMarket {
+            ctoken_address: alloy_primitives::Address::repeat_byte(ctoken),
+            underlying_address: alloy_primitives::Address::repeat_byte(0xb0),
+            underlying_symbol: "USDC".to_string(),
+            collateral_factor: None,
+        }
+    }
+
+    fn selected_calls(protocols: Option<&[DefiProtocol]>) -> Vec<infra::multicall::Call> {
+        let pools = vec![sample_pool(0x11, Some(3)), sample_pool(0x12, None)];
+        let markets = vec![sample_market(0x21), sample_market(0x22)];
+        phase_one_calls(
+            ProtocolSelection::from_args(protocols),
+            &pools,
+            &markets,
+            alloy_primitives::Address::repeat_byte(0x99),
+            alloy_primitives::Address::repeat_byte(0x01),
+        )
+    }
+
+    fn is_snapshot_call(call: &infra::multicall::Call) -> bool {
+        call.call_data
+            .starts_with(&abi::getAccountSnapshotCall::SELECTOR)
+    }
+
+    #[test]
+    fn protocols_default_to_all() {
+        let all = ProtocolSelection {
+            vvs: true,
+            tectonic: true,
+        };
+        assert_eq!(ProtocolSelection::from_args(None), all);
+        assert_eq!(ProtocolSelection::from_args(Some(&[])), all);
+        assert_eq!(selected_calls(None).len(), 5);
+    }
+
+    #[test]
+    fn vvs_only_produces_no_tectonic_calls() {
+        let calls = selected_calls(Some(&[DefiProtocol::Vvs]));
+        assert_eq!(calls.len(), 3);
+        assert!(!calls.iter().any(is_snapshot_call));
+    }
+
+    #[test]
+    fn tectonic_only_produces_no_vvs_calls() {
+        let calls = selected_calls(Some(&[DefiProtocol::Tectonic]));
+        assert_eq!(calls.len(), 2);
+        assert!(calls.iter().all(is_snapshot_call));
+    }
+
+    #[test]
+    fn unknown_protocol_is_rejected() {
+        let parsed: std::result::Result<GetDefiPositionsArgs, _> =
+            serde_json::from_value(serde_json::json!({
+                "address": "0x0000000000000000000000000000000000000001",
+                "protocols": ["aave"]
+            }));
+        assert!(parsed.is_err());
+    }
+
     fn parse_percent(value: &str) -> f64 {
         value.trim_end_matches('%').parse::<f64>().unwrap_or(0.0)
     }
 
+    fn sample_emissions() -> std::collections::HashMap<i64, FarmEmission> {
+        let one = U256::from(10u64).pow(U256::from(18u64));
+        let mut emissions = std::collections::HashMap::new();
+        emissions.insert(
+            7,
+            FarmEmission {
+                alloc_point: U256::from(10u64),
+                total_alloc_point: U256::from(100u64),
+                vvs_per_block: one,
+                staked_lp: one * U256::from(1_000u64),
+            },
+        );
+        emissions
+    }
+
+    #[test]
+    fn farmable_pool_gets_apy() {
+        let apy = vvs_position_apy(Some(7), &sample_emissions(), Some(0.001), Some(10.0))
+            .expect("farmable pool should have apy");
+        assert!(apy.ends_with('%'));
+        assert!(parse_percent(&apy) > 0.0);
+    }
+
+    #[test]
+    fn non_farmable_pool_has_no_apy() {
+        assert!(vvs_position_apy(None, &sample_emissions(), Some(0.001), Some(10.0)).is_none());
+        // pool_index 存在但没有排放数据
+        assert!(vvs_position_apy(Some(8), &sample_emissions(), Some(0.001), Some(10.0)).is_none());
+    }
+
+    #[test]
+    fn apy_requires_prices() {
+        assert!(vvs_position_apy(Some(7), &sample_emissions(), None, Some(10.0)).is_none());
+        assert!(vvs_position_apy(Some(7), &sample_emissions(), Some(0.001), None).is_none());
+    }
+
     #[test]
     fn apy_zero_is_zero() {
         assert_eq!(apy_percent_string(U256::ZERO), Some("0.00%".to_string()));
@@ -668,4 +1131,126 @@ mod tests {
     fn health_factor_handles_zero_supply() {
         assert_eq!(health_factor_string(0.0, 100.0), "0.00");
     }
+
+    fn reward_token(symbol: &str, byte: u8) -> infra::token::Token {
+        infra::token::Token {
+            address: alloy_primitives::Address::repeat_byte(byte),
+            symbol: symbol.to_string(),
+            decimals: 18,
+            is_stablecoin: false,
+            is_spam: false,
+        }
+    }
+
+    fn reward(byte: u8, amount: u64) -> PendingReward {
+        PendingReward {
+            token: alloy_primitives::Address::repeat_byte(byte),
+            amount: U256::from(amount),
+        }
+    }
+
+    #[test]
+    fn vvs_reward_is_default_first_entry() {
+        let vvs = alloy_primitives::Address::repeat_byte(0x0a);
+        let rewards = position_pending_rewards(vvs, U256::ZERO, Vec::new());
+        assert_eq!(rewards, vec![reward(0x0a, 0)]);
+    }
+
+    #[test]
+    fn bonus_rewards_follow_vvs_and_merge_duplicates() {
+        let vvs = alloy_primitives::Address::repeat_byte(0x0a);
+        let rewards = position_pending_rewards(
+            vvs,
+            U256::from(5u64),
+            vec![
+                reward(0x0b, 3),
+                reward(0x0a, 2),
+                reward(0x0c, 0),
+                reward(0x0b, 1),
+            ],
+        );
+        // 零数量的 bonus 代币被忽略，重复代币合并
+        assert_eq!(rewards, vec![reward(0x0a, 7), reward(0x0b, 4)]);
+    }
+
+    #[test]
+    fn multiple_reward_tokens_aggregate_across_positions() {
+        let vvs = alloy_primitives::Address::repeat_byte(0x0a);
+        let mut total = vec![reward(0x0a, 0)];
+        let first = position_pending_rewards(vvs, U256::from(10u64), vec![reward(0x0b, 4)]);
+        let second = position_pending_rewards(
+            vvs,
+            U256::from(1u64),
+            vec![reward(0x0c, 6), reward(0x0b, 2)],
+        );
+        merge_pending_rewards(&mut total, first);
+        merge_pending_rewards(&mut total, second);
+        assert_eq!(
+            total,
+            vec![reward(0x0a, 11), reward(0x0b, 6), reward(0x0c, 6)]
+        );
+    }
+
+    #[test]
+    fn bonus_rewards_decode_from_pending_tokens() {
+        let tokens = vec![
+            alloy_primitives::Address::repeat_byte(0x0b),
+            alloy_primitives::Address::repeat_byte(0x0c),
+        ];
+        let amounts = vec![U256::from(3u64), U256::from(9u64)];
+        let data = abi::pendingTokensCall::abi_encode_returns(&(tokens, amounts));
+        assert_eq!(
+            decode_bonus_rewards(&data),
+            vec![reward(0x0b, 3), reward(0x0c, 9)]
+        );
+        // 不支持 pendingTokens 的 farm 返回空数据或 revert
+        assert!(decode_bonus_rewards(&[]).is_empty());
+    }
+
+    #[test]
+    fn rewards_json_prices_known_tokens_only() {
+        let one = 1_000_000_000_000_000_000u64;
+        let tokens = vec![reward_token("VVS", 0x0a), reward_token("TONIC", 0x0b)];
+        let mut prices = std::collections::HashMap::new();
+        prices.insert(alloy_primitives::Address::repeat_byte(0x0a), 0.5);
+        prices.insert(alloy_primitives::Address::repeat_byte(0x0b), 2.0);
+        let rewards = vec![reward(0x0a, 2 * one), reward(0x0b, one), reward(0x0c, one)];
+
+        let json = pending_rewards_json(&rewards, &tokens, &prices, &types::Precision::default());
+        let list = json.as_array().expect("rewards list");
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0]["symbol"], "VVS");
+        assert_eq!(list[0]["usd"], "1.00");
+        assert_eq!(list[1]["symbol"], "TONIC");
+        assert_eq!(list[1]["usd"], "2.00");
+        assert!(list[2]["symbol"].is_null());
+        assert!(list[2]["usd"].is_null());
+
+        let total = pending_rewards_usd_total(&rewards, &tokens, &prices).expect("priced rewards");
+        assert!((total - 3.0).abs() < 1e-9);
+        assert!(pending_rewards_usd_total(&rewards[2..], &tokens, &prices).is_none());
+        assert_eq!(
+            pending_rewards_text(&rewards[..2], &tokens),
+            "2 VVS + 1 TONIC"
+        );
+    }
+
+    #[test]
+    fn truncated_position_keeps_phase1_balances_only() {
+        let pool = infra::config::DexPool {
+            pool_id: "vvs_cro_usdc".to_string(),
+            pool_index: Some(3),
+            lp_address: alloy_primitives::Address::repeat_byte(1),
+            token0_address: alloy_primitives::Address::repeat_byte(2),
+            token1_address: alloy_primitives::Address::repeat_byte(3),
+            token0_symbol: "CRO".to_string(),
+            token1_symbol: "USDC".to_string(),
+        };
+        let position = balance_only_vvs_position(&pool, U256::from(5u64), U256::from(7u64));
+        assert_eq!(position["pool_name"], "CRO-USDC");
+        assert_eq!(position["lp_amount"], "12");
+        assert_eq!(position["lp_staked_amount"], "7");
+        assert!(position["apy"].is_null());
+        assert_eq!(position["truncated"], true);
+    }
 }
@@ -0,0 +1,233 @@
+//! VVS farm APY shared by get_pool_info and get_defi_positions.
+//!
+//! APY = 年化 VVS 排放价值 / 质押在 MasterChef 中的 LP 价值 (不计复利)
+
+use std::collections::HashMap;
+
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::SolCall;
+
+use crate::abi;
+use crate::error::Result;
+use crate::infra;
+use crate::infra::multicall::Call;
+use crate::types;
+
+pub(crate) const BLOCKS_PER_YEAR: f64 = 179_740_800.0;
+pub(crate) const BLOCKS_PER_DAY: f64 = BLOCKS_PER_YEAR / 365.0;
+
+/// 单个 farm 的排放参数 (来自 MasterChef)
+#[derive(Debug, Clone, PartialEq)]
+pub struct FarmEmission {
+    pub alloc_point: U256,
+    pub total_alloc_point: U256,
+    pub vvs_per_block: U256,
+    /// MasterChef 持有的 LP 数量 (即总质押量)
+    pub staked_lp: U256,
+}
+
+/// 批量读取 farm 排放参数；`farms` 为 (pool_index, lp_address)
+/// 单个 farm 解码失败时不返回该 farm，而不是让整个请求失败
+pub async fn fetch_farm_emissions(
+    services: &infra::Services,
+    masterchef: Address,
+    farms: &[(i64, Address)],
+) -> Result<HashMap<i64, FarmEmission>> {
+    if farms.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut calls = Vec::with_capacity(2 + farms.len() * 2);
+    calls.push(Call {
+        target: masterchef,
+        call_data: abi::totalAllocPointCall {}.abi_encode().into(),
+    });
+    calls.push(Call {
+        target: masterchef,
+        call_data: abi::vvsPerBlockCall {}.abi_encode().into(),
+    });
+    for (pid, lp_address) in farms {
+        calls.push(Call {
+            target: masterchef,
+            call_data: abi::poolInfoCall {
+                pid: U256::from(*pid as u64),
+            }
+            .abi_encode()
+            .into(),
+        });
+        calls.push(Call {
+            target: *lp_address,
+            call_data: abi::balanceOfCall {
+                account: masterchef,
+            }
+            .abi_encode()
+            .into(),
+        });
+    }
+
+    let results = services.multicall()?.aggregate(calls).await?;
+
+    let total_alloc_point = results
+        .first()
+        .and_then(|r| r.as_ref().ok())
+        .and_then(|data| abi::totalAllocPointCall::abi_decode_returns(data, true).ok())
+        .map(|v| v._0)
+        .unwrap_or(U256::ZERO);
+    let vvs_per_block = results
+        .get(1)
+        .and_then(|r| r.as_ref().ok())
+        .and_then(|data| abi::vvsPerBlockCall::abi_decode_returns(data, true).ok())
+        .map(|v| v._0)
+        .unwrap_or(U256::ZERO);
+
+    let mut out = HashMap::with_capacity(farms.len());
+    for (i, (pid, _)) in farms.iter().enumerate() {
+        let alloc_point = results
+            .get(2 + i * 2)
+            .and_then(|r| r.as_ref().ok())
+            .and_then(|data| abi::poolInfoCall::abi_decode_returns(data, true).ok())
+            .map(|v| v.allocPoint);
+        let staked_lp = results
+            .get(3 + i * 2)
+            .and_then(|r| r.as_ref().ok())
+            .and_then(|data| abi::balanceOfCall::abi_decode_returns(data, true).ok())
+            .map(|v| v._0);
+        if let (Some(alloc_point), Some(staked_lp)) = (alloc_point, staked_lp) {
+            out.insert(
+                *pid,
+                FarmEmission {
+                    alloc_point,
+                    total_alloc_point,
+                    vvs_per_block,
+                    staked_lp,
+                },
+            );
+        }
+    }
+
+    Ok(out)
+}
+
+/// LP 单价 = 池子 TVL / LP 总供应量
+pub fn lp_price_usd(pool_tvl_usd: f64, total_lp_supply: U256) -> Option<f64> {
+    let supply = types::format_units(&total_lp_supply, 18)
+        .parse::<f64>()
+        .ok()?;
+    if supply <= 0.0 || !pool_tvl_usd.is_finite() || pool_tvl_usd <= 0.0 {
+        return None;
+    }
+    Some(pool_tvl_usd / supply)
+}
+
+/// 基于排放计算的 farm APY (百分比)
+pub fn farm_apy_percent(
+    emission: &FarmEmission,
+    vvs_price_usd: f64,
+    lp_price_usd: f64,
+) -> Option<f64> {
+    if emission.total_alloc_point.is_zero() || emission.vvs_per_block.is_zero() {
+        return None;
+    }
+
+    let alloc: f64 = types::format_units(&emission.alloc_point, 0).parse().ok()?;
+    let total_alloc: f64 = types::format_units(&emission.total_alloc_point, 0)
+        .parse()
+        .ok()?;
+    let vvs_per_block: f64 = types::format_units(&emission.vvs_per_block, 18)
+        .parse()
+        .ok()?;
+    let staked_lp: f64 = types::format_units(&emission.staked_lp, 18).parse().ok()?;
+
+    let staked_usd = staked_lp * lp_price_usd;
+    if staked_usd <= 0.0 || vvs_price_usd <= 0.0 {
+        return None;
+    }
+
+    let yearly_rewards_usd =
+        vvs_per_block * (alloc / total_alloc) * BLOCKS_PER_YEAR * vvs_price_usd;
+    let apy = yearly_rewards_usd / staked_usd * 100.0;
+    if !apy.is_finite() || apy < 0.0 {
+        return None;
+    }
+    Some(apy)
+}
+
+/// MasterChef 每日排放的 VVS 数量 (所有 farm 合计)
+pub fn daily_emission_vvs(vvs_per_block: U256) -> Option<f64> {
+    let per_block: f64 = types::format_units(&vvs_per_block, 18).parse().ok()?;
+    let daily = per_block * BLOCKS_PER_DAY;
+    daily.is_finite().then_some(daily)
+}
+
+/// 每日排放价值 (USD)；价格未知或非正时返回 None
+pub fn daily_emission_usd(vvs_per_block: U256, vvs_price_usd: f64) -> Option<f64> {
+    if !vvs_price_usd.is_finite() || vvs_price_usd <= 0.0 {
+        return None;
+    }
+    let usd = daily_emission_vvs(vvs_per_block)? * vvs_price_usd;
+    usd.is_finite().then_some(usd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wei(v: u64) -> U256 {
+        U256::from(v) * U256::from(10u64).pow(U256::from(18u64))
+    }
+
+    fn emission(alloc: u64, total: u64) -> FarmEmission {
+        FarmEmission {
+            alloc_point: U256::from(alloc),
+            total_alloc_point: U256::from(total),
+            vvs_per_block: wei(1),
+            staked_lp: wei(1_000),
+        }
+    }
+
+    #[test]
+    fn lp_price_divides_tvl_by_supply() {
+        assert_eq!(lp_price_usd(2_000.0, wei(1_000)), Some(2.0));
+        assert_eq!(lp_price_usd(2_000.0, U256::ZERO), None);
+        assert_eq!(lp_price_usd(0.0, wei(1_000)), None);
+    }
+
+    #[test]
+    fn apy_scales_with_allocation_weight() {
+        let half = farm_apy_percent(&emission(50, 100), 0.01, 1.0).expect("apy");
+        let full = farm_apy_percent(&emission(100, 100), 0.01, 1.0).expect("apy");
+        assert!((full / half - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn apy_matches_emission_formula() {
+        // 1 VVS/block * 10% * $0.001 * blocks/year / $10_000 staked
+        let apy = farm_apy_percent(&emission(10, 100), 0.001, 10.0).expect("apy");
+        let expected = BLOCKS_PER_YEAR * 0.1 * 0.001 / 10_000.0 * 100.0;
+        assert!((apy - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn daily_emission_scales_rate_by_blocks_per_day() {
+        let daily = daily_emission_vvs(wei(2)).expect("daily");
+        assert!((daily - 2.0 * BLOCKS_PER_DAY).abs() < 1e-6);
+        assert_eq!(daily_emission_vvs(U256::ZERO), Some(0.0));
+    }
+
+    #[test]
+    fn daily_emission_usd_uses_vvs_price() {
+        let usd = daily_emission_usd(wei(1), 0.002).expect("usd");
+        assert!((usd - BLOCKS_PER_DAY * 0.002).abs() < 1e-6);
+        assert!(daily_emission_usd(wei(1), 0.0).is_none());
+        assert!(daily_emission_usd(wei(1), f64::NAN).is_none());
+    }
+
+    #[test]
+    fn apy_is_none_without_emissions_or_stake() {
+        assert!(farm_apy_percent(&emission(0, 0), 0.01, 1.0).is_none());
+        let mut no_stake = emission(10, 100);
+        no_stake.staked_lp = U256::ZERO;
+        assert!(farm_apy_percent(&no_stake, 0.01, 1.0).is_none());
+        assert!(farm_apy_percent(&emission(10, 100), 0.0, 1.0).is_none());
+    }
+}
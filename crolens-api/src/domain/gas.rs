@@ -1,9 +1,10 @@
 use alloy_primitives::U256;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::error::Result;
 use crate::infra;
+use crate::infra::samples::{Sample, SampleRing};
 use crate::types;
 
 #[derive(Debug, Deserialize)]
@@ -20,6 +21,76 @@ const GAS_SWAP: u64 = 150_000;
 const GAS_ADD_LIQUIDITY: u64 = 200_000;
 const GAS_REMOVE_LIQUIDITY: u64 = 180_000;
 
+/// 最近 24h 的 gas 价格样本 (KV 环形缓冲区，由 get_gas_price 写入)
+const GAS_SAMPLES_KEY: &str = "gas:samples";
+/// 每 5 分钟最多一个样本，容量 24h / 5min
+const GAS_SAMPLES: SampleRing = SampleRing::new(24 * 3600 * 1000, 5 * 60 * 1000, 288);
+/// 样本数不足时不计算百分位
+const GAS_PERCENTILE_MIN_SAMPLES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct GasSample {
+    ts_ms: i64,
+    gwei: f64,
+}
+
+impl Sample for GasSample {
+    fn ts_ms(&self) -> i64 {
+        self.ts_ms
+    }
+
+    fn is_valid(&self) -> bool {
+        self.gwei.is_finite() && self.gwei >= 0.0
+    }
+}
+
+/// 当前价格在样本中的百分位 (低于当前值的样本占比，相等的样本计一半)
+fn gas_percentile(samples: &[GasSample], current_gwei: f64) -> Option<u8> {
+    if samples.len() < GAS_PERCENTILE_MIN_SAMPLES {
+        return None;
+    }
+    let below = samples.iter().filter(|s| s.gwei < current_gwei).count() as f64;
+    let equal = samples.iter().filter(|s| s.gwei == current_gwei).count() as f64;
+    let rank = (below + equal / 2.0) / samples.len() as f64 * 100.0;
+    Some(rank.round().clamp(0.0, 100.0) as u8)
+}
+
+fn ordinal(n: u8) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
+
+fn percentile_text(percentile: u8) -> String {
+    format!(
+        "Current gas is in the {} percentile of the last 24h",
+        ordinal(percentile)
+    )
+}
+
+/// 读取样本、计算百分位并写入当前样本 (尽力而为，KV 失败时不影响 gas 查询)
+async fn record_gas_sample(services: &infra::Services, current_gwei: f64) -> Option<(u8, usize)> {
+    let key = services.kv_key(GAS_SAMPLES_KEY);
+    let now = types::now_ms();
+    let samples: Vec<GasSample> = GAS_SAMPLES.load(services, &key).await;
+    let window = GAS_SAMPLES.window(&samples, now);
+    let percentile = gas_percentile(&window, current_gwei).map(|p| (p, window.len()));
+
+    let sample = GasSample {
+        ts_ms: now,
+        gwei: current_gwei,
+    };
+    if let Some(next) = GAS_SAMPLES.push(&samples, sample) {
+        GAS_SAMPLES.store(services, &key, &next).await;
+    }
+    percentile
+}
+
 fn gas_price_level(gas_price_gwei: f64) -> &'static str {
     if gas_price_gwei < 3000.0 {
         "low"
@@ -53,9 +124,8 @@ fn estimate_cost(gas_price: U256, gas: u64, cro_price_usd: f64) -> (String, Stri
 
 /// Get current gas price and estimated costs
 pub async fn get_gas_price(services: &infra::Services, args: Value) -> Result<Value> {
-    let input: GetGasPriceArgs = serde_json::from_value(args).unwrap_or(GetGasPriceArgs {
-        simple_mode: false,
-    });
+    let input: GetGasPriceArgs =
+        serde_json::from_value(args).unwrap_or(GetGasPriceArgs { simple_mode: false });
 
     let rpc = services.rpc()?;
 
@@ -70,14 +140,19 @@ pub async fn get_gas_price(services: &infra::Services, args: Value) -> Result<Va
     // Classify gas level.
     let level = gas_price_level(gas_price_f64);
 
+    // 相对最近 24h 样本的百分位
+    let percentile = record_gas_sample(services, gas_price_f64).await;
+
     // Fetch CRO price for USD estimates.
     let cro_price_usd = get_cro_price(services).await.unwrap_or(0.1);
 
     // Estimate typical transaction costs.
-    let (transfer_gas, transfer_cro, transfer_usd) = estimate_cost(gas_price, GAS_TRANSFER, cro_price_usd);
+    let (transfer_gas, transfer_cro, transfer_usd) =
+        estimate_cost(gas_price, GAS_TRANSFER, cro_price_usd);
     let (erc20_gas, erc20_cro, erc20_usd) =
         estimate_cost(gas_price, GAS_ERC20_TRANSFER, cro_price_usd);
-    let (approve_gas, approve_cro, approve_usd) = estimate_cost(gas_price, GAS_APPROVE, cro_price_usd);
+    let (approve_gas, approve_cro, approve_usd) =
+        estimate_cost(gas_price, GAS_APPROVE, cro_price_usd);
     let (swap_gas, swap_cro, swap_usd) = estimate_cost(gas_price, GAS_SWAP, cro_price_usd);
     let (add_liq_gas, add_liq_cro, add_liq_usd) =
         estimate_cost(gas_price, GAS_ADD_LIQUIDITY, cro_price_usd);
@@ -87,19 +162,27 @@ pub async fn get_gas_price(services: &infra::Services, args: Value) -> Result<Va
     let recommendation = recommendation_for_level(level);
 
     if input.simple_mode {
+        let percentile_hint = percentile
+            .map(|(p, _)| format!(" | {} pct (24h)", ordinal(p)))
+            .unwrap_or_default();
         let text = format!(
-            "Gas: {:.0} gwei ({}) | Transfer: ~{} CRO (~${}) | Swap: ~{} CRO (~${})",
-            gas_price_f64, level, transfer_cro, transfer_usd, swap_cro, swap_usd
+            "Gas: {:.0} gwei ({}){} | Transfer: ~{} CRO (~${}) | Swap: ~{} CRO (~${})",
+            gas_price_f64, level, percentile_hint, transfer_cro, transfer_usd, swap_cro, swap_usd
         );
         return Ok(serde_json::json!({ "text": text }));
     }
 
     Ok(serde_json::json!({
-        "current_gwei": format!("{:.2}", gas_price_f64),
+        "current_gwei": services.precision.price(gas_price_f64),
         "level": level,
-        "base_fee_gwei": base_fee.map(|v| format!("{:.2}", v)),
-        "priority_fee_gwei": priority_fee.map(|v| format!("{:.2}", v)),
-        "cro_price_usd": format!("{:.4}", cro_price_usd),
+        "percentile_24h": percentile.map(|(p, samples)| serde_json::json!({
+            "percentile": p,
+            "samples": samples,
+            "text": percentile_text(p)
+        })),
+        "base_fee_gwei": base_fee.map(|v| services.precision.price(v)),
+        "priority_fee_gwei": priority_fee.map(|v| services.precision.price(v)),
+        "cro_price_usd": services.precision.price(cro_price_usd),
         "estimated_costs": {
             "cro_transfer": {
                 "gas": transfer_gas,
@@ -138,9 +221,7 @@ pub async fn get_gas_price(services: &infra::Services, args: Value) -> Result<Va
 }
 
 /// Best-effort EIP-1559 fee hints.
-async fn get_eip1559_fees(
-    rpc: &infra::rpc::RpcClient,
-) -> Result<(Option<f64>, Option<f64>)> {
+async fn get_eip1559_fees(rpc: &infra::rpc::RpcClient) -> Result<(Option<f64>, Option<f64>)> {
     // Cronos may not fully support EIP-1559.
     let priority_fee = rpc.eth_max_priority_fee_per_gas().await.ok();
     let priority_gwei = priority_fee.map(|v| {
@@ -155,8 +236,8 @@ async fn get_eip1559_fees(
 /// Resolve CRO price (best-effort).
 async fn get_cro_price(services: &infra::Services) -> Result<f64> {
     // Try KV cache first.
-    let key = "price:anchor:cro";
-    if let Ok(Some(text)) = services.kv.get(key).text().await {
+    let key = services.kv_key("price:anchor:cro");
+    if let Some(text) = services.kv_get_text(&key).await {
         if let Ok(price) = text.parse::<f64>() {
             return Ok(price);
         }
@@ -184,7 +265,10 @@ mod tests {
         assert!(recommendation_for_level("low").contains("low"));
         assert!(recommendation_for_level("medium").contains("moderate"));
         assert!(recommendation_for_level("high").contains("high"));
-        assert_eq!(recommendation_for_level("unknown"), "Unable to determine gas level.");
+        assert_eq!(
+            recommendation_for_level("unknown"),
+            "Unable to determine gas level."
+        );
     }
 
     #[test]
@@ -197,6 +281,53 @@ mod tests {
         assert_eq!(usd, "0.0000");
     }
 
+    fn samples(gwei: &[f64]) -> Vec<GasSample> {
+        gwei.iter()
+            .enumerate()
+            .map(|(i, g)| GasSample {
+                ts_ms: i as i64 * GAS_SAMPLES.min_interval_ms,
+                gwei: *g,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn percentile_ranks_against_known_distribution() {
+        // 1..=100 gwei 均匀分布
+        let dist: Vec<f64> = (1..=100).map(|v| v as f64).collect();
+        let window = samples(&dist);
+        assert_eq!(gas_percentile(&window, 0.5), Some(0));
+        assert_eq!(gas_percentile(&window, 25.5), Some(25));
+        assert_eq!(gas_percentile(&window, 50.5), Some(50));
+        assert_eq!(gas_percentile(&window, 90.5), Some(90));
+        assert_eq!(gas_percentile(&window, 1_000.0), Some(100));
+    }
+
+    #[test]
+    fn percentile_counts_ties_as_half() {
+        let window = samples(&[5000.0; 10]);
+        assert_eq!(gas_percentile(&window, 5000.0), Some(50));
+        assert_eq!(gas_percentile(&window, 4999.0), Some(0));
+    }
+
+    #[test]
+    fn percentile_requires_minimum_samples() {
+        let window = samples(&[1.0; GAS_PERCENTILE_MIN_SAMPLES - 1]);
+        assert_eq!(gas_percentile(&window, 1.0), None);
+    }
+
+    #[test]
+    fn percentile_text_uses_ordinals() {
+        assert_eq!(
+            percentile_text(73),
+            "Current gas is in the 73rd percentile of the last 24h"
+        );
+        assert_eq!(ordinal(1), "1st");
+        assert_eq!(ordinal(12), "12th");
+        assert_eq!(ordinal(22), "22nd");
+        assert_eq!(ordinal(100), "100th");
+    }
+
     #[test]
     fn args_deserialize_defaults() {
         let json = serde_json::json!({});
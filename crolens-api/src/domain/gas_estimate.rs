@@ -49,8 +49,8 @@ pub async fn estimate_gas(services: &infra::Services, args: Value) -> Result<Val
     let input: EstimateGasArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let from = types::parse_address(&input.from)?;
-    let to = types::parse_address(&input.to)?;
+    let from = types::parse_address_field("from", &input.from)?;
+    let to = types::parse_address_field("to", &input.to)?;
 
     let data = input.data.trim();
     validate_calldata_hex(data)?;
@@ -64,20 +64,7 @@ pub async fn estimate_gas(services: &infra::Services, args: Value) -> Result<Val
         }));
     };
 
-    let tx_obj = serde_json::json!({
-        "from": from.to_string(),
-        "to": to.to_string(),
-        "data": data,
-        "value": format!("0x{:x}", value_u256),
-    });
-
-    let result = rpc
-        .call("eth_estimateGas", serde_json::json!([tx_obj]))
-        .await?;
-    let gas_hex = result.as_str().ok_or_else(|| {
-        CroLensError::RpcError("eth_estimateGas result is not a string".to_string())
-    })?;
-    let gas: U256 = types::parse_u256_hex(gas_hex)?;
+    let gas = U256::from(rpc.eth_estimate_gas(from, to, data, value_u256).await?);
 
     let gas_price_wei = rpc.eth_gas_price().await.ok();
     let (estimated_cost_wei, estimated_cost_cro) = match gas_price_wei {
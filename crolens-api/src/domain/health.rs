@@ -27,7 +27,7 @@ pub async fn get_health_alerts(services: &infra::Services, args: Value) -> Resul
     let input: HealthAlertsArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let _ = types::parse_address(&input.address)?;
+    let _ = types::parse_address_field("address", &input.address)?;
 
     let mut alerts: Vec<Value> = Vec::new();
 
@@ -73,7 +73,10 @@ mod tests {
         assert!(approval_risk_alert(0).is_none());
         assert!(approval_risk_alert(19).is_none());
         let alert = approval_risk_alert(20).expect("should emit alert");
-        assert_eq!(alert.get("category").and_then(|v| v.as_str()), Some("approvals"));
+        assert_eq!(
+            alert.get("category").and_then(|v| v.as_str()),
+            Some("approvals")
+        );
         assert_eq!(alert.get("level").and_then(|v| v.as_str()), Some("warning"));
     }
 
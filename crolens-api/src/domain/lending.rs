@@ -1,13 +1,13 @@
 use serde::Deserialize;
 use serde_json::Value;
 
+use crate::domain::{defi, tectonic};
 use crate::error::{CroLensError, Result};
 use crate::infra;
 use crate::types;
 
-fn default_protocol() -> String {
-    "tectonic".to_string()
-}
+/// Lending protocols evaluated when `get_liquidation_risk` runs without a `protocol` filter.
+const SUPPORTED_LENDING_PROTOCOLS: &[&str] = &["tectonic"];
 
 fn classify_liquidation_risk(health_factor: Option<&str>) -> (&'static str, Option<&'static str>) {
     match health_factor {
@@ -30,80 +30,405 @@ struct LendingRatesArgs {
     simple_mode: bool,
 }
 
+/// 单个借贷协议及其 (按 asset 过滤后的) 市场
+#[derive(Debug, Clone)]
+struct ProtocolMarkets {
+    protocol: String,
+    markets: Vec<infra::config::LendingMarket>,
+}
+
+/// 跨协议按 asset 过滤；过滤后没有市场的协议不出现在结果中
+fn select_markets(all: Vec<ProtocolMarkets>, asset: Option<&str>) -> Vec<ProtocolMarkets> {
+    let asset = asset.map(|a| a.trim()).filter(|a| !a.is_empty());
+    all.into_iter()
+        .map(|mut entry| {
+            entry
+                .markets
+                .retain(|m| tectonic::symbol_matches_asset_filter(&m.underlying_symbol, asset));
+            entry
+        })
+        .filter(|entry| !entry.markets.is_empty())
+        .collect()
+}
+
+/// 所有协议的利率调用按顺序拼接在同一个 multicall 中
+fn rate_calls(selected: &[ProtocolMarkets]) -> Vec<infra::multicall::Call> {
+    selected
+        .iter()
+        .flat_map(|entry| tectonic::rate_calls(&entry.markets))
+        .collect()
+}
+
+fn rates_by_protocol(
+    selected: &[ProtocolMarkets],
+    results: &infra::multicall::CallResults,
+) -> Vec<Value> {
+    let mut index = 0usize;
+    selected
+        .iter()
+        .map(|entry| {
+            let markets: Vec<Value> = entry
+                .markets
+                .iter()
+                .map(|m| {
+                    let (supply_rate, borrow_rate) = tectonic::market_rates(results, index);
+                    index += 1;
+                    serde_json::json!({
+                        "asset": m.underlying_symbol,
+                        "underlying_address": m.underlying_address.to_string(),
+                        "ctoken_address": m.ctoken_address.to_string(),
+                        "supply_apy": supply_rate.and_then(defi::apy_percent_string),
+                        "borrow_apy": borrow_rate.and_then(defi::apy_percent_string),
+                    })
+                })
+                .collect();
+            serde_json::json!({ "protocol": entry.protocol, "markets": markets })
+        })
+        .collect()
+}
+
+/// 兼容旧输出的扁平 `rates` 列表：每个市场一项，带 protocol 字段
+fn flat_rates(by_protocol: &[Value]) -> Vec<Value> {
+    by_protocol
+        .iter()
+        .flat_map(|entry| {
+            let protocol = entry["protocol"].clone();
+            entry["markets"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |m| {
+                    serde_json::json!({
+                        "protocol": protocol,
+                        "asset": m["asset"],
+                        "supply_apy": m["supply_apy"],
+                        "borrow_apy": m["borrow_apy"],
+                    })
+                })
+        })
+        .collect()
+}
+
 pub async fn get_lending_rates(services: &infra::Services, args: Value) -> Result<Value> {
     let input: LendingRatesArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    // Only Tectonic is supported today.
-    let rates = vec![serde_json::json!({
-        "protocol": "tectonic",
-        "asset": input.asset,
-        "supply_apy": Value::Null,
-        "borrow_apy": Value::Null,
-    })];
+    let protocols = infra::config::list_lending_protocols_cached(
+        &services.db,
+        &services.kv,
+        &services.kv_prefix,
+    )
+    .await?;
+    let all = futures_util::future::try_join_all(protocols.into_iter().map(|protocol| async {
+        let markets = infra::config::list_lending_markets_cached(
+            &services.db,
+            &services.kv,
+            &services.kv_prefix,
+            &protocol,
+        )
+        .await?;
+        Ok::<_, CroLensError>(ProtocolMarkets { protocol, markets })
+    }))
+    .await?;
+    let selected = select_markets(all, input.asset.as_deref());
+
+    let calls = rate_calls(&selected);
+    let results = if calls.is_empty() {
+        Vec::new()
+    } else {
+        services.multicall()?.aggregate(calls).await?
+    };
+    let rates = rates_by_protocol(&selected, &results);
 
     if input.simple_mode {
-        return Ok(serde_json::json!({
-            "text": "Lending rates (tectonic only).",
-            "meta": services.meta(),
-        }));
+        let lines: Vec<String> = rates
+            .iter()
+            .flat_map(|entry| {
+                let protocol = entry["protocol"].as_str().unwrap_or("?").to_string();
+                entry["markets"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(move |m| {
+                        format!(
+                            "{protocol} {}: supply {} / borrow {}",
+                            m["asset"].as_str().unwrap_or("?"),
+                            m["supply_apy"].as_str().unwrap_or("?"),
+                            m["borrow_apy"].as_str().unwrap_or("?"),
+                        )
+                    })
+            })
+            .collect();
+        let text = if lines.is_empty() {
+            "No lending markets found.".to_string()
+        } else {
+            lines.join(" | ")
+        };
+        return Ok(serde_json::json!({ "text": text, "meta": services.meta() }));
     }
 
-    Ok(serde_json::json!({ "rates": rates, "meta": services.meta() }))
+    Ok(serde_json::json!({
+        "asset": input.asset,
+        "rates": flat_rates(&rates),
+        "protocols": rates,
+        "meta": services.meta(),
+    }))
 }
 
 #[derive(Debug, Deserialize)]
 struct LiquidationRiskArgs {
     address: String,
-    #[serde(default = "default_protocol")]
-    protocol: String,
+    #[serde(default)]
+    protocol: Option<String>,
     #[serde(default)]
     simple_mode: bool,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+struct ProtocolHealth {
+    protocol: String,
+    health_factor: Option<String>,
+    /// 价格不变、仅靠利息累积时距清算的天数；"stable" 表示供应收益不低于借款利息
+    days_to_liquidation: Option<String>,
+}
+
+/// "3.45%" 形式的 APY 转为连续复利年化利率 ln(1 + apy)
+fn continuous_rate(apy: &str) -> Option<f64> {
+    let apy = apy.trim().strip_suffix('%')?.trim().parse::<f64>().ok()? / 100.0;
+    let rate = apy.ln_1p();
+    rate.is_finite().then_some(rate)
+}
+
+/// 按 USD 价值加权的连续利率；返回 (总 USD 价值, 加权利率)，没有可用 APY 时利率为 None
+fn weighted_rate(items: Option<&Value>, usd_key: &str, apy_key: &str) -> (f64, Option<f64>) {
+    let mut total_usd = 0.0_f64;
+    let mut rated_usd = 0.0_f64;
+    let mut weighted = 0.0_f64;
+    for item in items.and_then(|v| v.as_array()).into_iter().flatten() {
+        let Some(usd) = item
+            .get(usd_key)
+            .and_then(|v| v.as_str())
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| v.is_finite() && *v > 0.0)
+        else {
+            continue;
+        };
+        total_usd += usd;
+        if let Some(rate) = item
+            .get(apy_key)
+            .and_then(|v| v.as_str())
+            .and_then(continuous_rate)
+        {
+            rated_usd += usd;
+            weighted += usd * rate;
+        }
+    }
+    let rate = (rated_usd > 0.0).then(|| weighted / rated_usd);
+    (total_usd, rate)
+}
+
+/// 价格不变时 HF(t) = HF0 * e^((r_supply - r_borrow) * t)，求 HF 降到 1.0 的天数；
+/// 借款利率不高于供应利率时 HF 不会下降，返回 None (stable)
+fn days_until_liquidation(health_factor: f64, supply_rate: f64, borrow_rate: f64) -> Option<f64> {
+    if health_factor <= 1.0 {
+        return Some(0.0);
+    }
+    let net_rate = borrow_rate - supply_rate;
+    if !net_rate.is_finite() || net_rate <= 0.0 {
+        return None;
+    }
+    Some(health_factor.ln() / net_rate * 365.0)
+}
+
+/// 只有抵押品的收益会抬高 HF：按 USD 价值 × 抵押率 (缺省为 1) 加权，非抵押供应不计入
+fn collateral_supply_rate(supplies: Option<&Value>) -> Option<f64> {
+    let mut weight = 0.0_f64;
+    let mut weighted = 0.0_f64;
+    for item in supplies.and_then(|v| v.as_array()).into_iter().flatten() {
+        if item.get("is_collateral").and_then(|v| v.as_bool()) == Some(false) {
+            continue;
+        }
+        let Some(usd) = item
+            .get("supply_balance_usd")
+            .and_then(|v| v.as_str())
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| v.is_finite() && *v > 0.0)
+        else {
+            continue;
+        };
+        let Some(rate) = item
+            .get("supply_apy")
+            .and_then(|v| v.as_str())
+            .and_then(continuous_rate)
+        else {
+            continue;
+        };
+        let collateral_factor = item
+            .get("collateral_factor")
+            .and_then(|v| v.as_f64())
+            .filter(|v| v.is_finite() && *v > 0.0)
+            .unwrap_or(1.0);
+        weight += usd * collateral_factor;
+        weighted += usd * collateral_factor * rate;
+    }
+    (weight > 0.0).then(|| weighted / weight)
+}
+
+/// 根据 get_defi_positions 中某协议的 health_factor 与 supplies/borrows 利率估算距清算的时间；
+/// 没有借款时为 "stable"，HF 或借款利率未知时为 None
+fn days_to_liquidation_from_positions(defi: &Value, protocol: &str) -> Option<String> {
+    let position = defi.get(protocol)?;
+    let (borrow_usd, borrow_rate) =
+        weighted_rate(position.get("borrows"), "borrow_balance_usd", "borrow_apy");
+    if borrow_usd <= 0.0 {
+        return Some("stable".to_string());
+    }
+    // HF 已按抵押率计算，不能用总供应 / 总借款代替
+    let health_factor =
+        health_factor_value(health_factor_from_positions(defi, protocol).as_deref())?;
+    if health_factor.is_infinite() {
+        return Some("stable".to_string());
+    }
+    let days = days_until_liquidation(
+        health_factor,
+        collateral_supply_rate(position.get("supplies")).unwrap_or(0.0),
+        borrow_rate?,
+    );
+    Some(match days {
+        Some(days) => format!("{days:.1}"),
+        None => "stable".to_string(),
+    })
+}
+
+/// "∞" 表示无借款，按正无穷处理；无法解析的值返回 None。
+fn health_factor_value(health_factor: Option<&str>) -> Option<f64> {
+    match health_factor {
+        Some("∞") => Some(f64::INFINITY),
+        Some(v) => v.parse::<f64>().ok(),
+        None => None,
+    }
+}
+
+/// Pick the protocol with the lowest health factor. Unknown values only win when
+/// no protocol reported a usable health factor.
+fn select_worst_health_factor(breakdown: &[ProtocolHealth]) -> Option<&ProtocolHealth> {
+    let mut worst: Option<(&ProtocolHealth, f64)> = None;
+    for entry in breakdown {
+        let Some(hf) = health_factor_value(entry.health_factor.as_deref()) else {
+            continue;
+        };
+        match worst {
+            Some((_, current)) if current <= hf => {}
+            _ => worst = Some((entry, hf)),
+        }
+    }
+    worst.map(|(entry, _)| entry).or_else(|| breakdown.first())
+}
+
+fn health_factor_from_positions(defi: &Value, protocol: &str) -> Option<String> {
+    defi.get(protocol)
+        .and_then(|v| v.get("health_factor"))
+        .and_then(|v| v.as_str())
+        .map(|v| v.to_string())
+}
+
+fn protocol_health_json(entry: &ProtocolHealth) -> Value {
+    let (risk_level, warning) = classify_liquidation_risk(entry.health_factor.as_deref());
+    serde_json::json!({
+        "protocol": entry.protocol,
+        "health_factor": entry.health_factor,
+        "days_to_liquidation": entry.days_to_liquidation,
+        "risk_level": risk_level,
+        "warning": warning,
+    })
+}
+
 pub async fn get_liquidation_risk(services: &infra::Services, args: Value) -> Result<Value> {
     let input: LiquidationRiskArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let _ = types::parse_address(&input.address)?;
+    let _ = types::parse_address_field("address", &input.address)?;
 
-    let protocol = input.protocol.trim().to_lowercase();
-    if protocol != "tectonic" {
-        return Err(CroLensError::invalid_params(
-            "Only protocol 'tectonic' is supported in this build".to_string(),
-        ));
-    }
+    // 未指定 protocol 时聚合所有支持的借贷协议
+    let protocols: Vec<String> = match input.protocol.as_deref() {
+        Some(p) => {
+            let protocol = p.trim().to_lowercase();
+            if !SUPPORTED_LENDING_PROTOCOLS.contains(&protocol.as_str()) {
+                return Err(CroLensError::invalid_params(format!(
+                    "Unsupported protocol '{protocol}'. Supported: {}",
+                    SUPPORTED_LENDING_PROTOCOLS.join(", ")
+                )));
+            }
+            vec![protocol]
+        }
+        None => SUPPORTED_LENDING_PROTOCOLS
+            .iter()
+            .map(|p| p.to_string())
+            .collect(),
+    };
+    let aggregate = input.protocol.is_none();
 
-    let mut health_factor: Option<String> = None;
-    if let Ok(defi) = crate::domain::defi::get_defi_positions(
+    let defi = crate::domain::defi::get_defi_positions(
         services,
         serde_json::json!({ "address": input.address, "simple_mode": false }),
     )
     .await
-    {
-        health_factor = defi
-            .get("tectonic")
-            .and_then(|v| v.get("health_factor"))
-            .and_then(|v| v.as_str())
-            .map(|v| v.to_string());
-    }
+    .ok();
+
+    let breakdown: Vec<ProtocolHealth> = protocols
+        .iter()
+        .map(|protocol| ProtocolHealth {
+            protocol: protocol.clone(),
+            health_factor: defi
+                .as_ref()
+                .and_then(|d| health_factor_from_positions(d, protocol)),
+            days_to_liquidation: defi
+                .as_ref()
+                .and_then(|d| days_to_liquidation_from_positions(d, protocol)),
+        })
+        .collect();
 
+    let worst = select_worst_health_factor(&breakdown);
+    let health_factor = worst.and_then(|w| w.health_factor.clone());
+    let worst_protocol = worst.map(|w| w.protocol.clone());
+    let days_to_liquidation = worst.and_then(|w| w.days_to_liquidation.clone());
     let (risk_level, warning) = classify_liquidation_risk(health_factor.as_deref());
 
     if input.simple_mode {
-        let hf_display = health_factor.clone().unwrap_or_else(|| "unknown".to_string());
+        let hf_display = health_factor
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string());
+        let protocol_suffix = match (&worst_protocol, aggregate) {
+            (Some(p), true) => format!(" ({p})"),
+            _ => String::new(),
+        };
+        let eta_suffix = match days_to_liquidation.as_deref() {
+            Some("stable") | None => String::new(),
+            Some(days) => format!(" | ~{days} days to liquidation at current rates"),
+        };
         return Ok(serde_json::json!({
-            "text": format!("Liquidation risk: {risk_level} | Health factor: {hf_display}"),
+            "text": format!("Liquidation risk: {risk_level} | Health factor: {hf_display}{protocol_suffix}{eta_suffix}"),
             "meta": services.meta(),
         }));
     }
 
+    let protocol_label = if aggregate {
+        "all".to_string()
+    } else {
+        protocols.first().cloned().unwrap_or_default()
+    };
+
     Ok(serde_json::json!({
         "address": input.address,
-        "protocol": protocol,
+        "protocol": protocol_label,
         "health_factor": health_factor,
+        "worst_protocol": worst_protocol,
+        "days_to_liquidation": days_to_liquidation,
         "risk_level": risk_level,
         "warning": warning,
+        "protocols": breakdown.iter().map(protocol_health_json).collect::<Vec<_>>(),
         "meta": services.meta(),
     }))
 }
@@ -113,8 +438,8 @@ mod tests {
     use super::*;
 
     #[test]
-    fn default_protocol_is_tectonic() {
-        assert_eq!(default_protocol(), "tectonic");
+    fn supported_protocols_include_tectonic() {
+        assert!(SUPPORTED_LENDING_PROTOCOLS.contains(&"tectonic"));
     }
 
     #[test]
@@ -148,7 +473,7 @@ mod tests {
 
         let json = serde_json::json!({ "address": "0x1234567890123456789012345678901234567890" });
         let args: LiquidationRiskArgs = serde_json::from_value(json).expect("args should parse");
-        assert_eq!(args.protocol, "tectonic");
+        assert!(args.protocol.is_none());
         assert!(!args.simple_mode);
     }
 
@@ -160,7 +485,251 @@ mod tests {
             "simple_mode": true
         });
         let args: LiquidationRiskArgs = serde_json::from_value(json).expect("args should parse");
-        assert_eq!(args.protocol, "tectonic");
+        assert_eq!(args.protocol.as_deref(), Some("tectonic"));
         assert!(args.simple_mode);
     }
+
+    fn market(ctoken: u8, symbol: &str) -> infra::config::LendingMarket {
+        infra::config::LendingMarket {
+            ctoken_address: alloy_primitives::Address::repeat_byte(ctoken),
+            underlying_address: alloy_primitives::Address::repeat_byte(ctoken + 0x80),
+            underlying_symbol: symbol.to_string(),
+            collateral_factor: None,
+        }
+    }
+
+    fn two_protocols() -> Vec<ProtocolMarkets> {
+        vec![
+            ProtocolMarkets {
+                protocol: "tectonic".to_string(),
+                markets: vec![market(0x01, "USDC"), market(0x02, "CRO")],
+            },
+            ProtocolMarkets {
+                protocol: "forklend".to_string(),
+                markets: vec![market(0x11, "usdc")],
+            },
+        ]
+    }
+
+    fn rate_result(
+        rate_per_block: u64,
+    ) -> std::result::Result<alloy_primitives::Bytes, CroLensError> {
+        use alloy_sol_types::SolCall;
+        Ok(
+            crate::abi::supplyRatePerBlockCall::abi_encode_returns(&(
+                alloy_primitives::U256::from(rate_per_block),
+            ))
+            .into(),
+        )
+    }
+
+    #[test]
+    fn asset_filter_applies_across_protocols() {
+        let selected = select_markets(two_protocols(), Some(" USDC "));
+        assert_eq!(selected.len(), 2);
+        assert!(selected.iter().all(|p| p.markets.len() == 1));
+
+        let selected = select_markets(two_protocols(), Some("cro"));
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].protocol, "tectonic");
+
+        assert!(select_markets(two_protocols(), Some("DAI")).is_empty());
+        assert_eq!(select_markets(two_protocols(), None).len(), 2);
+    }
+
+    #[test]
+    fn rates_are_grouped_per_protocol_in_call_order() {
+        let selected = select_markets(two_protocols(), None);
+        let calls = rate_calls(&selected);
+        assert_eq!(calls.len(), 6);
+        assert_eq!(
+            calls[4].target,
+            alloy_primitives::Address::repeat_byte(0x11)
+        );
+
+        // 第二个协议的 borrow 调用失败时只影响该字段
+        let results = vec![
+            rate_result(0),
+            rate_result(0),
+            rate_result(0),
+            rate_result(0),
+            rate_result(1_000_000_000),
+            Err(CroLensError::RpcError(
+                "Multicall inner call failed".to_string(),
+            )),
+        ];
+        let rates = rates_by_protocol(&selected, &results);
+        assert_eq!(rates.len(), 2);
+        assert_eq!(rates[0]["protocol"], "tectonic");
+        assert_eq!(rates[0]["markets"].as_array().map(Vec::len), Some(2));
+        assert_eq!(rates[0]["markets"][1]["asset"], "CRO");
+        assert_eq!(rates[0]["markets"][0]["supply_apy"], "0.00%");
+
+        let fork = &rates[1]["markets"][0];
+        assert_eq!(rates[1]["protocol"], "forklend");
+        assert_eq!(
+            fork["ctoken_address"],
+            alloy_primitives::Address::repeat_byte(0x11).to_string()
+        );
+        assert!(fork["supply_apy"].as_str().is_some_and(|v| v != "0.00%"));
+        assert!(fork["borrow_apy"].is_null());
+
+        // 旧的扁平 rates 字段保留：每个市场一项
+        let flat = flat_rates(&rates);
+        assert_eq!(flat.len(), 3);
+        assert_eq!(flat[1]["protocol"], "tectonic");
+        assert_eq!(flat[1]["asset"], "CRO");
+        assert_eq!(flat[2]["protocol"], "forklend");
+        assert_eq!(flat[2]["supply_apy"], fork["supply_apy"]);
+        assert!(flat[2]["borrow_apy"].is_null());
+    }
+
+    fn entry(protocol: &str, hf: Option<&str>) -> ProtocolHealth {
+        ProtocolHealth {
+            protocol: protocol.to_string(),
+            health_factor: hf.map(|v| v.to_string()),
+            days_to_liquidation: None,
+        }
+    }
+
+    #[test]
+    fn health_factor_value_handles_infinity_and_garbage() {
+        assert_eq!(health_factor_value(Some("∞")), Some(f64::INFINITY));
+        assert_eq!(health_factor_value(Some("1.25")), Some(1.25));
+        assert_eq!(health_factor_value(Some("abc")), None);
+        assert_eq!(health_factor_value(None), None);
+    }
+
+    #[test]
+    fn select_worst_health_factor_picks_lowest_across_protocols() {
+        let breakdown = vec![
+            entry("tectonic", Some("1.80")),
+            entry("other", Some("1.05")),
+        ];
+        let worst = select_worst_health_factor(&breakdown).expect("worst entry");
+        assert_eq!(worst.protocol, "other");
+        assert_eq!(worst.health_factor.as_deref(), Some("1.05"));
+
+        let breakdown = vec![entry("tectonic", Some("1.20")), entry("other", Some("∞"))];
+        let worst = select_worst_health_factor(&breakdown).expect("worst entry");
+        assert_eq!(worst.protocol, "tectonic");
+    }
+
+    #[test]
+    fn select_worst_health_factor_skips_unknown_values() {
+        let breakdown = vec![entry("tectonic", None), entry("other", Some("2.50"))];
+        let worst = select_worst_health_factor(&breakdown).expect("worst entry");
+        assert_eq!(worst.protocol, "other");
+
+        let breakdown = vec![entry("tectonic", None), entry("other", Some("abc"))];
+        let worst = select_worst_health_factor(&breakdown).expect("worst entry");
+        assert_eq!(worst.protocol, "tectonic");
+
+        assert!(select_worst_health_factor(&[]).is_none());
+    }
+
+    #[test]
+    fn health_factor_from_positions_reads_protocol_section() {
+        let defi = serde_json::json!({ "tectonic": { "health_factor": "1.42" } });
+        assert_eq!(
+            health_factor_from_positions(&defi, "tectonic").as_deref(),
+            Some("1.42")
+        );
+        assert!(health_factor_from_positions(&defi, "other").is_none());
+    }
+
+    #[test]
+    fn days_until_liquidation_from_interest_accrual() {
+        // 10% 借款 APY、无供应收益，HF 1.5 -> ln(1.5) / ln(1.1) 年
+        let borrow = continuous_rate("10.00%").expect("rate");
+        let days = days_until_liquidation(1.5, 0.0, borrow).expect("finite estimate");
+        let expected = 1.5_f64.ln() / 1.1_f64.ln() * 365.0;
+        assert!((days - expected).abs() < 1e-6);
+
+        // 供应收益部分抵消借款利息时时间更长
+        let supply = continuous_rate("4.00%").expect("rate");
+        let slower = days_until_liquidation(1.5, supply, borrow).expect("finite estimate");
+        assert!(slower > days);
+
+        assert_eq!(days_until_liquidation(0.95, 0.0, borrow), Some(0.0));
+    }
+
+    #[test]
+    fn days_until_liquidation_is_stable_when_yield_covers_interest() {
+        let rate = continuous_rate("5.00%").expect("rate");
+        assert_eq!(days_until_liquidation(1.2, rate, rate), None);
+        assert_eq!(days_until_liquidation(1.2, rate * 2.0, rate), None);
+    }
+
+    #[test]
+    fn days_to_liquidation_reads_weighted_position_rates() {
+        let defi = serde_json::json!({
+            "tectonic": {
+                "supplies": [
+                    { "supply_balance_usd": "150", "supply_apy": "0.00%" }
+                ],
+                "borrows": [
+                    { "borrow_balance_usd": "60", "borrow_apy": "10.00%" },
+                    { "borrow_balance_usd": "40", "borrow_apy": "10.00%" }
+                ],
+                "health_factor": "1.50"
+            }
+        });
+        let expected = 1.5_f64.ln() / 1.1_f64.ln() * 365.0;
+        assert_eq!(
+            days_to_liquidation_from_positions(&defi, "tectonic"),
+            Some(format!("{expected:.1}"))
+        );
+
+        let no_debt = serde_json::json!({ "tectonic": { "supplies": [], "borrows": [] } });
+        assert_eq!(
+            days_to_liquidation_from_positions(&no_debt, "tectonic").as_deref(),
+            Some("stable")
+        );
+
+        let unknown_rate = serde_json::json!({
+            "tectonic": { "borrows": [{ "borrow_balance_usd": "10", "borrow_apy": null }] }
+        });
+        assert!(days_to_liquidation_from_positions(&unknown_rate, "tectonic").is_none());
+        assert!(days_to_liquidation_from_positions(&defi, "other").is_none());
+    }
+
+    #[test]
+    fn days_to_liquidation_uses_collateral_weighted_health_factor() {
+        // 150 供应、抵押率 0.75、借款 100：真实 HF 为 1.125 而不是 1.5
+        let mut collateral = market(0x01, "USDC");
+        collateral.collateral_factor = Some("0.75".to_string());
+        let supplies = vec![
+            defi::tectonic_supply_entry(
+                &collateral,
+                alloy_primitives::U256::from(150_u64),
+                Some("150".to_string()),
+                Some("2.00%".to_string()),
+            ),
+            defi::tectonic_supply_entry(
+                &market(0x02, "CRO"),
+                alloy_primitives::U256::from(500_u64),
+                Some("500".to_string()),
+                Some("50.00%".to_string()),
+            ),
+        ];
+        let defi = serde_json::json!({
+            "tectonic": {
+                "supplies": supplies,
+                "borrows": [{ "borrow_balance_usd": "100", "borrow_apy": "10.00%" }],
+                "health_factor": "1.125"
+            }
+        });
+        let net = 1.1_f64.ln() - 1.02_f64.ln();
+        let expected = 1.125_f64.ln() / net * 365.0;
+        assert_eq!(
+            days_to_liquidation_from_positions(&defi, "tectonic"),
+            Some(format!("{expected:.1}"))
+        );
+
+        let missing_hf = serde_json::json!({
+            "tectonic": { "borrows": [{ "borrow_balance_usd": "10", "borrow_apy": "5.00%" }] }
+        });
+        assert!(days_to_liquidation_from_positions(&missing_hf, "tectonic").is_none());
+    }
 }
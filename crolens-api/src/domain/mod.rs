@@ -3,25 +3,28 @@ pub mod assets;
 pub mod block;
 pub mod calldata;
 pub mod contract_info;
-pub mod cronos_id;
 pub mod cro;
+pub mod cronos_id;
 pub mod defi;
-pub mod gas_estimate;
+pub mod farm_apy;
 pub mod gas;
+pub mod gas_estimate;
 pub mod health;
 pub mod lending;
+pub mod nft;
 pub mod pool_info;
+pub mod portfolio;
 pub mod price;
 pub mod protocol_stats;
 pub mod revoke_approval;
-pub mod swap_route;
+pub mod route;
 pub mod search;
 pub mod simulation;
 pub mod swap;
+pub mod swap_route;
 pub mod tectonic;
 pub mod token_approvals;
 pub mod token_info;
 pub mod transaction;
 pub mod vvs;
 pub mod whale_activity;
-pub mod portfolio;
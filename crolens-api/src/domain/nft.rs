@@ -0,0 +1,595 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::{sol_data, SolType};
+use serde_json::Value;
+use worker::D1Type;
+
+use crate::error::{CroLensError, Result};
+use crate::infra;
+use crate::infra::rpc::LogFilter;
+use crate::types;
+
+/// ERC-721 Transfer(address indexed from, address indexed to, uint256 indexed tokenId)
+/// 与 ERC20 Transfer 同一 topic0，区别是 tokenId 也是 indexed (共 4 个 topic)
+/// ERC-1155 TransferSingle(address indexed operator, address indexed from, address indexed to, uint256 id, uint256 value)
+const TRANSFER_SINGLE_TOPIC: &str =
+    "0xc3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62";
+/// ERC-1155 TransferBatch(address indexed operator, address indexed from, address indexed to, uint256[] ids, uint256[] values)
+const TRANSFER_BATCH_TOPIC: &str =
+    "0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb";
+
+/// 扫描最近约 1 天的区块 (Cronos 出块约 5.7s)
+const NFT_SCAN_WINDOW_BLOCKS: u64 = 15_000;
+const NFT_LOG_CHUNK_BLOCKS: u64 = 5_000;
+/// 输出的合集数量上限 (按持有数量排序后截断)
+const MAX_NFT_COLLECTIONS: usize = 50;
+/// 每个合集列出的 tokenId 数量上限
+const MAX_TOKEN_IDS_PER_COLLECTION: usize = 20;
+
+const WINDOW_NOTE: &str = "Windowed approximation: only NFTs transferred to or from the wallet in the scanned block range are visible. Older holdings are missing, and ERC-1155 amounts are net in-window transfers";
+
+type BatchValues = (
+    sol_data::Array<sol_data::Uint<256>>,
+    sol_data::Array<sol_data::Uint<256>>,
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NftStandard {
+    Erc721,
+    Erc1155,
+}
+
+impl NftStandard {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Erc721 => "erc721",
+            Self::Erc1155 => "erc1155",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NftTransfer {
+    pub collection: Address,
+    pub standard: NftStandard,
+    pub from: Address,
+    pub to: Address,
+    pub token_id: U256,
+    pub amount: U256,
+    pub block_number: u64,
+    pub log_index: u64,
+}
+
+/// 钱包在某个合集中 (窗口内推算) 仍持有的 NFT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NftCollectionHolding {
+    pub collection: Address,
+    pub standard: NftStandard,
+    /// 升序排列
+    pub token_ids: Vec<U256>,
+    /// ERC-721 等于 tokenId 数量；ERC-1155 为各 id 的数量之和
+    pub amount: U256,
+}
+
+fn hex_quantity(value: Option<&Value>) -> Option<u64> {
+    let hex = value?.as_str()?;
+    u64::from_str_radix(hex.trim().trim_start_matches("0x"), 16).ok()
+}
+
+/// 解码 ERC-721 Transfer / ERC-1155 TransferSingle / TransferBatch 日志 (Batch 展开为多条)；
+/// ERC20 Transfer (3 个 topic) 和格式异常的日志返回空列表
+pub fn decode_nft_transfers(log: &Value) -> Vec<NftTransfer> {
+    decode_nft_transfers_inner(log).unwrap_or_default()
+}
+
+fn decode_nft_transfers_inner(log: &Value) -> Option<Vec<NftTransfer>> {
+    let topics: Vec<&str> = log
+        .get("topics")?
+        .as_array()?
+        .iter()
+        .map(|t| t.as_str())
+        .collect::<Option<_>>()?;
+    if topics.len() != 4 {
+        return None;
+    }
+    let collection = types::parse_address(log.get("address")?.as_str()?).ok()?;
+    let block_number = hex_quantity(log.get("blockNumber"))?;
+    let log_index = hex_quantity(log.get("logIndex")).unwrap_or(0);
+    let data =
+        types::hex0x_to_bytes(log.get("data").and_then(|v| v.as_str()).unwrap_or("0x")).ok()?;
+    let transfer = |standard, from, to, token_id, amount| NftTransfer {
+        collection,
+        standard,
+        from,
+        to,
+        token_id,
+        amount,
+        block_number,
+        log_index,
+    };
+
+    let topic0 = topics[0];
+    if topic0.eq_ignore_ascii_case(types::TRANSFER_TOPIC) {
+        let from = types::topic_to_address(topics[1])?;
+        let to = types::topic_to_address(topics[2])?;
+        let token_id = types::parse_u256_hex(topics[3]).ok()?;
+        return Some(vec![transfer(
+            NftStandard::Erc721,
+            from,
+            to,
+            token_id,
+            U256::from(1u64),
+        )]);
+    }
+
+    let from = types::topic_to_address(topics[2])?;
+    let to = types::topic_to_address(topics[3])?;
+    if topic0.eq_ignore_ascii_case(TRANSFER_SINGLE_TOPIC) {
+        if data.len() != 64 {
+            return None;
+        }
+        let token_id = U256::from_be_slice(&data[..32]);
+        let amount = U256::from_be_slice(&data[32..]);
+        return Some(vec![transfer(
+            NftStandard::Erc1155,
+            from,
+            to,
+            token_id,
+            amount,
+        )]);
+    }
+    if topic0.eq_ignore_ascii_case(TRANSFER_BATCH_TOPIC) {
+        let (ids, values) = BatchValues::abi_decode_params(&data, true).ok()?;
+        if ids.len() != values.len() {
+            return None;
+        }
+        return Some(
+            ids.into_iter()
+                .zip(values)
+                .map(|(id, amount)| transfer(NftStandard::Erc1155, from, to, id, amount))
+                .collect(),
+        );
+    }
+    None
+}
+
+/// 按 (区块, logIndex) 顺序回放窗口内的转账，推算钱包仍持有的 NFT：
+/// ERC-721 以最后一次转账的接收方为准；ERC-1155 为窗口内转入减转出 (不足时按 0 计)
+pub fn aggregate_nft_holdings(
+    wallet: Address,
+    transfers: &[NftTransfer],
+) -> Vec<NftCollectionHolding> {
+    let mut ordered: Vec<&NftTransfer> = transfers
+        .iter()
+        .filter(|t| t.from == wallet || t.to == wallet)
+        .collect();
+    ordered.sort_by_key(|t| (t.block_number, t.log_index));
+
+    let mut erc721: BTreeMap<(Address, U256), bool> = BTreeMap::new();
+    let mut erc1155: BTreeMap<(Address, U256), (U256, U256)> = BTreeMap::new();
+    for t in ordered {
+        let key = (t.collection, t.token_id);
+        match t.standard {
+            NftStandard::Erc721 => {
+                erc721.insert(key, t.to == wallet);
+            }
+            NftStandard::Erc1155 => {
+                let (received, sent) = erc1155.entry(key).or_default();
+                if t.to == wallet {
+                    *received = received.saturating_add(t.amount);
+                }
+                if t.from == wallet {
+                    *sent = sent.saturating_add(t.amount);
+                }
+            }
+        }
+    }
+
+    let mut collections: BTreeMap<Address, NftCollectionHolding> = BTreeMap::new();
+    let mut add = |collection: Address, standard, token_id, amount: U256| {
+        let holding = collections
+            .entry(collection)
+            .or_insert_with(|| NftCollectionHolding {
+                collection,
+                standard,
+                token_ids: Vec::new(),
+                amount: U256::ZERO,
+            });
+        holding.token_ids.push(token_id);
+        holding.amount = holding.amount.saturating_add(amount);
+    };
+    for ((collection, token_id), held) in erc721 {
+        if held {
+            add(collection, NftStandard::Erc721, token_id, U256::from(1u64));
+        }
+    }
+    for ((collection, token_id), (received, sent)) in erc1155 {
+        let net = received.saturating_sub(sent);
+        if !net.is_zero() {
+            add(collection, NftStandard::Erc1155, token_id, net);
+        }
+    }
+
+    let mut holdings: Vec<NftCollectionHolding> = collections.into_values().collect();
+    holdings.sort_by(|a, b| {
+        b.token_ids
+            .len()
+            .cmp(&a.token_ids.len())
+            .then_with(|| a.collection.cmp(&b.collection))
+    });
+    holdings
+}
+
+/// contracts/tokens 表中的合集名称与符号
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct CollectionLabel {
+    name: Option<String>,
+    symbol: Option<String>,
+}
+
+/// contracts 表的 name 优先，tokens 表补充 symbol (以及缺失的 name)
+fn collection_labels_from_rows(rows: &[Value]) -> HashMap<Address, CollectionLabel> {
+    let mut labels: HashMap<Address, CollectionLabel> = HashMap::new();
+    for row in rows {
+        let Some(address) = row
+            .get("address")
+            .and_then(|v| v.as_str())
+            .and_then(|v| types::parse_address(v).ok())
+        else {
+            continue;
+        };
+        let text = |key: &str| {
+            row.get(key)
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        let label = labels.entry(address).or_default();
+        if label.name.is_none() {
+            label.name = text("name");
+        }
+        if label.symbol.is_none() {
+            label.symbol = text("symbol");
+        }
+    }
+    labels
+}
+
+async fn load_collection_labels(
+    db: &worker::D1Database,
+    collections: &[Address],
+) -> Result<HashMap<Address, CollectionLabel>> {
+    if collections.is_empty() {
+        return Ok(HashMap::new());
+    }
+    // contracts/tokens 中为 checksum 地址，按小写比较 (lower(address) 有表达式索引)
+    let addresses: Vec<String> = collections
+        .iter()
+        .map(|a| a.to_string().to_lowercase())
+        .collect();
+    let placeholders = (1..=addresses.len())
+        .map(|i| format!("?{i}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let args: Vec<D1Type> = addresses.iter().map(|a| D1Type::Text(a)).collect();
+    let statement = db
+        .prepare(format!(
+            "SELECT address, name, NULL AS symbol FROM contracts WHERE lower(address) IN ({placeholders}) \
+             UNION ALL \
+             SELECT address, name, symbol FROM tokens WHERE lower(address) IN ({placeholders})"
+        ))
+        .bind_refs(args.iter())
+        .map_err(|err| CroLensError::DbError(err.to_string()))?;
+    let result = infra::db::run("load_nft_collection_labels", || statement.all()).await?;
+    let rows: Vec<Value> = result
+        .results()
+        .map_err(|err| CroLensError::DbError(err.to_string()))?;
+    Ok(collection_labels_from_rows(&rows))
+}
+
+fn holdings_json(
+    holdings: &[NftCollectionHolding],
+    labels: &HashMap<Address, CollectionLabel>,
+) -> Vec<Value> {
+    holdings
+        .iter()
+        .map(|holding| {
+            let label = labels.get(&holding.collection).cloned().unwrap_or_default();
+            let token_ids: Vec<String> = holding
+                .token_ids
+                .iter()
+                .take(MAX_TOKEN_IDS_PER_COLLECTION)
+                .map(|id| id.to_string())
+                .collect();
+            serde_json::json!({
+                "contract_address": holding.collection.to_string(),
+                "standard": holding.standard.as_str(),
+                "name": label.name,
+                "symbol": label.symbol,
+                "token_count": holding.token_ids.len(),
+                "amount": holding.amount.to_string(),
+                "token_ids": token_ids,
+                "token_ids_truncated": holding.token_ids.len() > MAX_TOKEN_IDS_PER_COLLECTION,
+            })
+        })
+        .collect()
+}
+
+/// 同一条日志可能同时出现在转入和转出查询中 (自转账)，按 (交易哈希, logIndex) 去重
+fn dedupe_logs(logs: Vec<Value>) -> Vec<Value> {
+    let mut seen = HashSet::new();
+    logs.into_iter()
+        .filter(|log| {
+            let tx_hash = log
+                .get("transactionHash")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_lowercase();
+            seen.insert((tx_hash, hex_quantity(log.get("logIndex"))))
+        })
+        .collect()
+}
+
+/// 扫描最近窗口内与钱包相关的 ERC-721/1155 转账日志，列出推算仍持有的 NFT 合集
+pub async fn nft_holdings(services: &infra::Services, wallet: Address) -> Result<Value> {
+    let rpc = services.rpc()?;
+    let latest = rpc.latest_block_number_cached().await?;
+    let from_block = latest.saturating_sub(NFT_SCAN_WINDOW_BLOCKS - 1);
+
+    // topic 位置不同无法合并为一个过滤器：ERC-721 的 from/to 在 topic1/2，ERC-1155 在 topic2/3
+    let erc1155 = || {
+        LogFilter::new()
+            .event(TRANSFER_SINGLE_TOPIC)
+            .topic(0, TRANSFER_BATCH_TOPIC)
+    };
+    let filters = [
+        LogFilter::new()
+            .event(types::TRANSFER_TOPIC)
+            .topic_address(2, wallet),
+        LogFilter::new()
+            .event(types::TRANSFER_TOPIC)
+            .topic_address(1, wallet),
+        erc1155().topic_address(3, wallet),
+        erc1155().topic_address(2, wallet),
+    ];
+    // 4 个过滤器 × 各分段在同一个并发上限内并行获取
+    let logs = rpc
+        .eth_get_logs_chunked_many(&filters, from_block, latest, NFT_LOG_CHUNK_BLOCKS)
+        .await?;
+    let transfers: Vec<NftTransfer> = dedupe_logs(logs)
+        .iter()
+        .flat_map(decode_nft_transfers)
+        .collect();
+
+    let mut holdings = aggregate_nft_holdings(wallet, &transfers);
+    let collection_count = holdings.len();
+    holdings.truncate(MAX_NFT_COLLECTIONS);
+    let addresses: Vec<Address> = holdings.iter().map(|h| h.collection).collect();
+    let labels = match load_collection_labels(&services.db, &addresses).await {
+        Ok(labels) => labels,
+        Err(err) => {
+            worker::console_warn!("[WARN] NFT collection labels unavailable: {}", err);
+            HashMap::new()
+        }
+    };
+
+    Ok(serde_json::json!({
+        "collections": holdings_json(&holdings, &labels),
+        "collection_count": collection_count,
+        "collections_truncated": collection_count > MAX_NFT_COLLECTIONS,
+        "from_block": from_block,
+        "to_block": latest,
+        "approximate": true,
+        "note": WINDOW_NOTE,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_sol_types::SolValue;
+
+    fn addr(byte: u8) -> Address {
+        Address::from([byte; 20])
+    }
+
+    fn wallet() -> Address {
+        addr(0xaa)
+    }
+
+    fn word(value: U256) -> String {
+        format!("0x{}", hex::encode(value.to_be_bytes::<32>()))
+    }
+
+    fn erc721_log(collection: Address, from: Address, to: Address, id: u64, block: u64) -> Value {
+        serde_json::json!({
+            "address": collection.to_string(),
+            "topics": [
+                types::TRANSFER_TOPIC,
+                types::address_to_topic(from),
+                types::address_to_topic(to),
+                word(U256::from(id)),
+            ],
+            "data": "0x",
+            "blockNumber": format!("0x{block:x}"),
+            "logIndex": "0x0",
+            "transactionHash": format!("0x{}", format!("{block:02x}").repeat(32)),
+        })
+    }
+
+    fn erc1155_single_log(
+        collection: Address,
+        from: Address,
+        to: Address,
+        id: u64,
+        amount: u64,
+        block: u64,
+    ) -> Value {
+        let data = format!(
+            "{}{}",
+            word(U256::from(id)),
+            word(U256::from(amount)).trim_start_matches("0x")
+        );
+        serde_json::json!({
+            "address": collection.to_string(),
+            "topics": [
+                TRANSFER_SINGLE_TOPIC,
+                types::address_to_topic(addr(0x01)),
+                types::address_to_topic(from),
+                types::address_to_topic(to),
+            ],
+            "data": data,
+            "blockNumber": format!("0x{block:x}"),
+            "logIndex": "0x1",
+        })
+    }
+
+    fn decode_all(logs: &[Value]) -> Vec<NftTransfer> {
+        logs.iter().flat_map(decode_nft_transfers).collect()
+    }
+
+    #[test]
+    fn event_topics_match_signatures() {
+        let topic = |signature: &str| types::bytes_to_hex0x(alloy_primitives::keccak256(signature));
+        assert_eq!(
+            topic("Transfer(address,address,uint256)"),
+            types::TRANSFER_TOPIC
+        );
+        assert_eq!(
+            topic("TransferSingle(address,address,address,uint256,uint256)"),
+            TRANSFER_SINGLE_TOPIC
+        );
+        assert_eq!(
+            topic("TransferBatch(address,address,address,uint256[],uint256[])"),
+            TRANSFER_BATCH_TOPIC
+        );
+    }
+
+    #[test]
+    fn skips_erc20_transfer_logs() {
+        let mut log = erc721_log(addr(1), addr(2), wallet(), 7, 10);
+        log["topics"].as_array_mut().expect("topics").pop();
+        log["data"] = Value::String(word(U256::from(1000u64)));
+        assert!(decode_nft_transfers(&log).is_empty());
+    }
+
+    #[test]
+    fn decodes_erc1155_batch_transfer() {
+        let data = (
+            vec![U256::from(1u64), U256::from(2u64)],
+            vec![U256::from(5u64), U256::from(3u64)],
+        )
+            .abi_encode_params();
+        let log = serde_json::json!({
+            "address": addr(3).to_string(),
+            "topics": [
+                TRANSFER_BATCH_TOPIC,
+                types::address_to_topic(addr(0x01)),
+                types::address_to_topic(Address::ZERO),
+                types::address_to_topic(wallet()),
+            ],
+            "data": types::bytes_to_hex0x(data),
+            "blockNumber": "0x10",
+        });
+        let transfers = decode_nft_transfers(&log);
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].standard, NftStandard::Erc1155);
+        assert_eq!(transfers[0].to, wallet());
+        assert_eq!(transfers[1].token_id, U256::from(2u64));
+        assert_eq!(transfers[1].amount, U256::from(3u64));
+    }
+
+    #[test]
+    fn aggregates_held_erc721_tokens_by_last_transfer() {
+        let collection = addr(1);
+        let logs = vec![
+            erc721_log(collection, Address::ZERO, wallet(), 1, 100),
+            erc721_log(collection, Address::ZERO, wallet(), 2, 101),
+            // 转出后不再持有
+            erc721_log(collection, wallet(), addr(9), 2, 102),
+            // 窗口前已持有、窗口内转出又转回
+            erc721_log(collection, wallet(), addr(9), 3, 103),
+            erc721_log(collection, addr(9), wallet(), 3, 104),
+        ];
+        let holdings = aggregate_nft_holdings(wallet(), &decode_all(&logs));
+        assert_eq!(holdings.len(), 1);
+        assert_eq!(holdings[0].standard, NftStandard::Erc721);
+        assert_eq!(
+            holdings[0].token_ids,
+            vec![U256::from(1u64), U256::from(3u64)]
+        );
+        assert_eq!(holdings[0].amount, U256::from(2u64));
+    }
+
+    #[test]
+    fn aggregates_erc1155_net_amounts_and_sorts_collections() {
+        let (art, game) = (addr(1), addr(2));
+        let logs = vec![
+            erc1155_single_log(game, Address::ZERO, wallet(), 7, 10, 100),
+            erc1155_single_log(game, wallet(), addr(9), 7, 4, 101),
+            erc1155_single_log(game, Address::ZERO, wallet(), 8, 1, 102),
+            // 全部转出的 id 不计入
+            erc1155_single_log(game, Address::ZERO, wallet(), 9, 2, 103),
+            erc1155_single_log(game, wallet(), addr(9), 9, 2, 104),
+            erc721_log(art, Address::ZERO, wallet(), 5, 105),
+            // 与钱包无关的转账被忽略
+            erc721_log(art, addr(8), addr(9), 6, 106),
+        ];
+        let holdings = aggregate_nft_holdings(wallet(), &decode_all(&logs));
+        assert_eq!(holdings.len(), 2);
+        assert_eq!(holdings[0].collection, game);
+        assert_eq!(
+            holdings[0].token_ids,
+            vec![U256::from(7u64), U256::from(8u64)]
+        );
+        assert_eq!(holdings[0].amount, U256::from(7u64));
+        assert_eq!(holdings[1].collection, art);
+        assert_eq!(holdings[1].token_ids, vec![U256::from(5u64)]);
+    }
+
+    #[test]
+    fn self_transfer_logs_are_deduplicated() {
+        let log = erc721_log(addr(1), wallet(), wallet(), 1, 100);
+        let logs = dedupe_logs(vec![log.clone(), log]);
+        assert_eq!(logs.len(), 1);
+        let holdings = aggregate_nft_holdings(wallet(), &decode_all(&logs));
+        assert_eq!(holdings[0].token_ids, vec![U256::from(1u64)]);
+    }
+
+    #[test]
+    fn labels_prefer_contract_name_and_fill_token_symbol() {
+        let rows = vec![
+            serde_json::json!({ "address": addr(1).to_string(), "name": "Loaded Lions", "symbol": null }),
+            serde_json::json!({ "address": addr(1).to_string(), "name": "LION token", "symbol": "LION" }),
+            serde_json::json!({ "address": addr(2).to_string(), "name": " ", "symbol": "MAD" }),
+        ];
+        let labels = collection_labels_from_rows(&rows);
+        let lions = labels.get(&addr(1)).expect("labelled");
+        assert_eq!(lions.name.as_deref(), Some("Loaded Lions"));
+        assert_eq!(lions.symbol.as_deref(), Some("LION"));
+        let mad = labels.get(&addr(2)).expect("labelled");
+        assert_eq!(mad.name, None);
+        assert_eq!(mad.symbol.as_deref(), Some("MAD"));
+    }
+
+    #[test]
+    fn holdings_json_truncates_token_ids() {
+        let holding = NftCollectionHolding {
+            collection: addr(1),
+            standard: NftStandard::Erc721,
+            token_ids: (0..25u64).map(U256::from).collect(),
+            amount: U256::from(25u64),
+        };
+        let json = holdings_json(&[holding], &HashMap::new());
+        assert_eq!(json[0]["token_count"], 25);
+        assert_eq!(json[0]["amount"], "25");
+        assert_eq!(
+            json[0]["token_ids"].as_array().expect("ids").len(),
+            MAX_TOKEN_IDS_PER_COLLECTION
+        );
+        assert_eq!(json[0]["token_ids_truncated"], true);
+        assert!(json[0]["name"].is_null());
+    }
+}
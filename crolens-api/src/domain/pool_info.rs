@@ -4,6 +4,8 @@ use serde::Deserialize;
 use serde_json::Value;
 
 use crate::abi;
+use crate::domain::farm_apy;
+use crate::domain::route;
 use crate::error::{CroLensError, Result};
 use crate::infra;
 use crate::infra::multicall::Call;
@@ -14,14 +16,29 @@ struct GetPoolInfoArgs {
     pool: String,
     #[serde(default)]
     dex: Option<String>,
+    /// 可选：按此交易量 (最小单位) 估算输出与价格影响，需与 token_in 同时提供
+    #[serde(default)]
+    amount_in: Option<String>,
+    #[serde(default)]
+    token_in: Option<String>,
+    /// 可选：多个交易量的价格影响曲线 (共用一次 reserves 读取)，需与 token_in 同时提供
+    #[serde(default)]
+    amounts_in: Option<Vec<String>>,
     #[serde(default)]
     simple_mode: bool,
 }
 
+/// amounts_in 曲线的最大点数
+const MAX_QUOTE_CURVE_POINTS: usize = 20;
+
 fn normalize_pool_symbol(symbol: &str) -> String {
     let s = symbol.trim().to_uppercase();
     // Treat CRO and WCRO as equivalent for pair lookups.
-    if s == "CRO" { "WCRO".to_string() } else { s }
+    if s == "CRO" {
+        "WCRO".to_string()
+    } else {
+        s
+    }
 }
 
 fn pool_symbols_match(query0: &str, query1: &str, pool0: &str, pool1: &str) -> bool {
@@ -32,6 +49,72 @@ fn pool_symbols_match(query0: &str, query1: &str, pool0: &str, pool1: &str) -> b
     (p0 == q0 && p1 == q1) || (p0 == q1 && p1 == q0)
 }
 
+/// token_in 是否为池子的 token0 (支持符号或地址，CRO 视为 WCRO)
+fn token_in_is_token0(token_in: &str, pool: &infra::config::DexPool) -> Result<bool> {
+    let token_in = token_in.trim();
+    if token_in.starts_with("0x") {
+        let address = types::parse_address_field("token_in", token_in)?;
+        if address == pool.token0_address {
+            return Ok(true);
+        }
+        if address == pool.token1_address {
+            return Ok(false);
+        }
+    } else {
+        let symbol = normalize_pool_symbol(token_in);
+        if symbol == normalize_pool_symbol(&pool.token0_symbol) {
+            return Ok(true);
+        }
+        if symbol == normalize_pool_symbol(&pool.token1_symbol) {
+            return Ok(false);
+        }
+    }
+    Err(CroLensError::invalid_params(format!(
+        "token_in {token_in} is not in pool {}-{}",
+        pool.token0_symbol, pool.token1_symbol
+    )))
+}
+
+/// 单池交易的 (预期输出, 价格影响 bps)，价格影响相对于不含手续费与滑点的理想输出
+fn price_impact_for_amount(amount_in: U256, reserve_in: U256, reserve_out: U256) -> (U256, U256) {
+    let actual_out = route::compute_actual_out(amount_in, reserve_in, reserve_out);
+    let impact_bps = route::price_impact_bps(amount_in, &[(reserve_in, reserve_out)]);
+    (actual_out, impact_bps)
+}
+
+/// 同一组 reserves 下每个交易量的 (交易量, 预期输出, 价格影响 bps)
+fn quote_curve(
+    amounts_in: &[U256],
+    reserve_in: U256,
+    reserve_out: U256,
+) -> Vec<(U256, U256, U256)> {
+    amounts_in
+        .iter()
+        .map(|amount_in| {
+            let (amount_out, impact_bps) =
+                price_impact_for_amount(*amount_in, reserve_in, reserve_out);
+            (*amount_in, amount_out, impact_bps)
+        })
+        .collect()
+}
+
+fn parse_curve_amounts(amounts_in: &[String]) -> Result<Vec<U256>> {
+    if amounts_in.is_empty() {
+        return Err(CroLensError::invalid_params(
+            "amounts_in array must not be empty".to_string(),
+        ));
+    }
+    if amounts_in.len() > MAX_QUOTE_CURVE_POINTS {
+        return Err(CroLensError::invalid_params(format!(
+            "Maximum {MAX_QUOTE_CURVE_POINTS} amounts_in per request"
+        )));
+    }
+    amounts_in
+        .iter()
+        .map(|amount| types::parse_u256_dec(amount))
+        .collect()
+}
+
 /// Get detailed LP pool information
 pub async fn get_pool_info(services: &infra::Services, args: Value) -> Result<Value> {
     let input: GetPoolInfoArgs = serde_json::from_value(args)
@@ -45,7 +128,9 @@ pub async fn get_pool_info(services: &infra::Services, args: Value) -> Result<Va
     }
 
     let dex = input.dex.as_deref().unwrap_or("vvs");
-    let pools = infra::config::list_dex_pools_cached(&services.db, &services.kv, dex).await?;
+    let pools =
+        infra::config::list_dex_pools_cached(&services.db, &services.kv, &services.kv_prefix, dex)
+            .await?;
 
     // Resolve pool by LP address or "TOKEN0-TOKEN1" pair string.
     let pool = if pool_query.starts_with("0x") {
@@ -74,6 +159,32 @@ pub async fn get_pool_info(services: &infra::Services, args: Value) -> Result<Va
             })?
     };
 
+    let trade_input = match (input.amount_in.as_deref(), input.token_in.as_deref()) {
+        (Some(amount_in), Some(token_in)) => Some((
+            types::parse_u256_dec(amount_in)?,
+            token_in_is_token0(token_in, pool)?,
+        )),
+        (None, None) => None,
+        (None, Some(_)) if input.amounts_in.is_some() => None,
+        _ => {
+            return Err(CroLensError::invalid_params(
+                "amount_in and token_in must be provided together".to_string(),
+            ))
+        }
+    };
+    let curve_input = match (input.amounts_in.as_deref(), input.token_in.as_deref()) {
+        (Some(amounts_in), Some(token_in)) => Some((
+            parse_curve_amounts(amounts_in)?,
+            token_in_is_token0(token_in, pool)?,
+        )),
+        (None, _) => None,
+        (Some(_), None) => {
+            return Err(CroLensError::invalid_params(
+                "amounts_in and token_in must be provided together".to_string(),
+            ))
+        }
+    };
+
     // Fetch on-chain data.
     let multicall = services.multicall()?;
     let calls = vec![
@@ -108,7 +219,8 @@ pub async fn get_pool_info(services: &infra::Services, args: Value) -> Result<Va
         .unwrap_or(U256::ZERO);
 
     // Load token metadata.
-    let tokens = infra::token::list_tokens_cached(&services.db, &services.kv).await?;
+    let tokens =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix).await?;
     let token0 = tokens.iter().find(|t| t.address == pool.token0_address);
     let token1 = tokens.iter().find(|t| t.address == pool.token1_address);
 
@@ -124,14 +236,8 @@ pub async fn get_pool_info(services: &infra::Services, args: Value) -> Result<Va
 
     // Fetch prices.
     let price_map = infra::price::get_prices_usd_batch(services, &tokens).await?;
-    let price0 = price_map
-        .get(&pool.token0_address)
-        .copied()
-        .unwrap_or(0.0);
-    let price1 = price_map
-        .get(&pool.token1_address)
-        .copied()
-        .unwrap_or(0.0);
+    let price0 = price_map.get(&pool.token0_address).copied().unwrap_or(0.0);
+    let price1 = price_map.get(&pool.token1_address).copied().unwrap_or(0.0);
 
     let value0_usd = reserve0_f64 * price0;
     let value1_usd = reserve1_f64 * price1;
@@ -149,18 +255,83 @@ pub async fn get_pool_info(services: &infra::Services, args: Value) -> Result<Va
         "N/A".to_string()
     };
 
-    // Best-effort APY from MasterChef.
-    let apy = get_pool_apy(services, pool.pool_index).await.ok().flatten();
+    let trade = trade_input.map(|(amount_in, is_token0)| {
+        let (symbol_in, symbol_out, reserve_in, reserve_out) = if is_token0 {
+            (&pool.token0_symbol, &pool.token1_symbol, reserve0, reserve1)
+        } else {
+            (&pool.token1_symbol, &pool.token0_symbol, reserve1, reserve0)
+        };
+        let (amount_out, impact_bps) = price_impact_for_amount(amount_in, reserve_in, reserve_out);
+        serde_json::json!({
+            "token_in": symbol_in,
+            "token_out": symbol_out,
+            "amount_in": amount_in.to_string(),
+            "amount_out": amount_out.to_string(),
+            "price_impact_bps": u64::try_from(impact_bps).unwrap_or(10_000)
+        })
+    });
+
+    let curve = curve_input.map(|(amounts_in, is_token0)| {
+        let (reserve_in, reserve_out) = if is_token0 {
+            (reserve0, reserve1)
+        } else {
+            (reserve1, reserve0)
+        };
+        quote_curve(&amounts_in, reserve_in, reserve_out)
+            .into_iter()
+            .map(|(amount_in, amount_out, impact_bps)| {
+                serde_json::json!({
+                    "amount_in": amount_in.to_string(),
+                    "amount_out": amount_out.to_string(),
+                    "price_impact_bps": u64::try_from(impact_bps).unwrap_or(10_000)
+                })
+            })
+            .collect::<Vec<_>>()
+    });
+
+    // Best-effort APY from MasterChef emissions.
+    let vvs_price = tokens
+        .iter()
+        .find(|t| t.symbol.eq_ignore_ascii_case("VVS"))
+        .and_then(|t| price_map.get(&t.address).copied());
+    let lp_price = farm_apy::lp_price_usd(tvl_usd, total_lp_supply);
+    let apy = get_pool_apy(services, pool, vvs_price, lp_price)
+        .await
+        .ok()
+        .flatten();
 
     // Build response.
     if input.simple_mode {
         let apy_str = apy
             .map(|v| format!("{:.2}%", v))
             .unwrap_or_else(|| "N/A".to_string());
-        let text = format!(
+        let mut text = format!(
             "{}-{} Pool ({}) | TVL: ${:.2} | APY: {} | {}",
-            pool.token0_symbol, pool.token1_symbol, dex.to_uppercase(), tvl_usd, apy_str, price_ratio
+            pool.token0_symbol,
+            pool.token1_symbol,
+            dex.to_uppercase(),
+            tvl_usd,
+            apy_str,
+            price_ratio
         );
+        if let Some(bps) = trade
+            .as_ref()
+            .and_then(|t| t.get("price_impact_bps"))
+            .and_then(|v| v.as_u64())
+        {
+            text.push_str(&format!(" | Impact: {:.2}%", bps as f64 / 100.0));
+        }
+        if let Some(max_bps) = curve.as_ref().and_then(|points| {
+            points
+                .iter()
+                .filter_map(|p| p.get("price_impact_bps").and_then(|v| v.as_u64()))
+                .max()
+        }) {
+            text.push_str(&format!(
+                " | Curve max impact: {:.2}%",
+                max_bps as f64 / 100.0
+            ));
+        }
         return Ok(serde_json::json!({ "text": text }));
     }
 
@@ -172,104 +343,52 @@ pub async fn get_pool_info(services: &infra::Services, args: Value) -> Result<Va
             "symbol": pool.token0_symbol,
             "address": pool.token0_address.to_string(),
             "reserve": reserve0_formatted,
-            "price_usd": format!("{:.6}", price0),
-            "value_usd": format!("{:.2}", value0_usd)
+            "price_usd": services.precision.price(price0),
+            "value_usd": services.precision.usd(value0_usd)
         },
         "token1": {
             "symbol": pool.token1_symbol,
             "address": pool.token1_address.to_string(),
             "reserve": reserve1_formatted,
-            "price_usd": format!("{:.6}", price1),
-            "value_usd": format!("{:.2}", value1_usd)
+            "price_usd": services.precision.price(price1),
+            "value_usd": services.precision.usd(value1_usd)
         },
-        "tvl_usd": format!("{:.2}", tvl_usd),
+        "tvl_usd": services.precision.usd(tvl_usd),
         "fee_rate": "0.3%",
-        "apy": apy.map(|v| format!("{:.2}", v)),
+        "apy": apy.map(|v| services.precision.pct(v)),
         "price_ratio": price_ratio,
         "total_lp_supply": total_lp_formatted,
+        "trade": trade,
+        "curve": curve,
         "meta": services.meta()
     }))
 }
 
-/// Best-effort APY proxy based on MasterChef allocation weight.
-async fn get_pool_apy(services: &infra::Services, pool_index: Option<i64>) -> Result<Option<f64>> {
-    let Some(pid) = pool_index else {
+/// Best-effort emission-based farm APY; only farmable pools (with `pool_index`) have one.
+async fn get_pool_apy(
+    services: &infra::Services,
+    pool: &infra::config::DexPool,
+    vvs_price_usd: Option<f64>,
+    lp_price_usd: Option<f64>,
+) -> Result<Option<f64>> {
+    let Some(pid) = pool.pool_index else {
+        return Ok(None);
+    };
+    let (Some(vvs_price), Some(lp_price)) = (vvs_price_usd, lp_price_usd) else {
         return Ok(None);
     };
 
     // Get MasterChef contract address.
-    let masterchef =
-        match infra::config::get_protocol_contract(&services.db, "vvs", "masterchef").await {
-            Ok(addr) => addr,
-            Err(_) => return Ok(None),
-        };
-
-    let multicall = services.multicall()?;
-    let calls = vec![
-        // poolInfo(pid)
-        Call {
-            target: masterchef,
-            call_data: abi::poolInfoCall {
-                pid: alloy_primitives::U256::from(pid as u64),
-            }
-            .abi_encode()
-            .into(),
-        },
-        // totalAllocPoint
-        Call {
-            target: masterchef,
-            call_data: abi::totalAllocPointCall {}.abi_encode().into(),
-        },
-        // vvsPerBlock
-        Call {
-            target: masterchef,
-            call_data: abi::vvsPerBlockCall {}.abi_encode().into(),
-        },
-    ];
-
-    let results = multicall.aggregate(calls).await?;
-
-    // Decode poolInfo.
-    let alloc_point = results
-        .get(0)
-        .and_then(|r| r.as_ref().ok())
-        .and_then(|data| abi::poolInfoCall::abi_decode_returns(data, true).ok())
-        .map(|v| v.allocPoint)
-        .unwrap_or(U256::ZERO);
-
-    let total_alloc_point = results
-        .get(1)
-        .and_then(|r| r.as_ref().ok())
-        .and_then(|data| abi::totalAllocPointCall::abi_decode_returns(data, true).ok())
-        .map(|v| v._0)
-        .unwrap_or(U256::ZERO);
-
-    let vvs_per_block = results
-        .get(2)
-        .and_then(|r| r.as_ref().ok())
-        .and_then(|data| abi::vvsPerBlockCall::abi_decode_returns(data, true).ok())
-        .map(|v| U256::from(v._0))
-        .unwrap_or(U256::ZERO);
-
-    if total_alloc_point.is_zero() || vvs_per_block.is_zero() {
-        return Ok(None);
-    }
+    let masterchef = match services.protocol_contract("vvs", "masterchef").await {
+        Ok(addr) => addr,
+        Err(_) => return Ok(None),
+    };
 
-    // Simplified APY proxy (allocation weight only).
-    // Real APY needs TVL and VVS price; we return allocation percentage as a proxy.
-    let alloc_f64: f64 = types::format_units(&alloc_point, 0)
-        .parse()
-        .unwrap_or(0.0);
-    let total_f64: f64 = types::format_units(&total_alloc_point, 0)
-        .parse()
-        .unwrap_or(1.0);
-
-    if total_f64 > 0.0 {
-        // Return weight percentage (not real APY, but a relative indicator).
-        Ok(Some((alloc_f64 / total_f64) * 100.0))
-    } else {
-        Ok(None)
-    }
+    let emissions =
+        farm_apy::fetch_farm_emissions(services, masterchef, &[(pid, pool.lp_address)]).await?;
+    Ok(emissions
+        .get(&pid)
+        .and_then(|emission| farm_apy::farm_apy_percent(emission, vvs_price, lp_price)))
 }
 
 #[cfg(test)]
@@ -292,12 +411,102 @@ mod tests {
         assert!(!pool_symbols_match("VVS", "USDC", "WCRO", "USDC"));
     }
 
+    fn test_pool() -> infra::config::DexPool {
+        infra::config::DexPool {
+            pool_id: "vvs_wcro_usdc".to_string(),
+            lp_address: alloy_primitives::Address::from([1u8; 20]),
+            token0_address: alloy_primitives::Address::from([2u8; 20]),
+            token1_address: alloy_primitives::Address::from([3u8; 20]),
+            token0_symbol: "WCRO".to_string(),
+            token1_symbol: "USDC".to_string(),
+            pool_index: None,
+        }
+    }
+
+    #[test]
+    fn price_impact_small_trade_is_near_fee() {
+        // 1 个代币对 100 万储备 (18 位精度)
+        let reserve = U256::from(10u64).pow(U256::from(24u64));
+        let (out, bps) =
+            price_impact_for_amount(U256::from(1_000_000_000_000_000_000u128), reserve, reserve);
+        assert_eq!(out, U256::from(996_999_005_991_991_025u128));
+        // 0.3% 手续费 + 可忽略的滑点
+        assert_eq!(bps, U256::from(30u64));
+    }
+
+    #[test]
+    fn price_impact_grows_with_trade_size() {
+        let reserve_in = U256::from(1_000_000u64);
+        let reserve_out = U256::from(2_000_000u64);
+        let (_, small) = price_impact_for_amount(U256::from(1_000u64), reserve_in, reserve_out);
+        let (out, large) = price_impact_for_amount(U256::from(100_000u64), reserve_in, reserve_out);
+        assert!(large > small);
+        assert!(out < U256::from(200_000u64));
+        // 10% of reserves: ~9.3% impact
+        assert!(large > U256::from(900u64) && large < U256::from(1_000u64));
+    }
+
+    #[test]
+    fn price_impact_zero_for_empty_pool() {
+        let (out, bps) = price_impact_for_amount(U256::from(1_000u64), U256::ZERO, U256::ZERO);
+        assert_eq!(out, U256::ZERO);
+        assert_eq!(bps, U256::ZERO);
+    }
+
+    #[test]
+    fn quote_curve_impact_is_monotonic_for_increasing_amounts() {
+        let reserve_in = U256::from(1_000_000_000u64);
+        let reserve_out = U256::from(500_000_000u64);
+        // 交易量过小时输出取整误差会主导价格影响，从 1e5 开始
+        let amounts: Vec<U256> = [100_000u64, 1_000_000, 10_000_000, 100_000_000]
+            .into_iter()
+            .map(U256::from)
+            .collect();
+
+        let curve = quote_curve(&amounts, reserve_in, reserve_out);
+        assert_eq!(curve.len(), amounts.len());
+        for pair in curve.windows(2) {
+            let (_, out_a, impact_a) = pair[0];
+            let (_, out_b, impact_b) = pair[1];
+            assert!(out_b > out_a);
+            assert!(impact_b >= impact_a);
+        }
+        // 每个点与单次报价一致
+        let (amount_in, amount_out, impact) = curve[2];
+        assert_eq!(
+            price_impact_for_amount(amount_in, reserve_in, reserve_out),
+            (amount_out, impact)
+        );
+    }
+
+    #[test]
+    fn curve_amounts_are_capped() {
+        assert!(parse_curve_amounts(&[]).is_err());
+        let amounts = vec!["1000".to_string(); MAX_QUOTE_CURVE_POINTS + 1];
+        assert!(parse_curve_amounts(&amounts).is_err());
+        let parsed = parse_curve_amounts(&amounts[..MAX_QUOTE_CURVE_POINTS]).expect("within cap");
+        assert_eq!(parsed.len(), MAX_QUOTE_CURVE_POINTS);
+        assert!(parse_curve_amounts(&["abc".to_string()]).is_err());
+    }
+
+    #[test]
+    fn token_in_resolves_pool_side() {
+        let pool = test_pool();
+        assert!(token_in_is_token0("CRO", &pool).unwrap());
+        assert!(!token_in_is_token0("usdc", &pool).unwrap());
+        assert!(!token_in_is_token0(&pool.token1_address.to_string(), &pool).unwrap());
+        assert!(token_in_is_token0("VVS", &pool).is_err());
+    }
+
     #[test]
     fn args_deserialize_defaults() {
         let json = serde_json::json!({ "pool": "CRO-USDC" });
         let args: GetPoolInfoArgs = serde_json::from_value(json).expect("args should parse");
         assert_eq!(args.pool, "CRO-USDC");
         assert!(args.dex.is_none());
+        assert!(args.amount_in.is_none());
+        assert!(args.token_in.is_none());
+        assert!(args.amounts_in.is_none());
         assert!(!args.simple_mode);
     }
 
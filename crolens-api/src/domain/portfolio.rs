@@ -1,6 +1,7 @@
 use serde::Deserialize;
 use serde_json::Value;
 
+use crate::domain::assets;
 use crate::error::{CroLensError, Result};
 use crate::infra;
 use crate::types;
@@ -12,30 +13,289 @@ struct PortfolioArgs {
     simple_mode: bool,
 }
 
-fn validate_address(address: &str) -> Result<()> {
-    let _ = types::parse_address(address)?;
-    Ok(())
+/// 单一资产占比超过该值视为集中持仓
+const CONCENTRATION_THRESHOLD_PCT: f64 = 60.0;
+
+/// CRO 相关资产 (CRO/WCRO/CRO 交易对 LP/CRO 存款) 合计占比超过该值时提示相关性风险
+const CORRELATED_THRESHOLD_PCT: f64 = 70.0;
+
+/// 与 CRO 价格强相关的符号
+const CRO_CORRELATED_SYMBOLS: &[&str] = &["CRO", "WCRO"];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Allocation {
+    asset: String,
+    kind: &'static str,
+    value_usd: f64,
+    cro_correlated: bool,
+}
+
+fn is_cro_correlated(symbol: &str) -> bool {
+    CRO_CORRELATED_SYMBOLS
+        .iter()
+        .any(|s| s.eq_ignore_ascii_case(symbol.trim()))
+}
+
+fn usd_field(value: &Value, key: &str) -> f64 {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| v.is_finite() && *v > 0.0)
+        .unwrap_or(0.0)
+}
+
+/// 从钱包持仓与 DeFi 头寸构建资产分布 (借款不计入，只看持有的资产)
+fn collect_allocations(wallet: &[Value], defi: Option<&Value>) -> Vec<Allocation> {
+    let mut out = Vec::new();
+    for item in wallet {
+        let symbol = item.get("symbol").and_then(|v| v.as_str()).unwrap_or("?");
+        out.push(Allocation {
+            asset: symbol.to_string(),
+            kind: "wallet",
+            value_usd: usd_field(item, "value_usd"),
+            cro_correlated: is_cro_correlated(symbol),
+        });
+    }
+
+    let Some(defi) = defi else {
+        return retain_positive(out);
+    };
+    let positions = defi
+        .pointer("/vvs/positions")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten();
+    for position in positions {
+        let pool_name = position
+            .get("pool_name")
+            .and_then(|v| v.as_str())
+            .unwrap_or("?");
+        out.push(Allocation {
+            asset: pool_name.to_string(),
+            kind: "vvs_lp",
+            value_usd: usd_field(position, "liquidity_usd"),
+            cro_correlated: pool_name.split('-').any(is_cro_correlated),
+        });
+    }
+    let supplies = defi
+        .pointer("/tectonic/supplies")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten();
+    for supply in supplies {
+        let symbol = supply
+            .get("asset_symbol")
+            .and_then(|v| v.as_str())
+            .unwrap_or("?");
+        out.push(Allocation {
+            asset: symbol.to_string(),
+            kind: "tectonic_supply",
+            value_usd: usd_field(supply, "supply_balance_usd"),
+            cro_correlated: is_cro_correlated(symbol),
+        });
+    }
+
+    retain_positive(out)
+}
+
+fn retain_positive(mut allocations: Vec<Allocation>) -> Vec<Allocation> {
+    allocations.retain(|a| a.value_usd > 0.0);
+    allocations.sort_by(|a, b| {
+        b.value_usd
+            .partial_cmp(&a.value_usd)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    allocations
+}
+
+#[derive(Debug, PartialEq)]
+struct Analysis {
+    total_value_usd: f64,
+    /// (allocation, percent of total)
+    shares: Vec<(Allocation, f64)>,
+    cro_correlated_pct: f64,
+    diversification_score: u32,
+    insights: Vec<Value>,
+}
+
+/// 多样化分数 0-100：1 - HHI (Herfindahl 指数)，CRO 相关资产合并为一个资产计算
+fn diversification_score(allocations: &[Allocation], total: f64) -> u32 {
+    if total <= 0.0 {
+        return 0;
+    }
+    let correlated: f64 = allocations
+        .iter()
+        .filter(|a| a.cro_correlated)
+        .map(|a| a.value_usd)
+        .sum();
+    let hhi: f64 = allocations
+        .iter()
+        .filter(|a| !a.cro_correlated)
+        .map(|a| (a.value_usd / total).powi(2))
+        .sum::<f64>()
+        + (correlated / total).powi(2);
+    ((1.0 - hhi) * 100.0).round().clamp(0.0, 100.0) as u32
+}
+
+fn analyze(allocations: Vec<Allocation>, precision: types::Precision) -> Analysis {
+    let total: f64 = allocations.iter().map(|a| a.value_usd).sum();
+    let pct = |value: f64| {
+        if total > 0.0 {
+            value / total * 100.0
+        } else {
+            0.0
+        }
+    };
+
+    let cro_correlated_pct = pct(allocations
+        .iter()
+        .filter(|a| a.cro_correlated)
+        .map(|a| a.value_usd)
+        .sum());
+    let diversification_score = diversification_score(&allocations, total);
+
+    let mut insights = Vec::new();
+    for allocation in &allocations {
+        let share = pct(allocation.value_usd);
+        if share > CONCENTRATION_THRESHOLD_PCT {
+            insights.push(serde_json::json!({
+                "type": "concentration",
+                "severity": "warning",
+                "asset": allocation.asset,
+                "percent": precision.pct(share),
+                "message": format!(
+                    "{} is {share:.1}% of the portfolio (threshold {CONCENTRATION_THRESHOLD_PCT}%)",
+                    allocation.asset
+                ),
+            }));
+        }
+    }
+    if cro_correlated_pct > CORRELATED_THRESHOLD_PCT {
+        insights.push(serde_json::json!({
+            "type": "correlated_exposure",
+            "severity": "warning",
+            "group": "CRO",
+            "percent": precision.pct(cro_correlated_pct),
+            "message": format!(
+                "{cro_correlated_pct:.1}% of the portfolio moves with CRO (CRO, WCRO, CRO-paired LPs and deposits)"
+            ),
+        }));
+    }
+
+    let shares = allocations
+        .into_iter()
+        .map(|a| {
+            let share = pct(a.value_usd);
+            (a, share)
+        })
+        .collect();
+
+    Analysis {
+        total_value_usd: total,
+        shares,
+        cro_correlated_pct,
+        diversification_score,
+        insights,
+    }
 }
 
 pub async fn get_portfolio_analysis(services: &infra::Services, args: Value) -> Result<Value> {
     let input: PortfolioArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    validate_address(&input.address)?;
+    let address = types::parse_address_field("address", &input.address)?;
+
+    // 钱包估值与 DeFi 头寸并行获取；DeFi 失败时只分析钱包
+    // 接近请求截止时间时跳过 DeFi，只分析钱包并标记 truncated
+    let skip_defi = services.deadline_near();
+    let (wallet, defi) = futures_util::future::join(
+        assets::wallet_holdings(services, address, false, "latest"),
+        async {
+            if skip_defi {
+                return Err(CroLensError::RpcError("deadline near".to_string()));
+            }
+            crate::domain::defi::get_defi_positions(
+                services,
+                serde_json::json!({ "address": input.address, "simple_mode": false }),
+            )
+            .await
+        },
+    )
+    .await;
+    let wallet = wallet?;
+    let defi = defi.ok();
+    let truncated = is_truncated(skip_defi, defi.as_ref());
+
+    let analysis = analyze(
+        collect_allocations(&wallet.items, defi.as_ref()),
+        services.precision,
+    );
 
     if input.simple_mode {
+        let top = analysis
+            .shares
+            .first()
+            .map(|(a, share)| format!(" | Top: {} ({share:.1}%)", a.asset))
+            .unwrap_or_default();
+        let warnings = analysis
+            .insights
+            .iter()
+            .filter_map(|i| i.get("type").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>();
+        let warnings = if warnings.is_empty() {
+            String::new()
+        } else {
+            format!(" | Warnings: {}", warnings.join(", "))
+        };
+        let truncated = if truncated { " (truncated)" } else { "" };
+        let text = format!(
+            "Portfolio ${} | Diversification: {}/100{top}{warnings}{truncated}",
+            services.precision.usd(analysis.total_value_usd),
+            analysis.diversification_score
+        );
         return Ok(serde_json::json!({
-            "text": "Portfolio analysis is a placeholder in this build.",
+            "text": text,
             "meta": services.meta(),
         }));
     }
 
-    Ok(serde_json::json!({
+    let allocations: Vec<Value> = analysis
+        .shares
+        .iter()
+        .map(|(a, share)| {
+            serde_json::json!({
+                "asset": a.asset,
+                "kind": a.kind,
+                "value_usd": services.precision.usd(a.value_usd),
+                "percent": services.precision.pct(*share),
+                "cro_correlated": a.cro_correlated,
+            })
+        })
+        .collect();
+
+    let mut result = serde_json::json!({
         "address": input.address,
-        "diversification_score": 0,
-        "insights": [],
+        "total_value_usd": services.precision.usd(analysis.total_value_usd),
+        "allocations": allocations,
+        "cro_correlated_percent": services.precision.pct(analysis.cro_correlated_pct),
+        "diversification_score": analysis.diversification_score,
+        "insights": analysis.insights,
         "meta": services.meta(),
-    }))
+    });
+    if truncated {
+        result["truncated"] = serde_json::json!(true);
+    }
+    Ok(result)
+}
+
+/// DeFi 因截止时间被跳过，或 DeFi 结果本身被截断时，分析结果不完整
+fn is_truncated(skipped_defi: bool, defi: Option<&Value>) -> bool {
+    skipped_defi
+        || defi
+            .and_then(|d| d.get("truncated"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
 }
 
 #[cfg(test)]
@@ -44,7 +304,7 @@ mod tests {
 
     #[test]
     fn validate_address_rejects_invalid() {
-        let err = validate_address("0x123").unwrap_err();
+        let err = types::parse_address_field("address", "0x123").unwrap_err();
         assert!(matches!(err, CroLensError::InvalidAddress(_)));
     }
 
@@ -65,6 +325,119 @@ mod tests {
         assert!(args.simple_mode);
     }
 
+    fn alloc(asset: &str, value_usd: f64) -> Allocation {
+        Allocation {
+            asset: asset.to_string(),
+            kind: "wallet",
+            value_usd,
+            cro_correlated: is_cro_correlated(asset),
+        }
+    }
+
+    fn insight_types(analysis: &Analysis) -> Vec<&str> {
+        analysis
+            .insights
+            .iter()
+            .filter_map(|i| i.get("type").and_then(|v| v.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn flags_single_asset_above_threshold() {
+        let analysis = analyze(
+            vec![alloc("VVS", 700.0), alloc("USDC", 300.0)],
+            types::Precision::default(),
+        );
+        assert_eq!(insight_types(&analysis), vec!["concentration"]);
+        assert_eq!(analysis.insights[0]["asset"], "VVS");
+        assert_eq!(analysis.insights[0]["percent"], "70.00");
+        assert_eq!(analysis.shares[0].1, 70.0);
+    }
+
+    #[test]
+    fn balanced_portfolio_has_no_warnings() {
+        let analysis = analyze(
+            vec![
+                alloc("VVS", 250.0),
+                alloc("USDC", 250.0),
+                alloc("ETH", 250.0),
+                alloc("CRO", 250.0),
+            ],
+            types::Precision::default(),
+        );
+        assert!(analysis.insights.is_empty());
+        assert_eq!(analysis.diversification_score, 75);
+    }
+
+    #[test]
+    fn correlated_cro_assets_are_grouped() {
+        let lp = Allocation {
+            asset: "WCRO-USDC".to_string(),
+            kind: "vvs_lp",
+            value_usd: 300.0,
+            cro_correlated: true,
+        };
+        let analysis = analyze(
+            vec![
+                alloc("CRO", 300.0),
+                alloc("WCRO", 200.0),
+                lp,
+                alloc("USDC", 200.0),
+            ],
+            types::Precision::default(),
+        );
+        // 单项都不超过 60%，但 CRO 相关合计 80%
+        assert_eq!(insight_types(&analysis), vec!["correlated_exposure"]);
+        assert_eq!(analysis.cro_correlated_pct, 80.0);
+        // 按两组 (80/20) 计算：1 - (0.64 + 0.04) = 0.32
+        assert_eq!(analysis.diversification_score, 32);
+    }
+
+    #[test]
+    fn score_bounds() {
+        assert_eq!(
+            analyze(vec![alloc("VVS", 100.0)], types::Precision::default()).diversification_score,
+            0
+        );
+        assert_eq!(
+            analyze(Vec::new(), types::Precision::default()).diversification_score,
+            0
+        );
+        let many: Vec<Allocation> = (0..10).map(|i| alloc(&format!("T{i}"), 10.0)).collect();
+        assert_eq!(
+            analyze(many, types::Precision::default()).diversification_score,
+            90
+        );
+    }
+
+    #[test]
+    fn allocations_include_lp_and_supplies_and_skip_zero_values() {
+        let wallet = vec![
+            serde_json::json!({ "symbol": "CRO", "value_usd": "100.00" }),
+            serde_json::json!({ "symbol": "DUST", "value_usd": null }),
+        ];
+        let defi = serde_json::json!({
+            "vvs": { "positions": [{ "pool_name": "VVS-WCRO", "liquidity_usd": "50.00" }] },
+            "tectonic": { "supplies": [{ "asset_symbol": "USDC", "supply_balance_usd": "150.00" }] }
+        });
+        let allocations = collect_allocations(&wallet, Some(&defi));
+        assert_eq!(allocations.len(), 3);
+        assert_eq!(allocations[0].asset, "USDC");
+        assert_eq!(allocations[0].kind, "tectonic_supply");
+        assert!(allocations[2].cro_correlated);
+        assert_eq!(allocations[2].kind, "vvs_lp");
+    }
+
+    #[test]
+    fn truncation_follows_skipped_or_truncated_defi() {
+        let complete = serde_json::json!({ "vvs": {}, "tectonic": {} });
+        let partial = serde_json::json!({ "vvs": {}, "truncated": true });
+        assert!(!is_truncated(false, None));
+        assert!(!is_truncated(false, Some(&complete)));
+        assert!(is_truncated(false, Some(&partial)));
+        assert!(is_truncated(true, None));
+    }
+
     #[test]
     fn args_rejects_missing_address() {
         let json = serde_json::json!({});
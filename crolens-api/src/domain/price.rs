@@ -1,8 +1,10 @@
+use alloy_primitives::Address;
 use serde::Deserialize;
 use serde_json::Value;
 
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::types;
 
 #[derive(Debug, Deserialize)]
 struct GetTokenPriceArgs {
@@ -13,6 +15,21 @@ struct GetTokenPriceArgs {
 
 const MAX_TOKENS_PER_REQUEST: usize = 20;
 
+/// 价格同步每 5 分钟一次，超过两个周期未刷新视为过期
+const PRICE_STALE_AFTER_SECS: i64 = 600;
+
+/// 根据抓取时间计算 (age_secs, stale)；抓取时间未知 (未命中聚合缓存的回退路径) 时两者都为 None，
+/// 不能当作新鲜价格
+fn price_freshness(fetched_ms: Option<i64>, now_ms: i64) -> (Option<i64>, Option<bool>) {
+    match fetched_ms {
+        Some(fetched) => {
+            let age_secs = now_ms.saturating_sub(fetched).max(0) / 1000;
+            (Some(age_secs), Some(age_secs > PRICE_STALE_AFTER_SECS))
+        }
+        None => (None, None),
+    }
+}
+
 fn validate_token_price_request(tokens: &[String]) -> Result<()> {
     if tokens.is_empty() {
         return Err(CroLensError::invalid_params(
@@ -27,6 +44,13 @@ fn validate_token_price_request(tokens: &[String]) -> Result<()> {
     Ok(())
 }
 
+/// 有足够历史时附带 change_24h_pct，否则不输出该字段
+fn attach_change_24h(entry: &mut Value, change_pct: Option<String>) {
+    if let (Some(map), Some(pct)) = (entry.as_object_mut(), change_pct) {
+        map.insert("change_24h_pct".to_string(), Value::String(pct));
+    }
+}
+
 /// Get prices for multiple tokens
 pub async fn get_token_price(services: &infra::Services, args: Value) -> Result<Value> {
     let input: GetTokenPriceArgs = serde_json::from_value(args)
@@ -35,7 +59,8 @@ pub async fn get_token_price(services: &infra::Services, args: Value) -> Result<
     validate_token_price_request(&input.tokens)?;
 
     // Load token list.
-    let all_tokens = infra::token::list_tokens_cached(&services.db, &services.kv).await?;
+    let all_tokens =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix).await?;
 
     // Resolve requested tokens.
     let mut requested_tokens = Vec::new();
@@ -56,12 +81,28 @@ pub async fn get_token_price(services: &infra::Services, args: Value) -> Result<
     }
 
     // Fetch prices in batch.
-    let price_map = infra::price::get_prices_usd_batch(services, &requested_tokens).await?;
+    let batch = infra::price::get_prices_usd_batch_with_age(services, &requested_tokens).await?;
+    let now_ms = types::now_ms();
+    // 读取价格历史计算 24h 变化，同时写入本次价格
+    let token_prices: Vec<(Address, f64)> = requested_tokens
+        .iter()
+        .map(|token| {
+            let price = batch.prices.get(&token.address).copied().unwrap_or(0.0);
+            (token.address, price)
+        })
+        .collect();
+    let changes =
+        infra::price_history::record_and_change_24h(services, &token_prices, now_ms).await;
 
     // Build result.
     let mut prices = Vec::new();
-    for token in &requested_tokens {
-        let price_usd = price_map.get(&token.address).copied().unwrap_or(0.0);
+    for (token, change_pct) in requested_tokens.iter().zip(changes) {
+        let price_usd = batch.prices.get(&token.address).copied().unwrap_or(0.0);
+        let (age_secs, stale) = if token.is_stablecoin {
+            (None, Some(false))
+        } else {
+            price_freshness(batch.fetched_ms.get(&token.address).copied(), now_ms)
+        };
 
         // Determine source/confidence.
         let (source, confidence) = if token.is_stablecoin {
@@ -73,13 +114,23 @@ pub async fn get_token_price(services: &infra::Services, args: Value) -> Result<
             ("unknown", "low")
         };
 
-        prices.push(serde_json::json!({
+        let price_source = batch.sources.get(&token.address).map(|s| s.as_str());
+
+        let mut entry = serde_json::json!({
             "symbol": token.symbol,
             "address": token.address.to_string(),
-            "price_usd": format!("{:.8}", price_usd),
+            "price_usd": services.precision.price(price_usd),
             "source": source,
-            "confidence": confidence
-        }));
+            "price_source": price_source,
+            "confidence": confidence,
+            "age_secs": age_secs,
+            "stale": stale
+        });
+        attach_change_24h(
+            &mut entry,
+            change_pct.map(|pct| services.precision.pct(pct)),
+        );
+        prices.push(entry);
     }
 
     // Build response.
@@ -90,7 +141,18 @@ pub async fn get_token_price(services: &infra::Services, args: Value) -> Result<
                 let symbol = p.get("symbol").and_then(|v| v.as_str()).unwrap_or("?");
                 let price = p.get("price_usd").and_then(|v| v.as_str()).unwrap_or("0");
                 let price_f64: f64 = price.parse().unwrap_or(0.0);
-                format!("{}: ${:.6}", symbol, price_f64)
+                let stale = p.get("stale").and_then(|v| v.as_bool());
+                let change = p
+                    .get("change_24h_pct")
+                    .and_then(|v| v.as_str())
+                    .and_then(|v| v.parse::<f64>().ok())
+                    .map(|v| format!(" ({v:+.2}% 24h)"))
+                    .unwrap_or_default();
+                match stale {
+                    Some(true) => format!("{}: ${:.6}{} (stale)", symbol, price_f64, change),
+                    Some(false) => format!("{}: ${:.6}{}", symbol, price_f64, change),
+                    None => format!("{}: ${:.6}{} (age unknown)", symbol, price_f64, change),
+                }
             })
             .collect();
         let text = text_parts.join(" | ");
@@ -137,6 +199,47 @@ mod tests {
         validate_token_price_request(&tokens).expect("should allow max tokens");
     }
 
+    #[test]
+    fn freshness_reports_age_for_recent_price() {
+        let fetched = 1_700_000_000_000;
+        let (age, stale) = price_freshness(Some(fetched), fetched + 120_000);
+        assert_eq!(age, Some(120));
+        assert_eq!(stale, Some(false));
+    }
+
+    #[test]
+    fn freshness_marks_old_price_stale() {
+        let fetched = 1_700_000_000_000;
+        let now = fetched + (PRICE_STALE_AFTER_SECS + 1) * 1000;
+        let (age, stale) = price_freshness(Some(fetched), now);
+        assert_eq!(age, Some(PRICE_STALE_AFTER_SECS + 1));
+        assert_eq!(stale, Some(true));
+    }
+
+    #[test]
+    fn freshness_boundary_is_not_stale() {
+        let fetched = 1_700_000_000_000;
+        let now = fetched + PRICE_STALE_AFTER_SECS * 1000;
+        assert_eq!(
+            price_freshness(Some(fetched), now),
+            (Some(PRICE_STALE_AFTER_SECS), Some(false))
+        );
+    }
+
+    #[test]
+    fn freshness_clamps_future_timestamp() {
+        let now = 1_700_000_000_000;
+        assert_eq!(
+            price_freshness(Some(now + 5_000), now),
+            (Some(0), Some(false))
+        );
+    }
+
+    #[test]
+    fn freshness_unknown_without_timestamp() {
+        assert_eq!(price_freshness(None, 1_700_000_000_000), (None, None));
+    }
+
     #[test]
     fn args_deserialize_defaults() {
         let json = serde_json::json!({ "tokens": ["CRO"] });
@@ -151,4 +254,25 @@ mod tests {
         let args: GetTokenPriceArgs = serde_json::from_value(json).expect("args should parse");
         assert!(args.simple_mode);
     }
+
+    #[test]
+    fn change_is_omitted_without_history() {
+        let change = infra::price_history::change_24h_pct(&[], 0.105, 1_700_000_000_000);
+        let mut entry = serde_json::json!({ "symbol": "WCRO", "price_usd": "0.105" });
+        attach_change_24h(&mut entry, change.map(|pct| format!("{pct:.2}")));
+        assert!(entry.get("change_24h_pct").is_none());
+    }
+
+    #[test]
+    fn change_is_attached_with_history() {
+        let now_ms = 1_700_000_000_000;
+        let day_ago = infra::price_history::PriceSample {
+            ts_ms: now_ms - 24 * 3600 * 1000,
+            price: 0.1,
+        };
+        let change = infra::price_history::change_24h_pct(&[day_ago], 0.105, now_ms);
+        let mut entry = serde_json::json!({ "symbol": "WCRO", "price_usd": "0.105" });
+        attach_change_24h(&mut entry, change.map(|pct| format!("{pct:.2}")));
+        assert_eq!(entry["change_24h_pct"], "5.00");
+    }
 }
@@ -1,9 +1,26 @@
-use serde::Deserialize;
+use alloy_primitives::U256;
+use alloy_sol_types::SolCall;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use worker::d1::D1Type;
 
+use crate::abi;
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::multicall::Call;
+use crate::infra::samples::{Sample, SampleRing};
+use crate::types;
+
+/// 计算出的协议 TVL 缓存 5 分钟；缓存未命中时重新计算并追加一个 TVL 样本
+const TVL_CACHE_PREFIX: &str = "protocol_stats:tvl:";
+const TVL_CACHE_TTL_SECS: u64 = 300;
+/// 最近 24h 的 TVL 样本 (每个协议一个 KV 环形缓冲区)，每 5 分钟最多一个，容量 24h / 5min
+const TVL_SAMPLES_PREFIX: &str = "protocol_stats:tvl_samples:";
+const TVL_SAMPLES: SampleRing = SampleRing::new(24 * 3600 * 1000, 5 * 60 * 1000, 288);
+/// 样本跨度不足 1 小时时不计算变化率
+const TVL_CHANGE_MIN_SPAN_MS: i64 = 3600 * 1000;
+const SPARKLINE_POINTS: usize = 24;
+const SPARKLINE_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
 
 #[derive(Debug, Deserialize)]
 struct ProtocolStatsArgs {
@@ -17,10 +34,7 @@ pub async fn get_protocol_stats(services: &infra::Services, args: Value) -> Resu
     let input: ProtocolStatsArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let protocol = input
-        .protocol
-        .clone()
-        .unwrap_or_else(|| "all".to_string());
+    let protocol = input.protocol.clone().unwrap_or_else(|| "all".to_string());
 
     let (pool_count, market_count) = if protocol == "all" {
         (
@@ -34,21 +48,221 @@ pub async fn get_protocol_stats(services: &infra::Services, args: Value) -> Resu
         )
     };
 
+    // TVL 为尽力而为：RPC 或价格不可用时只返回计数；只统计 DEX 池子，没有池子的协议 (如纯借贷) 不计算 TVL
+    let tvl = match cached_tvl_with_samples(services, &protocol).await {
+        Ok(tvl) => tvl,
+        Err(err) => {
+            worker::console_warn!("[WARN] protocol TVL unavailable ({}): {}", protocol, err);
+            None
+        }
+    };
+    let change_pct = tvl
+        .as_ref()
+        .and_then(|(_, samples)| tvl_change_pct(samples));
+
     if input.simple_mode {
+        let tvl_text = match (&tvl, change_pct) {
+            (Some((tvl_usd, _)), Some(pct)) => format!(", tvl=${tvl_usd:.0} (24h {pct:+.2}%)"),
+            (Some((tvl_usd, _)), None) => format!(", tvl=${tvl_usd:.0}"),
+            (None, _) => String::new(),
+        };
         return Ok(serde_json::json!({
-            "text": format!("Protocol stats ({protocol}): pools={pool_count}, markets={market_count}"),
+            "text": format!("Protocol stats ({protocol}): pools={pool_count}, markets={market_count}{tvl_text}"),
             "meta": services.meta(),
         }));
     }
 
+    let (tvl_usd, tvl_trend) = match &tvl {
+        Some((tvl_usd, samples)) => (
+            Value::from(services.precision.usd(*tvl_usd)),
+            serde_json::json!({
+                "change_24h_pct": change_pct.map(|pct| services.precision.pct(pct)),
+                "sparkline": sparkline(samples),
+                "samples": samples.len(),
+                "since_ms": samples.first().map(|s| s.ts_ms),
+            }),
+        ),
+        None => (Value::Null, Value::Null),
+    };
+
     Ok(serde_json::json!({
         "protocol": protocol,
         "pool_count": pool_count,
         "market_count": market_count,
+        "tvl_usd": tvl_usd,
+        "tvl_scope": tvl.as_ref().map(|_| "dex_pools"),
+        "tvl_trend": tvl_trend,
         "meta": services.meta(),
     }))
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct TvlSample {
+    ts_ms: i64,
+    tvl_usd: f64,
+}
+
+impl Sample for TvlSample {
+    fn ts_ms(&self) -> i64 {
+        self.ts_ms
+    }
+
+    fn is_valid(&self) -> bool {
+        self.tvl_usd.is_finite() && self.tvl_usd >= 0.0
+    }
+}
+
+/// 窗口内最早样本到最新样本的 TVL 变化百分比
+fn tvl_change_pct(samples: &[TvlSample]) -> Option<f64> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    if last.ts_ms.saturating_sub(first.ts_ms) < TVL_CHANGE_MIN_SPAN_MS || first.tvl_usd <= 0.0 {
+        return None;
+    }
+    Some((last.tvl_usd - first.tvl_usd) / first.tvl_usd * 100.0)
+}
+
+/// 把样本均匀抽取为最多 SPARKLINE_POINTS 个点，按最小/最大值映射到 8 级方块字符
+fn sparkline(samples: &[TvlSample]) -> String {
+    let values: Vec<f64> = if samples.len() > SPARKLINE_POINTS {
+        (0..SPARKLINE_POINTS)
+            .filter_map(|i| samples.get(i * (samples.len() - 1) / (SPARKLINE_POINTS - 1)))
+            .map(|s| s.tvl_usd)
+            .collect()
+    } else {
+        samples.iter().map(|s| s.tvl_usd).collect()
+    };
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let top = SPARKLINE_CHARS.len() - 1;
+    values
+        .iter()
+        .map(|v| {
+            let level = if max > min {
+                ((v - min) / (max - min) * top as f64).round() as usize
+            } else {
+                top / 2
+            };
+            SPARKLINE_CHARS[level.min(top)]
+        })
+        .collect()
+}
+
+/// 池子两侧储备按 USD 价格折算；缺少代币信息或价格的一侧按 0 计
+fn pool_tvl_usd(amount0: f64, price0: Option<f64>, amount1: f64, price1: Option<f64>) -> f64 {
+    let side = |amount: f64, price: Option<f64>| match price {
+        Some(p) if p.is_finite() && p > 0.0 && amount.is_finite() => amount * p,
+        _ => 0.0,
+    };
+    side(amount0, price0) + side(amount1, price1)
+}
+
+/// 协议 (或 all 时的全部 DEX 协议) 所有池子的储备价值之和；没有池子时为 None
+async fn dex_tvl_usd(services: &infra::Services, protocol: &str) -> Result<Option<f64>> {
+    let protocols = if protocol == "all" {
+        infra::config::list_dex_protocols(&services.db).await?
+    } else {
+        vec![protocol.to_string()]
+    };
+    let mut pools = Vec::new();
+    for protocol_id in &protocols {
+        pools.extend(
+            infra::config::list_dex_pools_cached(
+                &services.db,
+                &services.kv,
+                &services.kv_prefix,
+                protocol_id,
+            )
+            .await?,
+        );
+    }
+    if pools.is_empty() {
+        return Ok(None);
+    }
+
+    let multicall = services.multicall()?;
+    let calls: Vec<Call> = pools
+        .iter()
+        .map(|pool| Call {
+            target: pool.lp_address,
+            call_data: abi::getReservesCall {}.abi_encode().into(),
+        })
+        .collect();
+    let reserves = multicall.aggregate(calls).await?;
+
+    let tokens: Vec<infra::token::Token> =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix)
+            .await?
+            .into_iter()
+            .filter(|t| {
+                pools
+                    .iter()
+                    .any(|p| p.token0_address == t.address || p.token1_address == t.address)
+            })
+            .collect();
+    let prices = infra::price::get_prices_usd_batch(services, &tokens).await?;
+
+    let mut total = 0.0;
+    for (pool, result) in pools.iter().zip(reserves) {
+        let Some(decoded) = result
+            .ok()
+            .and_then(|data| abi::getReservesCall::abi_decode_returns(&data, true).ok())
+        else {
+            continue;
+        };
+        let amount = |reserve: U256, address| {
+            let decimals = tokens
+                .iter()
+                .find(|t| t.address == address)
+                .map(|t| t.decimals)
+                .unwrap_or(18);
+            types::format_units(&reserve, decimals)
+                .parse::<f64>()
+                .unwrap_or(0.0)
+        };
+        total += pool_tvl_usd(
+            amount(U256::from(decoded.reserve0), pool.token0_address),
+            prices.get(&pool.token0_address).copied(),
+            amount(U256::from(decoded.reserve1), pool.token1_address),
+            prices.get(&pool.token1_address).copied(),
+        );
+    }
+    Ok(Some(total))
+}
+
+/// 读取缓存的 TVL 和窗口内样本；缓存未命中时重新计算，写入缓存并追加样本。
+/// 协议没有 DEX 池子时返回 None，也不写入样本
+async fn cached_tvl_with_samples(
+    services: &infra::Services,
+    protocol: &str,
+) -> Result<Option<(f64, Vec<TvlSample>)>> {
+    let cache_key = services.kv_key(&format!("{TVL_CACHE_PREFIX}{protocol}"));
+    let samples_key = services.kv_key(&format!("{TVL_SAMPLES_PREFIX}{protocol}"));
+    let now = types::now_ms();
+
+    let cached = services
+        .kv_get_text(&cache_key)
+        .await
+        .and_then(|raw| raw.parse::<f64>().ok())
+        .filter(|v| v.is_finite() && *v >= 0.0);
+    if let Some(tvl) = cached {
+        let samples: Vec<TvlSample> = TVL_SAMPLES.load(services, &samples_key).await;
+        return Ok(Some((tvl, TVL_SAMPLES.window(&samples, now))));
+    }
+
+    let Some(tvl) = dex_tvl_usd(services, protocol).await? else {
+        return Ok(None);
+    };
+    services
+        .kv_put_text(&cache_key, tvl.to_string(), TVL_CACHE_TTL_SECS)
+        .await;
+    let sample = TvlSample {
+        ts_ms: now,
+        tvl_usd: tvl,
+    };
+    let samples = TVL_SAMPLES.record(services, &samples_key, sample).await;
+    Ok(Some((tvl, TVL_SAMPLES.window(&samples, now))))
+}
+
 fn build_count_rows_sql(table: &str, protocol: Option<&str>) -> String {
     match protocol {
         Some(_) => format!("SELECT COUNT(*) AS cnt FROM {table} WHERE protocol_id = ?1"),
@@ -69,7 +283,7 @@ async fn count_rows(db: &worker::D1Database, table: &str, protocol: Option<&str>
         }
         None => statement,
     };
-    let result = infra::db::run("get_protocol_stats_count", statement.all()).await?;
+    let result = infra::db::run("get_protocol_stats_count", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -110,4 +324,84 @@ mod tests {
         assert_eq!(args.protocol.as_deref(), Some("vvs"));
         assert!(args.simple_mode);
     }
+
+    fn series(points: &[(i64, f64)]) -> Vec<TvlSample> {
+        points
+            .iter()
+            .map(|&(ts_ms, tvl_usd)| TvlSample { ts_ms, tvl_usd })
+            .collect()
+    }
+
+    const HOUR_MS: i64 = 3600 * 1000;
+
+    #[test]
+    fn tvl_change_compares_oldest_and_latest_samples() {
+        let samples = series(&[
+            (0, 1_000_000.0),
+            (6 * HOUR_MS, 900_000.0),
+            (24 * HOUR_MS, 1_100_000.0),
+        ]);
+        let pct = tvl_change_pct(&samples).expect("enough history");
+        assert!((pct - 10.0).abs() < 1e-9);
+
+        let drop = series(&[(0, 2_000_000.0), (12 * HOUR_MS, 1_500_000.0)]);
+        let pct = tvl_change_pct(&drop).expect("enough history");
+        assert!((pct + 25.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tvl_change_requires_span_and_positive_base() {
+        assert_eq!(tvl_change_pct(&[]), None);
+        assert_eq!(tvl_change_pct(&series(&[(0, 1.0)])), None);
+        assert_eq!(
+            tvl_change_pct(&series(&[(0, 1.0), (HOUR_MS - 1, 2.0)])),
+            None
+        );
+        assert_eq!(
+            tvl_change_pct(&series(&[(0, 0.0), (2 * HOUR_MS, 2.0)])),
+            None
+        );
+    }
+
+    #[test]
+    fn window_drops_samples_older_than_24h() {
+        let now = 30 * HOUR_MS;
+        let samples = series(&[(0, 1.0), (5 * HOUR_MS, 2.0), (20 * HOUR_MS, 3.0)]);
+        let window = TVL_SAMPLES.window(&samples, now);
+        assert_eq!(window, series(&[(20 * HOUR_MS, 3.0)]));
+        // 变化率只基于窗口内样本
+        let window = TVL_SAMPLES.window(&samples, 25 * HOUR_MS);
+        assert_eq!(window.len(), 2);
+        let pct = tvl_change_pct(&window).expect("enough history");
+        assert!((pct - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sparkline_scales_between_min_and_max() {
+        let samples = series(&[(0, 10.0), (1, 20.0), (2, 15.0), (3, 80.0)]);
+        assert_eq!(sparkline(&samples), "▁▂▂█");
+        assert_eq!(sparkline(&series(&[(0, 5.0), (1, 5.0)])), "▄▄");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn sparkline_downsamples_long_series() {
+        let samples: Vec<TvlSample> = (0..288)
+            .map(|i| TvlSample {
+                ts_ms: i,
+                tvl_usd: i as f64,
+            })
+            .collect();
+        let line = sparkline(&samples);
+        assert_eq!(line.chars().count(), SPARKLINE_POINTS);
+        assert!(line.starts_with('▁'));
+        assert!(line.ends_with('█'));
+    }
+
+    #[test]
+    fn pool_tvl_skips_unpriced_sides() {
+        assert_eq!(pool_tvl_usd(100.0, Some(2.0), 50.0, Some(4.0)), 400.0);
+        assert_eq!(pool_tvl_usd(100.0, Some(2.0), 50.0, None), 200.0);
+        assert_eq!(pool_tvl_usd(100.0, Some(f64::NAN), 50.0, Some(0.0)), 0.0);
+    }
 }
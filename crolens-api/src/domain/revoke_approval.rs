@@ -1,4 +1,4 @@
-use alloy_primitives::U256;
+use alloy_primitives::{Address, U256};
 use alloy_sol_types::SolCall;
 use serde::Deserialize;
 use serde_json::Value;
@@ -6,37 +6,88 @@ use serde_json::Value;
 use crate::abi;
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::tenderly::Simulator;
 use crate::types;
 
 #[derive(Debug, Deserialize)]
 struct RevokeApprovalArgs {
     token: String,
     spender: String,
+    /// 持有授权的钱包，模拟时作为 from
+    #[serde(default)]
+    owner: Option<String>,
+    #[serde(default)]
+    simulate: bool,
     #[serde(default)]
     simple_mode: bool,
 }
 
+/// 模拟 `approve(spender, 0)`：成功返回 true，模拟器不可用返回 false (不阻止返回 calldata)，
+/// 回滚时返回 SimulationFailed (部分代币禁止设为 0 或有其他授权限制)
+async fn simulate_revoke<S: Simulator + ?Sized>(
+    simulator: &S,
+    owner: Address,
+    token: Address,
+    data_hex: &str,
+) -> Result<bool> {
+    match simulator
+        .simulate(owner, token, data_hex, U256::ZERO, None)
+        .await
+    {
+        Ok(sim) if sim.success => Ok(true),
+        Ok(sim) => Err(CroLensError::SimulationFailed(format!(
+            "revoke approve(spender, 0) on {token} would revert: {}",
+            sim.error_message
+                .unwrap_or_else(|| "execution reverted".to_string())
+        ))),
+        Err(_) => Ok(false),
+    }
+}
+
 pub async fn construct_revoke_approval(services: &infra::Services, args: Value) -> Result<Value> {
     let input: RevokeApprovalArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
     let token_address = if input.token.trim().starts_with("0x") {
-        types::parse_address(&input.token)?
+        types::parse_address_field("token", &input.token)?
     } else {
-        let tokens = infra::token::list_tokens_cached(&services.db, &services.kv).await?;
+        let tokens =
+            infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix)
+                .await?;
         infra::token::resolve_token(&tokens, &input.token)?.address
     };
-    let spender = types::parse_address(&input.spender)?;
+    let spender = types::parse_address_field("spender", &input.spender)?;
+    let owner = input
+        .owner
+        .as_deref()
+        .map(|owner| types::parse_address_field("owner", owner))
+        .transpose()?;
 
     let calldata = abi::approveCall {
         spender,
         amount: U256::ZERO,
     }
     .abi_encode();
+    let data_hex = types::bytes_to_hex0x(&calldata);
+
+    let mut simulation_verified = false;
+    if input.simulate {
+        let owner = owner.ok_or_else(|| {
+            CroLensError::invalid_params("owner is required when simulate is true".to_string())
+        })?;
+        if let Ok(simulator) = services.tenderly() {
+            simulation_verified =
+                simulate_revoke(simulator, owner, token_address, &data_hex).await?;
+        }
+    }
 
     if input.simple_mode {
         return Ok(serde_json::json!({
-            "text": format!("Revoke approval calldata for token {}", token_address),
+            "text": if simulation_verified {
+                format!("Revoke approval calldata for token {} (simulation verified)", token_address)
+            } else {
+                format!("Revoke approval calldata for token {}", token_address)
+            },
             "meta": services.meta(),
         }));
     }
@@ -46,9 +97,10 @@ pub async fn construct_revoke_approval(services: &infra::Services, args: Value)
         "spender_address": spender.to_string(),
         "tx_data": {
             "to": token_address.to_string(),
-            "data": types::bytes_to_hex0x(&calldata),
+            "data": data_hex,
             "value": "0",
         },
+        "simulation_verified": simulation_verified,
         "meta": services.meta(),
     }))
 }
@@ -56,6 +108,91 @@ pub async fn construct_revoke_approval(services: &infra::Services, args: Value)
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::infra::tenderly::SimulationResult;
+    use futures_util::FutureExt;
+
+    /// 返回固定结果的模拟器；None 表示后端不可用
+    struct FixedSimulator(Option<SimulationResult>);
+
+    #[async_trait::async_trait(?Send)]
+    impl Simulator for FixedSimulator {
+        async fn simulate(
+            &self,
+            _from: Address,
+            _to: Address,
+            _input: &str,
+            _value: U256,
+            _gas: Option<u64>,
+        ) -> Result<SimulationResult> {
+            self.0
+                .clone()
+                .ok_or_else(|| CroLensError::RpcError("simulator offline".to_string()))
+        }
+    }
+
+    fn sim_result(success: bool, error_message: Option<&str>) -> SimulationResult {
+        SimulationResult {
+            success,
+            gas_used: Some(30_000),
+            output: "0x".to_string(),
+            logs: Vec::new(),
+            internal_calls: Vec::new(),
+            error_message: error_message.map(|m| m.to_string()),
+            calls_truncated: false,
+            basic_mode: true,
+        }
+    }
+
+    fn run_simulation(simulator: FixedSimulator) -> Result<bool> {
+        simulate_revoke(
+            &simulator,
+            Address::repeat_byte(0x0a),
+            Address::repeat_byte(0x0b),
+            "0x095ea7b3",
+        )
+        .now_or_never()
+        .expect("ready")
+    }
+
+    #[test]
+    fn revoke_simulation_success_is_verified() {
+        let verified = run_simulation(FixedSimulator(Some(sim_result(true, None))))
+            .expect("simulation should pass");
+        assert!(verified);
+    }
+
+    #[test]
+    fn revoke_simulation_revert_surfaces_error() {
+        let err = run_simulation(FixedSimulator(Some(sim_result(
+            false,
+            Some("execution reverted: approve from non-zero"),
+        ))))
+        .expect_err("revert should fail");
+        assert!(matches!(
+            err,
+            CroLensError::SimulationFailed(ref message)
+                if message.contains("would revert") && message.contains("approve from non-zero")
+        ));
+    }
+
+    #[test]
+    fn revoke_simulation_unavailable_is_unverified() {
+        let verified = run_simulation(FixedSimulator(None)).expect("should not fail");
+        assert!(!verified);
+    }
+
+    #[test]
+    fn args_deserialize_simulate_with_owner() {
+        let json = serde_json::json!({
+            "token": "VVS",
+            "spender": "0x145863Eb42Cf62847A6Ca784e6416C1682b1b2Ae",
+            "owner": "0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23",
+            "simulate": true
+        });
+        let args: RevokeApprovalArgs = serde_json::from_value(json).expect("args should parse");
+        assert!(args.simulate);
+        assert!(args.owner.is_some());
+    }
 
     #[test]
     fn args_deserialize_defaults() {
@@ -81,8 +218,7 @@ mod tests {
 
     #[test]
     fn revoke_approval_calldata_is_approve_zero() {
-        let spender =
-            types::parse_address("0x145863Eb42Cf62847A6Ca784e6416C1682b1b2Ae").unwrap();
+        let spender = types::parse_address("0x145863Eb42Cf62847A6Ca784e6416C1682b1b2Ae").unwrap();
         let calldata = abi::approveCall {
             spender,
             amount: U256::ZERO,
@@ -0,0 +1,368 @@
+use alloy_primitives::aliases::U1024;
+use alloy_primitives::ruint::UintTryFrom;
+use alloy_primitives::{Address, Bytes, U256};
+use alloy_sol_types::SolCall;
+
+use crate::abi;
+use crate::error::{CroLensError, Result};
+use crate::infra;
+use crate::types;
+
+/// VVS (Uniswap V2) 路由：直连或经 WCRO 桥接，报价取两者中输出更多的一条
+pub async fn build_path(
+    factory: Address,
+    router: Address,
+    amount_in: U256,
+    wcro: Option<Address>,
+    token_in: Option<Address>,
+    token_out: Address,
+    rpc: &infra::rpc::RpcClient,
+) -> Result<Vec<Address>> {
+    let mut direct = Vec::new();
+    match token_in {
+        Some(addr_in) => {
+            direct.push(addr_in);
+            direct.push(token_out);
+        }
+        None => {
+            let Some(wcro_addr) = wcro else {
+                return Err(CroLensError::TokenNotFound("WCRO".to_string()));
+            };
+            direct.push(wcro_addr);
+            direct.push(token_out);
+        }
+    }
+
+    let bridged = wcro
+        .filter(|wcro_addr| {
+            token_in.is_some() && direct[0] != *wcro_addr && token_out != *wcro_addr
+        })
+        .map(|wcro_addr| vec![direct[0], wcro_addr, token_out]);
+
+    if is_pair_available(factory, direct[0], direct[1], rpc).await? {
+        let Some(bridged) = bridged else {
+            return Ok(direct);
+        };
+        // 直连池可能很浅，同时报价两条路径并选择输出更多的一条
+        let (direct_out, bridged_out) = futures_util::future::join(
+            quote_out(router, amount_in, &direct, rpc),
+            quote_out(router, amount_in, &bridged, rpc),
+        )
+        .await;
+        return Ok(select_better_path(
+            direct,
+            direct_out.ok(),
+            bridged,
+            bridged_out.ok(),
+        ));
+    }
+
+    if let Some(bridged) = bridged {
+        return Ok(bridged);
+    }
+
+    if wcro.is_none() {
+        return Err(CroLensError::TokenNotFound("WCRO".to_string()));
+    }
+
+    Ok(direct)
+}
+
+/// 比较直连与 WCRO 桥接路径的报价；报价失败视为不可用，持平时保留直连
+fn select_better_path(
+    direct: Vec<Address>,
+    direct_out: Option<U256>,
+    bridged: Vec<Address>,
+    bridged_out: Option<U256>,
+) -> Vec<Address> {
+    match (direct_out, bridged_out) {
+        (Some(d), Some(b)) if b > d => bridged,
+        (None, Some(b)) if b > U256::ZERO => bridged,
+        _ => direct,
+    }
+}
+
+async fn get_pair(
+    factory: Address,
+    a: Address,
+    b: Address,
+    rpc: &infra::rpc::RpcClient,
+) -> Result<Address> {
+    let call = abi::getPairCall {
+        tokenA: a,
+        tokenB: b,
+    }
+    .abi_encode();
+    let data = rpc.eth_call(factory, Bytes::from(call)).await?;
+    let decoded = abi::getPairCall::abi_decode_returns(&data, true)
+        .map_err(|err| CroLensError::RpcError(format!("getPair decode failed: {err}")))?;
+    Ok(decoded.pair)
+}
+
+async fn is_pair_available(
+    factory: Address,
+    a: Address,
+    b: Address,
+    rpc: &infra::rpc::RpcClient,
+) -> Result<bool> {
+    Ok(get_pair(factory, a, b, rpc).await? != Address::ZERO)
+}
+
+/// 路由合约 getAmountsOut 报价 (路径最后一跳的输出)
+pub async fn quote_out(
+    router: Address,
+    amount_in: U256,
+    path: &[Address],
+    rpc: &infra::rpc::RpcClient,
+) -> Result<U256> {
+    let call = abi::getAmountsOutCall {
+        amountIn: amount_in,
+        path: path.to_vec(),
+    }
+    .abi_encode();
+    let data = rpc.eth_call(router, Bytes::from(call)).await?;
+    let decoded = abi::getAmountsOutCall::abi_decode_returns(&data, true)
+        .map_err(|err| CroLensError::RpcError(format!("getAmountsOut decode failed: {err}")))?;
+    Ok(decoded.amounts.last().cloned().unwrap_or(U256::ZERO))
+}
+
+/// 交易对的 (reserve_in, reserve_out)，按 token_in → token_out 方向排列
+pub async fn get_pair_reserves(
+    factory: Address,
+    token_in: Address,
+    token_out: Address,
+    rpc: &infra::rpc::RpcClient,
+) -> Result<(U256, U256)> {
+    let pair = get_pair(factory, token_in, token_out, rpc).await?;
+    if pair == Address::ZERO {
+        return Err(CroLensError::RpcError(
+            "Pair not found for price impact calculation".to_string(),
+        ));
+    }
+
+    let reserves_call = abi::getReservesCall {}.abi_encode();
+    let reserves_data = rpc.eth_call(pair, Bytes::from(reserves_call)).await?;
+    let reserves_ret = abi::getReservesCall::abi_decode_returns(&reserves_data, true)
+        .map_err(|err| CroLensError::RpcError(format!("getReserves decode failed: {err}")))?;
+
+    Ok(order_reserves(
+        token_in,
+        token_out,
+        U256::from(reserves_ret.reserve0),
+        U256::from(reserves_ret.reserve1),
+    ))
+}
+
+/// 路径上每一跳的 (reserve_in, reserve_out)
+pub async fn get_path_reserves(
+    factory: Address,
+    path: &[Address],
+    rpc: &infra::rpc::RpcClient,
+) -> Result<Vec<(U256, U256)>> {
+    let mut reserves = Vec::with_capacity(path.len().saturating_sub(1));
+    for hop in path.windows(2) {
+        reserves.push(get_pair_reserves(factory, hop[0], hop[1], rpc).await?);
+    }
+    Ok(reserves)
+}
+
+/// pair 的 token0 是地址较小的一侧
+fn order_reserves(
+    token_in: Address,
+    token_out: Address,
+    reserve0: U256,
+    reserve1: U256,
+) -> (U256, U256) {
+    if token_in.as_slice() < token_out.as_slice() {
+        (reserve0, reserve1)
+    } else {
+        (reserve1, reserve0)
+    }
+}
+
+/// 按储备比例计算的理想输出 (不含手续费与滑点)
+pub fn compute_ideal_out(amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
+    if reserve_in.is_zero() {
+        return U256::ZERO;
+    }
+    types::mul_div(amount_in, reserve_out, reserve_in).unwrap_or(U256::MAX)
+}
+
+/// 恒定乘积公式的实际输出 (0.3% 手续费)
+pub fn compute_actual_out(amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        return U256::ZERO;
+    }
+
+    // amount_in * 997 * reserve_out 最多约 522 位，全部在 1024 位中间值中计算，避免饱和
+    let amount_in_with_fee = U1024::from(amount_in) * U1024::from(997u64);
+    let numerator = amount_in_with_fee * U1024::from(reserve_out);
+    let denominator = U1024::from(reserve_in) * U1024::from(1000u64) + amount_in_with_fee;
+    // 输出不超过 reserve_out，必然能放回 U256
+    U256::uint_try_from(numerator / denominator).unwrap_or(U256::ZERO)
+}
+
+/// 沿路径逐跳累计理想输出与实际输出，价格影响为两者差值相对理想输出的 bps
+pub fn price_impact_bps(amount_in: U256, hops: &[(U256, U256)]) -> U256 {
+    let mut ideal_amount = amount_in;
+    let mut actual_amount = amount_in;
+    for (reserve_in, reserve_out) in hops {
+        ideal_amount = compute_ideal_out(ideal_amount, *reserve_in, *reserve_out);
+        actual_amount = compute_actual_out(actual_amount, *reserve_in, *reserve_out);
+    }
+
+    if ideal_amount.is_zero() {
+        return U256::ZERO;
+    }
+
+    let diff = ideal_amount.saturating_sub(actual_amount);
+    diff.saturating_mul(U256::from(10_000u64)) / ideal_amount
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_paths() -> (Vec<Address>, Vec<Address>) {
+        let token_in =
+            types::parse_address("0x3333333333333333333333333333333333333333").expect("token_in");
+        let token_out =
+            types::parse_address("0x4444444444444444444444444444444444444444").expect("token_out");
+        let wcro =
+            types::parse_address("0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23").expect("wcro");
+        (vec![token_in, token_out], vec![token_in, wcro, token_out])
+    }
+
+    #[test]
+    fn prefers_bridged_path_when_it_quotes_more() {
+        let (direct, bridged) = sample_paths();
+        let selected = select_better_path(
+            direct,
+            Some(U256::from(900u64)),
+            bridged.clone(),
+            Some(U256::from(1_500u64)),
+        );
+        assert_eq!(selected, bridged);
+    }
+
+    #[test]
+    fn keeps_direct_path_when_it_quotes_more_or_equal() {
+        let (direct, bridged) = sample_paths();
+        let selected = select_better_path(
+            direct.clone(),
+            Some(U256::from(1_500u64)),
+            bridged.clone(),
+            Some(U256::from(900u64)),
+        );
+        assert_eq!(selected, direct);
+
+        let tie = select_better_path(
+            direct.clone(),
+            Some(U256::from(1_000u64)),
+            bridged,
+            Some(U256::from(1_000u64)),
+        );
+        assert_eq!(tie, direct);
+    }
+
+    #[test]
+    fn falls_back_when_a_quote_fails() {
+        let (direct, bridged) = sample_paths();
+        let selected = select_better_path(
+            direct.clone(),
+            Some(U256::from(1u64)),
+            bridged.clone(),
+            None,
+        );
+        assert_eq!(selected, direct);
+
+        let selected = select_better_path(
+            direct.clone(),
+            None,
+            bridged.clone(),
+            Some(U256::from(10u64)),
+        );
+        assert_eq!(selected, bridged);
+
+        let selected = select_better_path(direct.clone(), None, bridged, None);
+        assert_eq!(selected, direct);
+    }
+
+    #[test]
+    fn reserves_follow_trade_direction() {
+        let (direct, _) = sample_paths();
+        let (low, high) = (direct[0], direct[1]);
+        let (r0, r1) = (U256::from(1u64), U256::from(2u64));
+        assert_eq!(order_reserves(low, high, r0, r1), (r0, r1));
+        assert_eq!(order_reserves(high, low, r0, r1), (r1, r0));
+    }
+
+    #[test]
+    fn calculates_price_impact_bps_sane_ranges() {
+        let hop = (U256::from(1_000_000u64), U256::from(1_000_000u64));
+
+        let small = price_impact_bps(U256::from(1_000u64), &[hop]);
+        assert!(
+            small < U256::from(100u64),
+            "expected <1% for small trade, got {small}"
+        );
+
+        let large = price_impact_bps(U256::from(200_000u64), &[hop]);
+        assert!(
+            large > U256::from(1000u64),
+            "expected >10% for large trade, got {large}"
+        );
+    }
+
+    #[test]
+    fn price_impact_compounds_across_hops() {
+        let hop = (U256::from(1_000_000u64), U256::from(1_000_000u64));
+        let amount_in = U256::from(10_000u64);
+        let single = price_impact_bps(amount_in, &[hop]);
+        let double = price_impact_bps(amount_in, &[hop, hop]);
+        assert!(double > single, "expected {double} > {single}");
+    }
+
+    #[test]
+    fn price_impact_is_zero_without_trade_or_hops() {
+        let hop = (U256::from(1_000_000u64), U256::from(1_000_000u64));
+        assert_eq!(price_impact_bps(U256::ZERO, &[hop]), U256::ZERO);
+        assert_eq!(price_impact_bps(U256::from(1_000u64), &[]), U256::ZERO);
+        assert_eq!(
+            price_impact_bps(U256::from(1_000u64), &[(U256::ZERO, U256::ZERO)]),
+            U256::ZERO
+        );
+    }
+
+    #[test]
+    fn actual_out_does_not_saturate_for_huge_inputs() {
+        // amount_in * 997 超出 U256，朴素实现会饱和成错误报价
+        let amount_in = U256::MAX / U256::from(2u64);
+        let reserve = U256::from(1u64) << 200usize;
+        let out = compute_actual_out(amount_in, reserve, reserve);
+        assert!(out < reserve);
+        assert!(out > reserve - (reserve >> 50), "got {out}");
+
+        let saturated = compute_actual_out(U256::MAX, reserve, reserve);
+        assert!(saturated >= out && saturated < reserve);
+    }
+
+    #[test]
+    fn quotes_stay_exact_when_reserve_product_exceeds_u256() {
+        // amount_in * reserve_out = 2^260，朴素乘法会饱和
+        let amount_in = U256::from(1u64) << 130usize;
+        let reserve_in = U256::from(1u64) << 140usize;
+        let reserve_out = U256::from(1u64) << 130usize;
+        let naive = amount_in.saturating_mul(reserve_out) / reserve_in;
+        let ideal = compute_ideal_out(amount_in, reserve_in, reserve_out);
+        assert_eq!(ideal, U256::from(1u64) << 120usize);
+        assert!(naive < ideal);
+
+        let actual = compute_actual_out(amount_in, reserve_in, reserve_out);
+        assert!(actual > U256::ZERO && actual < ideal);
+        assert_eq!(
+            price_impact_bps(amount_in, &[(reserve_in, reserve_out)]),
+            U256::from(39u64)
+        );
+    }
+}
@@ -4,20 +4,24 @@ use worker::d1::D1Type;
 
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::types;
 
 #[derive(Debug, Deserialize)]
 struct SearchArgs {
     query: String,
     #[serde(default = "default_limit")]
-    limit: u8,
+    limit: u64,
 }
 
-fn default_limit() -> u8 {
-    20
+const DEFAULT_LIMIT: u64 = 20;
+const MAX_LIMIT: u64 = 50;
+
+fn default_limit() -> u64 {
+    DEFAULT_LIMIT
 }
 
-fn normalize_limit(limit: u8) -> i64 {
-    limit.clamp(1, 50) as i64
+fn normalize_limit(limit: u64) -> (u64, bool) {
+    types::clamp_limit(Some(limit), DEFAULT_LIMIT, MAX_LIMIT)
 }
 
 fn validate_search_query(query: &str) -> Result<String> {
@@ -44,7 +48,7 @@ pub async fn search_contract(services: &infra::Services, args: Value) -> Result<
     let input: SearchArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let limit = normalize_limit(input.limit);
+    let (limit, limited) = normalize_limit(input.limit);
     let q = validate_search_query(&input.query)?;
 
     let like = build_like_pattern(&q);
@@ -59,7 +63,7 @@ pub async fn search_contract(services: &infra::Services, args: Value) -> Result<
         )
         .bind_refs([&like_arg, &limit_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
-    let result = infra::db::run("search_contract", statement.all()).await?;
+    let result = infra::db::run("search_contract", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -74,7 +78,15 @@ pub async fn search_contract(services: &infra::Services, args: Value) -> Result<
         }));
     }
 
-    Ok(serde_json::json!({ "results": out, "meta": services.meta() }))
+    let mut result = serde_json::json!({
+        "results": out,
+        "limit": limit,
+        "meta": services.meta()
+    });
+    if limited {
+        result["limited"] = Value::Bool(true);
+    }
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -88,10 +100,10 @@ mod tests {
 
     #[test]
     fn normalize_limit_clamps() {
-        assert_eq!(normalize_limit(0), 1);
-        assert_eq!(normalize_limit(1), 1);
-        assert_eq!(normalize_limit(50), 50);
-        assert_eq!(normalize_limit(100), 50);
+        assert_eq!(normalize_limit(0), (1, false));
+        assert_eq!(normalize_limit(1), (1, false));
+        assert_eq!(normalize_limit(50), (50, false));
+        assert_eq!(normalize_limit(100), (50, true));
     }
 
     #[test]
@@ -131,4 +143,11 @@ mod tests {
         let args: SearchArgs = serde_json::from_value(json).expect("args should parse");
         assert_eq!(args.limit, 5);
     }
+
+    #[test]
+    fn args_accept_limit_above_u8_range() {
+        let json = serde_json::json!({ "query": "router", "limit": 1000 });
+        let args: SearchArgs = serde_json::from_value(json).expect("args should parse");
+        assert_eq!(normalize_limit(args.limit), (MAX_LIMIT, true));
+    }
 }
@@ -5,6 +5,7 @@ use serde_json::Value;
 use crate::error::{CroLensError, Result};
 use crate::infra;
 use crate::infra::rpc::InternalCall;
+use crate::infra::tenderly::Simulator;
 use crate::types;
 
 // Cronos gas price: ~5000 gwei (baseFee), 常规交易约 5000-10000 gwei
@@ -26,8 +27,9 @@ pub async fn simulate_transaction(services: &infra::Services, args: Value) -> Re
     let input: SimulateArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let from = types::parse_address(&input.from)?;
-    let to = types::parse_address(&input.to)?;
+    let from = types::parse_address_field("from", &input.from)?;
+    let to = types::parse_address_field("to", &input.to)?;
+    infra::tenderly::check_simulation_target(services.simulation_target_allowlist.as_deref(), to)?;
     if !input.data.trim().starts_with("0x") {
         return Err(CroLensError::invalid_params(
             "data must be 0x-prefixed hex".to_string(),
@@ -41,7 +43,7 @@ pub async fn simulate_transaction(services: &infra::Services, args: Value) -> Re
         types::parse_u256_dec(&input.value)?
     };
 
-    let Some(simulator) = services.tenderly() else {
+    let Ok(simulator) = services.tenderly() else {
         if input.simple_mode {
             return Ok(serde_json::json!({
                 "text": "Simulation not available (RPC not configured).",
@@ -90,11 +92,11 @@ pub async fn simulate_transaction(services: &infra::Services, args: Value) -> Re
         None
     };
 
-    let state_changes = decode_state_changes(&simulation.logs);
+    let state_changes = decode_state_changes(&simulation.logs, &services.approval_threshold);
     let internal_calls_json = format_internal_calls(&simulation.internal_calls);
 
     // 风险评估
-    let (risk_level, warnings) = assess_risk(&simulation);
+    let (risk_level, warnings) = assess_risk(&simulation, &services.approval_threshold);
 
     if input.simple_mode {
         let text = if simulation.success {
@@ -102,7 +104,11 @@ pub async fn simulate_transaction(services: &infra::Services, args: Value) -> Re
                 .as_ref()
                 .map(|c| format!(" | Cost: ~{c} CRO"))
                 .unwrap_or_default();
-            let mode_info = if simulation.basic_mode { " (basic)" } else { "" };
+            let mode_info = if simulation.basic_mode {
+                " (basic)"
+            } else {
+                ""
+            };
             format!("Simulation success{mode_info} | Gas: {gas_estimated}{cost_info}")
         } else {
             format!(
@@ -126,6 +132,7 @@ pub async fn simulate_transaction(services: &infra::Services, args: Value) -> Re
         "return_data": simulation.output,
         "state_changes": state_changes,
         "internal_calls": internal_calls_json,
+        "calls_truncated": simulation.calls_truncated,
         "risk_assessment": { "level": risk_level, "warnings": warnings },
         "basic_mode": simulation.basic_mode,
         "meta": services.meta(),
@@ -133,14 +140,144 @@ pub async fn simulate_transaction(services: &infra::Services, args: Value) -> Re
 }
 
 // 常见事件签名
-const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
 const APPROVAL_TOPIC: &str = "0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925";
 const SWAP_TOPIC: &str = "0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822"; // UniswapV2
 const SWAP_V3_TOPIC: &str = "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67"; // UniswapV3
 const DEPOSIT_TOPIC: &str = "0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"; // WETH Deposit
 const WITHDRAWAL_TOPIC: &str = "0x7fcf532c15f0a6db0bd6d0e038bea71d30d808c7d98cb3bf7268a95bf5081b65"; // WETH Withdrawal
 
-fn decode_state_changes(logs: &[infra::tenderly::SimulationLog]) -> Vec<Value> {
+// 协议事件签名 (Tectonic 为 Compound 分叉，事件参数均不 indexed)
+const TECTONIC_MINT_TOPIC: &str =
+    "0x4c209b5fc8ad50758f13e2e1088ba56a560dff690a1c6fef26394f4c03821c4f";
+const TECTONIC_REDEEM_TOPIC: &str =
+    "0xe5b754fb1abb7f01b499791d0b820ae3b6af3424ac1c59768edb53f4ec31a929";
+const TECTONIC_BORROW_TOPIC: &str =
+    "0x13ed6866d4e1ee6da46f845c46d7e54120883d75c5ea9a2dacc1c4ca8984ab80";
+const TECTONIC_REPAY_BORROW_TOPIC: &str =
+    "0x1a2a22cb034d26d1854bdc6666a5b91fe25efbbb5dcad3b0355478d6f5c362a1";
+const MASTERCHEF_DEPOSIT_TOPIC: &str =
+    "0x90890809c654f11d6e72a28fa60149770a0d11ec6c92319d6ceb2bb0a4ea1a15";
+const MASTERCHEF_WITHDRAW_TOPIC: &str =
+    "0xf279e6a1f5e320cca91135676d9cb6e44ca8a08c0b88342bcdb1144f6511b568";
+
+type EventDecoder = fn(&infra::tenderly::SimulationLog) -> Option<Value>;
+
+/// topic0 -> 协议事件解码器；新增协议事件时在此登记
+const PROTOCOL_EVENT_DECODERS: &[(&str, EventDecoder)] = &[
+    (TECTONIC_MINT_TOPIC, decode_tectonic_mint),
+    (TECTONIC_REDEEM_TOPIC, decode_tectonic_redeem),
+    (TECTONIC_BORROW_TOPIC, decode_tectonic_borrow),
+    (TECTONIC_REPAY_BORROW_TOPIC, decode_tectonic_repay_borrow),
+    (MASTERCHEF_DEPOSIT_TOPIC, decode_masterchef_deposit),
+    (MASTERCHEF_WITHDRAW_TOPIC, decode_masterchef_withdraw),
+];
+
+fn decode_protocol_event(topic0: &str, log: &infra::tenderly::SimulationLog) -> Option<Value> {
+    PROTOCOL_EVENT_DECODERS
+        .iter()
+        .find(|(topic, _)| topic0.eq_ignore_ascii_case(topic))
+        .and_then(|(_, decoder)| decoder(log))
+}
+
+/// 取出 data 中的 `count` 个 32 字节字；长度不足时返回 None
+fn data_words(log: &infra::tenderly::SimulationLog, count: usize) -> Option<Vec<&str>> {
+    let data = log.data.trim_start_matches("0x");
+    if data.len() < count * 64 {
+        return None;
+    }
+    Some((0..count).map(|i| &data[i * 64..(i + 1) * 64]).collect())
+}
+
+fn word_to_u256(word: &str) -> String {
+    parse_u256_from_hex_slice(word, 0).to_string()
+}
+
+// Mint(address minter, uint mintAmount, uint mintTokens)
+fn decode_tectonic_mint(log: &infra::tenderly::SimulationLog) -> Option<Value> {
+    let words = data_words(log, 3)?;
+    Some(serde_json::json!({
+        "type": "lending_supply",
+        "description": "Tectonic Supply",
+        "account": topic_to_address(words[0]),
+        "amount": word_to_u256(words[1]),
+        "ctokens_minted": word_to_u256(words[2]),
+        "market": log.address,
+    }))
+}
+
+// Redeem(address redeemer, uint redeemAmount, uint redeemTokens)
+fn decode_tectonic_redeem(log: &infra::tenderly::SimulationLog) -> Option<Value> {
+    let words = data_words(log, 3)?;
+    Some(serde_json::json!({
+        "type": "lending_withdraw",
+        "description": "Tectonic Withdraw",
+        "account": topic_to_address(words[0]),
+        "amount": word_to_u256(words[1]),
+        "ctokens_redeemed": word_to_u256(words[2]),
+        "market": log.address,
+    }))
+}
+
+// Borrow(address borrower, uint borrowAmount, uint accountBorrows, uint totalBorrows)
+fn decode_tectonic_borrow(log: &infra::tenderly::SimulationLog) -> Option<Value> {
+    let words = data_words(log, 4)?;
+    Some(serde_json::json!({
+        "type": "lending_borrow",
+        "description": "Tectonic Borrow",
+        "account": topic_to_address(words[0]),
+        "amount": word_to_u256(words[1]),
+        "account_borrows": word_to_u256(words[2]),
+        "market": log.address,
+    }))
+}
+
+// RepayBorrow(address payer, address borrower, uint repayAmount, uint accountBorrows, uint totalBorrows)
+fn decode_tectonic_repay_borrow(log: &infra::tenderly::SimulationLog) -> Option<Value> {
+    let words = data_words(log, 5)?;
+    Some(serde_json::json!({
+        "type": "lending_repay",
+        "description": "Tectonic Repay",
+        "payer": topic_to_address(words[0]),
+        "account": topic_to_address(words[1]),
+        "amount": word_to_u256(words[2]),
+        "account_borrows": word_to_u256(words[3]),
+        "market": log.address,
+    }))
+}
+
+// Deposit(address indexed user, uint256 indexed pid, uint256 amount)
+fn decode_masterchef_deposit(log: &infra::tenderly::SimulationLog) -> Option<Value> {
+    decode_masterchef_event(log, "farm_deposit", "VVS Farm Deposit")
+}
+
+// Withdraw(address indexed user, uint256 indexed pid, uint256 amount)
+fn decode_masterchef_withdraw(log: &infra::tenderly::SimulationLog) -> Option<Value> {
+    decode_masterchef_event(log, "farm_withdraw", "VVS Farm Withdraw")
+}
+
+fn decode_masterchef_event(
+    log: &infra::tenderly::SimulationLog,
+    kind: &str,
+    description: &str,
+) -> Option<Value> {
+    if log.topics.len() < 3 {
+        return None;
+    }
+    let words = data_words(log, 1)?;
+    Some(serde_json::json!({
+        "type": kind,
+        "description": description,
+        "user": topic_to_address(&log.topics[1]),
+        "pid": types::parse_u256_hex(&log.topics[2]).unwrap_or(U256::ZERO).to_string(),
+        "amount": word_to_u256(words[0]),
+        "masterchef": log.address,
+    }))
+}
+
+fn decode_state_changes(
+    logs: &[infra::tenderly::SimulationLog],
+    threshold: &types::ApprovalThreshold,
+) -> Vec<Value> {
     let mut out = Vec::new();
 
     for log in logs {
@@ -151,7 +288,7 @@ fn decode_state_changes(logs: &[infra::tenderly::SimulationLog]) -> Vec<Value> {
         let topic0 = &log.topics[0];
 
         // ERC20 Transfer
-        if topic0.eq_ignore_ascii_case(TRANSFER_TOPIC) && log.topics.len() >= 3 {
+        if topic0.eq_ignore_ascii_case(types::TRANSFER_TOPIC) && log.topics.len() >= 3 {
             let from = topic_to_address(&log.topics[1]);
             let to = topic_to_address(&log.topics[2]);
             let amount = types::parse_u256_hex(&log.data).unwrap_or(U256::ZERO);
@@ -171,7 +308,8 @@ fn decode_state_changes(logs: &[infra::tenderly::SimulationLog]) -> Vec<Value> {
             let spender = topic_to_address(&log.topics[2]);
             let amount = types::parse_u256_hex(&log.data).unwrap_or(U256::ZERO);
 
-            let is_unlimited = amount == U256::MAX;
+            // 模拟中拿不到总供应量，只按 U256::MAX / 固定阈值判断
+            let is_unlimited = threshold.is_unlimited(amount, None);
             out.push(serde_json::json!({
                 "type": "approval",
                 "description": if is_unlimited { "Unlimited Approval" } else { "ERC20 Approval" },
@@ -247,6 +385,10 @@ fn decode_state_changes(logs: &[infra::tenderly::SimulationLog]) -> Vec<Value> {
                 "token": log.address,
             }));
         }
+        // 协议事件 (Tectonic / VVS MasterChef)
+        else if let Some(change) = decode_protocol_event(topic0, log) {
+            out.push(change);
+        }
     }
 
     out
@@ -260,6 +402,7 @@ fn parse_u256_from_hex_slice(data: &str, offset: usize) -> U256 {
     types::parse_u256_hex(&format!("0x{slice}")).unwrap_or(U256::ZERO)
 }
 
+/// 32 字节 topic 的低 20 字节 -> 地址 (`types::address_to_topic` 的逆运算)
 fn topic_to_address(topic: &str) -> String {
     let trimmed = topic.trim().trim_start_matches("0x");
     if trimmed.len() < 40 {
@@ -286,7 +429,10 @@ fn format_internal_calls(calls: &[InternalCall]) -> Vec<Value> {
 }
 
 /// 风险评估
-fn assess_risk(simulation: &infra::tenderly::SimulationResult) -> (&'static str, Vec<String>) {
+fn assess_risk(
+    simulation: &infra::tenderly::SimulationResult,
+    threshold: &types::ApprovalThreshold,
+) -> (&'static str, Vec<String>) {
     let mut warnings = Vec::new();
 
     // 交易失败
@@ -303,7 +449,7 @@ fn assess_risk(simulation: &infra::tenderly::SimulationResult) -> (&'static str,
     for log in &simulation.logs {
         if !log.topics.is_empty() && log.topics[0].eq_ignore_ascii_case(APPROVAL_TOPIC) {
             let amount = types::parse_u256_hex(&log.data).unwrap_or(U256::ZERO);
-            if amount == U256::MAX {
+            if threshold.is_unlimited(amount, None) {
                 warnings.push("Unlimited token approval detected".to_string());
             }
         }
@@ -333,9 +479,24 @@ fn assess_risk(simulation: &infra::tenderly::SimulationResult) -> (&'static str,
 #[cfg(test)]
 mod tests {
     use super::*;
+
     use crate::infra::rpc::InternalCall;
     use crate::infra::tenderly::SimulationLog;
 
+    #[test]
+    fn topic_address_round_trip() {
+        for address in [
+            alloy_primitives::Address::ZERO,
+            alloy_primitives::Address::repeat_byte(0xab),
+            types::parse_address("0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23").expect("valid"),
+        ] {
+            let topic = types::address_to_topic(address);
+            assert_eq!(topic.len(), 66);
+            let back = types::parse_address(&topic_to_address(&topic)).expect("valid");
+            assert_eq!(back, address);
+        }
+    }
+
     // ============ decode_state_changes tests ============
 
     #[test]
@@ -343,27 +504,21 @@ mod tests {
         let logs = vec![SimulationLog {
             address: "0xc21223249CA28397B4B6541dfFaEcC539BfF0c59".to_string(), // USDC
             topics: vec![
-                TRANSFER_TOPIC.to_string(),
+                types::TRANSFER_TOPIC.to_string(),
                 "0x0000000000000000000000005c7f8a570d578ed84e63fdfa7b1ee72deae1ae23".to_string(), // from
                 "0x0000000000000000000000001234567890123456789012345678901234567890".to_string(), // to
             ],
             data: "0x00000000000000000000000000000000000000000000000000000000000f4240".to_string(), // 1000000
         }];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert_eq!(changes.len(), 1);
 
         let change = &changes[0];
         assert_eq!(change["type"], "transfer");
         assert_eq!(change["description"], "ERC20 Transfer");
-        assert_eq!(
-            change["from"],
-            "0x5c7f8a570d578ed84e63fdfa7b1ee72deae1ae23"
-        );
-        assert_eq!(
-            change["to"],
-            "0x1234567890123456789012345678901234567890"
-        );
+        assert_eq!(change["from"], "0x5c7f8a570d578ed84e63fdfa7b1ee72deae1ae23");
+        assert_eq!(change["to"], "0x1234567890123456789012345678901234567890");
         assert_eq!(change["amount"], "1000000");
     }
 
@@ -379,7 +534,7 @@ mod tests {
             data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000".to_string(), // 1e18
         }];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert_eq!(changes.len(), 1);
 
         let change = &changes[0];
@@ -401,7 +556,7 @@ mod tests {
             data: "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff".to_string(),
         }];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert_eq!(changes.len(), 1);
 
         let change = &changes[0];
@@ -418,7 +573,7 @@ mod tests {
             "0000000000000000000000000000000000000000000000000de0b6b3a7640000", // amount0In = 1e18
             "0000000000000000000000000000000000000000000000000000000000000000", // amount1In = 0
             "0000000000000000000000000000000000000000000000000000000000000000", // amount0Out = 0
-            "00000000000000000000000000000000000000000000000000000000000f4240"  // amount1Out = 1000000
+            "00000000000000000000000000000000000000000000000000000000000f4240" // amount1Out = 1000000
         );
 
         let logs = vec![SimulationLog {
@@ -431,7 +586,7 @@ mod tests {
             data,
         }];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert_eq!(changes.len(), 1);
 
         let change = &changes[0];
@@ -454,7 +609,7 @@ mod tests {
             data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000".to_string(),
         }];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert_eq!(changes.len(), 1);
 
         let change = &changes[0];
@@ -473,7 +628,7 @@ mod tests {
             data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000".to_string(),
         }];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert_eq!(changes.len(), 1);
 
         let change = &changes[0];
@@ -481,10 +636,116 @@ mod tests {
         assert_eq!(change["description"], "Wrapped Native Withdrawal");
     }
 
+    fn word(hex: &str) -> String {
+        format!("{:0>64}", hex)
+    }
+
+    #[test]
+    fn test_decode_tectonic_mint_event() {
+        let data = format!(
+            "0x{}{}{}",
+            word("5c7f8a570d578ed84e63fdfa7b1ee72deae1ae23"), // minter
+            word("de0b6b3a7640000"),                          // mintAmount = 1e18
+            word("2540be400")                                 // mintTokens = 1e10
+        );
+        let logs = vec![SimulationLog {
+            address: "0xeAdf7c01DA7E93FdB5f16B0aa9ee85f978e89E95".to_string(), // tCRO
+            topics: vec![TECTONIC_MINT_TOPIC.to_string()],
+            data,
+        }];
+
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
+        assert_eq!(changes.len(), 1);
+
+        let change = &changes[0];
+        assert_eq!(change["type"], "lending_supply");
+        assert_eq!(
+            change["account"],
+            "0x5c7f8a570d578ed84e63fdfa7b1ee72deae1ae23"
+        );
+        assert_eq!(change["amount"], "1000000000000000000");
+        assert_eq!(change["ctokens_minted"], "10000000000");
+    }
+
+    #[test]
+    fn test_decode_tectonic_mint_short_data_is_skipped() {
+        let logs = vec![SimulationLog {
+            address: "0xeAdf7c01DA7E93FdB5f16B0aa9ee85f978e89E95".to_string(),
+            topics: vec![TECTONIC_MINT_TOPIC.to_string()],
+            data: format!("0x{}", word("1")),
+        }];
+
+        assert!(decode_state_changes(&logs, &types::ApprovalThreshold::default()).is_empty());
+    }
+
+    #[test]
+    fn test_decode_tectonic_repay_borrow_event() {
+        let data = format!(
+            "0x{}{}{}{}{}",
+            word("1111111111111111111111111111111111111111"), // payer
+            word("2222222222222222222222222222222222222222"), // borrower
+            word("f4240"),                                    // repayAmount = 1e6
+            word("0"),                                        // accountBorrows
+            word("3d0900")                                    // totalBorrows
+        );
+        let logs = vec![SimulationLog {
+            address: "0xB3bbf1bE947b245Aef26e3B6a9D777d7703F4c8e".to_string(),
+            topics: vec![TECTONIC_REPAY_BORROW_TOPIC.to_string()],
+            data,
+        }];
+
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0]["type"], "lending_repay");
+        assert_eq!(
+            changes[0]["payer"],
+            "0x1111111111111111111111111111111111111111"
+        );
+        assert_eq!(
+            changes[0]["account"],
+            "0x2222222222222222222222222222222222222222"
+        );
+        assert_eq!(changes[0]["amount"], "1000000");
+    }
+
+    #[test]
+    fn test_decode_masterchef_deposit_event() {
+        let logs = vec![SimulationLog {
+            address: "0xDccd6455AE04b03d785F12196B492b18129564bc".to_string(), // VVS MasterChef
+            topics: vec![
+                MASTERCHEF_DEPOSIT_TOPIC.to_string(),
+                format!("0x{}", word("5c7f8a570d578ed84e63fdfa7b1ee72deae1ae23")), // user
+                format!("0x{}", word("3")),                                        // pid
+            ],
+            data: format!("0x{}", word("de0b6b3a7640000")),
+        }];
+
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
+        assert_eq!(changes.len(), 1);
+
+        let change = &changes[0];
+        assert_eq!(change["type"], "farm_deposit");
+        assert_eq!(change["description"], "VVS Farm Deposit");
+        assert_eq!(change["user"], "0x5c7f8a570d578ed84e63fdfa7b1ee72deae1ae23");
+        assert_eq!(change["pid"], "3");
+        assert_eq!(change["amount"], "1000000000000000000");
+    }
+
+    #[test]
+    fn test_decode_masterchef_withdraw_requires_indexed_topics() {
+        let logs = vec![SimulationLog {
+            address: "0xDccd6455AE04b03d785F12196B492b18129564bc".to_string(),
+            topics: vec![MASTERCHEF_WITHDRAW_TOPIC.to_string()],
+            data: format!("0x{}", word("1")),
+        }];
+
+        assert!(decode_state_changes(&logs, &types::ApprovalThreshold::default()).is_empty());
+    }
+
     #[test]
     fn test_decode_empty_logs() {
         let logs: Vec<SimulationLog> = vec![];
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert!(changes.is_empty());
     }
 
@@ -498,7 +759,7 @@ mod tests {
             data: "0x1234".to_string(),
         }];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert!(changes.is_empty()); // Unknown events are skipped
     }
 
@@ -510,7 +771,7 @@ mod tests {
             data: "0x1234".to_string(),
         }];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert!(changes.is_empty());
     }
 
@@ -541,10 +802,11 @@ mod tests {
             logs: vec![],
             internal_calls: vec![],
             error_message: None,
+            calls_truncated: false,
             basic_mode: false,
         };
 
-        let (level, warnings) = assess_risk(&simulation);
+        let (level, warnings) = assess_risk(&simulation, &types::ApprovalThreshold::default());
         assert_eq!(level, "low");
         assert!(warnings.is_empty());
     }
@@ -558,10 +820,11 @@ mod tests {
             logs: vec![],
             internal_calls: vec![],
             error_message: Some("execution reverted".to_string()),
+            calls_truncated: false,
             basic_mode: false,
         };
 
-        let (level, warnings) = assess_risk(&simulation);
+        let (level, warnings) = assess_risk(&simulation, &types::ApprovalThreshold::default());
         assert_eq!(level, "high");
         assert_eq!(warnings.len(), 1);
         assert!(warnings[0].contains("execution reverted"));
@@ -576,10 +839,11 @@ mod tests {
             logs: vec![],
             internal_calls: vec![],
             error_message: None,
+            calls_truncated: false,
             basic_mode: false,
         };
 
-        let (level, warnings) = assess_risk(&simulation);
+        let (level, warnings) = assess_risk(&simulation, &types::ApprovalThreshold::default());
         assert_eq!(level, "high");
         assert_eq!(warnings.len(), 1);
         assert_eq!(warnings[0], "Transaction reverted");
@@ -595,22 +859,66 @@ mod tests {
                 address: "0xc21223249CA28397B4B6541dfFaEcC539BfF0c59".to_string(),
                 topics: vec![
                     APPROVAL_TOPIC.to_string(),
-                    "0x0000000000000000000000005c7f8a570d578ed84e63fdfa7b1ee72deae1ae23".to_string(),
-                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae".to_string(),
+                    "0x0000000000000000000000005c7f8a570d578ed84e63fdfa7b1ee72deae1ae23"
+                        .to_string(),
+                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae"
+                        .to_string(),
                 ],
-                data: "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff".to_string(),
+                data: "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"
+                    .to_string(),
             }],
             internal_calls: vec![],
             error_message: None,
+            calls_truncated: false,
             basic_mode: false,
         };
 
-        let (level, warnings) = assess_risk(&simulation);
+        let (level, warnings) = assess_risk(&simulation, &types::ApprovalThreshold::default());
         assert_eq!(level, "medium");
         assert_eq!(warnings.len(), 1);
         assert!(warnings[0].contains("Unlimited token approval"));
     }
 
+    #[test]
+    fn test_assess_risk_huge_below_max_approval() {
+        // 1e31 < U256::MAX，但超过默认 1e30 阈值，应视为无限授权
+        let simulation = infra::tenderly::SimulationResult {
+            success: true,
+            gas_used: Some(50000),
+            output: "0x".to_string(),
+            logs: vec![SimulationLog {
+                address: "0xc21223249CA28397B4B6541dfFaEcC539BfF0c59".to_string(),
+                topics: vec![
+                    APPROVAL_TOPIC.to_string(),
+                    "0x0000000000000000000000005c7f8a570d578ed84e63fdfa7b1ee72deae1ae23"
+                        .to_string(),
+                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae"
+                        .to_string(),
+                ],
+                data: format!("{:#066x}", U256::from(10u64).pow(U256::from(31u64))),
+            }],
+            internal_calls: vec![],
+            error_message: None,
+            calls_truncated: false,
+            basic_mode: false,
+        };
+
+        let default_threshold = types::ApprovalThreshold::default();
+        let (level, warnings) = assess_risk(&simulation, &default_threshold);
+        assert_eq!(level, "medium");
+        assert!(warnings[0].contains("Unlimited token approval"));
+
+        let changes = decode_state_changes(&simulation.logs, &default_threshold);
+        assert_eq!(changes[0]["unlimited"], true);
+        assert_eq!(changes[0]["description"], "Unlimited Approval");
+
+        // 提高固定阈值后不再标记
+        let strict = types::ApprovalThreshold::parse(Some(&U256::MAX.to_string()), None);
+        let (level, warnings) = assess_risk(&simulation, &strict);
+        assert_eq!(level, "low");
+        assert!(warnings.is_empty());
+    }
+
     #[test]
     fn test_assess_risk_limited_approval() {
         let simulation = infra::tenderly::SimulationResult {
@@ -621,17 +929,21 @@ mod tests {
                 address: "0xc21223249CA28397B4B6541dfFaEcC539BfF0c59".to_string(),
                 topics: vec![
                     APPROVAL_TOPIC.to_string(),
-                    "0x0000000000000000000000005c7f8a570d578ed84e63fdfa7b1ee72deae1ae23".to_string(),
-                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae".to_string(),
+                    "0x0000000000000000000000005c7f8a570d578ed84e63fdfa7b1ee72deae1ae23"
+                        .to_string(),
+                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae"
+                        .to_string(),
                 ],
-                data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000".to_string(),
+                data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000"
+                    .to_string(),
             }],
             internal_calls: vec![],
             error_message: None,
+            calls_truncated: false,
             basic_mode: false,
         };
 
-        let (level, warnings) = assess_risk(&simulation);
+        let (level, warnings) = assess_risk(&simulation, &types::ApprovalThreshold::default());
         assert_eq!(level, "low");
         assert!(warnings.is_empty());
     }
@@ -654,10 +966,11 @@ mod tests {
                 error: Some("out of gas".to_string()),
             }],
             error_message: None,
+            calls_truncated: false,
             basic_mode: false,
         };
 
-        let (level, warnings) = assess_risk(&simulation);
+        let (level, warnings) = assess_risk(&simulation, &types::ApprovalThreshold::default());
         assert_eq!(level, "low"); // Failed internal call doesn't escalate to medium
         assert_eq!(warnings.len(), 1);
         assert!(warnings[0].contains("Internal call"));
@@ -756,7 +1069,7 @@ mod tests {
             data: "0x0000000000000000000000000000000000000000000000000000000000000000".to_string(),
         }];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert_eq!(changes.len(), 1);
 
         let change = &changes[0];
@@ -772,10 +1085,7 @@ mod tests {
             "0x5c7f8a570d578ed84e63fdfa7b1ee72deae1ae23"
         );
         // Pool address comes from log.address which is not modified
-        assert_eq!(
-            change["pool"],
-            "0x8ad599c3A0ff1De082011EFDDc58f1908eb6e6D8"
-        );
+        assert_eq!(change["pool"], "0x8ad599c3A0ff1De082011EFDDc58f1908eb6e6D8");
     }
 
     // ============ Multi-event combination tests ============
@@ -788,19 +1098,24 @@ mod tests {
             SimulationLog {
                 address: "0xc21223249CA28397B4B6541dfFaEcC539BfF0c59".to_string(), // USDC
                 topics: vec![
-                    TRANSFER_TOPIC.to_string(),
-                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23".to_string(),
-                    "0x000000000000000000000000bF62c67eA509E86F07c8c69d0286C0636C50270b".to_string(),
+                    types::TRANSFER_TOPIC.to_string(),
+                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23"
+                        .to_string(),
+                    "0x000000000000000000000000bF62c67eA509E86F07c8c69d0286C0636C50270b"
+                        .to_string(),
                 ],
-                data: "0x00000000000000000000000000000000000000000000000000000000000f4240".to_string(), // 1000000
+                data: "0x00000000000000000000000000000000000000000000000000000000000f4240"
+                    .to_string(), // 1000000
             },
             // Second: Swap event on the pair
             SimulationLog {
                 address: "0xbF62c67eA509E86F07c8c69d0286C0636C50270b".to_string(), // CRO-USDC pair
                 topics: vec![
                     SWAP_TOPIC.to_string(),
-                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae".to_string(),
-                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23".to_string(),
+                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae"
+                        .to_string(),
+                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23"
+                        .to_string(),
                 ],
                 data: format!(
                     "0x{}{}{}{}",
@@ -814,15 +1129,18 @@ mod tests {
             SimulationLog {
                 address: "0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23".to_string(), // WCRO
                 topics: vec![
-                    TRANSFER_TOPIC.to_string(),
-                    "0x000000000000000000000000bF62c67eA509E86F07c8c69d0286C0636C50270b".to_string(),
-                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23".to_string(),
+                    types::TRANSFER_TOPIC.to_string(),
+                    "0x000000000000000000000000bF62c67eA509E86F07c8c69d0286C0636C50270b"
+                        .to_string(),
+                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23"
+                        .to_string(),
                 ],
-                data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000".to_string(), // 1e18
+                data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000"
+                    .to_string(), // 1e18
             },
         ];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert_eq!(changes.len(), 3);
 
         // Verify order and types
@@ -844,23 +1162,29 @@ mod tests {
                 address: "0xc21223249CA28397B4B6541dfFaEcC539BfF0c59".to_string(),
                 topics: vec![
                     APPROVAL_TOPIC.to_string(),
-                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23".to_string(),
-                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae".to_string(),
+                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23"
+                        .to_string(),
+                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae"
+                        .to_string(),
                 ],
-                data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000".to_string(),
+                data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000"
+                    .to_string(),
             },
             SimulationLog {
                 address: "0xc21223249CA28397B4B6541dfFaEcC539BfF0c59".to_string(),
                 topics: vec![
-                    TRANSFER_TOPIC.to_string(),
-                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23".to_string(),
-                    "0x0000000000000000000000001234567890123456789012345678901234567890".to_string(),
+                    types::TRANSFER_TOPIC.to_string(),
+                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23"
+                        .to_string(),
+                    "0x0000000000000000000000001234567890123456789012345678901234567890"
+                        .to_string(),
                 ],
-                data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000".to_string(),
+                data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000"
+                    .to_string(),
             },
         ];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert_eq!(changes.len(), 2);
 
         assert_eq!(changes[0]["type"], "approval");
@@ -878,17 +1202,21 @@ mod tests {
                 address: "0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23".to_string(),
                 topics: vec![
                     DEPOSIT_TOPIC.to_string(),
-                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23".to_string(),
+                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23"
+                        .to_string(),
                 ],
-                data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000".to_string(),
+                data: "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000"
+                    .to_string(),
             },
             // Then swap WCRO for USDC
             SimulationLog {
                 address: "0xbF62c67eA509E86F07c8c69d0286C0636C50270b".to_string(),
                 topics: vec![
                     SWAP_TOPIC.to_string(),
-                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae".to_string(),
-                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23".to_string(),
+                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae"
+                        .to_string(),
+                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23"
+                        .to_string(),
                 ],
                 data: format!(
                     "0x{}{}{}{}",
@@ -900,7 +1228,7 @@ mod tests {
             },
         ];
 
-        let changes = decode_state_changes(&logs);
+        let changes = decode_state_changes(&logs, &types::ApprovalThreshold::default());
         assert_eq!(changes.len(), 2);
 
         assert_eq!(changes[0]["type"], "deposit");
@@ -920,10 +1248,13 @@ mod tests {
                 address: "0xc21223249CA28397B4B6541dfFaEcC539BfF0c59".to_string(),
                 topics: vec![
                     APPROVAL_TOPIC.to_string(),
-                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23".to_string(),
-                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae".to_string(),
+                    "0x0000000000000000000000005C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23"
+                        .to_string(),
+                    "0x000000000000000000000000145863eb42cf62847a6ca784e6416c1682b1b2ae"
+                        .to_string(),
                 ],
-                data: "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff".to_string(),
+                data: "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"
+                    .to_string(),
             }],
             internal_calls: vec![InternalCall {
                 call_type: "CALL".to_string(),
@@ -936,10 +1267,11 @@ mod tests {
                 error: Some("out of gas".to_string()),
             }],
             error_message: None,
+            calls_truncated: false,
             basic_mode: false,
         };
 
-        let (level, warnings) = assess_risk(&simulation);
+        let (level, warnings) = assess_risk(&simulation, &types::ApprovalThreshold::default());
         assert_eq!(level, "medium"); // Unlimited approval triggers medium
         assert_eq!(warnings.len(), 2); // Both warnings present
         assert!(warnings.iter().any(|w| w.contains("Unlimited")));
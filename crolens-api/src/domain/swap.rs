@@ -4,39 +4,117 @@ use serde::Deserialize;
 use serde_json::Value;
 
 use crate::abi;
+use crate::domain::route;
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::tenderly::Simulator;
+use crate::infra::MAX_SLIPPAGE_BPS;
 use crate::types;
 
+/// 单跳池子任一侧储备低于该值 (与 Uniswap V2 的 MINIMUM_LIQUIDITY 相同) 视为空池
+const MIN_POOL_RESERVE: u64 = 1_000;
+
 #[derive(Debug, Deserialize)]
 struct SwapArgs {
     from: String,
     token_in: String,
     token_out: String,
     amount_in: String,
-    slippage_bps: u16,
+    /// 未提供时使用 DEFAULT_SLIPPAGE_BPS
+    #[serde(default)]
+    slippage_bps: Option<u16>,
+    /// 为每个步骤附带建议的 nonce (基于 pending nonce 递增)
+    #[serde(default)]
+    include_nonce: bool,
+}
+
+fn resolve_slippage_bps(requested: Option<u16>, default_bps: u16) -> Result<u16> {
+    let slippage_bps = requested.unwrap_or(default_bps);
+    if slippage_bps > MAX_SLIPPAGE_BPS {
+        return Err(CroLensError::invalid_params(format!(
+            "slippage_bps must be between 0 and {MAX_SLIPPAGE_BPS}"
+        )));
+    }
+    Ok(slippage_bps)
+}
+
+/// 按步骤顺序 (approve 在 swap 之前) 从 pending nonce 开始依次分配
+fn assign_nonces(steps: &mut [Value], start_nonce: u64) {
+    for (offset, step) in steps.iter_mut().enumerate() {
+        if let Some(tx_data) = step.get_mut("tx_data").and_then(|v| v.as_object_mut()) {
+            let nonce = start_nonce.saturating_add(offset as u64);
+            tx_data.insert("nonce".to_string(), serde_json::json!(nonce));
+        }
+    }
+}
+
+/// minimum_out 由 getAmountsOut 报价计算，已包含本笔交易的价格影响，slippage_bps 只需覆盖报价到成交之间的价格变动；
+/// 价格影响超过滑点时只提示，不拒绝交易
+fn price_impact_warning(slippage_bps: u16, price_impact_bps: U256) -> Option<Value> {
+    if price_impact_bps <= U256::from(slippage_bps) {
+        return None;
+    }
+    let price_impact = format_percent_from_basis_points(price_impact_bps);
+    Some(serde_json::json!({
+        "type": "high_price_impact",
+        "message": format!(
+            "Estimated price impact of {price_impact}% exceeds slippage_bps {slippage_bps}; \
+             minimum_out already accounts for it, but consider reducing amount_in or splitting the trade"
+        ),
+    }))
+}
+
+fn insufficient_liquidity() -> CroLensError {
+    CroLensError::invalid_params("Insufficient liquidity for this trade".to_string())
+}
+
+/// 报价为 0 时 minimum_out 也为 0，交易会以任意价格成交，直接拒绝
+fn check_quote_has_liquidity(
+    amount_in: U256,
+    estimated_out: U256,
+    minimum_out: U256,
+) -> Result<()> {
+    if amount_in.is_zero() {
+        return Err(CroLensError::invalid_params(
+            "amount_in must be greater than 0".to_string(),
+        ));
+    }
+    if estimated_out.is_zero() || minimum_out.is_zero() {
+        return Err(insufficient_liquidity());
+    }
+    Ok(())
+}
+
+fn check_hop_reserves(reserve_in: U256, reserve_out: U256) -> Result<()> {
+    let min = U256::from(MIN_POOL_RESERVE);
+    if reserve_in < min || reserve_out < min {
+        return Err(insufficient_liquidity());
+    }
+    Ok(())
 }
 
 pub async fn construct_swap_tx(services: &infra::Services, args: Value) -> Result<Value> {
     let input: SwapArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let from = types::parse_address(&input.from)?;
+    let from = types::parse_address_field("from", &input.from)?;
     let amount_in = types::parse_u256_dec(&input.amount_in)?;
+    let slippage_bps = resolve_slippage_bps(input.slippage_bps, services.default_slippage_bps)?;
     let rpc = services.rpc()?;
 
-    let tokens = infra::token::list_tokens_cached(&services.db, &services.kv).await?;
-    let wcro = infra::token::resolve_token(&tokens, "WCRO").ok();
+    let tokens =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix).await?;
+    let wcro = resolve_wcro(&tokens).ok();
     let wcro_address = wcro.as_ref().map(|t| t.address);
 
-    let is_native_out = input.token_out.trim().eq_ignore_ascii_case("cro");
+    let is_native_out = is_native_cro(&input.token_out);
     let token_out_address = if is_native_out {
         wcro_address.ok_or_else(|| CroLensError::TokenNotFound("WCRO".to_string()))?
     } else {
         infra::token::resolve_token(&tokens, &input.token_out)?.address
     };
 
-    let is_native_in = input.token_in.trim().eq_ignore_ascii_case("cro");
+    let is_native_in = is_native_cro(&input.token_in);
     if is_native_in && is_native_out {
         return Err(CroLensError::invalid_params(
             "token_in and token_out cannot both be CRO".to_string(),
@@ -50,13 +128,15 @@ pub async fn construct_swap_tx(services: &infra::Services, args: Value) -> Resul
 
     // 并行获取 router 和 factory
     let (router, factory) = futures_util::future::try_join(
-        infra::config::get_protocol_contract(&services.db, "vvs", "router"),
-        infra::config::get_protocol_contract(&services.db, "vvs", "factory"),
+        services.protocol_contract("vvs", "router"),
+        services.protocol_contract("vvs", "factory"),
     )
     .await?;
 
-    let path = build_path(
+    let path = route::build_path(
         factory,
+        router,
+        amount_in,
         wcro_address,
         token_in.as_ref().map(|t| t.address),
         token_out_address,
@@ -72,10 +152,11 @@ pub async fn construct_swap_tx(services: &infra::Services, args: Value) -> Resul
 
     // 并行获取报价和价格影响
     let ((estimated_out, minimum_out), price_impact_bps) = futures_util::future::try_join(
-        quote_amounts(router, amount_in, &path, rpc, input.slippage_bps),
+        quote_amounts(router, amount_in, &path, rpc, slippage_bps),
         estimate_price_impact_bps(factory, &path, amount_in, rpc),
     )
     .await?;
+    check_quote_has_liquidity(amount_in, estimated_out, minimum_out)?;
     let price_impact = format_percent_from_basis_points(price_impact_bps);
 
     let mut steps: Vec<Value> = Vec::new();
@@ -130,7 +211,7 @@ pub async fn construct_swap_tx(services: &infra::Services, args: Value) -> Resul
     // 尝试模拟验证 (可选 - Tenderly 可能不支持 Cronos)
     let mut simulation_verified = false;
     if steps.len() == 1 {
-        if let Some(tenderly) = services.tenderly() {
+        if let Ok(tenderly) = services.tenderly() {
             let data_hex = types::bytes_to_hex0x(&swap_data);
             match tenderly
                 .simulate(from, swap_to, &data_hex, swap_value, None)
@@ -149,15 +230,35 @@ pub async fn construct_swap_tx(services: &infra::Services, args: Value) -> Resul
         }
     }
 
-    Ok(serde_json::json!({
+    if input.include_nonce {
+        let nonce = rpc.eth_get_transaction_count(from, "pending").await?;
+        assign_nonces(&mut steps, nonce);
+    }
+
+    let mut result = serde_json::json!({
         "operation_id": format!("swap_{}_{}_{}", input.token_in, input.token_out, types::now_ms()),
         "estimated_out": estimated_out.to_string(),
         "minimum_out": minimum_out.to_string(),
         "price_impact": price_impact,
+        "slippage_bps": slippage_bps,
         "simulation_verified": simulation_verified,
         "steps": steps,
         "meta": services.meta()
-    }))
+    });
+    if let Some(warning) = price_impact_warning(slippage_bps, price_impact_bps) {
+        result["warnings"] = serde_json::json!([warning]);
+    }
+    Ok(result)
+}
+
+/// 原生 CRO (非 ERC20，链上读取与路由都通过 WCRO)
+pub(crate) fn is_native_cro(query: &str) -> bool {
+    query.trim().eq_ignore_ascii_case("cro")
+}
+
+/// 原生 CRO 对应的 WCRO 代币
+pub(crate) fn resolve_wcro(tokens: &[infra::token::Token]) -> Result<infra::token::Token> {
+    infra::token::resolve_token(tokens, "WCRO")
 }
 
 async fn estimate_price_impact_bps(
@@ -169,100 +270,11 @@ async fn estimate_price_impact_bps(
     if amount_in.is_zero() {
         return Ok(U256::ZERO);
     }
-    if path.len() < 2 {
-        return Ok(U256::ZERO);
-    }
-
-    let mut ideal_amount = amount_in;
-    let mut actual_amount = amount_in;
-
-    for hop in path.windows(2) {
-        let (reserve_in, reserve_out) = get_pair_reserves(factory, hop[0], hop[1], rpc).await?;
-        ideal_amount = compute_ideal_out(ideal_amount, reserve_in, reserve_out);
-        actual_amount = compute_actual_out(actual_amount, reserve_in, reserve_out);
-    }
-
-    if ideal_amount.is_zero() {
-        return Ok(U256::ZERO);
-    }
-
-    let diff = ideal_amount.saturating_sub(actual_amount);
-    Ok(diff.saturating_mul(U256::from(10_000u64)) / ideal_amount)
-}
-
-async fn get_pair_reserves(
-    factory: Address,
-    token_in: Address,
-    token_out: Address,
-    rpc: &infra::rpc::RpcClient,
-) -> Result<(U256, U256)> {
-    let call = abi::getPairCall {
-        tokenA: token_in,
-        tokenB: token_out,
-    }
-    .abi_encode();
-    let data = rpc.eth_call(factory, Bytes::from(call)).await?;
-    let decoded = abi::getPairCall::abi_decode_returns(&data, true)
-        .map_err(|err| CroLensError::RpcError(format!("getPair decode failed: {err}")))?;
-
-    if decoded.pair == Address::ZERO {
-        return Err(CroLensError::RpcError(
-            "Pair not found for price impact calculation".to_string(),
-        ));
-    }
-
-    let reserves_call = abi::getReservesCall {}.abi_encode();
-    let reserves_data = rpc
-        .eth_call(decoded.pair, Bytes::from(reserves_call))
-        .await?;
-    let reserves_ret = abi::getReservesCall::abi_decode_returns(&reserves_data, true)
-        .map_err(|err| CroLensError::RpcError(format!("getReserves decode failed: {err}")))?;
-
-    let reserve0 = U256::from(reserves_ret.reserve0);
-    let reserve1 = U256::from(reserves_ret.reserve1);
-    if token_in.as_slice() < token_out.as_slice() {
-        Ok((reserve0, reserve1))
-    } else {
-        Ok((reserve1, reserve0))
-    }
-}
-
-fn compute_ideal_out(amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
-    if reserve_in.is_zero() {
-        return U256::ZERO;
-    }
-    amount_in.saturating_mul(reserve_out) / reserve_in
-}
-
-fn compute_actual_out(amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
-    if reserve_in.is_zero() || reserve_out.is_zero() {
-        return U256::ZERO;
-    }
-
-    let amount_in_with_fee = amount_in.saturating_mul(U256::from(997u64));
-    let numerator = amount_in_with_fee.saturating_mul(reserve_out);
-    let denominator = reserve_in
-        .saturating_mul(U256::from(1000u64))
-        .saturating_add(amount_in_with_fee);
-    if denominator.is_zero() {
-        return U256::ZERO;
-    }
-    numerator / denominator
-}
-
-#[cfg(test)]
-fn calculate_price_impact_bps_single_pair(
-    amount_in: U256,
-    reserve_in: U256,
-    reserve_out: U256,
-) -> U256 {
-    let ideal_out = compute_ideal_out(amount_in, reserve_in, reserve_out);
-    if ideal_out.is_zero() {
-        return U256::ZERO;
+    let reserves = route::get_path_reserves(factory, path, rpc).await?;
+    for (reserve_in, reserve_out) in &reserves {
+        check_hop_reserves(*reserve_in, *reserve_out)?;
     }
-    let actual_out = compute_actual_out(amount_in, reserve_in, reserve_out);
-    let diff = ideal_out.saturating_sub(actual_out);
-    diff.saturating_mul(U256::from(10_000u64)) / ideal_out
+    Ok(route::price_impact_bps(amount_in, &reserves))
 }
 
 fn format_percent_from_basis_points(bp: U256) -> String {
@@ -278,60 +290,6 @@ fn format_percent_from_basis_points(bp: U256) -> String {
     format!("{}.{}", int_part, frac)
 }
 
-async fn build_path(
-    factory: Address,
-    wcro: Option<Address>,
-    token_in: Option<Address>,
-    token_out: Address,
-    rpc: &infra::rpc::RpcClient,
-) -> Result<Vec<Address>> {
-    let mut direct = Vec::new();
-    match token_in {
-        Some(addr_in) => {
-            direct.push(addr_in);
-            direct.push(token_out);
-        }
-        None => {
-            let Some(wcro_addr) = wcro else {
-                return Err(CroLensError::TokenNotFound("WCRO".to_string()));
-            };
-            direct.push(wcro_addr);
-            direct.push(token_out);
-        }
-    }
-
-    if is_pair_available(factory, direct[0], direct[1], rpc).await? {
-        return Ok(direct);
-    }
-
-    let Some(wcro_addr) = wcro else {
-        return Err(CroLensError::TokenNotFound("WCRO".to_string()));
-    };
-
-    if token_in.is_some() && direct[0] != wcro_addr && token_out != wcro_addr {
-        return Ok(vec![direct[0], wcro_addr, token_out]);
-    }
-
-    Ok(direct)
-}
-
-async fn is_pair_available(
-    factory: Address,
-    a: Address,
-    b: Address,
-    rpc: &infra::rpc::RpcClient,
-) -> Result<bool> {
-    let call = abi::getPairCall {
-        tokenA: a,
-        tokenB: b,
-    }
-    .abi_encode();
-    let data = rpc.eth_call(factory, Bytes::from(call)).await?;
-    let decoded = abi::getPairCall::abi_decode_returns(&data, true)
-        .map_err(|err| CroLensError::RpcError(format!("getPair decode failed: {err}")))?;
-    Ok(decoded.pair != Address::ZERO)
-}
-
 async fn quote_amounts(
     router: Address,
     amount_in: U256,
@@ -339,15 +297,7 @@ async fn quote_amounts(
     rpc: &infra::rpc::RpcClient,
     slippage_bps: u16,
 ) -> Result<(U256, U256)> {
-    let call = abi::getAmountsOutCall {
-        amountIn: amount_in,
-        path: path.to_vec(),
-    }
-    .abi_encode();
-    let data = rpc.eth_call(router, Bytes::from(call)).await?;
-    let decoded = abi::getAmountsOutCall::abi_decode_returns(&data, true)
-        .map_err(|err| CroLensError::RpcError(format!("getAmountsOut decode failed: {err}")))?;
-    let last = decoded.amounts.last().cloned().unwrap_or(U256::ZERO);
+    let last = route::quote_out(router, amount_in, path, rpc).await?;
     let minimum =
         last.saturating_mul(U256::from(10_000u64 - slippage_bps as u64)) / U256::from(10_000u64);
     Ok((last, minimum))
@@ -413,6 +363,78 @@ struct SwapCalldataParams<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn nonces_increment_across_approval_and_swap() {
+        let mut steps = vec![
+            serde_json::json!({
+                "step_index": 1,
+                "type": "approval",
+                "tx_data": { "to": "0x01", "data": "0x", "value": "0" }
+            }),
+            serde_json::json!({
+                "step_index": 2,
+                "type": "swap",
+                "tx_data": { "to": "0x02", "data": "0x", "value": "0" }
+            }),
+        ];
+        assign_nonces(&mut steps, 41);
+        assert_eq!(steps[0]["tx_data"]["nonce"], 41);
+        assert_eq!(steps[1]["tx_data"]["nonce"], 42);
+    }
+
+    #[test]
+    fn single_swap_step_uses_pending_nonce() {
+        let mut steps = vec![serde_json::json!({
+            "step_index": 1,
+            "type": "swap",
+            "tx_data": { "to": "0x02", "data": "0x", "value": "0" }
+        })];
+        assign_nonces(&mut steps, 0);
+        assert_eq!(steps[0]["tx_data"]["nonce"], 0);
+    }
+
+    #[test]
+    fn include_nonce_defaults_to_false() {
+        let args: SwapArgs = serde_json::from_value(serde_json::json!({
+            "from": "0x0000000000000000000000000000000000000001",
+            "token_in": "CRO",
+            "token_out": "USDC",
+            "amount_in": "1"
+        }))
+        .expect("args should parse");
+        assert!(!args.include_nonce);
+        assert_eq!(args.slippage_bps, None);
+    }
+
+    #[test]
+    fn slippage_defaults_when_omitted() {
+        assert_eq!(
+            resolve_slippage_bps(None, infra::DEFAULT_SLIPPAGE_BPS).expect("default"),
+            50
+        );
+        assert_eq!(
+            resolve_slippage_bps(None, 120).expect("configured default"),
+            120
+        );
+        assert_eq!(
+            resolve_slippage_bps(Some(0), 120).expect("explicit zero"),
+            0
+        );
+        assert!(resolve_slippage_bps(Some(MAX_SLIPPAGE_BPS + 1), 50).is_err());
+    }
+
+    #[test]
+    fn price_impact_above_slippage_only_warns() {
+        let warning = price_impact_warning(50, U256::from(200u64)).expect("warning");
+        assert_eq!(warning["type"], "high_price_impact");
+        let message = warning["message"].as_str().expect("message");
+        assert!(message.contains("2.00%"));
+        assert!(message.contains("slippage_bps 50"));
+
+        assert!(price_impact_warning(200, U256::from(200u64)).is_none());
+        assert!(price_impact_warning(50, U256::from(31u64)).is_none());
+    }
+
     #[test]
     fn builds_swap_exact_tokens_for_eth_when_native_out() {
         let router = types::parse_address("0x1111111111111111111111111111111111111111").unwrap();
@@ -449,29 +471,42 @@ mod tests {
     }
 
     #[test]
-    fn calculates_price_impact_bps_sane_ranges() {
-        let reserve_in = U256::from(1_000_000u64);
-        let reserve_out = U256::from(1_000_000u64);
+    fn formats_basis_points_as_percent_string() {
+        assert_eq!(format_percent_from_basis_points(U256::ZERO), "0.00");
+        assert_eq!(format_percent_from_basis_points(U256::from(5u64)), "0.05");
+        assert_eq!(format_percent_from_basis_points(U256::from(123u64)), "1.23");
+    }
 
-        let small =
-            calculate_price_impact_bps_single_pair(U256::from(1_000u64), reserve_in, reserve_out);
-        assert!(
-            small < U256::from(100u64),
-            "expected <1% for small trade, got {small}"
+    #[test]
+    fn zero_quote_is_rejected_as_insufficient_liquidity() {
+        let amount_in = U256::from(1_000_000u64);
+        let err = check_quote_has_liquidity(amount_in, U256::ZERO, U256::ZERO).unwrap_err();
+        assert!(matches!(err, CroLensError::InvalidParams(_)));
+        assert_eq!(
+            err.to_string(),
+            "Invalid params: Insufficient liquidity for this trade"
         );
 
-        let large =
-            calculate_price_impact_bps_single_pair(U256::from(200_000u64), reserve_in, reserve_out);
+        // 报价极小时 minimum_out 可能被滑点向下取整为 0
+        let err = check_quote_has_liquidity(amount_in, U256::from(1u64), U256::ZERO).unwrap_err();
+        assert!(err.to_string().contains("Insufficient liquidity"));
+
         assert!(
-            large > U256::from(1000u64),
-            "expected >10% for large trade, got {large}"
+            check_quote_has_liquidity(amount_in, U256::from(990u64), U256::from(985u64)).is_ok()
         );
     }
 
     #[test]
-    fn formats_basis_points_as_percent_string() {
-        assert_eq!(format_percent_from_basis_points(U256::ZERO), "0.00");
-        assert_eq!(format_percent_from_basis_points(U256::from(5u64)), "0.05");
-        assert_eq!(format_percent_from_basis_points(U256::from(123u64)), "1.23");
+    fn zero_amount_in_is_rejected() {
+        let err = check_quote_has_liquidity(U256::ZERO, U256::ZERO, U256::ZERO).unwrap_err();
+        assert!(err.to_string().contains("amount_in must be greater than 0"));
+    }
+
+    #[test]
+    fn empty_pool_reserves_are_rejected() {
+        let min = U256::from(MIN_POOL_RESERVE);
+        assert!(check_hop_reserves(U256::ZERO, U256::ZERO).is_err());
+        assert!(check_hop_reserves(min, min - U256::from(1u64)).is_err());
+        assert!(check_hop_reserves(min, min).is_ok());
     }
 }
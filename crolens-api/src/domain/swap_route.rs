@@ -1,8 +1,15 @@
+use alloy_primitives::{Address, U256};
 use serde::Deserialize;
 use serde_json::Value;
 
+use crate::domain::{route, swap};
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::types;
+
+/// 最优路径缓存；KV 的最短 TTL 为 60 秒
+const ROUTE_CACHE_PREFIX: &str = "swap_route:";
+const ROUTE_CACHE_TTL_SECS: u64 = 60;
 
 #[derive(Debug, Deserialize)]
 struct BestSwapRouteArgs {
@@ -13,29 +20,191 @@ struct BestSwapRouteArgs {
     simple_mode: bool,
 }
 
+/// 按 amount_in 的二进制位数分桶 (每桶跨度 2 倍)，数量接近的请求共用同一条路径
+fn amount_bucket(amount_in: U256) -> usize {
+    amount_in.bit_len()
+}
+
+fn route_cache_key(token_in: Address, token_out: Address, amount_in: U256) -> String {
+    format!(
+        "{ROUTE_CACHE_PREFIX}{}:{}:{}",
+        token_in.to_string().to_lowercase(),
+        token_out.to_string().to_lowercase(),
+        amount_bucket(amount_in)
+    )
+}
+
+/// 路由的一端；原生 CRO 与 construct_swap_tx 一致按 WCRO 路由，
+/// wrap/unwrap 为 1:1，报价数量不变
+#[derive(Debug, Clone)]
+struct RouteEnd {
+    token: infra::token::Token,
+    native: bool,
+}
+
+fn resolve_route_end(tokens: &[infra::token::Token], query: &str) -> Result<RouteEnd> {
+    if swap::is_native_cro(query) {
+        return Ok(RouteEnd {
+            token: swap::resolve_wcro(tokens)?,
+            native: true,
+        });
+    }
+    Ok(RouteEnd {
+        token: infra::token::resolve_token(tokens, query)?,
+        native: false,
+    })
+}
+
+fn resolve_route_ends(
+    tokens: &[infra::token::Token],
+    token_in: &str,
+    token_out: &str,
+) -> Result<(RouteEnd, RouteEnd)> {
+    if swap::is_native_cro(token_in) && swap::is_native_cro(token_out) {
+        return Err(CroLensError::invalid_params(
+            "token_in and token_out cannot both be CRO".to_string(),
+        ));
+    }
+    let token_in = resolve_route_end(tokens, token_in)?;
+    let token_out = resolve_route_end(tokens, token_out)?;
+    // CRO <-> WCRO 只是 wrap/unwrap，不经过 DEX
+    if token_in.token.address == token_out.token.address {
+        return Err(CroLensError::invalid_params(
+            "token_in and token_out must differ".to_string(),
+        ));
+    }
+    Ok((token_in, token_out))
+}
+
+/// simple_mode 中的路径符号；原生 CRO 的一端显示为 CRO
+fn hop_symbols(
+    path: &[Address],
+    tokens: &[infra::token::Token],
+    native_in: bool,
+    native_out: bool,
+) -> Vec<String> {
+    let last = path.len().saturating_sub(1);
+    path.iter()
+        .enumerate()
+        .map(|(i, addr)| {
+            if (i == 0 && native_in) || (i == last && native_out) {
+                return "CRO".to_string();
+            }
+            tokens
+                .iter()
+                .find(|t| t.address == *addr)
+                .map(|t| t.symbol.clone())
+                .unwrap_or_else(|| "?".to_string())
+        })
+        .collect()
+}
+
+/// 缓存内容只有路径本身；命中后仍按精确的 amount_in 重新报价
+fn parse_cached_path(raw: &str) -> Option<Vec<Address>> {
+    let items: Vec<String> = serde_json::from_str(raw).ok()?;
+    let path = items
+        .iter()
+        .map(|item| types::parse_address(item).ok())
+        .collect::<Option<Vec<_>>>()?;
+    (path.len() >= 2).then_some(path)
+}
+
+async fn cached_path(services: &infra::Services, key: &str) -> Option<Vec<Address>> {
+    let raw = services.kv_get_text(key).await?;
+    parse_cached_path(&raw)
+}
+
+async fn store_path(services: &infra::Services, key: &str, path: &[Address]) {
+    let items: Vec<String> = path.iter().map(|a| a.to_string()).collect();
+    if let Ok(json) = serde_json::to_string(&items) {
+        services.kv_put_text(key, json, ROUTE_CACHE_TTL_SECS).await;
+    }
+}
+
 pub async fn get_best_swap_route(services: &infra::Services, args: Value) -> Result<Value> {
     let input: BestSwapRouteArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
+    let amount_in = types::parse_u256_dec(&input.amount_in)?;
+    if amount_in.is_zero() {
+        return Err(CroLensError::invalid_params(
+            "amount_in must be greater than 0".to_string(),
+        ));
+    }
+    let rpc = services.rpc()?;
+
+    let tokens =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix).await?;
+    let (route_in, route_out) = resolve_route_ends(&tokens, &input.token_in, &input.token_out)?;
+    let (token_in, token_out) = (&route_in.token, &route_out.token);
+    let wcro = swap::resolve_wcro(&tokens).ok().map(|t| t.address);
+
     // Currently, VVS is the only supported DEX in this repo.
-    let route = serde_json::json!({
-        "dex": "vvs",
-        "path": [input.token_in, input.token_out],
-        "estimated_out": Value::Null,
-    });
+    let router = services.protocol_contract("vvs", "router").await?;
+
+    let cache_key = services.kv_key(&route_cache_key(
+        token_in.address,
+        token_out.address,
+        amount_in,
+    ));
+    let (path, cached) = match cached_path(services, &cache_key).await {
+        Some(path) => (path, true),
+        None => {
+            let factory = services.protocol_contract("vvs", "factory").await?;
+            let path = route::build_path(
+                factory,
+                router,
+                amount_in,
+                wcro,
+                // 与 construct_swap_tx 相同：原生 CRO 输入从 WCRO 起步
+                (!route_in.native).then_some(token_in.address),
+                token_out.address,
+                rpc,
+            )
+            .await?;
+            store_path(services, &cache_key, &path).await;
+            (path, false)
+        }
+    };
+
+    let estimated_out = route::quote_out(router, amount_in, &path, rpc).await?;
+    let estimated_out_formatted = types::format_units(&estimated_out, token_out.decimals);
+    let out_symbol = if route_out.native {
+        "CRO"
+    } else {
+        token_out.symbol.as_str()
+    };
+
+    let mut meta = services.meta();
+    meta["cached"] = Value::Bool(cached);
 
     if input.simple_mode {
+        let hops = hop_symbols(&path, &tokens, route_in.native, route_out.native);
         return Ok(serde_json::json!({
-            "text": "Best swap route: vvs (placeholder).",
-            "meta": services.meta(),
+            "text": format!(
+                "Best swap route: vvs {} | Est. out: {} {}",
+                hops.join(" -> "),
+                estimated_out_formatted,
+                out_symbol
+            ),
+            "meta": meta,
         }));
     }
 
+    let route = serde_json::json!({
+        "dex": "vvs",
+        "path": path.iter().map(|a| a.to_string()).collect::<Vec<_>>(),
+        "estimated_out": estimated_out.to_string(),
+        "estimated_out_formatted": estimated_out_formatted,
+        "native_in": route_in.native,
+        "native_out": route_out.native,
+    });
+
     Ok(serde_json::json!({
         "amount_in": input.amount_in,
         "best_route": route,
         "routes": [route],
-        "meta": services.meta(),
+        "meta": meta,
     }))
 }
 
@@ -79,6 +248,137 @@ mod tests {
         assert!(result.is_err());
     }
 
+    fn addr(byte: u8) -> Address {
+        Address::repeat_byte(byte)
+    }
+
+    #[test]
+    fn amounts_in_the_same_power_of_two_share_a_bucket() {
+        let one = U256::from(10u64).pow(U256::from(18u64));
+        assert_eq!(
+            amount_bucket(one),
+            amount_bucket(one + U256::from(12_345u64))
+        );
+        assert_eq!(
+            amount_bucket(U256::from(1024u64)),
+            amount_bucket(U256::from(2047u64))
+        );
+        assert_ne!(
+            amount_bucket(U256::from(1023u64)),
+            amount_bucket(U256::from(1024u64))
+        );
+        assert!(amount_bucket(one * U256::from(1000u64)) > amount_bucket(one));
+        assert_eq!(amount_bucket(U256::MAX), 256);
+    }
+
+    #[test]
+    fn route_cache_key_is_stable_per_direction_and_bucket() {
+        let amount = U256::from(1_500_000u64);
+        let key = route_cache_key(addr(0xab), addr(0xcd), amount);
+        assert_eq!(
+            key,
+            format!("swap_route:0x{}:0x{}:21", "ab".repeat(20), "cd".repeat(20))
+        );
+        assert_eq!(
+            key,
+            route_cache_key(addr(0xab), addr(0xcd), U256::from(1_100_000u64))
+        );
+        assert_ne!(key, route_cache_key(addr(0xcd), addr(0xab), amount));
+        assert_ne!(
+            key,
+            route_cache_key(addr(0xab), addr(0xcd), amount * U256::from(4u64))
+        );
+    }
+
+    #[test]
+    fn cached_path_round_trips_and_rejects_garbage() {
+        let path = vec![addr(0x11), addr(0x22), addr(0x33)];
+        let raw = serde_json::to_string(&path.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+            .expect("serializes");
+        assert_eq!(parse_cached_path(&raw), Some(path));
+        assert_eq!(parse_cached_path("not json"), None);
+        assert_eq!(
+            parse_cached_path(r#"["0x1111111111111111111111111111111111111111"]"#),
+            None
+        );
+        assert_eq!(parse_cached_path(r#"["0x11", "0x22"]"#), None);
+    }
+
+    fn token(symbol: &str, byte: u8) -> infra::token::Token {
+        infra::token::Token {
+            address: addr(byte),
+            symbol: symbol.to_string(),
+            decimals: 18,
+            is_stablecoin: false,
+            is_spam: false,
+        }
+    }
+
+    fn sample_tokens() -> Vec<infra::token::Token> {
+        vec![token("WCRO", 0x01), token("USDC", 0x02), token("VVS", 0x03)]
+    }
+
+    #[test]
+    fn native_cro_in_routes_from_wcro() {
+        let (token_in, token_out) =
+            resolve_route_ends(&sample_tokens(), "CRO", "USDC").expect("CRO-in resolves");
+        assert!(token_in.native);
+        assert_eq!(token_in.token.address, addr(0x01));
+        assert!(!token_out.native);
+        assert_eq!(token_out.token.address, addr(0x02));
+    }
+
+    #[test]
+    fn native_cro_out_routes_to_wcro() {
+        let (token_in, token_out) =
+            resolve_route_ends(&sample_tokens(), "vvs", " cro ").expect("CRO-out resolves");
+        assert!(!token_in.native);
+        assert_eq!(token_in.token.address, addr(0x03));
+        assert!(token_out.native);
+        assert_eq!(token_out.token.address, addr(0x01));
+    }
+
+    #[test]
+    fn cro_to_cro_and_wrap_only_routes_are_rejected() {
+        let both = resolve_route_ends(&sample_tokens(), "CRO", "cro");
+        assert!(
+            matches!(both, Err(CroLensError::InvalidParams(msg)) if msg.contains("both be CRO"))
+        );
+        let wrap = resolve_route_ends(&sample_tokens(), "CRO", "WCRO");
+        assert!(
+            matches!(wrap, Err(CroLensError::InvalidParams(msg)) if msg.contains("must differ"))
+        );
+    }
+
+    #[test]
+    fn native_cro_requires_wcro_in_token_list() {
+        let tokens = vec![token("USDC", 0x02)];
+        assert!(matches!(
+            resolve_route_ends(&tokens, "CRO", "USDC"),
+            Err(CroLensError::TokenNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn hop_symbols_show_native_ends_as_cro() {
+        let path = vec![addr(0x01), addr(0x03), addr(0x02)];
+        let tokens = sample_tokens();
+        assert_eq!(
+            hop_symbols(&path, &tokens, true, false),
+            ["CRO", "VVS", "USDC"]
+        );
+        let reverse = vec![addr(0x02), addr(0x01)];
+        assert_eq!(hop_symbols(&reverse, &tokens, false, true), ["USDC", "CRO"]);
+        assert_eq!(
+            hop_symbols(&reverse, &tokens, false, false),
+            ["USDC", "WCRO"]
+        );
+        assert_eq!(
+            hop_symbols(&[addr(0x09), addr(0x02)], &tokens, false, false),
+            ["?", "USDC"]
+        );
+    }
+
     #[test]
     fn args_rejects_missing_amount_in() {
         let json = serde_json::json!({
@@ -1,8 +1,19 @@
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::SolCall;
 use serde::Deserialize;
 use serde_json::Value;
 
+use crate::abi;
+use crate::domain::defi;
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::types;
+
+/// exchangeRate 的缩放精度 (Compound 风格 mantissa)
+const EXCHANGE_RATE_SCALE: u128 = 1_000_000_000_000_000_000;
+
+/// 每个市场读取 totalSupply / totalBorrows / getCash / totalReserves / exchangeRateStored
+const CALLS_PER_MARKET: usize = 5;
 
 #[derive(Debug, Deserialize)]
 struct SimpleModeArgs {
@@ -14,87 +25,607 @@ pub async fn get_tectonic_markets(services: &infra::Services, args: Value) -> Re
     let input: SimpleModeArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let markets =
-        infra::config::list_lending_markets_cached(&services.db, &services.kv, "tectonic").await?;
-    let out: Vec<Value> = markets
+    let markets = infra::config::list_lending_markets_cached(
+        &services.db,
+        &services.kv,
+        &services.kv_prefix,
+        "tectonic",
+    )
+    .await?;
+
+    let mut calls = Vec::with_capacity(markets.len() * CALLS_PER_MARKET);
+    for m in &markets {
+        let encoded: [Vec<u8>; CALLS_PER_MARKET] = [
+            abi::totalSupplyCall {}.abi_encode(),
+            abi::totalBorrowsCall {}.abi_encode(),
+            abi::getCashCall {}.abi_encode(),
+            abi::totalReservesCall {}.abi_encode(),
+            abi::exchangeRateStoredCall {}.abi_encode(),
+        ];
+        for call_data in encoded {
+            calls.push(infra::multicall::Call {
+                target: m.ctoken_address,
+                call_data: call_data.into(),
+            });
+        }
+    }
+
+    let tokens =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix).await?;
+    let underlying_tokens: Vec<infra::token::Token> = tokens
         .into_iter()
-        .map(|m| {
-            serde_json::json!({
-                "ctoken_address": m.ctoken_address.to_string(),
-                "underlying_address": m.underlying_address.to_string(),
-                "underlying_symbol": m.underlying_symbol,
-                "collateral_factor": m.collateral_factor,
-            })
-        })
+        .filter(|t| markets.iter().any(|m| m.underlying_address == t.address))
         .collect();
 
+    // 链上快照与价格各自降级：任一失败只让对应字段为 null，市场列表照常返回
+    let (results, price_map) = futures_util::future::join(
+        async {
+            if calls.is_empty() {
+                Ok(Vec::new())
+            } else {
+                services.multicall()?.aggregate(calls).await
+            }
+        },
+        infra::price::get_prices_usd_batch(services, &underlying_tokens),
+    )
+    .await;
+    let results = results.unwrap_or_default();
+    let price_map = price_map.unwrap_or_default();
+
+    let mut totals = MarketTotalsUsd::default();
+    let mut out: Vec<Value> = Vec::with_capacity(markets.len());
+    for (i, m) in markets.into_iter().enumerate() {
+        let data = |offset: usize| {
+            results
+                .get(i * CALLS_PER_MARKET + offset)
+                .and_then(|r| r.as_ref().ok())
+        };
+        let snapshot = MarketSnapshot {
+            total_supply_ctoken: data(0)
+                .and_then(|d| abi::totalSupplyCall::abi_decode_returns(d, true).ok())
+                .map(|v| v._0),
+            total_borrows: data(1)
+                .and_then(|d| abi::totalBorrowsCall::abi_decode_returns(d, true).ok())
+                .map(|v| v._0),
+            cash: data(2)
+                .and_then(|d| abi::getCashCall::abi_decode_returns(d, true).ok())
+                .map(|v| v._0),
+            reserves: data(3)
+                .and_then(|d| abi::totalReservesCall::abi_decode_returns(d, true).ok())
+                .map(|v| v._0),
+            exchange_rate: data(4)
+                .and_then(|d| abi::exchangeRateStoredCall::abi_decode_returns(d, true).ok())
+                .map(|v| v._0),
+        };
+
+        let token = underlying_tokens
+            .iter()
+            .find(|t| t.address == m.underlying_address);
+        let decimals = token.map(|t| t.decimals).unwrap_or(18);
+        let price = token.and_then(|t| price_map.get(&t.address).copied());
+
+        let total_supply_underlying = snapshot.total_supply_underlying();
+        let supply_usd = amount_usd(total_supply_underlying, decimals, price);
+        let borrow_usd = amount_usd(snapshot.total_borrows, decimals, price);
+        let cash_usd = amount_usd(snapshot.cash, decimals, price);
+        let reserves_usd = amount_usd(snapshot.reserves, decimals, price);
+        totals.add(supply_usd, borrow_usd, reserves_usd);
+
+        let formatted = |v: Option<U256>| v.map(|v| types::format_units(&v, decimals));
+        let usd = |v: Option<f64>| v.map(|v| services.precision.usd(v));
+        out.push(serde_json::json!({
+            "ctoken_address": m.ctoken_address.to_string(),
+            "underlying_address": m.underlying_address.to_string(),
+            "underlying_symbol": m.underlying_symbol,
+            "collateral_factor": m.collateral_factor,
+            "exchange_rate": snapshot.exchange_rate.map(|v| v.to_string()),
+            "total_supply": formatted(total_supply_underlying),
+            "total_borrows": formatted(snapshot.total_borrows),
+            "cash": formatted(snapshot.cash),
+            "reserves": formatted(snapshot.reserves),
+            "price_usd": price.map(|p| services.precision.price(p)),
+            "total_supply_usd": usd(supply_usd),
+            "total_borrows_usd": usd(borrow_usd),
+            "cash_usd": usd(cash_usd),
+            "reserves_usd": usd(reserves_usd),
+        }));
+    }
+
     if input.simple_mode {
         return Ok(serde_json::json!({
-            "text": format!("Tectonic markets: {}", out.len()),
+            "text": format!(
+                "Tectonic markets: {} | Supplied: ${:.2} | Borrowed: ${:.2}",
+                out.len(),
+                totals.supply_usd,
+                totals.borrow_usd
+            ),
             "meta": services.meta(),
         }));
     }
 
-    Ok(serde_json::json!({ "markets": out, "meta": services.meta() }))
+    Ok(serde_json::json!({
+        "markets": out,
+        "totals": {
+            "total_supply_usd": services.precision.usd(totals.supply_usd),
+            "total_borrows_usd": services.precision.usd(totals.borrow_usd),
+            "total_reserves_usd": services.precision.usd(totals.reserves_usd),
+        },
+        "meta": services.meta(),
+    }))
+}
+
+/// 单个 cToken 市场的链上读数；读取失败的字段为 None
+#[derive(Debug, Default)]
+struct MarketSnapshot {
+    total_supply_ctoken: Option<U256>,
+    total_borrows: Option<U256>,
+    cash: Option<U256>,
+    reserves: Option<U256>,
+    exchange_rate: Option<U256>,
+}
+
+impl MarketSnapshot {
+    fn total_supply_underlying(&self) -> Option<U256> {
+        Some(underlying_from_ctoken(
+            self.total_supply_ctoken?,
+            self.exchange_rate?,
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+struct MarketTotalsUsd {
+    supply_usd: f64,
+    borrow_usd: f64,
+    reserves_usd: f64,
+}
+
+impl MarketTotalsUsd {
+    fn add(&mut self, supply: Option<f64>, borrow: Option<f64>, reserves: Option<f64>) {
+        self.supply_usd += supply.unwrap_or(0.0);
+        self.borrow_usd += borrow.unwrap_or(0.0);
+        self.reserves_usd += reserves.unwrap_or(0.0);
+    }
+}
+
+/// cToken 数量 -> underlying 数量 (基础单位)：ctoken * exchangeRate / 1e18
+pub(crate) fn underlying_from_ctoken(ctoken_amount: U256, exchange_rate: U256) -> U256 {
+    ctoken_amount.saturating_mul(exchange_rate) / U256::from(EXCHANGE_RATE_SCALE)
+}
+
+fn amount_usd(amount: Option<U256>, decimals: u8, price_usd: Option<f64>) -> Option<f64> {
+    let amount: f64 = types::format_units(&amount?, decimals).parse().ok()?;
+    let usd = amount * price_usd?;
+    usd.is_finite().then_some(usd)
 }
 
 #[derive(Debug, Deserialize)]
 struct TectonicRatesArgs {
     #[serde(default)]
     asset: Option<String>,
+    /// 假设的操作，与 amount、asset 一起用于预测操作后的利率
+    #[serde(default)]
+    action: Option<RateAction>,
+    /// underlying 基础单位 (十进制字符串)
+    #[serde(default)]
+    amount: Option<String>,
     #[serde(default)]
     simple_mode: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RateAction {
+    Supply,
+    Borrow,
+}
+
+impl RateAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Supply => "supply",
+            Self::Borrow => "borrow",
+        }
+    }
+}
+
+/// 每个市场读取 supplyRatePerBlock / borrowRatePerBlock
+const RATE_CALLS_PER_MARKET: usize = 2;
+
+/// 预测市场额外读取 getCash / totalBorrows / totalReserves / reserveFactorMantissa / interestRateModel
+const PROJECTION_MARKET_CALLS: usize = 5;
+
+/// 校验预测参数：action 需要 amount 和 asset，单独给出 amount 无意义
+fn projection_request(input: &TectonicRatesArgs) -> Result<Option<(RateAction, U256)>> {
+    match (input.action, input.amount.as_deref()) {
+        (None, None) => Ok(None),
+        (None, Some(_)) => Err(CroLensError::invalid_params(
+            "amount requires action (supply or borrow)".to_string(),
+        )),
+        (Some(_), None) => Err(CroLensError::invalid_params(
+            "action requires amount (underlying base units)".to_string(),
+        )),
+        (Some(action), Some(amount)) => {
+            if input
+                .asset
+                .as_deref()
+                .map(str::trim)
+                .unwrap_or("")
+                .is_empty()
+            {
+                return Err(CroLensError::invalid_params(
+                    "action requires asset".to_string(),
+                ));
+            }
+            let amount = types::parse_u256_dec(amount)?;
+            if amount.is_zero() {
+                return Err(CroLensError::invalid_params(
+                    "amount must be greater than 0".to_string(),
+                ));
+            }
+            Ok(Some((action, amount)))
+        }
+    }
+}
+
+/// 市场流动性 (underlying 基础单位)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MarketLiquidity {
+    cash: U256,
+    borrows: U256,
+    reserves: U256,
+}
+
+impl MarketLiquidity {
+    /// Compound 利用率 mantissa：borrows / (cash + borrows - reserves)，无借款时为 0
+    fn utilization(&self) -> U256 {
+        if self.borrows.is_zero() {
+            return U256::ZERO;
+        }
+        let denominator = self
+            .cash
+            .saturating_add(self.borrows)
+            .saturating_sub(self.reserves);
+        if denominator.is_zero() {
+            return U256::ZERO;
+        }
+        self.borrows.saturating_mul(U256::from(EXCHANGE_RATE_SCALE)) / denominator
+    }
+
+    /// 假设操作之后的流动性；借款超过可用现金时报错
+    fn after(&self, action: RateAction, amount: U256) -> Result<Self> {
+        match action {
+            RateAction::Supply => Ok(Self {
+                cash: self.cash.saturating_add(amount),
+                ..*self
+            }),
+            RateAction::Borrow => {
+                if amount > self.cash {
+                    return Err(CroLensError::invalid_params(format!(
+                        "borrow amount {amount} exceeds available market cash {}",
+                        self.cash
+                    )));
+                }
+                Ok(Self {
+                    cash: self.cash - amount,
+                    borrows: self.borrows.saturating_add(amount),
+                    reserves: self.reserves,
+                })
+            }
+        }
+    }
+}
+
+/// JumpRateModel 参数 (每区块利率，1e18 mantissa)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct JumpRateModel {
+    base_rate_per_block: U256,
+    multiplier_per_block: U256,
+    jump_multiplier_per_block: U256,
+    kink: U256,
+}
+
+impl JumpRateModel {
+    fn borrow_rate(&self, utilization: U256) -> U256 {
+        let scale = U256::from(EXCHANGE_RATE_SCALE);
+        let linear = |util: U256| util.saturating_mul(self.multiplier_per_block) / scale;
+        if utilization <= self.kink {
+            return linear(utilization).saturating_add(self.base_rate_per_block);
+        }
+        let normal_rate = linear(self.kink).saturating_add(self.base_rate_per_block);
+        let excess = utilization - self.kink;
+        normal_rate.saturating_add(excess.saturating_mul(self.jump_multiplier_per_block) / scale)
+    }
+
+    /// supplyRate = utilization * borrowRate * (1 - reserveFactor)
+    fn supply_rate(&self, utilization: U256, reserve_factor: U256) -> U256 {
+        let scale = U256::from(EXCHANGE_RATE_SCALE);
+        let one_minus_reserve = scale.saturating_sub(reserve_factor);
+        let rate_to_pool = self
+            .borrow_rate(utilization)
+            .saturating_mul(one_minus_reserve)
+            / scale;
+        utilization.saturating_mul(rate_to_pool) / scale
+    }
+}
+
+fn utilization_percent(utilization: U256, precision: &types::Precision) -> String {
+    let util = utilization.to_string().parse::<f64>().unwrap_or(0.0) / EXCHANGE_RATE_SCALE as f64;
+    format!("{}%", precision.pct(util * 100.0))
+}
+
+fn rate_state_json(
+    liquidity: &MarketLiquidity,
+    model: &JumpRateModel,
+    reserve_factor: U256,
+    precision: &types::Precision,
+) -> Value {
+    let utilization = liquidity.utilization();
+    serde_json::json!({
+        "utilization": utilization_percent(utilization, precision),
+        "supply_apy": defi::apy_percent_string(model.supply_rate(utilization, reserve_factor)),
+        "borrow_apy": defi::apy_percent_string(model.borrow_rate(utilization)),
+    })
+}
+
 fn normalize_asset_filter(asset: &Option<String>) -> Option<String> {
     asset.as_ref().map(|s| s.trim().to_lowercase())
 }
 
-fn symbol_matches_asset_filter(symbol: &str, asset_filter: Option<&str>) -> bool {
+pub(crate) fn symbol_matches_asset_filter(symbol: &str, asset_filter: Option<&str>) -> bool {
     match asset_filter {
         Some(f) => symbol.trim().eq_ignore_ascii_case(f),
         None => true,
     }
 }
 
+/// 每个市场的 supplyRatePerBlock / borrowRatePerBlock 调用 (get_lending_rates 复用)
+pub(crate) fn rate_calls(markets: &[infra::config::LendingMarket]) -> Vec<infra::multicall::Call> {
+    let mut calls = Vec::with_capacity(markets.len() * RATE_CALLS_PER_MARKET);
+    for m in markets {
+        for call_data in [
+            abi::supplyRatePerBlockCall {}.abi_encode(),
+            abi::borrowRatePerBlockCall {}.abi_encode(),
+        ] {
+            calls.push(infra::multicall::Call {
+                target: m.ctoken_address,
+                call_data: call_data.into(),
+            });
+        }
+    }
+    calls
+}
+
+/// `rate_calls` 结果中第 `index` 个市场的 (supply, borrow) 每区块利率
+pub(crate) fn market_rates(
+    results: &infra::multicall::CallResults,
+    index: usize,
+) -> (Option<U256>, Option<U256>) {
+    let data = |i: usize| results.get(i).and_then(|r| r.as_ref().ok());
+    let supply_rate = data(index * RATE_CALLS_PER_MARKET)
+        .and_then(|d| abi::supplyRatePerBlockCall::abi_decode_returns(d, true).ok())
+        .map(|v| v._0);
+    let borrow_rate = data(index * RATE_CALLS_PER_MARKET + 1)
+        .and_then(|d| abi::borrowRatePerBlockCall::abi_decode_returns(d, true).ok())
+        .map(|v| v._0);
+    (supply_rate, borrow_rate)
+}
+
 pub async fn get_tectonic_rates(services: &infra::Services, args: Value) -> Result<Value> {
     let input: TectonicRatesArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
+    let projection = projection_request(&input)?;
 
-    let markets =
-        infra::config::list_lending_markets_cached(&services.db, &services.kv, "tectonic").await?;
+    let markets = infra::config::list_lending_markets_cached(
+        &services.db,
+        &services.kv,
+        &services.kv_prefix,
+        "tectonic",
+    )
+    .await?;
 
     let asset_filter = normalize_asset_filter(&input.asset);
-    let out: Vec<Value> = markets
+    let markets: Vec<infra::config::LendingMarket> = markets
         .into_iter()
         .filter(|m| symbol_matches_asset_filter(&m.underlying_symbol, asset_filter.as_deref()))
-        .map(|m| {
-            serde_json::json!({
-                "underlying_symbol": m.underlying_symbol,
-                "supply_apy": Value::Null,
-                "borrow_apy": Value::Null,
-            })
-        })
         .collect();
+    if projection.is_some() && markets.len() != 1 {
+        return Err(CroLensError::invalid_params(format!(
+            "Unknown Tectonic asset: {}",
+            input.asset.as_deref().unwrap_or_default()
+        )));
+    }
+
+    let mut calls = rate_calls(&markets);
+    if let (Some(_), Some(m)) = (projection, markets.first()) {
+        let encoded: [Vec<u8>; PROJECTION_MARKET_CALLS] = [
+            abi::getCashCall {}.abi_encode(),
+            abi::totalBorrowsCall {}.abi_encode(),
+            abi::totalReservesCall {}.abi_encode(),
+            abi::reserveFactorMantissaCall {}.abi_encode(),
+            abi::interestRateModelCall {}.abi_encode(),
+        ];
+        for call_data in encoded {
+            calls.push(infra::multicall::Call {
+                target: m.ctoken_address,
+                call_data: call_data.into(),
+            });
+        }
+    }
+
+    let results = if calls.is_empty() {
+        Vec::new()
+    } else {
+        services.multicall()?.aggregate(calls).await?
+    };
+    let data = |index: usize| results.get(index).and_then(|r| r.as_ref().ok());
+
+    let mut out: Vec<Value> = Vec::with_capacity(markets.len());
+    for (i, m) in markets.iter().enumerate() {
+        let (supply_rate, borrow_rate) = market_rates(&results, i);
+        out.push(serde_json::json!({
+            "underlying_symbol": m.underlying_symbol,
+            "supply_apy": supply_rate.and_then(defi::apy_percent_string),
+            "borrow_apy": borrow_rate.and_then(defi::apy_percent_string),
+        }));
+    }
+
+    let mut projection_out = Value::Null;
+    if let Some((action, amount)) = projection {
+        let base = markets.len() * RATE_CALLS_PER_MARKET;
+        let missing =
+            |what: &str| CroLensError::RpcError(format!("Failed to read Tectonic {what}"));
+        let liquidity = MarketLiquidity {
+            cash: data(base)
+                .and_then(|d| abi::getCashCall::abi_decode_returns(d, true).ok())
+                .map(|v| v._0)
+                .ok_or_else(|| missing("getCash"))?,
+            borrows: data(base + 1)
+                .and_then(|d| abi::totalBorrowsCall::abi_decode_returns(d, true).ok())
+                .map(|v| v._0)
+                .ok_or_else(|| missing("totalBorrows"))?,
+            reserves: data(base + 2)
+                .and_then(|d| abi::totalReservesCall::abi_decode_returns(d, true).ok())
+                .map(|v| v._0)
+                .ok_or_else(|| missing("totalReserves"))?,
+        };
+        let reserve_factor = data(base + 3)
+            .and_then(|d| abi::reserveFactorMantissaCall::abi_decode_returns(d, true).ok())
+            .map(|v| v._0)
+            .ok_or_else(|| missing("reserveFactorMantissa"))?;
+        let model_address = data(base + 4)
+            .and_then(|d| abi::interestRateModelCall::abi_decode_returns(d, true).ok())
+            .map(|v| v._0)
+            .ok_or_else(|| missing("interestRateModel"))?;
+        let model = read_jump_rate_model(services.multicall()?, model_address).await?;
+        let projected = liquidity.after(action, amount)?;
+
+        projection_out = serde_json::json!({
+            "action": action.as_str(),
+            "amount": amount.to_string(),
+            "interest_rate_model": model_address.to_string(),
+            "current": rate_state_json(&liquidity, &model, reserve_factor, &services.precision),
+            "projected": rate_state_json(&projected, &model, reserve_factor, &services.precision),
+        });
+    }
 
     if input.simple_mode {
+        let text = match projection_out.get("projected") {
+            Some(projected) => format!(
+                "Tectonic {} after {}: supply {} -> {}, borrow {} -> {}",
+                input.asset.as_deref().unwrap_or_default(),
+                projection_out["action"].as_str().unwrap_or_default(),
+                projection_out["current"]["supply_apy"]
+                    .as_str()
+                    .unwrap_or("?"),
+                projected["supply_apy"].as_str().unwrap_or("?"),
+                projection_out["current"]["borrow_apy"]
+                    .as_str()
+                    .unwrap_or("?"),
+                projected["borrow_apy"].as_str().unwrap_or("?"),
+            ),
+            None => format!("Tectonic rates: {}", out.len()),
+        };
         return Ok(serde_json::json!({
-            "text": format!("Tectonic rates: {}", out.len()),
+            "text": text,
             "meta": services.meta(),
         }));
     }
 
-    Ok(serde_json::json!({
+    let mut result = serde_json::json!({
         "asset": input.asset,
         "rates": out,
         "meta": services.meta(),
-    }))
+    });
+    if !projection_out.is_null() {
+        result["projection"] = projection_out;
+    }
+    Ok(result)
+}
+
+/// 读取利率模型的 JumpRateModel 参数；任一参数缺失时报错 (非 jump-rate 模型无法预测)
+async fn read_jump_rate_model(
+    multicall: &infra::multicall::MulticallClient,
+    model: Address,
+) -> Result<JumpRateModel> {
+    let calls: Vec<infra::multicall::Call> = [
+        abi::baseRatePerBlockCall {}.abi_encode(),
+        abi::multiplierPerBlockCall {}.abi_encode(),
+        abi::jumpMultiplierPerBlockCall {}.abi_encode(),
+        abi::kinkCall {}.abi_encode(),
+    ]
+    .into_iter()
+    .map(|call_data| infra::multicall::Call {
+        target: model,
+        call_data: call_data.into(),
+    })
+    .collect();
+    let results = multicall.aggregate(calls).await?;
+    let data = |i: usize| results.get(i).and_then(|r| r.as_ref().ok());
+    let missing = || {
+        CroLensError::RpcError(format!(
+            "Interest rate model {model} is not a jump-rate model"
+        ))
+    };
+    Ok(JumpRateModel {
+        base_rate_per_block: data(0)
+            .and_then(|d| abi::baseRatePerBlockCall::abi_decode_returns(d, true).ok())
+            .map(|v| v._0)
+            .ok_or_else(missing)?,
+        multiplier_per_block: data(1)
+            .and_then(|d| abi::multiplierPerBlockCall::abi_decode_returns(d, true).ok())
+            .map(|v| v._0)
+            .ok_or_else(missing)?,
+        jump_multiplier_per_block: data(2)
+            .and_then(|d| abi::jumpMultiplierPerBlockCall::abi_decode_returns(d, true).ok())
+            .map(|v| v._0)
+            .ok_or_else(missing)?,
+        kink: data(3)
+            .and_then(|d| abi::kinkCall::abi_decode_returns(d, true).ok())
+            .map(|v| v._0)
+            .ok_or_else(missing)?,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn e18(v: u64) -> U256 {
+        U256::from(v) * U256::from(EXCHANGE_RATE_SCALE)
+    }
+
+    #[test]
+    fn underlying_from_ctoken_applies_exchange_rate() {
+        // 8 位小数的 cToken，exchangeRate 0.02 * 1e18 * 1e10 (underlying 18 位)
+        let ctoken = U256::from(50_000_000_000u64); // 500 cToken
+        let rate = U256::from(200_000_000_000_000_000_000_000_000u128);
+        assert_eq!(underlying_from_ctoken(ctoken, rate), e18(10));
+    }
+
+    #[test]
+    fn underlying_from_ctoken_identity_rate() {
+        assert_eq!(underlying_from_ctoken(e18(7), e18(1)), e18(7));
+        assert_eq!(underlying_from_ctoken(e18(7), U256::ZERO), U256::ZERO);
+    }
+
+    #[test]
+    fn snapshot_supply_requires_exchange_rate() {
+        let snapshot = MarketSnapshot {
+            total_supply_ctoken: Some(e18(3)),
+            ..Default::default()
+        };
+        assert_eq!(snapshot.total_supply_underlying(), None);
+    }
+
+    #[test]
+    fn amount_usd_uses_decimals_and_price() {
+        let usdc = U256::from(2_500_000u64); // 2.5 USDC (6 位)
+        assert_eq!(amount_usd(Some(usdc), 6, Some(1.0)), Some(2.5));
+        assert_eq!(amount_usd(Some(usdc), 6, None), None);
+        assert_eq!(amount_usd(None, 6, Some(1.0)), None);
+    }
+
     #[test]
     fn normalize_asset_filter_trims_and_lowercases() {
         assert_eq!(normalize_asset_filter(&None), None);
@@ -128,6 +659,124 @@ mod tests {
         assert!(!args.simple_mode);
     }
 
+    fn liquidity(cash: u64, borrows: u64, reserves: u64) -> MarketLiquidity {
+        MarketLiquidity {
+            cash: e18(cash),
+            borrows: e18(borrows),
+            reserves: e18(reserves),
+        }
+    }
+
+    /// 利用率 / 利率的 mantissa (1e18 = 100%)
+    fn pct_mantissa(pct: u64) -> U256 {
+        U256::from(pct) * U256::from(EXCHANGE_RATE_SCALE) / U256::from(100u64)
+    }
+
+    fn sample_model() -> JumpRateModel {
+        JumpRateModel {
+            base_rate_per_block: U256::from(1_000u64),
+            multiplier_per_block: U256::from(100_000u64),
+            jump_multiplier_per_block: U256::from(2_000_000u64),
+            kink: pct_mantissa(80),
+        }
+    }
+
+    #[test]
+    fn utilization_excludes_reserves_and_handles_no_borrows() {
+        assert_eq!(liquidity(600, 400, 0).utilization(), pct_mantissa(40));
+        // 400 / (700 + 400 - 100)
+        assert_eq!(liquidity(700, 400, 100).utilization(), pct_mantissa(40));
+        assert_eq!(liquidity(1_000, 0, 0).utilization(), U256::ZERO);
+    }
+
+    #[test]
+    fn projected_supply_lowers_utilization() {
+        let current = liquidity(600, 400, 0);
+        let projected = current
+            .after(RateAction::Supply, e18(1_000))
+            .expect("supply is always allowed");
+        assert_eq!(projected.cash, e18(1_600));
+        assert_eq!(projected.borrows, e18(400));
+        assert_eq!(projected.utilization(), pct_mantissa(20));
+    }
+
+    #[test]
+    fn projected_borrow_raises_utilization() {
+        let current = liquidity(600, 400, 0);
+        let projected = current
+            .after(RateAction::Borrow, e18(200))
+            .expect("enough cash");
+        assert_eq!(projected.cash, e18(400));
+        assert_eq!(projected.borrows, e18(600));
+        assert_eq!(projected.utilization(), pct_mantissa(60));
+    }
+
+    #[test]
+    fn projected_borrow_cannot_exceed_cash() {
+        let err = liquidity(100, 400, 0)
+            .after(RateAction::Borrow, e18(101))
+            .expect_err("insufficient cash");
+        assert!(matches!(err, CroLensError::InvalidParams(_)));
+    }
+
+    #[test]
+    fn jump_rate_model_applies_kink() {
+        let model = sample_model();
+        // 低于 kink: base + util * multiplier
+        assert_eq!(
+            model.borrow_rate(pct_mantissa(50)),
+            U256::from(1_000u64 + 50_000)
+        );
+        assert_eq!(
+            model.borrow_rate(pct_mantissa(80)),
+            U256::from(1_000u64 + 80_000)
+        );
+        // 高于 kink: 加上超出部分 * jump multiplier
+        assert_eq!(
+            model.borrow_rate(pct_mantissa(90)),
+            U256::from(1_000u64 + 80_000 + 200_000)
+        );
+    }
+
+    #[test]
+    fn supply_rate_nets_out_reserve_factor() {
+        let model = sample_model();
+        // 50% 利用率，借款利率 51_000，储备金率 10%: 0.5 * 51_000 * 0.9
+        assert_eq!(
+            model.supply_rate(pct_mantissa(50), pct_mantissa(10)),
+            U256::from(22_950u64)
+        );
+        assert_eq!(model.supply_rate(U256::ZERO, pct_mantissa(10)), U256::ZERO);
+    }
+
+    #[test]
+    fn projection_request_validation() {
+        let args = |json: Value| -> TectonicRatesArgs {
+            serde_json::from_value(json).expect("args should parse")
+        };
+        assert_eq!(
+            projection_request(&args(serde_json::json!({}))).expect("no projection"),
+            None
+        );
+        assert_eq!(
+            projection_request(&args(serde_json::json!({
+                "asset": "USDC",
+                "action": "borrow",
+                "amount": "1000000"
+            })))
+            .expect("valid projection"),
+            Some((RateAction::Borrow, U256::from(1_000_000u64)))
+        );
+        for invalid in [
+            serde_json::json!({ "asset": "USDC", "amount": "1" }),
+            serde_json::json!({ "asset": "USDC", "action": "supply" }),
+            serde_json::json!({ "action": "supply", "amount": "1" }),
+            serde_json::json!({ "asset": "USDC", "action": "supply", "amount": "0" }),
+        ] {
+            assert!(projection_request(&args(invalid)).is_err());
+        }
+    }
+
     #[test]
     fn rates_args_deserialize_with_asset() {
         let json = serde_json::json!({ "asset": "USDC", "simple_mode": true });
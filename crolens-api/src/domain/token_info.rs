@@ -1,14 +1,48 @@
-use alloy_primitives::U256;
+use alloy_primitives::{Address, U256};
 use alloy_sol_types::SolCall;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::abi;
+use crate::domain::swap::{is_native_cro, resolve_wcro};
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::config::DexPool;
 use crate::infra::multicall::Call;
+use crate::infra::rpc::LogFilter;
+use crate::infra::token::Token;
 use crate::types;
 
+/// 原生 CRO 的展示名称与符号
+const NATIVE_CRO_NAME: &str = "Cronos";
+const NATIVE_CRO_SYMBOL: &str = "CRO";
+
+const SWAP_TOPIC: &str = "0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822"; // UniswapV2
+
+/// 24h 成交量扫描的区块窗口 (Cronos 出块约 5.7s，24h 约 15k 块)，同时也是扫描上限
+const VOLUME_WINDOW_BLOCKS: u64 = 15_000;
+/// 单次 eth_getLogs 的区块跨度
+const VOLUME_LOG_CHUNK_BLOCKS: u64 = 5_000;
+const VOLUME_CACHE_PREFIX: &str = "token_volume:";
+const VOLUME_CACHE_TTL_SECS: u64 = 300;
+
+/// 解析查询的代币；原生 CRO 解析为 WCRO (用于链上读取)，并返回是否为原生
+fn resolve_token_query(tokens: &[Token], query: &str) -> Result<(Token, bool)> {
+    if is_native_cro(query) {
+        return resolve_wcro(tokens).map(|wcro| (wcro, true));
+    }
+    infra::token::resolve_token(tokens, query).map(|token| (token, false))
+}
+
+/// 原生 CRO 使用自己的名称与符号，而不是 WCRO 合约返回的值
+fn display_identity(native: bool, name: String, symbol: String) -> (String, String) {
+    if native {
+        (NATIVE_CRO_NAME.to_string(), NATIVE_CRO_SYMBOL.to_string())
+    } else {
+        (name, symbol)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct GetTokenInfoArgs {
     token: String,
@@ -29,8 +63,9 @@ pub async fn get_token_info(services: &infra::Services, args: Value) -> Result<V
     }
 
     // 1. Resolve token (address or symbol).
-    let tokens = infra::token::list_tokens_cached(&services.db, &services.kv).await?;
-    let token = infra::token::resolve_token(&tokens, token_query)?;
+    let tokens =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix).await?;
+    let (token, native) = resolve_token_query(&tokens, token_query)?;
 
     // 2. Fetch on-chain metadata via multicall (name, symbol, decimals, totalSupply).
     let multicall = services.multicall()?;
@@ -85,14 +120,21 @@ pub async fn get_token_info(services: &infra::Services, args: Value) -> Result<V
         .unwrap_or(U256::ZERO);
 
     let total_supply_formatted = types::format_units(&total_supply, decimals);
+    let (name, symbol) = display_identity(native, name, symbol);
 
-    // 3. Fetch token price (best-effort).
+    // 3. Fetch token price (best-effort). WCRO 与 CRO 共用 CRO 锚定价格
     let price_usd = infra::price::get_price_usd(services, &token)
         .await?
         .unwrap_or(0.0);
 
     // 4. Find main liquidity pools.
-    let pools = infra::config::list_dex_pools_cached(&services.db, &services.kv, "vvs").await?;
+    let pools = infra::config::list_dex_pools_cached(
+        &services.db,
+        &services.kv,
+        &services.kv_prefix,
+        "vvs",
+    )
+    .await?;
     let token_pools: Vec<_> = pools
         .iter()
         .filter(|p| p.token0_address == token.address || p.token1_address == token.address)
@@ -149,7 +191,7 @@ pub async fn get_token_info(services: &infra::Services, args: Value) -> Result<V
                             "dex": "vvs",
                             "pair": format!("{}-{}", pool.token0_symbol, pool.token1_symbol),
                             "lp_address": pool.lp_address.to_string(),
-                            "tvl_usd": format!("{:.2}", tvl)
+                            "tvl_usd": services.precision.usd(tvl)
                         }));
                     }
                 }
@@ -168,14 +210,27 @@ pub async fn get_token_info(services: &infra::Services, args: Value) -> Result<V
                 .and_then(|v| v.as_str())
                 .and_then(|s| s.parse::<f64>().ok())
                 .unwrap_or(0.0);
-            tvl_b.partial_cmp(&tvl_a).unwrap_or(std::cmp::Ordering::Equal)
+            tvl_b
+                .partial_cmp(&tvl_a)
+                .unwrap_or(std::cmp::Ordering::Equal)
         });
         main_pools.truncate(5);
     }
 
+    // 24h 成交量为尽力而为的估算，日志扫描失败时不影响其他字段
+    let (volume_24h, volume_24h_usd) = if token_pools.is_empty() {
+        (Value::Null, None)
+    } else {
+        match swap_volume_24h(services, token.address, &token_pools).await {
+            Ok(volume) => volume_json(services, &volume, decimals, price_usd),
+            Err(_) => (Value::Null, None),
+        }
+    };
+
     // 5. Compute market cap (if price is available).
+    // 原生 CRO 的总量无法从链上读取 (WCRO totalSupply 只是被包装的部分)，不计算市值
     let total_supply_f64 = total_supply_formatted.parse::<f64>().unwrap_or(0.0);
-    let market_cap_usd = if price_usd > 0.0 && total_supply_f64 > 0.0 {
+    let market_cap_usd = if !native && price_usd > 0.0 && total_supply_f64 > 0.0 {
         Some(price_usd * total_supply_f64)
     } else {
         None
@@ -198,25 +253,180 @@ pub async fn get_token_info(services: &infra::Services, args: Value) -> Result<V
             .map(|s| format!(" ({} pool)", s))
             .unwrap_or_default();
 
+        let volume_str = volume_24h_usd
+            .map(format_currency)
+            .unwrap_or_else(|| "N/A".to_string());
+
         let text = format!(
-            "{} ({}) | Price: ${:.6} | MCap: {} | Liquidity: {}{}",
-            name, symbol, price_usd, mcap_str, liq_str, pool_hint
+            "{} ({}) | Price: ${:.6} | MCap: {} | Liquidity: {}{} | 24h Vol (est.): {}",
+            name, symbol, price_usd, mcap_str, liq_str, pool_hint, volume_str
         );
         return Ok(serde_json::json!({ "text": text }));
     }
 
-    Ok(serde_json::json!({
+    let mut result = serde_json::json!({
         "address": token.address.to_string(),
         "name": name,
         "symbol": symbol,
         "decimals": decimals,
-        "total_supply": total_supply_formatted,
-        "price_usd": format!("{:.8}", price_usd),
-        "market_cap_usd": market_cap_usd.map(|v| format!("{:.2}", v)),
-        "liquidity_usd": format!("{:.2}", total_liquidity_usd),
+        // 原生 CRO 没有链上可读的总量，WCRO totalSupply 只放在 wrapped 下
+        "total_supply": (!native).then_some(&total_supply_formatted),
+        "price_usd": services.precision.price(price_usd),
+        "market_cap_usd": market_cap_usd.map(|v| services.precision.usd(v)),
+        "liquidity_usd": services.precision.usd(total_liquidity_usd),
         "main_pools": main_pools,
+        "volume_24h": volume_24h,
+        "native": native,
         "meta": services.meta()
-    }))
+    });
+    if native {
+        // address 来自 WCRO 合约
+        result["wrapped"] = serde_json::json!({
+            "symbol": token.symbol,
+            "address": token.address.to_string(),
+            "total_supply": total_supply_formatted,
+        });
+    }
+    Ok(result)
+}
+
+/// 窗口内该代币一侧的 Swap 成交量 (原始单位)；缓存中不含价格，命中后按当前价格估值
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct SwapVolume {
+    amount: String,
+    swaps: u64,
+    from_block: u64,
+    to_block: u64,
+    pools_scanned: usize,
+}
+
+fn volume_cache_key(token: Address) -> String {
+    format!("{VOLUME_CACHE_PREFIX}{}", token.to_string().to_lowercase())
+}
+
+/// UniswapV2 Swap data: amount0In, amount1In, amount0Out, amount1Out (各 32 字节)
+fn decode_swap_amounts(data: &str) -> Option<[U256; 4]> {
+    let hex = data.trim().trim_start_matches("0x");
+    if hex.len() < 256 {
+        return None;
+    }
+    let word = |i: usize| U256::from_str_radix(hex.get(i * 64..(i + 1) * 64)?, 16).ok();
+    Some([word(0)?, word(1)?, word(2)?, word(3)?])
+}
+
+/// 汇总已知池子上 Swap 日志中该代币一侧的成交量 (amountIn + amountOut)；
+/// 未知池子或格式异常的日志被忽略
+fn sum_swap_volume(logs: &[Value], pools: &[&DexPool], token: Address) -> (U256, u64) {
+    let mut total = U256::ZERO;
+    let mut swaps = 0u64;
+    for log in logs {
+        let Some(topic0) = log
+            .get("topics")
+            .and_then(|v| v.as_array())
+            .and_then(|topics| topics.first())
+            .and_then(|v| v.as_str())
+        else {
+            continue;
+        };
+        if !topic0.eq_ignore_ascii_case(SWAP_TOPIC) {
+            continue;
+        }
+        let Some(pool) = log
+            .get("address")
+            .and_then(|v| v.as_str())
+            .and_then(|v| types::parse_address(v).ok())
+            .and_then(|address| pools.iter().find(|p| p.lp_address == address))
+        else {
+            continue;
+        };
+        let Some([amount0_in, amount1_in, amount0_out, amount1_out]) = log
+            .get("data")
+            .and_then(|v| v.as_str())
+            .and_then(decode_swap_amounts)
+        else {
+            continue;
+        };
+        let side = if pool.token0_address == token {
+            amount0_in.saturating_add(amount0_out)
+        } else if pool.token1_address == token {
+            amount1_in.saturating_add(amount1_out)
+        } else {
+            continue;
+        };
+        total = total.saturating_add(side);
+        swaps += 1;
+    }
+    (total, swaps)
+}
+
+async fn cached_volume(services: &infra::Services, key: &str) -> Option<SwapVolume> {
+    let raw = services.kv_get_text(key).await?;
+    serde_json::from_str(&raw).ok()
+}
+
+async fn store_volume(services: &infra::Services, key: &str, volume: &SwapVolume) {
+    if let Ok(json) = serde_json::to_string(volume) {
+        services.kv_put_text(key, json, VOLUME_CACHE_TTL_SECS).await;
+    }
+}
+
+/// 扫描代币所在池子最近约 24h 的 Swap 日志，按代币一侧累计成交量
+async fn swap_volume_24h(
+    services: &infra::Services,
+    token: Address,
+    pools: &[&DexPool],
+) -> Result<SwapVolume> {
+    let key = services.kv_key(&volume_cache_key(token));
+    if let Some(cached) = cached_volume(services, &key).await {
+        return Ok(cached);
+    }
+
+    let rpc = services.rpc()?;
+    let latest = rpc.latest_block_number_cached().await?;
+    let from_block = latest.saturating_sub(VOLUME_WINDOW_BLOCKS - 1);
+    let filter = pools
+        .iter()
+        .fold(LogFilter::new(), |filter, pool| {
+            filter.address(pool.lp_address)
+        })
+        .event(SWAP_TOPIC);
+    let logs = rpc
+        .eth_get_logs_chunked(&filter, from_block, latest, VOLUME_LOG_CHUNK_BLOCKS)
+        .await?;
+    let (amount, swaps) = sum_swap_volume(&logs, pools, token);
+
+    let volume = SwapVolume {
+        amount: amount.to_string(),
+        swaps,
+        from_block,
+        to_block: latest,
+        pools_scanned: pools.len(),
+    };
+    store_volume(services, &key, &volume).await;
+    Ok(volume)
+}
+
+fn volume_json(
+    services: &infra::Services,
+    volume: &SwapVolume,
+    decimals: u8,
+    price_usd: f64,
+) -> (Value, Option<f64>) {
+    let amount = U256::from_str_radix(&volume.amount, 10).unwrap_or(U256::ZERO);
+    let formatted = types::format_units(&amount, decimals);
+    let volume_f64 = formatted.parse::<f64>().unwrap_or(0.0);
+    let volume_usd = (price_usd > 0.0).then_some(volume_f64 * price_usd);
+    let json = serde_json::json!({
+        "volume": formatted,
+        "volume_usd": volume_usd.map(|v| services.precision.usd(v)),
+        "swaps": volume.swaps,
+        "from_block": volume.from_block,
+        "to_block": volume.to_block,
+        "pools_scanned": volume.pools_scanned,
+        "estimate": true,
+        "note": "Windowed estimate from Swap logs on known VVS pools only"
+    });
+    (json, volume_usd)
 }
 
 /// Format currency with K/M/B suffixes.
@@ -244,6 +454,112 @@ mod tests {
         assert_eq!(format_currency(1_000_000_000.0), "$1.00B");
     }
 
+    fn token(symbol: &str, byte: u8) -> Token {
+        Token {
+            address: alloy_primitives::Address::from([byte; 20]),
+            symbol: symbol.to_string(),
+            decimals: 18,
+            is_stablecoin: false,
+            is_spam: false,
+        }
+    }
+
+    #[test]
+    fn native_cro_resolves_to_wcro() {
+        let tokens = vec![token("WCRO", 1), token("VVS", 2)];
+        let (resolved, native) = resolve_token_query(&tokens, " cro ").expect("CRO resolves");
+        assert!(native);
+        assert_eq!(resolved.symbol, "WCRO");
+
+        let (resolved, native) = resolve_token_query(&tokens, "WCRO").expect("WCRO resolves");
+        assert!(!native);
+        assert_eq!(resolved.address, tokens[0].address);
+
+        assert!(resolve_token_query(&[token("VVS", 2)], "CRO").is_err());
+    }
+
+    #[test]
+    fn native_cro_is_labeled_distinct_from_wcro() {
+        let (name, symbol) = display_identity(true, "Wrapped CRO".to_string(), "WCRO".to_string());
+        assert_eq!((name.as_str(), symbol.as_str()), ("Cronos", "CRO"));
+
+        let (name, symbol) = display_identity(false, "Wrapped CRO".to_string(), "WCRO".to_string());
+        assert_eq!((name.as_str(), symbol.as_str()), ("Wrapped CRO", "WCRO"));
+    }
+
+    fn pool(lp: u8, token0: u8, token1: u8) -> DexPool {
+        DexPool {
+            pool_id: format!("vvs_{lp}"),
+            pool_index: None,
+            lp_address: Address::from([lp; 20]),
+            token0_address: Address::from([token0; 20]),
+            token1_address: Address::from([token1; 20]),
+            token0_symbol: "T0".to_string(),
+            token1_symbol: "T1".to_string(),
+        }
+    }
+
+    fn word(value: u64) -> String {
+        format!("{:064x}", value)
+    }
+
+    fn swap_log(pool: u8, amounts: [u64; 4]) -> Value {
+        let data: String = amounts.iter().map(|v| word(*v)).collect();
+        serde_json::json!({
+            "address": Address::from([pool; 20]).to_string(),
+            "topics": [SWAP_TOPIC, format!("0x{}", word(1)), format!("0x{}", word(2))],
+            "data": format!("0x{data}"),
+        })
+    }
+
+    #[test]
+    fn swap_volume_sums_token_side_across_pools() {
+        // token = 0x02..；池子 A 中为 token1，池子 B 中为 token0
+        let token = Address::from([2u8; 20]);
+        let pool_a = pool(0xa0, 1, 2);
+        let pool_b = pool(0xb0, 2, 3);
+        let pools = vec![&pool_a, &pool_b];
+
+        let logs = vec![
+            // A: 卖出 token1 (amount1In = 100)
+            swap_log(0xa0, [0, 100, 7, 0]),
+            // A: 买入 token1 (amount1Out = 50)
+            swap_log(0xa0, [9, 0, 0, 50]),
+            // B: 卖出 token0 (amount0In = 30)
+            swap_log(0xb0, [30, 0, 0, 11]),
+            // 未知池子被忽略
+            swap_log(0xc0, [1_000, 1_000, 1_000, 1_000]),
+        ];
+
+        let (total, swaps) = sum_swap_volume(&logs, &pools, token);
+        assert_eq!(total, U256::from(180u64));
+        assert_eq!(swaps, 3);
+    }
+
+    #[test]
+    fn swap_volume_skips_other_events_and_malformed_data() {
+        let token = Address::from([2u8; 20]);
+        let pool_a = pool(0xa0, 2, 1);
+        let pools = vec![&pool_a];
+
+        let mut transfer = swap_log(0xa0, [5, 0, 0, 0]);
+        transfer["topics"][0] = Value::String(
+            "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef".to_string(),
+        );
+        let mut short = swap_log(0xa0, [5, 0, 0, 0]);
+        short["data"] = Value::String(format!("0x{}", word(5)));
+
+        let logs = vec![transfer, short, swap_log(0xa0, [5, 0, 0, 0])];
+        assert_eq!(sum_swap_volume(&logs, &pools, token), (U256::from(5u64), 1));
+        assert_eq!(sum_swap_volume(&[], &pools, token), (U256::ZERO, 0));
+    }
+
+    #[test]
+    fn volume_cache_key_is_lowercase_per_token() {
+        let key = volume_cache_key(Address::from([0xab; 20]));
+        assert_eq!(key, format!("token_volume:0x{}", "ab".repeat(20)));
+    }
+
     #[test]
     fn args_deserialize_defaults() {
         let json = serde_json::json!({ "token": "VVS" });
@@ -1,3 +1,4 @@
+use alloy_primitives::U256;
 use alloy_sol_types::SolCall;
 use serde::Deserialize;
 use serde_json::Value;
@@ -6,8 +7,12 @@ use worker::d1::D1Type;
 use crate::abi;
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::signatures::SignatureEntry;
 use crate::types;
 
+/// 超过该确认数视为 finalized
+const FINALITY_CONFIRMATIONS: u64 = 12;
+
 #[derive(Debug, Deserialize)]
 struct DecodeArgs {
     tx_hash: String,
@@ -23,25 +28,208 @@ pub async fn decode_transaction(services: &infra::Services, args: Value) -> Resu
     types::validate_hex_string(hash, 64)?;
 
     let rpc = services.rpc()?;
-    // 并行获取 transaction 和 receipt
-    let (tx, receipt) = futures_util::future::try_join(
-        rpc.eth_get_transaction_by_hash(hash),
-        rpc.eth_get_transaction_receipt(hash),
+    // 并行获取 transaction、receipt 和最新区块号 (后者失败不影响解码)
+    let (txs, latest_block) = futures_util::future::join(
+        futures_util::future::try_join(
+            rpc.eth_get_transaction_by_hash(hash),
+            rpc.eth_get_transaction_receipt(hash),
+        ),
+        rpc.latest_block_number_cached(),
     )
-    .await?;
+    .await;
+    let (tx, receipt) = txs?;
+
+    let decoded = decode_fetched(services, hash, &tx, &receipt, latest_block.ok()).await?;
+    if input.simple_mode {
+        return Ok(serde_json::json!({
+            "text": decoded_summary(&decoded),
+            "meta": services.meta()
+        }));
+    }
+
+    let mut decoded = decoded;
+    decoded["meta"] = services.meta();
+    Ok(decoded)
+}
+
+/// 单次 decode_transactions 最多处理的哈希数
+pub const MAX_BULK_DECODE_HASHES: usize = 20;
+
+/// 每个哈希对应的 batch 调用数 (transaction + receipt)
+const CALLS_PER_HASH: usize = 2;
+
+#[derive(Debug, Deserialize)]
+struct DecodeBulkArgs {
+    tx_hashes: Vec<String>,
+    #[serde(default)]
+    simple_mode: bool,
+}
+
+/// 批量解码：tx + receipt 通过一次 JSON-RPC batch 获取，单个哈希失败不影响其他项
+pub async fn decode_transactions(services: &infra::Services, args: Value) -> Result<Value> {
+    let input: DecodeBulkArgs = serde_json::from_value(args)
+        .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
+    validate_bulk_request(&input.tx_hashes)?;
+
+    let hashes: Vec<std::result::Result<String, String>> = input
+        .tx_hashes
+        .iter()
+        .map(|hash| {
+            let hash = hash.trim();
+            types::validate_hex_string(hash, 64)
+                .map(|_| hash.to_string())
+                .map_err(|err| err.to_string())
+        })
+        .collect();
+    let valid: Vec<&str> = hashes
+        .iter()
+        .filter_map(|h| h.as_ref().ok().map(String::as_str))
+        .collect();
+
+    let rpc = services.rpc()?;
+    let calls = batch_calls_for(&valid);
+    let (responses, latest_block) =
+        futures_util::future::join(rpc.call_batch(&calls), rpc.latest_block_number_cached()).await;
+    let fetched = pair_batch_results(responses?);
+    let latest_block = latest_block.ok();
+
+    let mut fetched = fetched.into_iter();
+    let decodes = hashes.iter().map(|hash| {
+        let fetched = hash.as_ref().ok().map(|_| fetched.next());
+        async move {
+            let hash = hash.as_ref().map_err(|err| err.clone())?;
+            let (tx, receipt) = fetched
+                .flatten()
+                .ok_or_else(|| "Missing RPC result for batch item".to_string())??;
+            decode_fetched(services, hash, &tx, &receipt, latest_block)
+                .await
+                .map_err(|err| err.to_string())
+        }
+    });
+    let outcomes = futures_util::future::join_all(decodes).await;
+
+    let items: Vec<Value> = input
+        .tx_hashes
+        .iter()
+        .zip(outcomes)
+        .map(|(hash, outcome)| bulk_item(hash.trim(), outcome, input.simple_mode))
+        .collect();
+    let decoded = decoded_count(&items);
+
+    Ok(serde_json::json!({
+        "results": items,
+        "decoded": decoded,
+        "failed": items.len() - decoded,
+        "credits_charged": decoded.max(1),
+        "meta": services.meta(),
+    }))
+}
+
+fn validate_bulk_request(tx_hashes: &[String]) -> Result<()> {
+    if tx_hashes.is_empty() {
+        return Err(CroLensError::invalid_params(
+            "tx_hashes array must not be empty".to_string(),
+        ));
+    }
+    if tx_hashes.len() > MAX_BULK_DECODE_HASHES {
+        return Err(CroLensError::invalid_params(format!(
+            "Maximum {MAX_BULK_DECODE_HASHES} tx_hashes per request"
+        )));
+    }
+    Ok(())
+}
+
+/// 每个哈希依次生成 [getTransactionByHash, getTransactionReceipt]
+fn batch_calls_for(hashes: &[&str]) -> Vec<(&'static str, Value)> {
+    hashes
+        .iter()
+        .flat_map(|hash| {
+            [
+                ("eth_getTransactionByHash", serde_json::json!([hash])),
+                ("eth_getTransactionReceipt", serde_json::json!([hash])),
+            ]
+        })
+        .collect()
+}
+
+type FetchedTx = std::result::Result<(Value, Value), String>;
+
+/// batch 结果按哈希两两配对；交易不存在 (null) 时报错，receipt 为 null 表示尚未上链
+fn pair_batch_results(responses: Vec<Result<Value>>) -> Vec<FetchedTx> {
+    let mut responses = responses.into_iter();
+    let mut out = Vec::with_capacity(responses.len() / CALLS_PER_HASH);
+    while let (Some(tx), Some(receipt)) = (responses.next(), responses.next()) {
+        out.push(match (tx, receipt) {
+            (Err(err), _) | (_, Err(err)) => Err(err.to_string()),
+            (Ok(Value::Null), _) => Err("Transaction not found".to_string()),
+            (Ok(tx), Ok(receipt)) => Ok((tx, receipt)),
+        });
+    }
+    out
+}
+
+fn bulk_item(hash: &str, outcome: std::result::Result<Value, String>, simple_mode: bool) -> Value {
+    match outcome {
+        Ok(decoded) if simple_mode => serde_json::json!({
+            "tx_hash": hash,
+            "ok": true,
+            "text": decoded_summary(&decoded),
+        }),
+        Ok(decoded) => serde_json::json!({ "tx_hash": hash, "ok": true, "result": decoded }),
+        Err(error) => serde_json::json!({ "tx_hash": hash, "ok": false, "error": error }),
+    }
+}
 
+fn decoded_count(items: &[Value]) -> usize {
+    items
+        .iter()
+        .filter(|item| item.get("ok").and_then(|v| v.as_bool()).unwrap_or(false))
+        .count()
+}
+
+/// 从已获取的 transaction/receipt 构建解码结果 (不含 meta)
+async fn decode_fetched(
+    services: &infra::Services,
+    hash: &str,
+    tx: &Value,
+    receipt: &Value,
+    latest_block: Option<u64>,
+) -> Result<Value> {
     let from = tx.get("from").and_then(|v| v.as_str()).unwrap_or_default();
     let to = tx.get("to").and_then(|v| v.as_str()).unwrap_or_default();
     let input_data = tx.get("input").and_then(|v| v.as_str()).unwrap_or("0x");
 
     let selector = input_data.get(0..10).unwrap_or("0x");
-    let (action, method_name, decoded_params) = decode_selector(selector, input_data)?;
+    // deposit()/withdraw(uint256) 是很多金库和质押合约共用的通用选择器，只有目标是 WCRO 时才解码为包装操作
+    let wcro_call = if WCRO_SELECTORS.contains(&selector) && is_wcro_target(services, to).await {
+        decode_wcro_call(selector, input_data, tx)?
+    } else {
+        None
+    };
+    let builtin = DecodedCall::builtin(match wcro_call {
+        Some(decoded) => decoded,
+        None => decode_selector(selector, input_data)?,
+    });
+    // 合约登记的协议用于标注 action；内置解码失败时再查函数签名表
+    let (protocol, call) = futures_util::future::join(
+        infer_protocol(&services.db, to),
+        with_signature_fallback(&services.db, selector, builtin),
+    )
+    .await;
+    let protocol = protocol.unwrap_or(None);
+    let action_label = action_label(&call.action, protocol.as_ref());
 
-    let status = receipt
-        .get("status")
-        .and_then(|v| v.as_str())
-        .unwrap_or("0x0")
-        .to_string();
+    let tx_block = parse_block_number(receipt.get("blockNumber"))
+        .or_else(|| parse_block_number(tx.get("blockNumber")));
+    // 未上链的交易没有 receipt
+    let status = match tx_block {
+        Some(_) => receipt
+            .get("status")
+            .and_then(|v| v.as_str())
+            .unwrap_or("0x0")
+            .to_string(),
+        None => "pending".to_string(),
+    };
     let gas_used = receipt
         .get("gasUsed")
         .and_then(|v| v.as_str())
@@ -49,27 +237,272 @@ pub async fn decode_transaction(services: &infra::Services, args: Value) -> Resu
         .map(|u| u.to_string())
         .unwrap_or_else(|| "0".to_string());
 
-    if input.simple_mode {
-        let summary = format!("{action}: {method_name} | Status: {status} | Gas: {gas_used}");
-        return Ok(serde_json::json!({ "text": summary, "meta": services.meta() }));
-    }
+    let confirmations = confirmations(tx_block, latest_block);
+    let finalized = is_finalized(confirmations);
+
+    let fee = match tx_fee(tx, receipt) {
+        Some(fee) => {
+            // CRO 价格只用于 USD 估值，读取失败时 fee_usd 为 null
+            let cro_price =
+                infra::price::get_anchor_price_usd(&services.kv, &services.kv_prefix, "CRO")
+                    .await
+                    .ok()
+                    .flatten();
+            fee_json(services, &fee, cro_price)
+        }
+        None => Value::Null,
+    };
 
     Ok(serde_json::json!({
         "hash": hash,
         "from": from,
         "to": to,
-        "action": action,
-        "protocol": infer_protocol(&services.db, to).await.unwrap_or(None),
+        "action": call.action,
+        "action_label": action_label,
+        "protocol": protocol.as_ref().map(|p| p.id.clone()),
+        "protocol_name": protocol.and_then(|p| p.name),
         "status": status,
         "gas_used": gas_used,
+        "fee": fee,
+        "block_number": tx_block,
+        "confirmations": confirmations,
+        "finalized": finalized,
         "decoded": {
-            "method_name": method_name,
-            "params": decoded_params,
+            "method_name": call.method_name,
+            "params": call.params,
+            "signature": call.signature,
+            "source": call.source,
         },
-        "meta": services.meta(),
     }))
 }
 
+/// 选择器解码结果；`source` 为 builtin (内置 ABI) 或 signature_db (函数签名表，无参数)
+#[derive(Debug, Clone, PartialEq)]
+struct DecodedCall {
+    action: String,
+    method_name: String,
+    params: Value,
+    signature: Option<String>,
+    source: &'static str,
+}
+
+impl DecodedCall {
+    fn builtin((action, method_name, params): (String, String, Value)) -> Self {
+        Self {
+            action,
+            method_name,
+            params,
+            signature: None,
+            source: "builtin",
+        }
+    }
+
+    fn is_unknown(&self) -> bool {
+        self.method_name == "unknown"
+    }
+
+    /// 用签名表中的条目替换未知解码；method_name 取签名括号前的部分
+    fn from_signature(entry: SignatureEntry) -> Self {
+        let method_name = entry.method_name().to_string();
+        Self {
+            action: entry.action.unwrap_or_else(|| "Unknown".to_string()),
+            method_name,
+            params: Value::Null,
+            signature: Some(entry.signature),
+            source: "signature_db",
+        }
+    }
+}
+
+/// contracts 表中登记的协议 (name 来自 protocols 表)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProtocolInfo {
+    id: String,
+    name: Option<String>,
+}
+
+/// DEX 类操作附带具体协议名，例如 "Swap on VVS Finance"；协议未知时为 None
+fn action_label(action: &str, protocol: Option<&ProtocolInfo>) -> Option<String> {
+    match action {
+        "Wrap" => return Some("Wrap CRO".to_string()),
+        "Unwrap" => return Some("Unwrap WCRO".to_string()),
+        _ => {}
+    }
+    if !matches!(action, "Swap" | "Liquidity") {
+        return None;
+    }
+    let protocol = protocol?;
+    let name = protocol.name.as_deref().unwrap_or(&protocol.id);
+    Some(format!("{action} on {name}"))
+}
+
+async fn with_signature_fallback(
+    db: &worker::D1Database,
+    selector: &str,
+    call: DecodedCall,
+) -> DecodedCall {
+    if !call.is_unknown() || selector.len() != 10 {
+        return call;
+    }
+    // 选择器碰撞时取排序后的第一个候选
+    match infra::signatures::lookup_signatures(db, selector).await {
+        Ok(entries) => match entries.into_iter().next() {
+            Some(entry) => DecodedCall::from_signature(entry),
+            None => call,
+        },
+        Err(err) => {
+            // 签名表不可用 (如旧库未迁移) 时保持 unknown
+            worker::console_warn!("[WARN] signature lookup failed: {}", err);
+            call
+        }
+    }
+}
+
+/// 交易实际支付的手续费；type-0/1 按 gasPrice 计费，type-2 按 receipt 中的 effectiveGasPrice
+#[derive(Debug, Clone, PartialEq)]
+struct TxFee {
+    tx_type: u64,
+    effective_gas_price: U256,
+    max_fee_per_gas: Option<U256>,
+    max_priority_fee_per_gas: Option<U256>,
+    fee_wei: U256,
+}
+
+fn quantity_field(value: &Value, key: &str) -> Option<U256> {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .and_then(|v| types::parse_u256_hex(v).ok())
+}
+
+/// receipt 缺少 gasUsed 或无法确定实际 gas 价格 (如未上链的交易) 时返回 None
+fn tx_fee(tx: &Value, receipt: &Value) -> Option<TxFee> {
+    let gas_used = quantity_field(receipt, "gasUsed")?;
+    // 缺少 type 字段的旧节点只返回 legacy 交易
+    let tx_type = quantity_field(tx, "type")
+        .and_then(|v| u64::try_from(v).ok())
+        .unwrap_or(0);
+    let (max_fee_per_gas, max_priority_fee_per_gas) = if tx_type >= 2 {
+        (
+            quantity_field(tx, "maxFeePerGas"),
+            quantity_field(tx, "maxPriorityFeePerGas"),
+        )
+    } else {
+        (None, None)
+    };
+    // 部分节点的 receipt 不含 effectiveGasPrice：legacy 交易即为 gasPrice，
+    // 已上链的 type-2 交易 gasPrice 字段同样是实际成交价格
+    let effective_gas_price =
+        quantity_field(receipt, "effectiveGasPrice").or_else(|| quantity_field(tx, "gasPrice"))?;
+    Some(TxFee {
+        tx_type,
+        effective_gas_price,
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+        fee_wei: gas_used.saturating_mul(effective_gas_price),
+    })
+}
+
+fn fee_json(services: &infra::Services, fee: &TxFee, cro_price_usd: Option<f64>) -> Value {
+    let fee_cro = types::format_units(&fee.fee_wei, 18);
+    let fee_usd = cro_price_usd.filter(|p| *p > 0.0).map(|price| {
+        let usd = fee_cro.parse::<f64>().unwrap_or(0.0) * price;
+        services.precision.usd(usd)
+    });
+    serde_json::json!({
+        "tx_type": fee.tx_type,
+        "effective_gas_price": fee.effective_gas_price.to_string(),
+        "max_fee_per_gas": fee.max_fee_per_gas.map(|v| v.to_string()),
+        "max_priority_fee_per_gas": fee.max_priority_fee_per_gas.map(|v| v.to_string()),
+        "fee_wei": fee.fee_wei.to_string(),
+        "fee_cro": fee_cro,
+        "fee_usd": fee_usd,
+    })
+}
+
+fn decoded_summary(decoded: &Value) -> String {
+    let field = |key: &str| decoded.get(key).and_then(|v| v.as_str()).unwrap_or("");
+    let action = decoded
+        .get("action_label")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| field("action"));
+    let status = field("status");
+    let gas_used = field("gas_used");
+    let method_name = decoded
+        .pointer("/decoded/method_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+    match decoded.get("confirmations").and_then(|v| v.as_u64()) {
+        Some(n) if n > 0 => format!(
+            "{action}: {method_name} | Status: {status} | Gas: {gas_used} | Confirmations: {n}"
+        ),
+        _ => format!("{action}: {method_name} | Status: {status} | Gas: {gas_used}"),
+    }
+}
+
+fn parse_block_number(value: Option<&Value>) -> Option<u64> {
+    value
+        .and_then(|v| v.as_str())
+        .and_then(|v| types::parse_u256_hex(v).ok())
+        .and_then(|v| u64::try_from(v).ok())
+}
+
+/// 交易所在区块本身计 1 个确认；未上链 (无区块号) 时为 0；最新区块号未知时为 None
+fn confirmations(tx_block: Option<u64>, latest_block: Option<u64>) -> Option<u64> {
+    match (tx_block, latest_block) {
+        (None, _) => Some(0),
+        (Some(_), None) => None,
+        (Some(tx_block), Some(latest)) => Some(latest.saturating_sub(tx_block) + 1),
+    }
+}
+
+fn is_finalized(confirmations: Option<u64>) -> bool {
+    matches!(confirmations, Some(n) if n > FINALITY_CONFIRMATIONS)
+}
+
+/// WCRO 的 deposit() / withdraw(uint256) 选择器
+const WCRO_SELECTORS: [&str; 2] = ["0xd0e30db0", "0x2e1a7d4d"];
+
+/// 交易目标是否为代币列表中的 WCRO (tokens 表不可用时使用内置核心代币列表)
+async fn is_wcro_target(services: &infra::Services, to: &str) -> bool {
+    let Ok(to) = types::parse_address(to) else {
+        return false;
+    };
+    let tokens = infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix)
+        .await
+        .unwrap_or_default();
+    infra::token::resolve_token(&tokens, "WCRO")
+        .or_else(|_| infra::token::resolve_token(&infra::token::fallback_tokens(), "WCRO"))
+        .is_ok_and(|wcro| wcro.address == to)
+}
+
+/// 解码发往 WCRO 的包装/解包调用；deposit() 没有参数，包装数量即交易的 value
+fn decode_wcro_call(
+    selector: &str,
+    input_data: &str,
+    tx: &Value,
+) -> Result<Option<(String, String, Value)>> {
+    let bytes = types::hex0x_to_bytes(input_data)?;
+    let decoded = match selector {
+        "0xd0e30db0" => {
+            let params = match quantity_field(tx, "value") {
+                Some(amount) => serde_json::json!({ "amount": amount.to_string() }),
+                None => Value::Null,
+            };
+            ("Wrap".to_string(), "deposit".to_string(), params)
+        }
+        "0x2e1a7d4d" => {
+            let params = match abi::withdrawCall::abi_decode(&bytes, true) {
+                Ok(decoded) => serde_json::json!({ "amount": decoded.wad.to_string() }),
+                Err(_) => Value::Null,
+            };
+            ("Unwrap".to_string(), "withdraw".to_string(), params)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(decoded))
+}
+
 fn decode_selector(selector: &str, input_data: &str) -> Result<(String, String, Value)> {
     let bytes = types::hex0x_to_bytes(input_data)?;
     if bytes.len() < 4 {
@@ -328,38 +761,217 @@ fn decode_selector(selector: &str, input_data: &str) -> Result<(String, String,
             };
             Ok(("Lending".to_string(), "repayBorrow".to_string(), params))
         }
+        // Permit2: 签名授权 (AllowanceTransfer) 与签名转账 (SignatureTransfer)
+        "0x2b67b570" => {
+            let params = match abi::IPermit2::permitCall::abi_decode(&bytes, true) {
+                Ok(decoded) => {
+                    let permit = decoded.permitSingle;
+                    serde_json::json!({
+                        "owner": decoded.owner.to_string(),
+                        "token": permit.details.token.to_string(),
+                        "spender": permit.spender.to_string(),
+                        "amount": permit.details.amount.to_string(),
+                        "expiration": permit.details.expiration.to_string(),
+                        "nonce": permit.details.nonce.to_string(),
+                        "deadline": permit.sigDeadline.to_string(),
+                    })
+                }
+                Err(_) => Value::Null,
+            };
+            Ok(("Approval".to_string(), "permit".to_string(), params))
+        }
+        "0x30f28b7a" => {
+            let params = match abi::IPermit2::permitTransferFromCall::abi_decode(&bytes, true) {
+                Ok(decoded) => serde_json::json!({
+                    "owner": decoded.owner.to_string(),
+                    "token": decoded.permit.permitted.token.to_string(),
+                    "to": decoded.transferDetails.to.to_string(),
+                    "amount": decoded.transferDetails.requestedAmount.to_string(),
+                    "permitted_amount": decoded.permit.permitted.amount.to_string(),
+                    "nonce": decoded.permit.nonce.to_string(),
+                    "deadline": decoded.permit.deadline.to_string(),
+                }),
+                Err(_) => Value::Null,
+            };
+            Ok((
+                "Transfer".to_string(),
+                "permitTransferFrom".to_string(),
+                params,
+            ))
+        }
+        "0x4da3e5d9" => {
+            let params = match abi::IPermit2::permitBatchTransferFromCall::abi_decode(&bytes, true)
+            {
+                Ok(decoded) => {
+                    let transfers: Vec<Value> = decoded
+                        .permit
+                        .permitted
+                        .iter()
+                        .zip(decoded.transferDetails.iter())
+                        .map(|(permitted, details)| {
+                            serde_json::json!({
+                                "token": permitted.token.to_string(),
+                                "to": details.to.to_string(),
+                                "amount": details.requestedAmount.to_string(),
+                            })
+                        })
+                        .collect();
+                    serde_json::json!({
+                        "owner": decoded.owner.to_string(),
+                        "transfers": transfers,
+                        "nonce": decoded.permit.nonce.to_string(),
+                        "deadline": decoded.permit.deadline.to_string(),
+                    })
+                }
+                Err(_) => Value::Null,
+            };
+            Ok((
+                "Transfer".to_string(),
+                "permitBatchTransferFrom".to_string(),
+                params,
+            ))
+        }
+        "0x36c78516" => {
+            let params = match abi::IPermit2::transferFromCall::abi_decode(&bytes, true) {
+                Ok(decoded) => serde_json::json!({
+                    "from": decoded.from.to_string(),
+                    "to": decoded.to.to_string(),
+                    "token": decoded.token.to_string(),
+                    "amount": decoded.amount.to_string(),
+                }),
+                Err(_) => Value::Null,
+            };
+            Ok(("Transfer".to_string(), "transferFrom".to_string(), params))
+        }
         _ => Ok(("Unknown".to_string(), "unknown".to_string(), Value::Null)),
     }
 }
 
-async fn infer_protocol(db: &worker::D1Database, address: &str) -> Result<Option<String>> {
+async fn infer_protocol(db: &worker::D1Database, address: &str) -> Result<Option<ProtocolInfo>> {
     if address.is_empty() {
         return Ok(None);
     }
 
-    let address_arg = D1Type::Text(address);
+    // RPC 返回小写地址，contracts 表中为 checksum 格式；lower(address) 有表达式索引
+    let address_lower = address.trim().to_lowercase();
+    let address_arg = D1Type::Text(&address_lower);
     let statement = db
-        .prepare("SELECT protocol_id FROM contracts WHERE address = ?1 LIMIT 1")
+        .prepare(
+            "SELECT c.protocol_id, p.name AS protocol_name FROM contracts c \
+             LEFT JOIN protocols p ON p.protocol_id = c.protocol_id \
+             WHERE lower(c.address) = ?1 LIMIT 1",
+        )
         .bind_refs([&address_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
-    let result = infra::db::run("infer_protocol", statement.all()).await?;
+    let result = infra::db::run("infer_protocol", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
-    let Some(row) = rows.first() else {
-        return Ok(None);
-    };
+    Ok(rows.first().and_then(protocol_from_row))
+}
 
-    Ok(row
-        .get("protocol_id")
-        .and_then(|v| v.as_str())
-        .map(|v| v.to_string()))
+fn protocol_from_row(row: &Value) -> Option<ProtocolInfo> {
+    let id = row.get("protocol_id").and_then(|v| v.as_str())?;
+    Some(ProtocolInfo {
+        id: id.to_string(),
+        name: row
+            .get("protocol_name")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string()),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use alloy_primitives::U256;
+    use crate::infra::signatures::signature_entry_from_row;
+    use alloy_primitives::{Address, U256};
+
+    #[test]
+    fn confirmations_count_inclusion_block() {
+        assert_eq!(confirmations(Some(100), Some(100)), Some(1));
+        assert_eq!(confirmations(Some(100), Some(112)), Some(13));
+    }
+
+    #[test]
+    fn confirmations_saturate_when_latest_lags() {
+        // 节点间区块高度可能短暂落后于交易所在区块
+        assert_eq!(confirmations(Some(105), Some(100)), Some(1));
+    }
+
+    #[test]
+    fn pending_tx_has_zero_confirmations() {
+        assert_eq!(confirmations(None, Some(100)), Some(0));
+        assert_eq!(parse_block_number(Some(&Value::Null)), None);
+        assert!(!is_finalized(Some(0)));
+    }
+
+    #[test]
+    fn confirmations_unknown_without_latest_block() {
+        assert_eq!(confirmations(Some(100), None), None);
+        assert!(!is_finalized(None));
+    }
+
+    #[test]
+    fn finalized_requires_more_than_threshold() {
+        assert!(!is_finalized(Some(FINALITY_CONFIRMATIONS)));
+        assert!(is_finalized(Some(FINALITY_CONFIRMATIONS + 1)));
+    }
+
+    #[test]
+    fn parses_hex_block_number() {
+        let value = Value::String("0x10".to_string());
+        assert_eq!(parse_block_number(Some(&value)), Some(16));
+    }
+
+    #[test]
+    fn legacy_tx_fee_uses_gas_price() {
+        let tx = serde_json::json!({ "type": "0x0", "gasPrice": "0x12a05f200" });
+        let receipt = serde_json::json!({ "gasUsed": "0x5208" });
+        let fee = tx_fee(&tx, &receipt).expect("legacy fee");
+        assert_eq!(fee.tx_type, 0);
+        assert_eq!(fee.effective_gas_price, U256::from(5_000_000_000u64));
+        assert_eq!(fee.max_fee_per_gas, None);
+        assert_eq!(fee.fee_wei, U256::from(21_000u64 * 5_000_000_000));
+
+        // receipt 带 effectiveGasPrice 时优先使用
+        let receipt = serde_json::json!({ "gasUsed": "0x5208", "effectiveGasPrice": "0x3b9aca00" });
+        let fee = tx_fee(&tx, &receipt).expect("legacy fee");
+        assert_eq!(fee.fee_wei, U256::from(21_000u64 * 1_000_000_000));
+    }
+
+    #[test]
+    fn eip1559_tx_fee_uses_effective_gas_price() {
+        let tx = serde_json::json!({
+            "type": "0x2",
+            "maxFeePerGas": "0x174876e800",
+            "maxPriorityFeePerGas": "0x3b9aca00",
+        });
+        let receipt = serde_json::json!({
+            "gasUsed": "0x186a0",
+            "effectiveGasPrice": "0x9502f9000",
+        });
+        let fee = tx_fee(&tx, &receipt).expect("type-2 fee");
+        assert_eq!(fee.tx_type, 2);
+        assert_eq!(fee.max_fee_per_gas, Some(U256::from(100_000_000_000u64)));
+        assert_eq!(
+            fee.max_priority_fee_per_gas,
+            Some(U256::from(1_000_000_000u64))
+        );
+        assert_eq!(fee.effective_gas_price, U256::from(40_000_000_000u64));
+        assert_eq!(fee.fee_wei, U256::from(100_000u64 * 40_000_000_000));
+        assert_eq!(types::format_units(&fee.fee_wei, 18), "0.004");
+    }
+
+    #[test]
+    fn tx_fee_unknown_without_receipt_or_price() {
+        let tx = serde_json::json!({ "type": "0x2", "maxFeePerGas": "0x1" });
+        assert_eq!(tx_fee(&tx, &Value::Null), None);
+        assert_eq!(
+            tx_fee(&tx, &serde_json::json!({ "gasUsed": "0x5208" })),
+            None
+        );
+    }
 
     #[test]
     fn decodes_erc20_transfer_params() {
@@ -380,6 +992,62 @@ mod tests {
         assert_eq!(params.get("amount").and_then(|v| v.as_str()), Some("42"));
     }
 
+    #[test]
+    fn decodes_wcro_withdraw_amount_and_label() {
+        let calldata = abi::withdrawCall {
+            wad: U256::from(1_500_000_000_000_000_000u128),
+        }
+        .abi_encode();
+        let input_hex = types::bytes_to_hex0x(&calldata);
+
+        let (action, method, params) = decode_wcro_call("0x2e1a7d4d", &input_hex, &Value::Null)
+            .unwrap()
+            .expect("wcro call");
+        assert_eq!(action, "Unwrap");
+        assert_eq!(method, "withdraw");
+        assert_eq!(
+            params.get("amount").and_then(|v| v.as_str()),
+            Some("1500000000000000000")
+        );
+        assert_eq!(action_label(&action, None).as_deref(), Some("Unwrap WCRO"));
+    }
+
+    #[test]
+    fn decodes_wcro_deposit_with_tx_value_as_amount() {
+        let input_hex = types::bytes_to_hex0x(abi::depositCall {}.abi_encode());
+        assert_eq!(input_hex, "0xd0e30db0");
+        let tx = serde_json::json!({ "value": "0xde0b6b3a7640000" });
+
+        let (action, method, params) = decode_wcro_call("0xd0e30db0", &input_hex, &tx)
+            .unwrap()
+            .expect("wcro call");
+        assert_eq!(action, "Wrap");
+        assert_eq!(method, "deposit");
+        assert_eq!(
+            params.get("amount").and_then(|v| v.as_str()),
+            Some("1000000000000000000")
+        );
+        assert_eq!(action_label(&action, None).as_deref(), Some("Wrap CRO"));
+    }
+
+    #[test]
+    fn deposit_and_withdraw_to_other_contracts_keep_generic_decode() {
+        // 金库/质押合约的 deposit()/withdraw(uint256) 不走 WCRO 解码，与其它未知选择器一样交给签名表
+        let deposit = types::bytes_to_hex0x(abi::depositCall {}.abi_encode());
+        let withdraw = types::bytes_to_hex0x(
+            abi::withdrawCall {
+                wad: U256::from(7u64),
+            }
+            .abi_encode(),
+        );
+        for (selector, input_hex) in [("0xd0e30db0", &deposit), ("0x2e1a7d4d", &withdraw)] {
+            let call = DecodedCall::builtin(decode_selector(selector, input_hex).unwrap());
+            assert!(call.is_unknown());
+            assert_eq!(call.action, "Unknown");
+            assert_eq!(action_label(&call.action, None), None);
+        }
+    }
+
     #[test]
     fn decodes_swap_exact_tokens_for_tokens_params() {
         let to = types::parse_address("0x2222222222222222222222222222222222222222").unwrap();
@@ -410,4 +1078,254 @@ mod tests {
             Some(2)
         );
     }
+
+    fn vvs_protocol() -> ProtocolInfo {
+        ProtocolInfo {
+            id: "vvs".to_string(),
+            name: Some("VVS Finance".to_string()),
+        }
+    }
+
+    #[test]
+    fn swap_decode_is_annotated_with_router_protocol() {
+        let calldata = abi::swapExactTokensForTokensCall {
+            amountIn: U256::from(1000u64),
+            amountOutMin: U256::from(900u64),
+            path: vec![Address::repeat_byte(0x33), Address::repeat_byte(0x44)],
+            to: Address::repeat_byte(0x22),
+            deadline: U256::from(123u64),
+        }
+        .abi_encode();
+        let input_hex = types::bytes_to_hex0x(&calldata);
+        let call = DecodedCall::builtin(decode_selector("0x38ed1739", &input_hex).unwrap());
+
+        assert_eq!(call.source, "builtin");
+        assert_eq!(
+            action_label(&call.action, Some(&vvs_protocol())),
+            Some("Swap on VVS Finance".to_string())
+        );
+        // protocols 表缺少名称时退回 protocol_id
+        let unnamed = ProtocolInfo {
+            id: "mmf".to_string(),
+            name: None,
+        };
+        assert_eq!(
+            action_label(&call.action, Some(&unnamed)),
+            Some("Swap on mmf".to_string())
+        );
+        assert_eq!(action_label(&call.action, None), None);
+    }
+
+    #[test]
+    fn non_dex_actions_are_not_annotated() {
+        assert_eq!(action_label("Transfer", Some(&vvs_protocol())), None);
+        assert_eq!(action_label("Lending", Some(&vvs_protocol())), None);
+        assert_eq!(
+            action_label("Liquidity", Some(&vvs_protocol())),
+            Some("Liquidity on VVS Finance".to_string())
+        );
+    }
+
+    #[test]
+    fn signature_db_entry_replaces_unknown_decode() {
+        let unknown = DecodedCall::builtin(decode_selector("0x5c11d795", "0x5c11d795").unwrap());
+        assert!(unknown.is_unknown());
+
+        let row = serde_json::json!({
+            "signature": "swapExactTokensForTokensSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)",
+            "action": "Swap",
+        });
+        let entry = signature_entry_from_row(&row).expect("signature row");
+        let call = DecodedCall::from_signature(entry);
+        assert_eq!(call.action, "Swap");
+        assert_eq!(
+            call.method_name,
+            "swapExactTokensForTokensSupportingFeeOnTransferTokens"
+        );
+        assert_eq!(call.source, "signature_db");
+        assert!(call.params.is_null());
+        assert!(!call.is_unknown());
+        assert_eq!(
+            action_label(&call.action, Some(&vvs_protocol())),
+            Some("Swap on VVS Finance".to_string())
+        );
+    }
+
+    #[test]
+    fn signature_rows_without_action_stay_unknown_action() {
+        let row = serde_json::json!({ "signature": "multicall(bytes[])", "action": null });
+        let call = DecodedCall::from_signature(signature_entry_from_row(&row).expect("row"));
+        assert_eq!(call.action, "Unknown");
+        assert_eq!(call.method_name, "multicall");
+        assert!(signature_entry_from_row(&serde_json::json!({ "signature": " " })).is_none());
+    }
+
+    #[test]
+    fn protocol_row_requires_protocol_id() {
+        let row = serde_json::json!({ "protocol_id": "vvs", "protocol_name": "VVS Finance" });
+        assert_eq!(protocol_from_row(&row), Some(vvs_protocol()));
+        let utility = serde_json::json!({ "protocol_id": null, "protocol_name": null });
+        assert_eq!(protocol_from_row(&utility), None);
+    }
+
+    #[test]
+    fn permit2_selectors_match_abi() {
+        let selectors = [
+            ("0x2b67b570", abi::IPermit2::permitCall::SELECTOR),
+            (
+                "0x30f28b7a",
+                abi::IPermit2::permitTransferFromCall::SELECTOR,
+            ),
+            (
+                "0x4da3e5d9",
+                abi::IPermit2::permitBatchTransferFromCall::SELECTOR,
+            ),
+            ("0x36c78516", abi::IPermit2::transferFromCall::SELECTOR),
+        ];
+        for (hex, selector) in selectors {
+            assert_eq!(types::bytes_to_hex0x(selector), hex);
+        }
+    }
+
+    #[test]
+    fn decodes_permit2_permit_transfer_from() {
+        let owner = Address::repeat_byte(0x11);
+        let token = Address::repeat_byte(0x22);
+        let recipient = Address::repeat_byte(0x33);
+        let calldata = abi::IPermit2::permitTransferFromCall {
+            permit: abi::IPermit2::PermitTransferFrom {
+                permitted: abi::IPermit2::TokenPermissions {
+                    token,
+                    amount: U256::from(1_000u64),
+                },
+                nonce: U256::from(7u64),
+                deadline: U256::from(1_700_000_000u64),
+            },
+            transferDetails: abi::IPermit2::SignatureTransferDetails {
+                to: recipient,
+                requestedAmount: U256::from(750u64),
+            },
+            owner,
+            signature: vec![0xab; 65].into(),
+        }
+        .abi_encode();
+        let input_hex = types::bytes_to_hex0x(&calldata);
+
+        let (action, method, params) = decode_selector("0x30f28b7a", &input_hex).unwrap();
+        assert_eq!(action, "Transfer");
+        assert_eq!(method, "permitTransferFrom");
+        assert_eq!(params["owner"], owner.to_string());
+        assert_eq!(params["token"], token.to_string());
+        assert_eq!(params["to"], recipient.to_string());
+        assert_eq!(params["amount"], "750");
+        assert_eq!(params["permitted_amount"], "1000");
+        assert_eq!(params["nonce"], "7");
+        assert_eq!(params["deadline"], "1700000000");
+    }
+
+    #[test]
+    fn decodes_permit2_permit_as_approval() {
+        let calldata = abi::IPermit2::permitCall {
+            owner: Address::repeat_byte(0x11),
+            permitSingle: abi::IPermit2::PermitSingle {
+                details: abi::IPermit2::PermitDetails {
+                    token: Address::repeat_byte(0x22),
+                    amount: U256::from(500u64),
+                    expiration: 1_800_000_000u64,
+                    nonce: 1u64,
+                },
+                spender: Address::repeat_byte(0x44),
+                sigDeadline: U256::from(1_700_000_000u64),
+            },
+            signature: vec![0xcd; 65].into(),
+        }
+        .abi_encode();
+
+        let (action, method, params) =
+            decode_selector("0x2b67b570", &types::bytes_to_hex0x(&calldata)).unwrap();
+        assert_eq!(action, "Approval");
+        assert_eq!(method, "permit");
+        assert_eq!(params["spender"], Address::repeat_byte(0x44).to_string());
+        assert_eq!(params["amount"], "500");
+        assert_eq!(params["deadline"], "1700000000");
+    }
+
+    const HASH_A: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+    const HASH_B: &str = "0x2222222222222222222222222222222222222222222222222222222222222222";
+
+    #[test]
+    fn bulk_request_enforces_limits() {
+        assert!(validate_bulk_request(&[]).is_err());
+        let hashes = vec![HASH_A.to_string(); MAX_BULK_DECODE_HASHES + 1];
+        assert!(validate_bulk_request(&hashes).is_err());
+        validate_bulk_request(&hashes[..MAX_BULK_DECODE_HASHES]).expect("limit allowed");
+    }
+
+    #[test]
+    fn batch_calls_fetch_tx_and_receipt_per_hash() {
+        let calls = batch_calls_for(&[HASH_A, HASH_B]);
+        assert_eq!(calls.len(), 2 * CALLS_PER_HASH);
+        assert_eq!(calls[0].0, "eth_getTransactionByHash");
+        assert_eq!(calls[1].0, "eth_getTransactionReceipt");
+        assert_eq!(calls[2].1, serde_json::json!([HASH_B]));
+        assert_eq!(calls[3].1, serde_json::json!([HASH_B]));
+    }
+
+    #[test]
+    fn batch_results_pair_per_hash_with_isolated_errors() {
+        let tx = serde_json::json!({ "input": "0x" });
+        let paired = pair_batch_results(vec![
+            Ok(tx.clone()),
+            Ok(Value::Null),
+            Err(CroLensError::RpcError("boom".to_string())),
+            Ok(Value::Null),
+            Ok(Value::Null),
+            Ok(Value::Null),
+        ]);
+        assert_eq!(paired.len(), 3);
+        assert_eq!(paired[0], Ok((tx, Value::Null)));
+        assert!(paired[1].as_ref().is_err_and(|err| err.contains("boom")));
+        assert_eq!(paired[2], Err("Transaction not found".to_string()));
+    }
+
+    #[test]
+    fn bulk_items_report_per_item_errors() {
+        let decoded = serde_json::json!({
+            "action": "Transfer",
+            "status": "0x1",
+            "gas_used": "21000",
+            "confirmations": 3,
+            "decoded": { "method_name": "transfer" }
+        });
+        let items = vec![
+            bulk_item(HASH_A, Ok(decoded.clone()), false),
+            bulk_item(HASH_B, Err("Transaction not found".to_string()), false),
+        ];
+        assert_eq!(items[0]["ok"], true);
+        assert_eq!(items[0]["result"], decoded);
+        assert_eq!(items[1]["ok"], false);
+        assert_eq!(items[1]["error"], "Transaction not found");
+        assert_eq!(decoded_count(&items), 1);
+
+        let simple = bulk_item(HASH_A, Ok(decoded), true);
+        assert_eq!(
+            simple["text"],
+            "Transfer: transfer | Status: 0x1 | Gas: 21000 | Confirmations: 3"
+        );
+    }
+
+    #[test]
+    fn summary_prefers_protocol_annotated_action() {
+        let decoded = serde_json::json!({
+            "action": "Swap",
+            "action_label": "Swap on VVS Finance",
+            "status": "0x1",
+            "gas_used": "150000",
+            "decoded": { "method_name": "swapExactTokensForTokens" }
+        });
+        assert_eq!(
+            decoded_summary(&decoded),
+            "Swap on VVS Finance: swapExactTokensForTokens | Status: 0x1 | Gas: 150000"
+        );
+    }
 }
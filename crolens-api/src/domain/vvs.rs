@@ -1,21 +1,67 @@
+use std::collections::HashMap;
+
+use alloy_primitives::U256;
 use serde::Deserialize;
 use serde_json::Value;
 
+use crate::domain::farm_apy;
 use crate::error::{CroLensError, Result};
 use crate::infra;
 use crate::types;
 
 #[derive(Debug, Deserialize)]
-struct SimpleModeArgs {
+struct VvsFarmsArgs {
+    /// 默认只列出 allocPoint 非零 (仍有排放) 的 farm
+    #[serde(default)]
+    include_inactive: bool,
     #[serde(default)]
     simple_mode: bool,
 }
 
+/// 只保留有 MasterChef pid 的池子 (没有 pid 的不是 farm)，并按 allocPoint 过滤已结束的 farm；
+/// 返回 (保留的 farm, 因 allocPoint 为零被隐藏的数量)。排放读取失败或某个 farm 缺少读数时无法判断，保留该 farm
+fn select_active_farms(
+    pools: Vec<infra::config::DexPool>,
+    emissions: Option<&HashMap<i64, farm_apy::FarmEmission>>,
+    include_inactive: bool,
+) -> (Vec<infra::config::DexPool>, usize) {
+    let emissions = emissions.filter(|_| !include_inactive);
+    let mut hidden = 0usize;
+    let active = pools
+        .into_iter()
+        .filter(|pool| {
+            let Some(pid) = pool.pool_index else {
+                return false;
+            };
+            let inactive = emissions
+                .and_then(|e| e.get(&pid))
+                .is_some_and(|e| e.alloc_point.is_zero());
+            hidden += usize::from(inactive);
+            !inactive
+        })
+        .collect();
+    (active, hidden)
+}
+
 pub async fn get_vvs_farms(services: &infra::Services, args: Value) -> Result<Value> {
-    let input: SimpleModeArgs = serde_json::from_value(args)
+    let input: VvsFarmsArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
 
-    let pools = infra::config::list_dex_pools_cached(&services.db, &services.kv, "vvs").await?;
+    let pools = infra::config::list_dex_pools_cached(
+        &services.db,
+        &services.kv,
+        &services.kv_prefix,
+        "vvs",
+    )
+    .await?;
+    // poolInfo (allocPoint) 通过一次 multicall 读取，同时用于过滤与排放汇总
+    let farm_emissions = fetch_emissions(services, &pools).await;
+    let emissions = match farm_emissions.as_ref() {
+        Some(map) => emission_summary(services, map).await,
+        None => None,
+    };
+    let (pools, inactive_hidden) =
+        select_active_farms(pools, farm_emissions.as_ref(), input.include_inactive);
     let farms: Vec<Value> = pools
         .into_iter()
         .map(|p| {
@@ -31,13 +77,84 @@ pub async fn get_vvs_farms(services: &infra::Services, args: Value) -> Result<Va
         .collect();
 
     if input.simple_mode {
+        let mut text = format!("VVS farms: {}", farms.len());
+        if let Some(daily_usd) = emissions
+            .as_ref()
+            .and_then(|e| e.get("daily_usd"))
+            .and_then(|v| v.as_str())
+        {
+            text.push_str(&format!(" | Daily emissions: ${daily_usd}"));
+        }
         return Ok(serde_json::json!({
-            "text": format!("VVS farms: {}", farms.len()),
+            "text": text,
             "meta": services.meta(),
         }));
     }
 
-    Ok(serde_json::json!({ "farms": farms, "meta": services.meta() }))
+    Ok(serde_json::json!({
+        "farms": farms,
+        "include_inactive": input.include_inactive,
+        "inactive_hidden": inactive_hidden,
+        "emissions": emissions,
+        "meta": services.meta()
+    }))
+}
+
+/// 读取所有 farm 的 poolInfo 等排放参数 (best-effort)：读取失败时返回 None
+async fn fetch_emissions(
+    services: &infra::Services,
+    pools: &[infra::config::DexPool],
+) -> Option<HashMap<i64, farm_apy::FarmEmission>> {
+    let farms: Vec<(i64, alloy_primitives::Address)> = pools
+        .iter()
+        .filter_map(|p| p.pool_index.map(|pid| (pid, p.lp_address)))
+        .collect();
+    let masterchef = services.protocol_contract("vvs", "masterchef").await.ok()?;
+    farm_apy::fetch_farm_emissions(services, masterchef, &farms)
+        .await
+        .ok()
+}
+
+/// 全部 farm 的排放汇总 (best-effort)：读取失败时返回 None，不影响 farm 列表
+async fn emission_summary(
+    services: &infra::Services,
+    emissions: &HashMap<i64, farm_apy::FarmEmission>,
+) -> Option<Value> {
+    // vvsPerBlock 为 MasterChef 全局参数，任一 farm 的读数都相同
+    let vvs_per_block = emissions.values().next()?.vvs_per_block;
+
+    let tokens = infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix)
+        .await
+        .ok()?;
+    let vvs_price_usd = match tokens.iter().find(|t| t.symbol.eq_ignore_ascii_case("VVS")) {
+        Some(token) => infra::price::get_price_usd(services, token)
+            .await
+            .ok()
+            .flatten(),
+        None => None,
+    };
+
+    Some(emission_summary_json(
+        vvs_per_block,
+        vvs_price_usd,
+        &services.precision,
+    ))
+}
+
+fn emission_summary_json(
+    vvs_per_block: U256,
+    vvs_price_usd: Option<f64>,
+    precision: &types::Precision,
+) -> Value {
+    let daily_vvs = farm_apy::daily_emission_vvs(vvs_per_block);
+    let daily_usd = vvs_price_usd.and_then(|p| farm_apy::daily_emission_usd(vvs_per_block, p));
+    serde_json::json!({
+        "vvs_per_block": types::format_units(&vvs_per_block, 18),
+        "blocks_per_day": farm_apy::BLOCKS_PER_DAY as u64,
+        "daily_vvs": daily_vvs.map(|v| format!("{:.2}", v)),
+        "vvs_price_usd": vvs_price_usd.map(|p| precision.price(p)),
+        "daily_usd": daily_usd.map(|v| precision.usd(v)),
+    })
 }
 
 #[derive(Debug, Deserialize)]
@@ -90,11 +207,51 @@ mod tests {
         assert!(matches!(err, crate::error::CroLensError::InvalidAddress(_)));
     }
 
+    #[test]
+    fn emission_summary_reports_daily_vvs_and_usd() {
+        let per_block = U256::from(10u64).pow(U256::from(18u64));
+        let summary = emission_summary_json(per_block, Some(0.01), &types::Precision::default());
+        assert_eq!(summary["vvs_per_block"], "1");
+        let daily_vvs: f64 = summary["daily_vvs"]
+            .as_str()
+            .expect("daily_vvs")
+            .parse()
+            .expect("number");
+        assert!((daily_vvs - farm_apy::BLOCKS_PER_DAY).abs() < 0.01);
+        let daily_usd: f64 = summary["daily_usd"]
+            .as_str()
+            .expect("daily_usd")
+            .parse()
+            .expect("number");
+        assert!((daily_usd - farm_apy::BLOCKS_PER_DAY * 0.01).abs() < 0.01);
+    }
+
+    #[test]
+    fn emission_summary_uses_configured_precision() {
+        let per_block = U256::from(10u64).pow(U256::from(18u64));
+        let precision = types::Precision::parse(Some("4"), Some("3"), None);
+        let summary = emission_summary_json(per_block, Some(0.0123456), &precision);
+        let daily_usd = summary["daily_usd"].as_str().expect("daily_usd");
+        assert_eq!(
+            daily_usd.split_once('.').map(|(_, frac)| frac.len()),
+            Some(4)
+        );
+        assert_eq!(summary["vvs_price_usd"], "0.012");
+    }
+
+    #[test]
+    fn emission_summary_without_price_omits_usd() {
+        let summary = emission_summary_json(U256::from(1u64), None, &types::Precision::default());
+        assert!(summary["daily_usd"].is_null());
+        assert!(summary["vvs_price_usd"].is_null());
+    }
+
     #[test]
     fn args_deserialize_defaults() {
         let json = serde_json::json!({});
-        let args: SimpleModeArgs = serde_json::from_value(json).expect("args should parse");
+        let args: VvsFarmsArgs = serde_json::from_value(json).expect("args should parse");
         assert!(!args.simple_mode);
+        assert!(!args.include_inactive);
 
         let json = serde_json::json!({ "address": "0x1234567890123456789012345678901234567890" });
         let args: VvsRewardsArgs = serde_json::from_value(json).expect("args should parse");
@@ -110,4 +267,70 @@ mod tests {
         let args: VvsRewardsArgs = serde_json::from_value(json).expect("args should parse");
         assert!(args.simple_mode);
     }
+
+    fn farm_pool(pool_index: Option<i64>) -> infra::config::DexPool {
+        let byte = pool_index.unwrap_or(0xff) as u8;
+        infra::config::DexPool {
+            pool_id: format!("pool-{byte}"),
+            pool_index,
+            lp_address: alloy_primitives::Address::from([byte; 20]),
+            token0_address: alloy_primitives::Address::from([0x01; 20]),
+            token1_address: alloy_primitives::Address::from([0x02; 20]),
+            token0_symbol: "VVS".to_string(),
+            token1_symbol: "CRO".to_string(),
+        }
+    }
+
+    fn emission(alloc_point: u64) -> farm_apy::FarmEmission {
+        farm_apy::FarmEmission {
+            alloc_point: U256::from(alloc_point),
+            total_alloc_point: U256::from(1_000u64),
+            vvs_per_block: U256::from(1u64),
+            staked_lp: U256::from(1u64),
+        }
+    }
+
+    fn mixed_pools() -> (
+        Vec<infra::config::DexPool>,
+        HashMap<i64, farm_apy::FarmEmission>,
+    ) {
+        let pools = vec![
+            farm_pool(Some(0)),
+            farm_pool(Some(1)),
+            farm_pool(Some(2)),
+            farm_pool(Some(3)),
+            farm_pool(None),
+        ];
+        // pid 3 缺少读数 (单个调用失败)
+        let emissions = HashMap::from([(0, emission(400)), (1, emission(0)), (2, emission(600))]);
+        (pools, emissions)
+    }
+
+    #[test]
+    fn active_farms_exclude_zero_alloc_points() {
+        let (pools, emissions) = mixed_pools();
+        let (active, hidden) = select_active_farms(pools, Some(&emissions), false);
+        let indexes: Vec<Option<i64>> = active.iter().map(|p| p.pool_index).collect();
+        assert_eq!(indexes, vec![Some(0), Some(2), Some(3)]);
+        // 只有 allocPoint 为零的 farm 计入隐藏数量，没有 pid 的池子不是 farm
+        assert_eq!(hidden, 1);
+    }
+
+    #[test]
+    fn include_inactive_keeps_every_farm() {
+        let (pools, emissions) = mixed_pools();
+        let (all, hidden) = select_active_farms(pools, Some(&emissions), true);
+        let indexes: Vec<Option<i64>> = all.iter().map(|p| p.pool_index).collect();
+        assert_eq!(indexes, vec![Some(0), Some(1), Some(2), Some(3)]);
+        assert_eq!(hidden, 0);
+    }
+
+    #[test]
+    fn unknown_emissions_keep_every_farm() {
+        let (pools, _) = mixed_pools();
+        let (all, hidden) = select_active_farms(pools, None, false);
+        assert_eq!(all.len(), 4);
+        assert!(all.iter().all(|p| p.pool_index.is_some()));
+        assert_eq!(hidden, 0);
+    }
 }
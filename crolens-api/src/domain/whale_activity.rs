@@ -1,8 +1,31 @@
+use std::collections::HashMap;
+
+use alloy_primitives::{Address, I256, U256};
 use serde::Deserialize;
 use serde_json::Value;
 
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::rpc::LogFilter;
+use crate::types;
+
+const DEFAULT_LIMIT: u64 = 20;
+const MAX_LIMIT: u64 = 100;
+
+const DEFAULT_TOKEN: &str = "WCRO";
+const DEFAULT_MIN_VALUE_USD: f64 = 100_000.0;
+const DEFAULT_BLOCKS: u64 = 1_000;
+/// 单次 eth_getLogs 的区块跨度上限，避免 RPC 拒绝或超时
+const MAX_BLOCKS: u64 = 5_000;
+
+/// contracts.type 中视为交易所/跨链桥的类型 (小写匹配)
+const EXCHANGE_CONTRACT_TYPES: &[&str] = &["cex", "bridge"];
+/// 交易所/跨链桥标签行的 KV 缓存
+const EXCHANGE_LABELS_CACHE_KEY: &str = "cache:exchange_labels";
+const EXCHANGE_LABELS_CACHE_TTL_SECS: u64 = 600;
+
+/// 净流量差额低于流入+流出总额的该百分比时视为中性
+const NET_FLOW_NEUTRAL_PCT: u64 = 10;
 
 #[derive(Debug, Deserialize)]
 struct WhaleActivityArgs {
@@ -13,27 +36,387 @@ struct WhaleActivityArgs {
     #[serde(default)]
     blocks: Option<u64>,
     #[serde(default)]
+    limit: Option<u64>,
+    #[serde(default)]
     simple_mode: bool,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+struct TransferLog {
+    tx_hash: String,
+    block_number: u64,
+    from: Address,
+    to: Address,
+    amount: U256,
+}
+
+/// contracts 表中带标签的交易所/跨链桥地址
+#[derive(Debug, Clone, PartialEq)]
+struct AddressLabel {
+    name: String,
+    kind: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlowDirection {
+    /// 钱包 -> 交易所/跨链桥 (潜在卖压)
+    ExchangeInflow,
+    /// 交易所/跨链桥 -> 钱包 (提币囤积)
+    ExchangeOutflow,
+    /// 两端都是交易所/跨链桥
+    ExchangeInternal,
+    Wallet,
+}
+
+impl FlowDirection {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::ExchangeInflow => "exchange_inflow",
+            Self::ExchangeOutflow => "exchange_outflow",
+            Self::ExchangeInternal => "exchange_internal",
+            Self::Wallet => "wallet",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct FlowTotal {
+    amount: U256,
+    transfers: u64,
+}
+
+impl FlowTotal {
+    fn add(&mut self, amount: U256) {
+        self.amount = self.amount.saturating_add(amount);
+        self.transfers += 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct NetFlow {
+    inflow: FlowTotal,
+    outflow: FlowTotal,
+    internal: FlowTotal,
+    wallet: FlowTotal,
+}
+
+impl NetFlow {
+    /// 流出交易所多于流入为囤积，反之为派发；差额不足总额的 10% 时为中性
+    fn signal(&self) -> &'static str {
+        let (inflow, outflow) = (self.inflow.amount, self.outflow.amount);
+        let total = inflow.saturating_add(outflow);
+        let diff = if outflow >= inflow {
+            outflow - inflow
+        } else {
+            inflow - outflow
+        };
+        if total.is_zero()
+            || diff.saturating_mul(U256::from(100u64))
+                < total.saturating_mul(U256::from(NET_FLOW_NEUTRAL_PCT))
+        {
+            "neutral"
+        } else if outflow > inflow {
+            "accumulation"
+        } else {
+            "distribution"
+        }
+    }
+
+    /// 净流出数量 (最小单位)，正数表示净流出交易所 (囤积)，负数为净流入
+    fn net_outflow(&self) -> I256 {
+        let to_signed = |amount: U256| I256::try_from(amount).unwrap_or(I256::MAX);
+        to_signed(self.outflow.amount).saturating_sub(to_signed(self.inflow.amount))
+    }
+}
+
+/// 有符号的最小单位数量按小数位格式化
+fn format_signed_units(amount: I256, decimals: u8) -> String {
+    let units = types::format_units(&amount.unsigned_abs(), decimals);
+    if amount.is_negative() {
+        format!("-{units}")
+    } else {
+        units
+    }
+}
+
+/// 解码 ERC20 Transfer 日志；ERC721 (tokenId 为 indexed，共 4 个 topic) 和格式异常的日志返回 None
+fn decode_transfer_log(log: &Value) -> Option<TransferLog> {
+    let topics = log.get("topics")?.as_array()?;
+    if topics.len() != 3 {
+        return None;
+    }
+    let topic0 = topics.first()?.as_str()?;
+    if !topic0.eq_ignore_ascii_case(types::TRANSFER_TOPIC) {
+        return None;
+    }
+    let from = types::topic_to_address(topics.get(1)?.as_str()?)?;
+    let to = types::topic_to_address(topics.get(2)?.as_str()?)?;
+    let amount = types::parse_u256_hex(log.get("data")?.as_str()?).ok()?;
+    let block_hex = log.get("blockNumber")?.as_str()?;
+    let block_number = u64::from_str_radix(block_hex.trim_start_matches("0x"), 16).ok()?;
+    let tx_hash = log.get("transactionHash")?.as_str()?.to_string();
+    Some(TransferLog {
+        tx_hash,
+        block_number,
+        from,
+        to,
+        amount,
+    })
+}
+
+fn classify_transfer(
+    transfer: &TransferLog,
+    labels: &HashMap<Address, AddressLabel>,
+) -> FlowDirection {
+    match (
+        labels.contains_key(&transfer.from),
+        labels.contains_key(&transfer.to),
+    ) {
+        (false, true) => FlowDirection::ExchangeInflow,
+        (true, false) => FlowDirection::ExchangeOutflow,
+        (true, true) => FlowDirection::ExchangeInternal,
+        (false, false) => FlowDirection::Wallet,
+    }
+}
+
+fn aggregate_net_flow(
+    transfers: &[TransferLog],
+    labels: &HashMap<Address, AddressLabel>,
+) -> NetFlow {
+    let mut flow = NetFlow::default();
+    for transfer in transfers {
+        let total = match classify_transfer(transfer, labels) {
+            FlowDirection::ExchangeInflow => &mut flow.inflow,
+            FlowDirection::ExchangeOutflow => &mut flow.outflow,
+            FlowDirection::ExchangeInternal => &mut flow.internal,
+            FlowDirection::Wallet => &mut flow.wallet,
+        };
+        total.add(transfer.amount);
+    }
+    flow
+}
+
+fn units_to_f64(amount: &U256, decimals: u8) -> f64 {
+    types::format_units(amount, decimals)
+        .parse::<f64>()
+        .unwrap_or(0.0)
+}
+
+async fn list_exchange_label_rows(db: &worker::D1Database) -> Result<Vec<Value>> {
+    let statement =
+        db.prepare("SELECT address, name, type FROM contracts WHERE type IN ('CEX', 'Bridge')");
+    let result = infra::db::run("load_exchange_labels", || statement.all()).await?;
+    result
+        .results()
+        .map_err(|err| CroLensError::DbError(err.to_string()))
+}
+
+/// 从 contracts 表加载交易所/跨链桥地址标签 (原始行在 KV 缓存 10 分钟)
+async fn load_exchange_labels(
+    services: &infra::Services,
+) -> Result<HashMap<Address, AddressLabel>> {
+    let key = services.kv_key(EXCHANGE_LABELS_CACHE_KEY);
+    let cached = services
+        .kv_get_text(&key)
+        .await
+        .and_then(|text| serde_json::from_str::<Vec<Value>>(&text).ok());
+    let rows = match cached {
+        Some(rows) => rows,
+        None => {
+            let rows = list_exchange_label_rows(&services.db).await?;
+            if let Ok(text) = serde_json::to_string(&rows) {
+                services
+                    .kv_put_text(&key, text, EXCHANGE_LABELS_CACHE_TTL_SECS)
+                    .await;
+            }
+            rows
+        }
+    };
+    Ok(exchange_labels_from_rows(&rows))
+}
+
+fn exchange_labels_from_rows(rows: &[Value]) -> HashMap<Address, AddressLabel> {
+    let mut labels = HashMap::new();
+    for row in rows {
+        let Some(kind) = row.get("type").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        if !EXCHANGE_CONTRACT_TYPES.contains(&kind.trim().to_lowercase().as_str()) {
+            continue;
+        }
+        let Some(address) = row
+            .get("address")
+            .and_then(|v| v.as_str())
+            .and_then(|v| types::parse_address(v).ok())
+        else {
+            continue;
+        };
+        let name = row
+            .get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        labels.insert(
+            address,
+            AddressLabel {
+                name,
+                kind: kind.trim().to_string(),
+            },
+        );
+    }
+    labels
+}
+
+fn label_json(labels: &HashMap<Address, AddressLabel>, address: &Address) -> Value {
+    labels
+        .get(address)
+        .map(|label| serde_json::json!({ "name": label.name, "type": label.kind }))
+        .unwrap_or(Value::Null)
+}
+
 pub async fn get_whale_activity(services: &infra::Services, args: Value) -> Result<Value> {
     let input: WhaleActivityArgs = serde_json::from_value(args)
         .map_err(|err| CroLensError::invalid_params(format!("Invalid input: {err}")))?;
+    let (limit, limited) = types::clamp_limit(input.limit, DEFAULT_LIMIT, MAX_LIMIT);
+    let (blocks, blocks_limited) = types::clamp_limit(input.blocks, DEFAULT_BLOCKS, MAX_BLOCKS);
+    let min_value_usd = input
+        .min_value_usd
+        .filter(|v| v.is_finite() && *v >= 0.0)
+        .unwrap_or(DEFAULT_MIN_VALUE_USD);
+
+    let tokens =
+        infra::token::list_tokens_cached(&services.db, &services.kv, &services.kv_prefix).await?;
+    let token =
+        infra::token::resolve_token(&tokens, input.token.as_deref().unwrap_or(DEFAULT_TOKEN))?;
+
+    let rpc = services.rpc()?;
+    let latest = rpc.latest_block_number_cached().await?;
+    let from_block = latest.saturating_sub(blocks.saturating_sub(1));
+    let filter = LogFilter::new()
+        .address(token.address)
+        .event(types::TRANSFER_TOPIC)
+        .blocks(from_block, latest);
+
+    let (logs, labels, price) = futures_util::future::join3(
+        rpc.eth_get_logs(&filter),
+        load_exchange_labels(services),
+        infra::price::get_price_usd(services, &token),
+    )
+    .await;
+    let transfers: Vec<TransferLog> = logs?.iter().filter_map(decode_transfer_log).collect();
+    let labels = labels?;
+    let price_usd = price?.filter(|p| *p > 0.0);
+
+    let flow = aggregate_net_flow(&transfers, &labels);
+    let value_usd = |amount: &U256| price_usd.map(|p| units_to_f64(amount, token.decimals) * p);
+
+    // 价格未知时无法按 USD 过滤，只按金额排序
+    let mut whales: Vec<&TransferLog> = transfers
+        .iter()
+        .filter(|t| {
+            value_usd(&t.amount)
+                .map(|v| v >= min_value_usd)
+                .unwrap_or(true)
+        })
+        .collect();
+    whales.sort_by_key(|t| std::cmp::Reverse(t.amount));
+    let total_whales = whales.len();
+    whales.truncate(limit as usize);
+
+    let precision = &services.precision;
+    let usd_json = |amount: &U256| {
+        value_usd(amount)
+            .map(|v| Value::String(precision.usd(v)))
+            .unwrap_or(Value::Null)
+    };
+    let total_json = |total: &FlowTotal| {
+        serde_json::json!({
+            "amount": types::format_units(&total.amount, token.decimals),
+            "value_usd": usd_json(&total.amount),
+            "transfers": total.transfers,
+        })
+    };
+
+    // 正数表示净流出交易所 (囤积)
+    let net_amount = format_signed_units(flow.net_outflow(), token.decimals);
+    let net_usd = price_usd.map(|p| precision.usd(net_amount.parse::<f64>().unwrap_or(0.0) * p));
+    let signal = flow.signal();
 
     if input.simple_mode {
+        let net_text = match &net_usd {
+            Some(usd) => format!("${usd}"),
+            None => format!("{net_amount} {}", token.symbol),
+        };
         return Ok(serde_json::json!({
-            "text": "Whale activity monitoring is not available in this build (placeholder).",
+            "text": format!(
+                "{} whale activity (last {} blocks): {} transfers >= ${}; exchange inflow {} / outflow {} transfers, net outflow {} -> {}",
+                token.symbol,
+                blocks,
+                total_whales,
+                precision.usd(min_value_usd),
+                flow.inflow.transfers,
+                flow.outflow.transfers,
+                net_text,
+                signal
+            ),
             "meta": services.meta(),
         }));
     }
 
-    Ok(serde_json::json!({
-        "token": input.token,
-        "min_value_usd": input.min_value_usd,
-        "blocks": input.blocks,
-        "events": [],
+    let events: Vec<Value> = whales
+        .iter()
+        .map(|t| {
+            serde_json::json!({
+                "tx_hash": t.tx_hash,
+                "block_number": t.block_number,
+                "from": t.from.to_string(),
+                "to": t.to.to_string(),
+                "from_label": label_json(&labels, &t.from),
+                "to_label": label_json(&labels, &t.to),
+                "direction": classify_transfer(t, &labels).as_str(),
+                "amount": types::format_units(&t.amount, token.decimals),
+                "value_usd": usd_json(&t.amount),
+            })
+        })
+        .collect();
+
+    let mut result = serde_json::json!({
+        "token": {
+            "symbol": token.symbol,
+            "address": token.address.to_string(),
+            "price_usd": price_usd.map(|p| precision.price(p)),
+        },
+        "min_value_usd": precision.usd(min_value_usd),
+        "blocks": blocks,
+        "from_block": from_block,
+        "to_block": latest,
+        "limit": limit,
+        "transfers_scanned": transfers.len(),
+        "events": events,
+        "net_flow": {
+            "exchange_inflow": total_json(&flow.inflow),
+            "exchange_outflow": total_json(&flow.outflow),
+            "exchange_internal": total_json(&flow.internal),
+            "net_outflow_amount": net_amount,
+            "net_outflow_usd": net_usd,
+            "signal": signal,
+            "labeled_addresses": labels.len(),
+        },
         "meta": services.meta(),
-    }))
+    });
+    if limited || total_whales > events.len() {
+        result["limited"] = Value::Bool(true);
+    }
+    if blocks_limited {
+        result["blocks_limited"] = Value::Bool(true);
+    }
+    if price_usd.is_none() {
+        result["warnings"] = serde_json::json!([{
+            "type": "price_unavailable",
+            "message": "USD price unknown; min_value_usd was not applied"
+        }]);
+    }
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -47,6 +430,7 @@ mod tests {
         assert!(args.token.is_none());
         assert!(args.min_value_usd.is_none());
         assert!(args.blocks.is_none());
+        assert!(args.limit.is_none());
         assert!(!args.simple_mode);
     }
 
@@ -77,4 +461,169 @@ mod tests {
         let args: WhaleActivityArgs = serde_json::from_value(json).expect("should parse");
         assert!(args.simple_mode);
     }
+
+    fn label(name: &str, kind: &str) -> AddressLabel {
+        AddressLabel {
+            name: name.to_string(),
+            kind: kind.to_string(),
+        }
+    }
+
+    fn transfer(from: Address, to: Address, amount: u64) -> TransferLog {
+        TransferLog {
+            tx_hash: "0xabc".to_string(),
+            block_number: 1,
+            from,
+            to,
+            amount: U256::from(amount),
+        }
+    }
+
+    fn sample_labels() -> (Address, Address, HashMap<Address, AddressLabel>) {
+        let cex = Address::repeat_byte(0xce);
+        let bridge = Address::repeat_byte(0xb1);
+        let labels = HashMap::from([
+            (cex, label("Example Exchange", "CEX")),
+            (bridge, label("Example Bridge", "Bridge")),
+        ]);
+        (cex, bridge, labels)
+    }
+
+    #[test]
+    fn net_flow_aggregates_per_direction() {
+        let (cex, bridge, labels) = sample_labels();
+        let wallet_a = Address::repeat_byte(0x0a);
+        let wallet_b = Address::repeat_byte(0x0b);
+        let transfers = vec![
+            transfer(wallet_a, cex, 100),
+            transfer(wallet_b, bridge, 50),
+            transfer(cex, wallet_a, 400),
+            transfer(cex, bridge, 70),
+            transfer(wallet_a, wallet_b, 9),
+        ];
+
+        let flow = aggregate_net_flow(&transfers, &labels);
+        assert_eq!(flow.inflow.amount, U256::from(150u64));
+        assert_eq!(flow.inflow.transfers, 2);
+        assert_eq!(flow.outflow.amount, U256::from(400u64));
+        assert_eq!(flow.outflow.transfers, 1);
+        assert_eq!(flow.internal.amount, U256::from(70u64));
+        assert_eq!(flow.wallet.transfers, 1);
+        assert_eq!(flow.signal(), "accumulation");
+    }
+
+    #[test]
+    fn net_flow_signals_distribution_and_neutral() {
+        let (cex, _, labels) = sample_labels();
+        let wallet = Address::repeat_byte(0x0a);
+
+        let distribution = aggregate_net_flow(
+            &[transfer(wallet, cex, 500), transfer(cex, wallet, 100)],
+            &labels,
+        );
+        assert_eq!(distribution.signal(), "distribution");
+
+        let neutral = aggregate_net_flow(
+            &[transfer(wallet, cex, 100), transfer(cex, wallet, 105)],
+            &labels,
+        );
+        assert_eq!(neutral.signal(), "neutral");
+
+        assert_eq!(aggregate_net_flow(&[], &labels).signal(), "neutral");
+    }
+
+    #[test]
+    fn net_outflow_keeps_full_precision_and_sign() {
+        let (cex, _, labels) = sample_labels();
+        let wallet = Address::repeat_byte(0x0a);
+        // 超过 f64 的 53 位有效精度
+        let big = U256::from(10u64).pow(U256::from(27u64)) + U256::from(1u64);
+        let accumulation = aggregate_net_flow(
+            &[
+                TransferLog {
+                    amount: big,
+                    ..transfer(cex, wallet, 0)
+                },
+                transfer(wallet, cex, 2),
+            ],
+            &labels,
+        );
+        assert_eq!(
+            format_signed_units(accumulation.net_outflow(), 18),
+            "999999999.999999999999999999"
+        );
+
+        let distribution = aggregate_net_flow(
+            &[transfer(wallet, cex, 1_500), transfer(cex, wallet, 500)],
+            &labels,
+        );
+        assert_eq!(format_signed_units(distribution.net_outflow(), 3), "-1");
+        assert_eq!(
+            format_signed_units(aggregate_net_flow(&[], &labels).net_outflow(), 18),
+            "0"
+        );
+    }
+
+    #[test]
+    fn exchange_labels_keep_only_cex_and_bridge_rows() {
+        let rows = vec![
+            serde_json::json!({
+                "address": "0xcececececececececececececececececececece",
+                "name": "Example Exchange",
+                "type": "CEX"
+            }),
+            serde_json::json!({
+                "address": "0x145863Eb42Cf62847A6Ca784e6416C1682b1b2Ae",
+                "name": "VVS Router",
+                "type": "DEX Router"
+            }),
+            serde_json::json!({
+                "address": "0xb1b1b1b1b1b1b1b1b1b1b1b1b1b1b1b1b1b1b1b1",
+                "name": "Example Bridge",
+                "type": "bridge"
+            }),
+        ];
+        let labels = exchange_labels_from_rows(&rows);
+        assert_eq!(labels.len(), 2);
+        assert_eq!(
+            labels.get(&Address::repeat_byte(0xb1)),
+            Some(&label("Example Bridge", "bridge"))
+        );
+    }
+
+    #[test]
+    fn decodes_erc20_transfer_log() {
+        let log = serde_json::json!({
+            "topics": [
+                types::TRANSFER_TOPIC,
+                types::address_to_topic(Address::repeat_byte(0x0a)),
+                types::address_to_topic(Address::repeat_byte(0xce)),
+            ],
+            "data": "0x00000000000000000000000000000000000000000000000000000000000003e8",
+            "blockNumber": "0x10",
+            "transactionHash": "0xdead"
+        });
+        let decoded = decode_transfer_log(&log).expect("should decode");
+        assert_eq!(decoded.from, Address::repeat_byte(0x0a));
+        assert_eq!(decoded.to, Address::repeat_byte(0xce));
+        assert_eq!(decoded.amount, U256::from(1000u64));
+        assert_eq!(decoded.block_number, 16);
+        assert_eq!(decoded.tx_hash, "0xdead");
+    }
+
+    #[test]
+    fn skips_erc721_transfer_log() {
+        let log = serde_json::json!({
+            "topics": [
+                types::TRANSFER_TOPIC,
+                types::address_to_topic(Address::repeat_byte(0x0a)),
+                types::address_to_topic(Address::repeat_byte(0xce)),
+                "0x0000000000000000000000000000000000000000000000000000000000000001",
+            ],
+            "data": "0x",
+            "blockNumber": "0x10",
+            "transactionHash": "0xdead"
+        });
+        assert!(decode_transfer_log(&log).is_none());
+    }
 }
@@ -1,7 +1,7 @@
 use serde_json::Value;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum CroLensError {
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
@@ -27,10 +27,17 @@ pub enum CroLensError {
         retry_after_secs: Option<u32>,
     },
 
+    /// 缺少必需的配置 (如 BLOCKPI_RPC_URL)，与运行时 RPC 故障区分
+    #[error("{0} not configured")]
+    NotConfigured(&'static str),
+
     #[error("Simulation failed: {0}")]
-    #[allow(dead_code)]
     SimulationFailed(String),
 
+    /// 整个请求超过 REQUEST_TIMEOUT_MS，HTTP 层映射为 504
+    #[error("Request timed out after {timeout_ms}ms")]
+    Timeout { timeout_ms: u64 },
+
     #[error("Rate limit exceeded")]
     #[allow(dead_code)]
     RateLimitExceeded { retry_after_secs: Option<u32> },
@@ -102,7 +109,13 @@ impl CroLensError {
                 self.to_string(),
                 retry_after_secs.map(|v| serde_json::json!({ "retry_after": v })),
             ),
+            Self::NotConfigured(_) => (-32501, self.to_string(), None),
             Self::SimulationFailed(_) => (-32500, self.to_string(), None),
+            Self::Timeout { timeout_ms } => (
+                -32504,
+                self.to_string(),
+                Some(serde_json::json!({ "timeout_ms": timeout_ms })),
+            ),
             Self::RateLimitExceeded { retry_after_secs } => (
                 -32003,
                 self.to_string(),
@@ -116,6 +129,39 @@ impl CroLensError {
     }
 }
 
+/// 限流响应未携带 retry_after 时使用的 Retry-After 秒数
+pub const DEFAULT_RATE_LIMIT_RETRY_AFTER_SECS: i64 = 3600;
+
+/// JSON-RPC 错误码对应的 HTTP 状态码
+pub fn http_status_for(code: i64) -> u16 {
+    match code {
+        -32003 => 429,
+        -32001 => 401,
+        -32002 => 402,
+        -32501 => 503,
+        -32504 => 504,
+        -32601 => 404,
+        -32600 | -32602 => 400,
+        _ => 500,
+    }
+}
+
+/// 需要设置 Retry-After 的错误码：限流总是返回 (缺省 1 小时)，服务不可用仅在 data 带 retry_after 时返回
+pub fn retry_after_for(code: i64, data: Option<&Value>) -> Option<i64> {
+    let retry_after = data
+        .and_then(|v| v.get("retry_after"))
+        .and_then(|v| v.as_i64());
+    match code {
+        -32003 => Some(
+            retry_after
+                .filter(|v| *v > 0)
+                .unwrap_or(DEFAULT_RATE_LIMIT_RETRY_AFTER_SECS),
+        ),
+        -32501 => retry_after,
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -186,10 +232,67 @@ mod tests {
         assert_eq!(out, Some(serde_json::json!({ "retry_after": 123 })));
     }
 
+    #[test]
+    fn maps_not_configured_as_service_unavailable() {
+        let err = CroLensError::NotConfigured("RPC");
+        let (code, message, data) = err.to_json_rpc_error();
+        assert_eq!(code, -32501);
+        assert_eq!(message, "RPC not configured");
+        assert_eq!(data, None);
+    }
+
+    #[test]
+    fn maps_timeout_code_with_budget_data() {
+        let err = CroLensError::Timeout { timeout_ms: 30_000 };
+        let (code, message, data) = err.to_json_rpc_error();
+        assert_eq!(code, -32504);
+        assert_eq!(message, "Request timed out after 30000ms");
+        assert_eq!(data, Some(serde_json::json!({ "timeout_ms": 30_000 })));
+    }
+
     #[test]
     fn maps_db_error_code() {
         let err = CroLensError::DbError("db".to_string());
         let (code, _, _) = err.to_json_rpc_error();
         assert_eq!(code, -32500);
     }
+
+    #[test]
+    fn maps_error_codes_to_http_status() {
+        assert_eq!(http_status_for(-32001), 401);
+        assert_eq!(http_status_for(-32002), 402);
+        assert_eq!(http_status_for(-32003), 429);
+        assert_eq!(http_status_for(-32501), 503);
+        assert_eq!(http_status_for(-32504), 504);
+        assert_eq!(http_status_for(-32601), 404);
+        assert_eq!(http_status_for(-32600), 400);
+        assert_eq!(http_status_for(-32602), 400);
+        assert_eq!(http_status_for(-32500), 500);
+        assert_eq!(http_status_for(-32700), 500);
+        assert_eq!(http_status_for(0), 500);
+    }
+
+    #[test]
+    fn rate_limit_retry_after_defaults_when_missing_or_invalid() {
+        let data = serde_json::json!({ "retry_after": 42 });
+        assert_eq!(retry_after_for(-32003, Some(&data)), Some(42));
+        assert_eq!(
+            retry_after_for(-32003, None),
+            Some(DEFAULT_RATE_LIMIT_RETRY_AFTER_SECS)
+        );
+        let zero = serde_json::json!({ "retry_after": 0 });
+        assert_eq!(
+            retry_after_for(-32003, Some(&zero)),
+            Some(DEFAULT_RATE_LIMIT_RETRY_AFTER_SECS)
+        );
+    }
+
+    #[test]
+    fn service_unavailable_retry_after_only_when_present() {
+        let data = serde_json::json!({ "retry_after": 30 });
+        assert_eq!(retry_after_for(-32501, Some(&data)), Some(30));
+        assert_eq!(retry_after_for(-32501, None), None);
+        assert_eq!(retry_after_for(-32601, Some(&data)), None);
+        assert_eq!(retry_after_for(-32504, Some(&data)), None);
+    }
 }
@@ -17,54 +17,124 @@ pub async fn lookup_api_key(db: &D1Database, api_key: &str) -> Result<Option<Api
     store.fetch_api_key(api_key.trim()).await
 }
 
-pub fn validate_api_key_format(api_key: &str) -> Result<()> {
-    let trimmed = api_key.trim();
-    if trimmed.is_empty() {
-        return Err(CroLensError::unauthorized("API key is empty".to_string()));
+pub const DEFAULT_API_KEY_PREFIX: &str = "cl_sk_";
+/// 含前缀的最小长度；Console 生成的 key 为 `cl_sk_` + 32 位 hex
+pub const DEFAULT_API_KEY_MIN_LENGTH: usize = 16;
+pub const API_KEY_MAX_LENGTH: usize = 128;
+
+/// API key 格式规则，在任何 DB 查询之前拒绝明显无效的 key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyPolicy {
+    /// 必需前缀 (不区分大小写)
+    pub prefix: String,
+    /// 含前缀的最小长度
+    pub min_len: usize,
+}
+
+impl Default for ApiKeyPolicy {
+    fn default() -> Self {
+        Self {
+            prefix: DEFAULT_API_KEY_PREFIX.to_string(),
+            min_len: DEFAULT_API_KEY_MIN_LENGTH,
+        }
     }
+}
 
-    let lower = trimmed.to_lowercase();
-    if !lower.starts_with("cl_sk_") {
-        return Err(CroLensError::unauthorized(
-            "API key must start with cl_sk_".to_string(),
-        ));
+fn is_api_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+impl ApiKeyPolicy {
+    /// 读取 API_KEY_PREFIX / API_KEY_MIN_LENGTH
+    pub fn from_env(env: &worker::Env) -> Self {
+        let var = |name: &str| env.var(name).ok().map(|v| v.to_string());
+        Self::parse(
+            var("API_KEY_PREFIX").as_deref(),
+            var("API_KEY_MIN_LENGTH").as_deref(),
+        )
     }
 
-    let suffix = &trimmed["cl_sk_".len()..];
-    if suffix.is_empty() {
-        return Err(CroLensError::unauthorized(
-            "API key suffix is empty".to_string(),
-        ));
+    /// 前缀为空或含非法字符时回退到默认值；最小长度必须长于前缀且不超过最大长度
+    pub fn parse(prefix: Option<&str>, min_len: Option<&str>) -> Self {
+        let defaults = Self::default();
+        let prefix = prefix
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| {
+                !v.is_empty() && v.len() < API_KEY_MAX_LENGTH && v.chars().all(is_api_key_char)
+            })
+            .unwrap_or(defaults.prefix);
+        let min_len = min_len
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .filter(|len| *len > prefix.len() && *len <= API_KEY_MAX_LENGTH)
+            .unwrap_or_else(|| defaults.min_len.max(prefix.len() + 1));
+        Self { prefix, min_len }
     }
 
-    let ok = trimmed
-        .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
-    if !ok {
-        return Err(CroLensError::unauthorized(
-            "API key contains invalid characters".to_string(),
-        ));
+    pub fn validate(&self, api_key: &str) -> Result<()> {
+        let trimmed = api_key.trim();
+        if trimmed.is_empty() {
+            return Err(CroLensError::unauthorized("API key is empty".to_string()));
+        }
+
+        if trimmed.len() > API_KEY_MAX_LENGTH {
+            return Err(CroLensError::unauthorized(format!(
+                "API key is too long (maximum {API_KEY_MAX_LENGTH} characters)"
+            )));
+        }
+
+        // 先校验字符集，保证下面按字节切片安全
+        if !trimmed.chars().all(is_api_key_char) {
+            return Err(CroLensError::unauthorized(
+                "API key contains invalid characters".to_string(),
+            ));
+        }
+
+        if !trimmed.to_lowercase().starts_with(&self.prefix) {
+            return Err(CroLensError::unauthorized(format!(
+                "API key must start with {}",
+                self.prefix
+            )));
+        }
+
+        if trimmed.len() <= self.prefix.len() {
+            return Err(CroLensError::unauthorized(
+                "API key suffix is empty".to_string(),
+            ));
+        }
+
+        if trimmed.len() < self.min_len {
+            return Err(CroLensError::unauthorized(format!(
+                "API key is too short (minimum {} characters)",
+                self.min_len
+            )));
+        }
+
+        Ok(())
     }
+}
 
-    Ok(())
+pub fn validate_api_key_format(api_key: &str, policy: &ApiKeyPolicy) -> Result<()> {
+    policy.validate(api_key)
 }
 
 pub async fn ensure_api_key(
     db: &D1Database,
+    policy: &ApiKeyPolicy,
     api_key: &str,
     owner_address: Option<&str>,
 ) -> Result<ApiKeyRecord> {
     let store = D1ApiKeyStore::new(db);
-    ensure_api_key_with_store(&store, api_key, owner_address).await
+    ensure_api_key_with_store(&store, policy, api_key, owner_address).await
 }
 
 pub async fn ensure_api_key_with_store<S: ApiKeyStore>(
     store: &S,
+    policy: &ApiKeyPolicy,
     api_key: &str,
     owner_address: Option<&str>,
 ) -> Result<ApiKeyRecord> {
     let trimmed = api_key.trim();
-    validate_api_key_format(trimmed)?;
+    validate_api_key_format(trimmed, policy)?;
 
     if let Some(record) = store.fetch_api_key(trimmed).await? {
         if !record.is_active {
@@ -1,3 +1,5 @@
+use alloy_primitives::{Address, U256};
+use serde::Serialize;
 use serde_json::Value;
 use worker::d1::D1Type;
 use worker::D1Database;
@@ -8,6 +10,112 @@ use crate::gateway::store::ApiKeyStore;
 use crate::gateway::D1ApiKeyStore;
 use crate::infra;
 
+/// 每次 tools/call 扣除的 credit 数
+pub const TOOL_CALL_CREDIT_COST: i64 = 1;
+
+/// 工具调用的 credit 成本 (目前所有工具统一计费)
+pub fn tool_credit_cost(_tool: &str) -> i64 {
+    TOOL_CALL_CREDIT_COST
+}
+
+/// 按哈希计费的批量工具
+const PER_ITEM_TOOLS: &[(&str, &str)] = &[("decode_transactions", "tx_hashes")];
+
+/// 调用前可能扣除的最大 credit 数 (用于余额预检和 dry-run 报价)
+pub fn max_tool_credit_cost(tool: &str, arguments: &Value) -> i64 {
+    let base = tool_credit_cost(tool);
+    let Some((_, field)) = PER_ITEM_TOOLS.iter().find(|(name, _)| *name == tool) else {
+        return base;
+    };
+    let items = arguments
+        .get(*field)
+        .and_then(|v| v.as_array())
+        .map(|v| v.len())
+        .unwrap_or(0);
+    base.saturating_mul(i64::try_from(items).unwrap_or(i64::MAX))
+        .max(base)
+}
+
+/// 调用完成后的实际成本：批量工具按成功项计费 (结果中的 `credits_charged`)，至少一次调用的成本
+pub fn result_credit_cost(tool: &str, arguments: &Value, result: &Value) -> i64 {
+    let base = tool_credit_cost(tool);
+    if !PER_ITEM_TOOLS.iter().any(|(name, _)| *name == tool) {
+        return base;
+    }
+    let charged = result
+        .get("credits_charged")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(1);
+    base.saturating_mul(charged)
+        .clamp(base, max_tool_credit_cost(tool, arguments))
+}
+
+/// 在已扣除的一次调用费用之外原子补扣 `extra` 个 credit；余额不足时不扣除，返回实际补扣数 (0 或 extra)
+pub async fn deduct_extra_credits_with_store<S: ApiKeyStore>(
+    store: &S,
+    api_key: &str,
+    extra: i64,
+) -> Result<i64> {
+    if extra <= 0 {
+        return Ok(0);
+    }
+    let charged = store
+        .deduct_credits_if_possible(api_key.trim(), extra)
+        .await?
+        .map_or(0, |_| extra);
+    Ok(charged)
+}
+
+/// 补扣失败或不足时把批量工具结果中的 `credits_charged` 改为实际扣除的数量
+pub fn reconcile_credits_charged(tool: &str, result: &mut Value, extra_charged: i64) {
+    if !PER_ITEM_TOOLS.iter().any(|(name, _)| *name == tool) {
+        return;
+    }
+    if let Some(obj) = result.as_object_mut() {
+        if obj.contains_key("credits_charged") {
+            let charged = tool_credit_cost(tool).saturating_add(extra_charged);
+            obj.insert("credits_charged".to_string(), serde_json::json!(charged));
+        }
+    }
+}
+
+pub async fn deduct_extra_credits(db: &D1Database, api_key: &str, extra: i64) -> Result<i64> {
+    let store = D1ApiKeyStore::new(db);
+    deduct_extra_credits_with_store(&store, api_key, extra).await
+}
+
+/// 充值报价接口路径 (相对于 API 根地址)
+pub const X402_QUOTE_PATH: &str = "/x402/quote";
+
+/// Cronos 主网
+const X402_CHAIN_ID: u64 = 25;
+
+/// credit 不足时 JSON-RPC 错误 (-32002) 的 `data`，客户端据此直接发起充值
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PaymentRequiredData {
+    pub chain_id: u64,
+    pub payment_address: String,
+    pub amount_wei: String,
+    pub credits: i64,
+    pub quote_url: String,
+}
+
+impl PaymentRequiredData {
+    pub fn new(payment_address: Address, amount_wei: U256, credits: i64) -> Self {
+        Self {
+            chain_id: X402_CHAIN_ID,
+            payment_address: payment_address.to_string(),
+            amount_wei: amount_wei.to_string(),
+            credits,
+            quote_url: X402_QUOTE_PATH.to_string(),
+        }
+    }
+
+    pub fn into_error(self) -> CroLensError {
+        CroLensError::payment_required(serde_json::to_value(self).ok())
+    }
+}
+
 pub async fn deduct_credit_with_store<S: ApiKeyStore>(store: &S, api_key: &str) -> Result<i64> {
     let remaining = store.deduct_credit_if_possible(api_key.trim()).await?;
     remaining.ok_or_else(|| CroLensError::payment_required(None))
@@ -18,6 +126,18 @@ pub async fn deduct_credit(db: &D1Database, api_key: &str) -> Result<i64> {
     deduct_credit_with_store(&store, api_key).await
 }
 
+/// 退还被放弃 (超时) 的调用已扣除的 credit；返回退还后的余额
+pub async fn refund_credits_with_store<S: ApiKeyStore>(
+    store: &S,
+    api_key: &str,
+    amount: i64,
+) -> Result<Option<i64>> {
+    if amount <= 0 {
+        return Ok(None);
+    }
+    store.refund_credits(api_key.trim(), amount).await
+}
+
 pub async fn grant_credits(
     db: &D1Database,
     api_key: &str,
@@ -40,7 +160,7 @@ pub async fn grant_credits(
         )
         .bind_refs([&api_key_arg, &owner_arg, &tier_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
-    infra::db::run("grant_credits_upsert", statement.run()).await?;
+    infra::db::run("grant_credits_upsert", || statement.run()).await?;
 
     let credits_arg = D1Type::Integer(credits.clamp(0, i32::MAX as i64) as i32);
     let statement = db
@@ -53,7 +173,12 @@ pub async fn grant_credits(
         .bind_refs([&credits_arg, &tier_arg, &owner_arg, &api_key_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-    let result = infra::db::run("grant_credits_update", statement.all()).await?;
+    // 增减额度不是幂等操作，瞬时错误也不重试，避免重复记账
+    let result =
+        infra::db::run_with_policy("grant_credits_update", infra::db::RetryPolicy::NONE, || {
+            statement.all()
+        })
+        .await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
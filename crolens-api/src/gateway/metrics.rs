@@ -0,0 +1,302 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::error::{CroLensError, Result};
+use crate::infra;
+use crate::infra::kv_store::KvTextStore;
+
+/// 计数器在最后一次写入后保留 30 天
+pub const METRICS_COUNTER_TTL_SECS: u64 = 30 * 24 * 3600;
+/// 同一 isolate 两次写入计数器的最小间隔；其间的递增先在内存中累积
+/// (KV 同一 key 每秒最多写一次，逐次读-改-写会被限流并丢失计数)
+pub const METRICS_FLUSH_INTERVAL_MS: i64 = 10_000;
+
+/// 工具名不在工具列表中时统一计入该标签，避免任意输入产生新的 KV key
+pub const UNKNOWN_TOOL: &str = "unknown";
+
+/// 常见错误类型的分类，用于 `metrics:tool:{tool}:err:{kind}`
+pub fn error_kind(err: &CroLensError) -> &'static str {
+    match err {
+        CroLensError::InvalidRequest(_)
+        | CroLensError::InvalidParams(_)
+        | CroLensError::InvalidAddress(_) => "invalid_params",
+        CroLensError::MethodNotFound(_) | CroLensError::TokenNotFound(_) => "not_found",
+        CroLensError::RpcError(_) => "rpc",
+        CroLensError::ServiceUnavailable { .. } | CroLensError::NotConfigured(_) => "unavailable",
+        CroLensError::SimulationFailed(_) => "simulation",
+        CroLensError::Timeout { .. } => "timeout",
+        CroLensError::RateLimitExceeded { .. } => "rate_limited",
+        CroLensError::Unauthorized(_) => "unauthorized",
+        CroLensError::PaymentRequired { .. } => "payment_required",
+        CroLensError::DbError(_) => "db",
+        CroLensError::KvError(_) => "kv",
+    }
+}
+
+pub fn tool_counter_key(kv_prefix: &str, tool: &str, ok: bool) -> String {
+    let outcome = if ok { "ok" } else { "err" };
+    infra::kv_key(kv_prefix, &format!("metrics:tool:{tool}:{outcome}"))
+}
+
+pub fn tool_error_kind_key(kv_prefix: &str, tool: &str, err: &CroLensError) -> String {
+    infra::kv_key(
+        kv_prefix,
+        &format!("metrics:tool:{tool}:err:{}", error_kind(err)),
+    )
+}
+
+/// 一次工具调用需要递增的计数器：成功只计 ok；失败计 err 及其错误类型
+pub fn outcome_keys(kv_prefix: &str, tool: &str, error: Option<&CroLensError>) -> Vec<String> {
+    match error {
+        None => vec![tool_counter_key(kv_prefix, tool, true)],
+        Some(err) => vec![
+            tool_counter_key(kv_prefix, tool, false),
+            tool_error_kind_key(kv_prefix, tool, err),
+        ],
+    }
+}
+
+/// 延迟直方图的桶上界 (毫秒，不含) 与名称；超过最后一个上界计入 LATENCY_BUCKET_OVERFLOW
+pub const LATENCY_BUCKETS: &[(i64, &str)] = &[
+    (100, "lt_100ms"),
+    (300, "lt_300ms"),
+    (1_000, "lt_1s"),
+    (3_000, "lt_3s"),
+];
+pub const LATENCY_BUCKET_OVERFLOW: &str = "gte_3s";
+
+/// 延迟所属的直方图桶
+pub fn latency_bucket(latency_ms: i64) -> &'static str {
+    LATENCY_BUCKETS
+        .iter()
+        .find(|(upper, _)| latency_ms < *upper)
+        .map(|(_, name)| *name)
+        .unwrap_or(LATENCY_BUCKET_OVERFLOW)
+}
+
+/// 所有桶名称，按延迟从低到高
+pub fn latency_bucket_names() -> impl Iterator<Item = &'static str> {
+    LATENCY_BUCKETS
+        .iter()
+        .map(|(_, name)| *name)
+        .chain(std::iter::once(LATENCY_BUCKET_OVERFLOW))
+}
+
+pub fn latency_bucket_key(kv_prefix: &str, tool: &str, bucket: &str) -> String {
+    infra::kv_key(kv_prefix, &format!("metrics:latency:{tool}:{bucket}"))
+}
+
+/// 读-改-写累加 `delta` (KV 无原子操作，并发时可能少计，监控用途可以接受)
+pub async fn add_to_counter<S: KvTextStore + ?Sized>(
+    store: &S,
+    key: &str,
+    delta: u64,
+) -> Result<u64> {
+    let current = store
+        .get_text(key)
+        .await?
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(0);
+    let next = current.saturating_add(delta);
+    store
+        .put_text_with_ttl(key, next.to_string(), METRICS_COUNTER_TTL_SECS)
+        .await?;
+    Ok(next)
+}
+
+/// 本 isolate 内尚未写入 KV 的计数增量
+#[derive(Debug, Default)]
+pub struct CounterBatch {
+    deltas: HashMap<String, u64>,
+    last_flush_ms: Option<i64>,
+}
+
+impl CounterBatch {
+    /// 累加一次调用的计数器；距上次写入满 METRICS_FLUSH_INTERVAL_MS (或从未写入) 时取出全部增量 (按 key 排序)
+    pub fn record(&mut self, keys: &[String], now_ms: i64) -> Option<Vec<(String, u64)>> {
+        for key in keys {
+            let delta = self.deltas.entry(key.clone()).or_insert(0);
+            *delta = delta.saturating_add(1);
+        }
+        let due = self
+            .last_flush_ms
+            .is_none_or(|last| now_ms.saturating_sub(last) >= METRICS_FLUSH_INTERVAL_MS);
+        if !due || self.deltas.is_empty() {
+            return None;
+        }
+        self.last_flush_ms = Some(now_ms);
+        let mut deltas: Vec<(String, u64)> = self.deltas.drain().collect();
+        deltas.sort();
+        Some(deltas)
+    }
+}
+
+thread_local! {
+    static PENDING_COUNTERS: RefCell<CounterBatch> = RefCell::new(CounterBatch::default());
+}
+
+/// 在本 isolate 累积计数；返回到期需要写入 KV 的增量 (交给 `flush_counters`)
+pub fn record_counters(keys: &[String], now_ms: i64) -> Option<Vec<(String, u64)>> {
+    PENDING_COUNTERS.with(|batch| batch.borrow_mut().record(keys, now_ms))
+}
+
+/// 把累积的增量写入 KV；写入失败的增量直接丢弃 (监控用途可以接受)
+pub async fn flush_counters<S: KvTextStore + ?Sized>(
+    store: &S,
+    deltas: &[(String, u64)],
+) -> Result<()> {
+    for (key, delta) in deltas {
+        add_to_counter(store, key, *delta).await?;
+    }
+    Ok(())
+}
+
+async fn read_counter<S: KvTextStore + ?Sized>(store: &S, key: &str) -> Result<u64> {
+    Ok(store
+        .get_text(key)
+        .await?
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(0))
+}
+
+/// 单个工具的成功/失败计数与延迟直方图 (供 `/metrics` 使用)
+pub async fn read_tool_metrics<S: KvTextStore + ?Sized>(
+    store: &S,
+    kv_prefix: &str,
+    tool: &str,
+) -> Result<Value> {
+    let ok = read_counter(store, &tool_counter_key(kv_prefix, tool, true)).await?;
+    let err = read_counter(store, &tool_counter_key(kv_prefix, tool, false)).await?;
+    let mut latency = serde_json::Map::new();
+    for bucket in latency_bucket_names() {
+        let count = read_counter(store, &latency_bucket_key(kv_prefix, tool, bucket)).await?;
+        latency.insert(bucket.to_string(), Value::from(count));
+    }
+    Ok(serde_json::json!({
+        "tool": tool,
+        "ok": ok,
+        "err": err,
+        "latency_ms": latency,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_keys_follow_metrics_layout() {
+        assert_eq!(
+            tool_counter_key("", "get_token_price", true),
+            "metrics:tool:get_token_price:ok"
+        );
+        assert_eq!(
+            tool_counter_key("prod", "get_token_price", false),
+            "prod:metrics:tool:get_token_price:err"
+        );
+        let err = CroLensError::RpcError("boom".to_string());
+        assert_eq!(
+            tool_error_kind_key("", "get_block_info", &err),
+            "metrics:tool:get_block_info:err:rpc"
+        );
+    }
+
+    #[test]
+    fn outcome_keys_include_error_kind_only_on_failure() {
+        assert_eq!(
+            outcome_keys("", "get_gas_price", None),
+            vec!["metrics:tool:get_gas_price:ok".to_string()]
+        );
+        let err = CroLensError::Timeout { timeout_ms: 30_000 };
+        assert_eq!(
+            outcome_keys("", "get_gas_price", Some(&err)),
+            vec![
+                "metrics:tool:get_gas_price:err".to_string(),
+                "metrics:tool:get_gas_price:err:timeout".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn latency_maps_to_bucket_by_upper_bound() {
+        assert_eq!(latency_bucket(0), "lt_100ms");
+        assert_eq!(latency_bucket(99), "lt_100ms");
+        assert_eq!(latency_bucket(100), "lt_300ms");
+        assert_eq!(latency_bucket(299), "lt_300ms");
+        assert_eq!(latency_bucket(300), "lt_1s");
+        assert_eq!(latency_bucket(999), "lt_1s");
+        assert_eq!(latency_bucket(1_000), "lt_3s");
+        assert_eq!(latency_bucket(2_999), "lt_3s");
+        assert_eq!(latency_bucket(3_000), "gte_3s");
+        assert_eq!(latency_bucket(120_000), "gte_3s");
+        // 时钟回拨导致的负值计入最低桶
+        assert_eq!(latency_bucket(-5), "lt_100ms");
+    }
+
+    #[test]
+    fn latency_bucket_keys_follow_metrics_layout() {
+        assert_eq!(
+            latency_bucket_key("", "get_gas_price", "lt_1s"),
+            "metrics:latency:get_gas_price:lt_1s"
+        );
+        assert_eq!(
+            latency_bucket_names().collect::<Vec<_>>(),
+            vec!["lt_100ms", "lt_300ms", "lt_1s", "lt_3s", "gte_3s"]
+        );
+    }
+
+    #[test]
+    fn counter_batch_flushes_at_most_once_per_interval() {
+        let mut batch = CounterBatch::default();
+        let ok = vec!["metrics:tool:x:ok".to_string()];
+        let err = vec![
+            "metrics:tool:x:err".to_string(),
+            "metrics:tool:x:err:rpc".to_string(),
+        ];
+
+        // isolate 内第一次调用立即写入
+        assert_eq!(
+            batch.record(&ok, 1_000),
+            Some(vec![("metrics:tool:x:ok".to_string(), 1)])
+        );
+        assert_eq!(batch.record(&ok, 2_000), None);
+        assert_eq!(batch.record(&err, 5_000), None);
+        assert_eq!(batch.record(&ok, 10_999), None);
+        assert_eq!(
+            batch.record(&err, 11_000),
+            Some(vec![
+                ("metrics:tool:x:err".to_string(), 2),
+                ("metrics:tool:x:err:rpc".to_string(), 2),
+                ("metrics:tool:x:ok".to_string(), 2),
+            ])
+        );
+        assert_eq!(batch.record(&[], 30_000), None);
+    }
+
+    #[test]
+    fn error_kinds_group_common_variants() {
+        assert_eq!(
+            error_kind(&CroLensError::invalid_params("x".to_string())),
+            "invalid_params"
+        );
+        assert_eq!(
+            error_kind(&CroLensError::InvalidAddress("x".to_string())),
+            "invalid_params"
+        );
+        assert_eq!(
+            error_kind(&CroLensError::TokenNotFound("x".to_string())),
+            "not_found"
+        );
+        assert_eq!(
+            error_kind(&CroLensError::NotConfigured("RPC")),
+            "unavailable"
+        );
+        assert_eq!(
+            error_kind(&CroLensError::payment_required(None)),
+            "payment_required"
+        );
+        assert_eq!(error_kind(&CroLensError::DbError("x".to_string())), "db");
+    }
+}
@@ -1,5 +1,6 @@
 pub mod auth;
 pub mod billing;
+pub mod metrics;
 pub mod ratelimit;
 pub mod store;
 
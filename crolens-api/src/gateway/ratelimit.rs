@@ -1,35 +1,49 @@
-use async_trait::async_trait;
-use worker::kv::KvStore;
+use std::collections::{HashMap, HashSet};
 
-use crate::error::{CroLensError, Result};
+use crate::error::Result;
+/// 限流与指标的存储接口定义在 infra，对外从这里导出
+pub use crate::infra::kv_store::KvTextStore;
 
-#[async_trait(?Send)]
-pub trait RateLimitStore {
-    async fn get_text(&self, key: &str) -> Result<Option<String>>;
-    async fn put_text_with_ttl(&self, key: &str, value: String, ttl_secs: u64) -> Result<()>;
+/// 不受限流约束的可信调用方 (RATE_LIMIT_BYPASS_KEYS / RATE_LIMIT_BYPASS_IPS，逗号分隔)；
+/// 只跳过限流，计费与 credit 扣减不受影响
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitBypass {
+    keys: HashSet<String>,
+    ips: HashSet<String>,
 }
 
-#[async_trait(?Send)]
-impl RateLimitStore for KvStore {
-    async fn get_text(&self, key: &str) -> Result<Option<String>> {
-        self.get(key)
-            .text()
-            .await
-            .map_err(|err| CroLensError::KvError(err.to_string()))
+impl RateLimitBypass {
+    pub fn from_env(env: &worker::Env) -> Self {
+        let var = |name: &str| env.var(name).ok().map(|v| v.to_string());
+        Self::parse(
+            var("RATE_LIMIT_BYPASS_KEYS").as_deref(),
+            var("RATE_LIMIT_BYPASS_IPS").as_deref(),
+        )
     }
 
-    async fn put_text_with_ttl(&self, key: &str, value: String, ttl_secs: u64) -> Result<()> {
-        self.put(key, value)
-            .map_err(|err| CroLensError::KvError(err.to_string()))?
-            .expiration_ttl(ttl_secs)
-            .execute()
-            .await
-            .map_err(|err| CroLensError::KvError(err.to_string()))?;
-        Ok(())
+    pub fn parse(keys: Option<&str>, ips: Option<&str>) -> Self {
+        let list = |value: Option<&str>| -> HashSet<String> {
+            value
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        Self {
+            keys: list(keys),
+            ips: list(ips),
+        }
+    }
+
+    pub fn allows(&self, api_key: Option<&str>, client_ip: &str) -> bool {
+        api_key.is_some_and(|key| self.keys.contains(key.trim()))
+            || self.ips.contains(client_ip.trim())
     }
 }
 
-pub async fn check_rate_limit<S: RateLimitStore>(
+pub async fn check_rate_limit<S: KvTextStore>(
     kv: &S,
     key: &str,
     limit: u32,
@@ -55,3 +69,133 @@ pub async fn check_rate_limit<S: RateLimitStore>(
 
     Ok(true)
 }
+
+/// 未配置 RATE_LIMIT_TOOL_LIMITS 时的单工具子限额 (每个窗口)
+const DEFAULT_TOOL_LIMITS: &[(&str, u32)] = &[("simulate_transaction", 30)];
+
+/// 解析单工具子限额 JSON (如 `{"simulate_transaction": 30}`)；未设置或无效时使用默认表，`{}` 关闭
+pub fn parse_tool_limits(value: Option<&str>) -> HashMap<String, u32> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .and_then(|v| serde_json::from_str::<HashMap<String, u32>>(v).ok())
+        .unwrap_or_else(|| {
+            DEFAULT_TOOL_LIMITS
+                .iter()
+                .map(|(tool, limit)| (tool.to_string(), *limit))
+                .collect()
+        })
+}
+
+/// 同时检查多个计数器 (全局 + 单工具)：任一达到上限则拒绝且都不计数，否则全部加一
+pub async fn check_combined_rate_limit<S: KvTextStore>(
+    kv: &S,
+    limits: &[(&str, u32)],
+    window_secs: u64,
+) -> Result<bool> {
+    check_combined_rate_limit_with_bypass(kv, limits, window_secs, false).await
+}
+
+/// 同 `check_combined_rate_limit`；`bypass` 为 true 时直接放行且不计数
+pub async fn check_combined_rate_limit_with_bypass<S: KvTextStore>(
+    kv: &S,
+    limits: &[(&str, u32)],
+    window_secs: u64,
+    bypass: bool,
+) -> Result<bool> {
+    if bypass || window_secs == 0 {
+        return Ok(true);
+    }
+
+    let mut counts = Vec::with_capacity(limits.len());
+    for (key, limit) in limits {
+        if *limit == 0 {
+            continue;
+        }
+        let count = kv
+            .get_text(key)
+            .await?
+            .as_deref()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(0);
+        if count >= *limit {
+            return Ok(false);
+        }
+        counts.push((*key, count));
+    }
+
+    for (key, count) in counts {
+        kv.put_text_with_ttl(key, (count + 1).to_string(), window_secs)
+            .await?;
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures_util::FutureExt;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        values: RefCell<HashMap<String, String>>,
+    }
+
+    #[async_trait(?Send)]
+    impl KvTextStore for MemoryStore {
+        async fn get_text(&self, key: &str) -> Result<Option<String>> {
+            Ok(self.values.borrow().get(key).cloned())
+        }
+
+        async fn put_text_with_ttl(&self, key: &str, value: String, _ttl_secs: u64) -> Result<()> {
+            self.values.borrow_mut().insert(key.to_string(), value);
+            Ok(())
+        }
+    }
+
+    fn check(store: &MemoryStore, bypass: bool) -> bool {
+        check_combined_rate_limit_with_bypass(store, &[("rl:tool:k", 2)], 60, bypass)
+            .now_or_never()
+            .expect("memory store is synchronous")
+            .expect("memory store never fails")
+    }
+
+    #[test]
+    fn bypass_lists_match_trimmed_keys_and_ips() {
+        let bypass = RateLimitBypass::parse(Some(" ops-key , monitor "), Some("10.0.0.1,"));
+        assert!(bypass.allows(Some("ops-key"), "1.2.3.4"));
+        assert!(bypass.allows(Some("monitor"), "1.2.3.4"));
+        assert!(bypass.allows(None, "10.0.0.1"));
+        assert!(bypass.allows(Some("regular"), "10.0.0.1"));
+        assert!(!bypass.allows(Some("regular"), "1.2.3.4"));
+        assert!(!bypass.allows(None, "1.2.3.4"));
+
+        let empty = RateLimitBypass::parse(None, Some(""));
+        assert!(!empty.allows(Some(""), ""));
+    }
+
+    #[test]
+    fn bypassed_key_skips_the_limiter() {
+        let store = MemoryStore::default();
+        for _ in 0..5 {
+            assert!(check(&store, true));
+        }
+        // 放行时不计数
+        assert!(store.values.borrow().is_empty());
+    }
+
+    #[test]
+    fn normal_key_is_still_limited() {
+        let store = MemoryStore::default();
+        assert!(check(&store, false));
+        assert!(check(&store, false));
+        assert!(!check(&store, false));
+        assert_eq!(
+            store.values.borrow().get("rl:tool:k").map(String::as_str),
+            Some("2")
+        );
+    }
+}
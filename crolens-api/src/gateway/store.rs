@@ -23,6 +23,12 @@ pub trait ApiKeyStore {
     async fn load_free_daily_limit(&self) -> Result<i64>;
 
     async fn deduct_credit_if_possible(&self, api_key: &str) -> Result<Option<i64>>;
+
+    /// 一条语句原子扣除 `amount` 个 credit；余额不足时不扣除并返回 None
+    async fn deduct_credits_if_possible(&self, api_key: &str, amount: i64) -> Result<Option<i64>>;
+
+    /// 退还 `amount` 个已扣除的 credit (被放弃的调用)；返回退还后的余额，key 不存在时为 None
+    async fn refund_credits(&self, api_key: &str, amount: i64) -> Result<Option<i64>>;
 }
 
 pub struct D1ApiKeyStore<'a> {
@@ -45,7 +51,7 @@ impl<'a> ApiKeyStore for D1ApiKeyStore<'a> {
             .bind_refs([&api_key_arg])
             .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-        let result = infra::db::run("fetch_api_key", statement.all()).await;
+        let result = infra::db::run("fetch_api_key", || statement.all()).await;
         let result = match result {
             Ok(v) => v,
             Err(CroLensError::DbError(msg))
@@ -56,7 +62,7 @@ impl<'a> ApiKeyStore for D1ApiKeyStore<'a> {
                     .prepare("SELECT api_key, tier, credits FROM api_keys WHERE api_key = ?1")
                     .bind_refs([&api_key_arg])
                     .map_err(|err| CroLensError::DbError(err.to_string()))?;
-                infra::db::run("fetch_api_key_legacy", statement.all()).await?
+                infra::db::run("fetch_api_key_legacy", || statement.all()).await?
             }
             Err(err) => return Err(err),
         };
@@ -122,7 +128,7 @@ impl<'a> ApiKeyStore for D1ApiKeyStore<'a> {
             .bind_refs([&api_key_arg, &owner_arg, &tier_arg, &credits_arg, &is_active_arg])
             .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-        infra::db::run("insert_api_key_if_missing", statement.run()).await?;
+        infra::db::run("insert_api_key_if_missing", || statement.run()).await?;
         Ok(())
     }
 
@@ -133,7 +139,7 @@ impl<'a> ApiKeyStore for D1ApiKeyStore<'a> {
             .prepare("SELECT value FROM system_config WHERE key = ?1 LIMIT 1")
             .bind_refs([&key_arg])
             .map_err(|err| CroLensError::DbError(err.to_string()))?;
-        let result = infra::db::run("load_free_daily_limit", statement.all()).await?;
+        let result = infra::db::run("load_free_daily_limit", || statement.all()).await?;
         let rows: Vec<Value> = result
             .results()
             .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -160,7 +166,13 @@ impl<'a> ApiKeyStore for D1ApiKeyStore<'a> {
             .bind_refs([&api_key_arg])
             .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-        let result = infra::db::run("deduct_credit_if_possible", statement.all()).await;
+        // 增减额度不是幂等操作，瞬时错误也不重试，避免重复记账
+        let result = infra::db::run_with_policy(
+            "deduct_credit_if_possible",
+            infra::db::RetryPolicy::NONE,
+            || statement.all(),
+        )
+        .await;
         let result = match result {
             Ok(v) => v,
             Err(CroLensError::DbError(msg))
@@ -176,7 +188,72 @@ impl<'a> ApiKeyStore for D1ApiKeyStore<'a> {
                     )
                     .bind_refs([&api_key_arg])
                     .map_err(|err| CroLensError::DbError(err.to_string()))?;
-                infra::db::run("deduct_credit_if_possible_legacy", statement.all()).await?
+                infra::db::run_with_policy(
+                    "deduct_credit_if_possible_legacy",
+                    infra::db::RetryPolicy::NONE,
+                    || statement.all(),
+                )
+                .await?
+            }
+            Err(err) => return Err(err),
+        };
+
+        let rows: Vec<Value> = result
+            .results()
+            .map_err(|err| CroLensError::DbError(err.to_string()))?;
+        let Some(row) = rows.first() else {
+            return Ok(None);
+        };
+
+        let remaining = row
+            .get("credits")
+            .and_then(|v| v.as_i64())
+            .ok_or_else(|| CroLensError::DbError("api_keys.credits missing".to_string()))?;
+
+        Ok(Some(remaining))
+    }
+
+    async fn deduct_credits_if_possible(&self, api_key: &str, amount: i64) -> Result<Option<i64>> {
+        let api_key_arg = D1Type::Text(api_key);
+        let amount_arg = D1Type::Integer(amount.clamp(0, i32::MAX as i64) as i32);
+        let statement = self
+            .db
+            .prepare(
+                "UPDATE api_keys \
+                 SET credits = credits - ?2, daily_used = daily_used + ?2 \
+                 WHERE api_key = ?1 AND credits >= ?2 AND is_active = 1 \
+                 RETURNING credits",
+            )
+            .bind_refs([&api_key_arg, &amount_arg])
+            .map_err(|err| CroLensError::DbError(err.to_string()))?;
+
+        let result = infra::db::run_with_policy(
+            "deduct_credits_if_possible",
+            infra::db::RetryPolicy::NONE,
+            || statement.all(),
+        )
+        .await;
+        let result = match result {
+            Ok(v) => v,
+            Err(CroLensError::DbError(msg))
+                if msg.contains("no such column") && msg.contains("is_active") =>
+            {
+                let statement = self
+                    .db
+                    .prepare(
+                        "UPDATE api_keys \
+                         SET credits = credits - ?2, daily_used = daily_used + ?2 \
+                         WHERE api_key = ?1 AND credits >= ?2 \
+                         RETURNING credits",
+                    )
+                    .bind_refs([&api_key_arg, &amount_arg])
+                    .map_err(|err| CroLensError::DbError(err.to_string()))?;
+                infra::db::run_with_policy(
+                    "deduct_credits_if_possible_legacy",
+                    infra::db::RetryPolicy::NONE,
+                    || statement.all(),
+                )
+                .await?
             }
             Err(err) => return Err(err),
         };
@@ -195,4 +272,33 @@ impl<'a> ApiKeyStore for D1ApiKeyStore<'a> {
 
         Ok(Some(remaining))
     }
+
+    async fn refund_credits(&self, api_key: &str, amount: i64) -> Result<Option<i64>> {
+        let api_key_arg = D1Type::Text(api_key);
+        let amount_arg = D1Type::Integer(amount.clamp(0, i32::MAX as i64) as i32);
+        let statement = self
+            .db
+            .prepare(
+                "UPDATE api_keys \
+                 SET credits = credits + ?2, daily_used = MAX(daily_used - ?2, 0) \
+                 WHERE api_key = ?1 \
+                 RETURNING credits",
+            )
+            .bind_refs([&api_key_arg, &amount_arg])
+            .map_err(|err| CroLensError::DbError(err.to_string()))?;
+
+        // 与扣除相同，不重试以免重复退还
+        let result =
+            infra::db::run_with_policy("refund_credits", infra::db::RetryPolicy::NONE, || {
+                statement.all()
+            })
+            .await?;
+        let rows: Vec<Value> = result
+            .results()
+            .map_err(|err| CroLensError::DbError(err.to_string()))?;
+        Ok(rows
+            .first()
+            .and_then(|row| row.get("credits"))
+            .and_then(|v| v.as_i64()))
+    }
 }
@@ -10,6 +10,9 @@ use crate::types;
 
 const MAX_REQUEST_BODY_BYTES: usize = 10 * 1024;
 
+/// 响应体超过该大小且客户端接受 gzip 时压缩 (可用 GZIP_MIN_BYTES 覆盖)
+pub const GZIP_MIN_BYTES_DEFAULT: usize = 1024;
+
 pub fn add_security_headers(headers: &mut Headers) -> std::result::Result<(), worker::Error> {
     headers.set("X-Content-Type-Options", "nosniff")?;
     headers.set("X-Frame-Options", "DENY")?;
@@ -25,6 +28,213 @@ pub fn add_security_headers(headers: &mut Headers) -> std::result::Result<(), wo
     Ok(())
 }
 
+pub fn gzip_min_bytes(env: &Env) -> usize {
+    env.var("GZIP_MIN_BYTES")
+        .ok()
+        .and_then(|v| v.to_string().parse::<usize>().ok())
+        .unwrap_or(GZIP_MIN_BYTES_DEFAULT)
+}
+
+/// 整个 JSON-RPC 请求的超时 (可用 REQUEST_TIMEOUT_MS 覆盖)；略长于默认 REQUEST_BUDGET_MS，
+/// 让多批次工具先返回部分结果
+pub const REQUEST_TIMEOUT_MS_DEFAULT: u64 = 30_000;
+const REQUEST_TIMEOUT_MS_MIN: u64 = 1_000;
+const REQUEST_TIMEOUT_MS_MAX: u64 = 300_000;
+
+pub fn request_timeout_ms(env: &Env) -> u64 {
+    env.var("REQUEST_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.to_string().trim().parse::<u64>().ok())
+        .map(|v| v.clamp(REQUEST_TIMEOUT_MS_MIN, REQUEST_TIMEOUT_MS_MAX))
+        .unwrap_or(REQUEST_TIMEOUT_MS_DEFAULT)
+}
+
+/// 请求体 JSON 的默认最大嵌套深度 (可用 MAX_JSON_DEPTH 覆盖)
+pub const MAX_JSON_DEPTH_DEFAULT: usize = 32;
+const MAX_JSON_DEPTH_MIN: usize = 4;
+/// serde_json 自身的递归上限为 128
+const MAX_JSON_DEPTH_MAX: usize = 128;
+
+pub fn max_json_depth(env: &Env) -> usize {
+    env.var("MAX_JSON_DEPTH")
+        .ok()
+        .and_then(|v| v.to_string().trim().parse::<usize>().ok())
+        .map(|v| v.clamp(MAX_JSON_DEPTH_MIN, MAX_JSON_DEPTH_MAX))
+        .unwrap_or(MAX_JSON_DEPTH_DEFAULT)
+}
+
+/// JSON 的嵌套深度 (标量为 0，每层数组/对象加 1)；使用显式栈，不依赖递归
+pub fn json_depth(value: &serde_json::Value) -> usize {
+    let mut max_depth = 0;
+    let mut stack = vec![(value, 0usize)];
+    while let Some((value, depth)) = stack.pop() {
+        match value {
+            serde_json::Value::Array(items) => {
+                max_depth = max_depth.max(depth + 1);
+                stack.extend(items.iter().map(|child| (child, depth + 1)));
+            }
+            serde_json::Value::Object(map) => {
+                max_depth = max_depth.max(depth + 1);
+                stack.extend(map.values().map(|child| (child, depth + 1)));
+            }
+            _ => {}
+        }
+    }
+    max_depth
+}
+
+/// 嵌套过深的 JSON 以 invalid_request 拒绝
+pub fn check_json_depth(value: &serde_json::Value, max_depth: usize) -> Result<()> {
+    let depth = json_depth(value);
+    if depth > max_depth {
+        return Err(CroLensError::invalid_request(format!(
+            "JSON nesting depth {depth} exceeds limit of {max_depth}"
+        )));
+    }
+    Ok(())
+}
+
+/// 解析请求体并校验嵌套深度
+pub fn parse_json_body(bytes: &[u8], max_depth: usize) -> Result<serde_json::Value> {
+    let value: serde_json::Value = serde_json::from_slice(bytes)
+        .map_err(|err| CroLensError::invalid_request(format!("Invalid JSON-RPC payload: {err}")))?;
+    check_json_depth(&value, max_depth)?;
+    Ok(value)
+}
+
+/// 客户端的 Accept-Encoding 是否接受 gzip (`gzip;q=0` 视为拒绝)
+fn accepts_gzip(accept_encoding: &str) -> bool {
+    accept_encoding.split(',').any(|entry| {
+        let mut parts = entry.split(';').map(|p| p.trim());
+        let coding = parts.next().unwrap_or_default();
+        if !coding.eq_ignore_ascii_case("gzip") && coding != "*" {
+            return false;
+        }
+        !parts.any(|p| {
+            p.strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q <= 0.0)
+        })
+    })
+}
+
+/// 是否对响应体做 gzip 压缩；小响应压缩收益不抵开销
+pub fn should_gzip(accept_encoding: Option<&str>, body_len: usize, min_bytes: usize) -> bool {
+    body_len >= min_bytes && accept_encoding.is_some_and(accepts_gzip)
+}
+
+/// 未在 CORS_CONFIG 中配置时的预检响应头
+pub const CORS_DEFAULT_METHODS: &str = "GET,POST,OPTIONS";
+pub const CORS_DEFAULT_HEADERS: &str = "Content-Type,x-api-key,x-request-id";
+pub const CORS_DEFAULT_MAX_AGE_SECS: u64 = 86_400;
+
+/// CORS_CONFIG 中单个 origin 的配置；缺省字段沿用默认值，max_age=0 关闭预检缓存
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsOriginConfig {
+    #[serde(default)]
+    pub methods: Option<Vec<String>>,
+    #[serde(default)]
+    pub headers: Option<Vec<String>>,
+    #[serde(default)]
+    pub max_age: Option<u64>,
+}
+
+/// 某个 origin 最终使用的预检响应头
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsPolicy {
+    pub methods: String,
+    pub headers: String,
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        Self {
+            methods: CORS_DEFAULT_METHODS.to_string(),
+            headers: CORS_DEFAULT_HEADERS.to_string(),
+            max_age_secs: CORS_DEFAULT_MAX_AGE_SECS,
+        }
+    }
+}
+
+/// origin (小写) -> 配置；"*" 作为未单独配置的 origin 的默认项
+pub type CorsConfig = std::collections::HashMap<String, CorsOriginConfig>;
+
+thread_local! {
+    /// 按原始 CORS_CONFIG 字符串缓存解析结果，同一 isolate 内只解析 (及告警) 一次
+    static CORS_CONFIG_CACHE: std::cell::RefCell<Option<(String, std::rc::Rc<CorsConfig>)>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// 读取 CORS_CONFIG；未设置或不是合法 JSON 时为空 (即全部使用默认值)
+pub fn cors_config(env: &Env) -> std::rc::Rc<CorsConfig> {
+    let raw = env
+        .var("CORS_CONFIG")
+        .ok()
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+    CORS_CONFIG_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if let Some((cached_raw, config)) = cache.as_ref() {
+            if *cached_raw == raw {
+                return std::rc::Rc::clone(config);
+            }
+        }
+        let config = try_parse_cors_config(Some(&raw)).unwrap_or_else(|err| {
+            worker::console_warn!("[WARN] invalid CORS_CONFIG, using defaults: {}", err);
+            CorsConfig::new()
+        });
+        let config = std::rc::Rc::new(config);
+        *cache = Some((raw, std::rc::Rc::clone(&config)));
+        config
+    })
+}
+
+fn try_parse_cors_config(raw: Option<&str>) -> serde_json::Result<CorsConfig> {
+    let Some(raw) = raw.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(CorsConfig::new());
+    };
+    Ok(serde_json::from_str::<CorsConfig>(raw)?
+        .into_iter()
+        .map(|(origin, cfg)| (origin.trim().to_ascii_lowercase(), cfg))
+        .collect())
+}
+
+/// 回显了请求 origin，或预检头按 CORS_CONFIG 随 origin 变化时，共享缓存必须按 Origin 区分
+pub fn cors_varies_by_origin(config: &CorsConfig, echoed_origin: bool) -> bool {
+    echoed_origin || !config.is_empty()
+}
+
+fn join_list(items: Option<&Vec<String>>, default: &str) -> String {
+    let items: Vec<&str> = items
+        .into_iter()
+        .flatten()
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .collect();
+    if items.is_empty() {
+        default.to_string()
+    } else {
+        items.join(",")
+    }
+}
+
+/// 按 origin 精确匹配 (忽略大小写)，其次 "*"，都没有时为默认策略
+pub fn cors_policy(config: &CorsConfig, origin: Option<&str>) -> CorsPolicy {
+    let entry = origin
+        .and_then(|o| config.get(&o.trim().to_ascii_lowercase()))
+        .or_else(|| config.get("*"));
+    let Some(entry) = entry else {
+        return CorsPolicy::default();
+    };
+    CorsPolicy {
+        methods: join_list(entry.methods.as_ref(), CORS_DEFAULT_METHODS),
+        headers: join_list(entry.headers.as_ref(), CORS_DEFAULT_HEADERS),
+        max_age_secs: entry.max_age.unwrap_or(CORS_DEFAULT_MAX_AGE_SECS),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct VerifyPaymentRequest {
     tx_hash: String,
@@ -34,7 +244,7 @@ pub async fn handle_stats(env: &Env, trace_id: &str, start_ms: i64) -> worker::R
     let db = env.d1("DB")?;
 
     let statement = db.prepare("SELECT COUNT(*) AS cnt FROM protocols WHERE is_active = 1");
-    let result = infra::db::run("stats_count_protocols", statement.all())
+    let result = infra::db::run("stats_count_protocols", || statement.all())
         .await
         .map_err(|err| worker::Error::RustError(err.to_string()))?;
     let rows: Vec<serde_json::Value> = result.results()?;
@@ -50,6 +260,36 @@ pub async fn handle_stats(env: &Env, trace_id: &str, start_ms: i64) -> worker::R
     }))
 }
 
+/// `GET /metrics?tool={name}`：单个工具的成功/失败计数与延迟直方图
+pub async fn handle_metrics(
+    req: &Request,
+    env: &Env,
+    trace_id: &str,
+    start_ms: i64,
+) -> worker::Result<Response> {
+    let url = req.url()?;
+    let tool = url
+        .query_pairs()
+        .find(|(key, _)| key == "tool")
+        .map(|(_, value)| value.trim().to_string())
+        .unwrap_or_default();
+    // 只允许已注册的工具名，避免任意输入触发 KV 读取
+    if !crate::mcp::tools::tool_exists(&tool) && tool != gateway::metrics::UNKNOWN_TOOL {
+        return Response::from_json(&serde_json::json!({
+            "error": { "message": "Query parameter 'tool' must name a registered tool" },
+            "meta": meta(trace_id, start_ms),
+        }))
+        .map(|r| r.with_status(400));
+    }
+
+    let kv = env.kv("KV")?;
+    let mut metrics = gateway::metrics::read_tool_metrics(&kv, &infra::kv_prefix(env), &tool)
+        .await
+        .map_err(|err| worker::Error::RustError(err.to_string()))?;
+    metrics["meta"] = meta(trace_id, start_ms);
+    Response::from_json(&metrics)
+}
+
 pub async fn handle_x402_quote(
     req: &Request,
     env: &Env,
@@ -58,7 +298,7 @@ pub async fn handle_x402_quote(
 ) -> worker::Result<Response> {
     let kv = env.kv("KV")?;
     let ip = types::get_client_ip(req);
-    let key = format!("rl:quote:{ip}");
+    let key = infra::kv_key(&infra::kv_prefix(env), &format!("rl:quote:{ip}"));
     let allowed = gateway::ratelimit::check_rate_limit(&kv, &key, 30, 60)
         .await
         .map_err(|err| worker::Error::RustError(err.to_string()))?;
@@ -111,8 +351,9 @@ pub async fn handle_x402_status(
         .map(|r| r.with_status(400));
     }
 
+    let policy = gateway::auth::ApiKeyPolicy::from_env(env);
     let db = env.d1("DB")?;
-    let record = match gateway::ensure_api_key(&db, &api_key, None).await {
+    let record = match gateway::ensure_api_key(&db, &policy, &api_key, None).await {
         Ok(v) => v,
         Err(CroLensError::Unauthorized(msg)) => {
             return Response::from_json(&serde_json::json!({
@@ -140,7 +381,7 @@ pub async fn handle_x402_verify(
 ) -> worker::Result<Response> {
     let kv = env.kv("KV")?;
     let ip = types::get_client_ip(&req);
-    let key = format!("rl:verify:{ip}");
+    let key = infra::kv_key(&infra::kv_prefix(env), &format!("rl:verify:{ip}"));
     let allowed = gateway::ratelimit::check_rate_limit(&kv, &key, 10, 60)
         .await
         .map_err(|err| worker::Error::RustError(err.to_string()))?;
@@ -162,7 +403,10 @@ pub async fn handle_x402_verify(
         }))
         .map(|r| r.with_status(400));
     }
-    if let Err(CroLensError::Unauthorized(msg)) = gateway::auth::validate_api_key_format(&api_key) {
+    let policy = gateway::auth::ApiKeyPolicy::from_env(env);
+    if let Err(CroLensError::Unauthorized(msg)) =
+        gateway::auth::validate_api_key_format(&api_key, &policy)
+    {
         return Response::from_json(&serde_json::json!({
             "error": { "message": msg },
             "meta": meta(trace_id, start_ms),
@@ -203,8 +447,11 @@ pub async fn handle_x402_verify(
     };
     let amount_required = cfg.topup_amount_wei();
 
-    let rpc = infra::rpc::RpcClient::try_new(env, Some(kv.clone()))
-        .ok_or_else(|| worker::Error::RustError("Missing env var: BLOCKPI_RPC_URL".to_string()))?;
+    let rpc = infra::rpc::RpcClient::try_new(
+        env,
+        Some(infra::kv_stats::TrackedKv::untracked(kv.clone())),
+    )
+    .ok_or_else(|| worker::Error::RustError("Missing env var: BLOCKPI_RPC_URL".to_string()))?;
 
     let tx = rpc
         .eth_get_transaction_by_hash(tx_hash)
@@ -330,8 +577,19 @@ async fn insert_payment_once(
         .bind_refs([&tx_arg, &api_key_arg, &from_arg, &to_arg, &value_arg, &credits_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-    match infra::db::run("insert_payment_once", statement.run()).await {
-        Ok(_) => Ok(true),
+    // 非幂等写入不重试：已提交的 INSERT 若被重试会撞上 UNIQUE，被误判为重复付款而漏记额度
+    let result =
+        infra::db::run_with_policy("insert_payment_once", infra::db::RetryPolicy::NONE, || {
+            statement.run()
+        })
+        .await;
+    payment_insert_outcome(result.map(|_| ()))
+}
+
+/// INSERT 成功为新付款；tx_hash 唯一约束冲突说明该交易已入账
+fn payment_insert_outcome(result: Result<()>) -> Result<bool> {
+    match result {
+        Ok(()) => Ok(true),
         Err(CroLensError::DbError(msg)) => {
             if msg.contains("UNIQUE constraint failed") || msg.contains("SQLITE_CONSTRAINT") {
                 Ok(false)
@@ -344,10 +602,187 @@ async fn insert_payment_once(
 }
 
 fn meta(trace_id: &str, start_ms: i64) -> serde_json::Value {
-    let now = types::now_ms();
+    meta_at(trace_id, start_ms, types::now_ms())
+}
+
+fn meta_at(trace_id: &str, start_ms: i64, now: i64) -> serde_json::Value {
     serde_json::json!({
         "trace_id": trace_id,
         "timestamp": now,
         "latency_ms": now.saturating_sub(start_ms),
+        "schema_version": types::RESPONSE_SCHEMA_VERSION,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_cors_config(raw: Option<&str>) -> CorsConfig {
+        try_parse_cors_config(raw).unwrap_or_default()
+    }
+
+    const ONBOARDING_CORS: &str = r#"{
+        "https://app.example.com": { "methods": ["GET", "POST"], "max_age": 600 },
+        "https://New.Partner.io": { "headers": ["Content-Type", "x-api-key"], "max_age": 0 }
+    }"#;
+
+    #[test]
+    fn http_meta_includes_schema_version() {
+        let meta = meta_at("trace", 1_000, 1_250);
+        assert_eq!(meta["schema_version"], types::RESPONSE_SCHEMA_VERSION);
+        assert_eq!(meta["latency_ms"], 250);
+    }
+
+    #[test]
+    fn cors_policy_defaults_when_config_unset() {
+        let config = parse_cors_config(None);
+        assert!(config.is_empty());
+        assert_eq!(
+            cors_policy(&config, Some("https://app.example.com")),
+            CorsPolicy {
+                methods: "GET,POST,OPTIONS".to_string(),
+                headers: "Content-Type,x-api-key,x-request-id".to_string(),
+                max_age_secs: 86_400,
+            }
+        );
+        assert_eq!(cors_policy(&config, None), CorsPolicy::default());
+        assert!(parse_cors_config(Some("   ")).is_empty());
+    }
+
+    #[test]
+    fn cors_policy_resolves_per_origin_entries() {
+        let config = parse_cors_config(Some(ONBOARDING_CORS));
+
+        let app = cors_policy(&config, Some("https://app.example.com"));
+        assert_eq!(app.methods, "GET,POST");
+        assert_eq!(app.headers, CORS_DEFAULT_HEADERS);
+        assert_eq!(app.max_age_secs, 600);
+
+        // origin 匹配忽略大小写；max_age=0 关闭预检缓存
+        let partner = cors_policy(&config, Some("https://new.partner.io"));
+        assert_eq!(partner.methods, CORS_DEFAULT_METHODS);
+        assert_eq!(partner.headers, "Content-Type,x-api-key");
+        assert_eq!(partner.max_age_secs, 0);
+
+        assert_eq!(
+            cors_policy(&config, Some("https://other.example.com")),
+            CorsPolicy::default()
+        );
+    }
+
+    #[test]
+    fn cors_wildcard_entry_covers_unlisted_origins() {
+        let config = parse_cors_config(Some(
+            r#"{ "*": { "max_age": 60 }, "https://a.io": { "methods": [] } }"#,
+        ));
+        assert_eq!(cors_policy(&config, Some("https://b.io")).max_age_secs, 60);
+        assert_eq!(cors_policy(&config, None).max_age_secs, 60);
+        // 空列表沿用默认值；单独配置的 origin 不继承 "*"
+        let a = cors_policy(&config, Some("https://a.io"));
+        assert_eq!(a.methods, CORS_DEFAULT_METHODS);
+        assert_eq!(a.max_age_secs, CORS_DEFAULT_MAX_AGE_SECS);
+    }
+
+    #[test]
+    fn invalid_cors_config_falls_back_to_defaults() {
+        assert!(parse_cors_config(Some("not json")).is_empty());
+        assert!(parse_cors_config(Some(r#"{ "https://a.io": { "max_age": -1 } }"#)).is_empty());
+        assert!(parse_cors_config(Some(r#"{ "https://a.io": { "maxAge": 5 } }"#)).is_empty());
+    }
+
+    #[test]
+    fn cors_config_requires_vary_origin() {
+        assert!(!cors_varies_by_origin(&parse_cors_config(None), false));
+        assert!(cors_varies_by_origin(&parse_cors_config(None), true));
+        // CORS_ALLOW_ORIGIN=* 时不回显 origin，但预检头仍随 origin 变化
+        let config = parse_cors_config(Some(ONBOARDING_CORS));
+        assert!(cors_varies_by_origin(&config, false));
+    }
+
+    #[test]
+    fn duplicate_payment_insert_is_already_credited() {
+        assert!(payment_insert_outcome(Ok(())).expect("inserted"));
+        let duplicate = CroLensError::DbError(
+            "D1_ERROR: UNIQUE constraint failed: payments.tx_hash: SQLITE_CONSTRAINT".to_string(),
+        );
+        assert!(!payment_insert_outcome(Err(duplicate)).expect("duplicate"));
+        let transient = CroLensError::DbError("D1_ERROR: Network connection lost".to_string());
+        assert!(payment_insert_outcome(Err(transient)).is_err());
+    }
+
+    #[test]
+    fn compresses_large_body_when_gzip_accepted() {
+        assert!(should_gzip(Some("gzip, deflate, br"), 4096, 1024));
+        assert!(should_gzip(Some("br;q=1.0, GZIP;q=0.5"), 1024, 1024));
+        assert!(should_gzip(Some("*"), 2048, 1024));
+    }
+
+    #[test]
+    fn small_body_stays_uncompressed() {
+        assert!(!should_gzip(Some("gzip"), 1023, 1024));
+        assert!(!should_gzip(Some("gzip"), 0, GZIP_MIN_BYTES_DEFAULT));
+    }
+
+    #[test]
+    fn request_timeout_maps_to_504_error() {
+        let err = CroLensError::Timeout {
+            timeout_ms: REQUEST_TIMEOUT_MS_DEFAULT,
+        };
+        let (code, _, _) = err.to_json_rpc_error();
+        assert_eq!(code, -32504);
+    }
+
+    fn nested_arrays(depth: usize) -> String {
+        format!("{}{}", "[".repeat(depth), "]".repeat(depth))
+    }
+
+    #[test]
+    fn json_depth_counts_arrays_and_objects() {
+        assert_eq!(json_depth(&serde_json::json!(1)), 0);
+        assert_eq!(json_depth(&serde_json::json!({})), 1);
+        assert_eq!(
+            json_depth(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "params": { "arguments": { "tokens": ["CRO"] } }
+            })),
+            4
+        );
+    }
+
+    #[test]
+    fn payload_at_depth_limit_is_accepted() {
+        let body = nested_arrays(MAX_JSON_DEPTH_DEFAULT);
+        let value =
+            parse_json_body(body.as_bytes(), MAX_JSON_DEPTH_DEFAULT).expect("boundary depth");
+        assert_eq!(json_depth(&value), MAX_JSON_DEPTH_DEFAULT);
+    }
+
+    #[test]
+    fn payload_over_depth_limit_is_rejected() {
+        let body = nested_arrays(MAX_JSON_DEPTH_DEFAULT + 1);
+        let err = parse_json_body(body.as_bytes(), MAX_JSON_DEPTH_DEFAULT).unwrap_err();
+        assert!(matches!(err, CroLensError::InvalidRequest(_)));
+        assert!(err.to_string().contains("exceeds limit of 32"));
+
+        let params = format!("{}1{}", r#"{"a":"#.repeat(40), "}".repeat(40));
+        let body = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{params}}}"#);
+        assert!(matches!(
+            parse_json_body(body.as_bytes(), MAX_JSON_DEPTH_DEFAULT),
+            Err(CroLensError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_json_is_invalid_request() {
+        let err = parse_json_body(b"{not json", MAX_JSON_DEPTH_DEFAULT).unwrap_err();
+        assert!(matches!(err, CroLensError::InvalidRequest(_)));
+    }
+
+    #[test]
+    fn respects_missing_or_refused_gzip() {
+        assert!(!should_gzip(None, 4096, 1024));
+        assert!(!should_gzip(Some("br, deflate"), 4096, 1024));
+        assert!(!should_gzip(Some("gzip;q=0"), 4096, 1024));
+    }
+}
@@ -0,0 +1,175 @@
+use std::future::Future;
+
+use futures_util::future::{select, Either, FutureExt};
+use futures_util::pin_mut;
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use worker::Env;
+
+/// 单个 fan-out 同时进行的下游请求 (KV/RPC) 默认上限
+pub const MAX_CONCURRENT_SUBREQUESTS_DEFAULT: usize = 6;
+const MAX_CONCURRENT_SUBREQUESTS_MIN: usize = 1;
+const MAX_CONCURRENT_SUBREQUESTS_MAX: usize = 50;
+
+/// 读取 MAX_CONCURRENT_SUBREQUESTS；无效或超出范围时使用默认值
+pub fn max_concurrent_subrequests(env: &Env) -> usize {
+    parse_max_concurrent_subrequests(
+        env.var("MAX_CONCURRENT_SUBREQUESTS")
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    )
+}
+
+pub fn parse_max_concurrent_subrequests(value: Option<&str>) -> usize {
+    value
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|v| (MAX_CONCURRENT_SUBREQUESTS_MIN..=MAX_CONCURRENT_SUBREQUESTS_MAX).contains(v))
+        .unwrap_or(MAX_CONCURRENT_SUBREQUESTS_DEFAULT)
+}
+
+/// 与 `join_all` 相同但最多同时运行 `limit` 个 future；结果按输入顺序返回
+pub async fn join_all_bounded<I>(futures: I, limit: usize) -> Vec<<I::Item as Future>::Output>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    stream::iter(futures).buffered(limit.max(1)).collect().await
+}
+
+/// 与 `try_join_all` 相同但最多同时运行 `limit` 个 future；遇到第一个错误即返回
+pub async fn try_join_all_bounded<I, T, E>(futures: I, limit: usize) -> Result<Vec<T>, E>
+where
+    I: IntoIterator,
+    I::Item: Future<Output = Result<T, E>>,
+{
+    stream::iter(futures)
+        .buffered(limit.max(1))
+        .try_collect()
+        .await
+}
+
+/// `fut` 先完成时返回其结果，`timeout` 先完成时返回 None (`fut` 被丢弃)
+pub async fn race_timeout<F, T>(fut: F, timeout: T) -> Option<F::Output>
+where
+    F: Future,
+    T: Future<Output = ()>,
+{
+    let fut = fut.fuse();
+    let timeout = timeout.fuse();
+    pin_mut!(fut, timeout);
+    match select(fut, timeout).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(((), _)) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// 记录同时在运行的任务数及其峰值
+    #[derive(Default)]
+    struct Gauge {
+        active: Cell<usize>,
+        peak: Cell<usize>,
+    }
+
+    impl Gauge {
+        async fn track(&self, id: usize) -> usize {
+            self.active.set(self.active.get() + 1);
+            self.peak.set(self.peak.get().max(self.active.get()));
+            // 让出执行权，使其他任务有机会在本任务完成前启动
+            for _ in 0..3 {
+                tokio::task::yield_now().await;
+            }
+            self.active.set(self.active.get() - 1);
+            id
+        }
+    }
+
+    #[tokio::test]
+    async fn join_all_bounded_never_exceeds_cap() {
+        let gauge = Gauge::default();
+        let results = join_all_bounded((0..20).map(|id| gauge.track(id)), 4).await;
+
+        assert_eq!(results, (0..20).collect::<Vec<_>>());
+        assert_eq!(gauge.peak.get(), 4);
+        assert_eq!(gauge.active.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn join_all_bounded_treats_zero_limit_as_sequential() {
+        let gauge = Gauge::default();
+        let results = join_all_bounded((0..5).map(|id| gauge.track(id)), 0).await;
+
+        assert_eq!(results, vec![0, 1, 2, 3, 4]);
+        assert_eq!(gauge.peak.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn try_join_all_bounded_never_exceeds_cap_and_keeps_order() {
+        let gauge = Gauge::default();
+        let tasks = (0..12).map(|id| {
+            let gauge = &gauge;
+            async move { Ok::<_, String>(gauge.track(id).await * 10) }
+        });
+        let results = try_join_all_bounded(tasks, 3)
+            .await
+            .expect("all tasks succeed");
+
+        assert_eq!(results, (0..12).map(|id| id * 10).collect::<Vec<_>>());
+        assert_eq!(gauge.peak.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn try_join_all_bounded_returns_first_error() {
+        let tasks = (0..8).map(|id| async move {
+            if id == 5 {
+                Err(format!("task {id} failed"))
+            } else {
+                Ok(id)
+            }
+        });
+        let result = try_join_all_bounded(tasks, 2).await;
+        assert_eq!(result, Err("task 5 failed".to_string()));
+    }
+
+    #[test]
+    fn parse_max_concurrent_subrequests_falls_back_to_default() {
+        assert_eq!(
+            parse_max_concurrent_subrequests(None),
+            MAX_CONCURRENT_SUBREQUESTS_DEFAULT
+        );
+        assert_eq!(parse_max_concurrent_subrequests(Some(" 12 ")), 12);
+        assert_eq!(parse_max_concurrent_subrequests(Some("1")), 1);
+        assert_eq!(
+            parse_max_concurrent_subrequests(Some("0")),
+            MAX_CONCURRENT_SUBREQUESTS_DEFAULT
+        );
+        assert_eq!(
+            parse_max_concurrent_subrequests(Some("51")),
+            MAX_CONCURRENT_SUBREQUESTS_DEFAULT
+        );
+        assert_eq!(
+            parse_max_concurrent_subrequests(Some("many")),
+            MAX_CONCURRENT_SUBREQUESTS_DEFAULT
+        );
+    }
+
+    #[test]
+    fn race_timeout_returns_output_when_call_finishes_first() {
+        let out = race_timeout(async { 7 }, futures_util::future::pending::<()>())
+            .now_or_never()
+            .expect("ready");
+        assert_eq!(out, Some(7));
+    }
+
+    #[test]
+    fn race_timeout_drops_call_when_timeout_elapses() {
+        let out = race_timeout(futures_util::future::pending::<u32>(), async {})
+            .now_or_never()
+            .expect("ready");
+        assert_eq!(out, None);
+    }
+}
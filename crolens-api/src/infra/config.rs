@@ -2,16 +2,23 @@ use alloy_primitives::Address;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use worker::d1::D1Type;
-use worker::kv::KvStore;
 use worker::D1Database;
 
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::kv_stats::TrackedKv;
 use crate::types;
 
 const DEX_POOLS_CACHE_PREFIX: &str = "cache:dex_pools:";
 const LENDING_MARKETS_CACHE_PREFIX: &str = "cache:lending_markets:";
+const LENDING_PROTOCOLS_CACHE_KEY: &str = "cache:lending_protocols";
+const TOOL_OVERRIDES_CACHE_KEY: &str = "cache:tool_overrides";
+const PROTOCOL_CONTRACT_CACHE_PREFIX: &str = "cache:protocol_contract:";
 const CONFIG_CACHE_TTL_SECS: u64 = 600; // 10 分钟
+/// router/factory/masterchef 等协议合约地址基本不变，缓存 1 天
+const PROTOCOL_CONTRACT_CACHE_TTL_SECS: u64 = 24 * 3600;
+/// tool_overrides 查询失败 (如表不存在) 时缓存空列表的 TTL (KV 允许的最小值)，避免每次 tools/list 都重试 D1
+const TOOL_OVERRIDES_ERROR_TTL_SECS: u64 = 60;
 
 #[derive(Debug, Clone)]
 pub struct DexPool {
@@ -32,6 +39,14 @@ pub struct LendingMarket {
     pub collateral_factor: Option<String>,
 }
 
+/// tool_overrides 表中的一行：覆盖 tools/list 中工具的描述与 inputSchema (JSON 文本)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolOverride {
+    pub name: String,
+    pub description: Option<String>,
+    pub schema_json: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct DexPoolCache {
     pool_id: String,
@@ -66,7 +81,7 @@ pub async fn get_protocol_contract(
         .bind_refs([&protocol_arg, &contract_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-    let result = infra::db::run("get_protocol_contract", statement.all()).await?;
+    let result = infra::db::run("get_protocol_contract", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -83,16 +98,64 @@ pub async fn get_protocol_contract(
     types::parse_address(address)
 }
 
+fn protocol_contract_cache_key(protocol_id: &str, contract_type: &str) -> String {
+    format!("{PROTOCOL_CONTRACT_CACHE_PREFIX}{protocol_id}:{contract_type}")
+}
+
+/// 缓存命中时直接返回 (不调用 `load`)；未命中或缓存值不是合法地址时调用 `load`，
+/// 第二个返回值表示是否需要写回缓存
+async fn read_through_address<F, Fut>(cached: Option<&str>, load: F) -> Result<(Address, bool)>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<Address>>,
+{
+    if let Some(address) = cached.and_then(|v| types::parse_address(v).ok()) {
+        return Ok((address, false));
+    }
+    Ok((load().await?, true))
+}
+
+/// 从 KV 缓存获取协议合约地址 (按 protocol_id + contract_type)，未命中时查询 D1 并写回；
+/// 缺失的合约不缓存
+pub async fn get_protocol_contract_cached(
+    db: &D1Database,
+    kv: &TrackedKv,
+    kv_prefix: &str,
+    protocol_id: &str,
+    contract_type: &str,
+) -> Result<Address> {
+    let cache_key = infra::kv_key(
+        kv_prefix,
+        &protocol_contract_cache_key(protocol_id, contract_type),
+    );
+    let cached = kv.get_text(&cache_key).await.ok().flatten();
+    let (address, store) = read_through_address(cached.as_deref(), || {
+        get_protocol_contract(db, protocol_id, contract_type)
+    })
+    .await?;
+    if store {
+        let _ = kv
+            .put_text_with_ttl(
+                &cache_key,
+                address.to_string(),
+                PROTOCOL_CONTRACT_CACHE_TTL_SECS,
+            )
+            .await;
+    }
+    Ok(address)
+}
+
 /// 从 KV 缓存获取 DEX 池子列表
 pub async fn list_dex_pools_cached(
     db: &D1Database,
-    kv: &KvStore,
+    kv: &TrackedKv,
+    kv_prefix: &str,
     protocol_id: &str,
 ) -> Result<Vec<DexPool>> {
-    let cache_key = format!("{DEX_POOLS_CACHE_PREFIX}{protocol_id}");
+    let cache_key = infra::kv_key(kv_prefix, &format!("{DEX_POOLS_CACHE_PREFIX}{protocol_id}"));
 
     // 先尝试从 KV 缓存获取
-    if let Ok(Some(cached)) = kv.get(&cache_key).text().await {
+    if let Ok(Some(cached)) = kv.get_text(&cache_key).await {
         if let Ok(pools_cache) = serde_json::from_str::<Vec<DexPoolCache>>(&cached) {
             let mut pools = Vec::with_capacity(pools_cache.len());
             for p in pools_cache {
@@ -135,9 +198,9 @@ pub async fn list_dex_pools_cached(
         })
         .collect();
     if let Ok(json) = serde_json::to_string(&cache) {
-        if let Ok(put) = kv.put(&cache_key, json) {
-            let _ = put.expiration_ttl(CONFIG_CACHE_TTL_SECS).execute().await;
-        }
+        let _ = kv
+            .put_text_with_ttl(&cache_key, json, CONFIG_CACHE_TTL_SECS)
+            .await;
     }
 
     Ok(pools)
@@ -152,7 +215,7 @@ pub async fn list_dex_pools(db: &D1Database, protocol_id: &str) -> Result<Vec<De
         )
         .bind_refs([&protocol_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
-    let result = infra::db::run("list_dex_pools", statement.all()).await?;
+    let result = infra::db::run("list_dex_pools", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -232,7 +295,7 @@ pub async fn get_token_address_by_symbol(db: &D1Database, symbol: &str) -> Resul
         .bind_refs([&symbol_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-    let result = infra::db::run("get_token_address_by_symbol", statement.all()).await?;
+    let result = infra::db::run("get_token_address_by_symbol", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -269,7 +332,7 @@ async fn find_pool_for_pair(
         .bind_refs([&protocol_arg, &token_a_arg, &token_b_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-    let result = infra::db::run("find_pool_for_pair", statement.all()).await?;
+    let result = infra::db::run("find_pool_for_pair", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -317,16 +380,86 @@ async fn find_pool_for_pair(
     }))
 }
 
+/// 已启用的 DEX 协议 (protocols.category = 'dex')
+pub async fn list_dex_protocols(db: &D1Database) -> Result<Vec<String>> {
+    let statement = db.prepare(
+        "SELECT protocol_id FROM protocols \
+         WHERE category = 'dex' AND is_active = 1 \
+         ORDER BY protocol_id",
+    );
+
+    let result = infra::db::run("list_dex_protocols", || statement.all()).await?;
+    let rows: Vec<Value> = result
+        .results()
+        .map_err(|err| CroLensError::DbError(err.to_string()))?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| row.get("protocol_id").and_then(|v| v.as_str()))
+        .map(|v| v.to_string())
+        .collect())
+}
+
+/// 已启用的 Compound 分叉借贷协议；新增借贷协议只需配置 protocols + lending_markets
+pub async fn list_lending_protocols(db: &D1Database) -> Result<Vec<String>> {
+    let statement = db.prepare(
+        "SELECT protocol_id FROM protocols \
+         WHERE category = 'lending' AND adapter_type = 'compound_v2_lending' AND is_active = 1 \
+         ORDER BY protocol_id",
+    );
+
+    let result = infra::db::run("list_lending_protocols", || statement.all()).await?;
+    let rows: Vec<Value> = result
+        .results()
+        .map_err(|err| CroLensError::DbError(err.to_string()))?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| row.get("protocol_id").and_then(|v| v.as_str()))
+        .map(|v| v.to_string())
+        .collect())
+}
+
+/// 从 KV 缓存获取已启用的借贷协议列表
+pub async fn list_lending_protocols_cached(
+    db: &D1Database,
+    kv: &TrackedKv,
+    kv_prefix: &str,
+) -> Result<Vec<String>> {
+    let cache_key = infra::kv_key(kv_prefix, LENDING_PROTOCOLS_CACHE_KEY);
+
+    if let Ok(Some(cached)) = kv.get_text(&cache_key).await {
+        if let Ok(protocols) = serde_json::from_str::<Vec<String>>(&cached) {
+            if !protocols.is_empty() {
+                return Ok(protocols);
+            }
+        }
+    }
+
+    let protocols = list_lending_protocols(db).await?;
+    if let Ok(json) = serde_json::to_string(&protocols) {
+        let _ = kv
+            .put_text_with_ttl(&cache_key, json, CONFIG_CACHE_TTL_SECS)
+            .await;
+    }
+
+    Ok(protocols)
+}
+
 /// 从 KV 缓存获取 Lending markets 列表
 pub async fn list_lending_markets_cached(
     db: &D1Database,
-    kv: &KvStore,
+    kv: &TrackedKv,
+    kv_prefix: &str,
     protocol_id: &str,
 ) -> Result<Vec<LendingMarket>> {
-    let cache_key = format!("{LENDING_MARKETS_CACHE_PREFIX}{protocol_id}");
+    let cache_key = infra::kv_key(
+        kv_prefix,
+        &format!("{LENDING_MARKETS_CACHE_PREFIX}{protocol_id}"),
+    );
 
     // 先尝试从 KV 缓存获取
-    if let Ok(Some(cached)) = kv.get(&cache_key).text().await {
+    if let Ok(Some(cached)) = kv.get_text(&cache_key).await {
         if let Ok(markets_cache) = serde_json::from_str::<Vec<LendingMarketCache>>(&cached) {
             let mut markets = Vec::with_capacity(markets_cache.len());
             for m in markets_cache {
@@ -362,9 +495,9 @@ pub async fn list_lending_markets_cached(
         })
         .collect();
     if let Ok(json) = serde_json::to_string(&cache) {
-        if let Ok(put) = kv.put(&cache_key, json) {
-            let _ = put.expiration_ttl(CONFIG_CACHE_TTL_SECS).execute().await;
-        }
+        let _ = kv
+            .put_text_with_ttl(&cache_key, json, CONFIG_CACHE_TTL_SECS)
+            .await;
     }
 
     Ok(markets)
@@ -383,7 +516,7 @@ pub async fn list_lending_markets(
         .bind_refs([&protocol_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-    let result = infra::db::run("list_lending_markets", statement.all()).await?;
+    let result = infra::db::run("list_lending_markets", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -422,3 +555,138 @@ pub async fn list_lending_markets(
 
     Ok(markets)
 }
+
+pub fn tool_override_from_row(row: &Value) -> Option<ToolOverride> {
+    let name = row.get("name").and_then(|v| v.as_str())?.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let text = |key: &str| {
+        row.get(key)
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+    };
+    Some(ToolOverride {
+        name: name.to_string(),
+        description: text("description"),
+        schema_json: text("schema_json"),
+    })
+}
+
+/// 从 KV 缓存获取工具描述覆盖；没有覆盖时也缓存空列表，避免每次 tools/list 都查询 DB。
+/// 查询失败时返回错误，同时把空列表缓存 1 分钟
+pub async fn list_tool_overrides_cached(
+    db: &D1Database,
+    kv: &TrackedKv,
+    kv_prefix: &str,
+) -> Result<Vec<ToolOverride>> {
+    let cache_key = infra::kv_key(kv_prefix, TOOL_OVERRIDES_CACHE_KEY);
+    if let Ok(Some(cached)) = kv.get_text(&cache_key).await {
+        if let Ok(overrides) = serde_json::from_str::<Vec<ToolOverride>>(&cached) {
+            return Ok(overrides);
+        }
+    }
+
+    let overrides = match list_tool_overrides(db).await {
+        Ok(overrides) => overrides,
+        Err(err) => {
+            let _ = kv
+                .put_text_with_ttl(&cache_key, "[]".to_string(), TOOL_OVERRIDES_ERROR_TTL_SECS)
+                .await;
+            return Err(err);
+        }
+    };
+    if let Ok(json) = serde_json::to_string(&overrides) {
+        let _ = kv
+            .put_text_with_ttl(&cache_key, json, CONFIG_CACHE_TTL_SECS)
+            .await;
+    }
+    Ok(overrides)
+}
+
+pub async fn list_tool_overrides(db: &D1Database) -> Result<Vec<ToolOverride>> {
+    let statement = db.prepare("SELECT name, description, schema_json FROM tool_overrides");
+    let result = infra::db::run("list_tool_overrides", || statement.all()).await?;
+    let rows: Vec<Value> = result
+        .results()
+        .map_err(|err| CroLensError::DbError(err.to_string()))?;
+    Ok(rows.iter().filter_map(tool_override_from_row).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+    use std::cell::Cell;
+
+    const ROUTER: &str = "0x145863Eb42Cf62847A6Ca784e6416C1682b1b2Ae";
+
+    fn router() -> Address {
+        types::parse_address(ROUTER).expect("valid address")
+    }
+
+    #[test]
+    fn protocol_contract_cache_key_is_scoped_by_protocol_and_type() {
+        assert_eq!(
+            protocol_contract_cache_key("vvs", "router"),
+            "cache:protocol_contract:vvs:router"
+        );
+        assert_ne!(
+            protocol_contract_cache_key("vvs", "router"),
+            protocol_contract_cache_key("vvs", "factory")
+        );
+        assert_eq!(
+            infra::kv_key(
+                "prod:",
+                &protocol_contract_cache_key("tectonic", "comptroller")
+            ),
+            infra::kv_key("prod:", "cache:protocol_contract:tectonic:comptroller")
+        );
+    }
+
+    #[test]
+    fn cache_hit_skips_the_database() {
+        let loads = Cell::new(0);
+        let (address, store) = read_through_address(Some(ROUTER), || async {
+            loads.set(loads.get() + 1);
+            Ok(Address::ZERO)
+        })
+        .now_or_never()
+        .expect("ready")
+        .expect("cached");
+        assert_eq!(address, router());
+        assert!(!store);
+        assert_eq!(loads.get(), 0);
+    }
+
+    #[test]
+    fn cache_miss_loads_and_requests_write_back() {
+        for cached in [None, Some("not-an-address"), Some("")] {
+            let loads = Cell::new(0);
+            let (address, store) = read_through_address(cached, || async {
+                loads.set(loads.get() + 1);
+                Ok(router())
+            })
+            .now_or_never()
+            .expect("ready")
+            .expect("loaded");
+            assert_eq!(address, router());
+            assert!(store);
+            assert_eq!(loads.get(), 1);
+        }
+    }
+
+    #[test]
+    fn missing_contract_error_is_not_cached() {
+        let result = read_through_address(None, || async {
+            Err(CroLensError::DbError(
+                "Missing protocol contract: vvs.router".to_string(),
+            ))
+        })
+        .now_or_never()
+        .expect("ready");
+        assert!(matches!(result, Err(CroLensError::DbError(_))));
+    }
+}
@@ -11,7 +11,112 @@ use crate::types;
 const DB_TIMEOUT: Duration = Duration::from_secs(5);
 const SLOW_QUERY_THRESHOLD_MS: i64 = 500;
 
-pub async fn run<T>(label: &str, fut: impl Future<Output = worker::Result<T>>) -> Result<T> {
+/// D1 偶发的瞬时错误 (小写匹配)，可以安全重试
+const TRANSIENT_DB_ERRORS: &[&str] = &[
+    "network connection lost",
+    "storage operation exceeded timeout",
+    "storage caused object to be reset",
+    "durable object reset",
+    "internal error",
+    "database is locked",
+    "sqlite_busy",
+];
+
+/// 永不重试的错误：约束冲突是确定性的，且 `insert_payment_once` 依赖它判断幂等
+const NON_RETRYABLE_DB_ERRORS: &[&str] = &["constraint", "unique"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff_base_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff_base_ms: 100,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不重试 (只执行一次)
+    pub const NONE: Self = Self {
+        max_attempts: 1,
+        backoff_base_ms: 0,
+    };
+
+    fn backoff_ms(&self, attempt: u32) -> u64 {
+        self.backoff_base_ms.saturating_mul(1u64 << attempt.min(10))
+    }
+}
+
+pub fn is_transient_db_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    if NON_RETRYABLE_DB_ERRORS.iter().any(|m| message.contains(m)) {
+        return false;
+    }
+    TRANSIENT_DB_ERRORS.iter().any(|m| message.contains(m))
+}
+
+/// 执行 D1 查询，瞬时错误按默认策略重试；`make` 每次尝试都会重新创建查询 future
+pub async fn run<T, F, Fut>(label: &str, make: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = worker::Result<T>>,
+{
+    run_with_policy(label, RetryPolicy::default(), make).await
+}
+
+pub async fn run_with_policy<T, F, Fut>(label: &str, policy: RetryPolicy, mut make: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = worker::Result<T>>,
+{
+    retry_transient(
+        policy,
+        || run_once(label, make()),
+        |ms| {
+            console_warn!("[WARN] Retrying DB query {} after {}ms", label, ms);
+            Delay::from(Duration::from_millis(ms))
+        },
+    )
+    .await
+}
+
+/// 仅对 `is_transient_db_error` 的 DbError 重试；超时不重试 (查询可能仍在执行)
+async fn retry_transient<T, Op, OpFut, Sleep, SleepFut>(
+    policy: RetryPolicy,
+    mut op: Op,
+    mut sleep: Sleep,
+) -> Result<T>
+where
+    Op: FnMut() -> OpFut,
+    OpFut: Future<Output = Result<T>>,
+    Sleep: FnMut(u64) -> SleepFut,
+    SleepFut: Future<Output = ()>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(err) => {
+                attempt += 1;
+                let transient = match &err {
+                    CroLensError::DbError(message) => is_transient_db_error(message),
+                    _ => false,
+                };
+                if !transient || attempt >= policy.max_attempts {
+                    return Err(err);
+                }
+                sleep(policy.backoff_ms(attempt - 1)).await;
+            }
+        }
+    }
+}
+
+async fn run_once<T>(label: &str, fut: impl Future<Output = worker::Result<T>>) -> Result<T> {
     let started = types::now_ms();
 
     let fut = fut.fuse();
@@ -33,3 +138,98 @@ pub async fn run<T>(label: &str, fut: impl Future<Output = worker::Result<T>>) -
         ))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    fn flaky(
+        fail_times: u32,
+        message: &'static str,
+    ) -> impl FnMut() -> std::future::Ready<Result<u32>> {
+        let calls = Cell::new(0u32);
+        move || {
+            calls.set(calls.get() + 1);
+            if calls.get() <= fail_times {
+                std::future::ready(Err(CroLensError::DbError(message.to_string())))
+            } else {
+                std::future::ready(Ok(calls.get()))
+            }
+        }
+    }
+
+    #[test]
+    fn classifies_transient_errors() {
+        assert!(is_transient_db_error("D1_ERROR: Network connection lost."));
+        assert!(is_transient_db_error(
+            "D1_ERROR: Storage operation exceeded timeout"
+        ));
+        assert!(!is_transient_db_error("no such table: tokens"));
+    }
+
+    #[test]
+    fn constraint_errors_are_never_transient() {
+        assert!(!is_transient_db_error(
+            "D1_ERROR: UNIQUE constraint failed: payments.tx_hash"
+        ));
+        assert!(!is_transient_db_error(
+            "internal error: SQLITE_CONSTRAINT_PRIMARYKEY"
+        ));
+    }
+
+    #[test]
+    fn transient_error_is_retried() {
+        let sleeps = RefCell::new(Vec::new());
+        let result = retry_transient(
+            RetryPolicy::default(),
+            flaky(2, "Network connection lost"),
+            |ms| {
+                sleeps.borrow_mut().push(ms);
+                async {}
+            },
+        )
+        .now_or_never()
+        .expect("ready");
+        assert_eq!(result.expect("should succeed"), 3);
+        assert_eq!(*sleeps.borrow(), vec![100, 200]);
+    }
+
+    #[test]
+    fn constraint_error_is_not_retried() {
+        let calls = Cell::new(0);
+        let result: Result<()> = retry_transient(
+            RetryPolicy::default(),
+            || {
+                calls.set(calls.get() + 1);
+                async {
+                    Err(CroLensError::DbError(
+                        "UNIQUE constraint failed: payments.tx_hash".to_string(),
+                    ))
+                }
+            },
+            |_| async {},
+        )
+        .now_or_never()
+        .expect("ready");
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn transient_retry_gives_up_after_max_attempts() {
+        let policy = RetryPolicy::default();
+        let result = retry_transient(policy, flaky(10, "database is locked"), |_| async {})
+            .now_or_never()
+            .expect("ready");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn policy_none_runs_once() {
+        let result = retry_transient(RetryPolicy::NONE, flaky(1, "internal error"), |_| async {})
+            .now_or_never()
+            .expect("ready");
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use worker::Env;
+
+use crate::error::{CroLensError, Result};
+use crate::infra;
+use crate::types;
+
+/// 合约创建信息不会变化，缓存 7 天
+const CONTRACT_CREATION_CACHE_TTL_SECS: u64 = 7 * 24 * 3600;
+/// explorer 明确返回无数据 (EOA 或尚未部署) 时的负缓存时间
+const CONTRACT_CREATION_NOT_FOUND_TTL_SECS: u64 = 3600;
+/// explorer 请求超时；超时按查询失败处理，不写缓存
+const EXPLORER_TIMEOUT_MS: u64 = 5_000;
+
+/// 读取 EXPLORER_API_URL (Etherscan 兼容的 `/api` 端点)；未配置时不查询创建信息
+pub fn explorer_api_url(env: &Env) -> Option<String> {
+    parse_explorer_api_url(
+        env.var("EXPLORER_API_URL")
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    )
+}
+
+fn parse_explorer_api_url(value: Option<&str>) -> Option<String> {
+    let trimmed = value?.trim().trim_end_matches('/');
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+/// 合约的部署者与部署交易
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractCreation {
+    pub creator_address: String,
+    pub creation_tx: String,
+    /// 部分 explorer 不返回区块号
+    #[serde(default)]
+    pub creation_block: Option<u64>,
+}
+
+/// `getcontractcreation` 请求 URL；explorer 未配置时为 None
+pub fn creation_url(base: Option<&str>, address: Address) -> Option<String> {
+    let base = base?;
+    let separator = if base.contains('?') { '&' } else { '?' };
+    Some(format!(
+        "{base}{separator}module=contract&action=getcontractcreation&contractaddresses={}",
+        address.to_string().to_lowercase()
+    ))
+}
+
+/// 解析 Etherscan 兼容的响应：`{"status":"1","result":[{"contractCreator","txHash","blockNumber"?}]}`
+pub fn parse_contract_creation(payload: &Value) -> Option<ContractCreation> {
+    let entry = payload.get("result")?.as_array()?.first()?;
+    let creator = entry.get("contractCreator").and_then(|v| v.as_str())?;
+    let tx_hash = entry.get("txHash").and_then(|v| v.as_str())?;
+    let creator_address = types::parse_address(creator).ok()?;
+    types::validate_hex_string(tx_hash, 64).ok()?;
+    let creation_block = entry.get("blockNumber").and_then(|v| match v {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse::<u64>().ok(),
+        _ => None,
+    });
+    Some(ContractCreation {
+        creator_address: creator_address.to_string(),
+        creation_tx: tx_hash.trim().to_lowercase(),
+        creation_block,
+    })
+}
+
+/// 区分“没有创建信息”与异常响应：`result` 为 null 或空数组时为 `Ok(None)`，
+/// 其它无法解析的响应 (如限流提示) 为错误，不应被负缓存
+pub fn classify_creation_response(payload: &Value) -> Result<Option<ContractCreation>> {
+    if let Some(creation) = parse_contract_creation(payload) {
+        return Ok(Some(creation));
+    }
+    match payload.get("result") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Array(entries)) if entries.is_empty() => Ok(None),
+        _ => Err(CroLensError::RpcError(format!(
+            "unexpected explorer response: {}",
+            payload
+                .get("message")
+                .and_then(|v| v.as_str())
+                .unwrap_or("malformed result")
+        ))),
+    }
+}
+
+async fn fetch_contract_creation(url: &str) -> Result<Option<ContractCreation>> {
+    let headers = worker::Headers::new();
+    headers
+        .set("Accept", "application/json")
+        .map_err(|err| CroLensError::RpcError(err.to_string()))?;
+    let req = worker::Request::new_with_init(
+        url,
+        worker::RequestInit::new()
+            .with_method(worker::Method::Get)
+            .with_headers(headers),
+    )
+    .map_err(|err| CroLensError::RpcError(err.to_string()))?;
+    let fetch = async {
+        let mut resp = worker::Fetch::Request(req)
+            .send()
+            .await
+            .map_err(|err| CroLensError::RpcError(err.to_string()))?;
+        resp.json::<Value>()
+            .await
+            .map_err(|err| CroLensError::RpcError(err.to_string()))
+    };
+    let timeout = worker::Delay::from(std::time::Duration::from_millis(EXPLORER_TIMEOUT_MS));
+    let payload = infra::concurrency::race_timeout(fetch, timeout)
+        .await
+        .ok_or_else(|| {
+            CroLensError::RpcError(format!("explorer timed out after {EXPLORER_TIMEOUT_MS}ms"))
+        })??;
+    classify_creation_response(&payload)
+}
+
+/// explorer 未返回区块号时通过 RPC 从部署交易补齐
+async fn fill_creation_block(services: &infra::Services, creation: &mut ContractCreation) {
+    if creation.creation_block.is_some() {
+        return;
+    }
+    let Ok(rpc) = services.rpc() else {
+        return;
+    };
+    creation.creation_block = rpc
+        .call(
+            "eth_getTransactionByHash",
+            serde_json::json!([creation.creation_tx]),
+        )
+        .await
+        .ok()
+        .as_ref()
+        .and_then(|tx| tx.get("blockNumber"))
+        .and_then(|v| v.as_str())
+        .and_then(|hex| u64::from_str_radix(hex.trim_start_matches("0x"), 16).ok());
+}
+
+/// 查询合约创建信息 (按地址缓存，区块号补齐后再写入)；explorer 未配置、查询失败或地址不是合约时返回 None。
+/// “不是合约”的结果缓存 1 小时，查询失败不缓存
+pub async fn lookup_contract_creation(
+    services: &infra::Services,
+    address: Address,
+) -> Option<ContractCreation> {
+    let url = creation_url(services.explorer_api_url.as_deref(), address)?;
+    let key = services.kv_key(&format!(
+        "contract:creation:{}",
+        address.to_string().to_lowercase()
+    ));
+    if let Some(cached) = services.kv_get_text(&key).await {
+        // "null" 为负缓存
+        if let Ok(creation) = serde_json::from_str::<Option<ContractCreation>>(&cached) {
+            return creation;
+        }
+    }
+
+    let creation = match fetch_contract_creation(&url).await {
+        Ok(creation) => creation,
+        Err(err) => {
+            worker::console_warn!("[WARN] explorer contract creation lookup failed: {}", err);
+            return None;
+        }
+    };
+    let Some(mut creation) = creation else {
+        services
+            .kv_put_text(
+                &key,
+                "null".to_string(),
+                CONTRACT_CREATION_NOT_FOUND_TTL_SECS,
+            )
+            .await;
+        return None;
+    };
+    fill_creation_block(services, &mut creation).await;
+    if let Ok(text) = serde_json::to_string(&creation) {
+        services
+            .kv_put_text(&key, text, CONTRACT_CREATION_CACHE_TTL_SECS)
+            .await;
+    }
+    Some(creation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CREATOR: &str = "0x1111111111111111111111111111111111111111";
+
+    fn tx_hash() -> String {
+        format!("0x{}", "ab".repeat(32))
+    }
+
+    #[test]
+    fn explorer_url_parsing() {
+        assert_eq!(parse_explorer_api_url(None), None);
+        assert_eq!(parse_explorer_api_url(Some("  ")), None);
+        assert_eq!(
+            parse_explorer_api_url(Some(" https://explorer-api.cronos.org/mainnet/api/ ")),
+            Some("https://explorer-api.cronos.org/mainnet/api".to_string())
+        );
+    }
+
+    #[test]
+    fn unconfigured_explorer_has_no_creation_url() {
+        let addr = Address::from([0x22u8; 20]);
+        assert_eq!(creation_url(None, addr), None);
+        assert_eq!(
+            creation_url(Some("https://example.org/api"), addr),
+            Some(format!(
+                "https://example.org/api?module=contract&action=getcontractcreation&contractaddresses=0x{}",
+                "22".repeat(20)
+            ))
+        );
+        let with_key =
+            creation_url(Some("https://example.org/api?apikey=k"), addr).expect("configured");
+        assert!(with_key.starts_with("https://example.org/api?apikey=k&module=contract"));
+    }
+
+    #[test]
+    fn parses_creation_response() {
+        let payload = serde_json::json!({
+            "status": "1",
+            "message": "OK",
+            "result": [{
+                "contractAddress": "0x2222222222222222222222222222222222222222",
+                "contractCreator": CREATOR,
+                "txHash": tx_hash().to_uppercase().replacen("0X", "0x", 1),
+                "blockNumber": "1234567"
+            }]
+        });
+        let creation = parse_contract_creation(&payload).expect("creation");
+        assert_eq!(creation.creator_address.to_lowercase(), CREATOR);
+        assert_eq!(creation.creation_tx, tx_hash());
+        assert_eq!(creation.creation_block, Some(1_234_567));
+    }
+
+    #[test]
+    fn missing_block_number_is_optional() {
+        let payload = serde_json::json!({
+            "status": "1",
+            "result": [{ "contractCreator": CREATOR, "txHash": tx_hash() }]
+        });
+        let creation = parse_contract_creation(&payload).expect("creation");
+        assert_eq!(creation.creation_block, None);
+    }
+
+    #[test]
+    fn rejects_empty_or_malformed_results() {
+        let not_found =
+            serde_json::json!({ "status": "0", "message": "No data found", "result": null });
+        assert!(parse_contract_creation(&not_found).is_none());
+        assert!(parse_contract_creation(&serde_json::json!({ "result": [] })).is_none());
+        let bad_tx = serde_json::json!({
+            "result": [{ "contractCreator": CREATOR, "txHash": "0x1234" }]
+        });
+        assert!(parse_contract_creation(&bad_tx).is_none());
+    }
+
+    #[test]
+    fn only_empty_results_count_as_not_found() {
+        let not_found =
+            serde_json::json!({ "status": "0", "message": "No data found", "result": null });
+        assert_eq!(classify_creation_response(&not_found).expect("ok"), None);
+        let empty = serde_json::json!({ "status": "1", "result": [] });
+        assert_eq!(classify_creation_response(&empty).expect("ok"), None);
+
+        let rate_limited = serde_json::json!({
+            "status": "0",
+            "message": "NOTOK",
+            "result": "Max rate limit reached"
+        });
+        assert!(classify_creation_response(&rate_limited).is_err());
+        let bad_tx = serde_json::json!({
+            "result": [{ "contractCreator": CREATOR, "txHash": "0x1234" }]
+        });
+        assert!(classify_creation_response(&bad_tx).is_err());
+
+        let found = serde_json::json!({
+            "result": [{ "contractCreator": CREATOR, "txHash": tx_hash() }]
+        });
+        assert!(classify_creation_response(&found).expect("ok").is_some());
+    }
+
+    #[test]
+    fn cached_entries_include_negative_results() {
+        let creation = ContractCreation {
+            creator_address: CREATOR.to_string(),
+            creation_tx: tx_hash(),
+            creation_block: Some(7),
+        };
+        let cached = serde_json::to_string(&creation).expect("json");
+        assert_eq!(
+            serde_json::from_str::<Option<ContractCreation>>(&cached).expect("hit"),
+            Some(creation)
+        );
+        assert_eq!(
+            serde_json::from_str::<Option<ContractCreation>>("null").expect("negative"),
+            None
+        );
+    }
+}
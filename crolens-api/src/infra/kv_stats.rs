@@ -0,0 +1,201 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::rc::Rc;
+
+use serde_json::Value;
+use worker::kv::KvStore;
+use worker::Env;
+
+use crate::error::{CroLensError, Result};
+use crate::types;
+
+/// 读取 KV_INSTRUMENTATION；"true"/"1" 时在 meta 中输出 kv_ops / kv_latency_ms
+pub fn kv_instrumentation_enabled(env: &Env) -> bool {
+    parse_kv_instrumentation(
+        env.var("KV_INSTRUMENTATION")
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    )
+}
+
+pub fn parse_kv_instrumentation(value: Option<&str>) -> bool {
+    value.is_some_and(|v| {
+        let v = v.trim();
+        v == "1" || v.eq_ignore_ascii_case("true")
+    })
+}
+
+/// 单个请求内的 KV 操作次数与累计耗时 (并发操作的耗时按各自计入，可能超过墙钟时间)
+#[derive(Debug, Default)]
+pub struct KvStats {
+    enabled: bool,
+    ops: Cell<u64>,
+    latency_ms: Cell<i64>,
+}
+
+impl KvStats {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    pub fn ops(&self) -> u64 {
+        self.ops.get()
+    }
+
+    pub fn latency_ms(&self) -> i64 {
+        self.latency_ms.get()
+    }
+
+    /// 记录一次 KV 操作；时钟回拨导致的负值按 0 计
+    pub fn record(&self, latency_ms: i64) {
+        self.ops.set(self.ops.get().saturating_add(1));
+        self.latency_ms
+            .set(self.latency_ms.get().saturating_add(latency_ms.max(0)));
+    }
+
+    /// 执行一次 KV 操作并记录其耗时
+    pub async fn track<F: Future>(&self, op: F) -> F::Output {
+        self.track_with(types::now_ms, op).await
+    }
+
+    pub async fn track_with<C, F>(&self, clock: C, op: F) -> F::Output
+    where
+        C: Fn() -> i64,
+        F: Future,
+    {
+        let started = clock();
+        let output = op.await;
+        self.record(clock().saturating_sub(started));
+        output
+    }
+
+    /// 开启时写入 meta.kv_ops / meta.kv_latency_ms
+    pub fn annotate_meta(&self, meta: &mut Value) {
+        if !self.enabled {
+            return;
+        }
+        if let Some(obj) = meta.as_object_mut() {
+            obj.insert("kv_ops".to_string(), Value::from(self.ops()));
+            obj.insert("kv_latency_ms".to_string(), Value::from(self.latency_ms()));
+        }
+    }
+}
+
+/// 每次操作都计入 KvStats 的 KV 访问；克隆后共享同一份统计
+#[derive(Clone)]
+pub struct TrackedKv {
+    kv: KvStore,
+    stats: Rc<KvStats>,
+}
+
+impl TrackedKv {
+    pub fn new(kv: KvStore, stats: Rc<KvStats>) -> Self {
+        Self { kv, stats }
+    }
+
+    /// 不属于任何请求 (cron、运维端点) 的访问，统计不会输出
+    pub fn untracked(kv: KvStore) -> Self {
+        Self::new(kv, Rc::new(KvStats::default()))
+    }
+
+    pub fn stats(&self) -> &KvStats {
+        &self.stats
+    }
+
+    pub async fn get_text(&self, key: &str) -> Result<Option<String>> {
+        self.stats
+            .track(self.kv.get(key).text())
+            .await
+            .map_err(|err| CroLensError::KvError(err.to_string()))
+    }
+
+    pub async fn put_text_with_ttl(&self, key: &str, value: String, ttl_secs: u64) -> Result<()> {
+        let put = self
+            .kv
+            .put(key, value)
+            .map_err(|err| CroLensError::KvError(err.to_string()))?
+            .expiration_ttl(ttl_secs);
+        self.stats
+            .track(put.execute())
+            .await
+            .map_err(|err| CroLensError::KvError(err.to_string()))
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        self.stats
+            .track(self.kv.delete(key))
+            .await
+            .map_err(|err| CroLensError::KvError(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每次读取前进固定步长的假时钟
+    fn stepping_clock(step_ms: i64) -> impl Fn() -> i64 {
+        let now = Cell::new(1_700_000_000_000_i64);
+        move || {
+            let current = now.get();
+            now.set(current + step_ms);
+            current
+        }
+    }
+
+    #[tokio::test]
+    async fn tracked_ops_accumulate_count_and_latency() {
+        let stats = KvStats::new(true);
+        let clock = stepping_clock(7);
+
+        let first = stats.track_with(&clock, async { Some("cached") }).await;
+        let second = stats
+            .track_with(&clock, async { Ok::<_, String>(()) })
+            .await;
+        assert_eq!(first, Some("cached"));
+        assert_eq!(second, Ok(()));
+
+        assert_eq!(stats.ops(), 2);
+        assert_eq!(stats.latency_ms(), 14);
+    }
+
+    #[test]
+    fn negative_latency_counts_as_zero() {
+        let stats = KvStats::new(true);
+        stats.record(12);
+        stats.record(-5);
+        assert_eq!(stats.ops(), 2);
+        assert_eq!(stats.latency_ms(), 12);
+    }
+
+    #[test]
+    fn meta_includes_kv_fields_only_when_enabled() {
+        let enabled = KvStats::new(true);
+        enabled.record(3);
+        let mut meta = serde_json::json!({ "trace_id": "t" });
+        enabled.annotate_meta(&mut meta);
+        assert_eq!(meta["kv_ops"], 1);
+        assert_eq!(meta["kv_latency_ms"], 3);
+
+        let disabled = KvStats::new(false);
+        disabled.record(3);
+        let mut meta = serde_json::json!({ "trace_id": "t" });
+        disabled.annotate_meta(&mut meta);
+        assert!(meta.get("kv_ops").is_none());
+        assert!(meta.get("kv_latency_ms").is_none());
+    }
+
+    #[test]
+    fn parse_kv_instrumentation_accepts_true_or_one() {
+        assert!(parse_kv_instrumentation(Some("true")));
+        assert!(parse_kv_instrumentation(Some(" TRUE ")));
+        assert!(parse_kv_instrumentation(Some("1")));
+        assert!(!parse_kv_instrumentation(Some("0")));
+        assert!(!parse_kv_instrumentation(Some("yes please")));
+        assert!(!parse_kv_instrumentation(None));
+    }
+}
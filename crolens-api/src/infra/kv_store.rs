@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use worker::kv::KvStore;
+
+use crate::error::{CroLensError, Result};
+use crate::infra::kv_stats::TrackedKv;
+
+/// 按 key 读写文本值的 KV 存储；限流、计费指标与价格缓存共用，测试中可替换为内存实现
+#[async_trait(?Send)]
+pub trait KvTextStore {
+    async fn get_text(&self, key: &str) -> Result<Option<String>>;
+    async fn put_text_with_ttl(&self, key: &str, value: String, ttl_secs: u64) -> Result<()>;
+}
+
+#[async_trait(?Send)]
+impl KvTextStore for KvStore {
+    async fn get_text(&self, key: &str) -> Result<Option<String>> {
+        self.get(key)
+            .text()
+            .await
+            .map_err(|err| CroLensError::KvError(err.to_string()))
+    }
+
+    async fn put_text_with_ttl(&self, key: &str, value: String, ttl_secs: u64) -> Result<()> {
+        self.put(key, value)
+            .map_err(|err| CroLensError::KvError(err.to_string()))?
+            .expiration_ttl(ttl_secs)
+            .execute()
+            .await
+            .map_err(|err| CroLensError::KvError(err.to_string()))?;
+        Ok(())
+    }
+}
+
+/// 请求内的读写计入 kv_stats
+#[async_trait(?Send)]
+impl KvTextStore for TrackedKv {
+    async fn get_text(&self, key: &str) -> Result<Option<String>> {
+        TrackedKv::get_text(self, key).await
+    }
+
+    async fn put_text_with_ttl(&self, key: &str, value: String, ttl_secs: u64) -> Result<()> {
+        TrackedKv::put_text_with_ttl(self, key, value, ttl_secs).await
+    }
+}
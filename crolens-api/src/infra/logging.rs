@@ -52,7 +52,7 @@ pub async fn log_request(
     ])
     .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-    infra::db::run("log_request", statement.run()).await?;
+    infra::db::run("log_request", || statement.run()).await?;
 
     Ok(())
 }
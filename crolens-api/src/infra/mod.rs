@@ -1,28 +1,117 @@
+pub mod concurrency;
 pub mod config;
 pub mod db;
+pub mod explorer;
+pub mod kv_stats;
+pub mod kv_store;
 pub mod logging;
 pub mod multicall;
+pub mod oracle;
 pub mod price;
+pub mod price_history;
 pub mod rpc;
+pub mod samples;
+pub mod signatures;
+pub mod single_flight;
 pub mod structured_log;
 pub mod tenderly;
 pub mod token;
 pub mod x402;
 
-use worker::kv::KvStore;
+use std::rc::Rc;
+
 use worker::{D1Database, Env};
 
 use crate::error::{CroLensError, Result};
 use crate::types;
 
+/// KV key 前缀 (多个部署共享同一 KV namespace 时避免冲突)
+pub fn kv_prefix(env: &Env) -> String {
+    env.var("KV_PREFIX")
+        .ok()
+        .map(|v| v.to_string().trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Build a KV key under the deployment prefix. An empty prefix yields `key` unchanged.
+pub fn kv_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        return key.to_string();
+    }
+    if prefix.ends_with(':') {
+        format!("{prefix}{key}")
+    } else {
+        format!("{prefix}:{key}")
+    }
+}
+
+/// 单个请求的默认时间预算 (毫秒)
+pub const REQUEST_BUDGET_MS_DEFAULT: i64 = 25_000;
+const REQUEST_BUDGET_MS_MIN: i64 = 1_000;
+const REQUEST_BUDGET_MS_MAX: i64 = 300_000;
+/// 距离截止时间不足该值时不再发起新的 multicall 批次
+pub const DEADLINE_MARGIN_MS: i64 = 3_000;
+
+pub fn request_budget_ms(env: &Env) -> i64 {
+    env.var("REQUEST_BUDGET_MS")
+        .ok()
+        .and_then(|v| v.to_string().trim().parse::<i64>().ok())
+        .map(|v| v.clamp(REQUEST_BUDGET_MS_MIN, REQUEST_BUDGET_MS_MAX))
+        .unwrap_or(REQUEST_BUDGET_MS_DEFAULT)
+}
+
+/// construct_swap_tx 未提供 slippage_bps 时的默认滑点 (0.5%)
+pub const DEFAULT_SLIPPAGE_BPS: u16 = 50;
+pub const MAX_SLIPPAGE_BPS: u16 = 5_000;
+
+/// 读取 DEFAULT_SLIPPAGE_BPS；无效或超过 MAX_SLIPPAGE_BPS 时使用默认值
+pub fn default_slippage_bps(env: &Env) -> u16 {
+    env.var("DEFAULT_SLIPPAGE_BPS")
+        .ok()
+        .and_then(|v| v.to_string().trim().parse::<u16>().ok())
+        .filter(|v| *v <= MAX_SLIPPAGE_BPS)
+        .unwrap_or(DEFAULT_SLIPPAGE_BPS)
+}
+
+/// 剩余时间不足以完成下一批下游调用时返回 true
+pub fn deadline_near(deadline_ms: i64, now_ms: i64) -> bool {
+    now_ms.saturating_add(DEADLINE_MARGIN_MS) >= deadline_ms
+}
+
 pub struct Services {
     pub trace_id: String,
     pub start_ms: i64,
+    /// 请求截止时间 (start_ms + REQUEST_BUDGET_MS)
+    pub deadline_ms: i64,
     rpc: Option<rpc::RpcClient>,
     multicall: Option<multicall::MulticallClient>,
     tenderly: Option<tenderly::TenderlyClient>,
     pub db: D1Database,
-    pub kv: KvStore,
+    /// 所有 KV 访问都计入本请求的 kv_stats
+    pub kv: kv_stats::TrackedKv,
+    pub kv_prefix: String,
+    /// derived 价格相对参考价格允许的最大倍数 (None 表示不校验)
+    pub price_sanity_multiple: Option<f64>,
+    /// 池子报价侧流动性 (USD) 低于该值时不用于 derived 定价
+    pub price_min_liquidity_usd: f64,
+    /// 价格来源的查询顺序 (PRICE_SOURCE_PRIORITY)
+    pub price_source_priority: Vec<price::PriceSource>,
+    /// construct_swap_tx 未提供 slippage_bps 时的默认值
+    pub default_slippage_bps: u16,
+    /// 输出精度 (PRECISION_*_DP)
+    pub precision: types::Precision,
+    /// 视为无限授权的阈值 (APPROVAL_UNLIMITED_*)
+    pub approval_threshold: types::ApprovalThreshold,
+    /// 单个 fan-out 同时进行的 KV/RPC 请求上限 (MAX_CONCURRENT_SUBREQUESTS)
+    pub max_concurrent_subrequests: usize,
+    /// 预言机答案允许的最大时长 (ORACLE_MAX_AGE_SECS，0 表示不校验)
+    pub oracle_max_age_secs: u64,
+    /// Etherscan 兼容的 explorer API (EXPLORER_API_URL)，用于查询合约创建信息
+    pub explorer_api_url: Option<String>,
+    /// simulate_transaction 允许的目标合约 (SIMULATION_TARGET_ALLOWLIST)；None 表示不限制
+    pub simulation_target_allowlist: Option<Vec<alloy_primitives::Address>>,
+    /// 本请求内缓存的上一轮聚合价格 (derived 价格合理性校验的参考点)
+    pub(crate) price_references: std::cell::OnceCell<std::collections::HashMap<String, f64>>,
 }
 
 impl Services {
@@ -30,59 +119,175 @@ impl Services {
         let db = env
             .d1("DB")
             .map_err(|err| CroLensError::DbError(err.to_string()))?;
-        let kv = env
-            .kv("KV")
-            .map_err(|err| CroLensError::KvError(err.to_string()))?;
-        let multicall_address = env
-            .var("MULTICALL3_ADDRESS")
-            .map(|v| v.to_string())
-            .ok()
-            .and_then(|v| types::parse_address(&v).ok())
-            .unwrap_or_else(|| {
-                types::parse_address("0xcA11bde05977b3631167028862bE2a173976CA11")
-                    .unwrap_or_default()
-            });
+        // 本请求的 KV 操作次数与累计耗时 (KV_INSTRUMENTATION)，RPC 缓存等共享同一份统计
+        let kv_stats = Rc::new(kv_stats::KvStats::new(
+            kv_stats::kv_instrumentation_enabled(env),
+        ));
+        let kv = kv_stats::TrackedKv::new(
+            env.kv("KV")
+                .map_err(|err| CroLensError::KvError(err.to_string()))?,
+            kv_stats,
+        );
+        let multicall_address = multicall::multicall3_address(env);
+
+        let kv_prefix = kv_prefix(env);
 
         let rpc = rpc::RpcClient::try_new(env, Some(kv.clone()));
         let multicall = rpc
             .as_ref()
-            .map(|client| multicall::MulticallClient::new(client.clone(), multicall_address));
-        // 模拟客户端: 使用 eth_call + eth_estimateGas (Tenderly 已停止支持 Cronos)
-        let tenderly = rpc.as_ref().map(|client| tenderly::SimulationClient::new(client.clone()));
+            .map(|client| multicall::MulticallClient::new(client.clone(), multicall_address))
+            .map(|client| client.with_max_calls_per_batch(multicall::max_calls_per_batch(env)))
+            .map(|client| {
+                client.with_max_concurrent_batches(concurrency::max_concurrent_subrequests(env))
+            })
+            .map(|client| client.with_code_check(kv.clone(), kv_prefix.clone()));
+        // 模拟客户端: 默认 eth_call + eth_estimateGas (Tenderly 已停止支持 Cronos)
+        let simulation_backend = tenderly::SimulationBackend::from_env(env);
+        let tenderly = rpc
+            .as_ref()
+            .map(|client| tenderly::SimulationClient::new(client.clone(), simulation_backend))
+            .map(|client| client.with_call_limits(rpc::CallTraceLimits::from_env(env)));
         Ok(Self {
             trace_id: trace_id.to_string(),
             start_ms,
+            deadline_ms: start_ms.saturating_add(request_budget_ms(env)),
             rpc,
             multicall,
             tenderly,
             db,
             kv,
+            kv_prefix,
+            price_sanity_multiple: price::price_sanity_multiple(env),
+            price_min_liquidity_usd: price::price_min_liquidity_usd(env),
+            price_source_priority: price::price_source_priority(env),
+            default_slippage_bps: default_slippage_bps(env),
+            precision: types::Precision::from_env(env),
+            approval_threshold: types::ApprovalThreshold::from_env(env),
+            max_concurrent_subrequests: concurrency::max_concurrent_subrequests(env),
+            oracle_max_age_secs: oracle::oracle_max_age_secs(env),
+            explorer_api_url: explorer::explorer_api_url(env),
+            simulation_target_allowlist: tenderly::simulation_target_allowlist(env),
+            price_references: std::cell::OnceCell::new(),
         })
     }
 
+    pub fn kv_key(&self, key: &str) -> String {
+        kv_key(&self.kv_prefix, key)
+    }
+
+    /// 读取文本值并计入 kv_stats；读取失败视为未命中
+    pub async fn kv_get_text(&self, key: &str) -> Option<String> {
+        self.kv.get_text(key).await.ok().flatten()
+    }
+
+    /// 尽力写入带 TTL 的文本值并计入 kv_stats
+    pub async fn kv_put_text(&self, key: &str, value: String, ttl_secs: u64) {
+        let _ = self.kv.put_text_with_ttl(key, value, ttl_secs).await;
+    }
+
+    /// 协议合约地址 (protocol_contracts，经 KV 缓存)
+    pub async fn protocol_contract(
+        &self,
+        protocol_id: &str,
+        contract_type: &str,
+    ) -> Result<alloy_primitives::Address> {
+        config::get_protocol_contract_cached(
+            &self.db,
+            &self.kv,
+            &self.kv_prefix,
+            protocol_id,
+            contract_type,
+        )
+        .await
+    }
+
     pub fn rpc(&self) -> Result<&rpc::RpcClient> {
-        self.rpc
-            .as_ref()
-            .ok_or_else(|| CroLensError::RpcError("Missing env var: BLOCKPI_RPC_URL".to_string()))
+        self.rpc.as_ref().ok_or(CroLensError::NotConfigured("RPC"))
     }
 
     pub fn multicall(&self) -> Result<&multicall::MulticallClient> {
         self.multicall
             .as_ref()
-            .ok_or_else(|| CroLensError::RpcError("Missing env var: BLOCKPI_RPC_URL".to_string()))
+            .ok_or(CroLensError::NotConfigured("RPC"))
     }
 
-    pub fn tenderly(&self) -> Option<&tenderly::TenderlyClient> {
-        self.tenderly.as_ref()
+    pub fn tenderly(&self) -> Result<&tenderly::TenderlyClient> {
+        self.tenderly
+            .as_ref()
+            .ok_or(CroLensError::NotConfigured("RPC"))
+    }
+
+    pub fn deadline_near(&self) -> bool {
+        deadline_near(self.deadline_ms, types::now_ms())
     }
 
     pub fn meta(&self) -> serde_json::Value {
-        let now = types::now_ms();
-        serde_json::json!({
-            "trace_id": self.trace_id,
-            "timestamp": now,
-            "latency_ms": now.saturating_sub(self.start_ms),
-            "cached": false,
-        })
+        let mut meta = tool_meta(&self.trace_id, self.start_ms, types::now_ms());
+        self.kv.stats().annotate_meta(&mut meta);
+        meta
+    }
+}
+
+fn tool_meta(trace_id: &str, start_ms: i64, now: i64) -> serde_json::Value {
+    serde_json::json!({
+        "trace_id": trace_id,
+        "timestamp": now,
+        "latency_ms": now.saturating_sub(start_ms),
+        "cached": false,
+        "schema_version": types::RESPONSE_SCHEMA_VERSION,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadline_not_near_with_budget_left() {
+        let start = 1_700_000_000_000;
+        let deadline = start + REQUEST_BUDGET_MS_DEFAULT;
+        assert!(!deadline_near(deadline, start));
+        assert!(!deadline_near(deadline, deadline - DEADLINE_MARGIN_MS - 1));
+    }
+
+    #[test]
+    fn deadline_near_when_budget_almost_spent() {
+        let deadline = 1_700_000_000_000;
+        assert!(deadline_near(deadline, deadline - DEADLINE_MARGIN_MS));
+        assert!(deadline_near(deadline, deadline - 1));
+        assert!(deadline_near(deadline, deadline + 5_000));
+    }
+
+    #[test]
+    fn tool_meta_includes_schema_version() {
+        let meta = tool_meta("trace", 1_000, 1_250);
+        assert_eq!(meta["schema_version"], types::RESPONSE_SCHEMA_VERSION);
+        assert_eq!(meta["latency_ms"], 250);
+        assert_eq!(meta["cached"], false);
+    }
+
+    #[test]
+    fn kv_key_empty_prefix_is_identity() {
+        assert_eq!(kv_key("", "cache:tokens:all"), "cache:tokens:all");
+        assert_eq!(kv_key("", "rl:jsonrpc:1.2.3.4"), "rl:jsonrpc:1.2.3.4");
+    }
+
+    #[test]
+    fn kv_key_prefixes_consistently() {
+        assert_eq!(
+            kv_key("staging", "cache:tokens:all"),
+            "staging:cache:tokens:all"
+        );
+        assert_eq!(
+            kv_key("staging:", "cache:tokens:all"),
+            "staging:cache:tokens:all"
+        );
+        for key in [
+            "price:anchor:cro",
+            "rpc:cache:eth_call:00",
+            "cron:price_sync:next_run_ms",
+        ] {
+            assert_eq!(kv_key("prod", key), format!("prod:{key}"));
+        }
     }
 }
@@ -1,9 +1,164 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::rc::Rc;
+
 use alloy_primitives::{Address, Bytes};
 use alloy_sol_types::SolCall;
+use serde_json::Value;
+use worker::console_warn;
 
 use crate::abi;
 use crate::error::{CroLensError, Result};
-use crate::infra::rpc::RpcClient;
+use crate::infra::concurrency;
+use crate::infra::kv_stats::TrackedKv;
+use crate::infra::rpc::{self, RpcClient};
+use crate::types;
+
+pub type CallResults = Vec<std::result::Result<Bytes, CroLensError>>;
+
+/// 单个 aggregate3 批次的调用数上限；过大的 calldata 会超过部分 RPC 提供方的请求大小限制
+pub const MAX_CALLS_PER_BATCH_DEFAULT: usize = 100;
+const MAX_CALLS_PER_BATCH_RANGE: std::ops::RangeInclusive<usize> = 1..=1000;
+
+/// MULTICALL_MAX_CALLS_PER_BATCH；无效值回退到默认值
+pub fn max_calls_per_batch(env: &worker::Env) -> usize {
+    parse_max_calls_per_batch(
+        env.var("MULTICALL_MAX_CALLS_PER_BATCH")
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    )
+}
+
+fn parse_max_calls_per_batch(raw: Option<&str>) -> usize {
+    raw.and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|v| MAX_CALLS_PER_BATCH_RANGE.contains(v))
+        .unwrap_or(MAX_CALLS_PER_BATCH_DEFAULT)
+}
+
+/// 各 EVM 链上的 Multicall3 标准部署地址
+pub const MULTICALL3_CANONICAL_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+/// eth_getCode 检查结果的 KV 缓存 (multicall3:has_code:{address})
+const MULTICALL3_CODE_CACHE_PREFIX: &str = "multicall3:has_code:";
+const MULTICALL3_CODE_PRESENT_TTL_SECS: u64 = 86_400;
+/// 没有代码时缓存较短，部署或修正配置后能较快恢复
+const MULTICALL3_CODE_ABSENT_TTL_SECS: u64 = 300;
+
+/// MULTICALL3_ADDRESS；未配置或无效时使用标准地址
+pub fn multicall3_address(env: &worker::Env) -> Address {
+    parse_multicall3_address(
+        env.var("MULTICALL3_ADDRESS")
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    )
+}
+
+fn parse_multicall3_address(raw: Option<&str>) -> Address {
+    raw.and_then(|v| types::parse_address(v.trim()).ok())
+        .or_else(|| types::parse_address(MULTICALL3_CANONICAL_ADDRESS).ok())
+        .unwrap_or_default()
+}
+
+/// eth_getCode 返回的字节码是否非空 ("0x" 或全 0 表示该地址没有合约)
+fn code_present(code_hex: &str) -> bool {
+    code_hex
+        .trim()
+        .trim_start_matches("0x")
+        .chars()
+        .any(|c| c != '0')
+}
+
+fn code_cache_key(kv_prefix: &str, address: Address) -> String {
+    crate::infra::kv_key(
+        kv_prefix,
+        &format!(
+            "{MULTICALL3_CODE_CACHE_PREFIX}{}",
+            address.to_string().to_lowercase()
+        ),
+    )
+}
+
+fn parse_cached_has_code(raw: &str) -> Option<bool> {
+    match raw.trim() {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn code_check_ttl_secs(has_code: bool) -> u64 {
+    if has_code {
+        MULTICALL3_CODE_PRESENT_TTL_SECS
+    } else {
+        MULTICALL3_CODE_ABSENT_TTL_SECS
+    }
+}
+
+thread_local! {
+    /// 本 isolate 内的代码检查结果 (按完整 KV key -> (has_code, 过期时间 ms))，与 KV 缓存同样的 TTL
+    static CODE_CHECKS: RefCell<HashMap<String, (bool, i64)>> = RefCell::new(HashMap::new());
+}
+
+fn memoized_code_check(key: &str, now_ms: i64) -> Option<bool> {
+    CODE_CHECKS.with(|memo| {
+        memo.borrow()
+            .get(key)
+            .filter(|(_, expires_ms)| now_ms < *expires_ms)
+            .map(|(has_code, _)| *has_code)
+    })
+}
+
+fn remember_code_check(key: &str, has_code: bool, now_ms: i64) {
+    let ttl_ms = i64::try_from(code_check_ttl_secs(has_code) * 1000).unwrap_or(i64::MAX);
+    CODE_CHECKS.with(|memo| {
+        memo.borrow_mut()
+            .insert(key.to_string(), (has_code, now_ms.saturating_add(ttl_ms)));
+    });
+}
+
+/// 检查 Multicall3 地址上是否部署了合约；结果先在本 isolate 内记忆，再缓存在 KV 中 (有 kv 时)
+pub async fn verify_multicall3_code(
+    rpc: &RpcClient,
+    kv: Option<&TrackedKv>,
+    kv_prefix: &str,
+    address: Address,
+) -> Result<bool> {
+    let key = code_cache_key(kv_prefix, address);
+    let now_ms = types::now_ms();
+    if let Some(known) = memoized_code_check(&key, now_ms) {
+        return Ok(known);
+    }
+    if let Some(kv) = kv {
+        if let Some(cached) = kv
+            .get_text(&key)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|raw| parse_cached_has_code(&raw))
+        {
+            remember_code_check(&key, cached, now_ms);
+            return Ok(cached);
+        }
+    }
+
+    let code = rpc
+        .call(
+            "eth_getCode",
+            serde_json::json!([address.to_string(), "latest"]),
+        )
+        .await?;
+    let has_code = code.as_str().is_some_and(code_present);
+    remember_code_check(&key, has_code, now_ms);
+    if let Some(kv) = kv {
+        let value = if has_code { "1" } else { "0" };
+        let _ = kv
+            .put_text_with_ttl(&key, value.to_string(), code_check_ttl_secs(has_code))
+            .await;
+    }
+    Ok(has_code)
+}
 
 #[derive(Debug, Clone)]
 pub struct Call {
@@ -16,6 +171,11 @@ pub struct MulticallClient {
     rpc: RpcClient,
     multicall_address: Address,
     max_calls_per_batch: usize,
+    /// 同时发出的 aggregate3 批次上限
+    max_concurrent_batches: usize,
+    code_cache: Option<(TrackedKv, String)>,
+    /// 本请求内首次使用时的代码检查结果
+    has_code: Rc<Cell<Option<bool>>>,
 }
 
 impl MulticallClient {
@@ -23,44 +183,580 @@ impl MulticallClient {
         Self {
             rpc,
             multicall_address,
-            max_calls_per_batch: 100, // 增加批量大小以减少 RPC 调用
+            max_calls_per_batch: MAX_CALLS_PER_BATCH_DEFAULT,
+            max_concurrent_batches: concurrency::MAX_CONCURRENT_SUBREQUESTS_DEFAULT,
+            code_cache: None,
+            has_code: Rc::new(Cell::new(None)),
         }
     }
 
-    pub async fn aggregate(
-        &self,
-        calls: Vec<Call>,
-    ) -> Result<Vec<std::result::Result<Bytes, CroLensError>>> {
-        let mut out = Vec::with_capacity(calls.len());
-        for chunk in calls.chunks(self.max_calls_per_batch) {
-            let mut call3s = Vec::with_capacity(chunk.len());
-            for call in chunk {
-                call3s.push(abi::Call3 {
-                    target: call.target,
-                    allowFailure: true,
-                    callData: call.call_data.clone(),
-                });
-            }
+    pub fn with_max_calls_per_batch(mut self, max_calls_per_batch: usize) -> Self {
+        self.max_calls_per_batch = max_calls_per_batch.max(1);
+        self
+    }
+
+    /// 拆分后的批次最多同时发出 `max_concurrent_batches` 个 (MAX_CONCURRENT_SUBREQUESTS)
+    pub fn with_max_concurrent_batches(mut self, max_concurrent_batches: usize) -> Self {
+        self.max_concurrent_batches = max_concurrent_batches.max(1);
+        self
+    }
+
+    /// 首次使用时检查 Multicall3 地址是否有代码，并把结果缓存在 KV 中
+    pub fn with_code_check(mut self, kv: TrackedKv, kv_prefix: String) -> Self {
+        self.code_cache = Some((kv, kv_prefix));
+        self
+    }
+
+    /// 未启用检查或检查本身失败时视为有代码，交给 aggregate 的失败回退处理
+    async fn multicall_available(&self) -> bool {
+        let Some((kv, kv_prefix)) = self.code_cache.as_ref() else {
+            return true;
+        };
+        if let Some(known) = self.has_code.get() {
+            return known;
+        }
+        let has_code =
+            match verify_multicall3_code(&self.rpc, Some(kv), kv_prefix, self.multicall_address)
+                .await
+            {
+                Ok(has_code) => has_code,
+                Err(_) => return true,
+            };
+        if !has_code {
+            console_warn!(
+                "[WARN] No Multicall3 contract at {}, using individual eth_calls",
+                self.multicall_address
+            );
+        }
+        self.has_code.set(Some(has_code));
+        has_code
+    }
+
+    /// Multicall3 调用失败 (地址错误、节点拒绝等) 时退化为逐个 eth_call 的 JSON-RPC batch
+    pub async fn aggregate(&self, calls: Vec<Call>) -> Result<CallResults> {
+        self.aggregate_at(calls, "latest").await
+    }
+
+    /// 同 `aggregate`，但在指定区块读取 (历史查询)
+    pub async fn aggregate_at(&self, calls: Vec<Call>, block_id: &str) -> Result<CallResults> {
+        if !calls.is_empty() && !self.multicall_available().await {
+            return aggregate_chunked(
+                &calls,
+                self.max_calls_per_batch,
+                self.max_concurrent_batches,
+                |chunk| self.individual_calls(chunk, block_id),
+            )
+            .await;
+        }
+        aggregate_chunked(
+            &calls,
+            self.max_calls_per_batch,
+            self.max_concurrent_batches,
+            |chunk| {
+                aggregate_or_fallback(
+                    self.aggregate_chunk(chunk, block_id),
+                    move || self.individual_calls(chunk, block_id),
+                    move |err| {
+                        console_warn!(
+                            "[WARN] Multicall3 at {} failed ({}), falling back to {} individual eth_calls",
+                            self.multicall_address,
+                            err,
+                            chunk.len()
+                        );
+                    },
+                )
+            },
+        )
+        .await
+    }
+
+    async fn aggregate_chunk(&self, chunk: &[Call], block_id: &str) -> Result<CallResults> {
+        let mut call3s = Vec::with_capacity(chunk.len());
+        for call in chunk {
+            call3s.push(abi::Call3 {
+                target: call.target,
+                allowFailure: true,
+                callData: call.call_data.clone(),
+            });
+        }
+
+        let data = abi::aggregate3Call { calls: call3s }.abi_encode();
+        let response = self
+            .rpc
+            .eth_call_at(self.multicall_address, Bytes::from(data), block_id)
+            .await?;
+        decode_aggregate3(&response)
+    }
+
+    async fn individual_calls(&self, chunk: &[Call], block_id: &str) -> Result<CallResults> {
+        let params = eth_call_params(chunk, block_id);
+        let batch: Vec<(&str, Value)> = params.into_iter().map(|p| ("eth_call", p)).collect();
+        let results = self.rpc.call_batch(&batch).await?;
+        Ok(decode_individual_results(results))
+    }
+}
+
+/// 按 `chunk_size` 切分后并发执行各批次 (最多同时 `concurrency` 个)，结果按原调用顺序拼接；
+/// 批次返回的结果数与调用数不一致时报错，避免后续结果整体错位
+async fn aggregate_chunked<'a, Run, Fut>(
+    calls: &'a [Call],
+    chunk_size: usize,
+    concurrency: usize,
+    run: Run,
+) -> Result<CallResults>
+where
+    Run: Fn(&'a [Call]) -> Fut,
+    Fut: Future<Output = Result<CallResults>>,
+{
+    let chunks: Vec<&'a [Call]> = calls.chunks(chunk_size.max(1)).collect();
+    let batches =
+        concurrency::try_join_all_bounded(chunks.iter().copied().map(&run), concurrency).await?;
+
+    let mut out = Vec::with_capacity(calls.len());
+    for (chunk, results) in chunks.iter().zip(batches) {
+        if results.len() != chunk.len() {
+            return Err(CroLensError::RpcError(format!(
+                "Multicall batch returned {} results for {} calls",
+                results.len(),
+                chunk.len()
+            )));
+        }
+        out.extend(results);
+    }
+    Ok(out)
+}
+
+/// decode_aggregate3 的错误前缀 (should_fallback 据此识别解码失败)
+const MULTICALL_DECODE_FAILED: &str = "Multicall decode failed";
+
+/// 只有 aggregate3 调用本身 revert (地址上不是 Multicall3、节点拒绝执行) 或返回数据无法解码时才回退；
+/// 超时、限流、熔断、配置缺失等错误逐个调用也无法恢复，回退只会放大请求量
+fn should_fallback(err: &CroLensError) -> bool {
+    let CroLensError::RpcError(message) = err else {
+        return false;
+    };
+    message.starts_with(MULTICALL_DECODE_FAILED) || {
+        let message = message.to_ascii_lowercase();
+        message.contains("revert") || message.contains("invalid opcode")
+    }
+}
+
+async fn aggregate_or_fallback<Primary, Fallback, FallbackFut, OnFallback>(
+    primary: Primary,
+    fallback: Fallback,
+    on_fallback: OnFallback,
+) -> Result<CallResults>
+where
+    Primary: Future<Output = Result<CallResults>>,
+    Fallback: FnOnce() -> FallbackFut,
+    FallbackFut: Future<Output = Result<CallResults>>,
+    OnFallback: FnOnce(&CroLensError),
+{
+    match primary.await {
+        Ok(results) => Ok(results),
+        Err(err) if should_fallback(&err) => {
+            on_fallback(&err);
+            fallback().await
+        }
+        Err(err) => Err(err),
+    }
+}
 
-            let data = abi::aggregate3Call { calls: call3s }.abi_encode();
-            let response = self
-                .rpc
-                .eth_call(self.multicall_address, Bytes::from(data))
-                .await?;
-            let decoded = abi::aggregate3Call::abi_decode_returns(&response, true)
-                .map_err(|err| CroLensError::RpcError(format!("Multicall decode failed: {err}")))?;
-
-            for item in decoded.returnData {
-                if item.success {
-                    out.push(Ok(item.returnData));
-                } else {
-                    out.push(Err(CroLensError::RpcError(
-                        "Multicall inner call failed".to_string(),
-                    )));
-                }
+fn decode_aggregate3(response: &[u8]) -> Result<CallResults> {
+    let decoded = abi::aggregate3Call::abi_decode_returns(response, true)
+        .map_err(|err| CroLensError::RpcError(format!("{MULTICALL_DECODE_FAILED}: {err}")))?;
+
+    Ok(decoded
+        .returnData
+        .into_iter()
+        .map(|item| {
+            if item.success {
+                Ok(item.returnData)
+            } else {
+                Err(CroLensError::RpcError(
+                    "Multicall inner call failed".to_string(),
+                ))
             }
+        })
+        .collect())
+}
+
+fn eth_call_params(chunk: &[Call], block_id: &str) -> Vec<Value> {
+    chunk
+        .iter()
+        .map(|call| {
+            serde_json::json!([
+                {
+                    "to": call.target.to_string(),
+                    "data": types::bytes_to_hex0x(&call.call_data)
+                },
+                block_id
+            ])
+        })
+        .collect()
+}
+
+/// 与 Multicall3 allowFailure 语义一致：单个调用失败只影响该项
+fn decode_individual_results(results: Vec<Result<Value>>) -> CallResults {
+    results
+        .into_iter()
+        .map(|item| {
+            let value = item?;
+            types::hex0x_to_bytes(rpc::expect_str("eth_call", &value)?).map(Bytes::from)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+    use std::cell::Cell;
+
+    fn call(byte: u8, data: &[u8]) -> Call {
+        Call {
+            target: Address::repeat_byte(byte),
+            call_data: Bytes::copy_from_slice(data),
         }
+    }
+
+    fn aggregate3_response(items: &[(bool, &[u8])]) -> Vec<u8> {
+        let return_data: Vec<abi::Result> = items
+            .iter()
+            .map(|(success, data)| abi::Result {
+                success: *success,
+                returnData: Bytes::copy_from_slice(data),
+            })
+            .collect();
+        abi::aggregate3Call::abi_encode_returns(&(return_data,))
+    }
+
+    fn summarize(results: &CallResults) -> Vec<Option<Bytes>> {
+        results.iter().map(|r| r.as_ref().ok().cloned()).collect()
+    }
+
+    #[test]
+    fn eth_call_params_match_single_eth_call_shape() {
+        let params = eth_call_params(&[call(0x11, &[0xab, 0xcd])], "latest");
+        assert_eq!(
+            params,
+            vec![serde_json::json!([
+                { "to": Address::repeat_byte(0x11).to_string(), "data": "0xabcd" },
+                "latest"
+            ])]
+        );
+    }
+
+    /// 每个调用的结果是其 target 的首字节，便于检查顺序
+    fn echo_targets(chunk: &[Call]) -> CallResults {
+        chunk
+            .iter()
+            .map(|c| Ok(Bytes::copy_from_slice(&c.target.as_slice()[..1])))
+            .collect()
+    }
+
+    #[test]
+    fn chunked_results_preserve_order_across_boundaries() {
+        let calls: Vec<Call> = (1..=7u8).map(|b| call(b, &[])).collect();
+        let chunk_sizes = std::cell::RefCell::new(Vec::new());
+        let results = aggregate_chunked(&calls, 3, 2, |chunk| {
+            chunk_sizes.borrow_mut().push(chunk.len());
+            async move { Ok(echo_targets(chunk)) }
+        })
+        .now_or_never()
+        .expect("ready")
+        .expect("ok");
+
+        assert_eq!(*chunk_sizes.borrow(), vec![3, 3, 1]);
+        let order: Vec<u8> = results.iter().map(|r| r.as_ref().expect("ok")[0]).collect();
+        assert_eq!(order, (1..=7u8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn chunked_failure_stays_at_its_position() {
+        let calls: Vec<Call> = (1..=4u8).map(|b| call(b, &[])).collect();
+        let results = aggregate_chunked(&calls, 2, 2, |chunk| async move {
+            Ok(chunk
+                .iter()
+                .map(|c| {
+                    if c.target == Address::repeat_byte(3) {
+                        Err(CroLensError::RpcError(
+                            "Multicall inner call failed".to_string(),
+                        ))
+                    } else {
+                        Ok(Bytes::copy_from_slice(&c.target.as_slice()[..1]))
+                    }
+                })
+                .collect())
+        })
+        .now_or_never()
+        .expect("ready")
+        .expect("ok");
+
+        assert_eq!(
+            summarize(&results),
+            vec![
+                Some(Bytes::from(vec![1u8])),
+                Some(Bytes::from(vec![2u8])),
+                None,
+                Some(Bytes::from(vec![4u8])),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunked_rejects_misaligned_batches() {
+        let calls: Vec<Call> = (1..=4u8).map(|b| call(b, &[])).collect();
+        let err = aggregate_chunked(&calls, 2, 2, |chunk| async move {
+            Ok(echo_targets(&chunk[..1]))
+        })
+        .now_or_never()
+        .expect("ready")
+        .unwrap_err();
+        assert!(
+            matches!(err, CroLensError::RpcError(ref m) if m.contains("1 results for 2 calls"))
+        );
+    }
+
+    #[tokio::test]
+    async fn chunked_batches_respect_the_concurrency_limit() {
+        let calls: Vec<Call> = (1..=10u8).map(|b| call(b, &[])).collect();
+        let in_flight = std::cell::Cell::new(0usize);
+        let peak = std::cell::Cell::new(0usize);
+        let results = aggregate_chunked(&calls, 2, 3, |chunk| {
+            let (in_flight, peak) = (&in_flight, &peak);
+            async move {
+                in_flight.set(in_flight.get() + 1);
+                peak.set(peak.get().max(in_flight.get()));
+                tokio::task::yield_now().await;
+                in_flight.set(in_flight.get() - 1);
+                Ok(echo_targets(chunk))
+            }
+        })
+        .await
+        .expect("ok");
+
+        assert_eq!(results.len(), 10);
+        assert_eq!(peak.get(), 3);
+    }
+
+    #[test]
+    fn chunked_empty_input_makes_no_batches() {
+        let results = aggregate_chunked(&[], 100, 2, |_| async { Ok(Vec::new()) })
+            .now_or_never()
+            .expect("ready")
+            .expect("ok");
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn max_calls_per_batch_parses_and_falls_back() {
+        assert_eq!(parse_max_calls_per_batch(None), MAX_CALLS_PER_BATCH_DEFAULT);
+        assert_eq!(parse_max_calls_per_batch(Some(" 250 ")), 250);
+        assert_eq!(
+            parse_max_calls_per_batch(Some("0")),
+            MAX_CALLS_PER_BATCH_DEFAULT
+        );
+        assert_eq!(
+            parse_max_calls_per_batch(Some("5000")),
+            MAX_CALLS_PER_BATCH_DEFAULT
+        );
+        assert_eq!(
+            parse_max_calls_per_batch(Some("abc")),
+            MAX_CALLS_PER_BATCH_DEFAULT
+        );
+    }
+
+    #[test]
+    fn eth_call_params_carry_historical_block() {
+        let params = eth_call_params(&[call(0x11, &[0xab]), call(0x22, &[0xcd])], "0x10");
+        assert_eq!(params.len(), 2);
+        assert!(params.iter().all(|p| p[1] == "0x10"));
+    }
+
+    #[test]
+    fn fallback_results_match_multicall_results() {
+        let multicall = decode_aggregate3(&aggregate3_response(&[
+            (true, &[0x01]),
+            (false, &[]),
+            (true, &[0x02, 0x03]),
+        ]))
+        .expect("decodes");
+
+        let fallback = aggregate_or_fallback(
+            async { Err(CroLensError::RpcError("execution reverted".to_string())) },
+            || async {
+                Ok(decode_individual_results(vec![
+                    Ok(serde_json::json!("0x01")),
+                    Err(CroLensError::RpcError("execution reverted".to_string())),
+                    Ok(serde_json::json!("0x0203")),
+                ]))
+            },
+            |_| {},
+        )
+        .now_or_never()
+        .expect("ready")
+        .expect("fallback succeeds");
+
+        assert_eq!(summarize(&fallback), summarize(&multicall));
+    }
+
+    #[test]
+    fn fallback_is_not_used_when_multicall_succeeds() {
+        let fallback_used = Cell::new(false);
+        let results = aggregate_or_fallback(
+            async { Ok(vec![Ok(Bytes::from(vec![0x01]))]) },
+            || async {
+                fallback_used.set(true);
+                Ok(Vec::new())
+            },
+            |_| {},
+        )
+        .now_or_never()
+        .expect("ready")
+        .expect("multicall succeeds");
+        assert_eq!(results.len(), 1);
+        assert!(!fallback_used.get());
+    }
+
+    #[test]
+    fn fallback_logs_the_multicall_error() {
+        let logged = Cell::new(false);
+        aggregate_or_fallback(
+            async {
+                Err(CroLensError::RpcError(
+                    "Multicall decode failed".to_string(),
+                ))
+            },
+            || async { Ok(Vec::new()) },
+            |err| logged.set(matches!(err, CroLensError::RpcError(_))),
+        )
+        .now_or_never()
+        .expect("ready")
+        .expect("fallback succeeds");
+        assert!(logged.get());
+    }
+
+    fn rpc_error(response: Value) -> CroLensError {
+        rpc::extract_rpc_result(&response).expect_err("error response")
+    }
+
+    #[test]
+    fn falls_back_only_on_revert_or_decode_failures() {
+        // 节点对 aggregate3 返回的 revert (如地址上不是 Multicall3)
+        let reverted = rpc_error(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": 3, "message": "execution reverted", "data": "0x" }
+        }));
+        assert!(should_fallback(&reverted));
+        let opcode = rpc_error(serde_json::json!({
+            "error": { "code": -32000, "message": "invalid opcode: INVALID" }
+        }));
+        assert!(should_fallback(&opcode));
+        // 地址上没有代码时 eth_call 返回空数据，解码失败
+        let empty = decode_aggregate3(&[]).expect_err("empty return data");
+        assert!(should_fallback(&empty));
+
+        // 限流、缺少 result 等错误逐个调用也会失败，不回退
+        let limited = rpc_error(serde_json::json!({
+            "error": { "code": -32005, "message": "daily request count exceeded, request rate limited" }
+        }));
+        assert!(!should_fallback(&limited));
+        let missing = rpc_error(serde_json::json!({ "jsonrpc": "2.0", "id": 1 }));
+        assert!(!should_fallback(&missing));
+        let shape =
+            rpc::expect_str("eth_call", &serde_json::json!(null)).expect_err("not a string");
+        assert!(!should_fallback(&shape));
+    }
+
+    #[test]
+    fn transport_errors_are_returned_without_fallback() {
+        let fallback_used = Cell::new(false);
+        let result = aggregate_or_fallback(
+            async {
+                Err(rpc_error(serde_json::json!({
+                    "error": { "code": 429, "message": "Too Many Requests" }
+                })))
+            },
+            || async {
+                fallback_used.set(true);
+                Ok(Vec::new())
+            },
+            |_| {},
+        )
+        .now_or_never()
+        .expect("ready");
+        assert!(matches!(result, Err(CroLensError::RpcError(_))));
+        assert!(!fallback_used.get());
+    }
+
+    #[test]
+    fn open_circuit_does_not_fall_back() {
+        let result = aggregate_or_fallback(
+            async {
+                Err(CroLensError::service_unavailable(
+                    "RPC circuit open".to_string(),
+                    Some(60),
+                ))
+            },
+            || async { Ok(Vec::new()) },
+            |_| {},
+        )
+        .now_or_never()
+        .expect("ready");
+        assert!(matches!(
+            result,
+            Err(CroLensError::ServiceUnavailable { .. })
+        ));
+    }
+
+    #[test]
+    fn non_string_eth_call_result_is_item_error() {
+        let results = decode_individual_results(vec![Ok(serde_json::json!(null))]);
+        assert!(results[0].is_err());
+    }
+
+    #[test]
+    fn code_present_requires_non_empty_bytecode() {
+        assert!(code_present("0x6080604052"));
+        assert!(code_present(" 0x60 "));
+        assert!(!code_present("0x"));
+        assert!(!code_present(""));
+        assert!(!code_present("0x0000"));
+    }
+
+    #[test]
+    fn code_check_cache_round_trips_and_expires_absent_sooner() {
+        assert_eq!(parse_cached_has_code("1"), Some(true));
+        assert_eq!(parse_cached_has_code("0"), Some(false));
+        assert_eq!(parse_cached_has_code("maybe"), None);
+        assert!(code_check_ttl_secs(false) < code_check_ttl_secs(true));
+    }
+
+    #[test]
+    fn code_check_memo_expires_with_the_kv_ttl() {
+        let present = "test:multicall3:has_code:present";
+        let absent = "test:multicall3:has_code:absent";
+        assert_eq!(memoized_code_check(present, 0), None);
+        remember_code_check(present, true, 0);
+        remember_code_check(absent, false, 0);
+        assert_eq!(memoized_code_check(present, 300_000), Some(true));
+        assert_eq!(memoized_code_check(absent, 299_999), Some(false));
+        assert_eq!(memoized_code_check(absent, 300_000), None);
+        assert_eq!(memoized_code_check(present, 86_400_000), None);
+    }
 
-        Ok(out)
+    #[test]
+    fn multicall3_address_falls_back_to_canonical() {
+        let canonical =
+            types::parse_address(MULTICALL3_CANONICAL_ADDRESS).expect("canonical address");
+        assert_eq!(parse_multicall3_address(None), canonical);
+        assert_eq!(parse_multicall3_address(Some("not an address")), canonical);
+        let custom = Address::from([0x11u8; 20]);
+        assert_eq!(
+            parse_multicall3_address(Some(&format!(" {custom} "))),
+            custom
+        );
     }
 }
@@ -0,0 +1,338 @@
+use alloy_primitives::{Address, Bytes, I256};
+use alloy_sol_types::SolCall;
+use serde_json::Value;
+use worker::Env;
+
+use crate::abi;
+use crate::error::{CroLensError, Result};
+use crate::infra;
+use crate::infra::multicall::Call;
+use crate::infra::token::Token;
+use crate::types;
+
+/// token_oracles 表的 KV 缓存 key 与 TTL
+const TOKEN_ORACLES_CACHE_KEY: &str = "cache:token_oracles";
+const TOKEN_ORACLES_CACHE_TTL_SECS: u64 = 300;
+/// D1 查询失败时缓存空列表的 TTL (KV 允许的最小值)，避免每次定价都重试 D1
+const TOKEN_ORACLES_ERROR_TTL_SECS: u64 = 60;
+
+/// Chainlink 标准 feed 的最长心跳为 24 小时，超过该时长的答案视为过期
+const ORACLE_MAX_AGE_SECS_DEFAULT: u64 = 86_400;
+
+/// 读取 ORACLE_MAX_AGE_SECS；`0` 关闭时效校验，无效值使用默认值
+pub fn oracle_max_age_secs(env: &Env) -> u64 {
+    parse_oracle_max_age_secs(
+        env.var("ORACLE_MAX_AGE_SECS")
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    )
+}
+
+fn parse_oracle_max_age_secs(value: Option<&str>) -> u64 {
+    value
+        .map(str::trim)
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(ORACLE_MAX_AGE_SECS_DEFAULT)
+}
+
+/// Chainlink 风格 (AggregatorV3) 的价格预言机配置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenOracle {
+    pub token_address: Address,
+    pub aggregator_address: Address,
+    /// 预言机答案的小数位 (USD 报价通常为 8)
+    pub decimals: u8,
+}
+
+pub fn token_oracle_from_row(row: &Value) -> Option<TokenOracle> {
+    let token = row.get("token_address").and_then(|v| v.as_str())?;
+    let aggregator = row.get("aggregator_address").and_then(|v| v.as_str())?;
+    let decimals = row.get("decimals").and_then(|v| v.as_u64())?;
+    Some(TokenOracle {
+        token_address: types::parse_address(token).ok()?,
+        aggregator_address: types::parse_address(aggregator).ok()?,
+        decimals: u8::try_from(decimals).ok()?,
+    })
+}
+
+async fn list_token_oracle_rows(services: &infra::Services) -> Result<Vec<Value>> {
+    let statement = services
+        .db
+        .prepare("SELECT token_address, aggregator_address, decimals FROM token_oracles");
+    let result = infra::db::run("list_token_oracles", || statement.all()).await?;
+    result
+        .results()
+        .map_err(|err| CroLensError::DbError(err.to_string()))
+}
+
+/// 读取预言机配置 (原始行在 KV 缓存 5 分钟)；表不存在或查询失败时视为未配置，并缓存 1 分钟
+async fn token_oracles_cached(services: &infra::Services) -> Vec<TokenOracle> {
+    let key = services.kv_key(TOKEN_ORACLES_CACHE_KEY);
+    let cached = services
+        .kv_get_text(&key)
+        .await
+        .and_then(|text| serde_json::from_str::<Vec<Value>>(&text).ok());
+    let rows = match cached {
+        Some(rows) => rows,
+        None => match list_token_oracle_rows(services).await {
+            Ok(rows) => {
+                if let Ok(text) = serde_json::to_string(&rows) {
+                    services
+                        .kv_put_text(&key, text, TOKEN_ORACLES_CACHE_TTL_SECS)
+                        .await;
+                }
+                rows
+            }
+            Err(err) => {
+                worker::console_warn!("[WARN] token_oracles unavailable: {}", err);
+                services
+                    .kv_put_text(&key, "[]".to_string(), TOKEN_ORACLES_ERROR_TTL_SECS)
+                    .await;
+                return Vec::new();
+            }
+        },
+    };
+    rows.iter().filter_map(token_oracle_from_row).collect()
+}
+
+/// 待定价代币中配置了预言机的部分 (保持代币顺序)
+pub fn oracles_for_tokens(oracles: &[TokenOracle], tokens: &[&Token]) -> Vec<TokenOracle> {
+    tokens
+        .iter()
+        .filter_map(|token| {
+            oracles
+                .iter()
+                .find(|oracle| oracle.token_address == token.address)
+                .cloned()
+        })
+        .collect()
+}
+
+/// 把预言机答案按 decimals 缩放为 USD 价格；非正数视为无效
+pub fn oracle_answer_to_price(answer: I256, decimals: u8) -> Option<f64> {
+    if !answer.is_positive() {
+        return None;
+    }
+    let price = types::format_units(&answer.into_raw(), decimals)
+        .parse::<f64>()
+        .ok()?;
+    (price.is_finite() && price > 0.0).then_some(price)
+}
+
+/// 优先使用 latestRoundData().answer (updatedAt 为 0 表示该轮未完成)，否则回退到 latestAnswer()；
+/// 轮次已完成但答案来自更早的轮次 (answeredInRound < roundId) 或超过 max_age_secs 时视为过期，不再回退
+pub fn decode_oracle_answer(
+    round_data: Option<&std::result::Result<Bytes, CroLensError>>,
+    latest_answer: Option<&std::result::Result<Bytes, CroLensError>>,
+    now_secs: u64,
+    max_age_secs: u64,
+) -> Option<I256> {
+    let round = round_data
+        .and_then(|r| r.as_ref().ok())
+        .and_then(|data| abi::latestRoundDataCall::abi_decode_returns(data, true).ok())
+        .filter(|round| !round.updatedAt.is_zero());
+    if let Some(round) = round {
+        if round.answeredInRound < round.roundId {
+            return None;
+        }
+        let updated_at = u64::try_from(round.updatedAt).unwrap_or(u64::MAX);
+        if max_age_secs > 0 && now_secs.saturating_sub(updated_at) > max_age_secs {
+            return None;
+        }
+        return Some(round.answer);
+    }
+    latest_answer
+        .and_then(|r| r.as_ref().ok())
+        .and_then(|data| abi::latestAnswerCall::abi_decode_returns(data, true).ok())
+        .map(|v| v._0)
+}
+
+/// 为配置了预言机的代币读取价格；未配置或 RPC 不可用时返回空列表
+pub async fn fetch_oracle_prices(
+    services: &infra::Services,
+    pending: &[&Token],
+) -> Vec<(Address, f64)> {
+    let oracles = oracles_for_tokens(&token_oracles_cached(services).await, pending);
+    if oracles.is_empty() {
+        return Vec::new();
+    }
+    let Ok(multicall) = services.multicall() else {
+        return Vec::new();
+    };
+    let calls: Vec<Call> = oracles
+        .iter()
+        .flat_map(|oracle| {
+            [
+                Call {
+                    target: oracle.aggregator_address,
+                    call_data: abi::latestRoundDataCall {}.abi_encode().into(),
+                },
+                Call {
+                    target: oracle.aggregator_address,
+                    call_data: abi::latestAnswerCall {}.abi_encode().into(),
+                },
+            ]
+        })
+        .collect();
+    let results = match multicall.aggregate(calls).await {
+        Ok(results) => results,
+        Err(err) => {
+            worker::console_warn!("[WARN] oracle price read failed: {}", err);
+            return Vec::new();
+        }
+    };
+    let now_secs = u64::try_from(types::now_ms() / 1000).unwrap_or_default();
+    oracles
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, oracle)| {
+            let answer = decode_oracle_answer(
+                results.get(idx * 2),
+                results.get(idx * 2 + 1),
+                now_secs,
+                services.oracle_max_age_secs,
+            )?;
+            let price = oracle_answer_to_price(answer, oracle.decimals)?;
+            Some((oracle.token_address, price))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::U256;
+
+    fn addr(byte: u8) -> Address {
+        Address::from([byte; 20])
+    }
+
+    fn token(byte: u8) -> Token {
+        Token {
+            address: addr(byte),
+            symbol: format!("T{byte}"),
+            decimals: 18,
+            is_stablecoin: false,
+            is_spam: false,
+        }
+    }
+
+    type CallResult = Option<std::result::Result<Bytes, CroLensError>>;
+
+    fn answer(value: i64) -> I256 {
+        I256::try_from(value).expect("fits")
+    }
+
+    #[test]
+    fn scales_answer_by_oracle_decimals() {
+        // 8 位小数: 9_876_543 -> 0.09876543
+        assert_eq!(
+            oracle_answer_to_price(answer(9_876_543), 8),
+            Some(0.09876543)
+        );
+        assert_eq!(
+            oracle_answer_to_price(answer(2_500_000_000_000_000_000), 18),
+            Some(2.5)
+        );
+        assert_eq!(oracle_answer_to_price(answer(42), 0), Some(42.0));
+    }
+
+    #[test]
+    fn rejects_non_positive_answers() {
+        assert_eq!(oracle_answer_to_price(I256::ZERO, 8), None);
+        assert_eq!(oracle_answer_to_price(answer(-1), 8), None);
+    }
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn round_at(answer_value: i64, updated_at: u64, answered_in_round: u128) -> CallResult {
+        Some(Ok(Bytes::from(
+            abi::latestRoundDataCall::abi_encode_returns(&(
+                10u128,
+                answer(answer_value),
+                U256::from(1u64),
+                U256::from(updated_at),
+                answered_in_round,
+            )),
+        )))
+    }
+
+    #[test]
+    fn prefers_round_data_and_falls_back_to_latest_answer() {
+        let round = |answer_value: i64, updated_at: u64| round_at(answer_value, updated_at, 10);
+        let latest: CallResult = Some(Ok(Bytes::from(abi::latestAnswerCall::abi_encode_returns(
+            &(answer(7),),
+        ))));
+        let reverted: CallResult = Some(Err(CroLensError::RpcError("revert".to_string())));
+        let decode = |round: &CallResult, latest: &CallResult| {
+            decode_oracle_answer(round.as_ref(), latest.as_ref(), NOW, 3_600)
+        };
+
+        assert_eq!(decode(&round(5, NOW), &latest), Some(answer(5)));
+        // updatedAt 为 0 的轮次未完成，改用 latestAnswer
+        assert_eq!(decode(&round(5, 0), &latest), Some(answer(7)));
+        assert_eq!(decode(&reverted, &latest), Some(answer(7)));
+        assert_eq!(decode(&reverted, &reverted), None);
+    }
+
+    #[test]
+    fn rejects_stale_or_carried_over_rounds() {
+        let latest: CallResult = Some(Ok(Bytes::from(abi::latestAnswerCall::abi_encode_returns(
+            &(answer(7),),
+        ))));
+        let decode = |round: &CallResult, max_age: u64| {
+            decode_oracle_answer(round.as_ref(), latest.as_ref(), NOW, max_age)
+        };
+
+        // 超过 max_age 的答案不可用，也不回退到同样过期的 latestAnswer
+        assert_eq!(decode(&round_at(5, NOW - 3_601, 10), 3_600), None);
+        assert_eq!(
+            decode(&round_at(5, NOW - 3_600, 10), 3_600),
+            Some(answer(5))
+        );
+        // max_age 为 0 时不校验时效
+        assert_eq!(decode(&round_at(5, NOW - 999_999, 10), 0), Some(answer(5)));
+        // answeredInRound < roundId: 答案沿用了更早的轮次
+        assert_eq!(decode(&round_at(5, NOW, 9), 3_600), None);
+    }
+
+    #[test]
+    fn max_age_parsing() {
+        assert_eq!(parse_oracle_max_age_secs(None), ORACLE_MAX_AGE_SECS_DEFAULT);
+        assert_eq!(parse_oracle_max_age_secs(Some(" 600 ")), 600);
+        assert_eq!(parse_oracle_max_age_secs(Some("0")), 0);
+        assert_eq!(
+            parse_oracle_max_age_secs(Some("-5")),
+            ORACLE_MAX_AGE_SECS_DEFAULT
+        );
+    }
+
+    #[test]
+    fn tokens_without_oracle_are_not_matched() {
+        let oracles = vec![TokenOracle {
+            token_address: addr(2),
+            aggregator_address: addr(9),
+            decimals: 8,
+        }];
+        let (with_oracle, without_oracle) = (token(2), token(3));
+        let matched = oracles_for_tokens(&oracles, &[&without_oracle, &with_oracle]);
+        assert_eq!(matched, oracles);
+        assert!(oracles_for_tokens(&oracles, &[&without_oracle]).is_empty());
+        assert!(oracles_for_tokens(&[], &[&with_oracle]).is_empty());
+    }
+
+    #[test]
+    fn parses_oracle_rows() {
+        let row = serde_json::json!({
+            "token_address": format!("0x{}", "02".repeat(20)),
+            "aggregator_address": format!("0x{}", "09".repeat(20)),
+            "decimals": 8
+        });
+        let oracle = token_oracle_from_row(&row).expect("row");
+        assert_eq!(oracle.token_address, addr(2));
+        assert_eq!(oracle.decimals, 8);
+        let bad = serde_json::json!({ "token_address": "nope", "aggregator_address": "0x", "decimals": 8 });
+        assert!(token_oracle_from_row(&bad).is_none());
+    }
+}
@@ -1,162 +1,594 @@
 use std::collections::HashMap;
 
-use alloy_primitives::{Address, U256};
+use alloy_primitives::{Address, Bytes, U256};
 use alloy_sol_types::SolCall;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use worker::kv::KvStore;
-use worker::Env;
+use std::time::Duration;
+use worker::{Delay, Env};
 
 use crate::abi;
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::kv_stats::TrackedKv;
+use crate::infra::kv_store::KvTextStore;
 use crate::infra::multicall::Call;
+use crate::infra::structured_log::{LogEntry, LogLevel};
 use crate::infra::token::Token;
 use crate::types;
 
 /// 所有价格的聚合缓存 key
 const ALL_PRICES_CACHE_KEY: &str = "cache:prices:all";
+/// 聚合缓存读取超时；KV 变慢时跳到逐个代币的来源，不阻塞所有需要价格的工具
+const ALL_PRICES_CACHE_READ_TIMEOUT_MS: u64 = 500;
 
 /// 价格缓存结构
 #[derive(Serialize, Deserialize)]
 struct PriceCache {
     // address (lowercase) -> price_usd
     prices: HashMap<String, f64>,
+    // 写入时间 (旧缓存没有该字段)
+    #[serde(default)]
+    fetched_ms: Option<i64>,
+    // 写入时 DB 中的 anchor 代币地址 (lowercase，旧缓存没有该字段)
+    #[serde(default)]
+    anchors: Option<Vec<String>>,
+    // 本轮被合理性校验拒绝、沿用上一轮价格的代币
+    #[serde(default)]
+    rejected: RejectedPrices,
 }
 
-/// 批量获取多个代币的 USD 价格
-/// 优化：从单个 KV key 读取所有价格，而不是多次 KV 查询
-pub async fn get_prices_usd_batch(
-    services: &infra::Services,
-    tokens: &[Token],
-) -> Result<HashMap<Address, f64>> {
-    let mut result = HashMap::with_capacity(tokens.len());
+/// 被拒绝的 derived 价格：沿用的旧价格保留原始抓取时间，并记录连续拒绝次数
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct RejectedPrices {
+    // address (lowercase) -> 沿用价格的原始抓取时间
+    fetched_ms: HashMap<String, i64>,
+    // address (lowercase) -> 连续拒绝次数
+    counts: HashMap<String, u32>,
+}
+
+/// 缓存中没有有效价格的 anchor 代币数量
+fn missing_anchors(cache: &PriceCache) -> usize {
+    cache.anchors.as_deref().map_or(0, |anchors| {
+        anchors
+            .iter()
+            .filter(|addr| !cache.prices.get(*addr).is_some_and(|&p| is_valid_price(p)))
+            .count()
+    })
+}
+
+/// 聚合缓存可解析但明显不完整 (部分同步写入的空 map、缺少某个 anchor 的价格) 时不可信
+fn price_cache_incomplete(cache: &PriceCache) -> bool {
+    let entries = cache
+        .prices
+        .values()
+        .filter(|&&p| is_valid_price(p))
+        .count();
+    entries == 0 || missing_anchors(cache) > 0
+}
+
+/// derived 价格与参考价格 (上一轮价格) 偏离超过该倍数时拒绝写入
+const PRICE_SANITY_MULTIPLE_DEFAULT: f64 = 10.0;
+/// 连续被拒绝达到该次数后接受新价格 (cron 每 5 分钟一轮，约 15 分钟)，真实的大幅波动不会被永久挡住
+const PRICE_SANITY_MAX_REJECTIONS: u32 = 3;
+
+/// 读取 PRICE_SANITY_MAX_MULTIPLE；`0` 关闭校验，无效值或不大于 1 的值使用默认值
+pub fn price_sanity_multiple(env: &Env) -> Option<f64> {
+    parse_price_sanity_multiple(
+        env.var("PRICE_SANITY_MAX_MULTIPLE")
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    )
+}
+
+fn parse_price_sanity_multiple(value: Option<&str>) -> Option<f64> {
+    match value.map(str::trim).and_then(|v| v.parse::<f64>().ok()) {
+        Some(0.0) => None,
+        Some(v) if v.is_finite() && v > 1.0 => Some(v),
+        _ => Some(PRICE_SANITY_MULTIPLE_DEFAULT),
+    }
+}
 
-    // 1. 先处理稳定币
-    for token in tokens {
-        if token.is_stablecoin {
-            result.insert(token.address, 1.0);
+/// 价格来源；按 PRICE_SOURCE_PRIORITY 的顺序依次查询，第一个有效价格胜出
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PriceSource {
+    /// 稳定币固定为 1 USD
+    Stablecoin,
+    /// token_oracles 中配置的 Chainlink 风格预言机 (latestRoundData / latestAnswer)
+    Oracle,
+    /// 定时任务写入的聚合缓存 (cache:prices:all)
+    Cache,
+    /// CoinGecko 锚定价格 (price:anchor:{symbol})
+    Anchor,
+    /// 链上池子推导价格 (price:derived:{address})
+    Derived,
+}
+
+impl PriceSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PriceSource::Stablecoin => "stablecoin",
+            PriceSource::Oracle => "oracle",
+            PriceSource::Cache => "cache",
+            PriceSource::Anchor => "anchor",
+            PriceSource::Derived => "derived",
         }
     }
 
-    // 2. 尝试从聚合缓存读取所有价格 (单次 KV 读取)
-    let t0 = crate::types::now_ms();
-    if let Ok(Some(cached)) = services.kv.get(ALL_PRICES_CACHE_KEY).text().await {
-        let t1 = crate::types::now_ms();
-        if let Ok(cache) = serde_json::from_str::<PriceCache>(&cached) {
-            for token in tokens {
-                if result.contains_key(&token.address) {
-                    continue; // 已经是稳定币
-                }
-                let addr_key = token.address.to_string().to_lowercase();
-                if let Some(&price) = cache.prices.get(&addr_key) {
-                    result.insert(token.address, price);
-                }
-            }
-            // 如果所有代币都找到了价格，直接返回
-            if result.len() == tokens.len() {
-                worker::console_log!("[PERF] price cache HIT: {}ms, {} prices", t1 - t0, result.len());
-                return Ok(result);
-            }
-            worker::console_log!("[PERF] price cache PARTIAL: {}ms, {}/{} prices", t1 - t0, result.len(), tokens.len());
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "stablecoin" => Some(PriceSource::Stablecoin),
+            "oracle" => Some(PriceSource::Oracle),
+            "cache" => Some(PriceSource::Cache),
+            "anchor" => Some(PriceSource::Anchor),
+            "derived" => Some(PriceSource::Derived),
+            _ => None,
         }
+    }
+}
+
+/// 预言机排在聚合缓存之前：聚合缓存对每个有池子的代币都有池子推导的价格，
+/// 放在前面会让配置的预言机只在缓存未命中时生效。只有 token_oracles 中配置的代币才会发起预言机 RPC
+const PRICE_SOURCE_PRIORITY_DEFAULT: [PriceSource; 5] = [
+    PriceSource::Stablecoin,
+    PriceSource::Oracle,
+    PriceSource::Cache,
+    PriceSource::Anchor,
+    PriceSource::Derived,
+];
+
+/// 读取 PRICE_SOURCE_PRIORITY (逗号分隔)；未列出的来源不再查询
+pub fn price_source_priority(env: &Env) -> Vec<PriceSource> {
+    parse_price_source_priority(
+        env.var("PRICE_SOURCE_PRIORITY")
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    )
+}
+
+/// 忽略未知名称与重复项；结果为空时使用默认顺序
+fn parse_price_source_priority(value: Option<&str>) -> Vec<PriceSource> {
+    let mut priority = Vec::new();
+    for source in value
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(PriceSource::parse)
+    {
+        if !priority.contains(&source) {
+            priority.push(source);
+        }
+    }
+    if priority.is_empty() {
+        PRICE_SOURCE_PRIORITY_DEFAULT.to_vec()
     } else {
-        let t1 = crate::types::now_ms();
-        worker::console_log!("[PERF] price cache MISS: {}ms", t1 - t0);
+        priority
     }
+}
+
+/// 池子报价侧流动性 (USD) 低于该值时不用于定价，避免薄池子产生离谱价格
+const PRICE_MIN_LIQUIDITY_USD_DEFAULT: f64 = 1_000.0;
+
+/// 读取 PRICE_MIN_LIQUIDITY_USD；`0` 关闭阈值，无效或负值使用默认值
+pub fn price_min_liquidity_usd(env: &Env) -> f64 {
+    parse_price_min_liquidity_usd(
+        env.var("PRICE_MIN_LIQUIDITY_USD")
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    )
+}
+
+fn parse_price_min_liquidity_usd(value: Option<&str>) -> f64 {
+    match value.map(str::trim).and_then(|v| v.parse::<f64>().ok()) {
+        Some(v) if v.is_finite() && v >= 0.0 => v,
+        _ => PRICE_MIN_LIQUIDITY_USD_DEFAULT,
+    }
+}
+
+/// 单个池子对目标代币的报价 (已按 decimals 换算)
+#[derive(Debug, Clone, Copy)]
+struct PoolQuote {
+    lp_address: Address,
+    token_amount: f64,
+    quote_amount: f64,
+    quote_price_usd: f64,
+}
 
-    // 3. 聚合缓存未命中或不完整，回退到原来的多次 KV 查询
-    let mut anchor_queries: Vec<(Address, String)> = Vec::new();
-    let mut derived_queries: Vec<(Address, String)> = Vec::new();
+impl PoolQuote {
+    fn quote_liquidity_usd(&self) -> f64 {
+        self.quote_amount * self.quote_price_usd
+    }
+}
 
-    for token in tokens {
-        if result.contains_key(&token.address) {
+/// 按顺序取第一个流动性达标的池子计算价格；同时返回因流动性不足被跳过的池子 (lp, liquidity_usd)
+fn price_from_pool_quotes(
+    quotes: &[PoolQuote],
+    min_liquidity_usd: f64,
+) -> (Option<f64>, Vec<(Address, f64)>) {
+    let mut thin = Vec::new();
+    for quote in quotes {
+        if quote.token_amount <= 0.0 || quote.quote_amount <= 0.0 {
+            continue;
+        }
+        let liquidity_usd = quote.quote_liquidity_usd();
+        if !liquidity_usd.is_finite() || liquidity_usd < min_liquidity_usd {
+            thin.push((quote.lp_address, liquidity_usd));
             continue;
         }
-        anchor_queries.push((token.address, normalize_anchor_symbol(&token.symbol)));
-        let addr_key = token.address.to_string().to_lowercase();
-        derived_queries.push((token.address, format!("price:derived:{addr_key}")));
-    }
-
-    // 并行查询所有 anchor 价格
-    let anchor_futures = anchor_queries.iter().map(|(_, symbol)| {
-        let key = format!("price:anchor:{symbol}");
-        let kv = &services.kv;
-        async move {
-            kv.get(&key)
-                .text()
-                .await
-                .ok()
-                .flatten()
-                .and_then(|t| t.parse::<f64>().ok())
+        let price = quote.quote_price_usd * (quote.quote_amount / quote.token_amount);
+        if price.is_finite() && price > 0.0 {
+            return (Some(price), thin);
         }
-    });
+    }
+    (None, thin)
+}
 
-    let anchor_results: Vec<Option<f64>> =
-        futures_util::future::join_all(anchor_futures).await;
+fn log_thin_pools(trace_id: &str, thin: &[(Address, f64)], min_liquidity_usd: f64) {
+    for (lp_address, liquidity_usd) in thin {
+        let message = format!("Skipping thin pool {lp_address} for derived price");
+        let reason = format!("quote liquidity ${liquidity_usd:.2} below ${min_liquidity_usd:.2}");
+        LogEntry::new(LogLevel::Warn, trace_id, &message)
+            .with_error(-32500, &reason)
+            .emit();
+    }
+}
 
-    for ((addr, _), price) in anchor_queries.iter().zip(anchor_results.into_iter()) {
-        if let Some(p) = price {
-            result.insert(*addr, p);
-        }
+/// 新价格与参考价格的偏离倍数在 `max_multiple` 以内 (双向) 时接受；无参考价格或关闭校验时总是接受
+fn within_sanity_bounds(candidate: f64, reference: Option<f64>, max_multiple: Option<f64>) -> bool {
+    let (Some(reference), Some(max_multiple)) = (reference, max_multiple) else {
+        return true;
+    };
+    if !reference.is_finite() || reference <= 0.0 {
+        return true;
     }
+    let ratio = if candidate >= reference {
+        candidate / reference
+    } else {
+        reference / candidate
+    };
+    ratio <= max_multiple
+}
+
+/// 通过校验时返回新价格；被拒绝时沿用参考价格 (拒绝意味着参考价格有效)，
+/// 保证下一轮仍有参考点，不会在没有参考时直接接受异常值。第二项表示是否被拒绝。
+/// `prior_rejections` 是此前的连续拒绝次数，本次拒绝将达到 PRICE_SANITY_MAX_REJECTIONS 时改为接受
+fn sanity_checked_price(
+    candidate: f64,
+    reference: Option<f64>,
+    max_multiple: Option<f64>,
+    prior_rejections: u32,
+) -> (f64, bool) {
+    if within_sanity_bounds(candidate, reference, max_multiple)
+        || prior_rejections.saturating_add(1) >= PRICE_SANITY_MAX_REJECTIONS
+    {
+        return (candidate, false);
+    }
+    (reference.unwrap_or(candidate), true)
+}
+
+fn log_rejected_price(trace_id: &str, token: &str, candidate: f64, reference: Option<f64>) {
+    let message = format!("Rejecting derived price for {token}");
+    let reason = format!(
+        "derived {candidate} deviates from reference {}",
+        reference.unwrap_or_default()
+    );
+    LogEntry::new(LogLevel::Warn, trace_id, &message)
+        .with_error(-32500, &reason)
+        .emit();
+}
+
+/// 同一请求内多个代币共用一次聚合缓存读取作为参考价格
+async fn price_references(services: &infra::Services) -> &HashMap<String, f64> {
+    if let Some(prices) = services.price_references.get() {
+        return prices;
+    }
+    let prices = read_price_cache(&services.kv, &services.kv_prefix)
+        .await
+        .map(|cache| cache.prices)
+        .unwrap_or_default();
+    services.price_references.get_or_init(|| prices)
+}
+
+/// 读取上一轮聚合缓存，其中的价格作为 derived 价格的参考点
+async fn read_price_cache<S: KvTextStore + ?Sized>(kv: &S, kv_prefix: &str) -> Option<PriceCache> {
+    kv.get_text(&infra::kv_key(kv_prefix, ALL_PRICES_CACHE_KEY))
+        .await
+        .ok()
+        .flatten()
+        .and_then(|text| serde_json::from_str::<PriceCache>(&text).ok())
+}
+
+/// 批量价格结果，附带每个代币价格的抓取时间 (仅聚合缓存命中时已知) 与来源
+pub struct PriceBatch {
+    pub prices: HashMap<Address, f64>,
+    pub fetched_ms: HashMap<Address, i64>,
+    pub sources: HashMap<Address, PriceSource>,
+}
+
+/// 批量获取多个代币的 USD 价格
+pub async fn get_prices_usd_batch(
+    services: &infra::Services,
+    tokens: &[Token],
+) -> Result<HashMap<Address, f64>> {
+    Ok(get_prices_usd_batch_with_age(services, tokens)
+        .await?
+        .prices)
+}
+
+/// 批量获取多个代币的 USD 价格及其抓取时间
+/// 按 `services.price_source_priority` 依次查询各来源，只为尚未定价的代币查询下一来源
+pub async fn get_prices_usd_batch_with_age(
+    services: &infra::Services,
+    tokens: &[Token],
+) -> Result<PriceBatch> {
+    let batch = resolve_by_priority(
+        &services.price_source_priority,
+        tokens,
+        |source, pending| fetch_source_prices(services, source, pending),
+    )
+    .await;
+    Ok(batch)
+}
+
+/// 单个来源返回的价格 (fetched_ms 仅聚合缓存已知)
+#[derive(Debug, Default)]
+struct SourcePrices {
+    prices: Vec<(Address, f64)>,
+    fetched_ms: Option<i64>,
+    // 聚合缓存中沿用旧价格的代币，覆盖 fetched_ms
+    carried_fetched_ms: HashMap<Address, i64>,
+}
 
-    // 对于还没找到价格的代币，查询 derived 缓存
-    let derived_futures = derived_queries.iter().map(|(addr, key)| {
-        let already_found = result.contains_key(addr);
-        let key = key.clone();
-        let kv = &services.kv;
-        async move {
-            if already_found {
-                return None;
+/// 依优先级逐个来源查询，每个代币取第一个有效价格并记录来源
+async fn resolve_by_priority<'a, F, Fut>(
+    priority: &[PriceSource],
+    tokens: &'a [Token],
+    mut fetch: F,
+) -> PriceBatch
+where
+    F: FnMut(PriceSource, Vec<&'a Token>) -> Fut,
+    Fut: std::future::Future<Output = SourcePrices>,
+{
+    let mut batch = PriceBatch {
+        prices: HashMap::with_capacity(tokens.len()),
+        fetched_ms: HashMap::new(),
+        sources: HashMap::with_capacity(tokens.len()),
+    };
+    for &source in priority {
+        let pending: Vec<&Token> = tokens
+            .iter()
+            .filter(|token| !batch.prices.contains_key(&token.address))
+            .collect();
+        if pending.is_empty() {
+            break;
+        }
+        let found = fetch(source, pending).await;
+        for (address, price) in found.prices {
+            if !is_valid_price(price) || batch.prices.contains_key(&address) {
+                continue;
+            }
+            batch.prices.insert(address, price);
+            batch.sources.insert(address, source);
+            let fetched_ms = found.carried_fetched_ms.get(&address).copied();
+            if let Some(ts) = fetched_ms.or(found.fetched_ms) {
+                batch.fetched_ms.insert(address, ts);
             }
-            kv.get(&key)
-                .text()
-                .await
-                .ok()
-                .flatten()
-                .and_then(|t| t.parse::<f64>().ok())
         }
-    });
+    }
+    batch
+}
 
-    let derived_results: Vec<Option<f64>> =
-        futures_util::future::join_all(derived_futures).await;
+fn is_valid_price(price: f64) -> bool {
+    price.is_finite() && price > 0.0
+}
 
-    for ((addr, _), price) in derived_queries.iter().zip(derived_results.into_iter()) {
-        if !result.contains_key(addr) {
-            if let Some(p) = price {
-                result.insert(*addr, p);
-            }
+async fn fetch_source_prices(
+    services: &infra::Services,
+    source: PriceSource,
+    pending: Vec<&Token>,
+) -> SourcePrices {
+    match source {
+        PriceSource::Stablecoin => SourcePrices {
+            prices: pending
+                .iter()
+                .filter(|token| token.is_stablecoin)
+                .map(|token| (token.address, 1.0))
+                .collect(),
+            fetched_ms: None,
+            carried_fetched_ms: HashMap::new(),
+        },
+        PriceSource::Oracle => SourcePrices {
+            prices: infra::oracle::fetch_oracle_prices(services, &pending).await,
+            fetched_ms: None,
+            carried_fetched_ms: HashMap::new(),
+        },
+        PriceSource::Cache => cached_source_prices(services, &pending).await,
+        PriceSource::Anchor => {
+            // 并行查询 anchor 价格 (并发数受 MAX_CONCURRENT_SUBREQUESTS 限制)
+            let futures = pending.iter().map(|token| {
+                let symbol = normalize_anchor_symbol(&token.symbol);
+                let key = services.kv_key(&format!("price:anchor:{symbol}"));
+                async move { read_kv_price(services, &key).await }
+            });
+            let results =
+                infra::concurrency::join_all_bounded(futures, services.max_concurrent_subrequests)
+                    .await;
+            zip_found(&pending, results)
+        }
+        PriceSource::Derived => {
+            let futures = pending.iter().map(|token| {
+                let addr_key = token.address.to_string().to_lowercase();
+                let key = services.kv_key(&format!("price:derived:{addr_key}"));
+                async move { read_kv_price(services, &key).await }
+            });
+            let results =
+                infra::concurrency::join_all_bounded(futures, services.max_concurrent_subrequests)
+                    .await;
+            zip_found(&pending, results)
         }
     }
+}
 
-    Ok(result)
+#[derive(Debug, PartialEq)]
+enum CacheRead {
+    Hit(String),
+    Miss,
+    TimedOut,
 }
 
-pub async fn get_price_usd(services: &infra::Services, token: &Token) -> Result<Option<f64>> {
-    if token.is_stablecoin {
-        return Ok(Some(1.0));
+/// 读取与超时竞争；超时时放弃本次读取
+async fn read_cache_within<R, T>(read: R, timeout: T) -> CacheRead
+where
+    R: std::future::Future<Output = Option<String>>,
+    T: std::future::Future<Output = ()>,
+{
+    match infra::concurrency::race_timeout(read, timeout).await {
+        Some(Some(raw)) => CacheRead::Hit(raw),
+        Some(None) => CacheRead::Miss,
+        None => CacheRead::TimedOut,
     }
+}
+
+/// 聚合缓存读取的结果，由调用方记录日志
+#[derive(Debug, PartialEq)]
+enum CacheOutcome {
+    Hit,
+    Miss,
+    TimedOut,
+    Invalid,
+    Incomplete {
+        entries: usize,
+        missing_anchors: usize,
+    },
+}
 
-    if let Some(anchor) = get_anchor_price_usd(&services.kv, &token.symbol).await? {
-        return Ok(Some(anchor));
+/// 从聚合缓存读取所有价格 (单次 KV 读取)
+async fn cached_source_prices(services: &infra::Services, pending: &[&Token]) -> SourcePrices {
+    let t0 = crate::types::now_ms();
+    let (outcome, prices) = fetch_cached_prices(
+        &services.kv,
+        &services.kv_key(ALL_PRICES_CACHE_KEY),
+        Delay::from(Duration::from_millis(ALL_PRICES_CACHE_READ_TIMEOUT_MS)),
+        pending,
+    )
+    .await;
+    let elapsed_ms = crate::types::now_ms() - t0;
+    match outcome {
+        CacheOutcome::Hit => worker::console_log!(
+            "[PERF] price cache read: {}ms, {}/{} prices",
+            elapsed_ms,
+            prices.prices.len(),
+            pending.len()
+        ),
+        CacheOutcome::Miss => worker::console_log!("[PERF] price cache MISS: {}ms", elapsed_ms),
+        CacheOutcome::TimedOut => worker::console_warn!(
+            "[WARN] price cache read timed out after {}ms, falling back to per-token reads",
+            ALL_PRICES_CACHE_READ_TIMEOUT_MS
+        ),
+        CacheOutcome::Invalid => {}
+        CacheOutcome::Incomplete {
+            entries,
+            missing_anchors,
+        } => worker::console_warn!(
+            "[WARN] price cache incomplete ({} entries, {} anchors missing), falling back to per-token reads",
+            entries,
+            missing_anchors
+        ),
     }
+    prices
+}
 
-    let addr_key = token.address.to_string().to_lowercase();
-    let derived_key = format!("price:derived:{addr_key}");
-    if let Some(text) = services
-        .kv
-        .get(&derived_key)
-        .text()
+/// 在 `timeout` 内从 `store` 读取聚合缓存并取出待定价代币的价格；
+/// 未命中、超时、无法解析或不完整时返回空结果，由后续来源逐个代币读取
+async fn fetch_cached_prices<S, T>(
+    store: &S,
+    key: &str,
+    timeout: T,
+    pending: &[&Token],
+) -> (CacheOutcome, SourcePrices)
+where
+    S: KvTextStore + ?Sized,
+    T: std::future::Future<Output = ()>,
+{
+    let read = read_cache_within(async { store.get_text(key).await.ok().flatten() }, timeout).await;
+    let cached = match read {
+        CacheRead::Hit(raw) => raw,
+        CacheRead::Miss => return (CacheOutcome::Miss, SourcePrices::default()),
+        CacheRead::TimedOut => return (CacheOutcome::TimedOut, SourcePrices::default()),
+    };
+    let Ok(cache) = serde_json::from_str::<PriceCache>(&cached) else {
+        return (CacheOutcome::Invalid, SourcePrices::default());
+    };
+    if price_cache_incomplete(&cache) {
+        // 丢弃整个缓存，由后续来源 (anchor/derived) 逐个代币读取
+        let outcome = CacheOutcome::Incomplete {
+            entries: cache.prices.len(),
+            missing_anchors: missing_anchors(&cache),
+        };
+        return (outcome, SourcePrices::default());
+    }
+    let prices: Vec<(Address, f64)> = pending
+        .iter()
+        .filter_map(|token| {
+            let addr_key = token.address.to_string().to_lowercase();
+            cache
+                .prices
+                .get(&addr_key)
+                .map(|&price| (token.address, price))
+        })
+        .collect();
+    let carried_fetched_ms = pending
+        .iter()
+        .filter_map(|token| {
+            let addr_key = token.address.to_string().to_lowercase();
+            cache
+                .rejected
+                .fetched_ms
+                .get(&addr_key)
+                .map(|&ts| (token.address, ts))
+        })
+        .collect();
+    let prices = SourcePrices {
+        prices,
+        fetched_ms: cache.fetched_ms,
+        carried_fetched_ms,
+    };
+    (CacheOutcome::Hit, prices)
+}
+
+async fn read_kv_price(services: &infra::Services, key: &str) -> Option<f64> {
+    services
+        .kv_get_text(key)
         .await
-        .map_err(|err| CroLensError::KvError(err.to_string()))?
-    {
-        let parsed = text.parse::<f64>().map_err(|err| {
-            CroLensError::KvError(format!("Invalid KV price for {derived_key}: {err}"))
-        })?;
-        return Ok(Some(parsed));
+        .and_then(|t| t.parse::<f64>().ok())
+}
+
+fn zip_found(pending: &[&Token], results: Vec<Option<f64>>) -> SourcePrices {
+    SourcePrices {
+        prices: pending
+            .iter()
+            .zip(results)
+            .filter_map(|(token, price)| price.map(|p| (token.address, p)))
+            .collect(),
+        fetched_ms: None,
+        carried_fetched_ms: HashMap::new(),
     }
+}
 
+/// 单个代币的 USD 价格；各 KV 来源都没有价格且优先级包含 derived 时，实时从池子推导
+pub async fn get_price_usd(services: &infra::Services, token: &Token) -> Result<Option<f64>> {
+    let batch = get_prices_usd_batch_with_age(services, std::slice::from_ref(token)).await?;
+    if let Some(price) = batch.prices.get(&token.address) {
+        return Ok(Some(*price));
+    }
+    if !services
+        .price_source_priority
+        .contains(&PriceSource::Derived)
+    {
+        return Ok(None);
+    }
     derive_price_from_pool(services, token.address).await
 }
 
@@ -164,14 +596,16 @@ pub async fn update_anchor_prices(env: &Env) -> Result<()> {
     let db = env
         .d1("DB")
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
-    let kv = env
-        .kv("KV")
-        .map_err(|err| CroLensError::KvError(err.to_string()))?;
+    let kv = TrackedKv::untracked(
+        env.kv("KV")
+            .map_err(|err| CroLensError::KvError(err.to_string()))?,
+    );
+    let kv_prefix = infra::kv_prefix(env);
 
     let statement = db.prepare(
         "SELECT symbol, coingecko_id FROM tokens WHERE is_anchor = 1 AND coingecko_id IS NOT NULL",
     );
-    let result = infra::db::run("update_anchor_prices_select", statement.all()).await?;
+    let result = infra::db::run("update_anchor_prices_select", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -241,14 +675,11 @@ pub async fn update_anchor_prices(env: &Env) -> Result<()> {
             continue;
         };
 
-        let key = format!("price:anchor:{symbol}");
+        let key = infra::kv_key(&kv_prefix, &format!("price:anchor:{symbol}"));
         worker::console_log!("[DEBUG] Writing anchor price: {} = {}", key, price_usd);
-        kv.put(&key, price_usd.to_string())
-            .map_err(|err| CroLensError::KvError(err.to_string()))?
-            .expiration_ttl(900) // 15 分钟，比 cron 间隔 (5分钟) 长，确保缓存不会过期
-            .execute()
-            .await
-            .map_err(|err| CroLensError::KvError(err.to_string()))?;
+        // 15 分钟，比 cron 间隔 (5分钟) 长，确保缓存不会过期
+        kv.put_text_with_ttl(&key, price_usd.to_string(), 900)
+            .await?;
         write_count += 1;
     }
 
@@ -263,21 +694,32 @@ pub async fn update_derived_prices(env: &Env) -> Result<()> {
     let db = env
         .d1("DB")
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
-    let kv = env
-        .kv("KV")
-        .map_err(|err| CroLensError::KvError(err.to_string()))?;
+    let kv = TrackedKv::untracked(
+        env.kv("KV")
+            .map_err(|err| CroLensError::KvError(err.to_string()))?,
+    );
+    let kv_prefix = infra::kv_prefix(env);
 
     // 聚合价格缓存：收集所有价格
     let mut all_prices: HashMap<String, f64> = HashMap::new();
+    // 上一轮的价格作为 derived 价格的合理性参考
+    let previous = read_price_cache(&kv, &kv_prefix).await;
+    let mut rejected = RejectedPrices::default();
+    let sanity_multiple = price_sanity_multiple(env);
+    let min_liquidity_usd = price_min_liquidity_usd(env);
 
     // 1. 获取所有 anchor 代币价格
-    let anchor_stmt = db.prepare(
-        "SELECT address, symbol FROM tokens WHERE is_anchor = 1",
-    );
-    let anchor_result = infra::db::run("update_derived_anchor_select", anchor_stmt.all()).await?;
+    let anchor_stmt = db.prepare("SELECT address, symbol FROM tokens WHERE is_anchor = 1");
+    let anchor_result =
+        infra::db::run("update_derived_anchor_select", || anchor_stmt.all()).await?;
     let anchor_rows: Vec<Value> = anchor_result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
+    let anchor_addresses: Vec<String> = anchor_rows
+        .iter()
+        .filter_map(|row| row.get("address").and_then(|v| v.as_str()))
+        .map(str::to_lowercase)
+        .collect();
 
     for row in &anchor_rows {
         let address_str = match row.get("address").and_then(|v| v.as_str()) {
@@ -288,14 +730,19 @@ pub async fn update_derived_prices(env: &Env) -> Result<()> {
             Some(v) => v,
             None => continue,
         };
-        if let Some(price) = get_anchor_price_usd(&kv, symbol).await.ok().flatten() {
+        if let Some(price) = get_anchor_price_usd(&kv, &kv_prefix, symbol)
+            .await
+            .ok()
+            .flatten()
+        {
             all_prices.insert(address_str.to_lowercase(), price);
         }
     }
 
     // 2. 获取所有稳定币
     let stable_stmt = db.prepare("SELECT address FROM tokens WHERE is_stablecoin = 1");
-    let stable_result = infra::db::run("update_derived_stable_select", stable_stmt.all()).await?;
+    let stable_result =
+        infra::db::run("update_derived_stable_select", || stable_stmt.all()).await?;
     let stable_rows: Vec<Value> = stable_result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -310,25 +757,69 @@ pub async fn update_derived_prices(env: &Env) -> Result<()> {
     let statement = db.prepare(
         "SELECT address, symbol, decimals FROM tokens WHERE is_anchor = 0 AND is_stablecoin = 0",
     );
-    let result = infra::db::run("update_derived_prices_select", statement.all()).await?;
+    let result = infra::db::run("update_derived_prices_select", || statement.all()).await?;
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
     if rows.is_empty() {
         // 仍然写入聚合缓存（包含 anchor 和 stablecoin）
-        write_aggregated_price_cache(&kv, &all_prices).await?;
+        write_aggregated_price_cache(
+            &kv,
+            &kv_prefix,
+            &all_prices,
+            &anchor_addresses,
+            &RejectedPrices::default(),
+            types::now_ms(),
+        )
+        .await?;
         return Ok(());
     }
 
-    // 构建 Services (需要 RPC)
-    let services = infra::Services::new(env, "cron:derived_prices", types::now_ms())?;
-    let multicall = services.multicall()?;
+    // 构建 Services (需要 RPC)；RPC 不可用时仍写入已收集的 anchor/stablecoin 价格
+    let services = match infra::Services::new(env, "cron:derived_prices", types::now_ms()) {
+        Ok(v) => v,
+        Err(err) => {
+            write_aggregated_price_cache(
+                &kv,
+                &kv_prefix,
+                &all_prices,
+                &anchor_addresses,
+                &RejectedPrices::default(),
+                types::now_ms(),
+            )
+            .await?;
+            return Err(err);
+        }
+    };
+    let multicall = match services.multicall() {
+        Ok(v) => v,
+        Err(err) => {
+            write_aggregated_price_cache(
+                &kv,
+                &kv_prefix,
+                &all_prices,
+                &anchor_addresses,
+                &RejectedPrices::default(),
+                types::now_ms(),
+            )
+            .await?;
+            return Err(err);
+        }
+    };
 
     // 获取所有 DEX 池子信息
     let pools = infra::config::list_dex_pools(&db, "vvs").await?;
     if pools.is_empty() {
-        write_aggregated_price_cache(&kv, &all_prices).await?;
+        write_aggregated_price_cache(
+            &kv,
+            &kv_prefix,
+            &all_prices,
+            &anchor_addresses,
+            &RejectedPrices::default(),
+            types::now_ms(),
+        )
+        .await?;
         return Ok(());
     }
 
@@ -341,41 +832,47 @@ pub async fn update_derived_prices(env: &Env) -> Result<()> {
         })
         .collect();
 
-    let reserve_results = multicall.aggregate(reserve_calls).await?;
-
-    // 解析 reserves 结果并建立映射
-    let mut pool_reserves: std::collections::HashMap<
-        alloy_primitives::Address,
-        (U256, U256, Address, Address),
-    > = std::collections::HashMap::new();
+    // 整体 multicall 单独重试 (在 RPC 层逐次重试之外)，避免一次抖动浪费整轮 cron
+    let reserves = retry_with_backoff(
+        DERIVED_RESERVES_MAX_ATTEMPTS,
+        || multicall.aggregate(reserve_calls.clone()),
+        |ms| Delay::from(Duration::from_millis(ms)),
+    )
+    .await;
+
+    // 重试仍失败时不中断本轮同步，仍写入 anchor/stablecoin 价格
+    let (reserve_results, failure) = reserves_or_empty(reserves);
+    if let Some(reason) = failure {
+        LogEntry::new(
+            LogLevel::Warn,
+            "cron:derived_prices",
+            "getReserves multicall failed after retries",
+        )
+        .with_error(-32500, &reason)
+        .emit();
+    }
 
-    for (pool, result) in pools.iter().zip(reserve_results.into_iter()) {
-        if let Ok(data) = result {
-            if let Ok(decoded) = abi::getReservesCall::abi_decode_returns(&data, true) {
-                pool_reserves.insert(
-                    pool.lp_address,
-                    (
-                        U256::from(decoded.reserve0),
-                        U256::from(decoded.reserve1),
-                        pool.token0_address,
-                        pool.token1_address,
-                    ),
-                );
-            }
-        }
+    // 解析 reserves 结果并建立映射 (失败的池子跳过并记录警告)
+    let (pool_reserves, skipped) = collect_pool_reserves(&pools, reserve_results);
+    for (lp_address, reason) in &skipped {
+        let message = format!("Skipping pool {lp_address} for derived prices");
+        LogEntry::new(LogLevel::Warn, "cron:derived_prices", &message)
+            .with_error(-32500, reason)
+            .emit();
     }
 
     // 获取所有代币信息用于 decimals 查询
     let all_tokens = infra::token::list_tokens(&db).await?;
-    let token_decimals: std::collections::HashMap<Address, u8> = all_tokens
-        .iter()
-        .map(|t| (t.address, t.decimals))
-        .collect();
+    let token_decimals: std::collections::HashMap<Address, u8> =
+        all_tokens.iter().map(|t| (t.address, t.decimals)).collect();
     let token_symbols: std::collections::HashMap<Address, String> = all_tokens
         .iter()
         .map(|t| (t.address, t.symbol.clone()))
         .collect();
 
+    // 同一轮中 quote 代币的锚定价格只读一次 KV
+    let mut anchor_prices: HashMap<String, Option<f64>> = HashMap::new();
+
     // 对每个需要计算 derived price 的代币
     for row in rows {
         let address_str = match row.get("address").and_then(|v| v.as_str()) {
@@ -388,109 +885,260 @@ pub async fn update_derived_prices(env: &Env) -> Result<()> {
         };
         let _token_decimals_val = token_decimals.get(&token_address).copied().unwrap_or(18);
 
-        // 查找该代币所在的池子
-        let pool = pools.iter().find(|p| {
-            p.token0_address == token_address || p.token1_address == token_address
-        });
-        let Some(pool) = pool else {
-            continue;
-        };
-
-        let Some((reserve0, reserve1, token0_addr, token1_addr)) =
-            pool_reserves.get(&pool.lp_address)
-        else {
-            continue;
-        };
-
-        let token0_dec = token_decimals.get(token0_addr).copied().unwrap_or(18);
-        let token1_dec = token_decimals.get(token1_addr).copied().unwrap_or(18);
-
-        let token0_amount = types::format_units(reserve0, token0_dec)
-            .parse::<f64>()
-            .unwrap_or(0.0);
-        let token1_amount = types::format_units(reserve1, token1_dec)
-            .parse::<f64>()
-            .unwrap_or(0.0);
-
-        let (token_amount, quote_amount, quote_symbol) = if token_address == *token0_addr {
-            let sym = token_symbols
-                .get(token1_addr)
-                .map(|s| s.as_str())
-                .unwrap_or("UNKNOWN");
-            (token0_amount, token1_amount, sym)
-        } else {
-            let sym = token_symbols
-                .get(token0_addr)
-                .map(|s| s.as_str())
-                .unwrap_or("UNKNOWN");
-            (token1_amount, token0_amount, sym)
-        };
+        // 该代币所在的所有池子，按配置顺序取第一个流动性达标的
+        let mut quotes: Vec<PoolQuote> = Vec::new();
+        for pool in pools
+            .iter()
+            .filter(|p| p.token0_address == token_address || p.token1_address == token_address)
+        {
+            let Some((reserve0, reserve1, token0_addr, token1_addr)) =
+                pool_reserves.get(&pool.lp_address)
+            else {
+                continue;
+            };
+
+            let token0_dec = token_decimals.get(token0_addr).copied().unwrap_or(18);
+            let token1_dec = token_decimals.get(token1_addr).copied().unwrap_or(18);
+
+            let token0_amount = types::format_units(reserve0, token0_dec)
+                .parse::<f64>()
+                .unwrap_or(0.0);
+            let token1_amount = types::format_units(reserve1, token1_dec)
+                .parse::<f64>()
+                .unwrap_or(0.0);
+
+            let (token_amount, quote_amount, quote_symbol) = if token_address == *token0_addr {
+                let sym = token_symbols
+                    .get(token1_addr)
+                    .map(|s| s.as_str())
+                    .unwrap_or("UNKNOWN");
+                (token0_amount, token1_amount, sym)
+            } else {
+                let sym = token_symbols
+                    .get(token0_addr)
+                    .map(|s| s.as_str())
+                    .unwrap_or("UNKNOWN");
+                (token1_amount, token0_amount, sym)
+            };
+
+            if token_amount <= 0.0 || quote_amount <= 0.0 {
+                continue;
+            }
 
-        if token_amount <= 0.0 || quote_amount <= 0.0 {
-            continue;
+            // 获取 quote token 的价格
+            let quote_price_usd = if quote_symbol.eq_ignore_ascii_case("USDC")
+                || quote_symbol.eq_ignore_ascii_case("USDT")
+            {
+                Some(1.0)
+            } else if let Some(price) = anchor_prices.get(quote_symbol) {
+                *price
+            } else {
+                let price = get_anchor_price_usd(&kv, &kv_prefix, quote_symbol)
+                    .await
+                    .ok()
+                    .flatten();
+                anchor_prices.insert(quote_symbol.to_string(), price);
+                price
+            };
+
+            let Some(quote_price) = quote_price_usd else {
+                continue;
+            };
+
+            quotes.push(PoolQuote {
+                lp_address: pool.lp_address,
+                token_amount,
+                quote_amount,
+                quote_price_usd: quote_price,
+            });
         }
 
-        // 获取 quote token 的价格
-        let quote_price_usd = if quote_symbol.eq_ignore_ascii_case("USDC")
-            || quote_symbol.eq_ignore_ascii_case("USDT")
-        {
-            Some(1.0)
-        } else {
-            get_anchor_price_usd(&kv, quote_symbol).await.ok().flatten()
-        };
-
-        let Some(quote_price) = quote_price_usd else {
+        let (derived_price, thin) = price_from_pool_quotes(&quotes, min_liquidity_usd);
+        log_thin_pools("cron:derived_prices", &thin, min_liquidity_usd);
+        let Some(derived_price) = derived_price else {
             continue;
         };
 
-        let derived_price = quote_price * (quote_amount / token_amount);
-        if !derived_price.is_finite() || derived_price <= 0.0 {
+        // 偏离上一轮价格过大 (池子被操纵或流动性枯竭) 时拒绝，聚合缓存中沿用上一轮价格及其抓取时间；
+        // 连续拒绝多轮后接受新价格
+        let addr_key = token_address.to_string().to_lowercase();
+        let reference = previous
+            .as_ref()
+            .and_then(|cache| cache.prices.get(&addr_key).copied());
+        let prior_rejections = previous
+            .as_ref()
+            .and_then(|cache| cache.rejected.counts.get(&addr_key).copied())
+            .unwrap_or(0);
+        let (cached_price, is_rejected) =
+            sanity_checked_price(derived_price, reference, sanity_multiple, prior_rejections);
+        if is_rejected {
+            log_rejected_price("cron:derived_prices", &addr_key, derived_price, reference);
+            let original_fetched_ms = previous.as_ref().and_then(|cache| {
+                cache
+                    .rejected
+                    .fetched_ms
+                    .get(&addr_key)
+                    .copied()
+                    .or(cache.fetched_ms)
+            });
+            if let Some(ts) = original_fetched_ms {
+                rejected.fetched_ms.insert(addr_key.clone(), ts);
+            }
+            rejected
+                .counts
+                .insert(addr_key.clone(), prior_rejections.saturating_add(1));
+            all_prices.insert(addr_key, cached_price);
             continue;
         }
 
         // 写入单独的 KV 缓存 (兼容旧逻辑)
-        let addr_key = token_address.to_string().to_lowercase();
-        let key = format!("price:derived:{addr_key}");
-        if let Ok(put) = kv.put(&key, derived_price.to_string()) {
-            let _ = put.expiration_ttl(600).execute().await;
-        }
+        let key = infra::kv_key(&kv_prefix, &format!("price:derived:{addr_key}"));
+        let _ = kv
+            .put_text_with_ttl(&key, derived_price.to_string(), 600)
+            .await;
 
         // 添加到聚合缓存
         all_prices.insert(addr_key, derived_price);
     }
 
     // 写入聚合价格缓存
-    write_aggregated_price_cache(&kv, &all_prices).await?;
+    write_aggregated_price_cache(
+        &kv,
+        &kv_prefix,
+        &all_prices,
+        &anchor_addresses,
+        &rejected,
+        types::now_ms(),
+    )
+    .await?;
 
     Ok(())
 }
 
+/// derived price 同步中 getReserves multicall 的最大尝试次数
+const DERIVED_RESERVES_MAX_ATTEMPTS: u32 = 3;
+const DERIVED_RESERVES_BACKOFF_BASE_MS: u64 = 500;
+
+/// 第 attempt 次失败后的等待时间 (指数退避：500ms, 1s, 2s ...)
+fn reserves_backoff_ms(attempt: u32) -> u64 {
+    DERIVED_RESERVES_BACKOFF_BASE_MS.saturating_mul(1u64 << attempt.min(10))
+}
+
+/// 最多尝试 `max_attempts` 次，两次尝试之间按 `reserves_backoff_ms` 等待
+async fn retry_with_backoff<T, Op, OpFut, Sleep, SleepFut>(
+    max_attempts: u32,
+    mut op: Op,
+    mut sleep: Sleep,
+) -> Result<T>
+where
+    Op: FnMut() -> OpFut,
+    OpFut: std::future::Future<Output = Result<T>>,
+    Sleep: FnMut(u64) -> SleepFut,
+    SleepFut: std::future::Future<Output = ()>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(err) => {
+                attempt += 1;
+                if attempt >= max_attempts {
+                    return Err(err);
+                }
+                sleep(reserves_backoff_ms(attempt - 1)).await;
+            }
+        }
+    }
+}
+
+type ReserveResults = Vec<std::result::Result<Bytes, CroLensError>>;
+
+/// reserves 获取最终失败时返回空结果和失败原因，后续所有池子按缺失跳过
+fn reserves_or_empty(result: Result<ReserveResults>) -> (ReserveResults, Option<String>) {
+    match result {
+        Ok(v) => (v, None),
+        Err(err) => (Vec::new(), Some(err.to_string())),
+    }
+}
+
+/// 解析 getReserves 返回值；revert、解码失败或空储备都返回原因，由调用方跳过该池子
+fn decode_pool_reserves(
+    result: std::result::Result<Bytes, CroLensError>,
+) -> std::result::Result<(U256, U256), String> {
+    let data = result.map_err(|err| format!("getReserves reverted: {err}"))?;
+    let decoded = abi::getReservesCall::abi_decode_returns(&data, true)
+        .map_err(|err| format!("getReserves decode failed: {err}"))?;
+    let reserve0 = U256::from(decoded.reserve0);
+    let reserve1 = U256::from(decoded.reserve1);
+    if reserve0.is_zero() || reserve1.is_zero() {
+        return Err("getReserves returned empty reserves".to_string());
+    }
+    Ok((reserve0, reserve1))
+}
+
+type PoolReserves = HashMap<Address, (U256, U256, Address, Address)>;
+
+/// 按池子收集 reserves；结果数量不足时缺失的池子同样视为失败
+fn collect_pool_reserves(
+    pools: &[infra::config::DexPool],
+    results: Vec<std::result::Result<Bytes, CroLensError>>,
+) -> (PoolReserves, Vec<(Address, String)>) {
+    let mut reserves = HashMap::new();
+    let mut skipped = Vec::new();
+    let mut results = results.into_iter();
+
+    for pool in pools {
+        let outcome = match results.next() {
+            Some(result) => decode_pool_reserves(result),
+            None => Err("getReserves result missing".to_string()),
+        };
+        match outcome {
+            Ok((reserve0, reserve1)) => {
+                reserves.insert(
+                    pool.lp_address,
+                    (reserve0, reserve1, pool.token0_address, pool.token1_address),
+                );
+            }
+            Err(reason) => skipped.push((pool.lp_address, reason)),
+        }
+    }
+
+    (reserves, skipped)
+}
+
 /// 写入聚合价格缓存
-async fn write_aggregated_price_cache(kv: &KvStore, prices: &HashMap<String, f64>) -> Result<()> {
+async fn write_aggregated_price_cache<S: KvTextStore + ?Sized>(
+    kv: &S,
+    kv_prefix: &str,
+    prices: &HashMap<String, f64>,
+    anchors: &[String],
+    rejected: &RejectedPrices,
+    fetched_ms: i64,
+) -> Result<()> {
     let cache = PriceCache {
         prices: prices.clone(),
+        fetched_ms: Some(fetched_ms),
+        anchors: Some(anchors.to_vec()),
+        rejected: rejected.clone(),
     };
     let json = serde_json::to_string(&cache)
         .map_err(|err| CroLensError::KvError(format!("Failed to serialize price cache: {err}")))?;
 
-    kv.put(ALL_PRICES_CACHE_KEY, json)
-        .map_err(|err| CroLensError::KvError(err.to_string()))?
-        .expiration_ttl(600) // 10 分钟
-        .execute()
-        .await
-        .map_err(|err| CroLensError::KvError(err.to_string()))?;
+    // 10 分钟
+    kv.put_text_with_ttl(&infra::kv_key(kv_prefix, ALL_PRICES_CACHE_KEY), json, 600)
+        .await?;
 
     Ok(())
 }
 
-async fn get_anchor_price_usd(kv: &KvStore, symbol: &str) -> Result<Option<f64>> {
+pub(crate) async fn get_anchor_price_usd(
+    kv: &TrackedKv,
+    kv_prefix: &str,
+    symbol: &str,
+) -> Result<Option<f64>> {
     let key_symbol = normalize_anchor_symbol(symbol);
-    let key = format!("price:anchor:{key_symbol}");
-    let value = kv
-        .get(&key)
-        .text()
-        .await
-        .map_err(|err| CroLensError::KvError(err.to_string()))?;
+    let key = infra::kv_key(kv_prefix, &format!("price:anchor:{key_symbol}"));
+    let value = kv.get_text(&key).await?;
 
     let Some(text) = value else {
         return Ok(None);
@@ -501,15 +1149,20 @@ async fn get_anchor_price_usd(kv: &KvStore, symbol: &str) -> Result<Option<f64>>
         .map_err(|err| CroLensError::KvError(format!("Invalid KV price for {key}: {err}")))
 }
 
+/// 包装代币 -> 原生代币的锚定价格别名 (均为 normalize_symbol 后的小写形式)
+/// 新增包装/原生对时在此追加，锚定价格 key 统一使用右侧符号
+const ANCHOR_SYMBOL_ALIASES: &[(&str, &str)] = &[("wcro", "cro"), ("weth", "eth"), ("wbtc", "btc")];
+
 fn normalize_anchor_symbol(symbol: &str) -> String {
     let normalized = types::normalize_symbol(symbol);
-    if normalized == "wcro" || normalized == "cro" {
-        return "cro".to_string();
-    }
-    normalized
+    ANCHOR_SYMBOL_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == normalized)
+        .map(|(_, canonical)| canonical.to_string())
+        .unwrap_or(normalized)
 }
 
-async fn derive_price_from_pool(
+pub(crate) async fn derive_price_from_pool(
     services: &infra::Services,
     token_address: Address,
 ) -> Result<Option<f64>> {
@@ -523,25 +1176,28 @@ async fn derive_price_from_pool(
         call_data: abi::getReservesCall {}.abi_encode().into(),
     };
     let reserves = rpc_pool.aggregate(vec![reserve_call]).await?;
-    let Some(item) = reserves.into_iter().next() else {
-        return Ok(None);
-    };
-    let Ok(return_data) = item else {
-        return Ok(None);
+    let item = reserves.into_iter().next().unwrap_or_else(|| {
+        Err(CroLensError::RpcError(
+            "getReserves result missing".to_string(),
+        ))
+    });
+    let (reserve0, reserve1) = match decode_pool_reserves(item) {
+        Ok(v) => v,
+        Err(reason) => {
+            let message = format!("Skipping pool {} for derived price", pool.lp_address);
+            LogEntry::new(LogLevel::Warn, &services.trace_id, &message)
+                .with_error(-32500, &reason)
+                .emit();
+            return Ok(None);
+        }
     };
 
-    let decoded = abi::getReservesCall::abi_decode_returns(&return_data, true)
-        .map_err(|err| CroLensError::RpcError(format!("getReserves decode failed: {err}")))?;
-
     let token0 = infra::token::get_token_by_address(&services.db, pool.token0_address).await?;
     let token1 = infra::token::get_token_by_address(&services.db, pool.token1_address).await?;
 
     let token0_decimals = token0.as_ref().map(|t| t.decimals).unwrap_or(18);
     let token1_decimals = token1.as_ref().map(|t| t.decimals).unwrap_or(18);
 
-    let reserve0 = U256::from(decoded.reserve0);
-    let reserve1 = U256::from(decoded.reserve1);
-
     let token0_amount = types::format_units(&reserve0, token0_decimals)
         .parse::<f64>()
         .unwrap_or(0.0);
@@ -573,28 +1229,690 @@ async fn derive_price_from_pool(
         if quote_symbol.eq_ignore_ascii_case("USDC") || quote_symbol.eq_ignore_ascii_case("USDT") {
             Some(1.0)
         } else {
-            get_anchor_price_usd(&services.kv, quote_symbol).await?
+            get_anchor_price_usd(&services.kv, &services.kv_prefix, quote_symbol).await?
         };
 
     let Some(quote_price) = quote_price_usd else {
         return Ok(None);
     };
 
-    let derived_price = quote_price * (quote_amount / token_amount);
-    if !derived_price.is_finite() || derived_price <= 0.0 {
+    let quote = PoolQuote {
+        lp_address: pool.lp_address,
+        token_amount,
+        quote_amount,
+        quote_price_usd: quote_price,
+    };
+    let (derived_price, thin) = price_from_pool_quotes(&[quote], services.price_min_liquidity_usd);
+    log_thin_pools(&services.trace_id, &thin, services.price_min_liquidity_usd);
+    let Some(derived_price) = derived_price else {
         return Ok(None);
-    }
+    };
 
     let addr_key = token_address.to_string().to_lowercase();
-    let key = format!("price:derived:{addr_key}");
+    let reference = price_references(services).await.get(&addr_key).copied();
+    if !within_sanity_bounds(derived_price, reference, services.price_sanity_multiple) {
+        log_rejected_price(&services.trace_id, &addr_key, derived_price, reference);
+        return Ok(None);
+    }
+    let key = services.kv_key(&format!("price:derived:{addr_key}"));
+    // 10 分钟，比 cron 间隔 (5分钟) 长
     services
         .kv
-        .put(&key, derived_price.to_string())
-        .map_err(|err| CroLensError::KvError(err.to_string()))?
-        .expiration_ttl(600) // 10 分钟，比 cron 间隔 (5分钟) 长
-        .execute()
-        .await
-        .map_err(|err| CroLensError::KvError(err.to_string()))?;
+        .put_text_with_ttl(&key, derived_price.to_string(), 600)
+        .await?;
 
     Ok(Some(derived_price))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+
+    fn addr(byte: u8) -> Address {
+        Address::from([byte; 20])
+    }
+
+    fn priced_token(symbol: &str, byte: u8, is_stablecoin: bool) -> Token {
+        Token {
+            address: addr(byte),
+            symbol: symbol.to_string(),
+            decimals: 18,
+            is_stablecoin,
+            is_spam: false,
+        }
+    }
+
+    /// 各来源的固定价格表：cache 中 CRO 为 CoinGecko 故障前的旧价，derived 为链上价格
+    fn fixed_source_prices(source: PriceSource, pending: Vec<&Token>) -> SourcePrices {
+        let table: &[(u8, f64)] = match source {
+            PriceSource::Stablecoin => &[(1, 1.0)],
+            PriceSource::Oracle => &[(4, 3.25)],
+            PriceSource::Cache => &[(2, 0.09), (4, 3.0)],
+            PriceSource::Anchor => &[(2, 0.0), (3, 0.5)],
+            PriceSource::Derived => &[(2, 0.12), (3, 0.48)],
+        };
+        SourcePrices {
+            prices: pending
+                .iter()
+                .filter_map(|token| {
+                    table
+                        .iter()
+                        .find(|(byte, _)| token.address == addr(*byte))
+                        .map(|(_, price)| (token.address, *price))
+                })
+                .collect(),
+            fetched_ms: (source == PriceSource::Cache).then_some(1_700_000_000_000),
+            carried_fetched_ms: HashMap::new(),
+        }
+    }
+
+    fn resolve_fixed(priority: &[PriceSource], tokens: &[Token]) -> PriceBatch {
+        resolve_by_priority(priority, tokens, |source, pending| async move {
+            fixed_source_prices(source, pending)
+        })
+        .now_or_never()
+        .expect("ready")
+    }
+
+    #[test]
+    fn default_priority_prefers_cache_over_derived() {
+        let tokens = vec![
+            priced_token("USDC", 1, true),
+            priced_token("CRO", 2, false),
+            priced_token("VVS", 3, false),
+        ];
+        let batch = resolve_fixed(&PRICE_SOURCE_PRIORITY_DEFAULT, &tokens);
+        assert_eq!(batch.prices.get(&addr(1)), Some(&1.0));
+        assert_eq!(batch.sources.get(&addr(1)), Some(&PriceSource::Stablecoin));
+        assert_eq!(batch.prices.get(&addr(2)), Some(&0.09));
+        assert_eq!(batch.sources.get(&addr(2)), Some(&PriceSource::Cache));
+        assert_eq!(batch.fetched_ms.get(&addr(2)), Some(&1_700_000_000_000));
+        assert_eq!(batch.prices.get(&addr(3)), Some(&0.5));
+        assert_eq!(batch.sources.get(&addr(3)), Some(&PriceSource::Anchor));
+    }
+
+    #[test]
+    fn degraded_priority_prefers_derived_prices() {
+        let tokens = vec![
+            priced_token("USDC", 1, true),
+            priced_token("CRO", 2, false),
+            priced_token("VVS", 3, false),
+        ];
+        let priority = parse_price_source_priority(Some("stablecoin,derived,anchor"));
+        let batch = resolve_fixed(&priority, &tokens);
+        assert_eq!(batch.sources.get(&addr(1)), Some(&PriceSource::Stablecoin));
+        assert_eq!(batch.prices.get(&addr(2)), Some(&0.12));
+        assert_eq!(batch.sources.get(&addr(2)), Some(&PriceSource::Derived));
+        assert!(!batch.fetched_ms.contains_key(&addr(2)));
+        assert_eq!(batch.prices.get(&addr(3)), Some(&0.48));
+        assert_eq!(batch.sources.get(&addr(3)), Some(&PriceSource::Derived));
+    }
+
+    #[test]
+    fn invalid_prices_fall_through_to_next_source() {
+        let tokens = vec![priced_token("CRO", 2, false)];
+        // anchor 中 CRO 为 0，视为无效，继续查询 derived
+        let priority = [PriceSource::Anchor, PriceSource::Derived];
+        let batch = resolve_fixed(&priority, &tokens);
+        assert_eq!(batch.prices.get(&addr(2)), Some(&0.12));
+        assert_eq!(batch.sources.get(&addr(2)), Some(&PriceSource::Derived));
+
+        // 未列出的来源不会被查询
+        let batch = resolve_fixed(&[PriceSource::Anchor], &tokens);
+        assert!(batch.prices.is_empty());
+    }
+
+    #[test]
+    fn oracle_price_wins_when_configured() {
+        let tokens = vec![priced_token("CRO", 2, false), priced_token("ORC", 4, false)];
+        let batch = resolve_fixed(&PRICE_SOURCE_PRIORITY_DEFAULT, &tokens);
+        // ORC 在聚合缓存中也有池子推导的价格，配置的预言机仍然优先
+        assert_eq!(batch.prices.get(&addr(4)), Some(&3.25));
+        assert_eq!(batch.sources.get(&addr(4)), Some(&PriceSource::Oracle));
+        assert!(!batch.fetched_ms.contains_key(&addr(4)));
+        assert_eq!(PriceSource::Oracle.as_str(), "oracle");
+        // 没有配置预言机的代币继续使用后续来源
+        assert_eq!(batch.sources.get(&addr(2)), Some(&PriceSource::Cache));
+
+        // 预言机不在优先级中时回退到缓存中的池子价格
+        let without_oracle = parse_price_source_priority(Some("stablecoin,cache"));
+        let batch = resolve_fixed(&without_oracle, &tokens);
+        assert_eq!(batch.prices.get(&addr(4)), Some(&3.0));
+        assert_eq!(batch.sources.get(&addr(4)), Some(&PriceSource::Cache));
+
+        let derived_only = parse_price_source_priority(Some("stablecoin,derived"));
+        let batch = resolve_fixed(&derived_only, &tokens);
+        assert_eq!(batch.sources.get(&addr(2)), Some(&PriceSource::Derived));
+        assert!(!batch.prices.contains_key(&addr(4)));
+    }
+
+    #[test]
+    fn price_source_priority_parsing() {
+        assert_eq!(
+            parse_price_source_priority(None),
+            PRICE_SOURCE_PRIORITY_DEFAULT.to_vec()
+        );
+        assert_eq!(
+            parse_price_source_priority(Some(" Derived , anchor,derived,bogus")),
+            vec![PriceSource::Derived, PriceSource::Anchor]
+        );
+        assert_eq!(
+            parse_price_source_priority(Some("bogus, ")),
+            PRICE_SOURCE_PRIORITY_DEFAULT.to_vec()
+        );
+        assert_eq!(PriceSource::Cache.as_str(), "cache");
+    }
+
+    #[test]
+    fn anchor_symbol_collapses_wrapped_aliases() {
+        assert_eq!(normalize_anchor_symbol("WCRO"), "cro");
+        assert_eq!(normalize_anchor_symbol("CRO"), "cro");
+        assert_eq!(normalize_anchor_symbol("WETH"), "eth");
+        assert_eq!(normalize_anchor_symbol("ETH"), "eth");
+        assert_eq!(normalize_anchor_symbol("WBTC"), "btc");
+        assert_eq!(normalize_anchor_symbol(" wbtc "), "btc");
+    }
+
+    #[test]
+    fn anchor_symbol_passes_through_unaliased() {
+        assert_eq!(normalize_anchor_symbol("USDC"), "usdc");
+        assert_eq!(normalize_anchor_symbol("VVS"), "vvs");
+    }
+
+    fn pool(lp: u8, token0: u8, token1: u8) -> infra::config::DexPool {
+        infra::config::DexPool {
+            pool_id: format!("pool-{lp}"),
+            pool_index: None,
+            lp_address: addr(lp),
+            token0_address: addr(token0),
+            token1_address: addr(token1),
+            token0_symbol: "A".to_string(),
+            token1_symbol: "B".to_string(),
+        }
+    }
+
+    fn encoded_reserves(reserve0: u64, reserve1: u64) -> Bytes {
+        let mut data = Vec::with_capacity(96);
+        for value in [U256::from(reserve0), U256::from(reserve1), U256::ZERO] {
+            data.extend_from_slice(&value.to_be_bytes::<32>());
+        }
+        Bytes::from(data)
+    }
+
+    #[test]
+    fn decodes_valid_reserves() {
+        let out = decode_pool_reserves(Ok(encoded_reserves(1_000, 2_000))).expect("decode");
+        assert_eq!(out, (U256::from(1_000u64), U256::from(2_000u64)));
+    }
+
+    #[test]
+    fn reverted_reserve_call_is_reported() {
+        let err = decode_pool_reserves(Err(CroLensError::RpcError(
+            "Multicall inner call failed".to_string(),
+        )))
+        .unwrap_err();
+        assert!(err.contains("reverted"));
+    }
+
+    #[test]
+    fn garbage_reserve_data_is_reported() {
+        let err = decode_pool_reserves(Ok(Bytes::from(vec![0x01, 0x02]))).unwrap_err();
+        assert!(err.contains("decode failed"));
+    }
+
+    #[test]
+    fn empty_reserves_are_rejected() {
+        assert!(decode_pool_reserves(Ok(encoded_reserves(0, 2_000))).is_err());
+    }
+
+    #[test]
+    fn failed_pool_is_skipped_while_others_proceed() {
+        let pools = vec![pool(0xa1, 0x01, 0x02), pool(0xa2, 0x03, 0x02)];
+        let results = vec![
+            Err(CroLensError::RpcError(
+                "Multicall inner call failed".to_string(),
+            )),
+            Ok(encoded_reserves(500, 1_500)),
+        ];
+
+        let (reserves, skipped) = collect_pool_reserves(&pools, results);
+
+        assert!(!reserves.contains_key(&addr(0xa1)));
+        assert_eq!(
+            reserves.get(&addr(0xa2)),
+            Some(&(
+                U256::from(500u64),
+                U256::from(1_500u64),
+                addr(0x03),
+                addr(0x02)
+            ))
+        );
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, addr(0xa1));
+    }
+
+    #[test]
+    fn reserves_retry_recovers_from_transient_failure() {
+        let calls = std::cell::Cell::new(0);
+        let sleeps = std::cell::RefCell::new(Vec::new());
+        let result = retry_with_backoff(
+            DERIVED_RESERVES_MAX_ATTEMPTS,
+            || {
+                calls.set(calls.get() + 1);
+                let n = calls.get();
+                async move {
+                    if n < 3 {
+                        Err(CroLensError::RpcError("timeout".to_string()))
+                    } else {
+                        Ok(n)
+                    }
+                }
+            },
+            |ms| {
+                sleeps.borrow_mut().push(ms);
+                async {}
+            },
+        )
+        .now_or_never()
+        .expect("ready");
+        assert_eq!(result.expect("should succeed"), 3);
+        assert_eq!(*sleeps.borrow(), vec![500, 1_000]);
+    }
+
+    #[test]
+    fn reserves_retry_gives_up_after_max_attempts() {
+        let calls = std::cell::Cell::new(0);
+        let result: Result<()> = retry_with_backoff(
+            DERIVED_RESERVES_MAX_ATTEMPTS,
+            || {
+                calls.set(calls.get() + 1);
+                async { Err(CroLensError::RpcError("down".to_string())) }
+            },
+            |_| async {},
+        )
+        .now_or_never()
+        .expect("ready");
+        assert!(result.is_err());
+        assert_eq!(calls.get(), DERIVED_RESERVES_MAX_ATTEMPTS);
+    }
+
+    #[derive(Default)]
+    struct MemoryStore {
+        values: std::cell::RefCell<HashMap<String, String>>,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl KvTextStore for MemoryStore {
+        async fn get_text(&self, key: &str) -> Result<Option<String>> {
+            Ok(self.values.borrow().get(key).cloned())
+        }
+
+        async fn put_text_with_ttl(&self, key: &str, value: String, _ttl: u64) -> Result<()> {
+            self.values.borrow_mut().insert(key.to_string(), value);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_reserves_fetch_keeps_base_prices_only() {
+        let pools = vec![pool(1, 2, 3), pool(4, 5, 6)];
+        let (results, failure) = reserves_or_empty(Err(CroLensError::RpcError("down".to_string())));
+        assert!(failure.is_some_and(|reason| reason.contains("down")));
+
+        // 所有池子被跳过，没有 derived 价格
+        let (reserves, skipped) = collect_pool_reserves(&pools, results);
+        assert!(reserves.is_empty());
+        assert_eq!(skipped.len(), pools.len());
+
+        // 聚合缓存仍写入已收集的 anchor/stablecoin 价格
+        let anchor = addr(2).to_string().to_lowercase();
+        let stable = addr(7).to_string().to_lowercase();
+        let base = HashMap::from([(anchor.clone(), 0.09), (stable.clone(), 1.0)]);
+        let store = MemoryStore::default();
+        write_aggregated_price_cache(
+            &store,
+            "test:",
+            &base,
+            std::slice::from_ref(&anchor),
+            &RejectedPrices::default(),
+            1_700_000_000_000,
+        )
+        .now_or_never()
+        .expect("ready")
+        .expect("written");
+
+        let persisted = read_price_cache(&store, "test:")
+            .now_or_never()
+            .expect("ready")
+            .expect("cache persisted");
+        assert_eq!(persisted.prices, base);
+        assert_eq!(persisted.anchors, Some(vec![anchor]));
+        assert_eq!(persisted.fetched_ms, Some(1_700_000_000_000));
+        assert!(persisted.rejected.counts.is_empty());
+    }
+
+    #[test]
+    fn reserves_backoff_is_exponential() {
+        assert_eq!(reserves_backoff_ms(0), 500);
+        assert_eq!(reserves_backoff_ms(1), 1_000);
+        assert_eq!(reserves_backoff_ms(2), 2_000);
+    }
+
+    #[test]
+    fn sanity_accepts_price_within_bounds() {
+        let max = Some(PRICE_SANITY_MULTIPLE_DEFAULT);
+        assert!(within_sanity_bounds(1.5, Some(1.0), max));
+        assert!(within_sanity_bounds(0.2, Some(1.0), max));
+        assert!(within_sanity_bounds(10.0, Some(1.0), max));
+    }
+
+    fn quote(lp: u8, token_amount: f64, quote_amount: f64) -> PoolQuote {
+        PoolQuote {
+            lp_address: addr(lp),
+            token_amount,
+            quote_amount,
+            quote_price_usd: 1.0,
+        }
+    }
+
+    #[test]
+    fn thin_pool_is_skipped_for_adequate_one() {
+        // 薄池子: 0.5 USDC 对 1e-6 token -> 500000 美元的离谱价格
+        let quotes = [quote(1, 0.000001, 0.5), quote(2, 1_000.0, 2_000.0)];
+        let (price, thin) = price_from_pool_quotes(&quotes, PRICE_MIN_LIQUIDITY_USD_DEFAULT);
+        assert_eq!(price, Some(2.0));
+        assert_eq!(thin, vec![(addr(1), 0.5)]);
+    }
+
+    #[test]
+    fn all_thin_pools_yield_no_price() {
+        let quotes = [quote(1, 10.0, 5.0), quote(2, 1.0, 999.0)];
+        let (price, thin) = price_from_pool_quotes(&quotes, PRICE_MIN_LIQUIDITY_USD_DEFAULT);
+        assert_eq!(price, None);
+        assert_eq!(thin.len(), 2);
+    }
+
+    #[test]
+    fn zero_reserve_pool_is_ignored() {
+        let quotes = [quote(1, 0.0, 5_000.0), quote(2, 100.0, 0.0)];
+        let (price, thin) = price_from_pool_quotes(&quotes, 0.0);
+        assert_eq!(price, None);
+        assert!(thin.is_empty());
+    }
+
+    #[test]
+    fn liquidity_floor_uses_quote_price() {
+        let mut anchor_quote = quote(1, 100.0, 10_000.0);
+        anchor_quote.quote_price_usd = 0.08;
+        // 10000 WCRO * $0.08 = $800 < $1000
+        let (price, _) = price_from_pool_quotes(&[anchor_quote], PRICE_MIN_LIQUIDITY_USD_DEFAULT);
+        assert_eq!(price, None);
+        let (price, _) = price_from_pool_quotes(&[anchor_quote], 500.0);
+        assert!((price.expect("above floor") - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn min_liquidity_parsing() {
+        assert_eq!(
+            parse_price_min_liquidity_usd(None),
+            PRICE_MIN_LIQUIDITY_USD_DEFAULT
+        );
+        assert_eq!(parse_price_min_liquidity_usd(Some("250")), 250.0);
+        assert_eq!(parse_price_min_liquidity_usd(Some("0")), 0.0);
+        assert_eq!(
+            parse_price_min_liquidity_usd(Some("-5")),
+            PRICE_MIN_LIQUIDITY_USD_DEFAULT
+        );
+        assert_eq!(
+            parse_price_min_liquidity_usd(Some("abc")),
+            PRICE_MIN_LIQUIDITY_USD_DEFAULT
+        );
+    }
+
+    #[test]
+    fn sanity_rejects_100x_outlier() {
+        let max = Some(PRICE_SANITY_MULTIPLE_DEFAULT);
+        assert!(!within_sanity_bounds(100.0, Some(1.0), max));
+        assert!(!within_sanity_bounds(0.01, Some(1.0), max));
+    }
+
+    #[test]
+    fn rejected_price_carries_previous_forward() {
+        let max = Some(10.0);
+        assert_eq!(sanity_checked_price(1.5, Some(1.0), max, 0), (1.5, false));
+        assert_eq!(sanity_checked_price(100.0, Some(1.0), max, 0), (1.0, true));
+        assert_eq!(sanity_checked_price(100.0, None, max, 0), (100.0, false));
+    }
+
+    #[test]
+    fn persistent_move_is_accepted_after_consecutive_rejections() {
+        let max = Some(10.0);
+        let last = PRICE_SANITY_MAX_REJECTIONS - 1;
+        assert_eq!(
+            sanity_checked_price(100.0, Some(1.0), max, last - 1),
+            (1.0, true)
+        );
+        assert_eq!(
+            sanity_checked_price(100.0, Some(1.0), max, last),
+            (100.0, false)
+        );
+    }
+
+    #[test]
+    fn sanity_accepts_without_reference_or_when_disabled() {
+        assert!(within_sanity_bounds(100.0, None, Some(10.0)));
+        assert!(within_sanity_bounds(100.0, Some(0.0), Some(10.0)));
+        assert!(within_sanity_bounds(100.0, Some(1.0), None));
+    }
+
+    #[test]
+    fn sanity_multiple_parsing() {
+        assert_eq!(parse_price_sanity_multiple(None), Some(10.0));
+        assert_eq!(parse_price_sanity_multiple(Some(" 5 ")), Some(5.0));
+        assert_eq!(parse_price_sanity_multiple(Some("0")), None);
+        assert_eq!(parse_price_sanity_multiple(Some("0.5")), Some(10.0));
+        assert_eq!(parse_price_sanity_multiple(Some("abc")), Some(10.0));
+    }
+
+    #[test]
+    fn missing_results_skip_remaining_pools() {
+        let pools = vec![pool(0xa1, 0x01, 0x02), pool(0xa2, 0x03, 0x02)];
+        let (reserves, skipped) = collect_pool_reserves(&pools, Vec::new());
+        assert!(reserves.is_empty());
+        assert_eq!(skipped.len(), 2);
+    }
+
+    fn price_cache(entries: &[(&str, f64)], anchors: Option<&[&str]>) -> PriceCache {
+        PriceCache {
+            prices: entries
+                .iter()
+                .map(|(addr, price)| (addr.to_string(), *price))
+                .collect(),
+            fetched_ms: Some(1_700_000_000_000),
+            anchors: anchors.map(|a| a.iter().map(|addr| addr.to_string()).collect()),
+            rejected: RejectedPrices::default(),
+        }
+    }
+
+    #[test]
+    fn empty_price_cache_is_incomplete() {
+        assert!(price_cache_incomplete(&price_cache(&[], None)));
+        assert!(price_cache_incomplete(&price_cache(&[], Some(&[]))));
+        // 只有无效价格的条目同样视为空
+        assert!(price_cache_incomplete(&price_cache(&[("0xaa", 0.0)], None)));
+    }
+
+    #[test]
+    fn price_cache_missing_any_anchor_is_incomplete() {
+        let anchors: &[&str] = &["0xaa", "0xbb"];
+        let partial = price_cache(&[("0xaa", 0.08)], Some(anchors));
+        assert!(price_cache_incomplete(&partial));
+        assert_eq!(missing_anchors(&partial), 1);
+
+        // 条目总数足够但缺少某个 anchor (被其它代币的价格凑数) 仍不完整
+        let padded = price_cache(
+            &[("0xaa", 0.08), ("0xcc", 1.0), ("0xdd", 2.0)],
+            Some(anchors),
+        );
+        assert!(price_cache_incomplete(&padded));
+
+        // anchor 价格无效同样视为缺失
+        let invalid = price_cache(&[("0xaa", 0.08), ("0xbb", f64::NAN)], Some(anchors));
+        assert!(price_cache_incomplete(&invalid));
+
+        let complete = price_cache(&[("0xaa", 0.08), ("0xbb", 3000.0)], Some(anchors));
+        assert!(!price_cache_incomplete(&complete));
+    }
+
+    #[test]
+    fn legacy_price_cache_without_anchor_count_is_trusted_when_non_empty() {
+        let legacy: PriceCache =
+            serde_json::from_str(r#"{"prices":{"0xaa":0.08}}"#).expect("legacy cache");
+        assert_eq!(legacy.anchors, None);
+        assert!(!price_cache_incomplete(&legacy));
+    }
+
+    #[test]
+    fn cache_read_returns_value_before_timeout() {
+        let read = read_cache_within(
+            async { Some("{}".to_string()) },
+            futures_util::future::pending(),
+        )
+        .now_or_never()
+        .expect("ready");
+        assert_eq!(read, CacheRead::Hit("{}".to_string()));
+
+        let read = read_cache_within(async { None }, futures_util::future::pending())
+            .now_or_never()
+            .expect("ready");
+        assert_eq!(read, CacheRead::Miss);
+    }
+
+    #[test]
+    fn slow_cache_read_times_out() {
+        // 注入延迟：读取先挂起一次，超时已到期
+        let slow_read = async {
+            futures_util::pending!();
+            Some(r#"{"prices":{}}"#.to_string())
+        };
+        let read = read_cache_within(slow_read, async {})
+            .now_or_never()
+            .expect("ready");
+        assert_eq!(read, CacheRead::TimedOut);
+    }
+
+    /// 可注入的 KV：固定返回值，或第一次轮询时挂起以模拟慢读取
+    enum CacheStore {
+        Fixed(Option<String>),
+        Slow,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl KvTextStore for CacheStore {
+        async fn get_text(&self, _key: &str) -> Result<Option<String>> {
+            match self {
+                CacheStore::Fixed(value) => Ok(value.clone()),
+                CacheStore::Slow => {
+                    futures_util::pending!();
+                    Ok(Some(r#"{"prices":{}}"#.to_string()))
+                }
+            }
+        }
+
+        async fn put_text_with_ttl(&self, _key: &str, _value: String, _ttl: u64) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn read_cached(store: &CacheStore, tokens: &[Token]) -> (CacheOutcome, SourcePrices) {
+        let pending: Vec<&Token> = tokens.iter().collect();
+        fetch_cached_prices(store, "cache:prices", async {}, &pending)
+            .now_or_never()
+            .expect("ready")
+    }
+
+    #[test]
+    fn cached_prices_are_read_for_pending_tokens() {
+        let tokens = vec![priced_token("CRO", 2, false), priced_token("VVS", 3, false)];
+        let raw = format!(
+            r#"{{"prices":{{"{0}":0.09}},"fetched_ms":1700000000000,"anchors":["{0}"]}}"#,
+            addr(2).to_string().to_lowercase()
+        );
+        let (outcome, prices) = read_cached(&CacheStore::Fixed(Some(raw)), &tokens);
+        assert_eq!(outcome, CacheOutcome::Hit);
+        assert_eq!(prices.prices, vec![(addr(2), 0.09)]);
+        assert_eq!(prices.fetched_ms, Some(1_700_000_000_000));
+
+        let carried = format!(
+            r#"{{"prices":{{"{0}":0.09,"{1}":0.5}},"fetched_ms":1700000600000,"anchors":["{0}"],
+            "rejected":{{"fetched_ms":{{"{1}":1700000000000}},"counts":{{"{1}":1}}}}}}"#,
+            addr(2).to_string().to_lowercase(),
+            addr(3).to_string().to_lowercase()
+        );
+        let batch = resolve_by_priority(&[PriceSource::Cache], &tokens, |_, pending| {
+            let store = CacheStore::Fixed(Some(carried.clone()));
+            async move {
+                fetch_cached_prices(&store, "cache:prices", async {}, &pending)
+                    .await
+                    .1
+            }
+        })
+        .now_or_never()
+        .expect("ready");
+        // 沿用的旧价格保留原始抓取时间，不被报告为新鲜
+        assert_eq!(batch.fetched_ms.get(&addr(2)), Some(&1_700_000_600_000));
+        assert_eq!(batch.fetched_ms.get(&addr(3)), Some(&1_700_000_000_000));
+
+        let (outcome, prices) = read_cached(&CacheStore::Fixed(None), &tokens);
+        assert_eq!(outcome, CacheOutcome::Miss);
+        assert!(prices.prices.is_empty());
+
+        let (outcome, _) = read_cached(&CacheStore::Fixed(Some("nope".to_string())), &tokens);
+        assert_eq!(outcome, CacheOutcome::Invalid);
+
+        let empty = r#"{"prices":{},"anchors":["0xaa","0xbb"]}"#.to_string();
+        let (outcome, prices) = read_cached(&CacheStore::Fixed(Some(empty)), &tokens);
+        assert_eq!(
+            outcome,
+            CacheOutcome::Incomplete {
+                entries: 0,
+                missing_anchors: 2
+            }
+        );
+        assert!(prices.prices.is_empty());
+    }
+
+    #[test]
+    fn cache_timeout_falls_through_to_per_token_sources() {
+        let tokens = vec![priced_token("CRO", 2, false), priced_token("VVS", 3, false)];
+        let (outcome, _) = read_cached(&CacheStore::Slow, &tokens);
+        assert_eq!(outcome, CacheOutcome::TimedOut);
+
+        let batch = resolve_by_priority(
+            &PRICE_SOURCE_PRIORITY_DEFAULT,
+            &tokens,
+            |source, pending| async move {
+                if source == PriceSource::Cache {
+                    return fetch_cached_prices(
+                        &CacheStore::Slow,
+                        "cache:prices",
+                        async {},
+                        &pending,
+                    )
+                    .await
+                    .1;
+                }
+                fixed_source_prices(source, pending)
+            },
+        )
+        .now_or_never()
+        .expect("ready");
+
+        assert_eq!(batch.prices.get(&addr(2)), Some(&0.12));
+        assert_eq!(batch.sources.get(&addr(2)), Some(&PriceSource::Derived));
+        assert_eq!(batch.prices.get(&addr(3)), Some(&0.5));
+        assert_eq!(batch.sources.get(&addr(3)), Some(&PriceSource::Anchor));
+        assert!(batch.fetched_ms.is_empty());
+    }
+}
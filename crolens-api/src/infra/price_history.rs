@@ -0,0 +1,198 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
+
+use crate::infra;
+use crate::infra::samples::{Sample, SampleRing};
+
+/// 每个代币最近约 25h 的 USD 价格样本 (KV 环形缓冲区，由 get_token_price 写入)
+const PRICE_HISTORY_PREFIX: &str = "price:history:";
+/// 比 24h 多保留 1 小时，保证能找到约 24h 前的参考样本；每 5 分钟最多一个样本，容量 25h / 5min
+const PRICE_SAMPLES: SampleRing = SampleRing::new(25 * 3600 * 1000, 5 * 60 * 1000, 300);
+const CHANGE_LOOKBACK_MS: i64 = 24 * 3600 * 1000;
+/// 参考样本与 24h 前的最大偏差；没有落在该范围内的样本时不输出变化率
+const CHANGE_LOOKBACK_TOLERANCE_MS: i64 = 3600 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PriceSample {
+    pub ts_ms: i64,
+    pub price: f64,
+}
+
+impl Sample for PriceSample {
+    fn ts_ms(&self) -> i64 {
+        self.ts_ms
+    }
+
+    fn is_valid(&self) -> bool {
+        self.price.is_finite() && self.price > 0.0
+    }
+}
+
+thread_local! {
+    /// 本 isolate 最近读写过的价格历史 (按完整 KV key)；最后一个样本仍在最小写入间隔内时直接复用
+    static RECENT_HISTORY: RefCell<HashMap<String, Vec<PriceSample>>> = RefCell::new(HashMap::new());
+}
+
+fn history_key(address: Address) -> String {
+    format!(
+        "{PRICE_HISTORY_PREFIX}{}",
+        address.to_string().to_lowercase()
+    )
+}
+
+/// 本 isolate 缓存的历史；只有写入会被节流时才复用 (此时既不需要读也不需要写 KV)
+fn memoized_history(key: &str, now_ms: i64) -> Option<Vec<PriceSample>> {
+    RECENT_HISTORY.with(|memo| {
+        memo.borrow()
+            .get(key)
+            .filter(|samples| PRICE_SAMPLES.is_throttled(samples, now_ms))
+            .cloned()
+    })
+}
+
+fn remember_history(key: String, samples: Vec<PriceSample>) {
+    RECENT_HISTORY.with(|memo| {
+        memo.borrow_mut().insert(key, samples);
+    });
+}
+
+/// 当前价格相对约 24h 前样本 (取最接近 now - 24h 且偏差不超过 1h 的样本) 的变化百分比
+pub fn change_24h_pct(samples: &[PriceSample], current_price: f64, now_ms: i64) -> Option<f64> {
+    if !current_price.is_finite() || current_price <= 0.0 {
+        return None;
+    }
+    let target = now_ms.saturating_sub(CHANGE_LOOKBACK_MS);
+    let reference = samples
+        .iter()
+        .filter(|s| s.ts_ms.abs_diff(target) <= CHANGE_LOOKBACK_TOLERANCE_MS.unsigned_abs())
+        .min_by_key(|s| s.ts_ms.abs_diff(target))?;
+    if reference.price <= 0.0 {
+        return None;
+    }
+    Some((current_price - reference.price) / reference.price * 100.0)
+}
+
+/// 批量读取历史计算 24h 变化并写入当前价格 (尽力而为，KV 失败时只是没有变化率)。
+/// 本 isolate 在最小写入间隔内处理过的代币不再访问 KV；其余代币并发读取，只写回未被节流的缓冲区
+pub async fn record_and_change_24h(
+    services: &infra::Services,
+    prices: &[(Address, f64)],
+    now_ms: i64,
+) -> Vec<Option<f64>> {
+    let mut histories: HashMap<Address, Vec<PriceSample>> = HashMap::new();
+    let mut to_load: Vec<(Address, f64, String)> = Vec::new();
+    for &(address, price) in prices {
+        if !price.is_finite() || price <= 0.0 || histories.contains_key(&address) {
+            continue;
+        }
+        if to_load.iter().any(|(a, _, _)| *a == address) {
+            continue;
+        }
+        let key = services.kv_key(&history_key(address));
+        match memoized_history(&key, now_ms) {
+            Some(samples) => {
+                histories.insert(address, samples);
+            }
+            None => to_load.push((address, price, key)),
+        }
+    }
+
+    let loaded = infra::concurrency::join_all_bounded(
+        to_load
+            .iter()
+            .map(|(_, _, key)| PRICE_SAMPLES.load::<PriceSample>(services, key)),
+        services.max_concurrent_subrequests,
+    )
+    .await;
+
+    let mut writes = Vec::new();
+    for ((address, price, key), samples) in to_load.into_iter().zip(loaded) {
+        let sample = PriceSample {
+            ts_ms: now_ms,
+            price,
+        };
+        let next = PRICE_SAMPLES.push(&samples, sample);
+        if let Some(next) = &next {
+            writes.push((key.clone(), next.clone()));
+        }
+        remember_history(key, next.unwrap_or_else(|| samples.clone()));
+        histories.insert(address, samples);
+    }
+    infra::concurrency::join_all_bounded(
+        writes
+            .iter()
+            .map(|(key, next)| PRICE_SAMPLES.store(services, key, next)),
+        services.max_concurrent_subrequests,
+    )
+    .await;
+
+    prices
+        .iter()
+        .map(|(address, price)| {
+            let samples = histories.get(address)?;
+            change_24h_pct(samples, *price, now_ms)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_MS: i64 = 3600 * 1000;
+    const NOW: i64 = 1_700_000_000_000;
+
+    fn sample(hours_ago: f64, price: f64) -> PriceSample {
+        PriceSample {
+            ts_ms: NOW - (hours_ago * HOUR_MS as f64) as i64,
+            price,
+        }
+    }
+
+    #[test]
+    fn change_uses_sample_closest_to_24h_ago() {
+        let samples = vec![
+            sample(24.8, 0.08),
+            sample(24.1, 0.10),
+            sample(12.0, 0.50),
+            sample(0.5, 0.11),
+        ];
+        let pct = change_24h_pct(&samples, 0.12, NOW).expect("has history");
+        assert!((pct - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn change_is_omitted_without_enough_history() {
+        assert_eq!(change_24h_pct(&[], 1.0, NOW), None);
+        // 只有最近 20 小时的样本
+        let recent = vec![sample(20.0, 1.0), sample(1.0, 1.1)];
+        assert_eq!(change_24h_pct(&recent, 1.2, NOW), None);
+        let with_history = vec![sample(23.5, 1.0)];
+        assert_eq!(change_24h_pct(&with_history, 0.0, NOW), None);
+    }
+
+    #[test]
+    fn history_key_uses_lowercase_address() {
+        let address = Address::from([0xabu8; 20]);
+        assert_eq!(
+            history_key(address),
+            format!("price:history:0x{}", "ab".repeat(20))
+        );
+    }
+
+    #[test]
+    fn memoized_history_is_reused_only_while_throttled() {
+        let key = "test:price:history:memo".to_string();
+        assert_eq!(memoized_history(&key, NOW), None);
+        remember_history(key.clone(), vec![sample(0.0, 1.0)]);
+        assert_eq!(
+            memoized_history(&key, NOW + HOUR_MS / 60),
+            Some(vec![sample(0.0, 1.0)])
+        );
+        // 超过最小写入间隔后需要重新读取并写入 KV
+        assert_eq!(memoized_history(&key, NOW + HOUR_MS / 12), None);
+    }
+}
@@ -2,25 +2,242 @@ use alloy_primitives::{Address, Bytes, U256};
 use futures_util::future::{select, Either, FutureExt};
 use futures_util::pin_mut;
 use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::time::Duration;
-use worker::{console_warn, Delay, KvStore};
+use worker::{console_warn, Delay};
 use worker::{Fetch, Headers, Method, Request, RequestInit};
 
 use crate::error::{CroLensError, Result};
+use crate::infra::concurrency;
+use crate::infra::kv_stats::TrackedKv;
+use crate::infra::single_flight::SingleFlight;
 use crate::types;
 
 const RPC_CACHE_PREFIX: &str = "rpc:cache:";
+const RPC_BLOCK_NUMBER_KEY: &str = "cache:block_number";
 const RPC_CIRCUIT_OPEN_UNTIL_KEY: &str = "rpc:cb:open_until_ms";
-const RPC_CIRCUIT_FAIL_COUNT_KEY: &str = "rpc:cb:fail_count";
 const RPC_CIRCUIT_LAST_PROBE_KEY: &str = "rpc:cb:last_probe_ms";
 
 const RPC_DEFAULT_TIMEOUT_MS: u64 = 10_000;
 const RPC_DEFAULT_CACHE_TTL_SECS: u64 = 300;
+/// 最新区块号缓存有效期 (略短于 Cronos 出块间隔)
+const RPC_BLOCK_NUMBER_MAX_AGE_MS: i64 = 3_000;
+
+/// 允许发往上游节点的 JSON-RPC 方法；其余方法在发出请求前拒绝，
+/// 防止用户输入的方法名被透传给节点提供方
+const RPC_METHOD_ALLOWLIST: &[&str] = &[
+    "debug_traceCall",
+    "eth_blockNumber",
+    "eth_call",
+    "eth_estimateGas",
+    "eth_gasPrice",
+    "eth_getBlockByNumber",
+    "eth_getCode",
+    "eth_getLogs",
+    "eth_getProof",
+    "eth_getStorageAt",
+    "eth_getTransactionByHash",
+    "eth_getTransactionCount",
+    "eth_getTransactionReceipt",
+    "eth_maxPriorityFeePerGas",
+];
+
+fn ensure_method_allowed(method: &str) -> Result<()> {
+    if RPC_METHOD_ALLOWLIST.contains(&method) {
+        Ok(())
+    } else {
+        Err(CroLensError::invalid_params(format!(
+            "RPC method not allowed: {method}"
+        )))
+    }
+}
 
-const RPC_CIRCUIT_WINDOW_SECS: u64 = 300;
+/// 失败次数的统计窗口 (每个 isolate 单独计数)
+const RPC_CIRCUIT_WINDOW_MS: i64 = 300_000;
+/// isolate 内缓存的熔断状态超过该时长后重新从 KV 读取，以看到其它 isolate 的状态变化
+const RPC_CIRCUIT_SYNC_MS: i64 = 10_000;
 const RPC_CIRCUIT_OPEN_SECS: u64 = 300;
 const RPC_CIRCUIT_FAIL_THRESHOLD: i64 = 10;
 const RPC_CIRCUIT_PROBE_INTERVAL_MS: i64 = 60_000;
+// KV expiration_ttl 最小 60 秒
+const RPC_CIRCUIT_MIN_OPEN_SECS: u64 = 60;
+const RPC_CIRCUIT_MAX_OPEN_SECS: u64 = 3_600;
+const RPC_CIRCUIT_MIN_PROBE_MS: i64 = 1_000;
+
+/// 熔断参数；`fail_threshold == 0` 表示关闭熔断
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitConfig {
+    pub fail_threshold: i64,
+    pub open_secs: u64,
+    pub probe_interval_ms: i64,
+}
+
+impl Default for CircuitConfig {
+    fn default() -> Self {
+        Self {
+            fail_threshold: RPC_CIRCUIT_FAIL_THRESHOLD,
+            open_secs: RPC_CIRCUIT_OPEN_SECS,
+            probe_interval_ms: RPC_CIRCUIT_PROBE_INTERVAL_MS,
+        }
+    }
+}
+
+impl CircuitConfig {
+    pub fn from_env(env: &worker::Env) -> Self {
+        let var = |name: &str| env.var(name).ok().map(|v| v.to_string());
+        Self::parse(
+            var("RPC_CB_FAIL_THRESHOLD").as_deref(),
+            var("RPC_CB_OPEN_SECS").as_deref(),
+            var("RPC_CB_PROBE_MS").as_deref(),
+        )
+    }
+
+    /// 无法解析的值回退到默认值；open_secs 限制在 [60, 3600]，探测间隔不超过 open 时长
+    pub fn parse(
+        fail_threshold: Option<&str>,
+        open_secs: Option<&str>,
+        probe_interval_ms: Option<&str>,
+    ) -> Self {
+        let defaults = Self::default();
+        let fail_threshold = fail_threshold
+            .and_then(|v| v.trim().parse::<i64>().ok())
+            .filter(|v| *v >= 0)
+            .unwrap_or(defaults.fail_threshold);
+        let open_secs = open_secs
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(|v| v.clamp(RPC_CIRCUIT_MIN_OPEN_SECS, RPC_CIRCUIT_MAX_OPEN_SECS))
+            .unwrap_or(defaults.open_secs);
+        let probe_interval_ms = probe_interval_ms
+            .and_then(|v| v.trim().parse::<i64>().ok())
+            .unwrap_or(defaults.probe_interval_ms)
+            .clamp(RPC_CIRCUIT_MIN_PROBE_MS, (open_secs as i64) * 1000);
+        Self {
+            fail_threshold,
+            open_secs,
+            probe_interval_ms,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.fail_threshold > 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CircuitState {
+    Closed,
+    /// 拒绝请求，直到下一次允许探测
+    Open {
+        retry_after_secs: u32,
+    },
+    /// 放行一个探测请求：成功则关闭熔断，失败则重新打开
+    HalfOpen,
+}
+
+/// 根据 KV 中的 open_until / last_probe 计算熔断状态
+/// open 期满或距上次探测超过探测间隔时进入 half-open；上次探测仍在间隔内时保持 open，
+/// 保证同一时间只有一个探测请求
+fn circuit_state(
+    open_until_ms: Option<i64>,
+    last_probe_ms: Option<i64>,
+    now_ms: i64,
+    config: &CircuitConfig,
+) -> CircuitState {
+    let Some(open_until_ms) = open_until_ms else {
+        return CircuitState::Closed;
+    };
+    let next_probe_ms = last_probe_ms
+        .map(|p| p.saturating_add(config.probe_interval_ms))
+        .unwrap_or(now_ms);
+    if now_ms >= next_probe_ms {
+        return CircuitState::HalfOpen;
+    }
+    let wait_ms = if now_ms >= open_until_ms {
+        next_probe_ms - now_ms
+    } else {
+        (next_probe_ms.min(open_until_ms)) - now_ms
+    };
+    CircuitState::Open {
+        retry_after_secs: ((wait_ms + 999) / 1000).clamp(1, config.open_secs as i64) as u32,
+    }
+}
+
+/// 探测结果：成功 -> None (关闭)，失败 -> Some(新的 open_until)，即重新开始一个完整窗口
+fn probe_transition(success: bool, now_ms: i64, config: &CircuitConfig) -> Option<i64> {
+    if success {
+        None
+    } else {
+        Some(now_ms.saturating_add((config.open_secs as i64) * 1000))
+    }
+}
+
+/// 本 isolate 内的熔断状态：open_until / last_probe 为 KV 状态的缓存，失败次数只在内存中累计，
+/// 只有打开、探测、关闭这些状态变化才写 KV
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct CircuitMemo {
+    open_until_ms: Option<i64>,
+    last_probe_ms: Option<i64>,
+    /// 上次与 KV 同步的时间；None 表示尚未读取
+    synced_at_ms: Option<i64>,
+    window_start_ms: i64,
+    failures: i64,
+}
+
+impl CircuitMemo {
+    fn needs_sync(&self, now_ms: i64) -> bool {
+        self.synced_at_ms
+            .is_none_or(|at| now_ms.saturating_sub(at) >= RPC_CIRCUIT_SYNC_MS)
+    }
+
+    fn sync(&mut self, open_until_ms: Option<i64>, last_probe_ms: Option<i64>, now_ms: i64) {
+        self.open_until_ms = open_until_ms;
+        self.last_probe_ms = last_probe_ms;
+        self.synced_at_ms = Some(now_ms);
+    }
+
+    /// 记录一次非探测失败；窗口内达到阈值时返回 true (需要打开熔断) 并清零计数
+    fn record_failure(&mut self, now_ms: i64, config: &CircuitConfig) -> bool {
+        if self.failures == 0
+            || now_ms.saturating_sub(self.window_start_ms) >= RPC_CIRCUIT_WINDOW_MS
+        {
+            self.window_start_ms = now_ms;
+            self.failures = 0;
+        }
+        self.failures += 1;
+        if self.failures < config.fail_threshold {
+            return false;
+        }
+        self.failures = 0;
+        true
+    }
+
+    fn open(&mut self, open_until_ms: i64, now_ms: i64) {
+        self.sync(Some(open_until_ms), Some(now_ms), now_ms);
+        self.failures = 0;
+    }
+
+    fn close(&mut self, now_ms: i64) {
+        self.sync(None, None, now_ms);
+        self.failures = 0;
+    }
+}
+
+thread_local! {
+    /// 按 open_until 的完整 KV key (含 KV_PREFIX) 区分部署
+    static CIRCUITS: RefCell<HashMap<String, CircuitMemo>> = RefCell::new(HashMap::new());
+}
+
+fn load_circuit_memo(key: &str) -> CircuitMemo {
+    CIRCUITS.with(|memo| memo.borrow().get(key).copied().unwrap_or_default())
+}
+
+fn store_circuit_memo(key: &str, circuit: CircuitMemo) {
+    CIRCUITS.with(|memo| {
+        memo.borrow_mut().insert(key.to_string(), circuit);
+    });
+}
 
 #[derive(Clone)]
 pub struct RpcClient {
@@ -28,11 +245,19 @@ pub struct RpcClient {
     max_retries: u8,
     timeout_ms: u64,
     cache_ttl_secs: u64,
-    kv: Option<KvStore>,
+    kv: Option<TrackedKv>,
+    kv_prefix: String,
+    circuit: CircuitConfig,
+    /// eth_get_logs_chunked 同时进行的分段请求上限
+    max_concurrent_subrequests: usize,
+    // 同一请求内相同调用去重 (等待者共享同一个结果，错误保留原始类型)
+    inflight: SingleFlight<Result<Value>>,
+    /// 同一请求内读到的最新区块号，避免重复读取 KV
+    latest_block: BlockNumberMemo,
 }
 
 impl RpcClient {
-    pub fn try_new(env: &worker::Env, kv: Option<KvStore>) -> Option<Self> {
+    pub fn try_new(env: &worker::Env, kv: Option<TrackedKv>) -> Option<Self> {
         let url = env.var("BLOCKPI_RPC_URL").ok()?.to_string();
         if url.trim().is_empty() {
             return None;
@@ -61,13 +286,21 @@ impl RpcClient {
             timeout_ms,
             cache_ttl_secs,
             kv,
+            kv_prefix: crate::infra::kv_prefix(env),
+            circuit: CircuitConfig::from_env(env),
+            max_concurrent_subrequests: concurrency::max_concurrent_subrequests(env),
+            inflight: SingleFlight::default(),
+            latest_block: BlockNumberMemo::default(),
         })
     }
 
     pub async fn call(&self, method: &str, params: Value) -> Result<Value> {
-        // 简化版：跳过 circuit breaker 检查以减少 KV 延迟
-        // self.enforce_circuit(method).await?;
+        ensure_method_allowed(method)?;
+
+        // half-open 时本次调用作为探测请求
+        let probe = self.enforce_circuit(method).await?;
 
+        let cache_key = self.cache_key(method, &params);
         let payload = serde_json::json!({
             "jsonrpc": "2.0",
             "id": 1,
@@ -77,24 +310,63 @@ impl RpcClient {
 
         let body = serde_json::to_string(&payload)
             .map_err(|err| CroLensError::RpcError(err.to_string()))?;
+
+        // 并发的相同调用只发一次网络请求；内部 client 不持有 inflight map，避免循环引用
+        let mut client = self.clone();
+        client.inflight = SingleFlight::default();
+        let method = method.to_string();
+        let key = cache_key.clone();
+        self.inflight
+            .run(&cache_key, move || {
+                async move { client.call_uncoalesced(&method, &body, &key, probe).await }
+                    .boxed_local()
+            })
+            .await
+    }
+
+    async fn call_uncoalesced(
+        &self,
+        method: &str,
+        body: &str,
+        cache_key: &str,
+        probe: bool,
+    ) -> Result<Value> {
         let mut last_err: Option<CroLensError> = None;
-        let cache_key = self.cache_key(method, &body);
+        let mut failure_recorded = false;
+        let mut probe_closed = false;
 
         for _ in 0..self.max_retries {
-            match self.send_with_timeout(&body).await {
+            // JSON-RPC 错误对象 (revert、不支持的 block tag 等) 说明提供方正常应答，不计入熔断；
+            // 只有传输、超时和 HTTP 状态失败才算提供方故障，与 call_batch 一致
+            let outcome = match self.post_with_timeout(body).await {
+                Ok(raw) => {
+                    // 只有探测成功才需要写 KV (关闭熔断)，正常成功不产生额外 KV 操作
+                    if probe && !probe_closed {
+                        self.on_rpc_success().await;
+                        probe_closed = true;
+                    }
+                    extract_rpc_result(&raw)
+                }
+                Err(err) => {
+                    // 每次调用只记一次失败 (重试不重复计数)
+                    if !failure_recorded {
+                        self.on_rpc_failure(probe).await;
+                        failure_recorded = true;
+                    }
+                    Err(err)
+                }
+            };
+
+            match outcome {
                 Ok(v) => {
-                    // 跳过 on_rpc_success 的 KV 操作以减少延迟
-                    // self.on_rpc_success().await;
                     // 缓存写入不等待结果
-                    self.put_cache_fire_and_forget(&cache_key, &v);
+                    self.put_cache_fire_and_forget(cache_key, &v);
                     return Ok(v);
                 }
                 Err(err) => {
-                    // 跳过 on_rpc_failure 的 KV 操作以减少延迟
-                    // self.on_rpc_failure().await;
                     last_err = Some(err);
 
-                    if let Some(cached) = self.get_cache(&cache_key).await {
+                    if let Some(cached) = self.get_cache(cache_key).await {
                         console_warn!(
                             "[WARN] RPC failed for {}, returning cached response",
                             method
@@ -108,8 +380,8 @@ impl RpcClient {
         Err(last_err.unwrap_or_else(|| CroLensError::RpcError("RPC retries exhausted".to_string())))
     }
 
-    async fn send_with_timeout(&self, body: &str) -> Result<Value> {
-        let fut = self.send(body).fuse();
+    async fn post_with_timeout(&self, body: &str) -> Result<Value> {
+        let fut = self.post(body).fuse();
         let timeout = Delay::from(Duration::from_millis(self.timeout_ms)).fuse();
         pin_mut!(fut, timeout);
         match select(fut, timeout).await {
@@ -121,7 +393,8 @@ impl RpcClient {
         }
     }
 
-    async fn send(&self, body: &str) -> Result<Value> {
+    /// 发送请求并返回原始 JSON 响应 (单个对象或 batch 数组)
+    async fn post(&self, body: &str) -> Result<Value> {
         let headers = Headers::new();
         headers
             .set("Content-Type", "application/json")
@@ -138,36 +411,75 @@ impl RpcClient {
             .send()
             .await
             .map_err(|err| CroLensError::RpcError(err.to_string()))?;
-        let value: Value = resp
-            .json()
+        let status = resp.status_code();
+        if is_provider_failure_status(status) {
+            return Err(CroLensError::RpcError(format!("RPC HTTP status {status}")));
+        }
+        resp.json()
             .await
+            .map_err(|err| CroLensError::RpcError(err.to_string()))
+    }
+
+    /// JSON-RPC batch：一次 HTTP 请求发送多个调用，结果按请求顺序返回
+    /// 单项的 RPC 错误只影响该项；传输层失败时整个 batch 返回错误 (不使用 KV 缓存)
+    pub async fn call_batch(&self, calls: &[(&str, Value)]) -> Result<Vec<Result<Value>>> {
+        if calls.is_empty() {
+            return Ok(Vec::new());
+        }
+        for (method, _) in calls {
+            ensure_method_allowed(method)?;
+        }
+        let probe = self.enforce_circuit("batch").await?;
+
+        let payload: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(id, (method, params))| {
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": method,
+                    "params": params
+                })
+            })
+            .collect();
+        let body = serde_json::to_string(&payload)
             .map_err(|err| CroLensError::RpcError(err.to_string()))?;
 
-        if let Some(err) = value.get("error") {
-            let message = err
-                .get("message")
-                .and_then(|v| v.as_str())
-                .unwrap_or("Unknown RPC error");
-            return Err(CroLensError::RpcError(message.to_string()));
+        let mut last_err: Option<CroLensError> = None;
+        for _ in 0..self.max_retries {
+            match self.post_with_timeout(&body).await {
+                Ok(value) => {
+                    if probe {
+                        self.on_rpc_success().await;
+                    }
+                    return split_batch_response(value, calls.len());
+                }
+                Err(err) => {
+                    if last_err.is_none() {
+                        self.on_rpc_failure(probe).await;
+                    }
+                    last_err = Some(err);
+                }
+            }
         }
 
-        value
-            .get("result")
-            .cloned()
-            .ok_or_else(|| CroLensError::RpcError("Missing RPC result".to_string()))
+        Err(last_err.unwrap_or_else(|| CroLensError::RpcError("RPC batch failed".to_string())))
+    }
+
+    /// 按 method + 规范化 params 计算，键顺序不同的相同请求共用缓存
+    fn cache_key(&self, method: &str, params: &Value) -> String {
+        let hash = types::canonical_json_hash(params);
+        self.kv_key(&format!("{RPC_CACHE_PREFIX}{method}:{hash}"))
     }
 
-    fn cache_key(&self, method: &str, body: &str) -> String {
-        use std::hash::{Hash, Hasher};
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        body.hash(&mut hasher);
-        let hash = hasher.finish();
-        format!("{RPC_CACHE_PREFIX}{method}:{hash:016x}")
+    fn kv_key(&self, key: &str) -> String {
+        crate::infra::kv_key(&self.kv_prefix, key)
     }
 
     async fn get_cache(&self, key: &str) -> Option<Value> {
         let kv = self.kv.as_ref()?;
-        let raw = kv.get(key).text().await.ok().flatten()?;
+        let raw = kv.get_text(key).await.ok().flatten()?;
         serde_json::from_str::<Value>(&raw).ok()
     }
 
@@ -181,10 +493,7 @@ impl RpcClient {
             return;
         };
 
-        let Ok(put) = kv.put(key, raw) else {
-            return;
-        };
-        let _ = put.expiration_ttl(self.cache_ttl_secs).execute().await;
+        let _ = kv.put_text_with_ttl(key, raw, self.cache_ttl_secs).await;
     }
 
     fn put_cache_fire_and_forget(&self, key: &str, value: &Value) {
@@ -202,119 +511,169 @@ impl RpcClient {
         let kv = kv.clone();
         // Fire and forget - 不等待结果
         worker::wasm_bindgen_futures::spawn_local(async move {
-            if let Ok(put) = kv.put(&key, raw) {
-                let _ = put.expiration_ttl(ttl).execute().await;
-            }
+            let _ = kv.put_text_with_ttl(&key, raw, ttl).await;
         });
     }
 
-    async fn enforce_circuit(&self, method: &str) -> Result<()> {
-        let kv = match self.kv.as_ref() {
-            Some(v) => v,
-            None => return Ok(()),
-        };
-
-        let now = types::now_ms();
-        let open_until_ms = kv
-            .get(RPC_CIRCUIT_OPEN_UNTIL_KEY)
-            .text()
+    async fn read_kv_i64(&self, key: &str) -> Option<i64> {
+        let kv = self.kv.as_ref()?;
+        kv.get_text(&self.kv_key(key))
             .await
             .ok()
             .flatten()
-            .and_then(|v| v.parse::<i64>().ok());
+            .and_then(|v| v.parse::<i64>().ok())
+    }
 
-        let Some(open_until_ms) = open_until_ms else {
-            return Ok(());
+    /// 从 KV 读取熔断状态并更新 isolate 缓存
+    async fn sync_circuit(&self, circuit: &mut CircuitMemo, now: i64) {
+        let open_until_ms = self.read_kv_i64(RPC_CIRCUIT_OPEN_UNTIL_KEY).await;
+        let last_probe_ms = match open_until_ms {
+            Some(_) => self.read_kv_i64(RPC_CIRCUIT_LAST_PROBE_KEY).await,
+            None => None,
         };
+        circuit.sync(open_until_ms, last_probe_ms, now);
+    }
 
-        if now >= open_until_ms {
-            let _ = kv.delete(RPC_CIRCUIT_OPEN_UNTIL_KEY).await;
-            let _ = kv.delete(RPC_CIRCUIT_LAST_PROBE_KEY).await;
-            return Ok(());
+    /// 熔断打开时返回 503；half-open 时记录探测时间并返回 `true` (本次调用为探测)。
+    /// 状态优先取 isolate 缓存，过期后才读 KV；进入 half-open 前总是重新读取，避免多个 isolate 同时探测
+    async fn enforce_circuit(&self, method: &str) -> Result<bool> {
+        if !self.circuit.enabled() {
+            return Ok(false);
         }
+        let Some(kv) = self.kv.as_ref() else {
+            return Ok(false);
+        };
 
-        let last_probe_ms = kv
-            .get(RPC_CIRCUIT_LAST_PROBE_KEY)
-            .text()
-            .await
-            .ok()
-            .flatten()
-            .and_then(|v| v.parse::<i64>().ok())
-            .unwrap_or(0);
-
-        if now.saturating_sub(last_probe_ms) >= RPC_CIRCUIT_PROBE_INTERVAL_MS {
-            if let Ok(put) = kv.put(RPC_CIRCUIT_LAST_PROBE_KEY, now.to_string()) {
-                let _ = put.expiration_ttl(RPC_CIRCUIT_OPEN_SECS).execute().await;
-            }
-            return Ok(());
+        let now = types::now_ms();
+        let memo_key = self.kv_key(RPC_CIRCUIT_OPEN_UNTIL_KEY);
+        let mut circuit = load_circuit_memo(&memo_key);
+        let mut synced = false;
+        if circuit.needs_sync(now) {
+            self.sync_circuit(&mut circuit, now).await;
+            synced = true;
+        }
+        let mut state = circuit_state(
+            circuit.open_until_ms,
+            circuit.last_probe_ms,
+            now,
+            &self.circuit,
+        );
+        if state == CircuitState::HalfOpen && !synced {
+            self.sync_circuit(&mut circuit, now).await;
+            state = circuit_state(
+                circuit.open_until_ms,
+                circuit.last_probe_ms,
+                now,
+                &self.circuit,
+            );
         }
 
-        let retry_after_secs = ((open_until_ms.saturating_sub(now) / 1000).max(1) as u32)
-            .min(RPC_CIRCUIT_OPEN_SECS as u32);
+        let result = match state {
+            CircuitState::Closed => Ok(false),
+            CircuitState::HalfOpen => {
+                let _ = kv
+                    .put_text_with_ttl(
+                        &self.kv_key(RPC_CIRCUIT_LAST_PROBE_KEY),
+                        now.to_string(),
+                        self.circuit_ttl_secs(),
+                    )
+                    .await;
+                circuit.last_probe_ms = Some(now);
+                Ok(true)
+            }
+            CircuitState::Open { retry_after_secs } => Err(CroLensError::service_unavailable(
+                format!("RPC circuit open for {}", method),
+                Some(retry_after_secs),
+            )),
+        };
+        store_circuit_memo(&memo_key, circuit);
+        result
+    }
 
-        Err(CroLensError::service_unavailable(
-            format!("RPC circuit open for {}", method),
-            Some(retry_after_secs),
-        ))
+    /// open_until 需要在 open 期满后继续保留，以便进入 half-open 而不是直接关闭
+    fn circuit_ttl_secs(&self) -> u64 {
+        self.circuit.open_secs.saturating_mul(2)
     }
 
+    /// 探测成功：关闭熔断
     async fn on_rpc_success(&self) {
         let kv = match self.kv.as_ref() {
             Some(v) => v,
             None => return,
         };
-        let _ = kv.delete(RPC_CIRCUIT_FAIL_COUNT_KEY).await;
-        let _ = kv.delete(RPC_CIRCUIT_OPEN_UNTIL_KEY).await;
-        let _ = kv.delete(RPC_CIRCUIT_LAST_PROBE_KEY).await;
+        let now = types::now_ms();
+        if probe_transition(true, now, &self.circuit).is_none() {
+            let memo_key = self.kv_key(RPC_CIRCUIT_OPEN_UNTIL_KEY);
+            let mut circuit = load_circuit_memo(&memo_key);
+            circuit.close(now);
+            store_circuit_memo(&memo_key, circuit);
+            let _ = kv.delete(&memo_key).await;
+            let _ = kv.delete(&self.kv_key(RPC_CIRCUIT_LAST_PROBE_KEY)).await;
+        }
     }
 
-    async fn on_rpc_failure(&self) {
+    /// 非探测失败只在 isolate 内计数，达到阈值才写 KV 打开熔断
+    async fn on_rpc_failure(&self, probe: bool) {
+        if !self.circuit.enabled() {
+            return;
+        }
         let kv = match self.kv.as_ref() {
             Some(v) => v,
             None => return,
         };
+        let now = types::now_ms();
 
-        let current = kv
-            .get(RPC_CIRCUIT_FAIL_COUNT_KEY)
-            .text()
-            .await
-            .ok()
-            .flatten()
-            .and_then(|v| v.parse::<i64>().ok())
-            .unwrap_or(0);
-        let next = current.saturating_add(1);
-
-        if let Ok(put) = kv.put(RPC_CIRCUIT_FAIL_COUNT_KEY, next.to_string()) {
-            let _ = put.expiration_ttl(RPC_CIRCUIT_WINDOW_SECS).execute().await;
-        }
-
-        if next < RPC_CIRCUIT_FAIL_THRESHOLD {
+        // 探测失败：重新打开一个完整窗口
+        if probe {
+            if let Some(open_until_ms) = probe_transition(false, now, &self.circuit) {
+                self.open_circuit(kv, open_until_ms, now).await;
+            }
             return;
         }
 
-        let now = types::now_ms();
-        let open_until_ms = now.saturating_add((RPC_CIRCUIT_OPEN_SECS as i64) * 1000);
-        if let Ok(put) = kv.put(RPC_CIRCUIT_OPEN_UNTIL_KEY, open_until_ms.to_string()) {
-            let _ = put.expiration_ttl(RPC_CIRCUIT_OPEN_SECS).execute().await;
-        }
-        if let Ok(put) = kv.put(RPC_CIRCUIT_LAST_PROBE_KEY, now.to_string()) {
-            let _ = put.expiration_ttl(RPC_CIRCUIT_OPEN_SECS).execute().await;
+        let memo_key = self.kv_key(RPC_CIRCUIT_OPEN_UNTIL_KEY);
+        let mut circuit = load_circuit_memo(&memo_key);
+        let trip = circuit.record_failure(now, &self.circuit);
+        store_circuit_memo(&memo_key, circuit);
+        if trip {
+            let open_until_ms = now.saturating_add((self.circuit.open_secs as i64) * 1000);
+            self.open_circuit(kv, open_until_ms, now).await;
         }
     }
 
+    async fn open_circuit(&self, kv: &TrackedKv, open_until_ms: i64, now: i64) {
+        let ttl = self.circuit_ttl_secs();
+        let open_until_key = self.kv_key(RPC_CIRCUIT_OPEN_UNTIL_KEY);
+        let mut circuit = load_circuit_memo(&open_until_key);
+        circuit.open(open_until_ms, now);
+        store_circuit_memo(&open_until_key, circuit);
+        let _ = kv
+            .put_text_with_ttl(&open_until_key, open_until_ms.to_string(), ttl)
+            .await;
+        let _ = kv
+            .put_text_with_ttl(
+                &self.kv_key(RPC_CIRCUIT_LAST_PROBE_KEY),
+                now.to_string(),
+                ttl,
+            )
+            .await;
+    }
+
     pub async fn eth_call(&self, to: Address, data: Bytes) -> Result<Vec<u8>> {
+        self.eth_call_at(to, data, "latest").await
+    }
+
+    /// 在指定区块 (区块号 hex 或 "latest" 等标签) 执行 eth_call
+    pub async fn eth_call_at(&self, to: Address, data: Bytes, block_id: &str) -> Result<Vec<u8>> {
         let to_hex = to.to_string();
         let data_hex = types::bytes_to_hex0x(&data);
         let result = self
             .call(
                 "eth_call",
-                serde_json::json!([{ "to": to_hex, "data": data_hex }, "latest"]),
+                serde_json::json!([{ "to": to_hex, "data": data_hex }, block_id]),
             )
             .await?;
-        let output = result
-            .as_str()
-            .ok_or_else(|| CroLensError::RpcError("eth_call result is not a string".to_string()))?;
-        types::hex0x_to_bytes(output)
+        types::hex0x_to_bytes(expect_str("eth_call", &result)?)
     }
 
     /// 带 from 地址的 eth_call，用于模拟特定账户的交易
@@ -331,11 +690,10 @@ impl RpcClient {
             "data": data,
             "value": format!("0x{:x}", value),
         });
-        let result = self.call("eth_call", serde_json::json!([tx_obj, "latest"])).await?;
-        let output = result
-            .as_str()
-            .ok_or_else(|| CroLensError::RpcError("eth_call result is not a string".to_string()))?;
-        Ok(output.to_string())
+        let result = self
+            .call("eth_call", serde_json::json!([tx_obj, "latest"]))
+            .await?;
+        Ok(expect_str("eth_call", &result)?.to_string())
     }
 
     /// 估算 gas 消耗
@@ -352,12 +710,10 @@ impl RpcClient {
             "data": data,
             "value": format!("0x{:x}", value),
         });
-        let result = self.call("eth_estimateGas", serde_json::json!([tx_obj])).await?;
-        let hex_str = result
-            .as_str()
-            .ok_or_else(|| CroLensError::RpcError("eth_estimateGas result is not a string".to_string()))?;
-        u64::from_str_radix(hex_str.trim_start_matches("0x"), 16)
-            .map_err(|e| CroLensError::RpcError(format!("Failed to parse gas: {}", e)))
+        let result = self
+            .call("eth_estimateGas", serde_json::json!([tx_obj]))
+            .await?;
+        expect_u64("eth_estimateGas", &result)
     }
 
     /// 基础交易模拟 (使用 eth_call + eth_estimateGas)
@@ -403,13 +759,68 @@ impl RpcClient {
     }
 
     pub async fn eth_get_transaction_by_hash(&self, tx_hash: &str) -> Result<Value> {
-        self.call("eth_getTransactionByHash", serde_json::json!([tx_hash]))
-            .await
+        let result = self
+            .call("eth_getTransactionByHash", serde_json::json!([tx_hash]))
+            .await?;
+        expect_object_or_null("eth_getTransactionByHash", result)
     }
 
     pub async fn eth_get_transaction_receipt(&self, tx_hash: &str) -> Result<Value> {
-        self.call("eth_getTransactionReceipt", serde_json::json!([tx_hash]))
-            .await
+        let result = self
+            .call("eth_getTransactionReceipt", serde_json::json!([tx_hash]))
+            .await?;
+        expect_object_or_null("eth_getTransactionReceipt", result)
+    }
+
+    /// 账户 nonce；block_tag 为 "pending" 时包含 mempool 中未确认的交易
+    pub async fn eth_get_transaction_count(
+        &self,
+        address: Address,
+        block_tag: &str,
+    ) -> Result<u64> {
+        let result = self
+            .call(
+                "eth_getTransactionCount",
+                serde_json::json!([address.to_string(), block_tag]),
+            )
+            .await?;
+        expect_u64("eth_getTransactionCount", &result)
+    }
+
+    /// 获取最新区块号 (不经过缓存)
+    pub async fn eth_block_number(&self) -> Result<u64> {
+        let result = self.call("eth_blockNumber", serde_json::json!([])).await?;
+        expect_u64("eth_blockNumber", &result)
+    }
+
+    /// 各工具共享的最新区块号：先查本请求内的记录，再查 KV (`cache:block_number`)，
+    /// 都不新鲜时才调用 eth_blockNumber；有效期为 RPC_BLOCK_NUMBER_MAX_AGE_MS
+    pub async fn latest_block_number_cached(&self) -> Result<u64> {
+        let now = types::now_ms();
+        if let Some(block_number) = self.latest_block.get(now) {
+            return Ok(block_number);
+        }
+
+        let key = self.kv_key(RPC_BLOCK_NUMBER_KEY);
+        if let Some(cached) = self.get_cache(&key).await {
+            if let Some(block_number) = fresh_cached_block_number(&cached, now) {
+                // 沿用 KV 中的抓取时间，保证本请求内的有效期不超过 KV 缓存
+                let fetched_ms = cached
+                    .get("fetched_ms")
+                    .and_then(|v| v.as_i64())
+                    .unwrap_or(now);
+                self.latest_block.set(block_number, fetched_ms);
+                return Ok(block_number);
+            }
+        }
+
+        let block_number = self.eth_block_number().await?;
+        self.latest_block.set(block_number, now);
+        self.put_cache_fire_and_forget(
+            &key,
+            &serde_json::json!({ "block_number": block_number, "fetched_ms": now }),
+        );
+        Ok(block_number)
     }
 
     /// 获取区块信息
@@ -419,29 +830,95 @@ impl RpcClient {
         block_id: &str,
         include_txs: bool,
     ) -> Result<Value> {
-        self.call(
-            "eth_getBlockByNumber",
-            serde_json::json!([block_id, include_txs]),
+        let result = self
+            .call(
+                "eth_getBlockByNumber",
+                serde_json::json!([block_id, include_txs]),
+            )
+            .await?;
+        expect_object_or_null("eth_getBlockByNumber", result)
+    }
+
+    /// EIP-1186 账户/存储证明；部分节点不支持该方法，返回 RPC 错误
+    pub async fn eth_get_proof(
+        &self,
+        address: Address,
+        storage_keys: &[String],
+        block_id: &str,
+    ) -> Result<Value> {
+        let result = self
+            .call(
+                "eth_getProof",
+                serde_json::json!([address.to_string(), storage_keys, block_id]),
+            )
+            .await?;
+        expect_object("eth_getProof", result)
+    }
+
+    /// 按过滤条件获取日志
+    pub async fn eth_get_logs(&self, filter: &LogFilter) -> Result<Vec<Value>> {
+        let result = self
+            .call("eth_getLogs", serde_json::json!([filter.to_params()]))
+            .await?;
+        expect_array("eth_getLogs", result)
+    }
+
+    /// 按区块区间分段获取日志 (并发数受 MAX_CONCURRENT_SUBREQUESTS 限制)，
+    /// 避免单次跨度过大被 RPC 拒绝；结果按区间顺序拼接
+    pub async fn eth_get_logs_chunked(
+        &self,
+        filter: &LogFilter,
+        from_block: u64,
+        to_block: u64,
+        chunk_blocks: u64,
+    ) -> Result<Vec<Value>> {
+        self.eth_get_logs_chunked_many(
+            std::slice::from_ref(filter),
+            from_block,
+            to_block,
+            chunk_blocks,
         )
         .await
     }
 
+    /// 同 `eth_get_logs_chunked`，多个过滤器的所有分段共用同一个并发上限；
+    /// 结果按过滤器、再按区间顺序拼接
+    pub async fn eth_get_logs_chunked_many(
+        &self,
+        filters: &[LogFilter],
+        from_block: u64,
+        to_block: u64,
+        chunk_blocks: u64,
+    ) -> Result<Vec<Value>> {
+        let ranges = block_ranges(from_block, to_block, chunk_blocks);
+        let filters: Vec<LogFilter> = filters
+            .iter()
+            .flat_map(|filter| {
+                ranges
+                    .iter()
+                    .map(|&(from, to)| filter.clone().blocks(from, to))
+            })
+            .collect();
+        let chunks = concurrency::try_join_all_bounded(
+            filters.iter().map(|f| self.eth_get_logs(f)),
+            self.max_concurrent_subrequests,
+        )
+        .await?;
+        Ok(chunks.into_iter().flatten().collect())
+    }
+
     /// 获取当前 gas 价格
     pub async fn eth_gas_price(&self) -> Result<U256> {
         let result = self.call("eth_gasPrice", serde_json::json!([])).await?;
-        let hex_str = result
-            .as_str()
-            .ok_or_else(|| CroLensError::RpcError("eth_gasPrice result is not a string".to_string()))?;
-        types::parse_u256_hex(hex_str)
+        expect_quantity("eth_gasPrice", &result)
     }
 
     /// 获取 EIP-1559 优先费用
     pub async fn eth_max_priority_fee_per_gas(&self) -> Result<U256> {
-        let result = self.call("eth_maxPriorityFeePerGas", serde_json::json!([])).await?;
-        let hex_str = result
-            .as_str()
-            .ok_or_else(|| CroLensError::RpcError("eth_maxPriorityFeePerGas result is not a string".to_string()))?;
-        types::parse_u256_hex(hex_str)
+        let result = self
+            .call("eth_maxPriorityFeePerGas", serde_json::json!([]))
+            .await?;
+        expect_quantity("eth_maxPriorityFeePerGas", &result)
     }
 
     /// 使用 debug_traceCall 模拟交易执行
@@ -453,6 +930,7 @@ impl RpcClient {
         data: &str,
         value: U256,
         gas: Option<u64>,
+        limits: CallTraceLimits,
     ) -> Result<DebugTraceResult> {
         // 构建交易对象，包含 gas 限制
         let gas_limit = gas.unwrap_or(5_000_000); // 默认 5M gas
@@ -473,16 +951,26 @@ impl RpcClient {
         });
 
         let result = self
-            .call("debug_traceCall", serde_json::json!([tx_obj, "latest", tracer_config]))
+            .call(
+                "debug_traceCall",
+                serde_json::json!([tx_obj, "latest", tracer_config]),
+            )
             .await?;
+        let result = expect_object("debug_traceCall", result)?;
 
         // 解析 callTracer 结果
-        let output = result.get("output").and_then(|v| v.as_str()).unwrap_or("0x");
+        let output = result
+            .get("output")
+            .and_then(|v| v.as_str())
+            .unwrap_or("0x");
         let gas_used = result
             .get("gasUsed")
             .and_then(|v| v.as_str())
             .and_then(|v| u64::from_str_radix(v.trim_start_matches("0x"), 16).ok());
-        let error = result.get("error").and_then(|v| v.as_str()).map(|v| v.to_string());
+        let error = result
+            .get("error")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string());
         let revert_reason = result
             .get("revertReason")
             .and_then(|v| v.as_str())
@@ -491,8 +979,8 @@ impl RpcClient {
         // 提取日志
         let logs = extract_logs_from_trace(&result);
 
-        // 提取内部调用
-        let internal_calls = extract_internal_calls(&result);
+        // 提取内部调用 (按 limits 截断)
+        let (internal_calls, calls_truncated) = extract_internal_calls(&result, limits);
 
         let success = error.is_none() && revert_reason.is_none();
         let error_message = error.or(revert_reason);
@@ -503,6 +991,7 @@ impl RpcClient {
             output: output.to_string(),
             logs,
             internal_calls,
+            calls_truncated,
             error_message,
         })
     }
@@ -524,6 +1013,8 @@ pub struct DebugTraceResult {
     pub output: String,
     pub logs: Vec<DebugTraceLog>,
     pub internal_calls: Vec<InternalCall>,
+    /// 内部调用超过 CallTraceLimits 被截断
+    pub calls_truncated: bool,
     pub error_message: Option<String>,
 }
 
@@ -594,16 +1085,97 @@ fn extract_logs_recursive(trace: &Value, logs: &mut Vec<DebugTraceLog>) {
     }
 }
 
-/// 从 callTracer 结果中提取内部调用
-fn extract_internal_calls(trace: &Value) -> Vec<InternalCall> {
-    let mut calls = Vec::new();
-    extract_calls_recursive(trace, &mut calls, true);
-    calls
+const SIMULATION_MAX_CALL_DEPTH_DEFAULT: usize = 8;
+const SIMULATION_MAX_CALL_DEPTH_MAX: usize = 64;
+const SIMULATION_MAX_INTERNAL_CALLS_DEFAULT: usize = 500;
+const SIMULATION_MAX_INTERNAL_CALLS_MAX: usize = 5_000;
+
+/// 展开内部调用树的上限，避免深层嵌套的 trace 产生巨大的 internal_calls
+/// depth 1 为根调用直接发起的 (顶层) 调用；顶层调用总是保留，max_calls 只约束更深的调用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallTraceLimits {
+    pub max_depth: usize,
+    pub max_calls: usize,
+}
+
+impl Default for CallTraceLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: SIMULATION_MAX_CALL_DEPTH_DEFAULT,
+            max_calls: SIMULATION_MAX_INTERNAL_CALLS_DEFAULT,
+        }
+    }
+}
+
+impl CallTraceLimits {
+    /// 读取 SIMULATION_MAX_CALL_DEPTH / SIMULATION_MAX_INTERNAL_CALLS
+    pub fn from_env(env: &worker::Env) -> Self {
+        let var = |name: &str| env.var(name).ok().map(|v| v.to_string());
+        Self::parse(
+            var("SIMULATION_MAX_CALL_DEPTH").as_deref(),
+            var("SIMULATION_MAX_INTERNAL_CALLS").as_deref(),
+        )
+    }
+
+    /// 无效值使用默认值，超出范围时截断到 `1..=MAX`
+    pub fn parse(max_depth: Option<&str>, max_calls: Option<&str>) -> Self {
+        let parse = |value: Option<&str>, default: usize, max: usize| {
+            value
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .map(|v| v.clamp(1, max))
+                .unwrap_or(default)
+        };
+        Self {
+            max_depth: parse(
+                max_depth,
+                SIMULATION_MAX_CALL_DEPTH_DEFAULT,
+                SIMULATION_MAX_CALL_DEPTH_MAX,
+            ),
+            max_calls: parse(
+                max_calls,
+                SIMULATION_MAX_INTERNAL_CALLS_DEFAULT,
+                SIMULATION_MAX_INTERNAL_CALLS_MAX,
+            ),
+        }
+    }
+}
+
+/// 从 callTracer 结果中提取内部调用，返回 (调用列表, 是否被截断)
+fn extract_internal_calls(trace: &Value, limits: CallTraceLimits) -> (Vec<InternalCall>, bool) {
+    let top_level = trace
+        .get("calls")
+        .and_then(|v| v.as_array())
+        .map_or(0, |calls| calls.len());
+    let mut state = CallExtraction {
+        calls: Vec::new(),
+        limits,
+        nested_budget: limits.max_calls.saturating_sub(top_level),
+        truncated: false,
+    };
+    extract_calls_recursive(trace, &mut state, 0);
+    (state.calls, state.truncated)
+}
+
+struct CallExtraction {
+    calls: Vec<InternalCall>,
+    limits: CallTraceLimits,
+    /// 顶层以下的调用还可以输出的数量
+    nested_budget: usize,
+    truncated: bool,
 }
 
-fn extract_calls_recursive(trace: &Value, calls: &mut Vec<InternalCall>, is_root: bool) {
+fn extract_calls_recursive(trace: &Value, state: &mut CallExtraction, depth: usize) {
+    if depth > 1 {
+        // 超过深度或数量上限时丢弃该调用及其子调用
+        if depth > state.limits.max_depth || state.nested_budget == 0 {
+            state.truncated = true;
+            return;
+        }
+        state.nested_budget -= 1;
+    }
+
     // 跳过根调用，只提取内部调用
-    if !is_root {
+    if depth > 0 {
         let call_type = trace
             .get("type")
             .and_then(|v| v.as_str())
@@ -643,7 +1215,7 @@ fn extract_calls_recursive(trace: &Value, calls: &mut Vec<InternalCall>, is_root
             .and_then(|v| v.as_str())
             .map(|v| v.to_string());
 
-        calls.push(InternalCall {
+        state.calls.push(InternalCall {
             call_type,
             from,
             to,
@@ -658,16 +1230,626 @@ fn extract_calls_recursive(trace: &Value, calls: &mut Vec<InternalCall>, is_root
     // 递归处理子调用
     if let Some(sub_calls) = trace.get("calls").and_then(|v| v.as_array()) {
         for call in sub_calls {
-            extract_calls_recursive(call, calls, false);
+            extract_calls_recursive(call, state, depth + 1);
+        }
+    }
+}
+
+/// 缓存的区块号未超过 RPC_BLOCK_NUMBER_MAX_AGE_MS 时返回
+fn fresh_cached_block_number(cached: &Value, now_ms: i64) -> Option<u64> {
+    let block_number = cached.get("block_number")?.as_u64()?;
+    let fetched_ms = cached.get("fetched_ms")?.as_i64()?;
+    block_number_is_fresh(fetched_ms, now_ms).then_some(block_number)
+}
+
+fn block_number_is_fresh(fetched_ms: i64, now_ms: i64) -> bool {
+    let age_ms = now_ms.saturating_sub(fetched_ms);
+    (0..=RPC_BLOCK_NUMBER_MAX_AGE_MS).contains(&age_ms)
+}
+
+/// 最近读到的 (区块号, 抓取时间)；RpcClient 的所有 clone 共享，不跨请求
+#[derive(Clone, Default)]
+struct BlockNumberMemo {
+    latest: Rc<Cell<Option<(u64, i64)>>>,
+}
+
+impl BlockNumberMemo {
+    fn get(&self, now_ms: i64) -> Option<u64> {
+        let (block_number, fetched_ms) = self.latest.get()?;
+        block_number_is_fresh(fetched_ms, now_ms).then_some(block_number)
+    }
+
+    fn set(&self, block_number: u64, fetched_ms: i64) {
+        self.latest.set(Some((block_number, fetched_ms)));
+    }
+}
+
+/// 将闭区间 [from_block, to_block] 切分为每段最多 `chunk_blocks` 个区块的闭区间
+pub fn block_ranges(from_block: u64, to_block: u64, chunk_blocks: u64) -> Vec<(u64, u64)> {
+    let chunk_blocks = chunk_blocks.max(1);
+    let mut ranges = Vec::new();
+    let mut start = from_block;
+    while start <= to_block {
+        let end = start.saturating_add(chunk_blocks - 1).min(to_block);
+        ranges.push((start, end));
+        if end == u64::MAX {
+            break;
+        }
+        start = end + 1;
+    }
+    ranges
+}
+
+/// eth_getLogs 过滤条件构建器；topic 位置未设置时为 null (匹配任意值)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogFilter {
+    addresses: Vec<Address>,
+    topics: [Option<Vec<String>>; 4],
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+}
+
+impl LogFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 只匹配这些合约发出的日志 (可多次调用)
+    pub fn address(mut self, address: Address) -> Self {
+        self.addresses.push(address);
+        self
+    }
+
+    /// topic0 (事件签名哈希)
+    pub fn event(self, signature_topic: &str) -> Self {
+        self.topic(0, signature_topic)
+    }
+
+    /// 在 `index` 位置追加一个候选 topic (同一位置的多个值为 OR)；index 超过 3 时忽略
+    pub fn topic(mut self, index: usize, topic: &str) -> Self {
+        if let Some(slot) = self.topics.get_mut(index) {
+            slot.get_or_insert_with(Vec::new)
+                .push(topic.trim().to_lowercase());
+        }
+        self
+    }
+
+    /// indexed address 参数 (左补零到 32 字节)
+    pub fn topic_address(self, index: usize, address: Address) -> Self {
+        self.topic(index, &types::address_to_topic(address))
+    }
+
+    pub fn blocks(mut self, from_block: u64, to_block: u64) -> Self {
+        self.from_block = Some(from_block);
+        self.to_block = Some(to_block);
+        self
+    }
+
+    pub fn to_params(&self) -> Value {
+        let mut params = serde_json::Map::new();
+        match self.addresses.as_slice() {
+            [] => {}
+            [single] => {
+                params.insert("address".to_string(), Value::String(single.to_string()));
+            }
+            many => {
+                params.insert(
+                    "address".to_string(),
+                    many.iter().map(|a| Value::String(a.to_string())).collect(),
+                );
+            }
+        }
+
+        // 去掉末尾未设置的位置
+        let used = self
+            .topics
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |i| i + 1);
+        if used > 0 {
+            let topics: Vec<Value> = self.topics[..used]
+                .iter()
+                .map(|slot| match slot.as_deref() {
+                    None => Value::Null,
+                    Some([single]) => Value::String(single.clone()),
+                    Some(many) => many.iter().cloned().map(Value::String).collect(),
+                })
+                .collect();
+            params.insert("topics".to_string(), Value::Array(topics));
         }
+
+        if let Some(from) = self.from_block {
+            params.insert(
+                "fromBlock".to_string(),
+                Value::String(format!("0x{from:x}")),
+            );
+        }
+        if let Some(to) = self.to_block {
+            params.insert("toBlock".to_string(), Value::String(format!("0x{to:x}")));
+        }
+        Value::Object(params)
     }
 }
 
+/// 限流和服务端错误视为提供方故障 (计入熔断)；其余状态交给 JSON-RPC 响应体判断
+fn is_provider_failure_status(status: u16) -> bool {
+    status == 429 || status >= 500
+}
+
+/// 单个 JSON-RPC 响应对象 -> result / 错误
+pub(crate) fn extract_rpc_result(value: &Value) -> Result<Value> {
+    if let Some(err) = value.get("error") {
+        let message = err
+            .get("message")
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown RPC error");
+        return Err(CroLensError::RpcError(message.to_string()));
+    }
+
+    value
+        .get("result")
+        .cloned()
+        .ok_or_else(|| CroLensError::RpcError("Missing RPC result".to_string()))
+}
+
+/// 错误信息中描述 JSON 值的类型
+fn json_shape(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn unexpected_shape(method: &str, expected: &str, value: &Value) -> CroLensError {
+    CroLensError::RpcError(format!(
+        "{method} returned {}, expected {expected}",
+        json_shape(value)
+    ))
+}
+
+/// result 校验：提供方返回意外类型时给出包含方法名和实际类型的错误，而不是下游的通用解码失败
+pub(crate) fn expect_str<'a>(method: &str, value: &'a Value) -> Result<&'a str> {
+    value
+        .as_str()
+        .ok_or_else(|| unexpected_shape(method, "a hex string", value))
+}
+
+/// 十六进制数量 (0x 前缀)
+fn expect_quantity(method: &str, value: &Value) -> Result<U256> {
+    let raw = value
+        .as_str()
+        .ok_or_else(|| unexpected_shape(method, "a hex quantity", value))?;
+    types::parse_u256_hex(raw).map_err(|_| {
+        CroLensError::RpcError(format!(
+            "{method} returned a string that is not a hex quantity: {raw:?}"
+        ))
+    })
+}
+
+fn expect_u64(method: &str, value: &Value) -> Result<u64> {
+    let quantity = expect_quantity(method, value)?;
+    u64::try_from(quantity).map_err(|_| {
+        CroLensError::RpcError(format!("{method} returned {quantity}, which overflows u64"))
+    })
+}
+
+fn expect_array(method: &str, value: Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Array(items) => Ok(items),
+        other => Err(unexpected_shape(method, "an array", &other)),
+    }
+}
+
+fn expect_object(method: &str, value: Value) -> Result<Value> {
+    if value.is_object() {
+        Ok(value)
+    } else {
+        Err(unexpected_shape(method, "an object", &value))
+    }
+}
+
+/// 区块/交易/回执不存在时节点返回 null
+fn expect_object_or_null(method: &str, value: Value) -> Result<Value> {
+    if value.is_object() || value.is_null() {
+        Ok(value)
+    } else {
+        Err(unexpected_shape(method, "an object or null", &value))
+    }
+}
+
+/// batch 响应按 id 对齐 (节点可以乱序返回)；缺失的项单独报错
+fn split_batch_response(response: Value, len: usize) -> Result<Vec<Result<Value>>> {
+    let Value::Array(items) = response else {
+        // 整个 batch 被拒绝时节点返回单个错误对象
+        return Err(extract_rpc_result(&response).err().unwrap_or_else(|| {
+            CroLensError::RpcError("RPC batch response is not an array".to_string())
+        }));
+    };
+
+    let mut out: Vec<Option<Result<Value>>> = (0..len).map(|_| None).collect();
+    for item in items {
+        let Some(id) = item.get("id").and_then(|v| v.as_u64()) else {
+            continue;
+        };
+        if let Some(slot) = usize::try_from(id).ok().and_then(|i| out.get_mut(i)) {
+            *slot = Some(extract_rpc_result(&item));
+        }
+    }
+
+    Ok(out
+        .into_iter()
+        .map(|item| {
+            item.unwrap_or_else(|| {
+                Err(CroLensError::RpcError(
+                    "Missing RPC result for batch item".to_string(),
+                ))
+            })
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
 
+    // ============ log filter tests ============
+
+    #[test]
+    fn log_filter_pads_indexed_address_topics() {
+        let token = Address::repeat_byte(0x11);
+        let wallet = Address::repeat_byte(0xab);
+        let transfer = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+        let params = LogFilter::new()
+            .address(token)
+            .event(transfer)
+            .topic_address(2, wallet)
+            .blocks(100, 255)
+            .to_params();
+
+        assert_eq!(
+            params,
+            serde_json::json!({
+                "address": token.to_string(),
+                "topics": [transfer, null, types::address_to_topic(wallet)],
+                "fromBlock": "0x64",
+                "toBlock": "0xff"
+            })
+        );
+    }
+
+    #[test]
+    fn log_filter_ors_multiple_values_per_position() {
+        let a = Address::repeat_byte(0x01);
+        let b = Address::repeat_byte(0x02);
+        let params = LogFilter::new()
+            .topic_address(1, a)
+            .topic_address(1, b)
+            .address(a)
+            .address(b)
+            .to_params();
+
+        assert_eq!(params["topics"][0], Value::Null);
+        assert_eq!(params["topics"][1].as_array().map(Vec::len), Some(2));
+        assert_eq!(params["address"].as_array().map(Vec::len), Some(2));
+        assert!(params.get("fromBlock").is_none());
+        assert_eq!(LogFilter::new().topic(4, "0x01"), LogFilter::new());
+    }
+
+    #[test]
+    fn block_ranges_split_inclusive_window() {
+        assert_eq!(
+            block_ranges(100, 349, 100),
+            vec![(100, 199), (200, 299), (300, 349)]
+        );
+        assert_eq!(block_ranges(5, 5, 100), vec![(5, 5)]);
+        assert_eq!(block_ranges(10, 12, 0), vec![(10, 10), (11, 11), (12, 12)]);
+        assert!(block_ranges(10, 9, 100).is_empty());
+        assert_eq!(
+            block_ranges(u64::MAX - 1, u64::MAX, 10),
+            vec![(u64::MAX - 1, u64::MAX)]
+        );
+    }
+
+    // ============ result shape tests ============
+
+    fn rpc_message(err: CroLensError) -> String {
+        match err {
+            CroLensError::RpcError(message) => message,
+            other => other.to_string(),
+        }
+    }
+
+    #[test]
+    fn eth_call_object_result_names_method_and_shape() {
+        let err = expect_str("eth_call", &serde_json::json!({ "data": "0x" })).unwrap_err();
+        assert_eq!(
+            rpc_message(err),
+            "eth_call returned an object, expected a hex string"
+        );
+    }
+
+    #[test]
+    fn quantity_rejects_wrong_type_and_non_hex() {
+        let err = expect_u64("eth_blockNumber", &serde_json::json!(12345)).unwrap_err();
+        assert_eq!(
+            rpc_message(err),
+            "eth_blockNumber returned a number, expected a hex quantity"
+        );
+
+        let err = expect_quantity("eth_gasPrice", &serde_json::json!("fast")).unwrap_err();
+        assert!(
+            rpc_message(err).contains("eth_gasPrice returned a string that is not a hex quantity")
+        );
+
+        let huge = serde_json::json!(format!("0x{:x}", U256::from(u64::MAX) + U256::from(1u64)));
+        let err = expect_u64("eth_estimateGas", &huge).unwrap_err();
+        assert!(rpc_message(err).contains("overflows u64"));
+
+        assert_eq!(
+            expect_u64("eth_blockNumber", &serde_json::json!("0x10")).expect("valid"),
+            16
+        );
+    }
+
+    #[test]
+    fn logs_and_objects_are_shape_checked() {
+        let err = expect_array("eth_getLogs", serde_json::json!(null)).unwrap_err();
+        assert_eq!(
+            rpc_message(err),
+            "eth_getLogs returned null, expected an array"
+        );
+
+        let err = expect_object("debug_traceCall", serde_json::json!("0x")).unwrap_err();
+        assert_eq!(
+            rpc_message(err),
+            "debug_traceCall returned a string, expected an object"
+        );
+
+        // 不存在的区块/交易返回 null 是正常情况
+        expect_object_or_null("eth_getBlockByNumber", serde_json::json!(null)).expect("null ok");
+        let err =
+            expect_object_or_null("eth_getTransactionReceipt", serde_json::json!([])).unwrap_err();
+        assert_eq!(
+            rpc_message(err),
+            "eth_getTransactionReceipt returned an array, expected an object or null"
+        );
+    }
+
+    // ============ batch tests ============
+
+    #[test]
+    fn batch_response_is_aligned_by_id() {
+        let response = serde_json::json!([
+            { "jsonrpc": "2.0", "id": 1, "result": "0x2" },
+            { "jsonrpc": "2.0", "id": 0, "result": "0x1" }
+        ]);
+        let out = split_batch_response(response, 2).expect("array");
+        assert_eq!(out[0].as_ref().expect("ok"), "0x1");
+        assert_eq!(out[1].as_ref().expect("ok"), "0x2");
+    }
+
+    #[test]
+    fn batch_item_error_is_isolated() {
+        let response = serde_json::json!([
+            { "jsonrpc": "2.0", "id": 0, "error": { "code": -32000, "message": "boom" } },
+            { "jsonrpc": "2.0", "id": 1, "result": null }
+        ]);
+        let out = split_batch_response(response, 3).expect("array");
+        assert!(out[0]
+            .as_ref()
+            .is_err_and(|err| err.to_string().contains("boom")));
+        assert_eq!(out[1].as_ref().expect("ok"), &Value::Null);
+        assert!(out[2].is_err());
+    }
+
+    #[test]
+    fn batch_rejected_as_whole_is_error() {
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32600, "message": "batch too large" }
+        });
+        let err = split_batch_response(response, 2).unwrap_err();
+        assert!(err.to_string().contains("batch too large"));
+    }
+
+    #[test]
+    fn only_throttling_and_server_errors_count_as_provider_failures() {
+        assert!(is_provider_failure_status(429));
+        assert!(is_provider_failure_status(500));
+        assert!(is_provider_failure_status(503));
+        assert!(!is_provider_failure_status(200));
+        assert!(!is_provider_failure_status(400));
+    }
+
+    // ============ circuit breaker tests ============
+
+    fn cb() -> CircuitConfig {
+        CircuitConfig {
+            fail_threshold: 10,
+            open_secs: 300,
+            probe_interval_ms: 60_000,
+        }
+    }
+
+    #[test]
+    fn circuit_config_parses_and_validates() {
+        assert_eq!(
+            CircuitConfig::parse(None, None, None),
+            CircuitConfig::default()
+        );
+        let cfg = CircuitConfig::parse(Some("5"), Some("120"), Some("10000"));
+        assert_eq!(cfg.fail_threshold, 5);
+        assert_eq!(cfg.open_secs, 120);
+        assert_eq!(cfg.probe_interval_ms, 10_000);
+
+        // open_secs 下限 60 (KV TTL)，探测间隔不超过 open 时长
+        let cfg = CircuitConfig::parse(Some("abc"), Some("10"), Some("600000"));
+        assert_eq!(cfg.fail_threshold, RPC_CIRCUIT_FAIL_THRESHOLD);
+        assert_eq!(cfg.open_secs, 60);
+        assert_eq!(cfg.probe_interval_ms, 60_000);
+
+        assert!(!CircuitConfig::parse(Some("0"), None, None).enabled());
+        assert_eq!(
+            CircuitConfig::parse(Some("-1"), None, None).fail_threshold,
+            RPC_CIRCUIT_FAIL_THRESHOLD
+        );
+    }
+
+    #[test]
+    fn circuit_closed_without_open_marker() {
+        assert_eq!(
+            circuit_state(None, None, 1_000, &cb()),
+            CircuitState::Closed
+        );
+    }
+
+    #[test]
+    fn circuit_open_until_probe_interval() {
+        let opened = 1_000_000;
+        let state = circuit_state(Some(opened + 300_000), Some(opened), opened + 10_000, &cb());
+        assert_eq!(
+            state,
+            CircuitState::Open {
+                retry_after_secs: 50
+            }
+        );
+    }
+
+    #[test]
+    fn circuit_half_open_after_probe_interval() {
+        let opened = 1_000_000;
+        let state = circuit_state(Some(opened + 300_000), Some(opened), opened + 60_000, &cb());
+        assert_eq!(state, CircuitState::HalfOpen);
+    }
+
+    #[test]
+    fn circuit_half_open_after_open_period_elapses() {
+        let opened = 1_000_000;
+        let state = circuit_state(
+            Some(opened + 300_000),
+            Some(opened),
+            opened + 300_000,
+            &cb(),
+        );
+        assert_eq!(state, CircuitState::HalfOpen);
+    }
+
+    #[test]
+    fn in_flight_probe_keeps_circuit_open() {
+        // 另一请求刚开始探测：其余请求仍被拒绝，只放行一个探测
+        let now = 2_000_000;
+        let state = circuit_state(Some(now - 1_000), Some(now - 500), now, &cb());
+        assert!(matches!(state, CircuitState::Open { .. }));
+    }
+
+    #[test]
+    fn probe_success_closes_and_failure_reopens_fresh_window() {
+        let now = 5_000_000;
+        assert_eq!(probe_transition(true, now, &cb()), None);
+        assert_eq!(probe_transition(false, now, &cb()), Some(now + 300_000));
+
+        // 重新打开后新窗口从探测时刻开始计算
+        let open_until = probe_transition(false, now, &cb());
+        let state = circuit_state(open_until, Some(now), now + 1_000, &cb());
+        assert_eq!(
+            state,
+            CircuitState::Open {
+                retry_after_secs: 59
+            }
+        );
+    }
+
+    #[test]
+    fn circuit_memo_counts_failures_per_window() {
+        let mut circuit = CircuitMemo::default();
+        let config = CircuitConfig {
+            fail_threshold: 3,
+            ..cb()
+        };
+        assert!(!circuit.record_failure(1_000, &config));
+        assert!(!circuit.record_failure(2_000, &config));
+        // 窗口过期后重新计数
+        assert!(!circuit.record_failure(1_000 + RPC_CIRCUIT_WINDOW_MS, &config));
+        assert!(!circuit.record_failure(1_001 + RPC_CIRCUIT_WINDOW_MS, &config));
+        assert!(circuit.record_failure(1_002 + RPC_CIRCUIT_WINDOW_MS, &config));
+        assert_eq!(circuit.failures, 0);
+    }
+
+    #[test]
+    fn circuit_memo_syncs_only_after_interval() {
+        let mut circuit = CircuitMemo::default();
+        assert!(circuit.needs_sync(0));
+        circuit.sync(None, None, 1_000);
+        assert!(!circuit.needs_sync(1_000 + RPC_CIRCUIT_SYNC_MS - 1));
+        assert!(circuit.needs_sync(1_000 + RPC_CIRCUIT_SYNC_MS));
+
+        circuit.open(400_000, 100_000);
+        assert_eq!(circuit.open_until_ms, Some(400_000));
+        assert_eq!(circuit.last_probe_ms, Some(100_000));
+        assert!(!circuit.needs_sync(100_000));
+        circuit.close(200_000);
+        assert_eq!(
+            circuit_state(circuit.open_until_ms, circuit.last_probe_ms, 200_000, &cb()),
+            CircuitState::Closed
+        );
+    }
+
+    #[test]
+    fn allowlisted_methods_pass() {
+        for method in ["eth_call", "eth_blockNumber", "debug_traceCall"] {
+            assert!(ensure_method_allowed(method).is_ok(), "{method}");
+        }
+    }
+
+    #[test]
+    fn unlisted_methods_are_rejected() {
+        for method in [
+            "eth_sendRawTransaction",
+            "admin_peers",
+            "ETH_CALL",
+            "eth_call ",
+            "",
+        ] {
+            let err = ensure_method_allowed(method).unwrap_err();
+            assert!(matches!(err, CroLensError::InvalidParams(_)), "{method}");
+        }
+    }
+
+    #[test]
+    fn cached_block_number_used_while_fresh() {
+        let cached = json!({ "block_number": 100, "fetched_ms": 1_000 });
+        assert_eq!(fresh_cached_block_number(&cached, 3_000), Some(100));
+        assert_eq!(
+            fresh_cached_block_number(&cached, 1_000 + RPC_BLOCK_NUMBER_MAX_AGE_MS + 1),
+            None
+        );
+        assert_eq!(fresh_cached_block_number(&json!({}), 1_000), None);
+    }
+
+    #[test]
+    fn block_number_memo_hits_within_ttl_and_misses_after() {
+        let memo = BlockNumberMemo::default();
+        assert_eq!(memo.get(1_000), None);
+
+        memo.set(100, 1_000);
+        // clone 与原实例共享同一记录
+        let shared = memo.clone();
+        assert_eq!(shared.get(1_000), Some(100));
+        assert_eq!(shared.get(1_000 + RPC_BLOCK_NUMBER_MAX_AGE_MS), Some(100));
+        assert_eq!(memo.get(1_000 + RPC_BLOCK_NUMBER_MAX_AGE_MS + 1), None);
+        // 时钟回拨时不使用记录
+        assert_eq!(memo.get(999), None);
+
+        shared.set(101, 5_000);
+        assert_eq!(memo.get(5_500), Some(101));
+    }
+
     // ============ extract_logs_from_trace tests ============
 
     #[test]
@@ -687,7 +1869,10 @@ mod tests {
 
         let logs = extract_logs_from_trace(&trace);
         assert_eq!(logs.len(), 1);
-        assert_eq!(logs[0].address, "0xc21223249ca28397b4b6541dffaecc539bff0c59");
+        assert_eq!(
+            logs[0].address,
+            "0xc21223249ca28397b4b6541dffaecc539bff0c59"
+        );
         assert_eq!(logs[0].topics.len(), 2);
         assert_eq!(
             logs[0].topics[0],
@@ -731,9 +1916,18 @@ mod tests {
 
         let logs = extract_logs_from_trace(&trace);
         assert_eq!(logs.len(), 3);
-        assert_eq!(logs[0].address, "0x1111111111111111111111111111111111111111");
-        assert_eq!(logs[1].address, "0x2222222222222222222222222222222222222222");
-        assert_eq!(logs[2].address, "0x3333333333333333333333333333333333333333");
+        assert_eq!(
+            logs[0].address,
+            "0x1111111111111111111111111111111111111111"
+        );
+        assert_eq!(
+            logs[1].address,
+            "0x2222222222222222222222222222222222222222"
+        );
+        assert_eq!(
+            logs[2].address,
+            "0x3333333333333333333333333333333333333333"
+        );
     }
 
     #[test]
@@ -761,7 +1955,10 @@ mod tests {
 
         let logs = extract_logs_from_trace(&trace);
         assert_eq!(logs.len(), 1);
-        assert_eq!(logs[0].address, "0xabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd");
+        assert_eq!(
+            logs[0].address,
+            "0xabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd"
+        );
     }
 
     #[test]
@@ -808,7 +2005,7 @@ mod tests {
             ]
         });
 
-        let calls = extract_internal_calls(&trace);
+        let (calls, _) = extract_internal_calls(&trace, CallTraceLimits::default());
         assert_eq!(calls.len(), 1); // Root call is skipped
         assert_eq!(calls[0].call_type, "STATICCALL");
         assert_eq!(calls[0].from, "0x2222222222222222222222222222222222222222");
@@ -846,7 +2043,7 @@ mod tests {
             ]
         });
 
-        let calls = extract_internal_calls(&trace);
+        let (calls, _) = extract_internal_calls(&trace, CallTraceLimits::default());
         assert_eq!(calls.len(), 3);
         assert_eq!(calls[0].call_type, "CALL");
         // extract_calls_recursive lowercases addresses
@@ -866,7 +2063,7 @@ mod tests {
             "to": "0xc21223249CA28397B4B6541dFFaEcC539bfF0c59"
         });
 
-        let calls = extract_internal_calls(&trace);
+        let (calls, _) = extract_internal_calls(&trace, CallTraceLimits::default());
         assert!(calls.is_empty()); // No internal calls, just root
     }
 
@@ -887,7 +2084,7 @@ mod tests {
             ]
         });
 
-        let calls = extract_internal_calls(&trace);
+        let (calls, _) = extract_internal_calls(&trace, CallTraceLimits::default());
         assert_eq!(calls.len(), 1);
         assert_eq!(calls[0].error, Some("execution reverted".to_string()));
     }
@@ -911,12 +2108,110 @@ mod tests {
             ]
         });
 
-        let calls = extract_internal_calls(&trace);
+        let (calls, _) = extract_internal_calls(&trace, CallTraceLimits::default());
         assert_eq!(calls.len(), 2);
         assert_eq!(calls[0].gas_used, Some(4660));
         assert_eq!(calls[1].gas_used, None); // invalid parsing returns None
     }
 
+    /// `to` 为 0x{tag}{tag}... 的调用，带有给定子调用
+    fn traced_call(tag: u8, calls: Vec<Value>) -> Value {
+        json!({
+            "type": "CALL",
+            "from": "0x1111111111111111111111111111111111111111",
+            "to": format!("0x{}", format!("{tag:02x}").repeat(20)),
+            "calls": calls
+        })
+    }
+
+    /// depth 1 起的链式嵌套调用，最深一层 tag 为 first_tag + levels - 1
+    fn call_chain(first_tag: u8, levels: u8) -> Value {
+        (first_tag..first_tag + levels)
+            .rev()
+            .fold(None, |child: Option<Value>, tag| {
+                Some(traced_call(tag, child.into_iter().collect()))
+            })
+            .expect("at least one level")
+    }
+
+    fn call_tags(calls: &[InternalCall]) -> Vec<String> {
+        calls.iter().map(|c| c.to[2..4].to_string()).collect()
+    }
+
+    #[test]
+    fn test_extract_internal_calls_truncates_at_max_depth() {
+        let trace = traced_call(0x01, vec![call_chain(0x10, 5), traced_call(0x20, vec![])]);
+        let limits = CallTraceLimits {
+            max_depth: 3,
+            max_calls: 100,
+        };
+        let (calls, truncated) = extract_internal_calls(&trace, limits);
+        assert!(truncated);
+        // depth 1..=3 的调用保留，深层的 0x13/0x14 被丢弃，后续顶层调用 0x20 仍在
+        assert_eq!(call_tags(&calls), vec!["10", "11", "12", "20"]);
+
+        let (calls, truncated) = extract_internal_calls(&trace, CallTraceLimits::default());
+        assert!(!truncated);
+        assert_eq!(calls.len(), 6);
+    }
+
+    #[test]
+    fn test_extract_internal_calls_count_limit_keeps_top_level() {
+        let trace = traced_call(
+            0x01,
+            vec![
+                traced_call(
+                    0x10,
+                    vec![traced_call(0x11, vec![]), traced_call(0x12, vec![])],
+                ),
+                traced_call(0x20, vec![traced_call(0x21, vec![])]),
+                traced_call(0x30, vec![]),
+            ],
+        );
+        let limits = CallTraceLimits {
+            max_depth: 8,
+            max_calls: 4,
+        };
+        let (calls, truncated) = extract_internal_calls(&trace, limits);
+        assert!(truncated);
+        assert_eq!(call_tags(&calls), vec!["10", "11", "20", "30"]);
+
+        // 上限小于顶层调用数时仍输出全部顶层调用
+        let limits = CallTraceLimits {
+            max_depth: 8,
+            max_calls: 1,
+        };
+        let (calls, truncated) = extract_internal_calls(&trace, limits);
+        assert!(truncated);
+        assert_eq!(call_tags(&calls), vec!["10", "20", "30"]);
+    }
+
+    #[test]
+    fn test_call_trace_limits_parsing() {
+        assert_eq!(
+            CallTraceLimits::parse(None, None),
+            CallTraceLimits::default()
+        );
+        assert_eq!(
+            CallTraceLimits::parse(Some(" 4 "), Some("50")),
+            CallTraceLimits {
+                max_depth: 4,
+                max_calls: 50
+            }
+        );
+        assert_eq!(
+            CallTraceLimits::parse(Some("0"), Some("999999")),
+            CallTraceLimits {
+                max_depth: 1,
+                max_calls: SIMULATION_MAX_INTERNAL_CALLS_MAX
+            }
+        );
+        assert_eq!(
+            CallTraceLimits::parse(Some("deep"), Some("-1")),
+            CallTraceLimits::default()
+        );
+    }
+
     // ============ DebugTraceResult parsing tests ============
 
     #[test]
@@ -927,7 +2222,10 @@ mod tests {
             "gasUsed": "0x5208"
         });
 
-        let error = result_success.get("error").and_then(|v| v.as_str()).map(|v| v.to_string());
+        let error = result_success
+            .get("error")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string());
         let revert_reason = result_success
             .get("revertReason")
             .and_then(|v| v.as_str())
@@ -940,7 +2238,10 @@ mod tests {
             "error": "execution reverted"
         });
 
-        let error = result_error.get("error").and_then(|v| v.as_str()).map(|v| v.to_string());
+        let error = result_error
+            .get("error")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string());
         let revert_reason = result_error
             .get("revertReason")
             .and_then(|v| v.as_str())
@@ -970,7 +2271,10 @@ mod tests {
             "output": "0x0000000000000000000000000000000000000000000000000000000000000001"
         });
 
-        let output = result.get("output").and_then(|v| v.as_str()).unwrap_or("0x");
+        let output = result
+            .get("output")
+            .and_then(|v| v.as_str())
+            .unwrap_or("0x");
         assert_eq!(
             output,
             "0x0000000000000000000000000000000000000000000000000000000000000001"
@@ -1014,10 +2318,13 @@ mod tests {
 
         let logs = extract_logs_from_trace(&trace);
         assert_eq!(logs.len(), 1);
-        assert_eq!(logs[0].address, "0xc21223249ca28397b4b6541dffaecc539bff0c59");
+        assert_eq!(
+            logs[0].address,
+            "0xc21223249ca28397b4b6541dffaecc539bff0c59"
+        );
         assert_eq!(logs[0].topics.len(), 3);
 
-        let calls = extract_internal_calls(&trace);
+        let (calls, _) = extract_internal_calls(&trace, CallTraceLimits::default());
         assert!(calls.is_empty()); // Simple transfer has no internal calls
     }
 
@@ -1074,7 +2381,7 @@ mod tests {
         let logs = extract_logs_from_trace(&trace);
         assert_eq!(logs.len(), 2); // Swap event + Transfer event
 
-        let calls = extract_internal_calls(&trace);
+        let (calls, _) = extract_internal_calls(&trace, CallTraceLimits::default());
         assert_eq!(calls.len(), 3); // router->pair, pair->token0, pair->token1
         assert_eq!(calls[0].to, "0xpair");
         assert_eq!(calls[1].to, "0xtoken0");
@@ -0,0 +1,181 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::infra;
+
+/// 存入 KV 环形缓冲区的时间序列样本
+pub trait Sample: Copy + Serialize + DeserializeOwned {
+    fn ts_ms(&self) -> i64;
+    /// 解析时丢弃无效样本 (非有限值、负值等)
+    fn is_valid(&self) -> bool;
+}
+
+/// 保存在单个 KV key 中的样本环形缓冲区：只保留窗口内、最多 `max_len` 个样本，
+/// 两次写入之间至少间隔 `min_interval_ms`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleRing {
+    pub window_ms: i64,
+    pub min_interval_ms: i64,
+    pub max_len: usize,
+}
+
+impl SampleRing {
+    pub const fn new(window_ms: i64, min_interval_ms: i64, max_len: usize) -> Self {
+        Self {
+            window_ms,
+            min_interval_ms,
+            max_len,
+        }
+    }
+
+    /// KV 条目的 TTL：窗口过后整个缓冲区都已过期
+    pub fn ttl_secs(&self) -> u64 {
+        (self.window_ms / 1000).max(60) as u64
+    }
+
+    pub fn parse<T: Sample>(&self, raw: &str) -> Vec<T> {
+        serde_json::from_str::<Vec<T>>(raw)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(Sample::is_valid)
+            .collect()
+    }
+
+    /// 窗口内的样本 (按时间顺序)
+    pub fn window<T: Sample>(&self, samples: &[T], now_ms: i64) -> Vec<T> {
+        samples
+            .iter()
+            .filter(|s| now_ms.saturating_sub(s.ts_ms()) <= self.window_ms)
+            .copied()
+            .collect()
+    }
+
+    /// 距上一个样本不足最小间隔时不需要写入
+    pub fn is_throttled<T: Sample>(&self, samples: &[T], ts_ms: i64) -> bool {
+        samples
+            .last()
+            .is_some_and(|last| ts_ms.saturating_sub(last.ts_ms()) < self.min_interval_ms)
+    }
+
+    /// 追加样本：丢弃窗口外的旧样本并保留最新的 max_len 个；被节流时返回 None (无需写入)
+    pub fn push<T: Sample>(&self, samples: &[T], sample: T) -> Option<Vec<T>> {
+        if self.is_throttled(samples, sample.ts_ms()) {
+            return None;
+        }
+        let mut next = self.window(samples, sample.ts_ms());
+        next.push(sample);
+        let overflow = next.len().saturating_sub(self.max_len);
+        next.drain(..overflow);
+        Some(next)
+    }
+
+    /// 读取缓冲区 (已加 kv_prefix 的 key)；缺失或无法解析时为空
+    pub async fn load<T: Sample>(&self, services: &infra::Services, key: &str) -> Vec<T> {
+        services
+            .kv_get_text(key)
+            .await
+            .map(|raw| self.parse(&raw))
+            .unwrap_or_default()
+    }
+
+    /// 尽力写回缓冲区
+    pub async fn store<T: Sample>(&self, services: &infra::Services, key: &str, samples: &[T]) {
+        if let Ok(json) = serde_json::to_string(samples) {
+            services.kv_put_text(key, json, self.ttl_secs()).await;
+        }
+    }
+
+    /// 读取、追加并在未被节流时写回；返回追加后的全部样本
+    pub async fn record<T: Sample>(
+        &self,
+        services: &infra::Services,
+        key: &str,
+        sample: T,
+    ) -> Vec<T> {
+        let samples = self.load(services, key).await;
+        match self.push(&samples, sample) {
+            Some(next) => {
+                self.store(services, key, &next).await;
+                next
+            }
+            None => samples,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        ts_ms: i64,
+        value: f64,
+    }
+
+    impl Sample for Point {
+        fn ts_ms(&self) -> i64 {
+            self.ts_ms
+        }
+
+        fn is_valid(&self) -> bool {
+            self.value.is_finite() && self.value >= 0.0
+        }
+    }
+
+    const RING: SampleRing = SampleRing::new(100, 10, 3);
+
+    fn point(ts_ms: i64) -> Point {
+        Point { ts_ms, value: 1.0 }
+    }
+
+    #[test]
+    fn push_throttles_evicts_and_caps() {
+        let existing = vec![point(0), point(50)];
+        assert!(RING.is_throttled(&existing, 59));
+        assert_eq!(RING.push(&existing, point(59)), None);
+        assert_eq!(
+            RING.push(&existing, point(60)),
+            Some(vec![point(0), point(50), point(60)])
+        );
+        // 超出窗口的旧样本被丢弃
+        assert_eq!(
+            RING.push(&existing, point(120)),
+            Some(vec![point(50), point(120)])
+        );
+        // 超出容量时保留最新的样本
+        let full = vec![point(0), point(10), point(20)];
+        assert_eq!(
+            RING.push(&full, point(30)),
+            Some(vec![point(10), point(20), point(30)])
+        );
+        assert!(!RING.is_throttled::<Point>(&[], 0));
+    }
+
+    #[test]
+    fn window_keeps_recent_samples() {
+        let samples = vec![point(0), point(50), point(150)];
+        assert_eq!(RING.window(&samples, 150), vec![point(50), point(150)]);
+        assert!(RING.window(&samples, 500).is_empty());
+    }
+
+    #[test]
+    fn parse_ignores_invalid_payloads() {
+        assert!(RING.parse::<Point>("not json").is_empty());
+        let raw = r#"[{"ts_ms":1,"value":5.0},{"ts_ms":2,"value":-1.0}]"#;
+        assert_eq!(
+            RING.parse::<Point>(raw),
+            vec![Point {
+                ts_ms: 1,
+                value: 5.0
+            }]
+        );
+    }
+
+    #[test]
+    fn ttl_covers_window_with_kv_minimum() {
+        assert_eq!(SampleRing::new(24 * 3600 * 1000, 1, 1).ttl_secs(), 86_400);
+        assert_eq!(RING.ttl_secs(), 60);
+    }
+}
@@ -0,0 +1,71 @@
+use serde_json::Value;
+use worker::d1::D1Type;
+use worker::D1Database;
+
+use crate::error::{CroLensError, Result};
+use crate::infra;
+
+/// 同一选择器最多返回的候选签名数 (4-byte 选择器可能碰撞)
+const MAX_SIGNATURE_CANDIDATES: usize = 5;
+
+/// function_signatures 表中的一行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureEntry {
+    pub signature: String,
+    pub action: Option<String>,
+}
+
+impl SignatureEntry {
+    /// 签名括号前的函数名，例如 `multicall(bytes[])` -> `multicall`
+    pub fn method_name(&self) -> &str {
+        self.signature.split('(').next().unwrap_or_default()
+    }
+}
+
+/// 查询选择器对应的所有签名，按签名排序保证结果稳定
+pub async fn lookup_signatures(db: &D1Database, selector: &str) -> Result<Vec<SignatureEntry>> {
+    let selector = selector.to_ascii_lowercase();
+    let selector_arg = D1Type::Text(&selector);
+    let limit_arg = D1Type::Integer(MAX_SIGNATURE_CANDIDATES as i32);
+    let statement = db
+        .prepare(
+            "SELECT signature, action FROM function_signatures \
+             WHERE selector = ?1 ORDER BY signature LIMIT ?2",
+        )
+        .bind_refs([&selector_arg, &limit_arg])
+        .map_err(|err| CroLensError::DbError(err.to_string()))?;
+    let result = infra::db::run("lookup_signatures", || statement.all()).await?;
+    let rows: Vec<Value> = result
+        .results()
+        .map_err(|err| CroLensError::DbError(err.to_string()))?;
+    Ok(rows.iter().filter_map(signature_entry_from_row).collect())
+}
+
+pub fn signature_entry_from_row(row: &Value) -> Option<SignatureEntry> {
+    let signature = row.get("signature").and_then(|v| v.as_str())?.trim();
+    if signature.is_empty() {
+        return None;
+    }
+    Some(SignatureEntry {
+        signature: signature.to_string(),
+        action: row
+            .get("action")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_parse_into_entries() {
+        let row = serde_json::json!({ "signature": "multicall(bytes[])", "action": null });
+        let entry = signature_entry_from_row(&row).expect("row");
+        assert_eq!(entry.method_name(), "multicall");
+        assert_eq!(entry.action, None);
+        assert!(signature_entry_from_row(&serde_json::json!({ "signature": " " })).is_none());
+        assert!(signature_entry_from_row(&serde_json::json!({ "action": "Swap" })).is_none());
+    }
+}
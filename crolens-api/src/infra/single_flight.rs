@@ -0,0 +1,188 @@
+//! Single-flight: 同一请求内并发的相同调用只发起一次，其余等待并共享结果。
+//!
+//! Workers 中每个请求通常运行在独立上下文，因此这里只在一个 `Services`
+//! (以及从它 clone 出的 RpcClient/MulticallClient) 内生效，不跨请求共享。
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use futures_util::future::{FutureExt, LocalBoxFuture, Shared};
+
+type InFlight<T> = Shared<LocalBoxFuture<'static, T>>;
+
+pub struct SingleFlight<T: Clone + 'static> {
+    inflight: Rc<RefCell<HashMap<String, InFlight<T>>>>,
+}
+
+impl<T: Clone + 'static> Clone for SingleFlight<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inflight: Rc::clone(&self.inflight),
+        }
+    }
+}
+
+impl<T: Clone + 'static> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self {
+            inflight: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+}
+
+impl<T: Clone + 'static> SingleFlight<T> {
+    /// 若 `key` 已有进行中的调用则等待它，否则用 `make` 创建并登记
+    pub async fn run<F>(&self, key: &str, make: F) -> T
+    where
+        F: FnOnce() -> LocalBoxFuture<'static, T>,
+    {
+        let existing = self.inflight.borrow().get(key).cloned();
+        let fut = match existing {
+            Some(fut) => fut,
+            None => {
+                let fut = make().shared();
+                self.inflight
+                    .borrow_mut()
+                    .insert(key.to_string(), fut.clone());
+                fut
+            }
+        };
+
+        let out = fut.clone().await;
+
+        // 只移除自己等待的那一次调用，避免误删之后新登记的调用
+        let mut inflight = self.inflight.borrow_mut();
+        if inflight
+            .get(key)
+            .map(|current| Shared::ptr_eq(current, &fut))
+            .unwrap_or(false)
+        {
+            inflight.remove(key);
+        }
+
+        out
+    }
+
+    #[cfg(test)]
+    fn in_flight(&self) -> usize {
+        self.inflight.borrow().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::task::{Context, Poll};
+
+    fn poll_once<F: std::future::Future + Unpin>(fut: &mut F) -> Poll<F::Output> {
+        let mut cx = Context::from_waker(futures_util::task::noop_waker_ref());
+        fut.poll_unpin(&mut cx)
+    }
+
+    /// 在 `ready` 置位前一直 Pending 的底层调用，记录被执行的次数
+    fn gated_call(ready: Rc<Cell<bool>>, calls: Rc<Cell<u32>>) -> LocalBoxFuture<'static, u64> {
+        async move {
+            calls.set(calls.get() + 1);
+            futures_util::future::poll_fn(|_| {
+                if ready.get() {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await;
+            42
+        }
+        .boxed_local()
+    }
+
+    #[test]
+    fn concurrent_identical_calls_share_one_future() {
+        let sf: SingleFlight<u64> = SingleFlight::default();
+        let ready = Rc::new(Cell::new(false));
+        let calls = Rc::new(Cell::new(0));
+
+        let mut first = sf
+            .run("eth_call:abc", || gated_call(ready.clone(), calls.clone()))
+            .boxed_local();
+        let mut second = sf
+            .run("eth_call:abc", || gated_call(ready.clone(), calls.clone()))
+            .boxed_local();
+
+        assert!(poll_once(&mut first).is_pending());
+        assert!(poll_once(&mut second).is_pending());
+        assert_eq!(sf.in_flight(), 1);
+
+        ready.set(true);
+        assert_eq!(poll_once(&mut first), Poll::Ready(42));
+        assert_eq!(poll_once(&mut second), Poll::Ready(42));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(sf.in_flight(), 0);
+    }
+
+    #[test]
+    fn shared_errors_keep_their_variant() {
+        let sf: SingleFlight<crate::error::Result<u64>> = SingleFlight::default();
+        let ready = Rc::new(Cell::new(false));
+        let calls = Rc::new(Cell::new(0));
+        let make = || {
+            let call = gated_call(ready.clone(), calls.clone());
+            async move {
+                call.await;
+                Err(crate::error::CroLensError::Timeout { timeout_ms: 10_000 })
+            }
+            .boxed_local()
+        };
+
+        let mut first = sf.run("eth_call:err", make).boxed_local();
+        let mut second = sf.run("eth_call:err", make).boxed_local();
+        assert!(poll_once(&mut first).is_pending());
+        assert!(poll_once(&mut second).is_pending());
+
+        ready.set(true);
+        for fut in [&mut first, &mut second] {
+            assert!(matches!(
+                poll_once(fut),
+                Poll::Ready(Err(crate::error::CroLensError::Timeout {
+                    timeout_ms: 10_000
+                }))
+            ));
+        }
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn different_keys_run_independently() {
+        let sf: SingleFlight<u64> = SingleFlight::default();
+        let ready = Rc::new(Cell::new(true));
+        let calls = Rc::new(Cell::new(0));
+
+        let mut a = sf
+            .run("a", || gated_call(ready.clone(), calls.clone()))
+            .boxed_local();
+        let mut b = sf
+            .run("b", || gated_call(ready.clone(), calls.clone()))
+            .boxed_local();
+
+        assert_eq!(poll_once(&mut a), Poll::Ready(42));
+        assert_eq!(poll_once(&mut b), Poll::Ready(42));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn completed_call_is_not_reused() {
+        let sf: SingleFlight<u64> = SingleFlight::default();
+        let ready = Rc::new(Cell::new(true));
+        let calls = Rc::new(Cell::new(0));
+
+        for _ in 0..2 {
+            let mut fut = sf
+                .run("k", || gated_call(ready.clone(), calls.clone()))
+                .boxed_local();
+            assert_eq!(poll_once(&mut fut), Poll::Ready(42));
+        }
+        assert_eq!(calls.get(), 2);
+    }
+}
@@ -215,24 +215,16 @@ macro_rules! log_info {
 #[macro_export]
 macro_rules! log_warn {
     ($trace_id:expr, $message:expr) => {
-        $crate::infra::log::LogEntry::new(
-            $crate::infra::log::LogLevel::Warn,
-            $trace_id,
-            $message,
-        )
-        .emit()
+        $crate::infra::log::LogEntry::new($crate::infra::log::LogLevel::Warn, $trace_id, $message)
+            .emit()
     };
 }
 
 #[macro_export]
 macro_rules! log_error {
     ($trace_id:expr, $message:expr) => {
-        $crate::infra::log::LogEntry::new(
-            $crate::infra::log::LogLevel::Error,
-            $trace_id,
-            $message,
-        )
-        .emit()
+        $crate::infra::log::LogEntry::new($crate::infra::log::LogLevel::Error, $trace_id, $message)
+            .emit()
     };
 }
 
@@ -284,7 +276,9 @@ mod tests {
             request_size: None,
             timestamp_ms: 1700000000000,
         };
-        entry = entry.with_tool("decode_transaction").with_error(-32602, "Invalid params");
+        entry = entry
+            .with_tool("decode_transaction")
+            .with_error(-32602, "Invalid params");
 
         assert_eq!(entry.level.as_str(), "error");
         assert_eq!(entry.tool, Some("decode_transaction"));
@@ -1,7 +1,10 @@
 use alloy_primitives::{Address, U256};
+use async_trait::async_trait;
 
-use crate::error::Result;
-use crate::infra::rpc::{InternalCall, RpcClient};
+use crate::error::{CroLensError, Result};
+use crate::infra::rpc::{
+    BasicSimulationResult, CallTraceLimits, DebugTraceResult, InternalCall, RpcClient,
+};
 
 /// 交易模拟结果
 /// 基础模式: 使用 eth_call + eth_estimateGas (所有 EVM RPC 支持)
@@ -13,6 +16,8 @@ pub struct SimulationResult {
     pub output: String,
     pub logs: Vec<SimulationLog>,
     pub internal_calls: Vec<InternalCall>,
+    /// 内部调用超过 SIMULATION_MAX_CALL_DEPTH / SIMULATION_MAX_INTERNAL_CALLS 被截断
+    pub calls_truncated: bool,
     pub error_message: Option<String>,
     /// 是否为基础模式 (无日志/内部调用追踪)
     pub basic_mode: bool,
@@ -29,47 +34,347 @@ pub struct SimulationLog {
 pub type TenderlySimulation = SimulationResult;
 pub type TenderlyLog = SimulationLog;
 
-/// 模拟客户端 - 使用 eth_call + eth_estimateGas 实现基础模拟
-/// 注意: Tenderly 已停止支持 Cronos，改用标准 RPC 方法
+/// 模拟后端 (由 SIMULATION_BACKEND 选择)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulationBackend {
+    /// debug_traceCall: 日志 + 内部调用追踪
+    Trace,
+    /// eth_call + eth_estimateGas: 适用于不支持 debug_traceCall 的 RPC
+    EstimateOnly,
+}
+
+impl SimulationBackend {
+    /// 未设置或无法识别时使用 estimate-only (大多数 Cronos RPC 不支持 debug_traceCall)
+    pub fn parse(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            Some("trace") => Self::Trace,
+            _ => Self::EstimateOnly,
+        }
+    }
+
+    pub fn from_env(env: &worker::Env) -> Self {
+        let value = env.var("SIMULATION_BACKEND").ok().map(|v| v.to_string());
+        Self::parse(value.as_deref())
+    }
+}
+
+/// 读取 SIMULATION_TARGET_ALLOWLIST (逗号分隔的合约地址)；未设置时不限制模拟目标
+pub fn simulation_target_allowlist(env: &worker::Env) -> Option<Vec<Address>> {
+    let value = env
+        .var("SIMULATION_TARGET_ALLOWLIST")
+        .ok()
+        .map(|v| v.to_string());
+    parse_simulation_target_allowlist(value.as_deref())
+}
+
+/// 空值视为未配置；无法解析的地址被忽略 (列表仍然生效，不会因为配置错误而放开)
+pub fn parse_simulation_target_allowlist(value: Option<&str>) -> Option<Vec<Address>> {
+    let value = value?.trim();
+    if value.is_empty() {
+        return None;
+    }
+    let mut allowlist = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+        if let Ok(address) = crate::types::parse_address(entry) {
+            if !allowlist.contains(&address) {
+                allowlist.push(address);
+            }
+        }
+    }
+    Some(allowlist)
+}
+
+/// 配置了允许列表时只能模拟列表中的目标合约，避免模拟器被当作任意合约的 RPC 代理
+pub fn check_simulation_target(allowlist: Option<&[Address]>, to: Address) -> Result<()> {
+    match allowlist {
+        Some(allowed) if !allowed.contains(&to) => Err(CroLensError::invalid_params(format!(
+            "Simulation target {to} is not in the allowed target list"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait(?Send)]
+pub trait Simulator {
+    async fn simulate(
+        &self,
+        from: Address,
+        to: Address,
+        input: &str,
+        value: U256,
+        gas: Option<u64>,
+    ) -> Result<SimulationResult>;
+}
+
+/// debug_traceCall 后端
 #[derive(Clone)]
-pub struct SimulationClient {
+pub struct TraceSimulator {
     rpc: RpcClient,
+    limits: CallTraceLimits,
 }
 
-impl SimulationClient {
-    /// 从 RpcClient 创建模拟客户端
+impl TraceSimulator {
+    pub fn new(rpc: RpcClient) -> Self {
+        Self {
+            rpc,
+            limits: CallTraceLimits::default(),
+        }
+    }
+
+    pub fn with_call_limits(mut self, limits: CallTraceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+#[async_trait(?Send)]
+impl Simulator for TraceSimulator {
+    async fn simulate(
+        &self,
+        from: Address,
+        to: Address,
+        input: &str,
+        value: U256,
+        gas: Option<u64>,
+    ) -> Result<SimulationResult> {
+        let trace = self
+            .rpc
+            .debug_trace_call(from, to, input, value, gas, self.limits)
+            .await?;
+        Ok(trace_result(trace))
+    }
+}
+
+/// eth_call + eth_estimateGas 后端
+/// 提供:
+/// - ✅ 交易成功/失败预测
+/// - ✅ Gas 估算
+/// - ✅ 合约返回值
+/// - ❌ 事件日志 (需要 debug_traceCall)
+/// - ❌ 内部调用追踪 (需要 debug_traceCall)
+#[derive(Clone)]
+pub struct EstimateOnlySimulator {
+    rpc: RpcClient,
+}
+
+impl EstimateOnlySimulator {
     pub fn new(rpc: RpcClient) -> Self {
         Self { rpc }
     }
+}
 
-    /// 模拟交易执行
-    /// 使用 eth_call + eth_estimateGas 提供:
-    /// - ✅ 交易成功/失败预测
-    /// - ✅ Gas 估算
-    /// - ✅ 合约返回值
-    /// - ❌ 事件日志 (需要 debug_traceCall)
-    /// - ❌ 内部调用追踪 (需要 debug_traceCall)
-    pub async fn simulate(
+#[async_trait(?Send)]
+impl Simulator for EstimateOnlySimulator {
+    async fn simulate(
         &self,
         from: Address,
         to: Address,
         input: &str,
         value: U256,
-        _gas: Option<u64>, // 保留参数以保持 API 兼容
+        _gas: Option<u64>, // eth_estimateGas 自行估算
     ) -> Result<SimulationResult> {
         let result = self.rpc.simulate_basic(from, to, input, value).await?;
+        Ok(estimate_only_result(result))
+    }
+}
+
+fn trace_result(trace: DebugTraceResult) -> SimulationResult {
+    SimulationResult {
+        success: trace.success,
+        gas_used: trace.gas_used,
+        output: trace.output,
+        logs: trace
+            .logs
+            .into_iter()
+            .map(|log| SimulationLog {
+                address: log.address,
+                topics: log.topics,
+                data: log.data,
+            })
+            .collect(),
+        internal_calls: trace.internal_calls,
+        calls_truncated: trace.calls_truncated,
+        error_message: trace.error_message,
+        basic_mode: false,
+    }
+}
 
-        Ok(SimulationResult {
-            success: result.success,
-            gas_used: result.gas_used,
-            output: result.output,
-            logs: vec![], // 基础模式无法获取日志
-            internal_calls: vec![], // 基础模式无法获取内部调用
-            error_message: result.error_message,
-            basic_mode: true,
-        })
+fn estimate_only_result(result: BasicSimulationResult) -> SimulationResult {
+    SimulationResult {
+        success: result.success,
+        gas_used: result.gas_used,
+        output: result.output,
+        logs: vec![],           // 基础模式无法获取日志
+        internal_calls: vec![], // 基础模式无法获取内部调用
+        calls_truncated: false,
+        error_message: result.error_message,
+        basic_mode: true,
+    }
+}
+
+/// 模拟客户端 - 按配置的后端分发
+/// 注意: Tenderly 已停止支持 Cronos，改用标准 RPC 方法
+#[derive(Clone)]
+pub enum SimulationClient {
+    Trace(TraceSimulator),
+    EstimateOnly(EstimateOnlySimulator),
+}
+
+impl SimulationClient {
+    /// 从 RpcClient 创建模拟客户端
+    pub fn new(rpc: RpcClient, backend: SimulationBackend) -> Self {
+        match backend {
+            SimulationBackend::Trace => Self::Trace(TraceSimulator::new(rpc)),
+            SimulationBackend::EstimateOnly => Self::EstimateOnly(EstimateOnlySimulator::new(rpc)),
+        }
+    }
+
+    /// 设置 trace 后端展开内部调用的上限 (estimate-only 后端没有内部调用)
+    pub fn with_call_limits(self, limits: CallTraceLimits) -> Self {
+        match self {
+            Self::Trace(sim) => Self::Trace(sim.with_call_limits(limits)),
+            other => other,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Simulator for SimulationClient {
+    async fn simulate(
+        &self,
+        from: Address,
+        to: Address,
+        input: &str,
+        value: U256,
+        gas: Option<u64>,
+    ) -> Result<SimulationResult> {
+        match self {
+            Self::Trace(sim) => sim.simulate(from, to, input, value, gas).await,
+            Self::EstimateOnly(sim) => sim.simulate(from, to, input, value, gas).await,
+        }
     }
 }
 
 // 保留旧的类型别名以兼容现有代码
 pub type TenderlyClient = SimulationClient;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::rpc::DebugTraceLog;
+
+    #[test]
+    fn unset_allowlist_allows_any_target() {
+        assert_eq!(parse_simulation_target_allowlist(None), None);
+        assert_eq!(parse_simulation_target_allowlist(Some(" ")), None);
+        assert!(check_simulation_target(None, Address::from([0x33u8; 20])).is_ok());
+    }
+
+    #[test]
+    fn configured_allowlist_allows_listed_and_denies_other_targets() {
+        let router = Address::from([0x11u8; 20]);
+        let allowlist = parse_simulation_target_allowlist(Some(&format!(
+            " {router}, 0x{} ,not-an-address,{}",
+            "22".repeat(20),
+            router.to_string().to_lowercase()
+        )))
+        .expect("configured");
+        assert_eq!(allowlist, vec![router, Address::from([0x22u8; 20])]);
+
+        assert!(check_simulation_target(Some(&allowlist), router).is_ok());
+        let err = check_simulation_target(Some(&allowlist), Address::from([0x33u8; 20]))
+            .expect_err("not allowed");
+        assert!(matches!(err, CroLensError::InvalidParams(_)));
+        assert!(err.to_string().contains("not in the allowed target list"));
+    }
+
+    #[test]
+    fn allowlist_with_only_invalid_entries_denies_everything() {
+        let allowlist =
+            parse_simulation_target_allowlist(Some("nope, 0x1234")).expect("configured");
+        assert!(allowlist.is_empty());
+        assert!(check_simulation_target(Some(&allowlist), Address::from([0x11u8; 20])).is_err());
+    }
+
+    #[test]
+    fn backend_defaults_to_estimate_only() {
+        assert_eq!(
+            SimulationBackend::parse(None),
+            SimulationBackend::EstimateOnly
+        );
+        assert_eq!(
+            SimulationBackend::parse(Some("")),
+            SimulationBackend::EstimateOnly
+        );
+        assert_eq!(
+            SimulationBackend::parse(Some("bogus")),
+            SimulationBackend::EstimateOnly
+        );
+    }
+
+    #[test]
+    fn backend_parses_known_values() {
+        assert_eq!(
+            SimulationBackend::parse(Some("trace")),
+            SimulationBackend::Trace
+        );
+        assert_eq!(
+            SimulationBackend::parse(Some(" TRACE ")),
+            SimulationBackend::Trace
+        );
+        assert_eq!(
+            SimulationBackend::parse(Some("estimate-only")),
+            SimulationBackend::EstimateOnly
+        );
+    }
+
+    #[test]
+    fn estimate_only_result_has_no_trace_data() {
+        let result = estimate_only_result(BasicSimulationResult {
+            success: true,
+            gas_used: Some(21_000),
+            output: "0x01".to_string(),
+            error_message: None,
+        });
+        assert!(result.success);
+        assert!(result.basic_mode);
+        assert_eq!(result.gas_used, Some(21_000));
+        assert_eq!(result.output, "0x01");
+        assert!(result.logs.is_empty());
+        assert!(result.internal_calls.is_empty());
+    }
+
+    #[test]
+    fn estimate_only_result_keeps_revert_message() {
+        let result = estimate_only_result(BasicSimulationResult {
+            success: false,
+            gas_used: None,
+            output: "0x".to_string(),
+            error_message: Some("execution reverted".to_string()),
+        });
+        assert!(!result.success);
+        assert!(result.basic_mode);
+        assert_eq!(result.error_message.as_deref(), Some("execution reverted"));
+    }
+
+    #[test]
+    fn trace_result_maps_logs() {
+        let result = trace_result(DebugTraceResult {
+            success: true,
+            gas_used: Some(50_000),
+            output: "0x".to_string(),
+            logs: vec![DebugTraceLog {
+                address: "0xabc".to_string(),
+                topics: vec!["0x01".to_string()],
+                data: "0x".to_string(),
+            }],
+            internal_calls: vec![],
+            calls_truncated: true,
+            error_message: None,
+        });
+        assert!(!result.basic_mode);
+        assert!(result.calls_truncated);
+        assert_eq!(result.logs.len(), 1);
+        assert_eq!(result.logs[0].address, "0xabc");
+    }
+}
@@ -2,22 +2,59 @@ use alloy_primitives::Address;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use worker::d1::D1Type;
-use worker::kv::KvStore;
-use worker::D1Database;
+use worker::{console_warn, D1Database};
 
 use crate::error::{CroLensError, Result};
 use crate::infra;
+use crate::infra::kv_stats::TrackedKv;
 use crate::types;
 
 const TOKENS_CACHE_KEY: &str = "cache:tokens:all";
 const TOKENS_CACHE_TTL_SECS: u64 = 600; // 10 分钟
 
+/// 核心代币兜底表 (address, symbol, decimals, is_stablecoin)，与 db/seed.sql 一致；
+/// 仅在 DB/缓存为空或不可用时使用，DB 可用时以 DB 为准。原生 CRO 通过 WCRO 解析
+const FALLBACK_TOKENS: &[(&str, &str, u8, bool)] = &[
+    (
+        "0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23",
+        "WCRO",
+        18,
+        false,
+    ),
+    (
+        "0xc21223249CA28397B4B6541dfFaEcC539BfF0c59",
+        "USDC",
+        6,
+        true,
+    ),
+    (
+        "0x66e428c3f67a68878562e79A0234c1F83c208770",
+        "USDT",
+        6,
+        true,
+    ),
+    (
+        "0x2D03bece6747ADC00E1a131BBA1469C15fD11e03",
+        "VVS",
+        18,
+        false,
+    ),
+    (
+        "0xDD73dEa10ABC2Bff99c60882EC5b2B81bb1Dc5B2",
+        "TONIC",
+        18,
+        false,
+    ),
+];
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub address: Address,
     pub symbol: String,
     pub decimals: u8,
     pub is_stablecoin: bool,
+    /// DB 中人工标记的垃圾币 (空投等)
+    pub is_spam: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -26,12 +63,20 @@ struct TokenCache {
     symbol: String,
     decimals: u8,
     is_stablecoin: bool,
+    #[serde(default)]
+    is_spam: bool,
 }
 
 /// 从 KV 缓存获取代币列表，缓存未命中时从 DB 加载
-pub async fn list_tokens_cached(db: &D1Database, kv: &KvStore) -> Result<Vec<Token>> {
+pub async fn list_tokens_cached(
+    db: &D1Database,
+    kv: &TrackedKv,
+    kv_prefix: &str,
+) -> Result<Vec<Token>> {
+    let cache_key = infra::kv_key(kv_prefix, TOKENS_CACHE_KEY);
+
     // 先尝试从 KV 缓存获取
-    if let Ok(Some(cached)) = kv.get(TOKENS_CACHE_KEY).text().await {
+    if let Ok(Some(cached)) = kv.get_text(&cache_key).await {
         if let Ok(tokens_cache) = serde_json::from_str::<Vec<TokenCache>>(&cached) {
             let mut tokens = Vec::with_capacity(tokens_cache.len());
             for t in tokens_cache {
@@ -41,6 +86,7 @@ pub async fn list_tokens_cached(db: &D1Database, kv: &KvStore) -> Result<Vec<Tok
                         symbol: t.symbol,
                         decimals: t.decimals,
                         is_stablecoin: t.is_stablecoin,
+                        is_spam: t.is_spam,
                     });
                 }
             }
@@ -50,8 +96,14 @@ pub async fn list_tokens_cached(db: &D1Database, kv: &KvStore) -> Result<Vec<Tok
         }
     }
 
-    // 缓存未命中，从 DB 加载
-    let tokens = list_tokens(db).await?;
+    // 缓存未命中，从 DB 加载；DB 为空或不可用时使用核心代币兜底 (不写入缓存)
+    let tokens = match tokens_or_fallback(list_tokens(db).await) {
+        (tokens, false) => {
+            console_warn!("[WARN] tokens table unavailable, using core token fallback");
+            return Ok(tokens);
+        }
+        (tokens, true) => tokens,
+    };
 
     // 写入缓存
     let cache: Vec<TokenCache> = tokens
@@ -61,20 +113,31 @@ pub async fn list_tokens_cached(db: &D1Database, kv: &KvStore) -> Result<Vec<Tok
             symbol: t.symbol.clone(),
             decimals: t.decimals,
             is_stablecoin: t.is_stablecoin,
+            is_spam: t.is_spam,
         })
         .collect();
     if let Ok(json) = serde_json::to_string(&cache) {
-        if let Ok(put) = kv.put(TOKENS_CACHE_KEY, json) {
-            let _ = put.expiration_ttl(TOKENS_CACHE_TTL_SECS).execute().await;
-        }
+        let _ = kv
+            .put_text_with_ttl(&cache_key, json, TOKENS_CACHE_TTL_SECS)
+            .await;
     }
 
     Ok(tokens)
 }
 
 pub async fn list_tokens(db: &D1Database) -> Result<Vec<Token>> {
-    let statement = db.prepare("SELECT address, symbol, decimals, is_stablecoin FROM tokens");
-    let result = infra::db::run("list_tokens", statement.all()).await?;
+    let statement =
+        db.prepare("SELECT address, symbol, decimals, is_stablecoin, is_spam FROM tokens");
+    let result = match infra::db::run("list_tokens", || statement.all()).await {
+        Ok(v) => v,
+        // 未执行 migrate_tokens_is_spam.sql 的库没有 is_spam 列，按全部非垃圾币处理
+        Err(CroLensError::DbError(msg)) if is_missing_spam_column(&msg) => {
+            let statement =
+                db.prepare("SELECT address, symbol, decimals, is_stablecoin FROM tokens");
+            infra::db::run("list_tokens_legacy", || statement.all()).await?
+        }
+        Err(err) => return Err(err),
+    };
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -94,33 +157,76 @@ pub async fn list_tokens(db: &D1Database) -> Result<Vec<Token>> {
             .and_then(|v| v.as_i64())
             .ok_or_else(|| CroLensError::DbError("tokens.decimals missing".to_string()))?;
 
-        let is_stablecoin = match row.get("is_stablecoin") {
-            Some(Value::Bool(v)) => *v,
-            Some(Value::Number(n)) => n.as_i64().unwrap_or(0) != 0,
-            _ => false,
-        };
-
         tokens.push(Token {
             address: types::parse_address(address)?,
             symbol: symbol.to_string(),
             decimals: decimals as u8,
-            is_stablecoin,
+            is_stablecoin: row_bool(&row, "is_stablecoin"),
+            is_spam: row_bool(&row, "is_spam"),
         });
     }
 
     Ok(tokens)
 }
 
+/// 编译期内置的核心代币列表
+pub fn fallback_tokens() -> Vec<Token> {
+    FALLBACK_TOKENS
+        .iter()
+        .filter_map(|(address, symbol, decimals, is_stablecoin)| {
+            Some(Token {
+                address: types::parse_address(address).ok()?,
+                symbol: symbol.to_string(),
+                decimals: *decimals,
+                is_stablecoin: *is_stablecoin,
+                is_spam: false,
+            })
+        })
+        .collect()
+}
+
+/// DB 返回非空列表时原样使用 (true)；为空或出错时返回兜底列表 (false)
+fn tokens_or_fallback(loaded: Result<Vec<Token>>) -> (Vec<Token>, bool) {
+    match loaded {
+        Ok(tokens) if !tokens.is_empty() => (tokens, true),
+        _ => (fallback_tokens(), false),
+    }
+}
+
+fn fallback_token_by_address(address: Address) -> Option<Token> {
+    fallback_tokens().into_iter().find(|t| t.address == address)
+}
+
+/// 按地址查询代币；DB 中没有或 DB 不可用时回退到核心代币兜底表
 pub async fn get_token_by_address(db: &D1Database, address: Address) -> Result<Option<Token>> {
-    let address_str = address.to_string();
+    match get_token_by_address_db(db, address).await {
+        Ok(Some(token)) => Ok(Some(token)),
+        Ok(None) => Ok(fallback_token_by_address(address)),
+        Err(err) => fallback_token_by_address(address).map(Some).ok_or(err),
+    }
+}
+
+async fn get_token_by_address_db(db: &D1Database, address: Address) -> Result<Option<Token>> {
+    // 地址大小写不敏感比较 (有 lower(address) 表达式索引)
+    let address_str = address.to_string().to_lowercase();
     let address_arg = D1Type::Text(&address_str);
 
     let statement = db
-        .prepare("SELECT address, symbol, decimals, is_stablecoin FROM tokens WHERE address = ?1 LIMIT 1")
+        .prepare("SELECT address, symbol, decimals, is_stablecoin, is_spam FROM tokens WHERE lower(address) = ?1 LIMIT 1")
         .bind_refs([&address_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
 
-    let result = infra::db::run("get_token_by_address", statement.all()).await?;
+    let result = match infra::db::run("get_token_by_address", || statement.all()).await {
+        Ok(v) => v,
+        Err(CroLensError::DbError(msg)) if is_missing_spam_column(&msg) => {
+            let statement = db
+                .prepare("SELECT address, symbol, decimals, is_stablecoin FROM tokens WHERE lower(address) = ?1 LIMIT 1")
+                .bind_refs([&address_arg])
+                .map_err(|err| CroLensError::DbError(err.to_string()))?;
+            infra::db::run("get_token_by_address_legacy", || statement.all()).await?
+        }
+        Err(err) => return Err(err),
+    };
     let rows: Vec<Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
@@ -142,20 +248,28 @@ pub async fn get_token_by_address(db: &D1Database, address: Address) -> Result<O
         .and_then(|v| v.as_i64())
         .ok_or_else(|| CroLensError::DbError("tokens.decimals missing".to_string()))?;
 
-    let is_stablecoin = match row.get("is_stablecoin") {
-        Some(Value::Bool(v)) => *v,
-        Some(Value::Number(n)) => n.as_i64().unwrap_or(0) != 0,
-        _ => false,
-    };
-
     Ok(Some(Token {
         address: types::parse_address(address)?,
         symbol: symbol.to_string(),
         decimals: decimals as u8,
-        is_stablecoin,
+        is_stablecoin: row_bool(row, "is_stablecoin"),
+        is_spam: row_bool(row, "is_spam"),
     }))
 }
 
+fn is_missing_spam_column(message: &str) -> bool {
+    message.contains("no such column") && message.contains("is_spam")
+}
+
+/// D1 的 BOOLEAN 列可能以 bool 或 0/1 返回；缺失视为 false
+fn row_bool(row: &Value, column: &str) -> bool {
+    match row.get(column) {
+        Some(Value::Bool(v)) => *v,
+        Some(Value::Number(n)) => n.as_i64().unwrap_or(0) != 0,
+        _ => false,
+    }
+}
+
 pub fn resolve_token(tokens: &[Token], query: &str) -> Result<Token> {
     let trimmed = query.trim();
     if trimmed.starts_with("0x") {
@@ -174,3 +288,77 @@ pub fn resolve_token(tokens: &[Token], query: &str) -> Result<Token> {
         .cloned()
         .ok_or_else(|| CroLensError::TokenNotFound(trimmed.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_missing_is_spam_column() {
+        assert!(is_missing_spam_column(
+            "D1_ERROR: no such column: is_spam: SQLITE_ERROR"
+        ));
+        assert!(!is_missing_spam_column("D1_ERROR: no such table: tokens"));
+        assert!(!is_missing_spam_column(
+            "D1_ERROR: no such column: is_active: SQLITE_ERROR"
+        ));
+    }
+
+    fn token(symbol: &str, byte: u8) -> Token {
+        Token {
+            address: Address::from([byte; 20]),
+            symbol: symbol.to_string(),
+            decimals: 18,
+            is_stablecoin: false,
+            is_spam: false,
+        }
+    }
+
+    #[test]
+    fn core_tokens_resolve_from_fallback_when_db_is_empty() {
+        let (tokens, from_db) = tokens_or_fallback(Ok(Vec::new()));
+        assert!(!from_db);
+
+        let usdc = resolve_token(&tokens, "usdc").expect("USDC in fallback");
+        assert_eq!(usdc.decimals, 6);
+        assert!(usdc.is_stablecoin);
+        for symbol in ["WCRO", "USDC", "USDT", "VVS", "TONIC"] {
+            assert!(resolve_token(&tokens, symbol).is_ok(), "{symbol} missing");
+        }
+        let wcro = resolve_token(&tokens, "0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23")
+            .expect("WCRO by address");
+        assert_eq!(wcro.symbol, "WCRO");
+    }
+
+    #[test]
+    fn core_tokens_resolve_from_fallback_when_db_fails() {
+        let (tokens, from_db) =
+            tokens_or_fallback(Err(CroLensError::DbError("D1 unavailable".to_string())));
+        assert!(!from_db);
+        assert_eq!(tokens.len(), FALLBACK_TOKENS.len());
+        assert_eq!(
+            resolve_token(&tokens, "TONIC").map(|t| t.decimals).ok(),
+            Some(18)
+        );
+    }
+
+    #[test]
+    fn db_tokens_are_authoritative_when_available() {
+        let (tokens, from_db) = tokens_or_fallback(Ok(vec![token("FOO", 0x11)]));
+        assert!(from_db);
+        assert_eq!(tokens.len(), 1);
+        assert!(resolve_token(&tokens, "FOO").is_ok());
+        assert!(resolve_token(&tokens, "USDC").is_err());
+    }
+
+    #[test]
+    fn fallback_lookup_by_address_covers_core_tokens_only() {
+        let usdt =
+            types::parse_address("0x66e428c3f67a68878562e79A0234c1F83c208770").expect("valid");
+        assert_eq!(
+            fallback_token_by_address(usdt).map(|t| t.symbol),
+            Some("USDT".to_string())
+        );
+        assert!(fallback_token_by_address(Address::from([0x11; 20])).is_none());
+    }
+}
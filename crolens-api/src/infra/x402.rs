@@ -51,7 +51,7 @@ async fn load_price_per_credit_wei(db: &D1Database) -> Result<U256> {
         .prepare("SELECT value FROM system_config WHERE key = ?1 LIMIT 1")
         .bind_refs([&key_arg])
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
-    let result = infra::db::run("load_price_per_credit_wei", statement.all()).await?;
+    let result = infra::db::run("load_price_per_credit_wei", || statement.all()).await?;
     let rows: Vec<serde_json::Value> = result
         .results()
         .map_err(|err| CroLensError::DbError(err.to_string()))?;
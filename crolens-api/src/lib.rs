@@ -44,6 +44,7 @@ pub async fn main(req: Request, env: Env, _ctx: Context) -> worker::Result<Respo
         (Method::Get, "/health") => handle_health(&env).await?,
         (Method::Get, "/ready") => handle_ready(&env).await?,
         (Method::Get, "/stats") => http::handle_stats(&env, &trace_id, start_ms).await?,
+        (Method::Get, "/metrics") => http::handle_metrics(&req, &env, &trace_id, start_ms).await?,
         (Method::Get, "/x402/quote") => {
             http::handle_x402_quote(&req, &env, &trace_id, start_ms).await?
         }
@@ -85,7 +86,11 @@ async fn handle_price_sync(env: &Env) -> worker::Result<Response> {
 
     // Check whether anchor prices were written to KV.
     if let Ok(kv) = env.kv("KV") {
-        if let Ok(Some(v)) = kv.get("price:anchor:cro").text().await {
+        if let Ok(Some(v)) = kv
+            .get(&infra::kv_key(&infra::kv_prefix(env), "price:anchor:cro"))
+            .text()
+            .await
+        {
             messages.push(format!("CRO price in KV: {v}"));
         } else {
             messages.push("CRO price NOT in KV".to_string());
@@ -104,7 +109,11 @@ async fn handle_price_sync(env: &Env) -> worker::Result<Response> {
 
     // Check the aggregated price cache.
     if let Ok(kv) = env.kv("KV") {
-        if let Ok(Some(v)) = kv.get("cache:prices:all").text().await {
+        if let Ok(Some(v)) = kv
+            .get(&infra::kv_key(&infra::kv_prefix(env), "cache:prices:all"))
+            .text()
+            .await
+        {
             messages.push(format!("Price cache: {} bytes", v.len()));
         }
     }
@@ -113,7 +122,8 @@ async fn handle_price_sync(env: &Env) -> worker::Result<Response> {
 }
 
 async fn handle_test_coingecko() -> worker::Result<Response> {
-    let url = "https://api.coingecko.com/api/v3/simple/price?ids=crypto-com-chain&vs_currencies=usd";
+    let url =
+        "https://api.coingecko.com/api/v3/simple/price?ids=crypto-com-chain&vs_currencies=usd";
 
     let mut headers = worker::Headers::new();
     headers.set("User-Agent", "CroLens/1.0 (https://crolens.io)")?;
@@ -136,6 +146,7 @@ async fn handle_json_rpc(mut req: Request, env: &Env, trace_id: &str) -> worker:
     let start_ms = types::now_ms();
     let api_key = types::get_header(&req, "x-api-key");
     let client_ip = types::get_client_ip(&req);
+    let accept_encoding = types::get_header(&req, "Accept-Encoding");
 
     // Parse the request body first so we can decide whether to apply rate limiting.
     let body_bytes = match req.bytes().await {
@@ -156,13 +167,16 @@ async fn handle_json_rpc(mut req: Request, env: &Env, trace_id: &str) -> worker:
         return Response::from_json(&resp).map(|r| r.with_status(413));
     }
 
-    let json_rpc_req: JsonRpcRequest = match serde_json::from_slice(&body_bytes) {
+    // 先解析为 Value 并校验嵌套深度，再转换为请求结构
+    let parsed = http::parse_json_body(&body_bytes, http::max_json_depth(env)).and_then(|value| {
+        serde_json::from_value::<JsonRpcRequest>(value).map_err(|err| {
+            CroLensError::invalid_request(format!("Invalid JSON-RPC payload: {err}"))
+        })
+    });
+    let json_rpc_req = match parsed {
         Ok(v) => v,
         Err(err) => {
-            let resp = JsonRpcResponse::error(
-                serde_json::Value::Null,
-                CroLensError::invalid_request(format!("Invalid JSON-RPC payload: {err}")),
-            );
+            let resp = JsonRpcResponse::error(serde_json::Value::Null, err);
             return Response::from_json(&resp).map(|r| r.with_status(400));
         }
     };
@@ -174,9 +188,17 @@ async fn handle_json_rpc(mut req: Request, env: &Env, trace_id: &str) -> worker:
         req.path()
     );
 
+    // JSON-RPC 通知 (无 id) 不返回响应体
+    if !json_rpc_req.expects_response() {
+        return Ok(Response::empty()?.with_status(204));
+    }
+
     // Apply a per-IP JSON-RPC rate limit for tools/list and tools/call.
     // tools/call also has its own per-api-key rate limit inside the MCP router.
-    let needs_ip_rate_limit = matches!(json_rpc_req.method.as_str(), "tools/list" | "tools/call");
+    // RATE_LIMIT_BYPASS_KEYS / RATE_LIMIT_BYPASS_IPS 中的可信调用方不受限流
+    let needs_ip_rate_limit = matches!(json_rpc_req.method.as_str(), "tools/list" | "tools/call")
+        && !gateway::ratelimit::RateLimitBypass::from_env(env)
+            .allows(api_key.as_deref(), &client_ip);
 
     if needs_ip_rate_limit {
         if let Ok(kv) = env.kv("KV") {
@@ -193,12 +215,12 @@ async fn handle_json_rpc(mut req: Request, env: &Env, trace_id: &str) -> worker:
                 .filter(|v| *v > 0)
                 .unwrap_or(JSONRPC_IP_RATE_WINDOW_SECS_DEFAULT);
 
-            let key = format!("rl:jsonrpc:{client_ip}");
+            let key = infra::kv_key(&infra::kv_prefix(env), &format!("rl:jsonrpc:{client_ip}"));
             match gateway::ratelimit::check_rate_limit(&kv, &key, limit, window_secs).await {
                 Ok(true) => {}
                 Ok(false) => {
                     let resp = JsonRpcResponse::error(
-                        json_rpc_req.id,
+                        json_rpc_req.response_id(),
                         CroLensError::rate_limit_exceeded(Some(window_secs as u32)),
                     );
                     let mut http_resp = Response::from_json(&resp)?.with_status(429);
@@ -215,61 +237,57 @@ async fn handle_json_rpc(mut req: Request, env: &Env, trace_id: &str) -> worker:
     }
 
     let request_size = body_bytes.len();
-    let resp = mcp::router::handle(
-        json_rpc_req,
-        env,
-        trace_id,
-        api_key.as_deref(),
-        start_ms,
-        &client_ip,
-        request_size,
+    let response_id = json_rpc_req.response_id();
+    let timeout_ms = http::request_timeout_ms(env);
+    let routed = infra::concurrency::race_timeout(
+        mcp::router::handle(
+            json_rpc_req,
+            env,
+            trace_id,
+            api_key.as_deref(),
+            start_ms,
+            &client_ip,
+            request_size,
+        ),
+        worker::Delay::from(std::time::Duration::from_millis(timeout_ms)),
     )
     .await;
+    let resp = match routed {
+        Some(resp) => resp,
+        None => {
+            console_warn!(
+                "[WARN] [{}] Request timed out after {}ms",
+                trace_id,
+                timeout_ms
+            );
+            JsonRpcResponse::error(response_id, CroLensError::Timeout { timeout_ms })
+        }
+    };
 
-    let mut http_resp = Response::from_json(&resp)?;
+    let body = serde_json::to_string(&resp)
+        .map_err(|err| worker::Error::RustError(format!("Failed to serialize response: {err}")))?;
+    let gzip = http::should_gzip(
+        accept_encoding.as_deref(),
+        body.len(),
+        http::gzip_min_bytes(env),
+    );
+    let mut http_resp = Response::ok(body)?;
+    {
+        let headers = http_resp.headers_mut();
+        headers.set("Content-Type", "application/json")?;
+        headers.append("Vary", "Accept-Encoding")?;
+        if gzip {
+            // Workers 运行时会按 Content-Encoding 压缩未编码的响应体
+            headers.set("Content-Encoding", "gzip")?;
+        }
+    }
     if let Some(err) = resp.error.as_ref() {
-        match err.code {
-            -32003 => {
-                http_resp = http_resp.with_status(429);
-                let retry_after = err
-                    .data
-                    .as_ref()
-                    .and_then(|v| v.get("retry_after"))
-                    .and_then(|v| v.as_i64())
-                    .filter(|v| *v > 0)
-                    .unwrap_or(3600);
-                http_resp
-                    .headers_mut()
-                    .set("Retry-After", &retry_after.to_string())?;
-            }
-            -32001 => {
-                http_resp = http_resp.with_status(401);
-            }
-            -32002 => {
-                http_resp = http_resp.with_status(402);
-            }
-            -32501 => {
-                http_resp = http_resp.with_status(503);
-                if let Some(retry_after) = err
-                    .data
-                    .as_ref()
-                    .and_then(|v| v.get("retry_after"))
-                    .and_then(|v| v.as_i64())
-                {
-                    http_resp
-                        .headers_mut()
-                        .set("Retry-After", &retry_after.to_string())?;
-                }
-            }
-            -32601 => {
-                http_resp = http_resp.with_status(404);
-            }
-            -32600 | -32602 => {
-                http_resp = http_resp.with_status(400);
-            }
-            _ => {
-                http_resp = http_resp.with_status(500);
-            }
+        let code = i64::from(err.code);
+        http_resp = http_resp.with_status(error::http_status_for(code));
+        if let Some(retry_after) = error::retry_after_for(code, err.data.as_ref()) {
+            http_resp
+                .headers_mut()
+                .set("Retry-After", &retry_after.to_string())?;
         }
     }
 
@@ -284,10 +302,12 @@ async fn run_price_sync(env: &Env) {
             return;
         }
     };
+    let kv_prefix = infra::kv_prefix(env);
+    let retry_state_key = infra::kv_key(&kv_prefix, PRICE_SYNC_RETRY_STATE_KEY);
 
     let now = types::now_ms();
     let next_run_ms = kv
-        .get(PRICE_SYNC_NEXT_RUN_KEY)
+        .get(&infra::kv_key(&kv_prefix, PRICE_SYNC_NEXT_RUN_KEY))
         .text()
         .await
         .ok()
@@ -295,7 +315,7 @@ async fn run_price_sync(env: &Env) {
         .and_then(|v| v.parse::<i64>().ok());
 
     let retry_state = kv
-        .get(PRICE_SYNC_RETRY_STATE_KEY)
+        .get(&retry_state_key)
         .text()
         .await
         .ok()
@@ -319,20 +339,33 @@ async fn run_price_sync(env: &Env) {
                         console_log!("[INFO] Derived price sync succeeded on retry {}", attempt);
                     }
                     Err(err) => {
-                        console_warn!("[WARN] Derived price sync failed on retry {}: {}", attempt, err);
+                        console_warn!(
+                            "[WARN] Derived price sync failed on retry {}: {}",
+                            attempt,
+                            err
+                        );
                     }
                 }
-                let _ = kv.delete(PRICE_SYNC_RETRY_STATE_KEY).await;
-                set_price_sync_next_run(&kv, now.saturating_add(PRICE_SYNC_BASE_INTERVAL_MS)).await;
+                let _ = kv.delete(&retry_state_key).await;
+                set_price_sync_next_run(
+                    &kv,
+                    &kv_prefix,
+                    now.saturating_add(PRICE_SYNC_BASE_INTERVAL_MS),
+                )
+                .await;
             }
             Err(err) => {
                 console_error!("[WARN] Price sync retry {} failed: {}", attempt, err);
 
                 if attempt >= 3 {
                     console_error!("[ERROR] Price sync exhausted retries: {}", err);
-                    let _ = kv.delete(PRICE_SYNC_RETRY_STATE_KEY).await;
-                    set_price_sync_next_run(&kv, now.saturating_add(PRICE_SYNC_BASE_INTERVAL_MS))
-                        .await;
+                    let _ = kv.delete(&retry_state_key).await;
+                    set_price_sync_next_run(
+                        &kv,
+                        &kv_prefix,
+                        now.saturating_add(PRICE_SYNC_BASE_INTERVAL_MS),
+                    )
+                    .await;
                     return;
                 }
 
@@ -344,7 +377,7 @@ async fn run_price_sync(env: &Env) {
                     retries_done: attempt,
                     next_retry_ms: now.saturating_add(delay_ms),
                 };
-                set_price_sync_retry_state(&kv, &next_state).await;
+                set_price_sync_retry_state(&kv, &kv_prefix, &next_state).await;
             }
         }
 
@@ -370,7 +403,12 @@ async fn run_price_sync(env: &Env) {
                     console_warn!("[WARN] Derived price sync failed: {}", err);
                 }
             }
-            set_price_sync_next_run(&kv, now.saturating_add(PRICE_SYNC_BASE_INTERVAL_MS)).await;
+            set_price_sync_next_run(
+                &kv,
+                &kv_prefix,
+                now.saturating_add(PRICE_SYNC_BASE_INTERVAL_MS),
+            )
+            .await;
         }
         Err(err) => {
             console_error!("[WARN] Anchor price sync failed: {}", err);
@@ -378,29 +416,41 @@ async fn run_price_sync(env: &Env) {
                 retries_done: 0,
                 next_retry_ms: now.saturating_add(PRICE_SYNC_RETRY_DELAYS_MS[0]),
             };
-            set_price_sync_retry_state(&kv, &state).await;
-            set_price_sync_next_run(&kv, now.saturating_add(PRICE_SYNC_BASE_INTERVAL_MS)).await;
+            set_price_sync_retry_state(&kv, &kv_prefix, &state).await;
+            set_price_sync_next_run(
+                &kv,
+                &kv_prefix,
+                now.saturating_add(PRICE_SYNC_BASE_INTERVAL_MS),
+            )
+            .await;
         }
     }
 }
 
-async fn set_price_sync_next_run(kv: &worker::kv::KvStore, next_run_ms: i64) {
-    if let Ok(put) = kv.put(PRICE_SYNC_NEXT_RUN_KEY, next_run_ms.to_string()) {
+async fn set_price_sync_next_run(kv: &worker::kv::KvStore, kv_prefix: &str, next_run_ms: i64) {
+    let key = infra::kv_key(kv_prefix, PRICE_SYNC_NEXT_RUN_KEY);
+    if let Ok(put) = kv.put(&key, next_run_ms.to_string()) {
         let _ = put.expiration_ttl(86_400).execute().await;
     }
 }
 
-async fn set_price_sync_retry_state(kv: &worker::kv::KvStore, state: &PriceSyncRetryState) {
+async fn set_price_sync_retry_state(
+    kv: &worker::kv::KvStore,
+    kv_prefix: &str,
+    state: &PriceSyncRetryState,
+) {
     let Ok(raw) = serde_json::to_string(state) else {
         return;
     };
-    if let Ok(put) = kv.put(PRICE_SYNC_RETRY_STATE_KEY, raw) {
+    let key = infra::kv_key(kv_prefix, PRICE_SYNC_RETRY_STATE_KEY);
+    if let Ok(put) = kv.put(&key, raw) {
         let _ = put.expiration_ttl(1_800).execute().await;
     }
 }
 
 /// Readiness probe - checks if the service is ready to accept traffic
-/// This is a lightweight check that only verifies the DB connection.
+/// This is a lightweight check that verifies the DB connection and, when RPC is
+/// configured, that the Multicall3 contract exists (result cached in KV).
 /// Use /health for a comprehensive health check including RPC.
 async fn handle_ready(env: &Env) -> worker::Result<Response> {
     let (db_ok, db_error) = match env.d1("DB") {
@@ -411,7 +461,25 @@ async fn handle_ready(env: &Env) -> worker::Result<Response> {
         Err(err) => (false, Some(err.to_string())),
     };
 
+    // Multicall3 缺失 (非标准链上常见) 时判定为未就绪；RPC 暂时失败不影响就绪状态
+    let mut multicall_error: Option<String> = None;
     if db_ok {
+        let kv = env.kv("KV").ok().map(infra::kv_stats::TrackedKv::untracked);
+        if let Some(rpc) = infra::rpc::RpcClient::try_new(env, kv.clone()) {
+            let address = infra::multicall::multicall3_address(env);
+            let kv_prefix = infra::kv_prefix(env);
+            if let Ok(false) =
+                infra::multicall::verify_multicall3_code(&rpc, kv.as_ref(), &kv_prefix, address)
+                    .await
+            {
+                multicall_error = Some(format!(
+                    "Multicall3 contract not found at {address}; set MULTICALL3_ADDRESS for this chain"
+                ));
+            }
+        }
+    }
+
+    if db_ok && multicall_error.is_none() {
         Response::from_json(&serde_json::json!({
             "status": "ready",
             "version": env!("CARGO_PKG_VERSION"),
@@ -419,7 +487,7 @@ async fn handle_ready(env: &Env) -> worker::Result<Response> {
     } else {
         Response::from_json(&serde_json::json!({
             "status": "not_ready",
-            "error": db_error,
+            "error": db_error.or(multicall_error),
         }))
         .map(|r| r.with_status(503))
     }
@@ -441,7 +509,11 @@ async fn handle_health(env: &Env) -> worker::Result<Response> {
 
     let kv_started = types::now_ms();
     let (kv_ok, kv_error) = match env.kv("KV") {
-        Ok(kv) => match kv.get("health:ping").text().await {
+        Ok(kv) => match kv
+            .get(&infra::kv_key(&infra::kv_prefix(env), "health:ping"))
+            .text()
+            .await
+        {
             Ok(_) => (true, None),
             Err(err) => (false, Some(err.to_string())),
         },
@@ -555,6 +627,8 @@ fn apply_cors(mut resp: Response, env: &Env, origin: Option<&str>) -> worker::Re
         .map(|v| v.to_string())
         .unwrap_or_default();
     let configured = configured.trim();
+    let cors_config = http::cors_config(env);
+    let mut echoed_origin = false;
 
     if configured.is_empty() {
         if let Some(origin) = origin {
@@ -579,7 +653,7 @@ fn apply_cors(mut resp: Response, env: &Env, origin: Option<&str>) -> worker::Re
         if let Some(origin) = origin {
             if allowed.iter().any(|v| v.eq_ignore_ascii_case(origin)) {
                 headers.set("Access-Control-Allow-Origin", origin)?;
-                headers.set("Vary", "Origin")?;
+                echoed_origin = true;
             } else {
                 console_error!("[WARN] CORS rejected for origin {}", origin);
                 return Response::error("CORS forbidden", 403);
@@ -587,11 +661,12 @@ fn apply_cors(mut resp: Response, env: &Env, origin: Option<&str>) -> worker::Re
         }
     }
 
-    headers.set("Access-Control-Allow-Methods", "GET,POST,OPTIONS")?;
-    headers.set(
-        "Access-Control-Allow-Headers",
-        "Content-Type,x-api-key,x-request-id",
-    )?;
-    headers.set("Access-Control-Max-Age", "86400")?;
+    if http::cors_varies_by_origin(&cors_config, echoed_origin) {
+        headers.append("Vary", "Origin")?;
+    }
+    let policy = http::cors_policy(&cors_config, origin);
+    headers.set("Access-Control-Allow-Methods", &policy.methods)?;
+    headers.set("Access-Control-Allow-Headers", &policy.headers)?;
+    headers.set("Access-Control-Max-Age", &policy.max_age_secs.to_string())?;
     Ok(resp)
 }
@@ -1,17 +1,46 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 
 use crate::error::CroLensError;
 
+/// MCP 客户端通知方法前缀 (如 `notifications/initialized`)
+pub const NOTIFICATION_METHOD_PREFIX: &str = "notifications/";
+
 #[derive(Debug, Deserialize)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
-    pub id: Value,
+    /// `None` 表示请求中没有 `id` 字段 (通知)；`"id": null` 为 `Some(Value::Null)`
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub id: Option<Value>,
     pub method: String,
     #[serde(default)]
     pub params: Value,
 }
 
+fn deserialize_present<'de, D>(deserializer: D) -> Result<Option<Value>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Value::deserialize(deserializer).map(Some)
+}
+
+impl JsonRpcRequest {
+    /// 响应中使用的 id；通知没有 id 时回退为 null
+    pub fn response_id(&self) -> Value {
+        self.id.clone().unwrap_or(Value::Null)
+    }
+
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+
+    /// 支持的通知 (无 id 且为 `notifications/*`) 不返回 JSON-RPC 响应 (HTTP 204)；
+    /// 其他缺少 id 的请求仍按 `id: null` 处理，保持兼容
+    pub fn expects_response(&self) -> bool {
+        !(self.is_notification() && self.method.starts_with(NOTIFICATION_METHOD_PREFIX))
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct JsonRpcResponse {
     pub jsonrpc: &'static str,
@@ -40,6 +69,19 @@ impl JsonRpcResponse {
         }
     }
 
+    /// MCP 工具执行错误：作为成功结果返回，错误信息放在 content 中供模型读取
+    pub fn tool_error(id: Value, err: CroLensError) -> Self {
+        let (code, message, data) = err.to_json_rpc_error();
+        Self::success(
+            id,
+            serde_json::json!({
+                "content": [{ "type": "text", "text": message }],
+                "isError": true,
+                "error": { "code": code, "message": message, "data": data },
+            }),
+        )
+    }
+
     pub fn error(id: Value, err: CroLensError) -> Self {
         let (code, message, data) = err.to_json_rpc_error();
         Self {
@@ -69,6 +111,54 @@ mod tests {
         assert!(resp.result.is_some());
     }
 
+    #[test]
+    fn missing_id_is_notification() {
+        let req: JsonRpcRequest = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized"
+        }))
+        .expect("should parse");
+        assert!(req.is_notification());
+        assert!(!req.expects_response());
+        assert_eq!(req.response_id(), Value::Null);
+    }
+
+    #[test]
+    fn null_id_is_not_notification() {
+        let req: JsonRpcRequest = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "method": "notifications/initialized"
+        }))
+        .expect("should parse");
+        assert!(!req.is_notification());
+        assert!(req.expects_response());
+    }
+
+    #[test]
+    fn missing_id_on_regular_method_still_responds() {
+        let req: JsonRpcRequest = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "tools/list"
+        }))
+        .expect("should parse");
+        assert!(req.is_notification());
+        assert!(req.expects_response());
+    }
+
+    #[test]
+    fn response_echoes_request_id() {
+        let req: JsonRpcRequest = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "tools/list"
+        }))
+        .expect("should parse");
+        assert!(req.expects_response());
+        let resp = JsonRpcResponse::success(req.response_id(), serde_json::json!({ "tools": [] }));
+        assert_eq!(resp.id, serde_json::json!(7));
+    }
+
     #[test]
     fn builds_error_response() {
         let id = serde_json::json!("req-1");
@@ -79,6 +169,36 @@ mod tests {
         let err = resp.error.expect("error must exist");
         assert_eq!(err.code, -32003);
     }
+
+    #[test]
+    fn builds_tool_error_result() {
+        let id = serde_json::json!(7);
+        let resp =
+            JsonRpcResponse::tool_error(id.clone(), CroLensError::TokenNotFound("FOO".to_string()));
+        assert_eq!(resp.id, id);
+        assert!(resp.error.is_none());
+        let result = resp.result.expect("result must exist");
+        assert_eq!(result["isError"], true);
+        assert_eq!(result["content"][0]["type"], "text");
+        let text = result["content"][0]["text"].as_str().expect("text");
+        assert!(text.contains("FOO"));
+        assert_eq!(result["error"]["message"], text);
+        assert!(result["error"]["code"].is_i64());
+    }
+
+    #[test]
+    fn tool_call_params_default_to_hard_errors() {
+        let params: ToolCallParams =
+            serde_json::from_value(serde_json::json!({ "name": "get_gas_price" }))
+                .expect("should parse");
+        assert!(!params.soft_errors);
+
+        let params: ToolCallParams = serde_json::from_value(
+            serde_json::json!({ "name": "get_gas_price", "soft_errors": true }),
+        )
+        .expect("should parse");
+        assert!(params.soft_errors);
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -86,6 +206,12 @@ pub struct ToolCallParams {
     pub name: String,
     #[serde(default)]
     pub arguments: Value,
+    /// Dry-run: validate arguments and report credit cost without executing.
+    #[serde(default)]
+    pub validate: bool,
+    /// 工具执行错误以 `{content, isError: true}` 结果返回，而不是 JSON-RPC error
+    #[serde(default)]
+    pub soft_errors: bool,
 }
 
 #[derive(Debug, Serialize)]
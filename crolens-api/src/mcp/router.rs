@@ -1,5 +1,5 @@
 use serde_json::Value;
-use worker::{console_error, Env};
+use worker::{console_error, console_warn, Env};
 
 use crate::domain;
 use crate::error::CroLensError;
@@ -20,13 +20,19 @@ pub async fn handle(
 ) -> JsonRpcResponse {
     if req.jsonrpc != "2.0" {
         return JsonRpcResponse::error(
-            req.id,
+            req.response_id(),
             CroLensError::invalid_request("jsonrpc must be '2.0'".to_string()),
         );
     }
 
     match req.method.as_str() {
-        "tools/list" => JsonRpcResponse::success(req.id, crate::mcp::tools::list()),
+        "tools/list" => {
+            let overrides = load_tool_overrides(env).await;
+            JsonRpcResponse::success(
+                req.response_id(),
+                crate::mcp::tools::list_with_overrides(&overrides),
+            )
+        }
         "tools/call" => {
             handle_tools_call(
                 req,
@@ -39,10 +45,28 @@ pub async fn handle(
             )
             .await
         }
-        _ => JsonRpcResponse::error(req.id, CroLensError::method_not_found(req.method)),
+        _ => JsonRpcResponse::error(
+            req.response_id(),
+            CroLensError::method_not_found(req.method),
+        ),
     }
 }
 
+/// tool_overrides 不可用 (表不存在、DB/KV 未绑定) 时使用静态工具定义
+async fn load_tool_overrides(env: &Env) -> Vec<infra::config::ToolOverride> {
+    let (Ok(db), Ok(kv)) = (env.d1("DB"), env.kv("KV")) else {
+        return Vec::new();
+    };
+    let kv = infra::kv_stats::TrackedKv::untracked(kv);
+    let kv_prefix = infra::kv_prefix(env);
+    infra::config::list_tool_overrides_cached(&db, &kv, &kv_prefix)
+        .await
+        .unwrap_or_else(|err| {
+            console_warn!("[WARN] tool_overrides unavailable: {}", err);
+            Vec::new()
+        })
+}
+
 async fn handle_tools_call(
     req: JsonRpcRequest,
     env: &Env,
@@ -52,11 +76,12 @@ async fn handle_tools_call(
     client_ip: &str,
     request_size: usize,
 ) -> JsonRpcResponse {
-    let params: ToolCallParams = match serde_json::from_value(req.params) {
+    let id = req.response_id();
+    let mut params: ToolCallParams = match serde_json::from_value(req.params) {
         Ok(v) => v,
         Err(err) => {
             return JsonRpcResponse::error(
-                req.id,
+                id,
                 CroLensError::invalid_params(format!("Invalid tools/call params: {err}")),
             )
         }
@@ -64,130 +89,295 @@ async fn handle_tools_call(
 
     let db = match env.d1("DB") {
         Ok(v) => v,
-        Err(err) => return JsonRpcResponse::error(req.id, CroLensError::DbError(err.to_string())),
+        Err(err) => return JsonRpcResponse::error(id, CroLensError::DbError(err.to_string())),
     };
 
-    let tool_name = params.name.clone();
+    let (tool_name, deprecated_name) = resolve_tool_name(&params.name);
+    if let Some(deprecated) = deprecated_name {
+        console_warn!(
+            "[WARN] tool name '{}' is deprecated, use '{}'",
+            deprecated,
+            tool_name
+        );
+    }
+    let soft_errors = params.soft_errors;
+    // 错误是否来自工具本身的执行 (而不是鉴权/限流/计费等协议层错误)
+    let mut tool_failed = false;
+    for (deprecated, canonical) in apply_argument_aliases(&tool_name, &mut params.arguments) {
+        console_warn!(
+            "[WARN] {} argument '{}' is deprecated, use '{}'",
+            tool_name,
+            deprecated,
+            canonical
+        );
+    }
+    apply_default_simple_mode(&tool_name, &mut params.arguments, default_simple_mode(env));
     let outcome: std::result::Result<Value, CroLensError> = async {
         // Lazily load X402 config only when we need to return a payment error.
-        let lazy_payment_data = || async {
+        let payment_required = || async {
             match infra::x402::X402Config::try_load(env, &db).await {
-                Ok(Some(cfg)) => Some(serde_json::json!({
-                    "chain_id": 25,
-                    "payment_address": cfg.payment_address.to_string(),
-                    "price": format!("{} CRO", types::format_units(&cfg.topup_amount_wei(), 18)),
-                    "credits": cfg.topup_credits,
-                })),
-                _ => None,
+                Ok(Some(cfg)) => gateway::billing::PaymentRequiredData::new(
+                    cfg.payment_address,
+                    cfg.topup_amount_wei(),
+                    cfg.topup_credits,
+                )
+                .into_error(),
+                _ => CroLensError::payment_required(None),
             }
         };
 
         let key = api_key.ok_or_else(|| {
             CroLensError::invalid_params("Missing API key header: x-api-key".to_string())
         })?;
-        let record = gateway::ensure_api_key(&db, key, None).await?;
+        let policy = gateway::auth::ApiKeyPolicy::from_env(env);
+        let record = gateway::ensure_api_key(&db, &policy, key, None).await?;
 
-        let kv = env
-            .kv("KV")
-            .map_err(|err| CroLensError::KvError(err.to_string()))?;
+        // 限流、指标与工具本身的 KV 访问计入同一份 kv_stats
+        let services = infra::Services::new(env, trace_id, start_ms)?;
+        let kv = services.kv.clone();
         // Rate limit: 300/min for all tiers (generous for testing/demo)
         let limit = 300u32;
         let window_secs = 60u64;
-        let rl_key = format!("rl:tool:{}:{}", record.api_key, types::now_ms() / 60000);
-        let allowed = gateway::ratelimit::check_rate_limit(&kv, &rl_key, limit, window_secs).await?;
-        if !allowed {
-            return Err(CroLensError::rate_limit_exceeded(Some(window_secs as u32)));
+        let minute = types::now_ms() / 60000;
+        let kv_prefix = infra::kv_prefix(env);
+        let rl_key = infra::kv_key(
+            &kv_prefix,
+            &format!("rl:tool:{}:{}", record.api_key, minute),
+        );
+        // 昂贵工具 (如 simulate_transaction) 另有单工具子限额，与全局限额同时生效
+        let tool_limits = gateway::ratelimit::parse_tool_limits(
+            env.var("RATE_LIMIT_TOOL_LIMITS")
+                .ok()
+                .map(|v| v.to_string())
+                .as_deref(),
+        );
+        let tool_rl_key = infra::kv_key(
+            &kv_prefix,
+            &format!("rl:tool:{}:{}:{}", record.api_key, tool_name, minute),
+        );
+        let mut limits = vec![(rl_key.as_str(), limit)];
+        if let Some(tool_limit) = tool_limits.get(&tool_name) {
+            limits.push((tool_rl_key.as_str(), *tool_limit));
         }
-
-        if record.credits <= 0 {
-            return Err(CroLensError::payment_required(lazy_payment_data().await));
+        // 可信 key/IP 跳过限流，计费照常
+        let bypass = gateway::ratelimit::RateLimitBypass::from_env(env)
+            .allows(Some(record.api_key.as_str()), client_ip);
+        let rate_limit = async {
+            let allowed = gateway::ratelimit::check_combined_rate_limit_with_bypass(
+                &kv,
+                &limits,
+                window_secs,
+                bypass,
+            )
+            .await?;
+            if !allowed {
+                return Err(CroLensError::rate_limit_exceeded(Some(window_secs as u32)));
+            }
+            Ok(())
+        };
+        let store = gateway::D1ApiKeyStore::new(&db);
+        let admission = admit_tool_call(
+            &store,
+            &record,
+            &tool_name,
+            &params.arguments,
+            params.validate,
+            rate_limit,
+        )
+        .await;
+        match admission {
+            Ok(Admission::DryRun(value)) => return Ok(value),
+            Ok(Admission::Charged) => {}
+            // 余额不足或并发请求在检查之后耗尽 credit，都返回充值信息
+            Err(CroLensError::PaymentRequired { .. }) => return Err(payment_required().await),
+            Err(err) => return Err(err),
         }
-        // Free tier can access all tools; access restrictions can be added later if needed.
-        gateway::deduct_credit(&db, &record.api_key).await?;
 
-        let services = infra::Services::new(env, trace_id, start_ms)?;
-        match tool_name.as_str() {
-            "get_account_summary" => {
-                domain::assets::get_account_summary(&services, params.arguments).await
+        let arguments = params.arguments.clone();
+        let dispatch = async {
+            match tool_name.as_str() {
+                "get_account_summary" => {
+                    domain::assets::get_account_summary(&services, params.arguments).await
+                }
+                "get_defi_positions" => {
+                    domain::defi::get_defi_positions(&services, params.arguments).await
+                }
+                "decode_transaction" => {
+                    domain::transaction::decode_transaction(&services, params.arguments).await
+                }
+                "decode_transactions" => {
+                    domain::transaction::decode_transactions(&services, params.arguments).await
+                }
+                "simulate_transaction" => {
+                    domain::simulation::simulate_transaction(&services, params.arguments).await
+                }
+                "search_contract" => {
+                    domain::search::search_contract(&services, params.arguments).await
+                }
+                "construct_swap_tx" => {
+                    domain::swap::construct_swap_tx(&services, params.arguments).await
+                }
+                // New tools
+                "get_token_info" => {
+                    domain::token_info::get_token_info(&services, params.arguments).await
+                }
+                "get_pool_info" => {
+                    domain::pool_info::get_pool_info(&services, params.arguments).await
+                }
+                "get_gas_price" => domain::gas::get_gas_price(&services, params.arguments).await,
+                "get_token_price" => {
+                    domain::price::get_token_price(&services, params.arguments).await
+                }
+                "get_approval_status" => {
+                    domain::approval::get_approval_status(&services, params.arguments).await
+                }
+                "get_block_info" => {
+                    domain::block::get_block_info(&services, params.arguments).await
+                }
+                // Phase 1
+                "estimate_gas" => {
+                    domain::gas_estimate::estimate_gas(&services, params.arguments).await
+                }
+                "decode_calldata" => {
+                    domain::calldata::decode_calldata(&services, params.arguments).await
+                }
+                "get_vvs_farms" => domain::vvs::get_vvs_farms(&services, params.arguments).await,
+                "get_vvs_rewards" => {
+                    domain::vvs::get_vvs_rewards(&services, params.arguments).await
+                }
+                "get_tectonic_markets" => {
+                    domain::tectonic::get_tectonic_markets(&services, params.arguments).await
+                }
+                "get_tectonic_rates" => {
+                    domain::tectonic::get_tectonic_rates(&services, params.arguments).await
+                }
+                "construct_revoke_approval" => {
+                    domain::revoke_approval::construct_revoke_approval(&services, params.arguments)
+                        .await
+                }
+                "get_lending_rates" => {
+                    domain::lending::get_lending_rates(&services, params.arguments).await
+                }
+                // Phase 2
+                "get_cro_overview" => {
+                    domain::cro::get_cro_overview(&services, params.arguments).await
+                }
+                "get_liquidation_risk" => {
+                    domain::lending::get_liquidation_risk(&services, params.arguments).await
+                }
+                "get_health_alerts" => {
+                    domain::health::get_health_alerts(&services, params.arguments).await
+                }
+                "get_best_swap_route" => {
+                    domain::swap_route::get_best_swap_route(&services, params.arguments).await
+                }
+                "get_protocol_stats" => {
+                    domain::protocol_stats::get_protocol_stats(&services, params.arguments).await
+                }
+                "resolve_cronos_id" => {
+                    domain::cronos_id::resolve_cronos_id(&services, params.arguments).await
+                }
+                "get_token_approvals" => {
+                    domain::token_approvals::get_token_approvals(&services, params.arguments).await
+                }
+                "get_contract_info" => {
+                    domain::contract_info::get_contract_info(&services, params.arguments).await
+                }
+                "get_whale_activity" => {
+                    domain::whale_activity::get_whale_activity(&services, params.arguments).await
+                }
+                "get_portfolio_analysis" => {
+                    domain::portfolio::get_portfolio_analysis(&services, params.arguments).await
+                }
+                _ => Err(CroLensError::method_not_found(format!(
+                    "Unknown tool: {tool_name}"
+                ))),
             }
-            "get_defi_positions" => {
-                domain::defi::get_defi_positions(&services, params.arguments).await
-            }
-            "decode_transaction" => {
-                domain::transaction::decode_transaction(&services, params.arguments).await
-            }
-            "simulate_transaction" => {
-                domain::simulation::simulate_transaction(&services, params.arguments).await
-            }
-            "search_contract" => domain::search::search_contract(&services, params.arguments).await,
-            "construct_swap_tx" => {
-                domain::swap::construct_swap_tx(&services, params.arguments).await
-            }
-            // New tools
-            "get_token_info" => {
-                domain::token_info::get_token_info(&services, params.arguments).await
-            }
-            "get_pool_info" => {
-                domain::pool_info::get_pool_info(&services, params.arguments).await
-            }
-            "get_gas_price" => domain::gas::get_gas_price(&services, params.arguments).await,
-            "get_token_price" => domain::price::get_token_price(&services, params.arguments).await,
-            "get_approval_status" => {
-                domain::approval::get_approval_status(&services, params.arguments).await
-            }
-            "get_block_info" => domain::block::get_block_info(&services, params.arguments).await,
-            // Phase 1
-            "estimate_gas" => {
-                domain::gas_estimate::estimate_gas(&services, params.arguments).await
-            }
-            "decode_calldata" => domain::calldata::decode_calldata(&services, params.arguments).await,
-            "get_vvs_farms" => domain::vvs::get_vvs_farms(&services, params.arguments).await,
-            "get_vvs_rewards" => domain::vvs::get_vvs_rewards(&services, params.arguments).await,
-            "get_tectonic_markets" => {
-                domain::tectonic::get_tectonic_markets(&services, params.arguments).await
-            }
-            "get_tectonic_rates" => {
-                domain::tectonic::get_tectonic_rates(&services, params.arguments).await
-            }
-            "construct_revoke_approval" => {
-                domain::revoke_approval::construct_revoke_approval(&services, params.arguments).await
-            }
-            "get_lending_rates" => {
-                domain::lending::get_lending_rates(&services, params.arguments).await
-            }
-            // Phase 2
-            "get_cro_overview" => domain::cro::get_cro_overview(&services, params.arguments).await,
-            "get_liquidation_risk" => {
-                domain::lending::get_liquidation_risk(&services, params.arguments).await
-            }
-            "get_health_alerts" => {
-                domain::health::get_health_alerts(&services, params.arguments).await
-            }
-            "get_best_swap_route" => {
-                domain::swap_route::get_best_swap_route(&services, params.arguments).await
-            }
-            "get_protocol_stats" => {
-                domain::protocol_stats::get_protocol_stats(&services, params.arguments).await
-            }
-            "resolve_cronos_id" => {
-                domain::cronos_id::resolve_cronos_id(&services, params.arguments).await
-            }
-            "get_token_approvals" => {
-                domain::token_approvals::get_token_approvals(&services, params.arguments).await
-            }
-            "get_contract_info" => {
-                domain::contract_info::get_contract_info(&services, params.arguments).await
-            }
-            "get_whale_activity" => {
-                domain::whale_activity::get_whale_activity(&services, params.arguments).await
+        };
+        // 在整体请求超时之前放弃工具调用：已扣除的 credit 退还，调用照常计入指标与 request_log
+        let request_timeout_ms = crate::http::request_timeout_ms(env);
+        // 延迟直方图只统计工具执行本身，不含鉴权、限流与扣费
+        let dispatch_start_ms = types::now_ms();
+        let budget_ms = tool_time_budget_ms(start_ms, request_timeout_ms, dispatch_start_ms);
+        let timeout = worker::Delay::from(std::time::Duration::from_millis(budget_ms));
+        let mut result = match infra::concurrency::race_timeout(dispatch, timeout).await {
+            Some(result) => result,
+            None => {
+                console_warn!(
+                    "[WARN] [{}] {} abandoned after {}ms, refunding credit",
+                    trace_id,
+                    tool_name,
+                    budget_ms
+                );
+                let refund = gateway::billing::refund_credits_with_store(
+                    &store,
+                    &record.api_key,
+                    gateway::billing::tool_credit_cost(&tool_name),
+                )
+                .await;
+                if let Err(err) = refund {
+                    console_error!("[WARN] credit refund failed: {}", err);
+                }
+                Err(CroLensError::Timeout {
+                    timeout_ms: request_timeout_ms,
+                })
             }
-            "get_portfolio_analysis" => {
-                domain::portfolio::get_portfolio_analysis(&services, params.arguments).await
+        };
+        tool_failed = result.as_ref().err().is_some_and(is_tool_execution_error);
+
+        // 按工具统计成功/失败次数与延迟直方图 (在 isolate 内累积后定期写入，尽力而为，不等待 KV 写入)
+        let metric_tool = if crate::mcp::tools::tool_exists(&tool_name) {
+            tool_name.as_str()
+        } else {
+            gateway::metrics::UNKNOWN_TOOL
+        };
+        let mut metric_keys =
+            gateway::metrics::outcome_keys(&kv_prefix, metric_tool, result.as_ref().err());
+        let latency_ms = types::now_ms().saturating_sub(dispatch_start_ms);
+        metric_keys.push(gateway::metrics::latency_bucket_key(
+            &kv_prefix,
+            metric_tool,
+            gateway::metrics::latency_bucket(latency_ms),
+        ));
+        if let Some(deltas) = gateway::metrics::record_counters(&metric_keys, types::now_ms()) {
+            let metrics_kv = kv.clone();
+            worker::wasm_bindgen_futures::spawn_local(async move {
+                if let Err(err) = gateway::metrics::flush_counters(&metrics_kv, &deltas).await {
+                    console_warn!("[WARN] Failed to update tool metrics: {}", err);
+                }
+            });
+        }
+
+        // 调用前已扣除一次；批量工具按成功项补扣差额 (尽力而为，不影响已完成的结果)
+        if let Ok(value) = &mut result {
+            let extra = gateway::billing::result_credit_cost(&tool_name, &arguments, value)
+                - gateway::billing::tool_credit_cost(&tool_name);
+            if extra > 0 {
+                let charged =
+                    match gateway::billing::deduct_extra_credits(&db, &record.api_key, extra).await
+                    {
+                        Ok(charged) => charged,
+                        Err(err) => {
+                            console_warn!("[WARN] Failed to deduct extra credits: {}", err);
+                            0
+                        }
+                    };
+                if charged < extra {
+                    gateway::billing::reconcile_credits_charged(&tool_name, value, charged);
+                }
             }
-            _ => Err(CroLensError::method_not_found(format!(
-                "Unknown tool: {tool_name}"
-            ))),
         }
+        // 结果过大时截断数组，避免超大响应耗尽 Worker 内存或客户端
+        result.map(|value| {
+            let (value, truncated) = cap_result_size(value, max_tool_result_bytes(env));
+            if truncated {
+                console_warn!(
+                    "[WARN] {} result exceeded size cap, arrays truncated",
+                    tool_name
+                );
+            }
+            value
+        })
     }
     .await;
 
@@ -235,9 +425,221 @@ async fn handle_tools_call(
         }
     }
 
+    tool_call_response(id, outcome, soft_errors && tool_failed)
+}
+
+/// 工具调用的准入结果
+#[derive(Debug, PartialEq)]
+pub enum Admission {
+    /// validate 空跑的结果：不计入限流，也不扣费
+    DryRun(Value),
+    /// 已通过限流并扣除基础 credit
+    Charged,
+}
+
+/// 工具执行前的关卡：validate 空跑 (只做 schema 校验) 在限流与扣费之前直接返回；
+/// 否则依次限流、按最大可能成本检查余额 (批量工具按项计费) 并扣除基础 credit
+pub async fn admit_tool_call<S, F>(
+    store: &S,
+    record: &gateway::ApiKeyRecord,
+    tool: &str,
+    arguments: &Value,
+    validate: bool,
+    rate_limit: F,
+) -> Result<Admission, CroLensError>
+where
+    S: gateway::store::ApiKeyStore,
+    F: std::future::Future<Output = Result<(), CroLensError>>,
+{
+    if validate {
+        return crate::mcp::tools::dry_run(tool, arguments).map(Admission::DryRun);
+    }
+    rate_limit.await?;
+    if record.credits < gateway::billing::max_tool_credit_cost(tool, arguments) {
+        return Err(CroLensError::payment_required(None));
+    }
+    gateway::billing::deduct_credit_with_store(store, &record.api_key).await?;
+    Ok(Admission::Charged)
+}
+
+/// 工具调用需在整体请求超时前这么多毫秒结束，留出退还 credit 与写 request_log 的时间
+const TOOL_ABANDON_MARGIN_MS: u64 = 1_000;
+
+/// 工具调用剩余的可用时间 (毫秒)；已超出时为 0
+fn tool_time_budget_ms(start_ms: i64, request_timeout_ms: u64, now_ms: i64) -> u64 {
+    let elapsed_ms = u64::try_from(now_ms.saturating_sub(start_ms)).unwrap_or(0);
+    request_timeout_ms
+        .saturating_sub(TOOL_ABANDON_MARGIN_MS)
+        .saturating_sub(elapsed_ms)
+}
+
+/// 工具执行中产生的错误；未知工具仍是协议错误
+fn is_tool_execution_error(err: &CroLensError) -> bool {
+    !matches!(err, CroLensError::MethodNotFound(_))
+}
+
+/// `soft_error` 为 true 时执行错误以 `isError` 结果返回，否则为 JSON-RPC error
+fn tool_call_response(
+    id: Value,
+    outcome: std::result::Result<Value, CroLensError>,
+    soft_error: bool,
+) -> JsonRpcResponse {
     match outcome {
-        Ok(value) => JsonRpcResponse::success(req.id, value),
-        Err(err) => JsonRpcResponse::error(req.id, err),
+        Ok(value) => JsonRpcResponse::success(id, value),
+        Err(err) if soft_error => JsonRpcResponse::tool_error(id, err),
+        Err(err) => JsonRpcResponse::error(id, err),
+    }
+}
+
+/// 单个工具结果序列化后的默认上限 (1 MiB)
+const MAX_TOOL_RESULT_BYTES_DEFAULT: usize = 1024 * 1024;
+const MAX_TOOL_RESULT_BYTES_MIN: usize = 16 * 1024;
+
+fn max_tool_result_bytes(env: &Env) -> usize {
+    env.var("MAX_TOOL_RESULT_BYTES")
+        .ok()
+        .and_then(|v| v.to_string().trim().parse::<usize>().ok())
+        .map(|v| v.max(MAX_TOOL_RESULT_BYTES_MIN))
+        .unwrap_or(MAX_TOOL_RESULT_BYTES_DEFAULT)
+}
+
+fn serialized_len(value: &Value) -> usize {
+    serde_json::to_vec(value).map(|v| v.len()).unwrap_or(0)
+}
+
+/// 找出元素最多的数组，返回 (长度, JSON pointer)
+fn largest_array(value: &Value, pointer: &str, best: &mut Option<(usize, String)>) {
+    match value {
+        Value::Array(items) => {
+            let longer = best
+                .as_ref()
+                .map(|(len, _)| items.len() > *len)
+                .unwrap_or(true);
+            if !items.is_empty() && longer {
+                *best = Some((items.len(), pointer.to_string()));
+            }
+            for (idx, item) in items.iter().enumerate() {
+                largest_array(item, &format!("{pointer}/{idx}"), best);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                let escaped = key.replace('~', "~0").replace('/', "~1");
+                largest_array(item, &format!("{pointer}/{escaped}"), best);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 序列化后超过 `max_bytes` 时反复把最长的数组减半，直到满足上限或没有可截断的数组；
+/// 发生截断时在顶层对象上设置 `truncated: true`
+fn cap_result_size(mut value: Value, max_bytes: usize) -> (Value, bool) {
+    let mut truncated = false;
+    while serialized_len(&value) > max_bytes {
+        let mut best = None;
+        largest_array(&value, "", &mut best);
+        let Some((len, pointer)) = best else {
+            break;
+        };
+        let Some(items) = value.pointer_mut(&pointer).and_then(|v| v.as_array_mut()) else {
+            break;
+        };
+        items.truncate(len / 2);
+        if !truncated {
+            // 标记本身也计入大小，先写入再继续检查
+            if let Some(map) = value.as_object_mut() {
+                map.insert("truncated".to_string(), Value::Bool(true));
+            }
+            truncated = true;
+        }
+    }
+    (value, truncated)
+}
+
+/// 已改名工具的旧名称 (deprecated, canonical)；只登记客户端实际调用过的旧名，工具改名时在此追加。
+/// tools/list 只列出 canonical 名称
+const TOOL_NAME_ALIASES: &[(&str, &str)] = &[];
+
+/// 规范化工具名 (去空白、小写) 并解析别名；返回 (canonical, 命中的废弃名称)。
+/// 未知名称原样返回，由分发时报 method not found
+fn resolve_tool_name(name: &str) -> (String, Option<&'static str>) {
+    resolve_tool_name_with(name, TOOL_NAME_ALIASES)
+}
+
+fn resolve_tool_name_with(
+    name: &str,
+    aliases: &[(&'static str, &'static str)],
+) -> (String, Option<&'static str>) {
+    let normalized = name.trim().to_ascii_lowercase();
+    match aliases
+        .iter()
+        .find(|(deprecated, _)| *deprecated == normalized)
+    {
+        Some((deprecated, canonical)) => (canonical.to_string(), Some(*deprecated)),
+        None => (normalized, None),
+    }
+}
+
+/// 已废弃的参数名 (tool, deprecated, canonical)，在反序列化前改写为当前名称
+const ARGUMENT_ALIASES: &[(&str, &str, &str)] = &[
+    ("get_account_summary", "wallet", "address"),
+    ("get_defi_positions", "wallet", "address"),
+    ("get_approval_status", "wallet", "address"),
+    ("get_vvs_rewards", "wallet", "address"),
+    ("get_liquidation_risk", "wallet", "address"),
+    ("get_health_alerts", "wallet", "address"),
+    ("get_token_approvals", "wallet", "address"),
+    ("get_portfolio_analysis", "wallet", "address"),
+    ("decode_transaction", "hash", "tx_hash"),
+];
+
+/// 改写废弃参数名并返回被改写的 (deprecated, canonical)；两者同时存在时以当前名称为准
+fn apply_argument_aliases(tool: &str, arguments: &mut Value) -> Vec<(&'static str, &'static str)> {
+    let Some(args) = arguments.as_object_mut() else {
+        return Vec::new();
+    };
+    let mut applied = Vec::new();
+    for (_, deprecated, canonical) in ARGUMENT_ALIASES.iter().filter(|(t, _, _)| *t == tool) {
+        let Some(value) = args.remove(*deprecated) else {
+            continue;
+        };
+        if !args.contains_key(*canonical) {
+            args.insert(canonical.to_string(), value);
+        }
+        applied.push((*deprecated, *canonical));
+    }
+    applied
+}
+
+/// 读取 DEFAULT_SIMPLE_MODE；"true"/"1" 时未传 simple_mode 的调用默认使用精简文本输出
+fn default_simple_mode(env: &Env) -> bool {
+    parse_default_simple_mode(
+        env.var("DEFAULT_SIMPLE_MODE")
+            .ok()
+            .map(|v| v.to_string())
+            .as_deref(),
+    )
+}
+
+fn parse_default_simple_mode(value: Option<&str>) -> bool {
+    value.is_some_and(|v| {
+        let v = v.trim();
+        v == "1" || v.eq_ignore_ascii_case("true")
+    })
+}
+
+/// 服务端默认开启 simple_mode 时，为支持该参数且未显式传值的调用补上 simple_mode: true；
+/// 调用方显式传入的值 (包括 false) 优先
+fn apply_default_simple_mode(tool: &str, arguments: &mut Value, enabled: bool) {
+    if !enabled || !crate::mcp::tools::accepts_argument(tool, "simple_mode") {
+        return;
+    }
+    if arguments.is_null() {
+        *arguments = Value::Object(serde_json::Map::new());
+    }
+    if let Some(args) = arguments.as_object_mut() {
+        args.entry("simple_mode").or_insert(Value::Bool(true));
     }
 }
 
@@ -259,3 +661,238 @@ fn should_sample(trace_id: &str, sample_rate: f64) -> bool {
     let bucket = (v % 10_000) as f64 / 10_000.0;
     bucket < sample_rate
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "0x1234567890123456789012345678901234567890";
+
+    fn oversized_result(positions: usize) -> Value {
+        let items: Vec<Value> = (0..positions)
+            .map(|i| serde_json::json!({ "pool": format!("pool-{i}"), "value_usd": "123.45" }))
+            .collect();
+        serde_json::json!({
+            "address": ADDRESS,
+            "positions": items,
+            "tags": ["a", "b"],
+            "meta": { "trace_id": "t" },
+        })
+    }
+
+    #[test]
+    fn small_result_is_untouched() {
+        let value = oversized_result(3);
+        let (capped, truncated) = cap_result_size(value.clone(), MAX_TOOL_RESULT_BYTES_DEFAULT);
+        assert!(!truncated);
+        assert_eq!(capped, value);
+    }
+
+    #[test]
+    fn oversized_result_is_truncated_under_cap() {
+        let max_bytes = 4 * 1024;
+        let value = oversized_result(5_000);
+        assert!(serialized_len(&value) > max_bytes);
+
+        let (capped, truncated) = cap_result_size(value, max_bytes);
+        assert!(truncated);
+        assert!(serialized_len(&capped) <= max_bytes);
+        assert_eq!(capped["truncated"], true);
+        assert_eq!(capped["address"], ADDRESS);
+        assert_eq!(capped["meta"]["trace_id"], "t");
+        let kept = capped["positions"].as_array().expect("positions").len();
+        assert!(kept > 0 && kept < 5_000);
+        assert_eq!(capped["positions"][0]["pool"], "pool-0");
+    }
+
+    #[test]
+    fn nested_arrays_are_truncated() {
+        let inner: Vec<Value> = (0..2_000).map(|i| serde_json::json!(i)).collect();
+        let value = serde_json::json!({ "vvs": { "positions": inner } });
+        let (capped, truncated) = cap_result_size(value, 1024);
+        assert!(truncated);
+        assert!(serialized_len(&capped) <= 1024);
+    }
+
+    #[test]
+    fn result_without_arrays_is_returned_as_is() {
+        let value = serde_json::json!({ "text": "x".repeat(2_000) });
+        let (capped, truncated) = cap_result_size(value.clone(), 1024);
+        assert!(!truncated);
+        assert_eq!(capped, value);
+    }
+
+    #[test]
+    fn tool_budget_ends_before_the_request_timeout() {
+        let start = 1_700_000_000_000_i64;
+        assert_eq!(
+            tool_time_budget_ms(start, 30_000, start),
+            30_000 - TOOL_ABANDON_MARGIN_MS
+        );
+        assert_eq!(
+            tool_time_budget_ms(start, 30_000, start + 4_000),
+            26_000 - TOOL_ABANDON_MARGIN_MS
+        );
+        assert_eq!(tool_time_budget_ms(start, 30_000, start + 45_000), 0);
+        // 时钟回拨不延长预算
+        assert_eq!(
+            tool_time_budget_ms(start, 30_000, start - 5_000),
+            30_000 - TOOL_ABANDON_MARGIN_MS
+        );
+    }
+
+    #[test]
+    fn soft_tool_error_becomes_is_error_result() {
+        let err = CroLensError::RpcError("upstream timeout".to_string());
+        assert!(is_tool_execution_error(&err));
+        let resp = tool_call_response(serde_json::json!(1), Err(err), true);
+        assert!(resp.error.is_none());
+        let result = resp.result.expect("soft error is a result");
+        assert_eq!(result["isError"], true);
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .expect("text")
+            .contains("upstream timeout"));
+    }
+
+    #[test]
+    fn hard_error_path_keeps_json_rpc_error() {
+        let err = CroLensError::RpcError("upstream timeout".to_string());
+        let resp = tool_call_response(serde_json::json!(1), Err(err), false);
+        assert!(resp.result.is_none());
+        let error = resp.error.expect("hard error");
+        assert!(error.message.contains("upstream timeout"));
+
+        let ok = tool_call_response(
+            serde_json::json!(1),
+            Ok(serde_json::json!({ "a": 1 })),
+            true,
+        );
+        assert_eq!(ok.result, Some(serde_json::json!({ "a": 1 })));
+    }
+
+    #[test]
+    fn unknown_tool_is_not_a_tool_execution_error() {
+        assert!(!is_tool_execution_error(&CroLensError::method_not_found(
+            "Unknown tool: nope".to_string()
+        )));
+        assert!(is_tool_execution_error(&CroLensError::invalid_params(
+            "Invalid input".to_string()
+        )));
+    }
+
+    #[test]
+    fn aliased_argument_is_rewritten_to_canonical() {
+        let mut args = serde_json::json!({ "wallet": ADDRESS, "simple_mode": true });
+        let applied = apply_argument_aliases("get_account_summary", &mut args);
+        assert_eq!(applied, vec![("wallet", "address")]);
+        assert_eq!(
+            args,
+            serde_json::json!({ "address": ADDRESS, "simple_mode": true })
+        );
+    }
+
+    #[test]
+    fn aliased_argument_validates_like_canonical() {
+        let mut aliased = serde_json::json!({ "hash": format!("0x{}", "ab".repeat(32)) });
+        apply_argument_aliases("decode_transaction", &mut aliased);
+        let canonical = serde_json::json!({ "tx_hash": format!("0x{}", "ab".repeat(32)) });
+        assert_eq!(
+            crate::mcp::tools::dry_run("decode_transaction", &aliased).expect("aliased is valid"),
+            crate::mcp::tools::dry_run("decode_transaction", &canonical)
+                .expect("canonical is valid")
+        );
+    }
+
+    #[test]
+    fn canonical_name_wins_over_alias() {
+        let other = "0x0000000000000000000000000000000000000001";
+        let mut args = serde_json::json!({ "address": ADDRESS, "wallet": other });
+        let applied = apply_argument_aliases("get_defi_positions", &mut args);
+        assert_eq!(applied, vec![("wallet", "address")]);
+        assert_eq!(args, serde_json::json!({ "address": ADDRESS }));
+    }
+
+    #[test]
+    fn aliases_are_scoped_per_tool() {
+        let mut args = serde_json::json!({ "wallet": ADDRESS });
+        assert!(apply_argument_aliases("get_token_price", &mut args).is_empty());
+        assert_eq!(args, serde_json::json!({ "wallet": ADDRESS }));
+
+        let mut not_object = serde_json::json!(null);
+        assert!(apply_argument_aliases("get_account_summary", &mut not_object).is_empty());
+    }
+
+    #[test]
+    fn tool_alias_routes_to_canonical_handler() {
+        const RENAMED: &[(&str, &str)] = &[("get_account_overview", "get_account_summary")];
+        let args = serde_json::json!({ "address": ADDRESS });
+        // 旧名本身不是已注册工具，只有解析后才能分发
+        assert!(matches!(
+            crate::mcp::tools::dry_run("get_account_overview", &args),
+            Err(CroLensError::MethodNotFound(_))
+        ));
+
+        let (canonical, deprecated) = resolve_tool_name_with(" Get_Account_Overview ", RENAMED);
+        assert_eq!(canonical, "get_account_summary");
+        assert_eq!(deprecated, Some("get_account_overview"));
+        assert!(crate::mcp::tools::dry_run(&canonical, &args).is_ok());
+    }
+
+    #[test]
+    fn tool_names_are_normalized_before_alias_lookup() {
+        assert_eq!(
+            resolve_tool_name(" Get_Token_Price "),
+            ("get_token_price".to_string(), None)
+        );
+        assert_eq!(
+            resolve_tool_name("no_such_tool"),
+            ("no_such_tool".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn tool_aliases_point_at_listed_tools_only() {
+        for (deprecated, canonical) in TOOL_NAME_ALIASES {
+            assert!(crate::mcp::tools::tool_exists(canonical), "{canonical}");
+            assert!(!crate::mcp::tools::tool_exists(deprecated), "{deprecated}");
+        }
+    }
+
+    #[test]
+    fn parse_default_simple_mode_accepts_true_or_one() {
+        assert!(parse_default_simple_mode(Some("true")));
+        assert!(parse_default_simple_mode(Some(" 1 ")));
+        assert!(!parse_default_simple_mode(Some("false")));
+        assert!(!parse_default_simple_mode(None));
+    }
+
+    #[test]
+    fn default_simple_mode_is_applied_when_unset() {
+        let mut args = serde_json::json!({ "tokens": ["CRO"] });
+        apply_default_simple_mode("get_token_price", &mut args, true);
+        assert_eq!(args["simple_mode"], true);
+
+        let mut args = Value::Null;
+        apply_default_simple_mode("get_gas_price", &mut args, true);
+        assert_eq!(args, serde_json::json!({ "simple_mode": true }));
+    }
+
+    #[test]
+    fn explicit_simple_mode_overrides_default() {
+        let mut args = serde_json::json!({ "tokens": ["CRO"], "simple_mode": false });
+        apply_default_simple_mode("get_token_price", &mut args, true);
+        assert_eq!(args["simple_mode"], false);
+    }
+
+    #[test]
+    fn default_simple_mode_is_noop_when_disabled_or_unsupported() {
+        let mut args = serde_json::json!({ "tokens": ["CRO"] });
+        apply_default_simple_mode("get_token_price", &mut args, false);
+        assert!(args.get("simple_mode").is_none());
+
+        let mut args = serde_json::json!({});
+        apply_default_simple_mode("no_such_tool", &mut args, true);
+        assert!(args.get("simple_mode").is_none());
+    }
+}
@@ -1,13 +1,21 @@
 use serde_json::Value;
 
+use crate::error::{CroLensError, Result};
+use crate::gateway::billing;
+use crate::infra::config::ToolOverride;
 use crate::mcp::protocol::ToolDefinition;
 
 pub fn list() -> Value {
+    list_with_overrides(&[])
+}
+
+/// tools/list 响应：DB 中的覆盖合并到静态定义之上 (只影响列表，参数校验仍使用静态 schema)
+pub fn list_with_overrides(overrides: &[ToolOverride]) -> Value {
     Value::Object(
         [(
             "tools".to_string(),
             Value::Array(
-                tool_definitions()
+                apply_overrides(tool_definitions(), overrides)
                     .into_iter()
                     .map(|t| serde_json::to_value(t).unwrap_or(Value::Null))
                     .collect(),
@@ -18,15 +26,155 @@ pub fn list() -> Value {
     )
 }
 
+/// 按工具名覆盖描述与 inputSchema；未知工具名与不是 JSON 对象的 schema 被忽略
+fn apply_overrides(
+    mut definitions: Vec<ToolDefinition>,
+    overrides: &[ToolOverride],
+) -> Vec<ToolDefinition> {
+    for o in overrides {
+        let Some(definition) = definitions.iter_mut().find(|d| d.name == o.name) else {
+            continue;
+        };
+        if let Some(description) = o.description.as_ref() {
+            definition.description = description.clone();
+        }
+        let schema = o
+            .schema_json
+            .as_deref()
+            .and_then(|text| serde_json::from_str::<Value>(text).ok())
+            .filter(|schema| schema.is_object());
+        if let Some(schema) = schema {
+            definition.input_schema = schema;
+        }
+    }
+    definitions
+}
+
+/// 工具名是否在工具列表中
+pub fn tool_exists(name: &str) -> bool {
+    tool_definitions().iter().any(|t| t.name == name)
+}
+
+/// 工具的 inputSchema 是否声明了该参数
+pub fn accepts_argument(name: &str, argument: &str) -> bool {
+    tool_definitions()
+        .iter()
+        .find(|t| t.name == name)
+        .and_then(|t| t.input_schema.get("properties"))
+        .is_some_and(|props| props.get(argument).is_some())
+}
+
+/// tools/call 的 validate 预检：只做 schema 校验，不执行、不计费
+pub fn dry_run(name: &str, arguments: &Value) -> Result<Value> {
+    validate_arguments(name, arguments)?;
+    Ok(serde_json::json!({
+        "valid": true,
+        "tool": name,
+        "credit_cost": billing::max_tool_credit_cost(name, arguments),
+    }))
+}
+
+/// 按工具的 inputSchema 校验参数 (required / type / minimum / maximum)
+pub fn validate_arguments(name: &str, arguments: &Value) -> Result<()> {
+    let Some(tool) = tool_definitions().into_iter().find(|t| t.name == name) else {
+        return Err(CroLensError::method_not_found(format!(
+            "Unknown tool: {name}"
+        )));
+    };
+
+    let empty = serde_json::Map::new();
+    let args = match arguments {
+        Value::Null => &empty,
+        Value::Object(map) => map,
+        _ => {
+            return Err(CroLensError::invalid_params(
+                "Invalid input: arguments must be an object".to_string(),
+            ))
+        }
+    };
+
+    let schema = &tool.input_schema;
+    for key in schema
+        .get("required")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str())
+    {
+        if matches!(args.get(key), None | Some(Value::Null)) {
+            return Err(CroLensError::invalid_params(format!(
+                "Invalid input: missing field `{key}`"
+            )));
+        }
+    }
+
+    let Some(properties) = schema.get("properties").and_then(|v| v.as_object()) else {
+        return Ok(());
+    };
+    for (key, value) in args {
+        let Some(property) = properties.get(key) else {
+            continue;
+        };
+        if value.is_null() {
+            continue;
+        }
+        validate_property(key, property, value)?;
+    }
+
+    Ok(())
+}
+
+fn validate_property(key: &str, property: &Value, value: &Value) -> Result<()> {
+    let expected = property.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    let type_ok = match expected {
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    };
+    if !type_ok {
+        return Err(CroLensError::invalid_params(format!(
+            "Invalid input: `{key}` must be of type {expected}"
+        )));
+    }
+
+    if let Some(n) = value.as_f64() {
+        if let Some(min) = property.get("minimum").and_then(|v| v.as_f64()) {
+            if n < min {
+                return Err(CroLensError::invalid_params(format!(
+                    "Invalid input: `{key}` must be >= {min}"
+                )));
+            }
+        }
+        if let Some(max) = property.get("maximum").and_then(|v| v.as_f64()) {
+            if n > max {
+                return Err(CroLensError::invalid_params(format!(
+                    "Invalid input: `{key}` must be <= {max}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn tool_definitions() -> Vec<ToolDefinition> {
     vec![
         ToolDefinition {
             name: "get_account_summary".to_string(),
-            description: "Complete account overview: wallet balances + DeFi summary.".to_string(),
+            description: "Complete account overview: wallet balances + DeFi summary. Tokens flagged as spam are counted in hidden_tokens unless include_spam is true; unpriced tokens are kept and marked unpriced.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "address": { "type": "string" },
+                    "include_spam": { "type": "boolean" },
+                    "with_proof": { "type": "boolean", "description": "Attach an eth_getProof account proof and state root for the native balance (omitted with supported=false if the RPC lacks eth_getProof)" },
+                    "block": { "type": "integer", "minimum": 0, "description": "Read balances at this historical block number. USD valuation and the DeFi summary are omitted (usd_valuation_omitted=true) since historical prices are unavailable" },
+                    "pending": { "type": "boolean", "description": "Read balances at the 'pending' block tag to include mempool state; falls back to 'latest' with pending_fallback=true if the RPC rejects the block tag. DeFi positions are read at the same tag. Cannot be combined with block" },
+                    "include_nfts": { "type": "boolean", "description": "Add an nfts section listing ERC-721/1155 collections currently held, with counts. Approximated from Transfer logs in a recent block window, so older holdings are missed. Cannot be combined with block" },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": ["address"]
@@ -34,11 +182,12 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_defi_positions".to_string(),
-            description: "Detailed DeFi positions (VVS LP, Tectonic supply/borrow).".to_string(),
+            description: "Detailed DeFi positions (VVS LP, Tectonic supply/borrow). Pending farm rewards are a list of {token, amount, usd}: VVS first, plus any bonus reward tokens the farm exposes.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "address": { "type": "string" },
+                    "protocols": { "type": "array", "items": { "type": "string", "enum": ["vvs", "tectonic"] }, "description": "Only query these protocols (default: all)" },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": ["address"]
@@ -46,7 +195,7 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "decode_transaction".to_string(),
-            description: "Translate transaction hash to human-readable action.".to_string(),
+            description: "Translate transaction hash to human-readable action, including the fee actually paid (legacy and EIP-1559). DEX actions are labelled with the router's protocol (e.g. \"Swap on VVS Finance\"), and WCRO deposit/withdraw as \"Wrap CRO\" / \"Unwrap WCRO\" with the amount; selectors outside the built-in ABI fall back to the function-signature table.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -56,9 +205,27 @@ fn tool_definitions() -> Vec<ToolDefinition> {
                 "required": ["tx_hash"]
             }),
         },
+        ToolDefinition {
+            name: "decode_transactions".to_string(),
+            description: "Decode up to 20 transaction hashes in one call. Each hash gets its own result or error; charges 1 credit per decoded hash."
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "tx_hashes": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Array of transaction hashes",
+                        "maxItems": 20
+                    },
+                    "simple_mode": { "type": "boolean" }
+                },
+                "required": ["tx_hashes"]
+            }),
+        },
         ToolDefinition {
             name: "simulate_transaction".to_string(),
-            description: "Simulate transaction execution and return state changes + risk hints."
+            description: "Simulate transaction execution and return state changes + risk hints. Internal calls are bounded by depth and count (calls_truncated: true when cut)."
                 .to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
@@ -80,7 +247,11 @@ fn tool_definitions() -> Vec<ToolDefinition> {
                 "type": "object",
                 "properties": {
                     "query": { "type": "string" },
-                    "limit": { "type": "integer", "minimum": 1, "maximum": 50 }
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Max results (default 20, capped at 50)"
+                    }
                 },
                 "required": ["query"]
             }),
@@ -95,20 +266,21 @@ fn tool_definitions() -> Vec<ToolDefinition> {
                     "token_in": { "type": "string" },
                     "token_out": { "type": "string" },
                     "amount_in": { "type": "string" },
-                    "slippage_bps": { "type": "integer", "minimum": 0, "maximum": 5000 }
+                    "slippage_bps": { "type": "integer", "minimum": 0, "maximum": 5000, "description": "Defaults to DEFAULT_SLIPPAGE_BPS (50); covers price movement before execution (the quote already includes price impact, a warning is returned when the impact exceeds it)" },
+                    "include_nonce": { "type": "boolean", "default": false, "description": "Add a suggested nonce (pending nonce, incremented per step) to each step's tx_data" }
                 },
-                "required": ["from", "token_in", "token_out", "amount_in", "slippage_bps"]
+                "required": ["from", "token_in", "token_out", "amount_in"]
             }),
         },
         // New tools
         ToolDefinition {
             name: "get_token_info".to_string(),
-            description: "Get detailed token information including price, supply, and liquidity."
+            description: "Get detailed token information including price, supply, liquidity, and an estimated 24h swap volume on known pools."
                 .to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
-                    "token": { "type": "string", "description": "Token symbol (e.g. 'VVS') or address" },
+                    "token": { "type": "string", "description": "Token symbol (e.g. 'VVS') or address; 'CRO' returns native CRO backed by WCRO reads" },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": ["token"]
@@ -122,6 +294,14 @@ fn tool_definitions() -> Vec<ToolDefinition> {
                 "properties": {
                     "pool": { "type": "string", "description": "Pool pair (e.g. 'CRO-USDC') or LP address" },
                     "dex": { "type": "string", "description": "DEX name (default: 'vvs')" },
+                    "amount_in": { "type": "string", "description": "Trade size in token_in base units; returns amount_out and price_impact_bps" },
+                    "token_in": { "type": "string", "description": "Pool token being sold (symbol or address)" },
+                    "amounts_in": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Up to 20 trade sizes in token_in base units; returns a price-impact curve from one reserves read",
+                        "maxItems": 20
+                    },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": ["pool"]
@@ -129,7 +309,7 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_gas_price".to_string(),
-            description: "Get current gas price and estimated costs for common operations."
+            description: "Get current gas price, its percentile among the last 24h of samples, and estimated costs for common operations."
                 .to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
@@ -141,7 +321,7 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_token_price".to_string(),
-            description: "Get USD prices for multiple tokens (max 20).".to_string(),
+            description: "Get USD prices for multiple tokens (max 20), with change_24h_pct when 24h price history is available.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -200,7 +380,7 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "decode_calldata".to_string(),
-            description: "Decode calldata into method signature and parameters.".to_string(),
+            description: "Decode calldata into method signature and parameters. Reports confidence: high for built-in ABIs, medium for a single signature-table match, low for selector collisions or raw 32-byte word guesses.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -212,10 +392,11 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_vvs_farms".to_string(),
-            description: "List VVS farms with estimated TVL and APY.".to_string(),
+            description: "List VVS farms with estimated TVL and APY, plus total MasterChef emissions (VVS per block, daily VVS and USD). Ended farms (allocPoint 0) are hidden unless include_inactive is true.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
+                    "include_inactive": { "type": "boolean", "description": "Also list farms with zero allocPoint (ended emissions)" },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": []
@@ -235,7 +416,7 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_tectonic_markets".to_string(),
-            description: "List Tectonic lending markets overview.".to_string(),
+            description: "List Tectonic lending markets with total supplied, borrowed, cash and reserves (underlying and USD) plus protocol totals.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -246,11 +427,20 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_tectonic_rates".to_string(),
-            description: "Compare Tectonic supply/borrow rates (optionally filter by asset).".to_string(),
+            description: "Compare Tectonic supply/borrow rates (optionally filter by asset). Pass action and amount with asset to project utilization and rates after a hypothetical supply or borrow.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "asset": { "type": "string" },
+                    "action": {
+                        "type": "string",
+                        "enum": ["supply", "borrow"],
+                        "description": "Hypothetical action for the rate projection; requires asset and amount"
+                    },
+                    "amount": {
+                        "type": "string",
+                        "description": "Action amount in underlying base units (decimal string)"
+                    },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": []
@@ -258,12 +448,20 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "construct_revoke_approval".to_string(),
-            description: "Construct calldata to revoke a token approval for a spender.".to_string(),
+            description: "Construct calldata to revoke a token approval for a spender. Set simulate with owner to verify approve(spender, 0) succeeds; fails with a simulation error if it would revert.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "token": { "type": "string" },
                     "spender": { "type": "string" },
+                    "owner": {
+                        "type": "string",
+                        "description": "Wallet holding the approval; required when simulate is true"
+                    },
+                    "simulate": {
+                        "type": "boolean",
+                        "description": "Simulate the revoke and report simulation_verified (default false)"
+                    },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": ["token", "spender"]
@@ -271,11 +469,11 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_lending_rates".to_string(),
-            description: "Get lending rates across supported protocols (currently: Tectonic).".to_string(),
+            description: "Get supply/borrow APYs for every active Compound-style lending protocol (e.g. Tectonic), grouped by protocol.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
-                    "asset": { "type": "string" },
+                    "asset": { "type": "string", "description": "Only include markets whose underlying symbol matches, across all protocols" },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": []
@@ -283,7 +481,7 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_cro_overview".to_string(),
-            description: "Get CRO overview: price, gas, and network status.".to_string(),
+            description: "Get CRO overview: price (CoinGecko cross-checked against on-chain WCRO pool, with price_disagreement_pct), gas, and network status.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -294,12 +492,12 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_liquidation_risk".to_string(),
-            description: "Assess liquidation risk for a wallet's lending positions.".to_string(),
+            description: "Assess liquidation risk for a wallet's lending positions across supported protocols, with an interest-accrual days_to_liquidation estimate.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "address": { "type": "string" },
-                    "protocol": { "type": "string" },
+                    "protocol": { "type": "string", "description": "Lending protocol (e.g. 'tectonic'); omit to aggregate all" },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": ["address"]
@@ -319,13 +517,13 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_best_swap_route".to_string(),
-            description: "Find the best swap route for a given trade.".to_string(),
+            description: "Find the best VVS swap route (direct or via WCRO) for a given trade. The chosen path is cached for 60s per token pair and amount range (meta.cached=true on hits); estimated_out is always quoted for the exact amount_in. Native CRO is accepted as token_in or token_out and routed via WCRO (native_in/native_out in the result); CRO->CRO is rejected.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "token_in": { "type": "string" },
                     "token_out": { "type": "string" },
-                    "amount_in": { "type": "string" },
+                    "amount_in": { "type": "string", "description": "Amount in token_in base units" },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": ["token_in", "token_out", "amount_in"]
@@ -333,7 +531,7 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_protocol_stats".to_string(),
-            description: "Get protocol stats such as TVL for VVS and Tectonic.".to_string(),
+            description: "Get protocol stats for VVS and Tectonic: pool/market counts plus DEX pool TVL (tvl_usd) with a 24h trend (tvl_trend: change_24h_pct and a sparkline built from samples recorded each time TVL is recomputed, at most every 5 minutes). tvl_usd is null for protocols without DEX pools.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -370,7 +568,7 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_contract_info".to_string(),
-            description: "Get contract information including type and code size.".to_string(),
+            description: "Get contract information including type, code size, token standard, and proxy implementation. When an explorer API is configured, also returns the deployer (creator_address) and deployment transaction/block.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -382,13 +580,27 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_whale_activity".to_string(),
-            description: "Monitor large transfer activity for major tokens.".to_string(),
+            description: "Monitor large transfers of a token and summarize exchange net flow: inflows to vs outflows from known CEX/bridge addresses, with an accumulation/distribution signal.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
-                    "token": { "type": "string" },
-                    "min_value_usd": { "type": "number" },
-                    "blocks": { "type": "integer" },
+                    "token": {
+                        "type": "string",
+                        "description": "Token symbol or address (default WCRO)"
+                    },
+                    "min_value_usd": {
+                        "type": "number",
+                        "description": "Minimum transfer value listed in events (default 100000)"
+                    },
+                    "blocks": {
+                        "type": "integer",
+                        "description": "Blocks to scan back from latest (default 1000, capped at 5000)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Max events (default 20, capped at 100)"
+                    },
                     "simple_mode": { "type": "boolean" }
                 },
                 "required": []
@@ -396,7 +608,7 @@ fn tool_definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "get_portfolio_analysis".to_string(),
-            description: "Analyze a wallet portfolio and provide diversification insights.".to_string(),
+            description: "Analyze a wallet portfolio: allocation percentages, concentration and CRO-correlation warnings, and a 0-100 diversification score.".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -420,7 +632,7 @@ mod tests {
             .get("tools")
             .and_then(|v| v.as_array())
             .expect("tools must be an array");
-        assert_eq!(tools.len(), 30);
+        assert_eq!(tools.len(), 31);
         for tool in tools {
             assert!(tool.get("name").and_then(|v| v.as_str()).is_some());
             assert!(tool.get("description").and_then(|v| v.as_str()).is_some());
@@ -428,6 +640,152 @@ mod tests {
         }
     }
 
+    fn tool_entry<'a>(list: &'a Value, name: &str) -> &'a Value {
+        list.get("tools")
+            .and_then(|v| v.as_array())
+            .and_then(|tools| tools.iter().find(|t| t["name"] == name))
+            .expect("tool listed")
+    }
+
+    #[test]
+    fn overrides_replace_description_and_schema() {
+        let overrides = vec![
+            ToolOverride {
+                name: "get_gas_price".to_string(),
+                description: Some("Current Cronos gas price (tuned wording).".to_string()),
+                schema_json: Some(r#"{"type":"object","properties":{}}"#.to_string()),
+            },
+            ToolOverride {
+                name: "get_token_price".to_string(),
+                description: None,
+                schema_json: Some("not json".to_string()),
+            },
+            ToolOverride {
+                name: "no_such_tool".to_string(),
+                description: Some("ignored".to_string()),
+                schema_json: None,
+            },
+        ];
+        let merged = list_with_overrides(&overrides);
+        let baseline = list();
+
+        let gas = tool_entry(&merged, "get_gas_price");
+        assert_eq!(
+            gas["description"],
+            "Current Cronos gas price (tuned wording)."
+        );
+        assert_eq!(
+            gas["inputSchema"],
+            serde_json::json!({ "type": "object", "properties": {} })
+        );
+        // 无效 schema 与空描述保留静态定义
+        assert_eq!(
+            tool_entry(&merged, "get_token_price"),
+            tool_entry(&baseline, "get_token_price")
+        );
+        // 覆盖不能新增工具
+        assert_eq!(
+            merged["tools"].as_array().map(Vec::len),
+            Some(tool_definitions().len())
+        );
+        assert!(!tool_exists("no_such_tool"));
+    }
+
+    #[test]
+    fn no_overrides_falls_back_to_static_definitions() {
+        assert_eq!(list_with_overrides(&[]), list());
+        let row =
+            serde_json::json!({ "name": "get_gas_price", "description": " ", "schema_json": null });
+        let parsed = crate::infra::config::tool_override_from_row(&row).expect("row");
+        assert_eq!(parsed.description, None);
+        assert_eq!(list_with_overrides(&[parsed]), list());
+        assert!(
+            crate::infra::config::tool_override_from_row(&serde_json::json!({ "name": "" }))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn dry_run_reports_credit_cost_for_valid_arguments() {
+        let out = dry_run(
+            "get_token_price",
+            &serde_json::json!({ "tokens": ["CRO", "VVS"] }),
+        )
+        .expect("valid arguments");
+        assert_eq!(out.get("valid").and_then(|v| v.as_bool()), Some(true));
+        assert_eq!(
+            out.get("credit_cost").and_then(|v| v.as_i64()),
+            Some(billing::TOOL_CALL_CREDIT_COST)
+        );
+    }
+
+    #[test]
+    fn dry_run_quotes_bulk_decode_per_hash() {
+        let out = dry_run(
+            "decode_transactions",
+            &serde_json::json!({ "tx_hashes": ["0x01", "0x02", "0x03"] }),
+        )
+        .expect("valid arguments");
+        assert_eq!(out.get("credit_cost").and_then(|v| v.as_i64()), Some(3));
+    }
+
+    #[test]
+    fn dry_run_accepts_missing_arguments_when_nothing_is_required() {
+        dry_run("get_block_info", &Value::Null).expect("no required fields");
+    }
+
+    #[test]
+    fn dry_run_rejects_missing_required_field() {
+        let err = dry_run("get_account_summary", &serde_json::json!({})).unwrap_err();
+        assert!(matches!(err, CroLensError::InvalidParams(_)));
+    }
+
+    #[test]
+    fn dry_run_rejects_wrong_type_and_out_of_range() {
+        let err = dry_run(
+            "get_account_summary",
+            &serde_json::json!({ "address": 123 }),
+        )
+        .unwrap_err();
+        assert!(matches!(err, CroLensError::InvalidParams(_)));
+
+        let err = dry_run(
+            "construct_swap_tx",
+            &serde_json::json!({
+                "from": "0x1",
+                "token_in": "CRO",
+                "token_out": "USDC",
+                "amount_in": "1",
+                "slippage_bps": 9000
+            }),
+        )
+        .unwrap_err();
+        assert!(matches!(err, CroLensError::InvalidParams(_)));
+
+        for tool in ["search_contract", "get_whale_activity"] {
+            let args = serde_json::json!({ "query": "vvs", "limit": 0 });
+            assert!(
+                matches!(dry_run(tool, &args), Err(CroLensError::InvalidParams(_))),
+                "{tool} limit 0"
+            );
+        }
+    }
+
+    #[test]
+    fn dry_run_accepts_limits_the_handler_clamps() {
+        // 超过上限的 limit 由 handler 截断而不是拒绝，预检结果需与真实调用一致
+        for (tool, limit) in [("search_contract", 51), ("get_whale_activity", 101)] {
+            let args = serde_json::json!({ "query": "vvs", "limit": limit });
+            assert!(dry_run(tool, &args).is_ok(), "{tool} limit {limit}");
+        }
+    }
+
+    #[test]
+    fn dry_run_rejects_unknown_tool() {
+        let err = dry_run("does_not_exist", &serde_json::json!({})).unwrap_err();
+        assert!(matches!(err, CroLensError::MethodNotFound(_)));
+    }
+
     #[test]
     fn tools_list_includes_core_tools() {
         let value = list();
@@ -445,6 +803,7 @@ mod tests {
             "get_account_summary",
             "get_defi_positions",
             "decode_transaction",
+            "decode_transactions",
             "simulate_transaction",
             "search_contract",
             "construct_swap_tx",
@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
-use alloy_primitives::{Address, U256};
+use alloy_primitives::ruint::UintTryFrom;
+use alloy_primitives::{Address, U256, U512};
 use uuid::Uuid;
 use worker::Request;
 
@@ -31,9 +32,39 @@ pub fn normalize_symbol(symbol: &str) -> String {
     symbol.trim().to_lowercase()
 }
 
+/// 错误信息中回显的输入最大字符数
+const ERROR_INPUT_MAX_CHARS: usize = 64;
+
+/// 截断用户输入用于错误信息，避免超长输入撑大响应
+pub fn truncate_for_error(input: &str, max_chars: usize) -> String {
+    let mut chars = input.chars();
+    let head: String = chars.by_ref().take(max_chars).collect();
+    if chars.next().is_some() {
+        format!("{head}...")
+    } else {
+        head
+    }
+}
+
 pub fn parse_address(address: &str) -> Result<Address> {
     let trimmed = address.trim();
-    Address::from_str(trimmed).map_err(|_| CroLensError::InvalidAddress(trimmed.to_string()))
+    Address::from_str(trimmed).map_err(|_| {
+        CroLensError::InvalidAddress(format!(
+            "{:?}",
+            truncate_for_error(trimmed, ERROR_INPUT_MAX_CHARS)
+        ))
+    })
+}
+
+/// 同 `parse_address`，错误信息带上参数名，便于定位多地址工具中的具体字段
+pub fn parse_address_field(field: &str, address: &str) -> Result<Address> {
+    let trimmed = address.trim();
+    Address::from_str(trimmed).map_err(|_| {
+        CroLensError::InvalidAddress(format!(
+            "`{field}` = {:?}",
+            truncate_for_error(trimmed, ERROR_INPUT_MAX_CHARS)
+        ))
+    })
 }
 
 pub fn parse_u256_dec(value: &str) -> Result<U256> {
@@ -97,6 +128,71 @@ pub fn bytes_to_hex0x<B: AsRef<[u8]>>(bytes: B) -> String {
     format!("0x{}", hex::encode(bytes.as_ref()))
 }
 
+/// `Transfer(address,address,uint256)` 事件的 topic0 (ERC-20 与 ERC-721 共用)
+pub const TRANSFER_TOPIC: &str =
+    "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+/// 地址左补零到 32 字节，作为 indexed address 的日志 topic (小写 `0x` + 64 位十六进制)
+pub fn address_to_topic(address: Address) -> String {
+    format!("0x{:0>64}", hex::encode(address))
+}
+
+/// `address_to_topic` 的逆操作；不是 32 字节的 topic 返回 None
+pub fn topic_to_address(topic: &str) -> Option<Address> {
+    let hex = topic.trim().trim_start_matches("0x");
+    if hex.len() != 64 {
+        return None;
+    }
+    parse_address(&format!("0x{}", &hex[24..])).ok()
+}
+
+/// 按键排序后的紧凑 JSON；键顺序不同但语义相同的值输出一致
+pub fn canonical_json(value: &serde_json::Value) -> String {
+    let mut out = String::new();
+    write_canonical_json(value, &mut out);
+    out
+}
+
+fn write_canonical_json(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::String(key.clone()).to_string());
+                out.push(':');
+                if let Some(v) = map.get(key) {
+                    write_canonical_json(v, out);
+                }
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_json(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// `canonical_json` 的 keccak256 (64 位小写十六进制，不带 `0x`)，用作缓存 key；
+/// 与 `DefaultHasher` 不同，跨 Rust 版本和部署保持稳定
+pub fn canonical_json_hash(value: &serde_json::Value) -> String {
+    hex::encode(alloy_primitives::keccak256(
+        canonical_json(value).as_bytes(),
+    ))
+}
+
 pub fn hex0x_to_bytes(value: &str) -> Result<Vec<u8>> {
     let trimmed = value.trim().trim_start_matches("0x");
     if trimmed.is_empty() {
@@ -105,6 +201,17 @@ pub fn hex0x_to_bytes(value: &str) -> Result<Vec<u8>> {
     hex::decode(trimmed).map_err(|err| CroLensError::invalid_params(format!("Invalid hex: {err}")))
 }
 
+/// `a * b / denom` (向下取整)，乘积使用 512 位中间值，不会因 U256 乘法饱和而失真；
+/// `denom` 为 0 或结果超出 U256 时返回 None
+pub fn mul_div(a: U256, b: U256, denom: U256) -> Option<U256> {
+    if denom.is_zero() {
+        return None;
+    }
+    let product: U512 = a.widening_mul(b);
+    let quotient = product / U512::from(denom);
+    U256::uint_try_from(quotient).ok()
+}
+
 pub fn format_units(value: &U256, decimals: u8) -> String {
     if decimals == 0 {
         return value.to_string();
@@ -132,6 +239,146 @@ pub fn format_units(value: &U256, decimals: u8) -> String {
     trim_trailing_zeros(&formatted)
 }
 
+/// USD 价格输出使用的最大小数位数，足以表示 1e-9 级别的派生价格
+pub const PRICE_MAX_DP: usize = 12;
+
+/// 响应 meta 的结构版本 (meta.schema_version)，工具输出结构有破坏性变更时递增
+pub const RESPONSE_SCHEMA_VERSION: u32 = 1;
+
+/// 以普通小数格式输出 f64 (不使用科学计数法)，最多 `max_dp` 位小数并去掉末尾的 0
+pub fn format_decimal(value: f64, max_dp: usize) -> String {
+    if !value.is_finite() {
+        return "0".to_string();
+    }
+    let formatted = trim_trailing_zeros(&format!("{value:.max_dp$}"));
+    // 舍入后的负零 (如 -0.0000000001) 统一输出为 0
+    if formatted == "-0" {
+        return "0".to_string();
+    }
+    formatted
+}
+
+/// 输出精度 (小数位数) 配置；运营方可通过环境变量调整，无需改代码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precision {
+    /// USD 金额 (value_usd、tvl_usd 等)
+    pub usd_dp: usize,
+    /// 单价 (price_usd)，去掉末尾的 0
+    pub price_dp: usize,
+    /// 百分比 (percent、gas_used_percent 等)
+    pub pct_dp: usize,
+}
+
+const PRECISION_MAX_DP: usize = 18;
+
+impl Default for Precision {
+    fn default() -> Self {
+        Self {
+            usd_dp: 2,
+            price_dp: PRICE_MAX_DP,
+            pct_dp: 2,
+        }
+    }
+}
+
+impl Precision {
+    /// 读取 PRECISION_USD_DP / PRECISION_PRICE_DP / PRECISION_PCT_DP
+    pub fn from_env(env: &worker::Env) -> Self {
+        let var = |name: &str| env.var(name).ok().map(|v| v.to_string());
+        Self::parse(
+            var("PRECISION_USD_DP").as_deref(),
+            var("PRECISION_PRICE_DP").as_deref(),
+            var("PRECISION_PCT_DP").as_deref(),
+        )
+    }
+
+    /// 无效或超过 18 位的值回退到默认值
+    pub fn parse(usd_dp: Option<&str>, price_dp: Option<&str>, pct_dp: Option<&str>) -> Self {
+        let defaults = Self::default();
+        let parse_dp = |value: Option<&str>, default: usize| {
+            value
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .filter(|dp| *dp <= PRECISION_MAX_DP)
+                .unwrap_or(default)
+        };
+        Self {
+            usd_dp: parse_dp(usd_dp, defaults.usd_dp),
+            price_dp: parse_dp(price_dp, defaults.price_dp),
+            pct_dp: parse_dp(pct_dp, defaults.pct_dp),
+        }
+    }
+
+    pub fn usd(&self, value: f64) -> String {
+        let dp = self.usd_dp;
+        format!("{value:.dp$}")
+    }
+
+    pub fn price(&self, value: f64) -> String {
+        format_decimal(value, self.price_dp)
+    }
+
+    pub fn pct(&self, value: f64) -> String {
+        let dp = self.pct_dp;
+        format!("{value:.dp$}")
+    }
+}
+
+/// 授权额度达到该阈值即视为"实际无限"授权：除 U256::MAX 外，1e30 这类授权同样危险
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApprovalThreshold {
+    /// 固定阈值 (代币基础单位)
+    pub fixed: U256,
+    /// 相对代币总供应量的倍数；0 表示不按总供应量判断
+    pub supply_multiple: u64,
+}
+
+impl Default for ApprovalThreshold {
+    fn default() -> Self {
+        Self {
+            fixed: U256::from(10u64).pow(U256::from(30u64)),
+            supply_multiple: 1,
+        }
+    }
+}
+
+impl ApprovalThreshold {
+    /// 读取 APPROVAL_UNLIMITED_THRESHOLD / APPROVAL_UNLIMITED_SUPPLY_MULTIPLE
+    pub fn from_env(env: &worker::Env) -> Self {
+        let var = |name: &str| env.var(name).ok().map(|v| v.to_string());
+        Self::parse(
+            var("APPROVAL_UNLIMITED_THRESHOLD").as_deref(),
+            var("APPROVAL_UNLIMITED_SUPPLY_MULTIPLE").as_deref(),
+        )
+    }
+
+    /// 无效值回退到默认值；固定阈值必须大于 0
+    pub fn parse(fixed: Option<&str>, supply_multiple: Option<&str>) -> Self {
+        let defaults = Self::default();
+        Self {
+            fixed: fixed
+                .and_then(|v| parse_u256_dec(v).ok())
+                .filter(|v| !v.is_zero())
+                .unwrap_or(defaults.fixed),
+            supply_multiple: supply_multiple
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(defaults.supply_multiple),
+        }
+    }
+
+    /// `total_supply` 未知时只按 U256::MAX 和固定阈值判断
+    pub fn is_unlimited(&self, allowance: U256, total_supply: Option<U256>) -> bool {
+        if allowance == U256::MAX || allowance >= self.fixed {
+            return true;
+        }
+        match total_supply {
+            Some(supply) if self.supply_multiple > 0 && !supply.is_zero() => {
+                allowance >= supply.saturating_mul(U256::from(self.supply_multiple))
+            }
+            _ => false,
+        }
+    }
+}
+
 fn trim_trailing_zeros(value: &str) -> String {
     if let Some((int_part, frac_part)) = value.split_once('.') {
         let trimmed_frac = frac_part.trim_end_matches('0');
@@ -143,6 +390,16 @@ fn trim_trailing_zeros(value: &str) -> String {
     value.to_string()
 }
 
+/// 列表类工具的条数上限：缺省取 `default`，小于 1 取 1，超过 `max` 截断为 `max`
+/// 返回 (实际条数, 是否因超过上限被截断)
+pub fn clamp_limit(requested: Option<u64>, default: u64, max: u64) -> (u64, bool) {
+    match requested {
+        None => (default.clamp(1, max), false),
+        Some(n) if n > max => (max, true),
+        Some(n) => (n.max(1), false),
+    }
+}
+
 #[allow(dead_code)]
 pub mod u256_as_string {
     use alloy_primitives::U256;
@@ -168,6 +425,209 @@ pub mod u256_as_string {
 mod tests {
     use super::*;
 
+    #[test]
+    fn mul_div_matches_naive_path_without_overflow() {
+        let reserve = U256::from(1_234_567_890_123_456_789u128);
+        let user_lp = U256::from(98_765_432_109u64);
+        let total_supply = U256::from(1_000_000_000_000u64);
+        assert_eq!(
+            mul_div(reserve, user_lp, total_supply),
+            Some(reserve.saturating_mul(user_lp) / total_supply)
+        );
+        assert_eq!(
+            mul_div(U256::from(7u64), U256::from(3u64), U256::from(2u64)),
+            Some(U256::from(10u64))
+        );
+    }
+
+    #[test]
+    fn mul_div_avoids_saturation_for_large_reserves() {
+        // reserve * user_lp = 2^300 超出 U256，朴素写法饱和后结果严重偏小
+        let reserve = U256::from(1u64) << 200usize;
+        let user_lp = U256::from(1u64) << 100usize;
+        let total_supply = U256::from(1u64) << 150usize;
+        let naive = reserve.saturating_mul(user_lp) / total_supply;
+        let exact = mul_div(reserve, user_lp, total_supply).expect("fits in U256");
+        assert_eq!(exact, U256::from(1u64) << 150usize);
+        assert!(naive < exact);
+
+        // 用户持有全部 LP 时应拿回全部储备
+        let reserve = U256::MAX >> 1;
+        assert_eq!(mul_div(reserve, total_supply, total_supply), Some(reserve));
+    }
+
+    #[test]
+    fn mul_div_rejects_zero_denominator_and_overflow() {
+        assert_eq!(
+            mul_div(U256::from(1u64), U256::from(1u64), U256::ZERO),
+            None
+        );
+        assert_eq!(mul_div(U256::MAX, U256::from(2u64), U256::from(1u64)), None);
+        assert_eq!(
+            mul_div(U256::ZERO, U256::MAX, U256::from(3u64)),
+            Some(U256::ZERO)
+        );
+    }
+
+    #[test]
+    fn precision_defaults_match_existing_formats() {
+        let precision = Precision::default();
+        assert_eq!(precision.usd(1234.5678), "1234.57");
+        assert_eq!(precision.price(1e-9), "0.000000001");
+        assert_eq!(precision.pct(12.345), "12.35");
+        assert_eq!(Precision::parse(None, None, None), precision);
+    }
+
+    #[test]
+    fn precision_settings_flow_through_formatters() {
+        let precision = Precision::parse(Some("4"), Some("6"), Some("1"));
+        assert_eq!(precision.usd(0.123456), "0.1235");
+        assert_eq!(precision.price(0.0000123456), "0.000012");
+        assert_eq!(precision.price(1.5), "1.5");
+        assert_eq!(precision.pct(33.333), "33.3");
+        assert_eq!(Precision::parse(Some("0"), None, None).usd(99.6), "100");
+    }
+
+    #[test]
+    fn precision_rejects_invalid_values() {
+        let precision = Precision::parse(Some("abc"), Some("19"), Some("-1"));
+        assert_eq!(precision, Precision::default());
+    }
+
+    #[test]
+    fn canonical_json_hash_ignores_key_order() {
+        let a: serde_json::Value = serde_json::from_str(
+            r#"{"to":"0xabc","data":"0x01","nested":{"b":1,"a":[{"y":2,"x":1}]}}"#,
+        )
+        .expect("valid json");
+        let b: serde_json::Value = serde_json::from_str(
+            r#"{ "nested": { "a": [ { "x": 1, "y": 2 } ], "b": 1 },
+                 "data": "0x01", "to": "0xabc" }"#,
+        )
+        .expect("valid json");
+        assert_eq!(canonical_json(&a), canonical_json(&b));
+        assert_eq!(canonical_json_hash(&a), canonical_json_hash(&b));
+        assert_eq!(canonical_json_hash(&a).len(), 64);
+    }
+
+    #[test]
+    fn canonical_json_hash_distinguishes_different_values() {
+        let base = serde_json::json!({ "address": "0xabc", "limit": 10 });
+        let other_value = serde_json::json!({ "address": "0xabc", "limit": 11 });
+        let other_key = serde_json::json!({ "address": "0xabc", "limits": 10 });
+        // 数组顺序有意义，不做排序
+        let arr_a = serde_json::json!(["0xabc", "latest"]);
+        let arr_b = serde_json::json!(["latest", "0xabc"]);
+        assert_ne!(
+            canonical_json_hash(&base),
+            canonical_json_hash(&other_value)
+        );
+        assert_ne!(canonical_json_hash(&base), canonical_json_hash(&other_key));
+        assert_ne!(canonical_json_hash(&arr_a), canonical_json_hash(&arr_b));
+        assert_eq!(canonical_json(&base), r#"{"address":"0xabc","limit":10}"#);
+    }
+
+    #[test]
+    fn approval_threshold_flags_huge_below_max_allowance() {
+        let threshold = ApprovalThreshold::default();
+        let e30 = U256::from(10u64).pow(U256::from(30u64));
+        assert!(threshold.is_unlimited(U256::MAX, None));
+        assert!(threshold.is_unlimited(e30, None));
+        assert!(!threshold.is_unlimited(e30 - U256::from(1u64), None));
+        assert!(!threshold.is_unlimited(U256::from(1_000u64), None));
+    }
+
+    #[test]
+    fn approval_threshold_uses_total_supply_multiple() {
+        let supply = U256::from(1_000_000u64);
+        let threshold = ApprovalThreshold::default();
+        assert!(threshold.is_unlimited(supply, Some(supply)));
+        assert!(!threshold.is_unlimited(supply - U256::from(1u64), Some(supply)));
+
+        let doubled = ApprovalThreshold::parse(None, Some("2"));
+        assert!(!doubled.is_unlimited(supply, Some(supply)));
+        assert!(doubled.is_unlimited(supply * U256::from(2u64), Some(supply)));
+
+        let disabled = ApprovalThreshold::parse(None, Some("0"));
+        assert!(!disabled.is_unlimited(supply * U256::from(5u64), Some(supply)));
+        // 总供应量为 0 (读取异常) 时不按倍数判断
+        assert!(!threshold.is_unlimited(U256::from(1u64), Some(U256::ZERO)));
+    }
+
+    #[test]
+    fn approval_threshold_parse_overrides_and_fallbacks() {
+        let custom = ApprovalThreshold::parse(Some("1000000000000000000000000"), Some("3"));
+        assert_eq!(custom.fixed, U256::from(10u64).pow(U256::from(24u64)));
+        assert_eq!(custom.supply_multiple, 3);
+        assert_eq!(
+            ApprovalThreshold::parse(Some("0"), Some("-1")),
+            ApprovalThreshold::default()
+        );
+        assert_eq!(
+            ApprovalThreshold::parse(Some("1e30"), None),
+            ApprovalThreshold::default()
+        );
+    }
+
+    #[test]
+    fn address_topic_is_left_padded() {
+        let address = parse_address("0x5C7F8A570d578ED84E63fdFA7b1eE72dEae1AE23").expect("valid");
+        assert_eq!(
+            address_to_topic(address),
+            "0x0000000000000000000000005c7f8a570d578ed84e63fdfa7b1ee72deae1ae23"
+        );
+        assert_eq!(
+            address_to_topic(Address::ZERO),
+            format!("0x{}", "0".repeat(64))
+        );
+        assert_eq!(topic_to_address(&address_to_topic(address)), Some(address));
+        assert_eq!(topic_to_address("0x1234"), None);
+    }
+
+    #[test]
+    fn format_decimal_tiny_values_avoid_exponent() {
+        assert_eq!(format_decimal(1e-9, PRICE_MAX_DP), "0.000000001");
+        assert_eq!(format_decimal(1.5e-7, 8), "0.00000015");
+        assert_eq!(format_decimal(1e-9, 6), "0");
+    }
+
+    #[test]
+    fn format_decimal_large_values_avoid_exponent() {
+        assert_eq!(format_decimal(1e21, 2), "1000000000000000000000");
+        assert_eq!(format_decimal(1234567.891, 2), "1234567.89");
+    }
+
+    #[test]
+    fn format_decimal_zero_and_non_finite() {
+        assert_eq!(format_decimal(0.0, 8), "0");
+        assert_eq!(format_decimal(-1e-12, 8), "0");
+        assert_eq!(format_decimal(f64::NAN, 8), "0");
+        assert_eq!(format_decimal(f64::INFINITY, 8), "0");
+    }
+
+    #[test]
+    fn format_decimal_trims_trailing_zeros() {
+        assert_eq!(format_decimal(1.0, 8), "1");
+        assert_eq!(format_decimal(0.25, 8), "0.25");
+        assert_eq!(format_decimal(-2.5, 4), "-2.5");
+    }
+
+    #[test]
+    fn clamp_limit_caps_above_max() {
+        assert_eq!(clamp_limit(Some(500), 20, 50), (50, true));
+        assert_eq!(clamp_limit(Some(50), 20, 50), (50, false));
+    }
+
+    #[test]
+    fn clamp_limit_raises_below_min() {
+        assert_eq!(clamp_limit(Some(0), 20, 50), (1, false));
+    }
+
+    #[test]
+    fn clamp_limit_uses_default_when_absent() {
+        assert_eq!(clamp_limit(None, 20, 50), (20, false));
+    }
+
     #[test]
     fn formats_units_with_decimals() {
         let value = U256::from(1234500u64);
@@ -216,6 +676,35 @@ mod tests {
         assert!(err.to_string().to_lowercase().contains("invalid address"));
     }
 
+    #[test]
+    fn invalid_address_error_echoes_input() {
+        let err = parse_address("  0x1234 ").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid address: \"0x1234\"");
+    }
+
+    #[test]
+    fn invalid_address_error_names_field() {
+        let err = parse_address_field("spender", "0xnot-an-address").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("`spender`"));
+        assert!(message.contains("0xnot-an-address"));
+        assert!(matches!(err, CroLensError::InvalidAddress(_)));
+    }
+
+    #[test]
+    fn invalid_address_error_truncates_long_input() {
+        let long = "x".repeat(500);
+        let message = parse_address_field("to", &long).unwrap_err().to_string();
+        assert!(message.contains(&format!("{}...", "x".repeat(ERROR_INPUT_MAX_CHARS))));
+        assert!(!message.contains(&"x".repeat(ERROR_INPUT_MAX_CHARS + 1)));
+    }
+
+    #[test]
+    fn truncate_for_error_is_char_safe() {
+        assert_eq!(truncate_for_error("abc", 5), "abc");
+        assert_eq!(truncate_for_error("地址地址", 2), "地址...");
+    }
+
     #[test]
     fn parses_u256_decimal() {
         let v = parse_u256_dec("42").unwrap();
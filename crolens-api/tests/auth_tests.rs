@@ -1,7 +1,9 @@
 mod support;
 
 use crolens_api::error::CroLensError;
-use crolens_api::gateway::auth::{ensure_api_key_with_store, ApiKeyRecord};
+use crolens_api::gateway::auth::{
+    ensure_api_key_with_store, validate_api_key_format, ApiKeyPolicy, ApiKeyRecord,
+};
 
 use support::MemoryApiKeyStore;
 
@@ -10,7 +12,7 @@ async fn test_valid_api_key() {
     let store = MemoryApiKeyStore::new(50);
     let api_key = "cl_sk_test_valid_001";
 
-    let record = ensure_api_key_with_store(&store, api_key, None)
+    let record = ensure_api_key_with_store(&store, &ApiKeyPolicy::default(), api_key, None)
         .await
         .expect("api key should be accepted");
 
@@ -24,7 +26,7 @@ async fn test_valid_api_key() {
 async fn test_invalid_api_key() {
     let store = MemoryApiKeyStore::new(50);
 
-    let err = ensure_api_key_with_store(&store, "not_a_key", None)
+    let err = ensure_api_key_with_store(&store, &ApiKeyPolicy::default(), "not_a_key", None)
         .await
         .expect_err("expected unauthorized");
     assert!(matches!(err, CroLensError::Unauthorized(_)));
@@ -44,8 +46,75 @@ async fn test_inactive_api_key() {
         })
         .await;
 
-    let err = ensure_api_key_with_store(&store, api_key, None)
+    let err = ensure_api_key_with_store(&store, &ApiKeyPolicy::default(), api_key, None)
         .await
         .expect_err("expected unauthorized");
     assert!(matches!(err, CroLensError::Unauthorized(_)));
 }
+
+#[test]
+fn test_well_formed_api_key_passes_format_check() {
+    let policy = ApiKeyPolicy::default();
+    validate_api_key_format("cl_sk_0123456789abcdef0123456789abcdef", &policy)
+        .expect("console-generated key should pass");
+    validate_api_key_format("  CL_SK_test_valid_001  ", &policy)
+        .expect("prefix is case-insensitive and whitespace is trimmed");
+}
+
+#[test]
+fn test_too_short_api_key_is_rejected() {
+    let policy = ApiKeyPolicy::default();
+    let err = validate_api_key_format("cl_sk_abc", &policy).expect_err("too short");
+    assert!(matches!(err, CroLensError::Unauthorized(msg) if msg.contains("too short")));
+
+    let err = validate_api_key_format("cl_sk_", &policy).expect_err("empty suffix");
+    assert!(matches!(err, CroLensError::Unauthorized(_)));
+}
+
+#[test]
+fn test_wrong_prefix_api_key_is_rejected() {
+    let policy = ApiKeyPolicy::default();
+    let err =
+        validate_api_key_format("sk_live_0123456789abcdef", &policy).expect_err("wrong prefix");
+    assert!(matches!(err, CroLensError::Unauthorized(msg) if msg.contains("cl_sk_")));
+}
+
+#[test]
+fn test_invalid_charset_and_oversized_keys_are_rejected() {
+    let policy = ApiKeyPolicy::default();
+    let err = validate_api_key_format("cl_sk_test valid 001", &policy).expect_err("charset");
+    assert!(matches!(err, CroLensError::Unauthorized(_)));
+
+    let long_key = format!("cl_sk_{}", "a".repeat(200));
+    let err = validate_api_key_format(&long_key, &policy).expect_err("too long");
+    assert!(matches!(err, CroLensError::Unauthorized(msg) if msg.contains("too long")));
+}
+
+#[test]
+fn test_api_key_policy_parse_overrides_and_fallbacks() {
+    let policy = ApiKeyPolicy::parse(Some("CLK_"), Some("24"));
+    assert_eq!(policy.prefix, "clk_");
+    assert_eq!(policy.min_len, 24);
+    validate_api_key_format("clk_0123456789abcdefghij", &policy).expect("matches custom policy");
+    let err = validate_api_key_format("cl_sk_0123456789abcdefghij", &policy)
+        .expect_err("default prefix no longer accepted");
+    assert!(matches!(err, CroLensError::Unauthorized(_)));
+
+    assert_eq!(ApiKeyPolicy::parse(None, None), ApiKeyPolicy::default());
+    assert_eq!(
+        ApiKeyPolicy::parse(Some("bad prefix!"), Some("abc")),
+        ApiKeyPolicy::default()
+    );
+    // 最小长度不能短于前缀本身
+    assert_eq!(ApiKeyPolicy::parse(None, Some("3")).min_len, 16);
+}
+
+#[tokio::test]
+async fn test_malformed_api_key_never_reaches_store() {
+    let store = MemoryApiKeyStore::new(50);
+    let err = ensure_api_key_with_store(&store, &ApiKeyPolicy::default(), "cl_sk_abc", None)
+        .await
+        .expect_err("expected unauthorized");
+    assert!(matches!(err, CroLensError::Unauthorized(_)));
+    assert!(store.get_api_key("cl_sk_abc").await.is_none());
+}
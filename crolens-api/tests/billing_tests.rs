@@ -4,10 +4,16 @@ use std::sync::Arc;
 
 use crolens_api::error::CroLensError;
 use crolens_api::gateway::auth::ApiKeyRecord;
-use crolens_api::gateway::billing::deduct_credit_with_store;
+use crolens_api::gateway::billing::{
+    deduct_credit_with_store, deduct_extra_credits_with_store, max_tool_credit_cost,
+    reconcile_credits_charged, refund_credits_with_store, result_credit_cost, tool_credit_cost,
+};
+use crolens_api::gateway::ratelimit::check_combined_rate_limit;
+use crolens_api::gateway::ratelimit::KvTextStore;
+use crolens_api::mcp::router::{admit_tool_call, Admission};
 use futures_util::future::join_all;
 
-use support::MemoryApiKeyStore;
+use support::{MemoryApiKeyStore, MemoryRateLimitStore};
 
 #[tokio::test]
 async fn test_deduct_credit_success() {
@@ -88,3 +94,175 @@ async fn test_atomic_deduction() {
         .expect("api key must exist");
     assert_eq!(final_record.credits, 0);
 }
+
+#[tokio::test]
+async fn test_validate_dry_run_does_not_deduct_credits() {
+    let store = MemoryApiKeyStore::new(50);
+    let limiter = MemoryRateLimitStore::new();
+    let api_key = "cl_sk_test_billing_dry_run_001";
+    let record = ApiKeyRecord {
+        api_key: api_key.to_string(),
+        tier: "pro".to_string(),
+        credits: 3,
+        is_active: true,
+    };
+    store.set_api_key(record.clone()).await;
+
+    let admit = |arguments: serde_json::Value, validate: bool| {
+        let store = &store;
+        let limiter = &limiter;
+        let record = record.clone();
+        async move {
+            let rate_limit = async {
+                let allowed =
+                    check_combined_rate_limit(limiter, &[("rl:tool:dry_run", 1)], 60).await?;
+                if allowed {
+                    Ok(())
+                } else {
+                    Err(CroLensError::rate_limit_exceeded(Some(60)))
+                }
+            };
+            admit_tool_call(
+                store,
+                &record,
+                "get_token_price",
+                &arguments,
+                validate,
+                rate_limit,
+            )
+            .await
+        }
+    };
+
+    // 空跑在限流与扣费之前返回：多次调用既不计数也不扣费
+    for _ in 0..3 {
+        let admission = admit(serde_json::json!({ "tokens": ["CRO"] }), true)
+            .await
+            .expect("valid arguments");
+        let Admission::DryRun(valid) = admission else {
+            panic!("expected dry-run result");
+        };
+        assert_eq!(valid.get("valid").and_then(|v| v.as_bool()), Some(true));
+        assert_eq!(valid.get("credit_cost").and_then(|v| v.as_i64()), Some(1));
+    }
+    let invalid = admit(serde_json::json!({ "tokens": "CRO" }), true)
+        .await
+        .expect_err("tokens must be an array");
+    assert!(matches!(invalid, CroLensError::InvalidParams(_)));
+    assert_eq!(
+        limiter.get_text("rl:tool:dry_run").await.expect("memory"),
+        None
+    );
+    assert_eq!(store.get_api_key(api_key).await.map(|r| r.credits), Some(3));
+
+    // 真实调用经过限流并扣费
+    let admission = admit(serde_json::json!({ "tokens": ["CRO"] }), false)
+        .await
+        .expect("admitted");
+    assert_eq!(admission, Admission::Charged);
+    assert_eq!(store.get_api_key(api_key).await.map(|r| r.credits), Some(2));
+    let limited = admit(serde_json::json!({ "tokens": ["CRO"] }), false)
+        .await
+        .expect_err("rate limited");
+    assert!(matches!(limited, CroLensError::RateLimitExceeded { .. }));
+    assert_eq!(store.get_api_key(api_key).await.map(|r| r.credits), Some(2));
+}
+
+#[test]
+fn test_bulk_decode_is_charged_per_decoded_hash() {
+    let args = serde_json::json!({ "tx_hashes": ["0x01", "0x02", "0x03"] });
+    assert_eq!(max_tool_credit_cost("decode_transactions", &args), 3);
+    assert_eq!(max_tool_credit_cost("get_token_price", &args), 1);
+
+    let partial = serde_json::json!({ "decoded": 2, "failed": 1, "credits_charged": 2 });
+    assert_eq!(
+        result_credit_cost("decode_transactions", &args, &partial),
+        2
+    );
+
+    // 全部失败也按一次调用计费；结果中的数值不会超过预检的上限
+    let none = serde_json::json!({ "decoded": 0, "failed": 3, "credits_charged": 1 });
+    assert_eq!(result_credit_cost("decode_transactions", &args, &none), 1);
+    let bogus = serde_json::json!({ "credits_charged": 99 });
+    assert_eq!(result_credit_cost("decode_transactions", &args, &bogus), 3);
+}
+
+#[tokio::test]
+async fn test_deduct_extra_credits_is_all_or_nothing() {
+    let store = MemoryApiKeyStore::new(50);
+    let api_key = "cl_sk_test_billing_extra_001";
+
+    store
+        .set_api_key(ApiKeyRecord {
+            api_key: api_key.to_string(),
+            tier: "pro".to_string(),
+            credits: 2,
+            is_active: true,
+        })
+        .await;
+
+    // 余额不足时一个都不扣，不会留下部分扣费
+    let charged = deduct_extra_credits_with_store(&store, api_key, 5)
+        .await
+        .expect("deduction should succeed");
+    assert_eq!(charged, 0);
+    let record = store.get_api_key(api_key).await.expect("record exists");
+    assert_eq!(record.credits, 2);
+
+    let charged = deduct_extra_credits_with_store(&store, api_key, 2)
+        .await
+        .expect("deduction should succeed");
+    assert_eq!(charged, 2);
+    let record = store.get_api_key(api_key).await.expect("record exists");
+    assert_eq!(record.credits, 0);
+}
+
+#[test]
+fn test_reconcile_credits_charged_reports_actual_charge() {
+    let mut result = serde_json::json!({ "decoded": 3, "credits_charged": 3 });
+    reconcile_credits_charged("decode_transactions", &mut result, 0);
+    assert_eq!(result["credits_charged"], 1);
+
+    // 非批量工具的结果不受影响
+    let mut other = serde_json::json!({ "credits_charged": 3 });
+    reconcile_credits_charged("get_block_info", &mut other, 0);
+    assert_eq!(other["credits_charged"], 3);
+}
+
+#[tokio::test]
+async fn test_abandoned_call_refund_restores_credit() {
+    let store = MemoryApiKeyStore::new(50);
+    let api_key = "cl_sk_test_billing_refund_001";
+
+    store
+        .set_api_key(ApiKeyRecord {
+            api_key: api_key.to_string(),
+            tier: "pro".to_string(),
+            credits: 2,
+            is_active: true,
+        })
+        .await;
+
+    deduct_credit_with_store(&store, api_key)
+        .await
+        .expect("deduction should succeed");
+    let refunded = refund_credits_with_store(&store, api_key, tool_credit_cost("get_token_price"))
+        .await
+        .expect("refund should succeed");
+    assert_eq!(refunded, Some(2));
+
+    // 非正数不退还；未知 key 没有余额
+    assert_eq!(
+        refund_credits_with_store(&store, api_key, 0)
+            .await
+            .expect("noop"),
+        None
+    );
+    assert_eq!(
+        refund_credits_with_store(&store, "cl_sk_missing", 1)
+            .await
+            .expect("missing"),
+        None
+    );
+    assert_eq!(store.get_api_key(api_key).await.map(|r| r.credits), Some(2));
+}
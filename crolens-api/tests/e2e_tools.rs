@@ -24,21 +24,33 @@ fn make_tool_call(tool_name: &str, arguments: serde_json::Value) -> serde_json::
 
 #[test]
 fn e2e_get_approval_status_request_parses() {
-    let req = make_tool_call("get_approval_status", json!({
-        "address": "0x1234567890123456789012345678901234567890"
-    }));
-
-    assert_eq!(req.get("method").and_then(|v| v.as_str()), Some("tools/call"));
+    let req = make_tool_call(
+        "get_approval_status",
+        json!({
+            "address": "0x1234567890123456789012345678901234567890"
+        }),
+    );
+
+    assert_eq!(
+        req.get("method").and_then(|v| v.as_str()),
+        Some("tools/call")
+    );
     let params = req.get("params").unwrap();
-    assert_eq!(params.get("name").and_then(|v| v.as_str()), Some("get_approval_status"));
+    assert_eq!(
+        params.get("name").and_then(|v| v.as_str()),
+        Some("get_approval_status")
+    );
 }
 
 #[test]
 fn e2e_get_approval_status_with_token_filter() {
-    let req = make_tool_call("get_approval_status", json!({
-        "address": "0x1234567890123456789012345678901234567890",
-        "token": "USDC"
-    }));
+    let req = make_tool_call(
+        "get_approval_status",
+        json!({
+            "address": "0x1234567890123456789012345678901234567890",
+            "token": "USDC"
+        }),
+    );
 
     let params = req.get("params").unwrap();
     let args = params.get("arguments").unwrap();
@@ -47,14 +59,20 @@ fn e2e_get_approval_status_with_token_filter() {
 
 #[test]
 fn e2e_get_approval_status_simple_mode() {
-    let req = make_tool_call("get_approval_status", json!({
-        "address": "0x1234567890123456789012345678901234567890",
-        "simple_mode": true
-    }));
+    let req = make_tool_call(
+        "get_approval_status",
+        json!({
+            "address": "0x1234567890123456789012345678901234567890",
+            "simple_mode": true
+        }),
+    );
 
     let params = req.get("params").unwrap();
     let args = params.get("arguments").unwrap();
-    assert_eq!(args.get("simple_mode").and_then(|v| v.as_bool()), Some(true));
+    assert_eq!(
+        args.get("simple_mode").and_then(|v| v.as_bool()),
+        Some(true)
+    );
 }
 
 // ============================================================
@@ -65,16 +83,25 @@ fn e2e_get_approval_status_simple_mode() {
 fn e2e_get_block_info_latest() {
     let req = make_tool_call("get_block_info", json!({}));
 
-    assert_eq!(req.get("method").and_then(|v| v.as_str()), Some("tools/call"));
+    assert_eq!(
+        req.get("method").and_then(|v| v.as_str()),
+        Some("tools/call")
+    );
     let params = req.get("params").unwrap();
-    assert_eq!(params.get("name").and_then(|v| v.as_str()), Some("get_block_info"));
+    assert_eq!(
+        params.get("name").and_then(|v| v.as_str()),
+        Some("get_block_info")
+    );
 }
 
 #[test]
 fn e2e_get_block_info_by_number() {
-    let req = make_tool_call("get_block_info", json!({
-        "block": "12345"
-    }));
+    let req = make_tool_call(
+        "get_block_info",
+        json!({
+            "block": "12345"
+        }),
+    );
 
     let params = req.get("params").unwrap();
     let args = params.get("arguments").unwrap();
@@ -83,9 +110,12 @@ fn e2e_get_block_info_by_number() {
 
 #[test]
 fn e2e_get_block_info_by_hex() {
-    let req = make_tool_call("get_block_info", json!({
-        "block": "0x3039"
-    }));
+    let req = make_tool_call(
+        "get_block_info",
+        json!({
+            "block": "0x3039"
+        }),
+    );
 
     let params = req.get("params").unwrap();
     let args = params.get("arguments").unwrap();
@@ -94,14 +124,20 @@ fn e2e_get_block_info_by_hex() {
 
 #[test]
 fn e2e_get_block_info_simple_mode() {
-    let req = make_tool_call("get_block_info", json!({
-        "block": "latest",
-        "simple_mode": true
-    }));
+    let req = make_tool_call(
+        "get_block_info",
+        json!({
+            "block": "latest",
+            "simple_mode": true
+        }),
+    );
 
     let params = req.get("params").unwrap();
     let args = params.get("arguments").unwrap();
-    assert_eq!(args.get("simple_mode").and_then(|v| v.as_bool()), Some(true));
+    assert_eq!(
+        args.get("simple_mode").and_then(|v| v.as_bool()),
+        Some(true)
+    );
 }
 
 // ============================================================
@@ -127,9 +163,15 @@ fn e2e_multiple_tools_request_format() {
         ("get_account_summary", json!({"address": "0x123"})),
         ("get_defi_positions", json!({"address": "0x123"})),
         ("decode_transaction", json!({"tx_hash": "0xabc"})),
-        ("simulate_transaction", json!({"from": "0x1", "to": "0x2", "data": "0x", "value": "0"})),
+        (
+            "simulate_transaction",
+            json!({"from": "0x1", "to": "0x2", "data": "0x", "value": "0"}),
+        ),
         ("search_contract", json!({"query": "usdc"})),
-        ("construct_swap_tx", json!({"from": "0x1", "token_in": "CRO", "token_out": "USDC", "amount_in": "100", "slippage_bps": 50})),
+        (
+            "construct_swap_tx",
+            json!({"from": "0x1", "token_in": "CRO", "token_out": "USDC", "amount_in": "100", "slippage_bps": 50}),
+        ),
         ("get_token_info", json!({"token": "VVS"})),
         ("get_pool_info", json!({"pool": "CRO-USDC"})),
         ("get_gas_price", json!({})),
@@ -183,7 +225,10 @@ fn approval_status_response_format() {
 
     // Verify structure
     assert!(response.get("address").is_some());
-    assert!(response.get("approvals").and_then(|v| v.as_array()).is_some());
+    assert!(response
+        .get("approvals")
+        .and_then(|v| v.as_array())
+        .is_some());
     assert!(response.get("summary").is_some());
 
     let summary = response.get("summary").unwrap();
@@ -229,12 +274,24 @@ fn block_info_response_format() {
     assert!(response.get("number").and_then(|v| v.as_i64()).is_some());
     assert!(response.get("hash").and_then(|v| v.as_str()).is_some());
     assert!(response.get("timestamp").and_then(|v| v.as_i64()).is_some());
-    assert!(response.get("timestamp_relative").and_then(|v| v.as_str()).is_some());
-    assert!(response.get("transactions_count").and_then(|v| v.as_i64()).is_some());
+    assert!(response
+        .get("timestamp_relative")
+        .and_then(|v| v.as_str())
+        .is_some());
+    assert!(response
+        .get("transactions_count")
+        .and_then(|v| v.as_i64())
+        .is_some());
     assert!(response.get("gas_used").and_then(|v| v.as_str()).is_some());
     assert!(response.get("gas_limit").and_then(|v| v.as_str()).is_some());
-    assert!(response.get("gas_used_percent").and_then(|v| v.as_str()).is_some());
-    assert!(response.get("base_fee_gwei").and_then(|v| v.as_str()).is_some());
+    assert!(response
+        .get("gas_used_percent")
+        .and_then(|v| v.as_str())
+        .is_some());
+    assert!(response
+        .get("base_fee_gwei")
+        .and_then(|v| v.as_str())
+        .is_some());
     assert!(response.get("miner").and_then(|v| v.as_str()).is_some());
 }
 
@@ -290,7 +347,10 @@ fn jsonrpc_error_response_format() {
         }
     });
 
-    assert_eq!(error_response.get("jsonrpc").and_then(|v| v.as_str()), Some("2.0"));
+    assert_eq!(
+        error_response.get("jsonrpc").and_then(|v| v.as_str()),
+        Some("2.0")
+    );
     assert!(error_response.get("result").is_none());
     let error = error_response.get("error").unwrap();
     assert!(error.get("code").and_then(|v| v.as_i64()).is_some());
@@ -312,7 +372,10 @@ fn jsonrpc_success_response_format() {
         }
     });
 
-    assert_eq!(success_response.get("jsonrpc").and_then(|v| v.as_str()), Some("2.0"));
+    assert_eq!(
+        success_response.get("jsonrpc").and_then(|v| v.as_str()),
+        Some("2.0")
+    );
     assert!(success_response.get("error").is_none());
     assert!(success_response.get("result").is_some());
 }
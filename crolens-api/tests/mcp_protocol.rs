@@ -1,4 +1,5 @@
-use crolens_api::error::CroLensError;
+use crolens_api::error::{http_status_for, CroLensError};
+use crolens_api::gateway::billing::{PaymentRequiredData, X402_QUOTE_PATH};
 use crolens_api::mcp::protocol::JsonRpcResponse;
 
 #[test]
@@ -30,3 +31,75 @@ fn json_rpc_success_payload_is_well_formed() {
     let result = value.get("result").expect("result must exist");
     assert_eq!(result.get("ok").and_then(|v| v.as_bool()), Some(true));
 }
+
+#[test]
+fn not_configured_error_uses_service_unavailable_code() {
+    // -32501 在 HTTP 层映射为 503
+    let resp = JsonRpcResponse::error(serde_json::json!(1), CroLensError::NotConfigured("RPC"));
+    let value = serde_json::to_value(&resp).expect("must serialize");
+
+    let err = value.get("error").expect("error must exist");
+    assert_eq!(err.get("code").and_then(|v| v.as_i64()), Some(-32501));
+    assert_eq!(
+        err.get("message").and_then(|v| v.as_str()),
+        Some("RPC not configured")
+    );
+}
+
+#[test]
+fn payment_required_error_carries_topup_instructions() {
+    let payment_address: alloy_primitives::Address = "0x1111111111111111111111111111111111111111"
+        .parse()
+        .expect("valid address");
+    let data = PaymentRequiredData::new(
+        payment_address,
+        alloy_primitives::U256::from(10_000_000_000_000_000_000u128),
+        1000,
+    );
+    let resp = JsonRpcResponse::error(serde_json::json!(1), data.into_error());
+    let value = serde_json::to_value(&resp).expect("must serialize");
+
+    let err = value.get("error").expect("error must exist");
+    assert_eq!(err.get("code").and_then(|v| v.as_i64()), Some(-32002));
+    assert_eq!(http_status_for(-32002), 402);
+
+    let data = err.get("data").expect("data must exist");
+    assert_eq!(data.get("chain_id").and_then(|v| v.as_u64()), Some(25));
+    assert_eq!(
+        data.get("payment_address").and_then(|v| v.as_str()),
+        Some("0x1111111111111111111111111111111111111111")
+    );
+    assert_eq!(
+        data.get("amount_wei").and_then(|v| v.as_str()),
+        Some("10000000000000000000")
+    );
+    assert_eq!(data.get("credits").and_then(|v| v.as_i64()), Some(1000));
+    assert_eq!(
+        data.get("quote_url").and_then(|v| v.as_str()),
+        Some(X402_QUOTE_PATH)
+    );
+}
+
+#[test]
+fn timeout_error_uses_gateway_timeout_code() {
+    // -32504 在 HTTP 层映射为 504
+    let resp = JsonRpcResponse::error(
+        serde_json::json!(9),
+        CroLensError::Timeout { timeout_ms: 30_000 },
+    );
+    let value = serde_json::to_value(&resp).expect("must serialize");
+
+    assert_eq!(value.get("id").and_then(|v| v.as_i64()), Some(9));
+    let err = value.get("error").expect("error must exist");
+    assert_eq!(err.get("code").and_then(|v| v.as_i64()), Some(-32504));
+    assert_eq!(
+        err.get("message").and_then(|v| v.as_str()),
+        Some("Request timed out after 30000ms")
+    );
+    assert_eq!(
+        err.get("data")
+            .and_then(|v| v.get("timeout_ms"))
+            .and_then(|v| v.as_u64()),
+        Some(30_000)
+    );
+}
@@ -0,0 +1,157 @@
+mod support;
+
+use crolens_api::error::CroLensError;
+use crolens_api::gateway::metrics::{
+    flush_counters, latency_bucket, latency_bucket_key, outcome_keys, read_tool_metrics,
+    record_counters, CounterBatch, METRICS_FLUSH_INTERVAL_MS,
+};
+use crolens_api::gateway::ratelimit::KvTextStore;
+
+use support::MemoryRateLimitStore;
+
+async fn counter(store: &MemoryRateLimitStore, key: &str) -> Option<String> {
+    store.get_text(key).await.expect("read counter")
+}
+
+/// 单次调用经 CounterBatch 累积后立即写入 (新批次的首次记录总会到期)
+async fn record_and_flush(store: &MemoryRateLimitStore, keys: &[String]) {
+    let deltas = CounterBatch::default()
+        .record(keys, 0)
+        .expect("first record flushes");
+    flush_counters(store, &deltas).await.expect("flush");
+}
+
+#[tokio::test]
+async fn counter_starts_at_one_and_increments() {
+    let store = MemoryRateLimitStore::new();
+    let delta = [("metrics:tool:x:ok".to_string(), 1)];
+    flush_counters(&store, &delta).await.expect("flush");
+    assert_eq!(
+        counter(&store, "metrics:tool:x:ok").await.as_deref(),
+        Some("1")
+    );
+    flush_counters(&store, &delta).await.expect("flush");
+    assert_eq!(
+        counter(&store, "metrics:tool:x:ok").await.as_deref(),
+        Some("2")
+    );
+}
+
+#[tokio::test]
+async fn tool_error_increments_err_and_kind_counters() {
+    let store = MemoryRateLimitStore::new();
+    let err = CroLensError::RpcError("execution reverted".to_string());
+
+    let keys = outcome_keys("", "get_token_price", Some(&err));
+    record_and_flush(&store, &keys).await;
+
+    assert_eq!(
+        counter(&store, "metrics:tool:get_token_price:err")
+            .await
+            .as_deref(),
+        Some("1")
+    );
+    assert_eq!(
+        counter(&store, "metrics:tool:get_token_price:err:rpc")
+            .await
+            .as_deref(),
+        Some("1")
+    );
+    assert_eq!(
+        counter(&store, "metrics:tool:get_token_price:ok").await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn success_only_touches_ok_counter() {
+    let store = MemoryRateLimitStore::new();
+    let keys = outcome_keys("", "get_gas_price", None);
+    record_and_flush(&store, &keys).await;
+    record_and_flush(&store, &keys).await;
+
+    assert_eq!(
+        counter(&store, "metrics:tool:get_gas_price:ok")
+            .await
+            .as_deref(),
+        Some("2")
+    );
+    assert_eq!(
+        counter(&store, "metrics:tool:get_gas_price:err").await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn corrupt_counter_value_restarts_from_zero() {
+    let store = MemoryRateLimitStore::new();
+    store
+        .put_text_with_ttl("metrics:tool:x:err", "garbage".to_string(), 60)
+        .await
+        .expect("seed");
+    flush_counters(&store, &[("metrics:tool:x:err".to_string(), 1)])
+        .await
+        .expect("flush");
+    assert_eq!(
+        counter(&store, "metrics:tool:x:err").await.as_deref(),
+        Some("1")
+    );
+}
+
+#[tokio::test]
+async fn tool_metrics_report_counters_and_latency_histogram() {
+    let store = MemoryRateLimitStore::new();
+    let tool = "get_token_info";
+    // 首次调用立即写入，其余在本 isolate 累积到下一个写入间隔
+    let calls = [
+        (40, 0),
+        (80, 1_000),
+        (250, 2_000),
+        (4_000, METRICS_FLUSH_INTERVAL_MS),
+    ];
+    for (latency_ms, now_ms) in calls {
+        let mut keys = outcome_keys("", tool, None);
+        keys.push(latency_bucket_key("", tool, latency_bucket(latency_ms)));
+        if let Some(deltas) = record_counters(&keys, now_ms) {
+            flush_counters(&store, &deltas).await.expect("flush");
+        }
+    }
+
+    let metrics = read_tool_metrics(&store, "", tool).await.unwrap();
+    assert_eq!(metrics["tool"], tool);
+    assert_eq!(metrics["ok"], 4);
+    assert_eq!(metrics["err"], 0);
+    assert_eq!(
+        metrics["latency_ms"],
+        serde_json::json!({
+            "lt_100ms": 2,
+            "lt_300ms": 1,
+            "lt_1s": 0,
+            "lt_3s": 0,
+            "gte_3s": 1,
+        })
+    );
+}
+
+#[tokio::test]
+async fn batched_counters_flush_accumulated_deltas() {
+    let store = MemoryRateLimitStore::new();
+    store
+        .put_text_with_ttl("metrics:tool:x:ok", "5".to_string(), 60)
+        .await
+        .expect("seed");
+    let mut batch = CounterBatch::default();
+    let keys = outcome_keys("", "x", None);
+
+    let first = batch.record(&keys, 0).expect("first call flushes");
+    flush_counters(&store, &first).await.expect("flush");
+    assert!(batch.record(&keys, 1_000).is_none());
+    assert!(batch.record(&keys, 2_000).is_none());
+    let later = batch.record(&keys, 60_000).expect("interval elapsed");
+    flush_counters(&store, &later).await.expect("flush");
+
+    assert_eq!(
+        counter(&store, "metrics:tool:x:ok").await.as_deref(),
+        Some("9")
+    );
+}
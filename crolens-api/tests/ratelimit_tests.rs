@@ -1,6 +1,8 @@
 mod support;
 
-use crolens_api::gateway::ratelimit::check_rate_limit;
+use crolens_api::gateway::ratelimit::{
+    check_combined_rate_limit, check_rate_limit, parse_tool_limits,
+};
 
 use support::MemoryRateLimitStore;
 
@@ -21,3 +23,48 @@ async fn test_at_limit() {
     assert!(check_rate_limit(&store, "rl:test:at", 2, 60).await.unwrap());
     assert!(!check_rate_limit(&store, "rl:test:at", 2, 60).await.unwrap());
 }
+
+async fn allow(store: &MemoryRateLimitStore, limits: &[(&str, u32)]) -> bool {
+    check_combined_rate_limit(store, limits, 60)
+        .await
+        .expect("rate limit check")
+}
+
+#[tokio::test]
+async fn test_tool_limit_blocks_before_global_limit() {
+    let store = MemoryRateLimitStore::new();
+    let limits = [("rl:tool:k:1", 5), ("rl:tool:k:simulate_transaction:1", 2)];
+
+    assert!(allow(&store, &limits).await);
+    assert!(allow(&store, &limits).await);
+    assert!(!allow(&store, &limits).await);
+
+    // 被单工具限额拒绝的调用不消耗全局额度，其他工具仍可调用
+    let other = [("rl:tool:k:1", 5)];
+    assert!(allow(&store, &other).await);
+    assert!(allow(&store, &other).await);
+    assert!(allow(&store, &other).await);
+    assert!(!allow(&store, &other).await);
+}
+
+#[tokio::test]
+async fn test_global_limit_blocks_tool_with_remaining_sub_limit() {
+    let store = MemoryRateLimitStore::new();
+    let limits = [("rl:tool:g:1", 1), ("rl:tool:g:simulate_transaction:1", 10)];
+
+    assert!(allow(&store, &limits).await);
+    assert!(!allow(&store, &limits).await);
+}
+
+#[test]
+fn test_parse_tool_limits() {
+    let defaults = parse_tool_limits(None);
+    assert_eq!(defaults.get("simulate_transaction"), Some(&30));
+
+    let custom = parse_tool_limits(Some(r#"{"estimate_gas": 10}"#));
+    assert_eq!(custom.get("estimate_gas"), Some(&10));
+    assert!(!custom.contains_key("simulate_transaction"));
+
+    assert!(parse_tool_limits(Some("{}")).is_empty());
+    assert_eq!(parse_tool_limits(Some("not json")), defaults);
+}
@@ -6,7 +6,7 @@ use tokio::sync::Mutex;
 
 use crolens_api::error::Result;
 use crolens_api::gateway::auth::ApiKeyRecord;
-use crolens_api::gateway::ratelimit::RateLimitStore;
+use crolens_api::gateway::ratelimit::KvTextStore;
 use crolens_api::gateway::store::ApiKeyStore;
 
 #[derive(Default)]
@@ -74,6 +74,26 @@ impl ApiKeyStore for MemoryApiKeyStore {
         record.credits -= 1;
         Ok(Some(record.credits))
     }
+
+    async fn deduct_credits_if_possible(&self, api_key: &str, amount: i64) -> Result<Option<i64>> {
+        let mut keys = self.keys.lock().await;
+        let Some(record) = keys.get_mut(api_key) else {
+            return Ok(None);
+        };
+        if !record.is_active || record.credits < amount {
+            return Ok(None);
+        }
+        record.credits -= amount;
+        Ok(Some(record.credits))
+    }
+
+    async fn refund_credits(&self, api_key: &str, amount: i64) -> Result<Option<i64>> {
+        let mut keys = self.keys.lock().await;
+        Ok(keys.get_mut(api_key).map(|record| {
+            record.credits += amount;
+            record.credits
+        }))
+    }
 }
 
 #[derive(Default)]
@@ -88,7 +108,7 @@ impl MemoryRateLimitStore {
 }
 
 #[async_trait(?Send)]
-impl RateLimitStore for MemoryRateLimitStore {
+impl KvTextStore for MemoryRateLimitStore {
     async fn get_text(&self, key: &str) -> Result<Option<String>> {
         let values = self.values.lock().await;
         Ok(values.get(key).cloned())
@@ -13,11 +13,12 @@ fn tools_list_has_all_required_tools() {
         .filter_map(|t| t.get("name").and_then(|v| v.as_str()))
         .collect::<Vec<_>>();
 
-    // All 30 MCP tools (SOP v2.0.0)
+    // All 31 MCP tools (SOP v2.0.0)
     for required in [
         "get_account_summary",
         "get_defi_positions",
         "decode_transaction",
+        "decode_transactions",
         "simulate_transaction",
         "search_contract",
         "construct_swap_tx",
@@ -58,7 +59,7 @@ fn tools_list_has_correct_count() {
         .and_then(|v| v.as_array())
         .expect("tools must be an array");
 
-    assert_eq!(tools.len(), 30, "expected 30 MCP tools");
+    assert_eq!(tools.len(), 31, "expected 31 MCP tools");
 }
 
 #[test]
@@ -98,9 +99,7 @@ fn get_approval_status_tool_has_correct_schema() {
         .find(|t| t.get("name").and_then(|v| v.as_str()) == Some("get_approval_status"))
         .expect("get_approval_status tool must exist");
 
-    let schema = tool
-        .get("inputSchema")
-        .expect("must have inputSchema");
+    let schema = tool.get("inputSchema").expect("must have inputSchema");
 
     // Check required fields
     let required = schema
@@ -113,12 +112,13 @@ fn get_approval_status_tool_has_correct_schema() {
     );
 
     // Check properties
-    let props = schema
-        .get("properties")
-        .expect("must have properties");
+    let props = schema.get("properties").expect("must have properties");
     assert!(props.get("address").is_some(), "must have address property");
     assert!(props.get("token").is_some(), "must have token property");
-    assert!(props.get("simple_mode").is_some(), "must have simple_mode property");
+    assert!(
+        props.get("simple_mode").is_some(),
+        "must have simple_mode property"
+    );
 }
 
 #[test]
@@ -134,23 +134,25 @@ fn get_block_info_tool_has_correct_schema() {
         .find(|t| t.get("name").and_then(|v| v.as_str()) == Some("get_block_info"))
         .expect("get_block_info tool must exist");
 
-    let schema = tool
-        .get("inputSchema")
-        .expect("must have inputSchema");
+    let schema = tool.get("inputSchema").expect("must have inputSchema");
 
     // Check that no fields are required (block defaults to "latest")
     let required = schema
         .get("required")
         .and_then(|v| v.as_array())
         .expect("must have required array");
-    assert!(required.is_empty(), "get_block_info should have no required fields");
+    assert!(
+        required.is_empty(),
+        "get_block_info should have no required fields"
+    );
 
     // Check properties
-    let props = schema
-        .get("properties")
-        .expect("must have properties");
+    let props = schema.get("properties").expect("must have properties");
     assert!(props.get("block").is_some(), "must have block property");
-    assert!(props.get("simple_mode").is_some(), "must have simple_mode property");
+    assert!(
+        props.get("simple_mode").is_some(),
+        "must have simple_mode property"
+    );
 }
 
 #[test]
@@ -176,7 +178,10 @@ fn all_tools_have_valid_json_schema() {
             .get("type")
             .and_then(|v| v.as_str())
             .expect(&format!("{name} inputSchema must have type"));
-        assert_eq!(schema_type, "object", "{name} inputSchema type must be object");
+        assert_eq!(
+            schema_type, "object",
+            "{name} inputSchema type must be object"
+        );
 
         // All schemas must have properties
         assert!(